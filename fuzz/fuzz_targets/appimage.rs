@@ -0,0 +1,25 @@
+//! Opens the input as an AppImage, or a bare squashfs image, and reads its executable, so
+//! its superblock, tables, directories, inodes and blocks all go through the squashfs reader.
+#![no_main]
+
+use std::fs;
+
+use elf_dynamic_lib_getter::{AppImage, ResolverConfig};
+use libfuzzer_sys::fuzz_target;
+
+/// How far any table or file of the image may decompress.
+const DECOMPRESSED_SIZE: u64 = 1 << 20;
+
+fuzz_target!(|data: &[u8]| {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.AppImage");
+    fs::write(&path, data).unwrap();
+    let config = ResolverConfig::new().max_decompressed_size(DECOMPRESSED_SIZE);
+    let Ok(image) = AppImage::open_with_config(&path, &config) else {
+        return;
+    };
+    if let Some(executable) = image.executable() {
+        let _ = image.read(executable);
+    }
+    let _ = image.read(image.mount_point().join("usr/lib/libc.so.6"));
+});
@@ -0,0 +1,19 @@
+//! Reads the `.modinfo` section of the input as a kernel module, compressed with gzip, xz or
+//! zstd or not at all.
+#![no_main]
+
+use std::fs;
+
+use elf_dynamic_lib_getter::{KernelModule, ResolverConfig};
+use libfuzzer_sys::fuzz_target;
+
+/// How far the module may decompress.
+const DECOMPRESSED_SIZE: u64 = 1 << 20;
+
+fuzz_target!(|data: &[u8]| {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.ko");
+    fs::write(&path, data).unwrap();
+    let config = ResolverConfig::new().max_decompressed_size(DECOMPRESSED_SIZE);
+    let _ = KernelModule::with_config(&path, config).modinfo();
+});
@@ -0,0 +1,21 @@
+//! Opens the input as a `.deb` or `.rpm` package, so its ar archive or rpm headers, and the
+//! tar or cpio archive of its files, go through their readers.
+#![no_main]
+
+use std::fs;
+
+use elf_dynamic_lib_getter::{PackageSet, ResolverConfig};
+use libfuzzer_sys::fuzz_target;
+
+/// How far the files of the package may decompress.
+const DECOMPRESSED_SIZE: u64 = 1 << 20;
+
+fuzz_target!(|data: &[u8]| {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.pkg");
+    fs::write(&path, data).unwrap();
+    let config = ResolverConfig::new().max_decompressed_size(DECOMPRESSED_SIZE);
+    if let Ok(packages) = PackageSet::open_with_config([&path], &config) {
+        let _ = packages.read("/usr/lib/libc.so.6");
+    }
+});
@@ -0,0 +1,21 @@
+//! Opens the input as a rootfs archive, a tar archive compressed with gzip, xz or zstd or
+//! not at all, and reads a file from it.
+#![no_main]
+
+use std::fs;
+
+use elf_dynamic_lib_getter::{ResolverConfig, RootfsArchive};
+use libfuzzer_sys::fuzz_target;
+
+/// How far the archive may decompress.
+const DECOMPRESSED_SIZE: u64 = 1 << 20;
+
+fuzz_target!(|data: &[u8]| {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.tar");
+    fs::write(&path, data).unwrap();
+    let config = ResolverConfig::new().max_decompressed_size(DECOMPRESSED_SIZE);
+    if let Ok(rootfs) = RootfsArchive::open_with_config(&path, &config) {
+        let _ = rootfs.read("/usr/lib/libc.so.6");
+    }
+});
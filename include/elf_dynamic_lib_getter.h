@@ -0,0 +1,72 @@
+/*
+ * The C interface of elf-dynamic-lib-getter, built with the `ffi` feature:
+ *
+ *     cargo build --release --features ffi
+ *
+ * which produces libelf_dynamic_lib_getter.so and libelf_dynamic_lib_getter.a.
+ *
+ * Strings are NUL-terminated bytes, as paths are on Unix. Every string a result holds lives
+ * as long as the result. A resolver can be used from several threads at once.
+ */
+#ifndef ELF_DYNAMIC_LIB_GETTER_H
+#define ELF_DYNAMIC_LIB_GETTER_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A resolver shared by analyses, remembering the files it parsed. */
+typedef struct DlfResolver dlf_resolver;
+
+/* The libraries of an analyzed file, or why it could not be analyzed. */
+typedef struct DlfResult dlf_result;
+
+/* A library of the closure of an analyzed file. */
+typedef struct dlf_entry {
+    /* The name the library was requested by. */
+    const char *soname;
+    /* Where it was found, or NULL if it was not, or is the vDSO or an ignored library. */
+    const char *path;
+    /* Why it was not found, or NULL if it was, or is the vDSO or an ignored library. */
+    const char *error;
+} dlf_entry;
+
+/*
+ * Creates a resolver searching for libraries as if `sysroot` were mounted at /, or in the
+ * host's directories if `sysroot` is NULL, and using LD_LIBRARY_PATH if `use_env`.
+ */
+dlf_resolver *dlf_resolver_new(const char *sysroot, bool use_env);
+
+/* Frees a resolver. Results of analyses done with it stay valid. NULL is ignored. */
+void dlf_resolver_free(dlf_resolver *resolver);
+
+/*
+ * Resolves the libraries the ELF file at `path` needs, recursively. Returns NULL only if
+ * `resolver` or `path` is NULL; if the file can't be analyzed, the result holds the error
+ * instead. Free the result with dlf_result_free.
+ */
+dlf_result *dlf_analyze(const dlf_resolver *resolver, const char *path);
+
+/* Why the file of `result` could not be analyzed, or NULL if it was. */
+const char *dlf_result_error(const dlf_result *result);
+
+/* The number of libraries in `result`, in the order they were encountered, each once. */
+size_t dlf_result_len(const dlf_result *result);
+
+/*
+ * Fills in `entry` with the library at `index` of `result`. Returns false, leaving `entry`
+ * alone, if `index` is out of bounds.
+ */
+bool dlf_result_get(const dlf_result *result, size_t index, dlf_entry *entry);
+
+/* Frees a result and every string it holds. NULL is ignored. */
+void dlf_result_free(dlf_result *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ELF_DYNAMIC_LIB_GETTER_H */
@@ -0,0 +1,32 @@
+"""Python bindings of elf-dynamic-lib-getter.
+
+The classes are those of the pyo3 extension module ``_native``, which the crate is built into
+with the `python` feature. Installing the package from a checkout, with ``pip install
+./python``, builds it with maturin::
+
+    from elf_dynamic_lib_getter import ElfFile
+
+    for dependency in ElfFile("/usr/bin/ls").dependencies():
+        print(dependency.soname, dependency.path or dependency.error)
+
+    graph = ElfFile("/usr/bin/ls").dependency_graph()
+    for needed_by, needed in graph.edges():
+        print(graph.nodes[needed_by].soname, "->", graph.nodes[needed].soname)
+"""
+from ._native import (
+    AnalysisError,
+    Dependency,
+    DependencyGraph,
+    DependencyNode,
+    ElfFile,
+    Resolver,
+)
+
+__all__ = [
+    "AnalysisError",
+    "Dependency",
+    "DependencyGraph",
+    "DependencyNode",
+    "ElfFile",
+    "Resolver",
+]
@@ -0,0 +1,91 @@
+"""Checks the bindings against the command-line tool, on the Python interpreter running them.
+
+Both are built first, the package into the environment running the tests::
+
+    cargo build --release --features cli
+    pip install ./python
+    python3 -m unittest discover -s python/tests
+"""
+import json
+import os
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+
+from elf_dynamic_lib_getter import AnalysisError, Dependency, ElfFile, Resolver
+
+_ROOT = os.path.dirname(os.path.dirname(os.path.dirname(os.path.abspath(__file__))))
+_TARGET = os.environ.get("CARGO_TARGET_DIR", os.path.join(_ROOT, "target"))
+_RELEASE = os.path.join(_TARGET, "release")
+
+_CLI = shutil.which("dyn-lib-finder", path=_RELEASE)
+
+
+def _libraries(path):
+    """The libraries of the closure of `path`, as the command-line tool outputs them in JSON,
+    without the vDSO, which only it lists."""
+    output = subprocess.run([_CLI, "--json", path], stdout=subprocess.PIPE, check=True)
+    nodes = json.loads(output.stdout)["nodes"][1:]
+    return [node for node in nodes if node["kind"] != "Virtual"]
+
+
+def _expected(node):
+    """The soname, path and whether there is an error, of a node of `_libraries`."""
+    resolution = node["resolution"]
+    if "Err" in resolution:
+        return node["soname"], None, True
+    found = resolution["Ok"]
+    path = found["Found"] if isinstance(found, dict) else None
+    return node["soname"], path, False
+
+
+@unittest.skipIf(_CLI is None, "dyn-lib-finder is not built")
+class BindingsTest(unittest.TestCase):
+    def test_dependencies(self):
+        expected = [_expected(node) for node in _libraries(sys.executable)]
+        dependencies = ElfFile(sys.executable).dependencies()
+        actual = [(dep.soname, dep.path, dep.error is not None) for dep in dependencies]
+        self.assertEqual(actual, expected)
+
+    def test_full_paths(self):
+        # Entries that are not files, like the vDSO, are left out rather than failing
+        expected = [path for _, path, _ in map(_expected, _libraries(sys.executable)) if path]
+        self.assertEqual(ElfFile(sys.executable).full_paths(), expected)
+        # In an empty sysroot, none of the libraries is found
+        with tempfile.TemporaryDirectory() as sysroot:
+            resolver = Resolver(sysroot, use_env=False)
+            with self.assertRaisesRegex(AnalysisError, "was not found"):
+                ElfFile(sys.executable, resolver).full_paths()
+
+    def test_dependency_graph(self):
+        nodes = _libraries(sys.executable)
+        graph = ElfFile(sys.executable).dependency_graph()
+        self.assertEqual(graph.root, 0)
+        libraries = [node for node in graph.nodes[1:] if node.kind != "Virtual"]
+        self.assertEqual([node.soname for node in libraries], [node["soname"] for node in nodes])
+        self.assertEqual(
+            [(node.soname, node.path, node.error is not None) for node in libraries],
+            list(map(_expected, nodes)),
+        )
+        for needed_by, needed in graph.edges():
+            self.assertIn(needed, graph.dependencies(needed_by))
+        with self.assertRaises(IndexError):
+            graph.dependencies(len(graph))
+
+    def test_dependency(self):
+        dependency = Dependency("libfoo.so.1", "/lib/libfoo.so.1")
+        self.assertEqual(dependency, Dependency("libfoo.so.1", "/lib/libfoo.so.1", None))
+        self.assertTrue(dependency.found)
+        self.assertFalse(Dependency("linux-vdso.so.1").found)
+        expected = "Dependency(soname='libfoo.so.1', path='/lib/libfoo.so.1', error=None)"
+        self.assertEqual(repr(dependency), expected)
+
+    def test_analysis_error(self):
+        with self.assertRaises(AnalysisError):
+            Resolver(use_env=False).dependencies(__file__)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,104 @@
+//! The constants of the ELF format that are needed here, with the names and types of
+//! `<elf.h>`, whichever backend parses files. Those only tests need are left out of other
+//! builds.
+
+// Object file types, `e_type`
+pub(crate) const ET_REL: u16 = 1;
+pub(crate) const ET_EXEC: u16 = 2;
+pub(crate) const ET_DYN: u16 = 3;
+pub(crate) const ET_CORE: u16 = 4;
+
+// OS ABIs, `e_ident[EI_OSABI]`
+pub(crate) const ELFOSABI_NONE: u8 = 0;
+pub(crate) const ELFOSABI_SYSV: u8 = 0;
+pub(crate) const ELFOSABI_NETBSD: u8 = 2;
+pub(crate) const ELFOSABI_GNU: u8 = 3;
+pub(crate) const ELFOSABI_SOLARIS: u8 = 6;
+pub(crate) const ELFOSABI_FREEBSD: u8 = 9;
+pub(crate) const ELFOSABI_OPENBSD: u8 = 12;
+
+// Machines, `e_machine`
+pub(crate) const EM_SPARC: u16 = 2;
+pub(crate) const EM_386: u16 = 3;
+pub(crate) const EM_MIPS: u16 = 8;
+pub(crate) const EM_PPC: u16 = 20;
+pub(crate) const EM_PPC64: u16 = 21;
+pub(crate) const EM_S390: u16 = 22;
+pub(crate) const EM_ARM: u16 = 40;
+pub(crate) const EM_SPARCV9: u16 = 43;
+pub(crate) const EM_X86_64: u16 = 62;
+pub(crate) const EM_AARCH64: u16 = 183;
+pub(crate) const EM_RISCV: u16 = 243;
+pub(crate) const EM_LOONGARCH: u16 = 258;
+pub(crate) const EM_BPF: u16 = 247;
+
+// Segment types, `p_type`, and flags, `p_flags`
+#[cfg(test)]
+pub(crate) const PT_NULL: u32 = 0;
+pub(crate) const PT_LOAD: u32 = 1;
+pub(crate) const PT_DYNAMIC: u32 = 2;
+pub(crate) const PT_INTERP: u32 = 3;
+pub(crate) const PT_NOTE: u32 = 4;
+pub(crate) const PT_GNU_STACK: u32 = 0x6474e551;
+pub(crate) const PT_GNU_RELRO: u32 = 0x6474e552;
+pub(crate) const PF_X: u32 = 1;
+#[cfg(test)]
+pub(crate) const PF_W: u32 = 2;
+#[cfg(test)]
+pub(crate) const PF_R: u32 = 4;
+
+// Section types, `sh_type`, and special section indexes
+#[cfg(test)]
+pub(crate) const SHT_PROGBITS: u32 = 1;
+pub(crate) const SHT_SYMTAB: u32 = 2;
+#[cfg(test)]
+pub(crate) const SHT_STRTAB: u32 = 3;
+pub(crate) const SHT_HASH: u32 = 5;
+pub(crate) const SHT_DYNAMIC: u32 = 6;
+pub(crate) const SHT_NOTE: u32 = 7;
+pub(crate) const SHT_NOBITS: u32 = 8;
+pub(crate) const SHT_DYNSYM: u32 = 11;
+pub(crate) const SHT_GNU_HASH: u32 = 0x6ffffff6;
+pub(crate) const SHT_GNU_VERDEF: u32 = 0x6ffffffd;
+pub(crate) const SHT_GNU_VERNEED: u32 = 0x6ffffffe;
+pub(crate) const SHT_GNU_VERSYM: u32 = 0x6fffffff;
+pub(crate) const SHN_UNDEF: u16 = 0;
+pub(crate) const SHN_XINDEX: u16 = 0xffff;
+pub(crate) const PN_XNUM: u16 = 0xffff;
+
+// Dynamic array tags, `d_tag`, and the flags of `DT_FLAGS` and `DT_FLAGS_1`
+pub(crate) const DT_NULL: i64 = 0;
+pub(crate) const DT_NEEDED: i64 = 1;
+pub(crate) const DT_STRTAB: i64 = 5;
+pub(crate) const DT_STRSZ: i64 = 10;
+pub(crate) const DT_SONAME: i64 = 14;
+pub(crate) const DT_RPATH: i64 = 15;
+pub(crate) const DT_BIND_NOW: i64 = 24;
+pub(crate) const DT_FLAGS: i64 = 30;
+pub(crate) const DT_RUNPATH: i64 = 29;
+pub(crate) const DT_FLAGS_1: i64 = 0x6ffffffb;
+#[cfg(test)]
+pub(crate) const DF_ORIGIN: i64 = 0x1;
+pub(crate) const DF_BIND_NOW: i64 = 0x8;
+pub(crate) const DF_1_NOW: i64 = 0x00000001;
+#[cfg(test)]
+pub(crate) const DF_1_NODEFLIB: i64 = 0x00000800;
+pub(crate) const DF_1_PIE: i64 = 0x08000000;
+
+// Symbol bindings, types and visibilities
+pub(crate) const STB_LOCAL: u8 = 0;
+#[cfg(test)]
+pub(crate) const STB_GLOBAL: u8 = 1;
+pub(crate) const STB_WEAK: u8 = 2;
+pub(crate) const STB_GNU_UNIQUE: u8 = 10;
+#[cfg(test)]
+pub(crate) const STT_FUNC: u8 = 2;
+pub(crate) const STV_INTERNAL: u8 = 1;
+pub(crate) const STV_HIDDEN: u8 = 2;
+
+// Symbol versions and notes
+pub(crate) const VER_FLG_BASE: u16 = 0x1;
+pub(crate) const VER_FLG_WEAK: u16 = 0x2;
+pub(crate) const VERSYM_VERSION: u16 = 0x7fff;
+pub(crate) const VERSYM_HIDDEN: u16 = 0x8000;
+pub(crate) const NT_GNU_BUILD_ID: u64 = 3;
@@ -0,0 +1,144 @@
+//! The search paths of Android's bionic linker, which come from the linker namespace
+//! configuration in `ld.config.txt` rather than from `ld.so.conf` and a cache.
+use std::path::{Path, PathBuf};
+
+/// Where the linker configuration is generated on Android 11 and later, followed by where it
+/// was installed before.
+pub(crate) const LD_CONFIG_PATHS: [&str; 2] =
+    ["/linkerconfig/ld.config.txt", "/system/etc/ld.config.txt"];
+
+/// The search path bionic falls back to without a linker configuration, with `${LIB}` standing
+/// for `lib` or `lib64`.
+const DEFAULT_SEARCH_DIRS: [&str; 3] = ["/system/${LIB}", "/odm/${LIB}", "/vendor/${LIB}"];
+
+/// The parts of an `ld.config.txt` file that decide where the dependencies of an executable
+/// are searched for.
+#[derive(Debug, Default)]
+pub(crate) struct LdConfig {
+    /// `dir.<section>` properties: executables in the directory use the section's namespaces.
+    dirs: Vec<(PathBuf, String)>,
+    /// The `namespace.default.search.paths` of each section.
+    sections: Vec<(String, Vec<String>)>,
+}
+
+impl LdConfig {
+    pub(crate) fn parse(contents: &str) -> Self {
+        let mut config = LdConfig::default();
+        let mut section = None;
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                config.sections.push((name.trim().to_owned(), Vec::new()));
+                section = Some(config.sections.len() - 1);
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            // `+=` appends to a property instead of replacing it
+            let (key, append) = match key.strip_suffix('+') {
+                Some(key) => (key.trim(), true),
+                None => (key.trim(), false),
+            };
+            let value = value.trim();
+            match section {
+                None => {
+                    if let Some(name) = key.strip_prefix("dir.") {
+                        config.dirs.push((PathBuf::from(value), name.to_owned()));
+                    }
+                }
+                Some(index) if key == "namespace.default.search.paths" => {
+                    let paths = &mut config.sections[index].1;
+                    if !append {
+                        paths.clear();
+                    }
+                    let value = value.split(':').filter(|path| !path.is_empty());
+                    paths.extend(value.map(str::to_owned));
+                }
+                Some(_) => {}
+            }
+        }
+        config
+    }
+
+    /// The index of the section for the executable at `path`, as seen on the device. When
+    /// several directories contain it, the most specific one wins.
+    pub(crate) fn section_for(&self, path: &Path) -> Option<usize> {
+        let (_, name) = self
+            .dirs
+            .iter()
+            .filter(|(dir, _)| path.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count())?;
+        self.sections
+            .iter()
+            .position(|(section, _)| section == name)
+    }
+
+    /// The default namespace's search path of the section at `index`, or bionic's built-in
+    /// search path if `None`, for 64-bit executables or 32-bit ones.
+    pub(crate) fn search_dirs(&self, index: Option<usize>, is_64_bit: bool) -> Vec<PathBuf> {
+        let lib = if is_64_bit { "lib64" } else { "lib" };
+        let paths: Vec<&str> = match index.and_then(|index| self.sections.get(index)) {
+            Some((_, paths)) => paths.iter().map(String::as_str).collect(),
+            None => DEFAULT_SEARCH_DIRS.to_vec(),
+        };
+        paths
+            .iter()
+            .map(|path| PathBuf::from(path.replace("${LIB}", lib)))
+            .collect()
+    }
+}
+
+/// Whether `file_name` is that of bionic's dynamic linker, like `linker64`.
+pub(crate) fn is_interpreter(file_name: &str) -> bool {
+    matches!(
+        file_name,
+        "linker" | "linker64" | "linker_asan" | "linker_asan64"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LdConfig;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_ld_config() {
+        let config = LdConfig::parse(
+            "# Sections\n\
+             dir.system = /system/bin/\n\
+             dir.vendor = /vendor/bin/\n\
+             dir.vendor_hw = /vendor/bin/hw\n\
+             \n\
+             [system]\n\
+             additional.namespaces = sphal\n\
+             namespace.default.isolated = true\n\
+             namespace.default.search.paths = /system/${LIB}\n\
+             namespace.default.search.paths += /apex/com.android.runtime/${LIB}\n\
+             namespace.sphal.search.paths = /odm/${LIB}\n\
+             [vendor]\n\
+             namespace.default.search.paths = /odm/${LIB}:/vendor/${LIB}\n",
+        );
+        let system = config.section_for(Path::new("/system/bin/app"));
+        assert_eq!(
+            config.search_dirs(system, true),
+            [
+                PathBuf::from("/system/lib64"),
+                PathBuf::from("/apex/com.android.runtime/lib64"),
+            ]
+        );
+        let vendor = config.section_for(Path::new("/vendor/bin/app"));
+        assert_eq!(
+            config.search_dirs(vendor, false),
+            [PathBuf::from("/odm/lib"), PathBuf::from("/vendor/lib")]
+        );
+        // The vendor_hw directory is more specific, but has no section
+        let vendor_hw = config.section_for(Path::new("/vendor/bin/hw/app"));
+        assert_eq!(vendor_hw, None);
+        assert_eq!(config.search_dirs(vendor_hw, true).len(), 3);
+        assert_eq!(config.section_for(Path::new("/data/app")), None);
+    }
+}
@@ -0,0 +1,363 @@
+//! AppImages: an ELF runtime with a squashfs image of the app appended, which the runtime
+//! mounts to run its `AppRun` with the libraries the app bundles.
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::filesystem::{FileKind, FileSystem, HostFs};
+use crate::mount_fs::MountFs;
+use crate::squashfs::SquashFs;
+use crate::{DependencyGraph, ElfFile, Error, NodeId, ResolverConfig};
+
+/// Where the image is mounted over the files of the host, as the runtime mounts it under
+/// `/tmp/.mount_*`.
+const MOUNT_POINT: &str = "/tmp/.mount_appimage";
+
+/// The directories of the image libraries are bundled in, searched in this order, with the
+/// multiarch directories in them, like `usr/lib/x86_64-linux-gnu`.
+const LIB_DIRS: [&str; 4] = ["usr/lib", "usr/lib64", "lib", "lib64"];
+
+/// An AppImage, its squashfs image mounted over the files of the host. Libraries are searched
+/// for in the directories of the image first, as its `AppRun` sets `LD_LIBRARY_PATH` to them,
+/// and on the host second, so [`AppImage::host_dependencies`] tells the libraries an app
+/// relies on the host for. Images compressed with gzip, xz or zstd, or not at all, can be read.
+///
+/// ```no_run
+/// use elf_dynamic_lib_getter::{AppImage, ResolverConfig};
+///
+/// let app = AppImage::open("Example-x86_64.AppImage")?;
+/// let executable = app.executable().expect("no payload binary");
+/// let graph = app.dependency_graph(&executable, ResolverConfig::new())?;
+/// for id in app.host_dependencies(&graph) {
+///     println!("from the host: {}", graph.node(id).soname);
+/// }
+/// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct AppImage {
+    path: PathBuf,
+    fs: Arc<MountFs>,
+    /// The directories bundled libraries are searched for in, at the mount point.
+    lib_dirs: Vec<PathBuf>,
+}
+
+/// Where the squashfs image of the AppImage `file` starts: right after the section headers of
+/// its runtime, or at the start of a bare image.
+fn image_offset(file: &mut File) -> io::Result<u64> {
+    let mut header = [0; 64];
+    let len = file.read(&mut header)?;
+    let header = &header[..len];
+    if header.starts_with(b"hsqs") {
+        return Ok(0);
+    }
+    if !header.starts_with(b"\x7fELF") || header.len() < 52 {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    let big_endian = header[5] == 2;
+    let field = |at: usize, size: usize| {
+        let bytes = &header[at..at + size];
+        let fold = |value: u64, &byte: &u8| value << 8 | u64::from(byte);
+        match big_endian {
+            true => bytes.iter().fold(0, fold),
+            false => bytes.iter().rev().fold(0, fold),
+        }
+    };
+    let (shoff, shentsize, shnum) = match header[4] {
+        1 => (field(0x20, 4), field(0x2e, 2), field(0x30, 2)),
+        2 if header.len() == 64 => (field(0x28, 8), field(0x3a, 2), field(0x3c, 2)),
+        _ => return Err(io::ErrorKind::InvalidData.into()),
+    };
+    Ok(shoff + shentsize * shnum)
+}
+
+impl AppImage {
+    /// Opens the AppImage at `path`, reading the tables of its squashfs image. A bare squashfs
+    /// image, as extracted with `--appimage-offset`, is opened as well.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if the file can't be read, or [`Error::InvalidAppImage`] if it has no
+    /// squashfs image after its runtime, or the image is compressed other than with gzip, xz or
+    /// zstd. An image compressed with a format whose feature is disabled gives an
+    /// [`Error::Io`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        AppImage::open_with_config(path, &ResolverConfig::new())
+    }
+
+    /// Opens the AppImage at `path` as [`AppImage::open`] does, decompressing its tables, and
+    /// any file read from it later, only up to the
+    /// [maximum decompressed size](ResolverConfig::max_decompressed_size) of `config`.
+    ///
+    /// # Return Value [Err]
+    /// As for [`AppImage::open`], or [`Error::LimitExceeded`] if a table of the image
+    /// decompresses to more.
+    pub fn open_with_config<P: AsRef<Path>>(
+        path: P,
+        config: &ResolverConfig,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let io_error = |source: io::Error| {
+            config.decompression_error(path, source, |source| match source.kind() {
+                io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                    Error::InvalidAppImage {
+                        path: path.to_owned(),
+                    }
+                }
+                _ => Error::Io {
+                    path: path.to_owned(),
+                    source,
+                },
+            })
+        };
+        let offset = File::open(path)
+            .and_then(|mut file| image_offset(&mut file))
+            .map_err(io_error)?;
+        let limit = config.decompressed_size_limit();
+        let image = SquashFs::open(path, offset, limit).map_err(io_error)?;
+        let mut lib_dirs = Vec::new();
+        for dir in LIB_DIRS {
+            let Ok(names) = image.read_dir(&Path::new("/").join(dir)) else {
+                continue;
+            };
+            lib_dirs.push(Path::new(MOUNT_POINT).join(dir));
+            let mut multiarch: Vec<_> = names
+                .into_iter()
+                .filter(|name| name.to_string_lossy().contains("-linux-"))
+                .map(|name| Path::new(dir).join(name))
+                .filter(|dir| {
+                    let metadata = image.metadata(&Path::new("/").join(dir));
+                    metadata.is_ok_and(|metadata| metadata.kind == FileKind::Dir)
+                })
+                .collect();
+            multiarch.sort();
+            lib_dirs.extend(multiarch.iter().map(|dir| Path::new(MOUNT_POINT).join(dir)));
+        }
+        let fs = MountFs::over(Arc::new(HostFs)).mount_fs(MOUNT_POINT, Arc::new(image), "/");
+        Ok(AppImage {
+            path: path.to_owned(),
+            fs: Arc::new(fs),
+            lib_dirs,
+        })
+    }
+
+    /// The path of the AppImage.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the image is mounted in the graphs [`AppImage::dependency_graph`] builds, which
+    /// the paths of bundled libraries start with.
+    pub fn mount_point(&self) -> &Path {
+        Path::new(MOUNT_POINT)
+    }
+
+    /// `path` in the image, at the mount point.
+    fn mounted(&self, path: &Path) -> PathBuf {
+        Path::new(MOUNT_POINT).join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// Whether the image has a file or directory at `path`, following symlinks.
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.fs.exists(&self.mounted(path.as_ref()))
+    }
+
+    /// The contents of the file at `path` in the image, following symlinks.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if the image has no such file, or it can't be read.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, Error> {
+        let path = self.mounted(path.as_ref());
+        self.fs
+            .read(&path)
+            .map_err(|source| Error::Io { path, source })
+    }
+
+    /// The path in the image of the binary the AppImage runs: the program its desktop file
+    /// names in `Exec`, looked up in `usr/bin`, `bin` and the root of the image, or else
+    /// `AppRun` if that is an ELF file. `None` if neither is an ELF file, as when `AppRun`
+    /// is a script and there is no desktop file.
+    pub fn executable(&self) -> Option<PathBuf> {
+        let is_elf = |path: &Path| {
+            self.read(path)
+                .is_ok_and(|contents| contents.starts_with(b"\x7fELF"))
+        };
+        let mut desktop_files: Vec<_> = self
+            .fs
+            .read_dir(Path::new(MOUNT_POINT))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|name| name.to_string_lossy().ends_with(".desktop"))
+            .collect();
+        desktop_files.sort();
+        for name in desktop_files {
+            let Ok(contents) = self.read(&name) else {
+                continue;
+            };
+            let contents = String::from_utf8_lossy(&contents);
+            let exec = contents
+                .lines()
+                .skip_while(|line| line.trim() != "[Desktop Entry]")
+                .take_while(|line| !line.starts_with('[') || line.trim() == "[Desktop Entry]")
+                .find_map(|line| line.trim().strip_prefix("Exec="));
+            let Some(program) = exec.and_then(|exec| exec.split_whitespace().next()) else {
+                continue;
+            };
+            let program = Path::new(program.trim_matches('"'));
+            let Some(name) = program.file_name() else {
+                continue;
+            };
+            let found = ["usr/bin", "bin", ""]
+                .into_iter()
+                .map(|dir| Path::new("/").join(dir).join(name))
+                .find(|path| is_elf(path));
+            if found.is_some() {
+                return found;
+            }
+        }
+        let app_run = PathBuf::from("/AppRun");
+        is_elf(&app_run).then_some(app_run)
+    }
+
+    /// Builds the [`DependencyGraph`] of the executable or library at `path` in the image, like
+    /// `/usr/bin/example`, as [`ElfFile::dependency_graph`] does, according to `config`. The
+    /// library directories of the image are searched before the custom search directories of
+    /// `config` and the directories of the host. Paths in the graph are those of the host, the
+    /// image's under its [mount point](AppImage::mount_point). `LD_LIBRARY_PATH` and
+    /// `LD_PRELOAD` are not used, and any [sysroot](ResolverConfig::sysroot) is replaced by
+    /// the host filesystem.
+    ///
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn dependency_graph<P: AsRef<Path>>(
+        &self,
+        path: P,
+        config: ResolverConfig,
+    ) -> Result<DependencyGraph, Error> {
+        let mut config = config
+            .use_env(false)
+            .sysroot("/")
+            .filesystem(self.fs.clone());
+        config
+            .search_dirs
+            .splice(0..0, self.lib_dirs.iter().cloned());
+        ElfFile::with_config(self.mounted(path.as_ref()), config).dependency_graph()
+    }
+
+    /// The libraries of `graph`, built with [`AppImage::dependency_graph`], that were found on
+    /// the host rather than in the image: those the app leaks to the host system for.
+    pub fn host_dependencies(&self, graph: &DependencyGraph) -> Vec<NodeId> {
+        graph
+            .node_ids()
+            .filter(|&id| id != graph.root())
+            .filter(|&id| {
+                let path = graph.node(id).path();
+                path.is_some_and(|path| !path.starts_with(MOUNT_POINT))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppImage;
+    use crate::test_util::{squashfs, ArchiveEntry, ElfBuilder, SquashfsBlocks};
+    use crate::{Error, ResolverConfig, SearchRule};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    #[cfg_attr(
+        not(all(feature = "gzip", feature = "xz", feature = "zstd")),
+        ignore = "needs the `gzip`, `xz` and `zstd` features"
+    )]
+    fn test_appimage() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = ElfBuilder::new()
+            .needed("libbundled.so.1")
+            .needed("libarch.so.1")
+            .needed("libhost.so.1")
+            .needed("libmissing.so.1")
+            .build();
+        let library = ElfBuilder::new().build();
+        let desktop = b"[Desktop Entry]\nName=Example\nExec=example %F\nType=Application\n";
+        let image = squashfs(
+            &[
+                ArchiveEntry::File("AppRun", b"#!/bin/sh\nexec \"$APPDIR/usr/bin/example\"\n"),
+                ArchiveEntry::File("example.desktop", desktop),
+                ArchiveEntry::File("usr/bin/example", &app),
+                ArchiveEntry::File("usr/lib/libbundled.so.1.0", &library),
+                ArchiveEntry::Symlink("usr/lib/libbundled.so.1", "libbundled.so.1.0"),
+                ArchiveEntry::File("usr/lib/x86_64-linux-gnu/libarch.so.1", &library),
+            ],
+            SquashfsBlocks::Zstd,
+        );
+        let host = dir.path().join("host");
+        ElfBuilder::new().write(&host, "libhost.so.1");
+        // A library of the image the host has as well is taken from the image
+        ElfBuilder::new().write(&host, "libbundled.so.1");
+
+        let mut appimage = ElfBuilder::new().build();
+        appimage.extend(&image);
+        let path = dir.path().join("Example-x86_64.AppImage");
+        fs::write(&path, &appimage).unwrap();
+        let bare = dir.path().join("example.squashfs");
+        fs::write(&bare, &image).unwrap();
+
+        for path in [&path, &bare] {
+            let app = AppImage::open(path).unwrap();
+            assert_eq!(app.path(), path.as_path());
+            assert!(app.exists("usr/lib/libbundled.so.1"));
+            assert!(!app.exists("/usr/lib/libhost.so.1"));
+            assert_eq!(app.read("/example.desktop").unwrap(), desktop);
+            assert_eq!(app.executable(), Some("/usr/bin/example".into()));
+
+            let config = ResolverConfig::new().append_search_dir(&host);
+            let graph = app.dependency_graph("usr/bin/example", config).unwrap();
+            let mount = app.mount_point();
+            let found: Vec<_> = graph.nodes()[1..]
+                .iter()
+                .map(|node| (node.soname.as_str(), node.path(), node.found_by.clone()))
+                .collect();
+            let custom = Some(SearchRule::SearchDir);
+            let bundled = mount.join("usr/lib/libbundled.so.1");
+            let arch = mount.join("usr/lib/x86_64-linux-gnu/libarch.so.1");
+            let host_lib = host.join("libhost.so.1");
+            assert_eq!(
+                found,
+                [
+                    ("libbundled.so.1", Some(bundled.as_path()), custom.clone()),
+                    ("libarch.so.1", Some(arch.as_path()), custom.clone()),
+                    ("libhost.so.1", Some(host_lib.as_path()), custom),
+                    ("libmissing.so.1", None, None),
+                ]
+            );
+            let leaks: Vec<_> = app
+                .host_dependencies(&graph)
+                .into_iter()
+                .map(|id| graph.node(id).soname.as_str())
+                .collect();
+            assert_eq!(leaks, ["libhost.so.1"]);
+        }
+
+        // Without a desktop file, AppRun is the payload only if it is a binary
+        let image = squashfs(&[ArchiveEntry::File("AppRun", &app)], SquashfsBlocks::Gzip);
+        fs::write(&bare, image).unwrap();
+        let app = AppImage::open(&bare).unwrap();
+        assert_eq!(app.executable(), Some("/AppRun".into()));
+        let image = squashfs(
+            &[ArchiveEntry::File("AppRun", b"#!/bin/sh\n")],
+            SquashfsBlocks::Xz,
+        );
+        fs::write(&bare, image).unwrap();
+        assert_eq!(AppImage::open(&bare).unwrap().executable(), None);
+
+        let open = |path: &Path| AppImage::open(path);
+        let runtime = dir.path().join("runtime");
+        fs::write(&runtime, ElfBuilder::new().build()).unwrap();
+        assert!(matches!(open(&runtime), Err(Error::InvalidAppImage { .. })));
+        assert!(matches!(
+            open(&dir.path().join("missing.AppImage")),
+            Err(Error::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,424 @@
+//! A tree of files read from archives, like the layers of a container image or the contents
+//! of packages, laid over one another in memory so binaries can be resolved without extracting
+//! anything.
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use crate::filesystem::{FileKind, FileMetadata, FileSystem};
+use crate::os_str::OsStrExt;
+use crate::tar::{self, EntryKind};
+use crate::{cpio, gzip, sysroot, xz, zstd};
+
+/// The prefix of the files that delete a path of the layers below.
+const WHITEOUT_PREFIX: &[u8] = b".wh.";
+/// The file that hides everything the layers below have in its directory.
+const OPAQUE_WHITEOUT: &[u8] = b".wh..wh..opq";
+
+/// The format of an archive of files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Tar,
+    /// A `newc` cpio archive, as rpm packages have.
+    Cpio,
+}
+
+/// The entries of the `format` archive `reader` reads, from `start` up to `end`, reading
+/// records of up to `limit` bytes into memory.
+fn entries<R: Read + Seek>(
+    format: Format,
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    limit: u64,
+) -> io::Result<Vec<tar::Entry>> {
+    match format {
+        Format::Tar => tar::entries(reader, start, end, limit),
+        Format::Cpio => cpio::entries(reader, start, end, limit),
+    }
+}
+
+/// Decompresses a whole stream of up to a number of bytes, or returns an
+/// [`io::ErrorKind::InvalidData`] error if it is malformed, or an
+/// [`io::ErrorKind::FileTooLarge`] error if it decompresses to more.
+pub(crate) type Decompress = fn(&[u8], u64) -> io::Result<Vec<u8>>;
+
+/// Appends what `decoder` decompresses to `output`, failing with an
+/// [`io::ErrorKind::FileTooLarge`] error as soon as `output` grows past `limit` bytes, or
+/// with an [`io::ErrorKind::InvalidData`] error if the `name` stream is malformed.
+#[cfg(any(feature = "gzip", feature = "xz", feature = "zstd"))]
+pub(crate) fn decompress_into<R: Read>(
+    decoder: R,
+    name: &str,
+    output: &mut Vec<u8>,
+    limit: u64,
+) -> io::Result<()> {
+    let room = limit.saturating_sub(output.len() as u64);
+    decoder
+        .take(room.saturating_add(1))
+        .read_to_end(output)
+        .map_err(|_| malformed(name))?;
+    if output.len() as u64 > limit {
+        return Err(too_large(&format!("{name} stream"), limit));
+    }
+    Ok(())
+}
+
+/// The [`io::ErrorKind::FileTooLarge`] error for `what`, which takes more than `limit` bytes.
+pub(crate) fn too_large(what: &str, limit: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::FileTooLarge,
+        format!("{what} takes more than {limit} bytes"),
+    )
+}
+
+/// The error for a `name` stream that is malformed, truncated or fails its checks.
+#[cfg(any(feature = "gzip", feature = "xz", feature = "zstd"))]
+pub(crate) fn malformed(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed {name} stream"),
+    )
+}
+
+/// The decoder of the compression of the stream starting with `magic`, gzip, xz or zstd, or
+/// `None` if it is none of them.
+pub(crate) fn compression(magic: &[u8]) -> Option<Decompress> {
+    if gzip::is_gzip(magic) {
+        Some(gzip::decompress)
+    } else if xz::is_xz(magic) {
+        Some(xz::decompress)
+    } else if zstd::is_zstd(magic) {
+        Some(zstd::decompress)
+    } else {
+        None
+    }
+}
+
+/// The `size` bytes at `offset` in `file`.
+pub(crate) fn read_at(file: &mut File, offset: u64, size: u64) -> io::Result<Vec<u8>> {
+    let mut data = vec![0; usize::try_from(size).map_err(io::Error::other)?];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// The merged files of a stack of archives, each a layer over the ones added before it.
+pub(crate) struct ArchiveFs {
+    /// Whether layers can delete the files of the layers below, as those of images do.
+    whiteouts: bool,
+    /// The most bytes a layer may decompress to, or a record of its archive may take.
+    limit: u64,
+    /// The files the layers were read from.
+    archives: Vec<PathBuf>,
+    /// The layers that were compressed.
+    decompressed: Vec<Vec<u8>>,
+    /// Every path of the tree, absolute, except for directories only implied by the paths
+    /// below them.
+    files: BTreeMap<PathBuf, Node>,
+    layers: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    kind: NodeKind,
+    mode: u32,
+    /// The index of the layer that added the node.
+    layer: usize,
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    File(Contents),
+    Dir,
+    Symlink(PathBuf),
+    /// A device or other special file, which has no contents.
+    Other,
+}
+
+/// Where the contents of a file are.
+#[derive(Debug, Clone, Copy)]
+enum Contents {
+    Archive {
+        archive: usize,
+        offset: u64,
+        size: u64,
+    },
+    Decompressed {
+        layer: usize,
+        offset: u64,
+        size: u64,
+    },
+}
+
+impl ArchiveFs {
+    pub(crate) fn new(whiteouts: bool, limit: u64) -> Self {
+        ArchiveFs {
+            whiteouts,
+            limit,
+            archives: Vec::new(),
+            decompressed: Vec::new(),
+            files: BTreeMap::new(),
+            layers: 0,
+        }
+    }
+
+    /// Adds the `format` archive of `size` bytes at `offset` in the file at `archive` as a
+    /// layer over the ones added before, decompressing it first if it is compressed with gzip,
+    /// xz or zstd. Returns an [`io::ErrorKind::InvalidData`] error if it is not an archive of
+    /// `format`, or is compressed otherwise, or an [`io::ErrorKind::FileTooLarge`] error if it
+    /// decompresses to more than the limit of the tree.
+    pub(crate) fn add_layer(
+        &mut self,
+        archive: &Path,
+        offset: u64,
+        size: u64,
+        format: Format,
+    ) -> io::Result<()> {
+        let index = match self.archives.iter().position(|known| known == archive) {
+            Some(index) => index,
+            None => {
+                self.archives.push(archive.to_owned());
+                self.archives.len() - 1
+            }
+        };
+        let mut file = File::open(archive)?;
+        // The longest magic, that of xz
+        let mut magic = vec![0; size.min(6) as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut magic)?;
+        let layer = self.layers;
+        self.layers += 1;
+        if let Some(decompress) = compression(&magic) {
+            let compressed = read_at(&mut file, offset, size)?;
+            let data = decompress(&compressed, self.limit)?;
+            let end = data.len() as u64;
+            let entries = entries(format, &mut Cursor::new(&data), 0, end, self.limit)?;
+            let decompressed = self.decompressed.len();
+            self.decompressed.push(data);
+            self.apply(layer, entries, |offset, size| Contents::Decompressed {
+                layer: decompressed,
+                offset,
+                size,
+            });
+        } else {
+            let entries = entries(format, &mut file, offset, offset + size, self.limit)?;
+            self.apply(layer, entries, |offset, size| Contents::Archive {
+                archive: index,
+                offset,
+                size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds the entries of the layer with index `layer` over the layers below, where
+    /// `contents` locates the contents of a file given their offset and size in the layer.
+    fn apply(
+        &mut self,
+        layer: usize,
+        entries: Vec<tar::Entry>,
+        contents: impl Fn(u64, u64) -> Contents,
+    ) {
+        for entry in entries {
+            let path = Path::new("/").join(&entry.path);
+            let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                continue;
+            };
+            // Whiteouts only hide what the layers below have
+            let name = name.as_bytes();
+            if self.whiteouts && name == OPAQUE_WHITEOUT {
+                self.remove_below(parent, layer, false);
+                continue;
+            }
+            let hidden = name
+                .strip_prefix(WHITEOUT_PREFIX)
+                .filter(|_| self.whiteouts);
+            if let Some(hidden) = hidden {
+                self.remove_below(&parent.join(OsStr::from_bytes(hidden)), layer, true);
+                continue;
+            }
+            let kind = match entry.kind {
+                EntryKind::File => NodeKind::File(contents(entry.offset, entry.size)),
+                EntryKind::HardLink(target) => match self.files.get(&Path::new("/").join(target)) {
+                    Some(Node {
+                        kind: NodeKind::File(contents),
+                        ..
+                    }) => NodeKind::File(*contents),
+                    _ => continue,
+                },
+                EntryKind::Symlink(target) => NodeKind::Symlink(target),
+                EntryKind::Dir => NodeKind::Dir,
+                EntryKind::Other => NodeKind::Other,
+            };
+            // Anything but a directory replaces a directory of the layers below whole
+            if !matches!(kind, NodeKind::Dir) {
+                self.remove_below(&path, layer, false);
+            }
+            let node = Node {
+                kind,
+                mode: entry.mode,
+                layer,
+            };
+            self.files.insert(path, node);
+        }
+    }
+
+    /// Removes what the layers below `layer` have below `path`, and with `itself`, at `path`.
+    fn remove_below(&mut self, path: &Path, layer: usize, itself: bool) {
+        let below: Vec<PathBuf> = self
+            .descendants(path)
+            .filter(|(_, node)| node.layer < layer)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in below {
+            self.files.remove(&path);
+        }
+        if itself && self.files.get(path).is_some_and(|node| node.layer < layer) {
+            self.files.remove(path);
+        }
+    }
+
+    /// The nodes below the directory `path`, in order.
+    fn descendants<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = (&'a PathBuf, &'a Node)> {
+        let after = (Bound::Excluded(path), Bound::Unbounded);
+        self.files
+            .range::<Path, _>(after)
+            .take_while(move |(descendant, _)| descendant.starts_with(path))
+    }
+
+    /// The index of the layer the file at `path` comes from, following symlinks.
+    pub(crate) fn layer(&self, path: &Path) -> Option<usize> {
+        let (_, node) = self.resolve(path).ok()?;
+        Some(node?.layer)
+    }
+
+    /// The node at `path`, following symlinks.
+    fn resolve(&self, path: &Path) -> io::Result<(PathBuf, Option<&Node>)> {
+        let not_found = || io::Error::from(io::ErrorKind::NotFound);
+        let path = sysroot::resolve_in(self, Path::new("/"), path).ok_or_else(not_found)?;
+        let node = self.files.get(&path);
+        Ok((path, node))
+    }
+
+    /// The node at `path`, following symlinks in its directories but not a final one, and its
+    /// path without them. No node if it is a directory implied by the paths below it.
+    fn lookup(&self, path: &Path) -> io::Result<(PathBuf, Option<&Node>)> {
+        let found = |path: &Path| {
+            let node = self.files.get(path);
+            let implied = path == Path::new("/") || self.descendants(path).next().is_some();
+            (node.is_some() || implied).then_some(node)
+        };
+        if let Some(node) = found(path) {
+            return Ok((path.to_owned(), node));
+        }
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return self.resolve(path);
+        };
+        let (parent, _) = self.resolve(parent)?;
+        let path = parent.join(name);
+        match found(&path) {
+            Some(node) => Ok((path, node)),
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+    }
+}
+
+impl FileSystem for ArchiveFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let (_, node) = self.resolve(path)?;
+        let Some(Node {
+            kind: NodeKind::File(contents),
+            ..
+        }) = node
+        else {
+            return Err(io::Error::other("not a regular file"));
+        };
+        match *contents {
+            Contents::Archive {
+                archive,
+                offset,
+                size,
+            } => read_at(&mut File::open(&self.archives[archive])?, offset, size),
+            Contents::Decompressed {
+                layer,
+                offset,
+                size,
+            } => {
+                let (start, end) = (offset as usize, (offset + size) as usize);
+                Ok(self.decompressed[layer][start..end].to_vec())
+            }
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let (_, node) = self.lookup(path)?;
+        // Directories only implied by their files have no mode of their own
+        let Some(node) = node else {
+            return Ok(FileMetadata {
+                kind: FileKind::Dir,
+                len: 0,
+                mode: 0o755,
+            });
+        };
+        let (kind, len) = match &node.kind {
+            NodeKind::File(Contents::Archive { size, .. })
+            | NodeKind::File(Contents::Decompressed { size, .. }) => (FileKind::File, *size),
+            NodeKind::Other => (FileKind::File, 0),
+            NodeKind::Dir => (FileKind::Dir, 0),
+            NodeKind::Symlink(target) => (FileKind::Symlink, target.as_os_str().len() as u64),
+        };
+        Ok(FileMetadata {
+            kind,
+            len,
+            mode: node.mode,
+        })
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.lookup(path)? {
+            (
+                _,
+                Some(Node {
+                    kind: NodeKind::Symlink(target),
+                    ..
+                }),
+            ) => Ok(target.clone()),
+            _ => Err(io::Error::from(io::ErrorKind::InvalidInput)),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        let (path, _) = self.resolve(path)?;
+        if self.metadata(&path)?.kind != FileKind::Dir {
+            return Err(io::Error::other("not a directory"));
+        }
+        let mut names: Vec<OsString> = Vec::new();
+        for (descendant, _) in self.descendants(&path) {
+            let relative = descendant.strip_prefix(&path).unwrap_or(descendant);
+            if let Some(name) = relative.components().next() {
+                if names.last().map(OsString::as_os_str) != Some(name.as_os_str()) {
+                    names.push(name.as_os_str().to_owned());
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.resolve(path).is_ok()
+    }
+}
+
+impl fmt::Debug for ArchiveFs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArchiveFs")
+            .field("archives", &self.archives)
+            .field("files", &self.files.len())
+            .finish()
+    }
+}
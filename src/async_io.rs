@@ -0,0 +1,82 @@
+//! An asynchronous variant of the analysis for tokio-based programs, behind the `tokio` feature.
+use std::path::{Path, PathBuf};
+
+use tokio::task;
+
+use crate::{ElfFile, Error, TraversalOrder};
+
+impl ElfFile {
+    /// Like [`ElfFile::get_libs_full_paths`], but doesn't block the runtime. Like `tokio::fs`,
+    /// the file system is accessed on tokio's blocking thread pool, one library at a time, with
+    /// the task yielding in between. Fails with [`Error::Cancelled`] if the runtime shuts down
+    /// before the analysis is done.
+    pub async fn get_libs_full_paths_async(&self) -> Result<Vec<PathBuf>, Error> {
+        let file = ElfFile {
+            path: self.path.clone(),
+            resolver: self.resolver.clone(),
+            contents: self.contents.clone(),
+        };
+        let iter = move || file.iter_dependencies(TraversalOrder::DepthFirst);
+        let mut dependencies = blocking(&self.path, iter).await??;
+        let mut libs = Vec::new();
+        loop {
+            let (rest, dependency) = blocking(&self.path, move || {
+                let dependency = dependencies.next();
+                (dependencies, dependency)
+            })
+            .await?;
+            let Some(dependency) = dependency else {
+                return Ok(libs);
+            };
+            match dependency.resolution {
+                Ok(resolved) => libs.extend(resolved.into_path()),
+                Err(error) => return Err(error.into_error(dependency.soname)),
+            }
+            dependencies = rest;
+        }
+    }
+}
+
+/// Runs `f` on the blocking thread pool for the analysis of `path`, passing on any panic.
+/// Fails with [`Error::Cancelled`] if the runtime shuts down before `f` runs.
+async fn blocking<T, F>(path: &Path, f: F) -> Result<T, Error>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match task::spawn_blocking(f).await {
+        Ok(value) => Ok(value),
+        Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+        Err(_) => Err(Error::Cancelled {
+            path: path.to_owned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ElfFile, Error};
+    use std::path::Path;
+
+    #[test]
+    fn test_cancelled() {
+        // The blocking tasks of a runtime that is shut down never run
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let handle = runtime.handle().clone();
+        drop(runtime);
+        let elf_file = ElfFile::new("/usr/bin/ls");
+        let result = handle.block_on(elf_file.get_libs_full_paths_async());
+        assert!(
+            matches!(result, Err(Error::Cancelled { path }) if path == Path::new("/usr/bin/ls"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_libs_full_paths_async() {
+        let elf_file = ElfFile::new("/usr/bin/ls");
+        let libs = elf_file.get_libs_full_paths_async().await.unwrap();
+        assert_eq!(libs, elf_file.get_libs_full_paths().unwrap());
+    }
+}
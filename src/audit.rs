@@ -0,0 +1,301 @@
+//! Scans of whole directory trees for binaries with libraries that can't be found, like
+//! `find / -type f | xargs ldd | grep "not found"`, but without running anything.
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{parse, DependencyGraph, ElfFile, Error, ResolveError, Resolver};
+
+/// A function told of each file a scan analyzed, see
+/// [`ResolverConfig::progress`](crate::ResolverConfig::progress).
+type ProgressFn = dyn Fn(usize, &Path, usize) + Send + Sync;
+
+/// The [progress hook](crate::ResolverConfig::progress) of a configuration.
+#[derive(Clone)]
+pub(crate) struct ProgressHook(pub(crate) Arc<ProgressFn>);
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressHook(..)")
+    }
+}
+
+/// A function passed each file a scan analyzed with its dependency graph, or each file or
+/// directory it failed on with why, that breaks to stop the scan.
+pub(crate) type Visit<'a> =
+    dyn FnMut(&Path, Result<DependencyGraph, Error>) -> ControlFlow<()> + 'a;
+
+/// How far a scan got, to pass to the progress hook.
+#[derive(Default)]
+struct Progress {
+    files: usize,
+    /// The libraries found for the files analyzed so far.
+    libraries: HashSet<PathBuf>,
+}
+
+/// The results of [`Resolver::audit`], displayed as each soname that was not found with the
+/// files needing it, followed by the files that could not be analyzed.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuditReport {
+    /// The number of ELF files analyzed.
+    pub scanned: usize,
+    /// Each soname that was not found, with the scanned files whose closure needs it.
+    pub missing: BTreeMap<String, BTreeSet<PathBuf>>,
+    /// The directories that could not be listed and the ELF files that could not be analyzed,
+    /// with why, in the order they were come across.
+    pub failed: Vec<(PathBuf, Error)>,
+    /// Whether the audit was stopped by the [cancel flag](crate::ResolverConfig::cancel_flag)
+    /// or the [deadline](crate::ResolverConfig::deadline) before every file was scanned.
+    pub interrupted: bool,
+}
+
+impl AuditReport {
+    /// Whether every library of every scanned file was found and every file could be analyzed.
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.failed.is_empty() && !self.interrupted
+    }
+
+    /// The scanned files with at least one library that was not found.
+    pub fn broken(&self) -> BTreeSet<&Path> {
+        self.missing
+            .values()
+            .flatten()
+            .map(PathBuf::as_path)
+            .collect()
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (soname, files) in &self.missing {
+            writeln!(f, "{soname} not found, needed by:")?;
+            for file in files {
+                writeln!(f, "\t{}", file.display())?;
+            }
+        }
+        for (_, error) in &self.failed {
+            writeln!(f, "{error}")?;
+        }
+        if self.interrupted {
+            writeln!(f, "interrupted after scanning {} files", self.scanned)?;
+        }
+        Ok(())
+    }
+}
+
+impl Resolver {
+    /// Finds every ELF file under the directories `roots`, by its magic bytes rather than its
+    /// name, and analyzes it with this resolver, collecting which libraries are not found and
+    /// which files need them. Symlinks are not followed, so each file is analyzed once, and
+    /// relocatable objects and core dumps, which have no dependencies, are skipped. A root that
+    /// is a file is analyzed by itself.
+    ///
+    /// The roots are paths on the host even with a [sysroot](crate::ResolverConfig::sysroot);
+    /// pass the sysroot itself to audit all of it.
+    pub fn audit<I, P>(self: &Arc<Self>, roots: I) -> AuditReport
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut report = AuditReport::default();
+        report.interrupted = self.scan(roots, &mut |path, graph| {
+            let graph = match graph {
+                Ok(graph) => graph,
+                Err(error) => {
+                    report.failed.push((path.to_owned(), error));
+                    return ControlFlow::Continue(());
+                }
+            };
+            report.scanned += 1;
+            let missing = graph
+                .nodes()
+                .iter()
+                .filter(|node| matches!(node.resolution, Err(ResolveError::NotFound { .. })));
+            for node in missing {
+                let files = report.missing.entry(node.soname.clone()).or_default();
+                files.insert(path.to_owned());
+            }
+            ControlFlow::Continue(())
+        });
+        report
+    }
+
+    /// Passes each ELF file at or under `roots` to `visit` with its dependency graph, in path
+    /// order, finding and skipping files like [`Resolver::audit`], and passes the directories
+    /// and files that fail with why. Returns whether the scan was stopped by `visit`, or by the
+    /// cancel flag or the deadline, checked before each file; the file being analyzed then is
+    /// not passed on, as its graph may be partial.
+    pub(crate) fn scan<I, P>(self: &Arc<Self>, roots: I, visit: &mut Visit) -> bool
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut progress = Progress::default();
+        roots
+            .into_iter()
+            .any(|root| self.scan_path(root.as_ref(), visit, &mut progress))
+    }
+
+    /// Like [`Resolver::scan`], for one root.
+    fn scan_path(
+        self: &Arc<Self>,
+        path: &Path,
+        visit: &mut Visit,
+        progress: &mut Progress,
+    ) -> bool {
+        if self.config().is_interrupted() {
+            return true;
+        }
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return false;
+        };
+        if metadata.is_dir() {
+            let entries = match fs::read_dir(path) {
+                Ok(entries) => entries,
+                Err(source) => {
+                    let error = Error::Io {
+                        path: path.to_owned(),
+                        source,
+                    };
+                    return visit(path, Err(error)).is_break();
+                }
+            };
+            let mut entries: Vec<PathBuf> = entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .collect();
+            entries.sort();
+            return entries
+                .iter()
+                .any(|entry| self.scan_path(entry, visit, progress));
+        } else if metadata.is_file() && parse::has_elf_magic(path) {
+            let graph = ElfFile::with_resolver(path, self.clone()).dependency_graph();
+            match &graph {
+                Ok(graph) if graph.is_interrupted() => return true,
+                Ok(graph) => {
+                    let found = graph.nodes()[1..].iter().filter_map(|node| node.path());
+                    progress.libraries.extend(found.map(Path::to_owned));
+                }
+                Err(Error::NotLoadable { .. }) => return false,
+                Err(_) => {}
+            }
+            progress.files += 1;
+            if let Some(hook) = &self.config().progress {
+                (hook.0)(progress.files, path, progress.libraries.len());
+            }
+            return visit(path, graph).is_break();
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ElfBuilder;
+    use crate::{Resolver, ResolverConfig};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_audit() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        ElfBuilder::new().write(&lib, "libfoo.so.1");
+        let broken_lib = ElfBuilder::new()
+            .needed("libgone.so.1")
+            .write(&lib, "libbar.so.1");
+        let bin = dir.path().join("bin");
+        ElfBuilder::new().needed("libfoo.so.1").write(&bin, "ok");
+        // Found by its magic bytes, whatever its name
+        let tool = ElfBuilder::new()
+            .needed("libbar.so.1")
+            .needed("libmissing.so.1")
+            .write(&bin, "tool.sh");
+        let script = ElfBuilder::new()
+            .needed("libmissing.so.1")
+            .write(dir.path().join("share"), "script");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        symlink(&tool, bin.join("tool")).unwrap();
+        ElfBuilder::new()
+            .e_type(crate::abi::ET_REL)
+            .needed("libmissing.so.1")
+            .write(&lib, "foo.o");
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(&lib);
+        let resolver = Arc::new(Resolver::new(config));
+        let report = resolver.audit([dir.path()]);
+        assert_eq!(report.scanned, 4);
+        assert!(report.failed.is_empty());
+        let missing: Vec<_> = report
+            .missing
+            .iter()
+            .map(|(soname, files)| (soname.as_str(), files.iter().collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(
+            missing,
+            [
+                ("libgone.so.1", vec![&tool, &broken_lib]),
+                ("libmissing.so.1", vec![&tool]),
+            ]
+        );
+        assert!(report.broken().into_iter().eq([&tool, &broken_lib]));
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "libgone.so.1 not found, needed by:\n\t{}\n\t{}\nlibmissing.so.1 not found, \
+                 needed by:\n\t{}\n",
+                tool.display(),
+                broken_lib.display(),
+                tool.display()
+            )
+        );
+
+        let report = resolver.audit([bin.join("ok")]);
+        assert_eq!(report.scanned, 1);
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        let libfoo = ElfBuilder::new().write(&lib, "libfoo.so.1");
+        let libbar = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .write(&lib, "libbar.so.1");
+        let bin = dir.path().join("bin");
+        let app = ElfBuilder::new().needed("libfoo.so.1").write(&bin, "app");
+        let tool = ElfBuilder::new()
+            .needed("libbar.so.1")
+            .needed("libmissing.so.1")
+            .write(&bin, "tool");
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let progress_calls = calls.clone();
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(&lib)
+            .progress(move |files, path, libraries| {
+                let mut calls = progress_calls.lock().unwrap();
+                calls.push((files, path.to_owned(), libraries));
+            });
+        let resolver = Arc::new(Resolver::new(config));
+        let report = resolver.audit([&bin, &lib]);
+        assert_eq!(report.scanned, 4);
+        let calls: Vec<(usize, PathBuf, usize)> = calls.lock().unwrap().clone();
+        assert_eq!(
+            calls,
+            [(1, app, 1), (2, tool, 2), (3, libbar, 2), (4, libfoo, 2)]
+        );
+    }
+}
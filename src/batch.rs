@@ -0,0 +1,277 @@
+//! Analysis of many files at once with a shared [`Resolver`], and of what the closures of
+//! several files have in common.
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{DependencyGraph, ElfFile, Error, ResolveError, Resolver};
+
+/// The results of [`Resolver::analyze_all`].
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BatchAnalysis {
+    /// The dependency graph of each input, in input order, or why it could not be analyzed.
+    pub results: Vec<(PathBuf, Result<DependencyGraph, Error>)>,
+    /// Every library resolved for any of the inputs.
+    pub libraries: BTreeSet<PathBuf>,
+}
+
+/// The results of [`Resolver::merged_closure`]: the union of the closures of several roots,
+/// like the binaries of one package, with the roots that share each library.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MergedClosure {
+    /// The roots that were analyzed, in input order.
+    pub roots: Vec<PathBuf>,
+    /// The roots that could not be analyzed, with why, in input order.
+    pub failed: Vec<(PathBuf, Error)>,
+    /// Every library resolved for any of the roots: everything the roots need installed.
+    pub libraries: BTreeMap<PathBuf, SharedLibrary>,
+    /// The sonames of the libraries that were not found, each with the roots whose closure
+    /// needs it, in input order.
+    pub missing: BTreeMap<String, Vec<PathBuf>>,
+}
+
+/// A library in a [`MergedClosure`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SharedLibrary {
+    /// The sonames it was loaded as.
+    pub sonames: BTreeSet<String>,
+    /// The roots whose closure has it, in input order.
+    pub roots: Vec<PathBuf>,
+    /// The size of the file in bytes, with symlinks followed. `None` if it could not be read.
+    pub size: Option<u64>,
+}
+
+/// Figures about how much the roots of a [`MergedClosure`] share, as computed by
+/// [`MergedClosure::sharing_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SharingStats {
+    /// The number of roots analyzed.
+    pub roots: usize,
+    /// The number of distinct libraries.
+    pub libraries: usize,
+    /// The number of libraries in the closure of each root, summed over the roots: how many
+    /// there would be if each root shipped its own.
+    pub references: usize,
+    /// The number of libraries more than one root needs.
+    pub shared: usize,
+    /// The number of libraries every root needs, if there is more than one.
+    pub common: usize,
+    /// The size of the distinct libraries, in bytes. Libraries whose size can't be read are
+    /// left out.
+    pub total_size: u64,
+    /// The bytes installing each library once saves over having a copy for each root that
+    /// needs it.
+    pub deduplicated_size: u64,
+}
+
+impl MergedClosure {
+    /// The libraries more than one root needs, in path order.
+    pub fn shared(&self) -> impl Iterator<Item = (&Path, &SharedLibrary)> + '_ {
+        self.libraries
+            .iter()
+            .filter(|(_, library)| library.roots.len() > 1)
+            .map(|(path, library)| (path.as_path(), library))
+    }
+
+    /// The libraries only `root` needs among the roots, in path order.
+    pub fn exclusive_to<P: AsRef<Path>>(&self, root: P) -> impl Iterator<Item = &Path> + '_ {
+        let root = root.as_ref().to_owned();
+        self.libraries
+            .iter()
+            .filter(move |(_, library)| library.roots == [root.as_path()])
+            .map(|(path, _)| path.as_path())
+    }
+
+    /// Computes how much the roots share.
+    pub fn sharing_stats(&self) -> SharingStats {
+        let mut stats = SharingStats {
+            roots: self.roots.len(),
+            libraries: self.libraries.len(),
+            ..SharingStats::default()
+        };
+        for library in self.libraries.values() {
+            let users = library.roots.len();
+            stats.references += users;
+            stats.shared += usize::from(users > 1);
+            stats.common += usize::from(users > 1 && users == self.roots.len());
+            let size = library.size.unwrap_or_default();
+            stats.total_size += size;
+            stats.deduplicated_size += size * (users as u64 - 1);
+        }
+        stats
+    }
+}
+
+impl Resolver {
+    /// Analyzes every file in `paths` with this resolver, so libraries they have in common are
+    /// only read once.
+    pub fn analyze_all<I>(self: &Arc<Self>, paths: I) -> BatchAnalysis
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        let mut libraries = BTreeSet::new();
+        let results = paths
+            .into_iter()
+            .map(|path| {
+                let graph = ElfFile::with_resolver(&path, self.clone()).dependency_graph();
+                if let Ok(graph) = &graph {
+                    let resolved = graph.nodes()[1..].iter().filter_map(|node| node.path());
+                    libraries.extend(resolved.map(PathBuf::from));
+                }
+                (path, graph)
+            })
+            .collect();
+        BatchAnalysis { results, libraries }
+    }
+
+    /// Analyzes every file in `roots` with this resolver, like [`Resolver::analyze_all`], and
+    /// merges their closures, recording which roots need each library, to tell what a whole
+    /// application needs installed and how much its files have in common. A library found at
+    /// different paths for different roots is listed at each of them.
+    pub fn merged_closure<I>(self: &Arc<Self>, roots: I) -> MergedClosure
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        let mut merged = MergedClosure::default();
+        for root in roots {
+            let file = ElfFile::with_resolver(&root, self.clone());
+            let graph = match file.dependency_graph() {
+                Ok(graph) => graph,
+                Err(error) => {
+                    merged.failed.push((root, error));
+                    continue;
+                }
+            };
+            for node in &graph.nodes()[1..] {
+                // A library may be loaded as several sonames, and be missing more than once
+                let Some(path) = node.path() else {
+                    if let Err(ResolveError::NotFound { .. }) = node.resolution {
+                        let users = merged.missing.entry(node.soname.clone()).or_default();
+                        if users.last() != Some(&root) {
+                            users.push(root.clone());
+                        }
+                    }
+                    continue;
+                };
+                let library =
+                    merged
+                        .libraries
+                        .entry(path.to_owned())
+                        .or_insert_with(|| SharedLibrary {
+                            sonames: BTreeSet::new(),
+                            roots: Vec::new(),
+                            size: file.file_size(path),
+                        });
+                library.sonames.insert(node.soname.clone());
+                if library.roots.last() != Some(&root) {
+                    library.roots.push(root.clone());
+                }
+            }
+            merged.roots.push(root);
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ElfBuilder;
+    use crate::{Error, Resolver, ResolverConfig, SharingStats};
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_analyze_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let common = ElfBuilder::new().write(dir.path(), "libcommon.so.1");
+        let extra = ElfBuilder::new().write(dir.path(), "libextra.so.1");
+        let app1 = ElfBuilder::new()
+            .needed("libcommon.so.1")
+            .write(dir.path(), "app1");
+        let app2 = ElfBuilder::new()
+            .needed("libcommon.so.1")
+            .needed("libextra.so.1")
+            .write(dir.path(), "app2");
+        let missing = dir.path().join("missing");
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path()])
+            .without_default_dirs()
+            .use_env(false);
+        let resolver = Arc::new(Resolver::new(config));
+
+        let batch = resolver.analyze_all([app1.clone(), app2.clone(), missing.clone()]);
+        let inputs: Vec<_> = batch.results.iter().map(|(path, _)| path).collect();
+        assert_eq!(inputs, [&app1, &app2, &missing]);
+        assert!(matches!(batch.results[2].1, Err(Error::Io { .. })));
+        assert!(batch.libraries.iter().eq([&common, &extra]));
+        assert_eq!(resolver.parsed_files(), 4);
+    }
+
+    #[test]
+    fn test_merged_closure() {
+        let dir = tempfile::tempdir().unwrap();
+        let common = ElfBuilder::new().write(dir.path(), "libcommon.so.1");
+        let extra = ElfBuilder::new().write(dir.path(), "libextra.so.1");
+        let solo = ElfBuilder::new()
+            .needed("libcommon.so.1")
+            .write(dir.path(), "libsolo.so.1");
+        let app1 = ElfBuilder::new()
+            .needed("libcommon.so.1")
+            .needed("libextra.so.1")
+            .write(dir.path(), "app1");
+        let app2 = ElfBuilder::new()
+            .needed("libextra.so.1")
+            .needed("libgone.so.1")
+            .needed("libcommon.so.1")
+            .write(dir.path(), "app2");
+        let app3 = ElfBuilder::new()
+            .needed("libsolo.so.1")
+            .needed("libgone.so.1")
+            .write(dir.path(), "app3");
+        let missing = dir.path().join("missing");
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path()])
+            .without_default_dirs()
+            .use_env(false);
+        let resolver = Arc::new(Resolver::new(config));
+
+        let roots = [app1.clone(), app2.clone(), missing.clone(), app3.clone()];
+        let merged = resolver.merged_closure(roots);
+        assert_eq!(merged.roots, [app1.clone(), app2.clone(), app3.clone()]);
+        assert!(matches!(&merged.failed[..], [(path, Error::Io { .. })] if *path == missing));
+        assert!(merged.libraries.keys().eq([&common, &extra, &solo]));
+        assert!(merged.libraries[&common]
+            .roots
+            .iter()
+            .eq([&app1, &app2, &app3]));
+        assert!(merged.libraries[&common]
+            .sonames
+            .iter()
+            .eq(["libcommon.so.1"]));
+        assert!(merged.missing["libgone.so.1"].iter().eq([&app2, &app3]));
+        let shared: Vec<_> = merged.shared().map(|(path, _)| path).collect();
+        assert_eq!(shared, [&common, &extra]);
+        assert!(merged.exclusive_to(&app3).eq([&solo]));
+        assert_eq!(merged.exclusive_to(&app1).count(), 0);
+
+        let size = |path| fs::metadata(path).unwrap().len();
+        let sizes = [&common, &extra, &solo].map(size);
+        assert_eq!(merged.libraries[&solo].size, Some(sizes[2]));
+        assert_eq!(
+            merged.sharing_stats(),
+            SharingStats {
+                roots: 3,
+                libraries: 3,
+                references: 6,
+                shared: 2,
+                common: 1,
+                total_size: sizes.iter().sum(),
+                deduplicated_size: 2 * sizes[0] + sizes[1],
+            }
+        );
+    }
+}
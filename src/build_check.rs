@@ -0,0 +1,269 @@
+//! Checks for build scripts that the native libraries a crate links against, and the binaries
+//! it builds or ships, will find their libraries at run time, reported as `cargo:warning=`
+//! lines.
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::{ElfFile, Error, Resolver, ResolverConfig, TraversalOrder};
+
+/// A check of the libraries a crate links against and of binaries, for its build script.
+///
+/// ```no_run
+/// use elf_dynamic_lib_getter::BuildCheck;
+///
+/// // In build.rs, next to `cargo:rustc-link-lib=ssl`
+/// let check = BuildCheck::new().link_lib("ssl").link_lib("dylib=z");
+/// if !check.emit_warnings() {
+///     std::process::exit(1);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BuildCheck {
+    config: ResolverConfig,
+    link_search: Vec<PathBuf>,
+    link_libs: Vec<String>,
+    binaries: Vec<PathBuf>,
+}
+
+/// Something [`BuildCheck`] found that would keep the program from loading.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BuildProblem {
+    /// The library to link `name`, as `cargo:rustc-link-lib` takes it, is not where the linker
+    /// would look for it.
+    LinkLib { name: String, error: Error },
+    /// The library to link `name` was found, but the library it names in its `DT_SONAME`,
+    /// which the program loads at run time, or one of that library's own, would not be.
+    RunTime { name: String, error: Error },
+    /// The binary at `path` could not be analyzed, or one of its libraries would not be found.
+    Binary { path: PathBuf, error: Error },
+}
+
+impl fmt::Display for BuildProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildProblem::LinkLib { name, error } => write!(f, "cannot link -l{name}: {error}"),
+            BuildProblem::RunTime { name, error } => {
+                write!(f, "-l{name} links, but would not load at run time: {error}")
+            }
+            BuildProblem::Binary { path, error } => write!(f, "{}: {error}", path.display()),
+        }
+    }
+}
+
+impl BuildCheck {
+    /// A check resolving with the default [`ResolverConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A check resolving at run time with `config`, like the `LD_LIBRARY_PATH` or
+    /// [sysroot](ResolverConfig::sysroot) the program will run with.
+    pub fn with_config(config: ResolverConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Adds a directory the linker searches, like `cargo:rustc-link-search` does. It is not
+    /// searched at run time, unless the run-time configuration has it too.
+    pub fn link_search<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.link_search.push(dir.into());
+        self
+    }
+
+    /// Adds a library to link, as `cargo:rustc-link-lib` takes it: `NAME` or `KIND=NAME`, with
+    /// optional modifiers after the kind. The linker looks for `libNAME.so`, and the program
+    /// loads the library that names at run time. Static libraries and frameworks are not
+    /// checked.
+    pub fn link_lib<S: Into<String>>(mut self, lib: S) -> Self {
+        self.link_libs.push(lib.into());
+        self
+    }
+
+    /// Adds a binary whose libraries are to be found, like the executable being built or a
+    /// shared library the crate ships.
+    pub fn binary<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.binaries.push(path.into());
+        self
+    }
+
+    /// Runs the check, returning every problem found in the order of the libraries and
+    /// binaries.
+    pub fn problems(&self) -> Vec<BuildProblem> {
+        let mut link_config = self.config.clone();
+        for dir in &self.link_search {
+            link_config = link_config.append_search_dir(dir);
+        }
+        let linker = Resolver::new(link_config);
+        let runtime = Arc::new(Resolver::new(self.config.clone()));
+        let mut problems = Vec::new();
+        for lib in &self.link_libs {
+            let Some(name) = shared_lib_name(lib) else {
+                continue;
+            };
+            let name = name.to_owned();
+            let linked = match linker.resolve_soname(format!("lib{name}.so")) {
+                Ok(path) => path,
+                Err(error) => {
+                    problems.push(BuildProblem::LinkLib { name, error });
+                    continue;
+                }
+            };
+            // Without a DT_SONAME, the program records and loads the linked file's name
+            let soname = match ElfFile::with_resolver(&linked, runtime.clone()).dynamic_info() {
+                Ok(dynamic) => dynamic.soname.unwrap_or_else(|| format!("lib{name}.so")),
+                Err(error) => {
+                    problems.push(BuildProblem::LinkLib { name, error });
+                    continue;
+                }
+            };
+            let errors = match runtime.resolve_soname(&soname) {
+                Ok(path) => unresolved(&ElfFile::with_resolver(path, runtime.clone())),
+                Err(error) => vec![error],
+            };
+            let problem = |error| BuildProblem::RunTime {
+                name: name.clone(),
+                error,
+            };
+            problems.extend(errors.into_iter().map(problem));
+        }
+        for path in &self.binaries {
+            let errors = unresolved(&ElfFile::with_resolver(path, runtime.clone()));
+            let problem = |error| BuildProblem::Binary {
+                path: path.clone(),
+                error,
+            };
+            problems.extend(errors.into_iter().map(problem));
+        }
+        problems
+    }
+
+    /// Runs the check and prints a `cargo:warning=` line for each problem, which Cargo shows
+    /// when the build finishes. Returns whether there were none, for the build script to fail
+    /// when there were.
+    pub fn emit_warnings(&self) -> bool {
+        let problems = self.problems();
+        let _ = write_warnings(&mut io::stdout().lock(), &problems);
+        problems.is_empty()
+    }
+}
+
+/// The name of the shared library `lib` says to link, `None` if it is not one.
+fn shared_lib_name(lib: &str) -> Option<&str> {
+    match lib.split_once('=') {
+        None => Some(lib),
+        Some((kind, name)) => {
+            let kind = kind.split_once(':').map_or(kind, |(kind, _)| kind);
+            (kind == "dylib").then_some(name)
+        }
+    }
+}
+
+/// Why `file` can't be analyzed, or the libraries in its closure that are not found.
+fn unresolved(file: &ElfFile) -> Vec<Error> {
+    match file.iter_dependencies(TraversalOrder::DepthFirst) {
+        Ok(dependencies) => dependencies
+            .filter_map(|dependency| match dependency.resolution {
+                Ok(_) => None,
+                Err(error) => Some(error.into_error(dependency.soname)),
+            })
+            .collect(),
+        Err(error) => vec![error],
+    }
+}
+
+/// Writes each of `problems` to `out` as a `cargo:warning=` line.
+fn write_warnings(out: &mut impl Write, problems: &[BuildProblem]) -> io::Result<()> {
+    for problem in problems {
+        // Cargo ends the warning at the end of the line
+        let message = problem.to_string().replace('\n', " ");
+        writeln!(out, "cargo:warning={message}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{shared_lib_name, write_warnings, BuildCheck, BuildProblem};
+    use crate::test_util::ElfBuilder;
+    use crate::{Error, ResolverConfig};
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_shared_lib_name() {
+        assert_eq!(shared_lib_name("ssl"), Some("ssl"));
+        assert_eq!(shared_lib_name("dylib=z"), Some("z"));
+        assert_eq!(shared_lib_name("dylib:+verbatim=z"), Some("z"));
+        assert_eq!(shared_lib_name("static=foo"), None);
+        assert_eq!(shared_lib_name("framework=Security"), None);
+    }
+
+    #[test]
+    fn test_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let build = dir.path().join("build");
+        let run = dir.path().join("run");
+        // libfoo links to libfoo.so.1, which is installed along with what it needs
+        ElfBuilder::new().write(&run, "libbar.so.2");
+        ElfBuilder::new()
+            .soname("libfoo.so.1")
+            .needed("libbar.so.2")
+            .write(&run, "libfoo.so.1");
+        std::fs::create_dir(&build).unwrap();
+        symlink(run.join("libfoo.so.1"), build.join("libfoo.so")).unwrap();
+        // libqux is only where the linker looks, and needs a library that is not anywhere
+        ElfBuilder::new()
+            .soname("libqux.so.3")
+            .write(&build, "libqux.so.3");
+        symlink("libqux.so.3", build.join("libqux.so")).unwrap();
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .needed("libgone.so.1")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(&run);
+
+        let check = BuildCheck::with_config(config)
+            .link_search(&build)
+            .link_lib("foo")
+            .link_lib("dylib=qux")
+            .link_lib("missing")
+            .link_lib("static=vendored")
+            .binary(&app);
+        let problems = check.problems();
+        assert!(matches!(
+            &problems[..],
+            [
+                BuildProblem::RunTime {
+                    error: Error::SonameNotFound { soname, .. },
+                    ..
+                },
+                BuildProblem::LinkLib {
+                    error: Error::SonameNotFound { .. },
+                    ..
+                },
+                BuildProblem::Binary {
+                    error: Error::Unresolved { soname: gone, .. },
+                    ..
+                },
+            ] if soname == "libqux.so.3" && gone == "libgone.so.1"
+        ));
+
+        let mut out = Vec::new();
+        write_warnings(&mut out, &problems[..2]).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(
+            "cargo:warning=-lqux links, but would not load at run time: library \"libqux.so.3\" \
+             was not found"
+        ));
+        assert!(lines[1].starts_with("cargo:warning=cannot link -lmissing: library"));
+    }
+}
@@ -0,0 +1,242 @@
+//! Copying a dependency closure into a directory that mirrors where each file is installed.
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::os::unix::fs::symlink;
+
+use crate::sysroot::{components, MAX_SYMLINKS};
+use crate::{patch, DependencyGraph, DependencyKind, Error, ResolverConfig, SearchRule};
+
+/// How [`ElfFile::bundle_to`](crate::ElfFile::bundle_to) puts each file into the bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BundleMode {
+    /// Copies each file, keeping its permissions.
+    #[default]
+    Copy,
+    /// Hard-links each file, copying it where that fails, e.g. across file systems.
+    HardLink,
+}
+
+/// The `DT_RUNPATH` one file of a bundle needs to find its libraries inside the bundle, as
+/// planned by [`ElfFile::runpath_plan`](crate::ElfFile::runpath_plan).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunpathRewrite {
+    /// The path of the file, relative to the bundle directory.
+    pub path: PathBuf,
+    /// The new `RUNPATH`: the directories of the libraries the file needs, relative to
+    /// `$ORIGIN`, e.g. `$ORIGIN/../lib`, as `patchelf --set-rpath` takes it.
+    pub runpath: String,
+}
+
+impl RunpathRewrite {
+    /// Writes the new `RUNPATH` into the file in the bundle at `dir`, turning a `DT_RPATH` into
+    /// a `DT_RUNPATH`. Unlike `patchelf`, the file is not grown: the new string takes the place
+    /// of the old one. The file is replaced rather than modified, so the original of a file
+    /// bundled with [`BundleMode::HardLink`] is left alone.
+    /// # Return Value [Err]
+    /// Returned with [`Error::RunpathNoSpace`] if the file has no `DT_RUNPATH` or `DT_RPATH`
+    /// at least as long as the new one, leaving the file as it was.
+    pub fn apply<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let path = dir.as_ref().join(&self.path);
+        // The file itself is replaced, not a symlink to it
+        let path = fs::canonicalize(&path).map_err(|source| Error::Io { path, source })?;
+        patch::set_runpath(&path, &self.runpath)
+    }
+}
+
+/// The directory `path` is put below when bundling it: the sysroot if it is inside it,
+/// otherwise `/`.
+pub(crate) fn bundle_root<'a>(config: &'a ResolverConfig, path: &Path) -> &'a Path {
+    match &config.sysroot {
+        Some(root) if path.starts_with(root) => root,
+        _ => Path::new("/"),
+    }
+}
+
+/// Where `path` ends up relative to the bundle directory.
+fn bundled(config: &ResolverConfig, path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    let root = bundle_root(config, &path);
+    path.strip_prefix(root).unwrap_or(&path).to_owned()
+}
+
+/// Plans the `RUNPATH` of each file in `graph` with libraries to search for, in load order. The
+/// graph must have been built without canonical paths, so each library is at the path it was
+/// found at.
+pub(crate) fn runpath_plan(
+    graph: &DependencyGraph,
+    config: &ResolverConfig,
+) -> Vec<RunpathRewrite> {
+    // ld.so searches, and expands $ORIGIN to, the directory each library is found in, whose
+    // symlinks the kernel follows before `..` components
+    let real_dir = |path: &Path| bundled(config, &config.canonical(path.parent().unwrap_or(path)));
+    let mut plan = Vec::new();
+    for id in graph.load_order() {
+        let node = graph.node(id);
+        let Some(path) = node.path() else {
+            continue;
+        };
+        let origin = if id == graph.root() {
+            // For the executable, $ORIGIN is the real directory of the file itself
+            let mut dir = bundled(config, &config.canonical(path));
+            dir.pop();
+            dir
+        } else {
+            real_dir(path)
+        };
+        let mut entries: Vec<String> = Vec::new();
+        for &dependency in graph.dependencies(id) {
+            let dependency = graph.node(dependency);
+            let searched = !matches!(
+                dependency.kind,
+                DependencyKind::Preload | DependencyKind::Interpreter | DependencyKind::Virtual
+            ) && dependency.found_by != Some(SearchRule::Path);
+            let Some(library) = dependency.path().filter(|_| searched) else {
+                continue;
+            };
+            let relative = relative_dir(&origin, &real_dir(library));
+            let entry = if relative.as_os_str().is_empty() {
+                "$ORIGIN".to_owned()
+            } else {
+                format!("$ORIGIN/{}", relative.to_string_lossy())
+            };
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+        if !entries.is_empty() {
+            plan.push(RunpathRewrite {
+                path: bundled(config, path),
+                runpath: entries.join(":"),
+            });
+        }
+    }
+    plan
+}
+
+/// The path of the directory `to` relative to the directory `from`, both without symlinks.
+fn relative_dir(from: &Path, to: &Path) -> PathBuf {
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|(from, to)| from == to)
+        .count();
+    let mut relative: PathBuf = from.components().skip(common).map(|_| "..").collect();
+    relative.extend(to.components().skip(common));
+    relative
+}
+
+/// Puts the file at `path`, which is inside `root`, at the same place inside `dir`, with the
+/// symlinks leading to it, including those among the directories on the way. Absolute symlinks
+/// are made relative, so they point inside `dir` wherever it is moved. Files already in `dir`
+/// are kept.
+pub(crate) fn place(root: &Path, path: &Path, dir: &Path, mode: BundleMode) -> Result<(), Error> {
+    let read_error = |path: &Path| {
+        let path = path.to_owned();
+        move |source| Error::Io { path, source }
+    };
+    let write_error = |path: &Path| {
+        let path = path.to_owned();
+        move |source| Error::Write { path, source }
+    };
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut remaining: VecDeque<OsString> = components(relative).collect();
+    // The path so far, relative to `root` and `dir`, with every symlink followed
+    let mut resolved = PathBuf::new();
+    let mut symlinks = 0;
+    fs::create_dir_all(dir).map_err(write_error(dir))?;
+    while let Some(component) = remaining.pop_front() {
+        if component == ".." {
+            resolved.pop();
+            continue;
+        }
+        let next = resolved.join(&component);
+        let (source, target) = (root.join(&next), dir.join(&next));
+        let metadata = fs::symlink_metadata(&source).map_err(read_error(&source))?;
+        let exists = fs::symlink_metadata(&target).is_ok();
+        if metadata.file_type().is_symlink() {
+            symlinks += 1;
+            if symlinks > MAX_SYMLINKS {
+                let loop_error = io::Error::other("too many levels of symbolic links");
+                return Err(read_error(path)(loop_error));
+            }
+            let link = fs::read_link(&source).map_err(read_error(&source))?;
+            if !exists {
+                let relative_link = relative_link(&resolved, &link);
+                symlink(relative_link, &target).map_err(write_error(&target))?;
+            }
+            if link.is_absolute() {
+                resolved.clear();
+            }
+            for component in components(&link).rev() {
+                remaining.push_front(component);
+            }
+        } else if !metadata.is_dir() {
+            if !exists {
+                put_file(&source, &target, mode).map_err(write_error(&target))?;
+            }
+            resolved = next;
+        } else {
+            if !exists {
+                fs::create_dir(&target).map_err(write_error(&target))?;
+            }
+            resolved = next;
+        }
+    }
+    Ok(())
+}
+
+/// The target of a symlink in the directory `parent`, relative to the root, that points to
+/// `link`, with absolute targets turned into relative ones.
+fn relative_link(parent: &Path, link: &Path) -> PathBuf {
+    let Ok(absolute) = link.strip_prefix("/") else {
+        return link.to_owned();
+    };
+    let mut relative: PathBuf = parent.components().map(|_| "..").collect();
+    relative.push(absolute);
+    relative
+}
+
+/// Bundles keep the symlinks of the sysroot, which only unix targets can create.
+#[cfg(not(unix))]
+fn symlink(_original: impl AsRef<Path>, _link: impl AsRef<Path>) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+fn put_file(source: &Path, target: &Path, mode: BundleMode) -> io::Result<()> {
+    if mode == BundleMode::HardLink && fs::hard_link(source, target).is_ok() {
+        return Ok(());
+    }
+    fs::copy(source, target).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{relative_dir, relative_link};
+    use std::path::Path;
+
+    #[test]
+    fn test_relative_dir() {
+        let relative = |from: &str, to: &str| relative_dir(Path::new(from), Path::new(to));
+        assert_eq!(relative("usr/bin", "usr/lib"), Path::new("../lib"));
+        assert_eq!(relative("usr/lib", "usr/lib"), Path::new(""));
+        assert_eq!(relative("usr/lib", "usr/lib/foo"), Path::new("foo"));
+        assert_eq!(relative("opt/app/bin", "lib"), Path::new("../../../lib"));
+    }
+
+    #[test]
+    fn test_relative_link() {
+        let relative = |parent: &str, link: &str| relative_link(Path::new(parent), Path::new(link));
+        assert_eq!(relative("", "/usr/lib"), Path::new("usr/lib"));
+        assert_eq!(
+            relative("usr/lib", "/lib/x.so"),
+            Path::new("../../lib/x.so")
+        );
+        assert_eq!(relative("usr/lib", "x.so.1.2"), Path::new("x.so.1.2"));
+    }
+}
@@ -0,0 +1,917 @@
+//! Configuration of how needed libraries are searched for.
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::audit::ProgressHook;
+use crate::filesystem::{FileSystem, HostFs};
+use crate::iter::TraversalFilter;
+use crate::ld_so_conf::wildcard_match;
+use crate::mount_fs::MountFs;
+use crate::store::StorePath;
+use crate::{sysroot, ContentDigest, DiskCache, Error, Target, TraversalDecision, TraversalOrder};
+
+/// The platform whose dynamic linker is emulated, which decides where the system libraries are
+/// found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// Linux with glibc, whose system directories are listed in `/etc/ld.so.cache` and
+    /// `/etc/ld.so.conf`.
+    Glibc,
+    /// Linux with musl, e.g. Alpine, whose system directories are listed in
+    /// `/etc/ld-musl-$(ARCH).path`.
+    Musl,
+    /// FreeBSD, whose system directories are listed in `/var/run/ld-elf.so.hints`.
+    FreeBsd,
+    /// Android, whose system directories are the search path of the default linker namespace
+    /// in `ld.config.txt`, picked by the directory of the executable. Like bionic, `RPATH`s are
+    /// ignored.
+    Android,
+}
+
+/// What to do with candidate libraries built for another operating system ABI than the
+/// analyzed file, by their `EI_OSABI` and `EI_ABIVERSION`, like a FreeBSD library in a directory
+/// shared with Linux ones. Libraries built for no particular operating system are compatible
+/// with every file, and those using GNU extensions with every Linux file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OsAbiCheck {
+    /// Skip them and keep searching, as for libraries of another architecture, since the
+    /// dynamic linker would refuse to load them.
+    #[default]
+    Reject,
+    /// Accept them, but mark them as [mismatched](crate::DependencyNode::os_abi_mismatch).
+    Warn,
+    /// Accept them without checking.
+    Ignore,
+}
+
+/// A cap on what analyzing untrusted files may take, set with
+/// [`ResolverConfig::max_file_size`], [`ResolverConfig::max_dependencies`],
+/// [`ResolverConfig::max_bytes_parsed`] and [`ResolverConfig::max_decompressed_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResourceLimit {
+    /// The size in bytes of any one file read.
+    FileSize(u64),
+    /// The number of libraries in a dependency graph, found or not.
+    Dependencies(usize),
+    /// The size in bytes of the files read for a dependency graph, summed.
+    BytesParsed(u64),
+    /// The size in bytes of any one compressed stream once decompressed, such as a layer of
+    /// an image or a kernel module.
+    DecompressedSize(u64),
+}
+
+impl fmt::Display for ResourceLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceLimit::FileSize(bytes) => write!(f, "the file size limit of {bytes} bytes"),
+            ResourceLimit::Dependencies(count) => {
+                write!(f, "the limit of {count} dependencies")
+            }
+            ResourceLimit::BytesParsed(bytes) => {
+                write!(f, "the limit of {bytes} bytes parsed")
+            }
+            ResourceLimit::DecompressedSize(bytes) => {
+                write!(f, "the decompressed size limit of {bytes} bytes")
+            }
+        }
+    }
+}
+
+/// Tunes how [`ElfFile`](crate::ElfFile) resolves dependencies. The [`Default`] configuration
+/// behaves like the dynamic linker would for the current process.
+///
+/// ```
+/// use elf_dynamic_lib_getter::{ElfFile, ResolverConfig};
+///
+/// let config = ResolverConfig::new()
+///     .use_env(false)
+///     .append_search_dir("/opt/vendor/lib")
+///     .recursive(false);
+/// let elf_file = ElfFile::with_config("/usr/bin/ls", config);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResolverConfig {
+    pub(crate) use_env: bool,
+    /// The environment variables to use instead of the process's.
+    pub(crate) environment: Option<HashMap<OsString, OsString>>,
+    pub(crate) search_dirs: Vec<PathBuf>,
+    pub(crate) default_dirs: bool,
+    pub(crate) recursive: bool,
+    pub(crate) order: TraversalOrder,
+    pub(crate) sysroot: Option<PathBuf>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) traversal_filter: Option<TraversalFilter>,
+    pub(crate) cancel_flag: Option<Arc<AtomicBool>>,
+    pub(crate) progress: Option<ProgressHook>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) max_file_size: Option<u64>,
+    pub(crate) max_dependencies: Option<usize>,
+    pub(crate) max_bytes_parsed: Option<u64>,
+    pub(crate) max_decompressed_size: Option<u64>,
+    pub(crate) secure_execution: Option<bool>,
+    pub(crate) ld_library_path_empty_as_cwd: bool,
+    pub(crate) working_dir: Option<PathBuf>,
+    pub(crate) system_preload: bool,
+    pub(crate) preload: Option<Vec<String>>,
+    pub(crate) hwcaps: Option<Vec<String>>,
+    pub(crate) legacy_hwcaps: Vec<String>,
+    pub(crate) lib_token: Option<String>,
+    pub(crate) platform: Option<Platform>,
+    pub(crate) target: Option<Target>,
+    pub(crate) os_abi_check: OsAbiCheck,
+    pub(crate) canonicalize: bool,
+    pub(crate) exhaustive_search: bool,
+    pub(crate) trace_search: bool,
+    pub(crate) search_paths: bool,
+    pub(crate) virtual_objects: bool,
+    pub(crate) include_interpreter: bool,
+    pub(crate) ignored_sonames: Vec<String>,
+    pub(crate) soname_overrides: HashMap<OsString, PathBuf>,
+    pub(crate) plugin_dirs: Vec<PathBuf>,
+    pub(crate) dlopen_notes: bool,
+    pub(crate) content_digest: Option<ContentDigest>,
+    pub(crate) stat_files: bool,
+    pub(crate) disk_cache: Option<Arc<DiskCache>>,
+    pub(crate) store: Option<PathBuf>,
+    pub(crate) store_root: Option<PathBuf>,
+    /// The files to resolve in instead of the host's, with the sysroot at their root, and the
+    /// store read from its root.
+    pub(crate) filesystem: Option<Arc<dyn FileSystem>>,
+    /// The files set with [`ResolverConfig::filesystem`], which `filesystem` reads the store
+    /// from.
+    pub(crate) given_filesystem: Option<Arc<dyn FileSystem>>,
+}
+
+impl Default for ResolverConfig {
+    fn default() -> Self {
+        Self {
+            use_env: true,
+            environment: None,
+            search_dirs: Vec::new(),
+            default_dirs: true,
+            recursive: true,
+            order: TraversalOrder::DepthFirst,
+            sysroot: None,
+            max_depth: None,
+            traversal_filter: None,
+            cancel_flag: None,
+            progress: None,
+            deadline: None,
+            max_file_size: None,
+            max_dependencies: None,
+            max_bytes_parsed: None,
+            max_decompressed_size: None,
+            secure_execution: None,
+            ld_library_path_empty_as_cwd: true,
+            working_dir: None,
+            system_preload: false,
+            preload: None,
+            hwcaps: None,
+            legacy_hwcaps: Vec::new(),
+            lib_token: None,
+            platform: None,
+            target: None,
+            os_abi_check: OsAbiCheck::Reject,
+            canonicalize: false,
+            exhaustive_search: false,
+            trace_search: false,
+            search_paths: false,
+            virtual_objects: false,
+            include_interpreter: false,
+            ignored_sonames: Vec::new(),
+            soname_overrides: HashMap::new(),
+            plugin_dirs: Vec::new(),
+            dlopen_notes: false,
+            content_digest: None,
+            stat_files: false,
+            disk_cache: None,
+            store: None,
+            store_root: None,
+            filesystem: None,
+            given_filesystem: None,
+        }
+    }
+}
+
+impl ResolverConfig {
+    /// Same as [`ResolverConfig::default`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to honor `LD_LIBRARY_PATH` and `LD_PRELOAD` from the process environment, as it
+    /// is when the [`Resolver`](crate::Resolver) is created, or from the variables set with
+    /// [`ResolverConfig::environment`]. Defaults to `true`.
+    pub fn use_env(mut self, use_env: bool) -> Self {
+        self.use_env = use_env;
+        self
+    }
+
+    /// Resolves as if the analyzed file were run with exactly the environment variables
+    /// `vars`, instead of the process's, e.g. to see how another environment's
+    /// `LD_LIBRARY_PATH` and `LD_PRELOAD` would change what is loaded. Variables left out are
+    /// unset. Only applies if [`ResolverConfig::use_env`] is `true`.
+    ///
+    /// ```
+    /// use elf_dynamic_lib_getter::ResolverConfig;
+    ///
+    /// let config = ResolverConfig::new().environment([("LD_LIBRARY_PATH", "/opt/app/lib")]);
+    /// ```
+    pub fn environment<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<OsString>,
+        V: Into<OsString>,
+    {
+        let vars = vars
+            .into_iter()
+            .map(|(name, value)| (name.into(), value.into()));
+        self.environment = Some(vars.collect());
+        self
+    }
+
+    /// Replaces the custom search directories, which are searched after `RUNPATH` and before
+    /// the system directories, in the given order.
+    pub fn with_search_dirs<I, P>(mut self, dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.search_dirs = dirs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Adds a directory to the end of the custom search directories.
+    pub fn append_search_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.search_dirs.push(dir.into());
+        self
+    }
+
+    /// Stops searching the system directories: the `ld.so.cache`, the `ld.so.conf` directories
+    /// and the built-in default directories. Combined with [`ResolverConfig::with_search_dirs`]
+    /// and [`ResolverConfig::use_env`], this leaves only the custom directories and the files'
+    /// own `RPATH`/`RUNPATH` to search.
+    pub fn without_default_dirs(mut self) -> Self {
+        self.default_dirs = false;
+        self
+    }
+
+    /// Whether to also collect the dependencies of dependencies. When `false`, only the
+    /// analyzed file's own `DT_NEEDED` entries are resolved. Defaults to `true`.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// The order the closure is walked in, which is the order libraries are listed in by
+    /// [`ElfFile::get_libs_resolutions`](crate::ElfFile::get_libs_resolutions) and the other
+    /// methods, and the order of the nodes of a
+    /// [`DependencyGraph`](crate::DependencyGraph). With [`TraversalOrder::BreadthFirst`],
+    /// libraries are searched for in the order ld.so loads them, which is also the order it
+    /// searches them for symbols, so the library that a dependency needed by several objects
+    /// is first found as is the one ld.so would find. Either way, the same files always give
+    /// the same results, in the same order. Defaults to [`TraversalOrder::DepthFirst`].
+    pub fn traversal_order(mut self, order: TraversalOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Stops descending into the dependencies of libraries `max_depth` levels below the analyzed
+    /// file, where its own dependencies are at level 1. Libraries at that level that need others
+    /// are marked as [truncated](crate::DependencyNode::truncated). Unlimited by default.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Asks `filter` before collecting the dependencies of each found library, with its
+    /// soname, the path it was found at and its depth, where the analyzed file's own dependencies
+    /// are at depth 1. Libraries it returns [`TraversalDecision::SkipSubtree`] for are still
+    /// reported, but marked as [truncated](crate::DependencyNode::truncated) if they need
+    /// others, which are only collected if another library needs them. For example, to stop at
+    /// the system libraries:
+    ///
+    /// ```
+    /// use elf_dynamic_lib_getter::{ResolverConfig, TraversalDecision};
+    ///
+    /// let config = ResolverConfig::new().traversal_filter(|_soname, path, _depth| {
+    ///     if path.starts_with("/usr") {
+    ///         TraversalDecision::SkipSubtree
+    ///     } else {
+    ///         TraversalDecision::Descend
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// Every dependency is collected by default.
+    pub fn traversal_filter(
+        mut self,
+        filter: impl Fn(&str, &Path, usize) -> TraversalDecision + Send + Sync + 'static,
+    ) -> Self {
+        self.traversal_filter = Some(TraversalFilter(Arc::new(filter)));
+        self
+    }
+
+    /// Stops collecting dependencies once `flag` is set, e.g. by another thread when the user
+    /// gives up. The flag is checked before each library is read, and what was collected until
+    /// then is returned, marked as [interrupted](crate::DependencyGraph::is_interrupted):
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use elf_dynamic_lib_getter::ResolverConfig;
+    ///
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let config = ResolverConfig::new().cancel_flag(cancel.clone());
+    /// // Later, from anywhere
+    /// cancel.store(true, Ordering::Relaxed);
+    /// ```
+    ///
+    /// Never set by default.
+    pub fn cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel_flag = Some(flag);
+        self
+    }
+
+    /// Calls `progress` after each ELF file a batch scan like
+    /// [`Resolver::audit`](crate::Resolver::audit) or
+    /// [`Resolver::reverse_index`](crate::Resolver::reverse_index) analyzes, with the number of
+    /// files analyzed so far, the path of the file and the number of distinct libraries found
+    /// for the files so far, e.g. to show a progress bar:
+    ///
+    /// ```
+    /// use elf_dynamic_lib_getter::ResolverConfig;
+    ///
+    /// let config = ResolverConfig::new().progress(|files, path, libraries| {
+    ///     eprint!("\r{files} files, {libraries} libraries: {}", path.display());
+    /// });
+    /// ```
+    ///
+    /// Files that could not be analyzed count too. Nothing is called by default.
+    pub fn progress(
+        mut self,
+        progress: impl Fn(usize, &Path, usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(ProgressHook(Arc::new(progress)));
+        self
+    }
+
+    /// Stops collecting dependencies at `deadline`, as if a [cancel flag](Self::cancel_flag)
+    /// were set then, so traversals over network file systems that hang don't hang forever. A
+    /// library being read at the deadline is still read to the end. None by default.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Refuses to read ELF files larger than `bytes`, whether the analyzed file, which then
+    /// fails with [`Error::LimitExceeded`](crate::Error::LimitExceeded), or a library, which is
+    /// then recorded as failing to load with it. Their digests are not computed either. Meant
+    /// for files from untrusted sources, along with [`ResolverConfig::max_dependencies`] and
+    /// [`ResolverConfig::max_bytes_parsed`]. Unlimited by default.
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Stops collecting a dependency graph once it has `count` libraries, found or not, so
+    /// crafted files needing thousands of libraries that need thousands more can't make it
+    /// grow without bound. The graph then holds what was collected until then, and says which
+    /// [limit it reached](crate::DependencyGraph::exceeded_limit). Unlimited by default.
+    pub fn max_dependencies(mut self, count: usize) -> Self {
+        self.max_dependencies = Some(count);
+        self
+    }
+
+    /// Stops collecting a dependency graph before reading a library that would take the size
+    /// of the files read for it, the analyzed file included, past `bytes`, as
+    /// [`ResolverConfig::max_dependencies`] does. Files are counted whether they were read or
+    /// remembered from an earlier analysis, so the graph is the same either way. Unlimited by
+    /// default.
+    pub fn max_bytes_parsed(mut self, bytes: u64) -> Self {
+        self.max_bytes_parsed = Some(bytes);
+        self
+    }
+
+    /// Refuses to decompress any one stream to more than `bytes`, so a small crafted file
+    /// can't expand to fill memory: a layer of an OCI image, the files of a package of a
+    /// [`PackageSet`](crate::PackageSet), a compressed [`RootfsArchive`](crate::RootfsArchive)
+    /// or [kernel module](crate::KernelModule), or a table or file of an
+    /// [`AppImage`](crate::AppImage). Opening fails with
+    /// [`Error::LimitExceeded`](crate::Error::LimitExceeded), and a kernel module needed by
+    /// the analyzed one is recorded as failing to load with it. Unlimited by default.
+    pub fn max_decompressed_size(mut self, bytes: u64) -> Self {
+        self.max_decompressed_size = Some(bytes);
+        self
+    }
+
+    /// Analyzes files as if `root` were mounted at `/`, e.g. for an extracted embedded rootfs.
+    /// The default directories, absolute `RPATH`/`RUNPATH` entries, `ld.so.cache`, `ld.so.conf`
+    /// and symlinks inside `root` are all interpreted relative to `root`, and so are the entries
+    /// toolchains write relative to the sysroot they link against, like `=/usr/lib` and
+    /// `$SYSROOT/usr/lib`. `LD_LIBRARY_PATH`, the custom search directories and the path of the
+    /// analyzed file itself are host paths.
+    pub fn sysroot<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        self.sysroot = Some(root.into());
+        self
+    }
+
+    /// Resolves the way NixOS and Guix System do, for programs in the store at `dir`, like
+    /// [`NIX_STORE_DIR`](crate::NIX_STORE_DIR) or [`GUIX_STORE_DIR`](crate::GUIX_STORE_DIR).
+    /// Their programs find every library through `RUNPATH`s into the store, so no system
+    /// directory is searched, as with [`ResolverConfig::without_default_dirs`], and libraries
+    /// in the store are reported with the [store path](crate::DependencyNode::store_path) of
+    /// the package they come from.
+    pub fn store<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.store = Some(dir.into());
+        self.default_dirs = false;
+        self.update_filesystem();
+        self
+    }
+
+    /// Reads the files of the [store](ResolverConfig::store) from the directory `root` instead
+    /// of the store directory itself, like the store of another machine mounted there. Paths
+    /// into the store, in `RUNPATH`s, symlinks and elsewhere, are followed into `root`, but
+    /// the libraries found are still reported at their paths in the store. Without a store
+    /// directory set, the Nix store is taken. Not needed with a [sysroot](Self::sysroot), whose
+    /// store is inside it.
+    pub fn store_root<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        if self.store.is_none() {
+            self = self.store(crate::NIX_STORE_DIR);
+        }
+        self.sysroot.get_or_insert_with(|| PathBuf::from("/"));
+        self.store_root = Some(root.into());
+        self.update_filesystem();
+        self
+    }
+
+    /// Whether to resolve dependencies the way ld.so does in secure-execution mode, as it does
+    /// for privileged programs: `LD_LIBRARY_PATH` is ignored, and `RPATH`/`RUNPATH` entries
+    /// using `$ORIGIN` or another token are only searched if they expand to a default directory.
+    /// By default, this mode is used for set-user-ID and set-group-ID files.
+    pub fn secure_execution(mut self, secure: bool) -> Self {
+        self.secure_execution = Some(secure);
+        self
+    }
+
+    /// Whether empty `LD_LIBRARY_PATH` entries, as in `:/opt/lib` or `/a::/b`, are searched as
+    /// the working directory, like glibc's ld.so does. Otherwise they are skipped, so they can
+    /// be reported by [`ElfFile::insecure_runpaths`](crate::ElfFile::insecure_runpaths)
+    /// without being predicted. Either way, an `LD_LIBRARY_PATH` that is empty altogether is
+    /// ignored, and musl skips empty entries. Defaults to `true`.
+    pub fn ld_library_path_empty_as_cwd(mut self, empty_as_cwd: bool) -> Self {
+        self.ld_library_path_empty_as_cwd = empty_as_cwd;
+        self
+    }
+
+    /// The working directory the analyzed program is taken to be started in, which relative
+    /// paths are searched in: `DT_NEEDED` names like `lib/libfoo.so` or `./libfoo.so`, which
+    /// ld.so opens as paths relative to it, and relative or empty search directories. Inside
+    /// the [sysroot](ResolverConfig::sysroot), if there is one. By default, relative paths are
+    /// left relative, and so are looked up in the working directory of this process.
+    pub fn working_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Whether to include the libraries listed in
+    /// [`LD_SO_PRELOAD_PATH`](crate::LD_SO_PRELOAD_PATH), which ld.so loads into every process,
+    /// as [preloads](crate::DependencyKind::Preload) of the analyzed file. Entries naming a
+    /// path are used as-is, while others are searched for like the file's own dependencies.
+    /// Defaults to `false`.
+    pub fn system_preload(mut self, system_preload: bool) -> Self {
+        self.system_preload = system_preload;
+        self
+    }
+
+    /// Takes the libraries whose soname matches one of `sonames` as always present, like the
+    /// glibc family bundling tools leave to the target system: they are neither searched for
+    /// nor are their own dependencies collected, and they are reported as
+    /// [ignored](crate::Resolved::Ignored). Sonames may contain the `*` and `?` wildcards,
+    /// as in `ld-linux*`. None are ignored by default.
+    pub fn ignore_sonames<I, S>(mut self, sonames: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignored_sonames = sonames.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Resolves each soname of `overrides` to the file at its path instead of searching for
+    /// it, e.g. to pin `libssl.so.3` to a vendored build, or to stand in for a driver library
+    /// missing on the analyzing machine. The file is still read and its own dependencies
+    /// collected, and the library is reported as found by
+    /// [`SearchRule::Override`](crate::SearchRule::Override). Paths are host paths, even with
+    /// a [sysroot](ResolverConfig::sysroot), and a file that does not exist or is built for
+    /// another architecture leaves the library not found. None are overridden by default.
+    pub fn soname_overrides<I, S, P>(mut self, overrides: I) -> Self
+    where
+        I: IntoIterator<Item = (S, P)>,
+        S: Into<OsString>,
+        P: Into<PathBuf>,
+    {
+        self.soname_overrides = overrides
+            .into_iter()
+            .map(|(soname, path)| (soname.into(), path.into()))
+            .collect();
+        self
+    }
+
+    /// Takes every shared library directly in each of `dirs`, like `libfoo.so` or
+    /// `foo.cpython-312-x86_64-linux-gnu.so`, as loaded by the analyzed file, for frameworks
+    /// that load whatever is in a directory with `dlopen`, like GStreamer, Qt or Python
+    /// extension directories. They are listed as [plugins](crate::DependencyKind::Plugin) of
+    /// the analyzed file after its own dependencies, by directory and then name, and their own
+    /// dependencies are collected like any others. Like absolute `RPATH` entries, `dirs` are
+    /// inside the sysroot. None by default.
+    pub fn plugin_dirs<I, P>(mut self, dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.plugin_dirs = dirs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Whether to take the libraries each object declares in its `.note.dlopen` notes, as
+    /// systemd and the libraries it uses do, as loaded by it. They are listed as
+    /// [`DependencyKind::Dlopen`](crate::DependencyKind::Dlopen) dependencies after its own,
+    /// and searched for like them. Of the alternatives a note lists for a feature, the first
+    /// that is found is taken, or the first if none is. `false` by default.
+    pub fn dlopen_notes(mut self, dlopen_notes: bool) -> Self {
+        self.dlopen_notes = dlopen_notes;
+        self
+    }
+
+    /// Uses `libs` instead of `LD_PRELOAD` from the process environment as the libraries to
+    /// [preload](crate::DependencyKind::Preload), even if [`ResolverConfig::use_env`] is
+    /// `false`. Like `LD_PRELOAD`, they are loaded before those in `ld.so.preload`, and entries
+    /// naming a path are used as-is. In secure-execution mode, those are ignored.
+    pub fn preload<I, S>(mut self, libs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.preload = Some(libs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the `glibc-hwcaps` subdirectories to probe, best first, e.g.
+    /// `["x86-64-v3", "x86-64-v2"]` to find the libraries ld.so would load on a CPU supporting
+    /// x86-64-v3. Like ld.so, every search directory `dir` is searched as
+    /// `dir/glibc-hwcaps/<subdir>` for each of them before `dir` itself. By default, the
+    /// subdirectories the host CPU supports are probed for files of the host's architecture, and
+    /// none for others.
+    pub fn hwcaps<I, S>(mut self, subdirs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.hwcaps = Some(subdirs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Probes the legacy hardware capability subdirectories glibc before 2.37 searches, for
+    /// the capability names `caps` in order, like `["tls", "haswell", "x86_64"]`: `tls` for
+    /// thread-local storage support, then the names of the CPU's capabilities and platform as
+    /// `LD_SHOW_AUXV` or `ld.so --list-diagnostics` show them. Every search directory `dir` is
+    /// then searched as `dir/tls/haswell/x86_64`, `dir/tls/haswell`, `dir/tls/x86_64`,
+    /// `dir/tls` and so on down to `dir/x86_64`, every combination of the names from most to
+    /// least specific, after its `glibc-hwcaps` subdirectories and before `dir` itself. Each
+    /// name doubles the directories searched. None by default, as current glibc doesn't probe
+    /// them.
+    pub fn legacy_hwcaps<I, S>(mut self, caps: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.legacy_hwcaps = caps.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Substitutes `lib` for `$LIB` in `RPATH`/`RUNPATH` entries. glibc fixes the value when it
+    /// is built, after the layout of the distribution: `lib/x86_64-linux-gnu` on Debian and its
+    /// derivatives, `lib64` on Fedora. By default, it is `lib/<triplet>` if the multiarch
+    /// directory of the file's architecture is in `/lib` or `/usr/lib` (in the
+    /// [sysroot](Self::sysroot)), and otherwise `lib64`, `libx32` or `lib` by the file's class.
+    pub fn lib_token<S: Into<String>>(mut self, lib: S) -> Self {
+        self.lib_token = Some(lib.into());
+        self
+    }
+
+    /// Emulates the dynamic linker of `platform`. By default, the platform is detected from the
+    /// `PT_INTERP` and OS/ABI of the analyzed file, or is the one this crate was compiled for if
+    /// the file has no interpreter, as for shared libraries.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = Some(platform);
+        self
+    }
+
+    /// Resolves as if on `target` instead of the host this crate was compiled for, e.g. to
+    /// audit the dependencies of binaries cross-compiled into a [sysroot](Self::sysroot). The
+    /// analyzed file must be built for the target's architecture, and the multiarch
+    /// directories searched are those of the target. Files with no `PT_INTERP` are assumed to
+    /// be loaded by the target's dynamic linker, as is
+    /// [`Resolver::resolve_soname`](crate::Resolver::resolve_soname), which only accepts
+    /// libraries for the target. Files naming another dynamic linker are still resolved the
+    /// way it would, unless [`ResolverConfig::platform`] says otherwise. Since the host CPU
+    /// does not matter then, no `glibc-hwcaps` subdirectories are probed unless they are set
+    /// with [`ResolverConfig::hwcaps`].
+    ///
+    /// ```
+    /// use elf_dynamic_lib_getter::{ResolverConfig, Target};
+    ///
+    /// let target = Target::from_triple("aarch64-unknown-linux-gnu")?;
+    /// let config = ResolverConfig::new().target(target).sysroot("/srv/aarch64-rootfs");
+    /// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+    /// ```
+    pub fn target(mut self, target: Target) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// What to do with libraries built for another operating system ABI than the analyzed
+    /// file. Defaults to [`OsAbiCheck::Reject`].
+    pub fn os_abi_check(mut self, check: OsAbiCheck) -> Self {
+        self.os_abi_check = check;
+        self
+    }
+
+    /// Whether to report libraries by their canonical path, with every symlink along it
+    /// followed, so that one reached as both `/lib64/libc.so.6` and `/usr/lib64/libc.so.6` is
+    /// only listed once. Like ld.so, the `$ORIGIN` of a library is still the directory it was
+    /// found in. Defaults to `false`, reporting each library by the path it was found at.
+    pub fn canonicalize_paths(mut self, canonicalize: bool) -> Self {
+        self.canonicalize = canonicalize;
+        self
+    }
+
+    /// Whether to keep searching for each library after it is found, recording every file by
+    /// its name in the directories searched for it in
+    /// [`DependencyNode::candidates`](crate::DependencyNode::candidates), to debug which of
+    /// several copies shadows the others. Libraries still resolve to the first that can be
+    /// loaded. Defaults to `false`, as it probes every directory for every library.
+    pub fn exhaustive_search(mut self, exhaustive: bool) -> Self {
+        self.exhaustive_search = exhaustive;
+        self
+    }
+
+    /// Whether to record every path probed for each library, and what was there, in
+    /// [`DependencyNode::search_trace`](crate::DependencyNode::search_trace), to find out why
+    /// resolution differs from what `LD_DEBUG=libs` shows at run time. See
+    /// [`DependencyGraph::to_search_trace`](crate::DependencyGraph::to_search_trace). Defaults
+    /// to `false`.
+    pub fn trace_search(mut self, trace: bool) -> Self {
+        self.trace_search = trace;
+        self
+    }
+
+    /// Whether to record the directories searched for the dependencies of each object, in
+    /// order, in [`DependencyNode::search_path`](crate::DependencyNode::search_path), to see
+    /// at a glance where its libraries may come from. See
+    /// [`DependencyGraph::to_search_paths`](crate::DependencyGraph::to_search_paths). Defaults
+    /// to `false`.
+    pub fn record_search_paths(mut self, record: bool) -> Self {
+        self.search_paths = record;
+        self
+    }
+
+    /// Whether to list the objects the kernel maps into every process, like the vDSO
+    /// (`linux-vdso.so.1` on most architectures), as [virtual](crate::DependencyKind::Virtual)
+    /// dependencies of the analyzed file, first, the way `ldd` does. They are never searched for,
+    /// and methods returning only paths leave them out. Only dynamically linked files analyzed
+    /// for Linux get them. Defaults to `false`.
+    pub fn virtual_objects(mut self, virtual_objects: bool) -> Self {
+        self.virtual_objects = virtual_objects;
+        self
+    }
+
+    /// Whether to list the program interpreter named by the `PT_INTERP` of the analyzed file,
+    /// such as `/lib64/ld-linux-x86-64.so.2`, as its last dependency, with the
+    /// [interpreter](crate::DependencyKind::Interpreter) kind, so that the closure holds every
+    /// file the process maps, as bundlers need. Inside the sysroot, it is looked for there.
+    /// Defaults to `false`, listing it only where a library names it in `DT_NEEDED`.
+    pub fn include_interpreter(mut self, include_interpreter: bool) -> Self {
+        self.include_interpreter = include_interpreter;
+        self
+    }
+
+    /// Digests the contents of the analyzed file and of each library found, recording it as
+    /// [`DependencyNode::digest`](crate::DependencyNode::digest) and
+    /// [`Dependency::digest`](crate::Dependency::digest), so the exact files a binary loads
+    /// can be pinned and verified later. Each file is read whole once. Not set by default.
+    pub fn content_digest(mut self, digest: ContentDigest) -> Self {
+        self.content_digest = Some(digest);
+        self
+    }
+
+    /// Whether to record the size, modification time, owner, group and permissions of the
+    /// analyzed file and of each library found as
+    /// [`DependencyNode::stat`](crate::DependencyNode::stat) and
+    /// [`Dependency::stat`](crate::Dependency::stat), as the search finds them, so reports
+    /// don't race with updates made after the analysis. Defaults to `false`.
+    pub fn stat_files(mut self, stat_files: bool) -> Self {
+        self.stat_files = stat_files;
+        self
+    }
+
+    /// Remembers what the files read say about their dependencies in `cache`, and takes it
+    /// from there for files that have not changed since, so repeated analyses of mostly
+    /// unchanged systems barely read any. Files read through a
+    /// [filesystem](ResolverConfig::filesystem) are not cached. Not set by default.
+    pub fn disk_cache(mut self, cache: Arc<DiskCache>) -> Self {
+        self.disk_cache = Some(cache);
+        self
+    }
+
+    /// Reads every file from `fs` instead of the host's, including the analyzed file itself,
+    /// `ld.so.cache`, `ld.so.conf` and the libraries in each search directory. Without a
+    /// [sysroot](ResolverConfig::sysroot), the root of `fs` is taken as one, so symlinks are
+    /// followed within `fs` and found paths are reported as `fs` names them; with one, it
+    /// is a directory of `fs`. The host's files are read by default, as with [`HostFs`].
+    pub fn filesystem(mut self, fs: Arc<dyn FileSystem>) -> Self {
+        self.sysroot.get_or_insert_with(|| PathBuf::from("/"));
+        self.given_filesystem = Some(fs);
+        self.update_filesystem();
+        self
+    }
+
+    /// Sets the files read to those [given](ResolverConfig::filesystem), with the store read
+    /// from its [root](ResolverConfig::store_root).
+    fn update_filesystem(&mut self) {
+        self.filesystem = match (&self.store, &self.store_root) {
+            (Some(dir), Some(root)) => {
+                let inner = self
+                    .given_filesystem
+                    .clone()
+                    .unwrap_or_else(|| Arc::new(HostFs));
+                Some(Arc::new(MountFs::over(inner).mount(dir, root)))
+            }
+            _ => self.given_filesystem.clone(),
+        };
+    }
+
+    /// The value of the environment variable `name` the analyzed file would see, or `None` if
+    /// the environment is not used.
+    pub(crate) fn env_var(&self, name: &str) -> Option<OsString> {
+        if !self.use_env {
+            return None;
+        }
+        match &self.environment {
+            Some(vars) => vars.get(OsStr::new(name)).cloned(),
+            None => env::var_os(name),
+        }
+    }
+
+    /// Whether collecting dependencies is to stop, by the [cancel flag](Self::cancel_flag) or
+    /// the [deadline](Self::deadline).
+    pub(crate) fn is_interrupted(&self) -> bool {
+        let cancelled = self.cancel_flag.as_ref();
+        cancelled.is_some_and(|flag| flag.load(Ordering::Relaxed))
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Fails with [`Error::LimitExceeded`] if the file at `path`, `len` bytes long, is larger
+    /// than the [maximum file size](Self::max_file_size).
+    pub(crate) fn check_file_size(&self, path: &Path, len: u64) -> Result<(), Error> {
+        match self.max_file_size {
+            Some(max) if len > max => Err(Error::LimitExceeded {
+                path: path.to_owned(),
+                limit: ResourceLimit::FileSize(max),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// The [maximum decompressed size](Self::max_decompressed_size), or [`u64::MAX`].
+    pub(crate) fn decompressed_size_limit(&self) -> u64 {
+        self.max_decompressed_size.unwrap_or(u64::MAX)
+    }
+
+    /// The error for `source`, from reading the file at `path`: [`Error::LimitExceeded`] if it
+    /// is the [`io::ErrorKind::FileTooLarge`] error of a stream decompressing past the
+    /// [maximum decompressed size](Self::max_decompressed_size), and `other(source)`
+    /// otherwise.
+    pub(crate) fn decompression_error(
+        &self,
+        path: &Path,
+        source: io::Error,
+        other: impl FnOnce(io::Error) -> Error,
+    ) -> Error {
+        match self.max_decompressed_size {
+            Some(max) if source.kind() == io::ErrorKind::FileTooLarge => Error::LimitExceeded {
+                path: path.to_owned(),
+                limit: ResourceLimit::DecompressedSize(max),
+            },
+            _ => other(source),
+        }
+    }
+
+    /// Whether the library `soname` is one of the [ignored](ResolverConfig::ignore_sonames).
+    pub(crate) fn is_ignored(&self, soname: &OsStr) -> bool {
+        self.ignored_sonames
+            .iter()
+            .any(|pattern| wildcard_match(pattern.as_bytes(), soname.as_encoded_bytes()))
+    }
+
+    /// The files dependencies are resolved in.
+    pub(crate) fn fs(&self) -> &dyn FileSystem {
+        self.filesystem.as_deref().unwrap_or(&HostFs)
+    }
+
+    /// Whether objects `depth` levels below the analyzed file are at the maximum depth.
+    pub(crate) fn is_max_depth(&self, depth: usize) -> bool {
+        self.max_depth.is_some_and(|max_depth| depth >= max_depth)
+    }
+
+    /// Whether the dependencies of the library `soname`, found at `path` `depth` levels below
+    /// the analyzed file, are left out, at the maximum depth or by the traversal filter.
+    pub(crate) fn stops_at(&self, soname: &str, path: &Path, depth: usize) -> bool {
+        let skip = |filter: &TraversalFilter| {
+            filter.0(soname, path, depth) == TraversalDecision::SkipSubtree
+        };
+        self.is_max_depth(depth) || (depth > 0 && self.traversal_filter.as_ref().is_some_and(skip))
+    }
+
+    /// The path `path` is reported and deduplicated by: its canonical path with
+    /// [`ResolverConfig::canonicalize_paths`], which inside the sysroot stays inside it, or
+    /// `path` itself.
+    pub(crate) fn identity(&self, path: &Path) -> PathBuf {
+        if !self.canonicalize {
+            return path.to_owned();
+        }
+        self.canonical(path)
+    }
+
+    /// The canonical path of `path`, which inside the sysroot stays inside it, or `path` itself
+    /// if it has none.
+    pub(crate) fn canonical(&self, path: &Path) -> PathBuf {
+        let canonical = match &self.sysroot {
+            Some(root) if path.starts_with(root) => sysroot::resolve_in(self.fs(), root, path),
+            _ if self.filesystem.is_some() => sysroot::resolve_in(self.fs(), Path::new("/"), path),
+            _ => fs::canonicalize(path).ok(),
+        };
+        canonical.unwrap_or_else(|| path.to_owned())
+    }
+
+    /// The symlinks followed from `path` to the library it refers to, see
+    /// [`DependencyNode::links`](crate::DependencyNode::links).
+    pub(crate) fn symlink_chain(&self, path: &Path) -> Vec<PathBuf> {
+        let root = self
+            .sysroot
+            .as_deref()
+            .filter(|root| path.starts_with(root));
+        sysroot::symlink_chain(self.fs(), root, path)
+    }
+
+    /// The relative path `path` in the [working directory](ResolverConfig::working_dir), if
+    /// there is one, or `path` itself.
+    pub(crate) fn in_working_dir(&self, path: &Path) -> PathBuf {
+        match &self.working_dir {
+            Some(dir) if path.is_relative() => self.rebase(dir).join(path),
+            _ => path.to_owned(),
+        }
+    }
+
+    /// The host path of the target's configuration file at the absolute path `path`, like
+    /// [`LD_SO_CACHE_PATH`](crate::LD_SO_CACHE_PATH): inside the sysroot, if there is one, with
+    /// symlinks along it followed there rather than on the host.
+    pub(crate) fn system_file(&self, path: &Path) -> PathBuf {
+        let path = self.rebase(path);
+        match &self.sysroot {
+            Some(root) => sysroot::resolve_in(self.fs(), root, &path).unwrap_or(path),
+            None => path,
+        }
+    }
+
+    /// Interprets the absolute path `path` relative to the sysroot, if there is one.
+    pub(crate) fn rebase(&self, path: &Path) -> PathBuf {
+        match &self.sysroot {
+            Some(root) => sysroot::rebase(root, path),
+            None => path.to_owned(),
+        }
+    }
+
+    /// The [store path](crate::DependencyNode::store_path) the file at the host path `path`
+    /// lies in, if it is in the [store](ResolverConfig::store), through its root or the
+    /// sysroot or not.
+    pub(crate) fn store_path(&self, path: &Path) -> Option<StorePath> {
+        let dir = self.store.as_deref()?;
+        let moved = |from: &Path, to: &Path| Some(to.join(path.strip_prefix(from).ok()?));
+        StorePath::parse(dir, path)
+            .or_else(|| StorePath::parse(dir, moved(self.store_root.as_deref()?, dir)?))
+            .or_else(|| StorePath::parse(dir, moved(self.sysroot.as_deref()?, Path::new("/"))?))
+    }
+}
@@ -0,0 +1,357 @@
+//! The shared objects a crashed process had mapped, from the notes of its core dump.
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::abi::{ET_CORE, NT_GNU_BUILD_ID, PT_LOAD, PT_NOTE};
+use crate::object::{self, Backend, DefaultBackend, FileHeader, Object, ProgramHeader};
+use crate::os_str::OsStrExt;
+use crate::parse::{hex, parse_error};
+use crate::process::DELETED_SUFFIX;
+use crate::Error;
+
+/// The owner of the notes the kernel writes into core dumps.
+const NOTE_OWNER: &str = "CORE";
+/// The type of the note listing the files mapped into the process.
+const NT_FILE: u64 = 0x4649_4c45;
+/// The type of the note holding the auxiliary vector of the process.
+const NT_AUXV: u64 = 6;
+/// The auxiliary vector entry holding the entry point of the executable.
+const AT_ENTRY: u64 = 9;
+
+/// A range of addresses a file was mapped at in a crashed process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MappedRange {
+    /// The first address of the range.
+    pub start: u64,
+    /// The address after the last one of the range.
+    pub end: u64,
+    /// The offset in the file that is mapped at `start`.
+    pub offset: u64,
+}
+
+impl MappedRange {
+    /// Whether `address` is in this range.
+    pub fn contains(&self, address: u64) -> bool {
+        (self.start..self.end).contains(&address)
+    }
+}
+
+/// A shared object mapped into a crashed process, as listed by
+/// [`CoreFile::mapped_libraries`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoreLibrary {
+    /// The path the object was mapped from, without the ` (deleted)` suffix.
+    pub path: PathBuf,
+    /// Whether the file was deleted or replaced after being mapped.
+    pub deleted: bool,
+    /// The ranges the object was mapped at, from the lowest address.
+    pub ranges: Vec<MappedRange>,
+    /// The GNU build ID of the object, as a hex string, if its headers were dumped into the
+    /// core, as they are by default (see `coredump_filter` in core(5)). It names the exact
+    /// build to find symbols for.
+    pub build_id: Option<String>,
+}
+
+/// Represents an ELF core dump on disk, as the kernel writes when a process crashes, and lists
+/// the files the process had mapped from its `NT_FILE` note. The files need not exist anymore,
+/// or on this machine: only the core is read.
+pub struct CoreFile {
+    path: PathBuf,
+}
+
+impl CoreFile {
+    /// Creates a [`CoreFile`] instance from [`AsRef<Path>`]
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_owned();
+        CoreFile { path }
+    }
+
+    /// The executable of the crashed process, which is the file mapped at its entry point, or
+    /// `None` if the core doesn't record it.
+    /// # Return Value [Err]
+    /// Like [`CoreFile::mapped_libraries`].
+    pub fn executable(&self) -> Result<Option<PathBuf>, Error> {
+        let (mut files, executable) = self.parse()?;
+        Ok(executable.map(|index| files.swap_remove(index).path))
+    }
+
+    /// The shared objects the crashed process had mapped, in the order of their lowest
+    /// addresses, and the ranges they were mapped at. The executable is left out, and so are
+    /// mapped files that are not ELF files, like locale archives. A file counts as a shared
+    /// object if the core holds an ELF header at its start or, if its start was not dumped,
+    /// if it is named like one.
+    /// # Return Value [Err]
+    /// Returns [`Error::InvalidCore`] if the file is not a core dump or has no `NT_FILE`
+    /// note, as those written by kernels before Linux 3.7.
+    pub fn mapped_libraries(&self) -> Result<Vec<CoreLibrary>, Error> {
+        let (mut files, executable) = self.parse()?;
+        if let Some(index) = executable {
+            files.remove(index);
+        }
+        Ok(files)
+    }
+
+    /// Every shared object the process had mapped, and the index of its executable among them.
+    fn parse(&self) -> Result<(Vec<CoreLibrary>, Option<usize>), Error> {
+        let invalid = || Error::InvalidCore {
+            path: self.path.clone(),
+        };
+        let parse_error = |source| parse_error(&self.path, source);
+        let data = fs::read(&self.path).map_err(|source| Error::Io {
+            path: self.path.clone(),
+            source,
+        })?;
+        let mut elf = Object::open(&data[..]).map_err(parse_error)?;
+        if elf.header.e_type != ET_CORE {
+            return Err(invalid());
+        }
+        let (header, segments) = (elf.header, elf.segments.clone());
+        let core = Core {
+            data: &data,
+            header: &header,
+            segments: &segments,
+        };
+        let (mut files, mut entry) = (None, None);
+        for phdr in segments.iter().filter(|phdr| phdr.p_type == PT_NOTE) {
+            let notes = elf.segment_data(phdr).map_err(parse_error)?;
+            for note in DefaultBackend::notes(core.header, phdr.p_align, &notes) {
+                if note.name != NOTE_OWNER.as_bytes() {
+                    continue;
+                }
+                match note.n_type {
+                    NT_FILE => files = core.files(note.desc),
+                    NT_AUXV => entry = core.entry(note.desc),
+                    _ => {}
+                }
+            }
+        }
+        let mut files = files.ok_or_else(invalid)?;
+        let is_executable = |file: &CoreLibrary| {
+            let contains = |range: &MappedRange| entry.is_some_and(|entry| range.contains(entry));
+            file.ranges.iter().any(contains)
+        };
+        files.retain(|file| is_executable(file) || core.is_shared_object(file));
+        let executable = files.iter().position(is_executable);
+        Ok((files, executable))
+    }
+}
+
+/// The contents of a core dump, with its memory.
+struct Core<'a> {
+    data: &'a [u8],
+    header: &'a FileHeader,
+    segments: &'a [ProgramHeader],
+}
+
+impl Core<'_> {
+    /// Reads a word of the process at `*pos` in `data`, and moves past it.
+    fn word(&self, data: &[u8], pos: &mut usize) -> Option<u64> {
+        self.header.read_word(data, pos)
+    }
+
+    /// The files listed in the `NT_FILE` note `desc`: its number of files and page size, the
+    /// range and page offset of each, and then their NUL-terminated paths. Ranges of the same
+    /// file are merged into one [`CoreLibrary`].
+    fn files(&self, desc: &[u8]) -> Option<Vec<CoreLibrary>> {
+        let mut pos = 0;
+        let count = self.word(desc, &mut pos)?;
+        let page_size = self.word(desc, &mut pos)?;
+        let mut ranges = Vec::new();
+        for _ in 0..count {
+            let start = self.word(desc, &mut pos)?;
+            let end = self.word(desc, &mut pos)?;
+            let offset = self.word(desc, &mut pos)?.checked_mul(page_size)?;
+            ranges.push(MappedRange { start, end, offset });
+        }
+        let mut names = desc.get(pos..)?.split(|&byte| byte == 0);
+        let mut files: Vec<CoreLibrary> = Vec::new();
+        for range in ranges {
+            let name = names.next()?;
+            let (name, deleted) = match name.strip_suffix(DELETED_SUFFIX) {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+            let path = Path::new(OsStr::from_bytes(name));
+            let seen = files
+                .iter_mut()
+                .find(|file| file.path == path && file.deleted == deleted);
+            match seen {
+                Some(file) => file.ranges.push(range),
+                None => files.push(CoreLibrary {
+                    path: path.to_owned(),
+                    deleted,
+                    ranges: vec![range],
+                    build_id: None,
+                }),
+            }
+        }
+        for file in &mut files {
+            file.ranges.sort_by_key(|range| range.start);
+            file.build_id = self.build_id(file);
+        }
+        files.sort_by_key(|file| file.ranges[0].start);
+        Some(files)
+    }
+
+    /// The entry point of the executable, from the auxiliary vector in the `NT_AUXV` note
+    /// `desc`.
+    fn entry(&self, desc: &[u8]) -> Option<u64> {
+        let mut pos = 0;
+        loop {
+            let key = self.word(desc, &mut pos)?;
+            let value = self.word(desc, &mut pos)?;
+            if key == AT_ENTRY {
+                return Some(value);
+            }
+        }
+    }
+
+    /// The `len` bytes of memory of the process at `address`, if they were dumped.
+    fn memory(&self, address: u64, len: u64) -> Option<&[u8]> {
+        let phdr = self.segments.iter().find(|phdr| {
+            phdr.p_type == PT_LOAD
+                && address >= phdr.p_vaddr
+                && address
+                    .checked_add(len)
+                    .is_some_and(|end| end <= phdr.p_vaddr + phdr.p_filesz)
+        })?;
+        let start = usize::try_from(phdr.p_offset + (address - phdr.p_vaddr)).ok()?;
+        self.data.get(start..start + usize::try_from(len).ok()?)
+    }
+
+    /// The start of `file` in memory, and how many of its bytes are mapped there.
+    fn file_start(&self, file: &CoreLibrary) -> Option<(u64, u64)> {
+        let range = file.ranges.iter().find(|range| range.offset == 0)?;
+        Some((range.start, range.end - range.start))
+    }
+
+    /// Whether the start of `file` holds an ELF header or, if it was not dumped, whether it is
+    /// named like a shared object.
+    fn is_shared_object(&self, file: &CoreLibrary) -> bool {
+        let start = self.file_start(file);
+        match start.and_then(|(start, _)| self.memory(start, 4)) {
+            Some(magic) => magic == b"\x7fELF",
+            None => {
+                let name = file.path.file_name().unwrap_or_default().as_bytes();
+                name.ends_with(b".so") || name.windows(4).any(|window| window == b".so.")
+            }
+        }
+    }
+
+    /// The build ID in the `PT_NOTE` segments of the ELF headers at the start of `file` in
+    /// memory.
+    fn build_id(&self, file: &CoreLibrary) -> Option<String> {
+        let (start, len) = self.file_start(file)?;
+        let mut header = self.memory(start, len.min(object::MAX_HEADER_SIZE))?;
+        let header = object::file_header(&mut header).ok()?;
+        let table_size = u64::from(header.e_phnum) * u64::from(header.e_phentsize);
+        if header.e_phoff + table_size > len {
+            return None;
+        }
+        let table = self.memory(start + header.e_phoff, table_size)?;
+        let segments: Vec<ProgramHeader> = DefaultBackend::program_headers(&header, table).ok()?;
+        segments
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_NOTE && phdr.p_offset + phdr.p_filesz <= len)
+            .find_map(|phdr| {
+                let notes = self.memory(start + phdr.p_offset, phdr.p_filesz)?;
+                let notes = DefaultBackend::notes(&header, phdr.p_align.max(4), notes);
+                let mut notes = notes.into_iter();
+                notes.find_map(|note| {
+                    let is_build_id = note.n_type == NT_GNU_BUILD_ID && note.name == b"GNU";
+                    is_build_id.then(|| hex(note.desc))
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CoreFile, MappedRange};
+    use crate::test_util::{core, write_file, ElfBuilder};
+    use crate::Error;
+    use std::path::Path;
+
+    #[test]
+    fn test_mapped_libraries() {
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new().build_id(&[0xab, 0xcd, 0xef]).build();
+        let app = ElfBuilder::new().build();
+        let files = [
+            (0x1000, 0x2000, 0, "/usr/bin/app"),
+            (
+                0x7f00_0000_2000,
+                0x7f00_0000_4000,
+                1,
+                "/usr/lib/libfoo.so.1",
+            ),
+            (
+                0x7f00_0000_0000,
+                0x7f00_0000_2000,
+                0,
+                "/usr/lib/libfoo.so.1",
+            ),
+            (
+                0x7f00_0001_0000,
+                0x7f00_0001_1000,
+                0,
+                "/usr/lib/locale/locale-archive",
+            ),
+            (
+                0x7f00_0002_0000,
+                0x7f00_0002_1000,
+                0,
+                "/opt/libgone.so (deleted)",
+            ),
+            (
+                0x7f00_0003_0000,
+                0x7f00_0003_1000,
+                0,
+                "/opt/data.bin (deleted)",
+            ),
+        ];
+        let memory: [(u64, &[u8]); 3] = [
+            (0x1000, &app),
+            (0x7f00_0000_0000, &libfoo),
+            (0x7f00_0001_0000, b"locale data"),
+        ];
+        let path = write_file(dir.path(), "core", &core(&files, &memory, 0x1800));
+        let core = CoreFile::new(&path);
+        assert_eq!(
+            core.executable().unwrap().unwrap(),
+            Path::new("/usr/bin/app")
+        );
+
+        // The ranges of a file are merged, and files that are not shared objects left out
+        let libraries = core.mapped_libraries().unwrap();
+        assert_eq!(libraries.len(), 2);
+        assert_eq!(libraries[0].path, Path::new("/usr/lib/libfoo.so.1"));
+        let ranges = [
+            MappedRange {
+                start: 0x7f00_0000_0000,
+                end: 0x7f00_0000_2000,
+                offset: 0,
+            },
+            MappedRange {
+                start: 0x7f00_0000_2000,
+                end: 0x7f00_0000_4000,
+                offset: 4096,
+            },
+        ];
+        assert_eq!(libraries[0].ranges, ranges);
+        assert_eq!(libraries[0].build_id.as_deref(), Some("abcdef"));
+        assert!(!libraries[0].deleted);
+        // Its start wasn't dumped, but it is named like a shared object
+        assert_eq!(libraries[1].path, Path::new("/opt/libgone.so"));
+        assert!(libraries[1].deleted);
+        assert_eq!(libraries[1].build_id, None);
+
+        let error = CoreFile::new(write_file(dir.path(), "libfoo.so", &libfoo))
+            .mapped_libraries()
+            .unwrap_err();
+        assert!(matches!(error, Error::InvalidCore { .. }));
+    }
+}
@@ -0,0 +1,185 @@
+//! A reader of the entries of cpio archives in the `newc` format, which rpm packages carry their
+//! files in.
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::archive_fs::too_large;
+use crate::os_str::OsStrExt;
+use crate::tar::{self, Entry, EntryKind};
+
+const HEADER_SIZE: u64 = 110;
+/// The name of the entry that ends the archive.
+const TRAILER: &[u8] = b"TRAILER!!!";
+
+/// The entries of the cpio archive `reader` reads, from `start` up to `end`, described as the
+/// entries of a tar archive would be. The links to a file with several all have its contents.
+/// Returns an [`io::ErrorKind::InvalidData`] error if the headers are malformed, or an
+/// [`io::ErrorKind::FileTooLarge`] error if a symlink target is over `limit` bytes.
+pub(crate) fn entries<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    limit: u64,
+) -> io::Result<Vec<Entry>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed cpio header");
+    // Fields and contents start at multiples of four bytes from the start of the archive
+    let align = |pos: u64| start + (pos - start).next_multiple_of(4);
+    let mut entries = Vec::new();
+    // The contents of a file with several links are stored with only one of them
+    let mut links: HashMap<(u64, u64, u64), Vec<usize>> = HashMap::new();
+    let mut contents = HashMap::new();
+    let mut pos = start;
+    loop {
+        if pos + HEADER_SIZE > end {
+            return Err(invalid());
+        }
+        let mut header = [0; HEADER_SIZE as usize];
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut header)?;
+        if !matches!(&header[..6], b"070701" | b"070702") {
+            return Err(invalid());
+        }
+        let mut fields = [0; 13];
+        for (i, field) in fields.iter_mut().enumerate() {
+            let digits = std::str::from_utf8(&header[6 + 8 * i..14 + 8 * i]).ok();
+            let value = digits.and_then(|digits| u64::from_str_radix(digits, 16).ok());
+            *field = value.ok_or_else(invalid)?;
+        }
+        let [ino, mode, _, _, nlink, _, size, dev_major, dev_minor, _, _, name_size, _] = fields;
+        let offset = align(pos + HEADER_SIZE + name_size);
+        if name_size == 0
+            || offset
+                .checked_add(size)
+                .is_none_or(|data_end| data_end > end)
+        {
+            return Err(invalid());
+        }
+        let mut name = vec![0; name_size as usize];
+        reader.seek(SeekFrom::Start(pos + HEADER_SIZE))?;
+        reader.read_exact(&mut name)?;
+        let name = name.strip_suffix(b"\0").ok_or_else(invalid)?;
+        if name == TRAILER {
+            break;
+        }
+        pos = align(offset + size);
+        let kind = match mode & 0o170000 {
+            0o100000 => EntryKind::File,
+            0o040000 => EntryKind::Dir,
+            0o120000 => {
+                if size > limit {
+                    return Err(too_large("cpio symlink target", limit));
+                }
+                let mut target = vec![0; size as usize];
+                reader.seek(SeekFrom::Start(offset))?;
+                reader.read_exact(&mut target)?;
+                EntryKind::Symlink(PathBuf::from(OsStr::from_bytes(&target)))
+            }
+            _ => EntryKind::Other,
+        };
+        if kind == EntryKind::File && nlink > 1 {
+            let inode = (dev_major, dev_minor, ino);
+            links.entry(inode).or_default().push(entries.len());
+            if size > 0 {
+                contents.insert(inode, (offset, size));
+            }
+        }
+        let size = if kind == EntryKind::File { size } else { 0 };
+        entries.push(Entry {
+            path: tar::normalize(name),
+            kind,
+            mode: mode as u32 & 0o7777,
+            offset,
+            size,
+        });
+    }
+    for (inode, (offset, size)) in contents {
+        for &link in &links[&inode] {
+            entries[link].offset = offset;
+            entries[link].size = size;
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::entries;
+    use crate::tar::EntryKind;
+    use crate::test_util::{cpio, ArchiveEntry};
+    use std::io::{self, Cursor};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_entries() {
+        let archive = cpio(&[
+            ArchiveEntry::Dir("usr/lib64"),
+            ArchiveEntry::File("usr/lib64/libfoo.so.1.0", b"foo"),
+            ArchiveEntry::Symlink("usr/lib64/libfoo.so.1", "libfoo.so.1.0"),
+            ArchiveEntry::HardLink("usr/lib64/libfoo-copy.so.1", "usr/lib64/libfoo.so.1.0"),
+        ]);
+        let listed = entries(
+            &mut Cursor::new(&archive),
+            0,
+            archive.len() as u64,
+            u64::MAX,
+        )
+        .unwrap();
+        let summary: Vec<_> = listed
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.kind.clone(), entry.mode))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (PathBuf::from("usr/lib64"), EntryKind::Dir, 0o755),
+                (
+                    PathBuf::from("usr/lib64/libfoo.so.1.0"),
+                    EntryKind::File,
+                    0o755
+                ),
+                (
+                    PathBuf::from("usr/lib64/libfoo.so.1"),
+                    EntryKind::Symlink(PathBuf::from("libfoo.so.1.0")),
+                    0o777
+                ),
+                (
+                    PathBuf::from("usr/lib64/libfoo-copy.so.1"),
+                    EntryKind::File,
+                    0o755
+                ),
+            ]
+        );
+        // Both links to the file have its contents
+        for entry in [&listed[1], &listed[3]] {
+            assert_eq!(
+                &archive[entry.offset as usize..][..entry.size as usize],
+                b"foo"
+            );
+        }
+
+        // The archive must end with its trailer
+        let truncated = &archive[..archive.len() - 4];
+        assert!(entries(
+            &mut Cursor::new(truncated),
+            0,
+            truncated.len() as u64,
+            u64::MAX
+        )
+        .is_err());
+        let mut corrupt = archive.clone();
+        corrupt[0] = b'1';
+        assert!(entries(
+            &mut Cursor::new(&corrupt),
+            0,
+            corrupt.len() as u64,
+            u64::MAX
+        )
+        .is_err());
+        // Symlink targets are only read up to the limit
+        let end = archive.len() as u64;
+        let error = entries(&mut Cursor::new(&archive), 0, end, 4).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::FileTooLarge);
+    }
+}
@@ -0,0 +1,117 @@
+//! CSV exports of dependency closures, for sharing them as spreadsheets.
+use std::borrow::Cow;
+use std::path::Path;
+
+use crate::{DependencyGraph, SearchRule};
+
+/// The columns of [`ElfFile::to_csv`](crate::ElfFile::to_csv).
+const HEADER: [&str; 6] = ["root", "soname", "path", "found_by", "size", "digest"];
+
+/// Renders one row per resolved library of `graph` in load order, after a header row, with
+/// the size of each file from `size`. Unresolved and virtual objects, which are not files, are
+/// left out.
+pub(crate) fn to_csv(graph: &DependencyGraph, size: impl Fn(&Path) -> Option<u64>) -> String {
+    let root = graph.node(graph.root());
+    let root = match root.path() {
+        Some(path) => path.to_string_lossy(),
+        None => Cow::Borrowed(root.soname.as_str()),
+    };
+    let mut csv = String::new();
+    push_row(&mut csv, HEADER);
+    for id in graph.load_order().into_iter().skip(1) {
+        let node = graph.node(id);
+        let Some(path) = node.path() else {
+            continue;
+        };
+        let size = size(path).map(|size| size.to_string()).unwrap_or_default();
+        push_row(
+            &mut csv,
+            [
+                &root,
+                &node.soname,
+                &path.to_string_lossy(),
+                node.found_by.as_ref().map_or("", rule_name),
+                &size,
+                node.digest.as_deref().unwrap_or_default(),
+            ],
+        );
+    }
+    csv
+}
+
+/// The name of `rule` in the `found_by` column.
+fn rule_name(rule: &SearchRule) -> &'static str {
+    match rule {
+        SearchRule::Path => "path",
+        SearchRule::Rpath { .. } => "rpath",
+        SearchRule::LdLibraryPath => "ld_library_path",
+        SearchRule::Runpath { .. } => "runpath",
+        SearchRule::SearchDir => "search_dir",
+        SearchRule::Cache => "cache",
+        SearchRule::ConfiguredDir => "configured_dir",
+        SearchRule::DefaultDir => "default_dir",
+        SearchRule::Override => "override",
+    }
+}
+
+/// Appends the record `fields` to `csv`, ended with CRLF as RFC 4180 has it.
+fn push_row(csv: &mut String, fields: [&str; 6]) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            csv.push(',');
+        }
+        csv.push_str(&escape(field));
+    }
+    csv.push_str("\r\n");
+}
+
+/// Quotes `field` if it has a comma, quote or line break, doubling its quotes.
+fn escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_csv;
+    use crate::{
+        DependencyGraph, DependencyKind, DependencyNode, ResolveError, Resolved, SearchRule,
+    };
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_to_csv() {
+        let path = |path: &str| Ok(Resolved::Found(PathBuf::from(path)));
+        let mut graph = DependencyGraph::new(DependencyNode::new("app", path("/opt/app")));
+        let root = graph.root();
+        let mut foo = DependencyNode::new("libfoo.so.1", path("/opt/lib/libfoo, \"new\".so.1"));
+        foo.found_by = Some(SearchRule::Runpath {
+            owner: PathBuf::from("/opt/app"),
+        });
+        foo.digest = Some("ab12".to_owned());
+        let foo = graph.add_node(foo);
+        let mut libc = DependencyNode::new("libc.so.6", path("/lib/libc.so.6"));
+        libc.found_by = Some(SearchRule::Cache);
+        let libc = graph.add_node(libc);
+        let not_found = ResolveError::NotFound {
+            needed_by: PathBuf::from("/opt/app"),
+        };
+        let missing = graph.add_node(DependencyNode::new("libmissing.so", Err(not_found)));
+        let mut vdso = DependencyNode::new("linux-vdso.so.1", Ok(Resolved::Virtual));
+        vdso.kind = DependencyKind::Virtual;
+        let vdso = graph.add_node(vdso);
+        for id in [vdso, foo, missing, libc] {
+            graph.add_edge(root, id);
+        }
+        graph.add_edge(foo, libc);
+
+        let size = |path: &Path| (path == Path::new("/lib/libc.so.6")).then_some(2048);
+        let expected = "root,soname,path,found_by,size,digest\r\n\
+            /opt/app,libfoo.so.1,\"/opt/lib/libfoo, \"\"new\"\".so.1\",runpath,,ab12\r\n\
+            /opt/app,libc.so.6,/lib/libc.so.6,cache,2048,\r\n";
+        assert_eq!(to_csv(&graph, size), expected);
+    }
+}
@@ -0,0 +1,456 @@
+//! A long-running server answering resolution queries over a Unix socket with JSON-RPC, so
+//! that build pipelines asking about thousands of files share one warm [`Resolver`].
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use serde_json::Value;
+
+use crate::{ElfFile, Error, Resolver, ResolverConfig};
+
+/// The JSON-RPC code of a request that is not JSON.
+const PARSE_ERROR: i64 = -32700;
+/// The JSON-RPC code of a request that is not a request object.
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The JSON-RPC code of a request whose answer can't be serialized, as when it has a path
+/// that is not UTF-8.
+const INTERNAL_ERROR: i64 = -32603;
+/// The code of a request that was understood, but whose analysis failed, with the [`Error`]
+/// as its data.
+const ANALYSIS_FAILED: i64 = -32000;
+
+/// The default of [`Daemon::max_request_len`], 1 MiB.
+const DEFAULT_MAX_REQUEST_LEN: usize = 1 << 20;
+
+/// A server answering JSON-RPC 2.0 requests on a Unix socket, one per line, with one response
+/// per line in the same order. Clients are answered concurrently, and every one of them
+/// shares the same [`Resolver`], so each file is parsed once for all of them, until it
+/// changes. The methods are:
+///
+/// - `resolve`, with the param `soname`: the path the library would be found at, as with
+///   [`Resolver::resolve_soname`].
+/// - `closure`, with the param `path`: the [`DependencyGraph`](crate::DependencyGraph) of
+///   the file there.
+/// - `diff`, with the params `old` and `new`: the [`ClosureDiff`](crate::ClosureDiff) between
+///   the closures of the files at those paths.
+/// - `invalidate`, with the param `path`: [`Resolver::invalidate`]s the file there.
+/// - `metrics`: the [`ResolutionMetrics`](crate::ResolutionMetrics) of the resolver.
+///
+/// A failed analysis is answered with an error of code -32000, with the [`Error`] as its
+/// data, and one whose answer can't be serialized with an error of code -32603. Notifications, requests without an `id`, are carried out but not answered. A client
+/// sending a line longer than [`Daemon::max_request_len`] is answered with an error and hung
+/// up on.
+///
+/// ```no_run
+/// use elf_dynamic_lib_getter::{Daemon, ResolverConfig};
+///
+/// let daemon = Daemon::bind("/run/dyn-lib-finder.sock", ResolverConfig::new()).unwrap();
+/// daemon.run().unwrap();
+/// // echo '{"jsonrpc":"2.0","id":1,"method":"closure","params":{"path":"/usr/bin/ls"}}' |
+/// //     socat - UNIX-CONNECT:/run/dyn-lib-finder.sock
+/// ```
+#[derive(Debug)]
+pub struct Daemon {
+    listener: UnixListener,
+    resolver: Arc<Resolver>,
+    max_request_len: usize,
+}
+
+/// A line of a client, whose `id`, if it has one, is read before it is parsed as one.
+#[derive(serde::Deserialize)]
+struct Request {
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// A line answering a [`Request`].
+#[derive(serde::Serialize)]
+struct Response<'a> {
+    jsonrpc: &'static str,
+    id: &'a Value,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+/// What became of a [`Request`], under the key `result` or `error`.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Result(Value),
+    Error(RpcError),
+}
+
+#[derive(serde::Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+#[derive(serde::Deserialize)]
+struct SonameParams {
+    soname: String,
+}
+
+#[derive(serde::Deserialize)]
+struct PathParams {
+    path: PathBuf,
+}
+
+#[derive(serde::Deserialize)]
+struct DiffParams {
+    old: PathBuf,
+    new: PathBuf,
+}
+
+impl Daemon {
+    /// A server listening on a new socket at `path`, resolving with `config`. Fails if a file
+    /// is already there, like the socket of a server that did not remove it.
+    pub fn bind<P: AsRef<Path>>(path: P, config: ResolverConfig) -> io::Result<Self> {
+        let listener = UnixListener::bind(path)?;
+        Ok(Daemon::new(listener, Arc::new(Resolver::new(config))))
+    }
+
+    /// A server listening with `listener`, like a socket passed by systemd, resolving with
+    /// `resolver`.
+    pub fn new(listener: UnixListener, resolver: Arc<Resolver>) -> Self {
+        Self {
+            listener,
+            resolver,
+            max_request_len: DEFAULT_MAX_REQUEST_LEN,
+        }
+    }
+
+    /// Limits each request to `bytes`, without the end of its line, so a client can't make
+    /// the server buffer without bound. Defaults to 1 MiB.
+    pub fn max_request_len(mut self, bytes: usize) -> Self {
+        self.max_request_len = bytes;
+        self
+    }
+
+    /// The resolver answering the requests.
+    pub fn resolver(&self) -> &Arc<Resolver> {
+        &self.resolver
+    }
+
+    /// Accepts clients, each answered on a thread of its own until it hangs up. Only returns
+    /// if accepting a client fails.
+    pub fn run(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let resolver = self.resolver.clone();
+            let max_request_len = self.max_request_len;
+            thread::spawn(move || serve(&resolver, stream, max_request_len));
+        }
+        Ok(())
+    }
+}
+
+/// Answers the requests of the client on `stream` until it hangs up, or sends a line longer
+/// than `max_len`.
+fn serve(resolver: &Arc<Resolver>, stream: UnixStream, max_len: usize) -> io::Result<()> {
+    let mut out = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        // One byte more than a line may have, and its end
+        let limit = max_len as u64 + 2;
+        if reader.by_ref().take(limit).read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        let ended = line.last() == Some(&b'\n');
+        if ended {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        if line.len() > max_len {
+            let error = rpc_error(
+                INVALID_REQUEST,
+                format!("the request exceeds {max_len} bytes"),
+            );
+            return respond(&mut out, &Value::Null, error);
+        }
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        if let Some((id, outcome)) = answer(resolver, &line) {
+            respond(&mut out, &id, outcome)?;
+        }
+        if !ended {
+            return Ok(());
+        }
+    }
+}
+
+/// Writes the line answering the request `id` with `outcome`.
+fn respond(out: &mut impl Write, id: &Value, outcome: Outcome) -> io::Result<()> {
+    let response = Response {
+        jsonrpc: "2.0",
+        id,
+        outcome,
+    };
+    serde_json::to_writer(&mut *out, &response).map_err(io::Error::from)?;
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+/// The `id` of the request `line`, and what became of it, or `None` for a notification,
+/// which is not answered.
+fn answer(resolver: &Arc<Resolver>, line: &[u8]) -> Option<(Value, Outcome)> {
+    let value = match serde_json::from_slice::<Value>(line) {
+        Ok(value) => value,
+        Err(error) => return Some((Value::Null, rpc_error(PARSE_ERROR, error))),
+    };
+    let id = value.get("id").cloned();
+    match serde_json::from_value::<Request>(value) {
+        Ok(request) => {
+            let outcome = call(resolver, &request.method, request.params);
+            Some((id?, outcome))
+        }
+        Err(error) => Some((id.unwrap_or_default(), rpc_error(INVALID_REQUEST, error))),
+    }
+}
+
+/// Calls `method` with `params`.
+fn call(resolver: &Arc<Resolver>, method: &str, params: Value) -> Outcome {
+    let graph = |path: &Path| ElfFile::with_resolver(path, resolver.clone()).dependency_graph();
+    let result = match method {
+        "resolve" => with_params(params, |SonameParams { soname }| {
+            resolver.resolve_soname(soname)
+        }),
+        "closure" => with_params(params, |PathParams { path }| graph(&path)),
+        "diff" => with_params(params, |DiffParams { old, new }| {
+            Ok(graph(&old)?.diff(&graph(&new)?))
+        }),
+        "invalidate" => with_params(params, |PathParams { path }| Ok(resolver.invalidate(path))),
+        "metrics" => to_value(resolver.metrics()),
+        _ => return rpc_error(METHOD_NOT_FOUND, format!("there is no method {method:?}")),
+    };
+    match result {
+        Ok(value) => Outcome::Result(value),
+        Err(Failure::Params(error)) => rpc_error(INVALID_PARAMS, error),
+        Err(Failure::Analysis(error)) => match to_value(&error) {
+            Ok(data) => Outcome::Error(RpcError {
+                code: ANALYSIS_FAILED,
+                message: error.to_string(),
+                data: Some(data),
+            }),
+            Err(_) => rpc_error(INTERNAL_ERROR, "the error can't be serialized"),
+        },
+        Err(Failure::Serialize(error)) => rpc_error(INTERNAL_ERROR, error),
+    }
+}
+
+/// Why a call failed.
+enum Failure {
+    Params(serde_json::Error),
+    Analysis(Error),
+    Serialize(serde_json::Error),
+}
+
+/// Calls `method` with `params` deserialized, and serializes what it returns.
+fn with_params<P, T, F>(params: Value, method: F) -> Result<Value, Failure>
+where
+    P: serde::de::DeserializeOwned,
+    T: serde::Serialize,
+    F: FnOnce(P) -> Result<T, Error>,
+{
+    let params = serde_json::from_value(params).map_err(Failure::Params)?;
+    to_value(method(params).map_err(Failure::Analysis)?)
+}
+
+/// `value` as JSON, unless it can't be represented, as when it has a path that is not UTF-8.
+fn to_value<T: serde::Serialize>(value: T) -> Result<Value, Failure> {
+    serde_json::to_value(value).map_err(Failure::Serialize)
+}
+
+fn rpc_error(code: i64, message: impl ToString) -> Outcome {
+    Outcome::Error(RpcError {
+        code,
+        message: message.to_string(),
+        data: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{answer, respond, Daemon};
+    use crate::test_util::ElfBuilder;
+    use crate::{Resolver, ResolverConfig};
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_answer() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        ElfBuilder::new().write(dir.path(), "libbar.so.1");
+        let old = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .write(dir.path(), "old");
+        let new = ElfBuilder::new()
+            .needed("libbar.so.1")
+            .write(dir.path(), "new");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path());
+        let resolver = Arc::new(Resolver::new(config));
+        let answer = |line: &[u8]| -> Option<Value> {
+            let (id, outcome) = answer(&resolver, line)?;
+            let mut out = Vec::new();
+            respond(&mut out, &id, outcome).unwrap();
+            Some(serde_json::from_slice(&out).unwrap())
+        };
+        let call = |request: Value| answer(request.to_string().as_bytes()).unwrap();
+        let request = |id, method, params| {
+            json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+        };
+
+        let resolved = call(request(1, "resolve", json!({"soname": "libfoo.so.1"})));
+        assert_eq!(resolved, json!({"jsonrpc": "2.0", "id": 1, "result": lib}));
+        let closure = call(request(2, "closure", json!({"path": old})));
+        let nodes = closure["result"]["nodes"].as_array().unwrap();
+        assert_eq!(nodes[1]["soname"], "libfoo.so.1");
+        let diff = call(request(3, "diff", json!({"old": old, "new": new})));
+        assert_eq!(diff["result"]["added"][0][0], "libbar.so.1");
+        assert_eq!(diff["result"]["removed"][0][0], "libfoo.so.1");
+        // Each file was parsed once, and old again found parsed for the diff
+        let metrics = call(request(4, "metrics", Value::Null));
+        assert_eq!(metrics["result"]["files_parsed"], 4);
+        assert!(metrics["result"]["cache_hits"].as_u64().unwrap() > 0);
+        let invalidated = call(request(5, "invalidate", json!({"path": old})));
+        assert_eq!(invalidated["result"], true);
+
+        let missing = call(request(6, "resolve", json!({"soname": "libgone.so.1"})));
+        assert_eq!(missing["error"]["code"], -32000);
+        assert!(missing["error"]["data"]["SonameNotFound"].is_object());
+        assert_eq!(
+            call(request(7, "closure", json!({})))["error"]["code"],
+            -32602
+        );
+        assert_eq!(
+            call(request(8, "ldd", Value::Null))["error"]["code"],
+            -32601
+        );
+        assert_eq!(call(json!({"id": 9}))["error"]["code"], -32600);
+        let garbage = answer(b"{").unwrap();
+        assert_eq!(garbage["error"]["code"], -32700);
+        assert_eq!(garbage["id"], Value::Null);
+
+        // A notification is carried out, but not answered
+        let notification =
+            json!({"jsonrpc": "2.0", "method": "invalidate", "params": {"path": new}});
+        assert!(answer(notification.to_string().as_bytes()).is_none());
+        assert_eq!(
+            call(request(10, "invalidate", json!({"path": new})))["result"],
+            false
+        );
+    }
+
+    #[test]
+    fn test_unserializable_answer() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().join(OsStr::from_bytes(b"dir\xff"));
+        let lib = ElfBuilder::new().write(&lib_dir, "libfoo.so");
+        let root = ElfBuilder::new()
+            .needed("libfoo.so")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(&lib_dir);
+        let resolver = Arc::new(Resolver::new(config));
+        let call = |request: Value| {
+            let (id, outcome) = answer(&resolver, request.to_string().as_bytes()).unwrap();
+            let mut out = Vec::new();
+            respond(&mut out, &id, outcome).unwrap();
+            serde_json::from_slice::<Value>(&out).unwrap()
+        };
+
+        // A path alone is answered as a string, which the library's, not UTF-8, can't be
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": "resolve",
+            "params": {"soname": "libfoo.so"}});
+        let resolved = call(request);
+        assert_eq!(resolved["id"], 1);
+        assert_eq!(resolved["error"]["code"], -32603);
+        // while graphs hold such paths as their bytes
+        let request = json!({"jsonrpc": "2.0", "id": 2, "method": "closure",
+            "params": {"path": root}});
+        let closure = call(request);
+        let path = &closure["result"]["nodes"][1]["resolution"]["Ok"]["Found"];
+        let path: Vec<u8> = serde_json::from_value(path.clone()).unwrap();
+        assert_eq!(path, lib.as_os_str().as_bytes());
+    }
+
+    #[test]
+    fn test_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path());
+        let socket = dir.path().join("socket");
+        let daemon = Daemon::bind(&socket, config).unwrap();
+        assert!(Daemon::bind(&socket, ResolverConfig::new()).is_err());
+        std::thread::spawn(move || daemon.run());
+
+        // Two clients at once, each answered in order
+        let clients: Vec<_> = (0..2)
+            .map(|_| UnixStream::connect(&socket).unwrap())
+            .collect();
+        for mut client in clients {
+            let requests = [
+                r#"{"jsonrpc":"2.0","id":1,"method":"resolve","params":{"soname":"libfoo.so.1"}}"#,
+                "",
+                r#"{"jsonrpc":"2.0","id":2,"method":"metrics"}"#,
+            ];
+            writeln!(client, "{}", requests.join("\n")).unwrap();
+            let mut lines = BufReader::new(client).lines();
+            let first: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+            assert_eq!(first["result"], serde_json::to_value(&lib).unwrap());
+            let second: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+            assert_eq!(second["id"], 2);
+        }
+    }
+
+    #[test]
+    fn test_max_request_len() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("socket");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let resolver = Arc::new(Resolver::new(ResolverConfig::new().use_env(false)));
+        let daemon = Daemon::new(listener, resolver).max_request_len(64);
+        std::thread::spawn(move || daemon.run());
+
+        let mut client = UnixStream::connect(&socket).unwrap();
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"metrics"}"#;
+        // In one write, as the daemon may hang up between several
+        let payload = format!("{request}\n{}\n", "x".repeat(1000));
+        client.write_all(payload.as_bytes()).unwrap();
+        // The rest of the line may be left unread, and the hang-up seen as a reset
+        let lines: Vec<Value> = BufReader::new(client)
+            .lines()
+            .map_while(Result::ok)
+            .map(|line| serde_json::from_str(&line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["id"], 1);
+        assert_eq!(lines[1]["error"]["code"], -32600);
+    }
+}
@@ -0,0 +1,194 @@
+//! Finding the separate debug information of libraries by their GNU build IDs, as debuggers
+//! and profilers do.
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::sysroot;
+
+/// Where distributions install separate debug files.
+const DEBUG_DIR: &str = "/usr/lib/debug";
+
+/// A function downloading the file at a URL, returning where it put it.
+type FetchFn = dyn Fn(&str) -> Option<PathBuf> + Send + Sync;
+
+/// Where the debug information of an object is, as found by [`DebugInfoLocator::locate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DebugInfo {
+    /// A debug file on disk, installed below a debug directory or fetched from a debuginfod
+    /// server.
+    File(PathBuf),
+    /// The URLs the debuginfod servers serve the debug file at, in order, when it is not
+    /// installed and there is no [fetcher](DebugInfoLocator::fetch_with) to download it.
+    Debuginfod(Vec<String>),
+}
+
+/// Looks up separate debug files by build ID, in the `.build-id` directories below the debug
+/// directories and, optionally, on debuginfod servers.
+///
+/// The crate has no HTTP client, so downloads are left to a function passed to
+/// [`DebugInfoLocator::fetch_with`]; without one, the URLs to download from are returned.
+#[derive(Clone)]
+pub struct DebugInfoLocator {
+    sysroot: Option<PathBuf>,
+    debug_dirs: Vec<PathBuf>,
+    debuginfod_urls: Vec<String>,
+    fetch: Option<Arc<FetchFn>>,
+}
+
+impl Default for DebugInfoLocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugInfoLocator {
+    /// A locator searching `/usr/lib/debug` only.
+    pub fn new() -> Self {
+        Self {
+            sysroot: None,
+            debug_dirs: vec![PathBuf::from(DEBUG_DIR)],
+            debuginfod_urls: Vec::new(),
+            fetch: None,
+        }
+    }
+
+    /// Looks up the debug directories inside the root filesystem at `sysroot`, following
+    /// symlinks as if it were mounted at `/`, for libraries found with
+    /// [`ResolverConfig::sysroot`](crate::ResolverConfig::sysroot).
+    pub fn sysroot<P: AsRef<Path>>(mut self, sysroot: P) -> Self {
+        self.sysroot = Some(sysroot.as_ref().to_owned());
+        self
+    }
+
+    /// Also searches `dir`, after the directories already added.
+    pub fn debug_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.debug_dirs.push(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Queries the debuginfod servers `urls` for debug files that are not installed. The URLs
+    /// are separated by spaces, as in the `DEBUGINFOD_URLS` variable debuginfod clients read.
+    pub fn debuginfod_urls<S: AsRef<str>>(mut self, urls: S) -> Self {
+        let urls = urls.as_ref().split_whitespace();
+        self.debuginfod_urls
+            .extend(urls.map(|url| url.trim_end_matches('/').to_owned()));
+        self
+    }
+
+    /// Downloads debug files from the debuginfod servers with `fetch`, which, given the URL of
+    /// a debug file, returns where it saved it, or `None` if the server doesn't have it.
+    pub fn fetch_with(
+        mut self,
+        fetch: impl Fn(&str) -> Option<PathBuf> + Send + Sync + 'static,
+    ) -> Self {
+        self.fetch = Some(Arc::new(fetch));
+        self
+    }
+
+    /// Finds the debug file of the object with the GNU build ID `build_id`, in lowercase
+    /// hexadecimal as [`DependencyNode::build_id`](crate::DependencyNode::build_id) has it:
+    /// `<dir>/.build-id/<first two digits>/<other digits>.debug` in the first debug directory
+    /// that has it, or else a file fetched from, or the URLs of, the debuginfod servers.
+    /// Returns `None` if none has it, or if `build_id` is not a build ID.
+    pub fn locate(&self, build_id: &str) -> Option<DebugInfo> {
+        let is_hex = |byte: u8| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte);
+        if build_id.len() < 3 || !build_id.bytes().all(is_hex) {
+            return None;
+        }
+        let (prefix, rest) = build_id.split_at(2);
+        let file = self.debug_dirs.iter().find_map(|dir| {
+            let path = dir
+                .join(".build-id")
+                .join(prefix)
+                .join(format!("{rest}.debug"));
+            let found = match &self.sysroot {
+                Some(root) => sysroot::resolve(root, &sysroot::rebase(root, &path))?,
+                None => path,
+            };
+            found.is_file().then_some(found)
+        });
+        if let Some(file) = file {
+            return Some(DebugInfo::File(file));
+        }
+        if self.debuginfod_urls.is_empty() {
+            return None;
+        }
+        let mut urls = self
+            .debuginfod_urls
+            .iter()
+            .map(|url| format!("{url}/buildid/{build_id}/debuginfo"));
+        match &self.fetch {
+            Some(fetch) => urls.find_map(|url| fetch(&url)).map(DebugInfo::File),
+            None => Some(DebugInfo::Debuginfod(urls.collect())),
+        }
+    }
+}
+
+impl fmt::Debug for DebugInfoLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugInfoLocator")
+            .field("sysroot", &self.sysroot)
+            .field("debug_dirs", &self.debug_dirs)
+            .field("debuginfod_urls", &self.debuginfod_urls)
+            .field("fetch", &self.fetch.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DebugInfo, DebugInfoLocator};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_locate() {
+        let dir = tempfile::tempdir().unwrap();
+        let debug_dir = dir.path().join("usr/lib/debug");
+        let build_id_dir = debug_dir.join(".build-id/ab");
+        fs::create_dir_all(&build_id_dir).unwrap();
+        let debug_file = build_id_dir.join("cdef01.debug");
+        fs::write(&debug_file, b"").unwrap();
+
+        let locator = DebugInfoLocator::new().debug_dir(&debug_dir);
+        assert_eq!(
+            locator.locate("abcdef01"),
+            Some(DebugInfo::File(debug_file.clone()))
+        );
+        assert_eq!(locator.locate("abcdef02"), None);
+        assert_eq!(locator.locate("../../etc"), None);
+        let in_sysroot = DebugInfoLocator::new().sysroot(dir.path());
+        assert_eq!(
+            in_sysroot.locate("abcdef01"),
+            Some(DebugInfo::File(debug_file))
+        );
+
+        let servers = locator.debuginfod_urls("https://one.example/ http://two.example");
+        let urls = [
+            "https://one.example/buildid/abcdef02/debuginfo",
+            "http://two.example/buildid/abcdef02/debuginfo",
+        ];
+        let expected = urls.map(str::to_owned).to_vec();
+        assert_eq!(
+            servers.locate("abcdef02"),
+            Some(DebugInfo::Debuginfod(expected))
+        );
+
+        // Servers are tried in order until one has the file
+        let fetched = Arc::new(Mutex::new(Vec::new()));
+        let fetching = servers.fetch_with({
+            let fetched = Arc::clone(&fetched);
+            move |url| {
+                fetched.lock().unwrap().push(url.to_owned());
+                url.contains("two")
+                    .then(|| PathBuf::from("/cache/debuginfo"))
+            }
+        });
+        let file = DebugInfo::File(PathBuf::from("/cache/debuginfo"));
+        assert_eq!(fetching.locate("abcdef02"), Some(file));
+        assert_eq!(*fetched.lock().unwrap(), urls);
+    }
+}
@@ -0,0 +1,1800 @@
+//! Demangling of the Itanium C++ and Rust symbol names the symbol-level APIs report, so that
+//! `_ZNSt6vectorIiSaIiEE9push_backERKi` reads as
+//! `std::vector<int, std::allocator<int>>::push_back(int const&)`. Only the common parts of each
+//! mangling are understood; names using the rest, like C++ expressions in template arguments,
+//! are left mangled rather than shown half-demangled.
+
+/// The longest demangled name produced. Back references let a short name stand for an
+/// exponentially longer one, so the output must be bounded.
+const MAX_LEN: usize = 1 << 16;
+
+/// How deeply types and names may nest.
+const MAX_DEPTH: usize = 256;
+
+/// Demangles `name` if it is an Itanium C++ (`_Z...`) or Rust (`_ZN...E` with a hash, or
+/// `_R...`) symbol name, returning `None` if it is not or uses parts of the mangling this crate
+/// doesn't understand. Rust names are shown without their hash or crate disambiguators, and
+/// suffixes added by compiler passes, like `.cold`, are shown as clones, like `c++filt` does.
+pub fn demangle(name: &str) -> Option<String> {
+    if let Some(demangled) = demangle_whole(name) {
+        return Some(demangled);
+    }
+    // Legacy Rust names have dots of their own, so each can start the suffix
+    name.match_indices('.').find_map(|(at, _)| {
+        let (mangled, suffix) = name.split_at(at);
+        Some(demangle_whole(mangled)? + &clones(suffix))
+    })
+}
+
+/// The suffixes `suffix` is made of, like `.isra.0.cold`, as ` [clone .isra.0] [clone .cold]`.
+fn clones(suffix: &str) -> String {
+    let mut clones: Vec<String> = Vec::new();
+    for part in suffix.split('.').skip(1) {
+        match clones.last_mut() {
+            // Numbers tell apart clones of the same kind
+            Some(clone) if part.starts_with(|c: char| c.is_ascii_digit()) => {
+                clone.push('.');
+                clone.push_str(part);
+            }
+            _ => clones.push(format!(".{part}")),
+        }
+    }
+    clones
+        .iter()
+        .map(|clone| format!(" [clone {clone}]"))
+        .collect()
+}
+
+/// Demangles `name`, which has no suffix.
+fn demangle_whole(name: &str) -> Option<String> {
+    if let Some(rest) = name.strip_prefix("_R") {
+        V0::new(rest.as_bytes()).symbol()
+    } else if let Some(rest) = name.strip_prefix("_Z") {
+        legacy_rust(rest.as_bytes()).or_else(|| Itanium::new(rest.as_bytes()).symbol())
+    } else {
+        None
+    }
+}
+
+/// Demangles the rest of a legacy Rust symbol name after `_Z`: an Itanium nested name of plain
+/// identifiers ending with a `h` and 16 hex digits hash, with `$..$` escapes for punctuation.
+fn legacy_rust(mangled: &[u8]) -> Option<String> {
+    let mut rest = mangled.strip_prefix(b"N")?;
+    let mut components = Vec::new();
+    while !rest.starts_with(b"E") {
+        let digits = rest.iter().take_while(|byte| byte.is_ascii_digit()).count();
+        let len: usize = std::str::from_utf8(&rest[..digits]).ok()?.parse().ok()?;
+        let component = rest.get(digits..digits + len)?;
+        components.push(std::str::from_utf8(component).ok()?);
+        rest = &rest[digits + len..];
+    }
+    let (&hash, components) = components.split_last()?;
+    let is_hash = |hash: &str| {
+        hash.len() == 17
+            && hash.starts_with('h')
+            && hash[1..].bytes().all(|byte| byte.is_ascii_hexdigit())
+    };
+    if rest != b"E" || components.is_empty() || !is_hash(hash) {
+        return None;
+    }
+    let mut demangled = String::new();
+    for (i, component) in components.iter().enumerate() {
+        if i > 0 {
+            demangled.push_str("::");
+        }
+        // Identifiers can't start with $, so escapes there get an underscore in front
+        let mut component = match component.strip_prefix("_$") {
+            Some(_) => &component[1..],
+            None => component,
+        };
+        while !component.is_empty() {
+            if let Some(rest) = component.strip_prefix('$') {
+                let (escape, rest) = rest.split_once('$')?;
+                let unescaped = match escape {
+                    "SP" => '@',
+                    "BP" => '*',
+                    "RF" => '&',
+                    "LT" => '<',
+                    "GT" => '>',
+                    "LP" => '(',
+                    "RP" => ')',
+                    "C" => ',',
+                    _ => {
+                        let hex = escape.strip_prefix('u')?;
+                        char::from_u32(u32::from_str_radix(hex, 16).ok()?)?
+                    }
+                };
+                demangled.push(unescaped);
+                component = rest;
+            } else if let Some(rest) = component.strip_prefix("..") {
+                demangled.push_str("::");
+                component = rest;
+            } else {
+                let len = component[1..]
+                    .find(['$', '.'])
+                    .map_or(component.len(), |at| at + 1);
+                demangled.push_str(&component[..len]);
+                component = &component[len..];
+            }
+        }
+    }
+    Some(demangled)
+}
+
+/// An Itanium C++ type, kept structured until it is rendered, as pointers to functions and
+/// arrays wrap their declarator in the middle of the type.
+#[derive(Debug, Clone)]
+enum Type {
+    /// A builtin type or a class, like `int` or `std::string`.
+    Name(String),
+    /// A type with `const`, `volatile` or `restrict`, written after it.
+    Qualified(Box<Type>, String),
+    /// A pointer or reference, with its `*`, `&` or `&&`.
+    Pointer(Box<Type>, &'static str),
+    Function {
+        ret: Box<Type>,
+        params: String,
+    },
+    /// An array, with its dimension if it has one.
+    Array(Box<Type>, String),
+    /// A pointer to a member of a class.
+    MemberPointer {
+        class: Box<Type>,
+        member: Box<Type>,
+    },
+    /// A template argument pack, or the expansion of one, written as the list of its elements.
+    Pack(Vec<Type>),
+    /// A template parameter as a substitution, which stands for the template argument of its
+    /// index where it is referred to, as GCC mangles it.
+    Param(usize),
+}
+
+impl Type {
+    fn render(&self) -> String {
+        self.declare(String::new())
+    }
+
+    /// This type around `declarator`, what is written in the middle of it, like `(*)` in
+    /// `void (*)()` or ` [3]` in `int [3]`.
+    fn declare(&self, declarator: String) -> String {
+        // Declarators of functions and arrays are parenthesized, and set apart from the rest
+        let spaced = |declarator: String| {
+            if declarator.starts_with('(') {
+                format!(" {declarator}")
+            } else {
+                declarator
+            }
+        };
+        match self {
+            Type::Name(name) => name.clone() + &spaced(declarator),
+            Type::Qualified(inner, qualifiers) => match &**inner {
+                // The qualifiers of a method come after its parameters
+                Type::Function { ret, params } => ret.declare(declarator + params + qualifiers),
+                _ => inner.declare(qualifiers.clone() + &spaced(declarator)),
+            },
+            Type::Pointer(inner, pointer) => {
+                if inner.function().is_some() || matches!(**inner, Type::Array(..)) {
+                    inner.declare(format!("({pointer}{declarator})"))
+                } else {
+                    inner.declare(format!("{pointer}{}", spaced(declarator)))
+                }
+            }
+            Type::Function { ret, params } => ret.declare(declarator + params),
+            Type::Array(element, dimension) => {
+                // The dimensions of arrays of arrays follow one another
+                let space = if declarator.ends_with(']') { "" } else { " " };
+                element.declare(format!("{declarator}{space}[{dimension}]"))
+            }
+            Type::MemberPointer { class, member } => {
+                let pointer = format!("{}::*{declarator}", class.render());
+                if member.function().is_some() {
+                    member.declare(format!("({pointer})"))
+                } else {
+                    member.declare(format!(" {pointer}"))
+                }
+            }
+            Type::Pack(elements) => list(elements),
+            // Only substitutions are parameters, which are resolved where they are referred to
+            Type::Param(_) => String::new(),
+        }
+    }
+
+    /// The function this is, if it is one, qualified like the methods member pointers point to
+    /// or not.
+    fn function(&self) -> Option<&Type> {
+        match self {
+            Type::Function { .. } => Some(self),
+            Type::Qualified(inner, _) if matches!(**inner, Type::Function { .. }) => Some(inner),
+            _ => None,
+        }
+    }
+}
+
+/// An Itanium C++ name, with what decides how a function of that name is mangled.
+struct Name {
+    text: String,
+    /// Whether the name ends with template arguments, which gives its function a return type.
+    is_template: bool,
+    /// Whether it names a constructor, destructor or conversion operator, which never have a
+    /// return type.
+    is_special: bool,
+    /// The qualifiers of a method, like ` const`.
+    qualifiers: String,
+    /// The template arguments the name ends with, which `T_`, `T0_` and so on in the type of
+    /// its function refer to.
+    template_args: Vec<Type>,
+}
+
+impl Name {
+    fn plain(text: String) -> Self {
+        Name {
+            text,
+            is_template: false,
+            is_special: false,
+            qualifiers: String::new(),
+            template_args: Vec::new(),
+        }
+    }
+}
+
+/// A parser of the Itanium C++ ABI mangling, at byte `at` of `mangled`, which follows `_Z`.
+struct Itanium<'a> {
+    mangled: &'a [u8],
+    at: usize,
+    /// The names and types later ones can refer to with `S_`, `S0_` and so on.
+    substitutions: Vec<Type>,
+    /// The template arguments of the function, which `T_`, `T0_` and so on refer to.
+    template_args: Vec<Type>,
+    /// Which element of the packs the template parameters in the pattern of a pack expansion
+    /// being written stand for.
+    pack_index: Option<usize>,
+    /// The length of the first pack a template parameter in the pattern of a pack expansion
+    /// being parsed referred to.
+    pack_len: Option<usize>,
+    /// The name of the class the component of a nested name being parsed is in, which
+    /// constructors and destructors are named after.
+    class_name: String,
+    depth: usize,
+}
+
+impl<'a> Itanium<'a> {
+    fn new(mangled: &'a [u8]) -> Self {
+        Itanium {
+            mangled,
+            at: 0,
+            substitutions: Vec::new(),
+            template_args: Vec::new(),
+            pack_index: None,
+            pack_len: None,
+            class_name: String::new(),
+            depth: 0,
+        }
+    }
+
+    fn symbol(mut self) -> Option<String> {
+        let demangled = self.encoding(true)?;
+        (self.at == self.mangled.len()).then_some(demangled)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.mangled.get(self.at).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.mangled.get(self.at + offset).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let eaten = self.peek() == Some(byte);
+        self.at += usize::from(eaten);
+        eaten
+    }
+
+    fn eat_str(&mut self, prefix: &[u8]) -> bool {
+        let eaten = self.mangled[self.at..].starts_with(prefix);
+        if eaten {
+            self.at += prefix.len();
+        }
+        eaten
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.at += 1;
+        Some(byte)
+    }
+
+    /// Guards against names nested deeply enough to overflow the stack, or back references
+    /// blowing up into huge names.
+    fn enter(&mut self, len: usize) -> Option<()> {
+        self.depth += 1;
+        (self.depth < MAX_DEPTH && len < MAX_LEN).then_some(())
+    }
+
+    fn number(&mut self) -> Option<String> {
+        let negative = self.eat(b'n');
+        let start = self.at;
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.at += 1;
+        }
+        (self.at > start).then_some(())?;
+        let digits = std::str::from_utf8(&self.mangled[start..self.at]).ok()?;
+        Some(if negative {
+            format!("-{digits}")
+        } else {
+            digits.to_owned()
+        })
+    }
+
+    /// A function or data name, with the parameters of a function, and its return type if it
+    /// has one and `with_ret`.
+    fn encoding(&mut self, with_ret: bool) -> Option<String> {
+        self.enter(0)?;
+        let encoding = match self.peek()? {
+            b'T' | b'G' => self.special_name(),
+            _ => {
+                let name = self.name()?;
+                if name.is_template {
+                    self.template_args = name.template_args;
+                }
+                if matches!(self.peek(), None | Some(b'E')) {
+                    Some(name.text)
+                } else {
+                    let ret = if name.is_template && !name.is_special {
+                        Some(self.type_()?)
+                    } else {
+                        None
+                    };
+                    let params = self.params(|rest| rest[0] == b'E')?;
+                    let text = format!("{}{params}{}", name.text, name.qualifiers);
+                    Some(match ret {
+                        Some(ret) if with_ret => format!("{} {text}", ret.render()),
+                        _ => text,
+                    })
+                }
+            }
+        };
+        self.depth -= 1;
+        encoding
+    }
+
+    /// Parameter types up to the end of the name or bytes `end` says end them, as a
+    /// parenthesized list.
+    fn params(&mut self, end: impl Fn(&[u8]) -> bool) -> Option<String> {
+        let ends = |parser: &Self, offset: usize| {
+            let rest = parser.mangled.get(parser.at + offset..).unwrap_or_default();
+            rest.is_empty() || end(rest)
+        };
+        if self.peek() == Some(b'v') && ends(self, 1) {
+            self.at += 1;
+            return Some("()".to_owned());
+        }
+        let mut params = Vec::new();
+        while !ends(self, 0) {
+            if self.eat(b'z') {
+                params.push("...".to_owned());
+            } else {
+                let param = self.type_()?.render();
+                // Expansions of empty packs are left out
+                if !param.is_empty() {
+                    params.push(param);
+                }
+            }
+        }
+        Some(format!("({})", params.join(", ")))
+    }
+
+    fn special_name(&mut self) -> Option<String> {
+        let kind = self.next()?;
+        let what = self.next()?;
+        let described = |described: &str, parser: &mut Self| -> Option<String> {
+            Some(format!("{described} {}", parser.type_()?.render()))
+        };
+        match (kind, what) {
+            (b'T', b'V') => described("vtable for", self),
+            (b'T', b'T') => described("VTT for", self),
+            (b'T', b'I') => described("typeinfo for", self),
+            (b'T', b'S') => described("typeinfo name for", self),
+            (b'T', b'C') => {
+                let derived = self.type_()?.render();
+                self.number()?;
+                self.eat(b'_').then_some(())?;
+                let base = self.type_()?.render();
+                Some(format!("construction vtable for {base}-in-{derived}"))
+            }
+            (b'T', b'H') => Some(format!("TLS init function for {}", self.name()?.text)),
+            (b'T', b'W') => Some(format!("TLS wrapper function for {}", self.name()?.text)),
+            (b'T', b'h') => {
+                self.call_offset(b'h')?;
+                Some(format!("non-virtual thunk to {}", self.encoding(true)?))
+            }
+            (b'T', b'v') => {
+                self.call_offset(b'v')?;
+                Some(format!("virtual thunk to {}", self.encoding(true)?))
+            }
+            (b'T', b'c') => {
+                for _ in 0..2 {
+                    let kind = self.next()?;
+                    self.call_offset(kind)?;
+                }
+                Some(format!(
+                    "covariant return thunk to {}",
+                    self.encoding(true)?
+                ))
+            }
+            (b'G', b'V') => Some(format!("guard variable for {}", self.name()?.text)),
+            (b'G', b'T') => match self.next()? {
+                b't' => Some(format!("transaction clone for {}", self.encoding(true)?)),
+                b'n' => Some(format!(
+                    "non-transaction clone for {}",
+                    self.encoding(true)?
+                )),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The offsets of a thunk, after their `h` or `v`, which demangled names leave out.
+    fn call_offset(&mut self, kind: u8) -> Option<()> {
+        let offsets = match kind {
+            b'h' => 1,
+            b'v' => 2,
+            _ => return None,
+        };
+        for _ in 0..offsets {
+            self.number()?;
+            self.eat(b'_').then_some(())?;
+        }
+        Some(())
+    }
+
+    fn name(&mut self) -> Option<Name> {
+        match self.peek()? {
+            b'N' => self.nested_name(),
+            b'Z' => self.local_name(),
+            b'S' if self.peek_at(1) == Some(b't') => {
+                self.at += 2;
+                let (name, is_special) = self.unqualified_name()?;
+                self.template_name(format!("std::{name}"), is_special, true)
+            }
+            b'S' => {
+                let substitution = self.substitution()?.render();
+                self.template_name(substitution, false, false)
+            }
+            _ => {
+                // Names of internal linkage, which are mangled the same otherwise
+                self.eat(b'L');
+                let (name, is_special) = self.unqualified_name()?;
+                self.template_name(name, is_special, true)
+            }
+        }
+    }
+
+    /// An unscoped name, `text`, with the template arguments that may follow it, before which
+    /// the name can be referred to later if `substitutable`.
+    fn template_name(
+        &mut self,
+        text: String,
+        is_special: bool,
+        substitutable: bool,
+    ) -> Option<Name> {
+        if self.peek() != Some(b'I') {
+            return Some(Name {
+                is_special,
+                ..Name::plain(text)
+            });
+        }
+        if substitutable {
+            self.substitutions.push(Type::Name(text.clone()));
+        }
+        let args = self.template_arg_list()?;
+        Some(Name {
+            text: with_template_args(&text, &args),
+            is_template: true,
+            is_special,
+            qualifiers: String::new(),
+            template_args: args,
+        })
+    }
+
+    fn nested_name(&mut self) -> Option<Name> {
+        self.eat(b'N').then_some(())?;
+        let mut qualifiers = String::new();
+        for (code, qualifier) in [(b'r', " restrict"), (b'V', " volatile"), (b'K', " const")] {
+            if self.eat(code) {
+                qualifiers.insert_str(0, qualifier);
+            }
+        }
+        if self.eat(b'R') {
+            qualifiers.push_str(" &");
+        } else if self.eat(b'O') {
+            qualifiers.push_str(" &&");
+        }
+        let mut text = String::new();
+        let (mut is_template, mut is_special) = (false, false);
+        let mut args = Vec::new();
+        let mut class_name = String::new();
+        while !self.eat(b'E') {
+            self.enter(text.len())?;
+            let join = |text: &mut String, component: &str| {
+                if !text.is_empty() {
+                    text.push_str("::");
+                }
+                text.push_str(component);
+            };
+            match self.peek()? {
+                b'S' if self.peek_at(1) == Some(b't') => {
+                    self.at += 2;
+                    join(&mut text, "std");
+                    self.depth -= 1;
+                    continue;
+                }
+                b'S' => {
+                    let substitution = self.substitution()?.render();
+                    join(&mut text, &substitution);
+                    class_name = unqualified(&substitution).to_owned();
+                    self.depth -= 1;
+                    continue;
+                }
+                b'I' if !text.is_empty() => {
+                    args = self.template_arg_list()?;
+                    text = with_template_args(&text, &args);
+                    is_template = true;
+                }
+                b'T' => {
+                    let param = self.template_param()?.render();
+                    join(&mut text, &param);
+                    is_template = false;
+                }
+                _ => {
+                    self.eat(b'L');
+                    let unnamed = self.peek() == Some(b'U');
+                    self.class_name = class_name.clone();
+                    let (name, special) = self.unqualified_name()?;
+                    join(&mut text, &name);
+                    // Constructors and destructors repeat the name of their class, but not its
+                    // ABI tags, and that of the class around unnamed ones like `c++filt` does
+                    if !unnamed {
+                        class_name = name.split("[abi:").next().unwrap_or_default().to_owned();
+                    }
+                    (is_template, is_special) = (false, special);
+                }
+            }
+            if self.peek() != Some(b'E') {
+                self.substitutions.push(Type::Name(text.clone()));
+            }
+            self.depth -= 1;
+        }
+        (!text.is_empty()).then_some(())?;
+        Some(Name {
+            text,
+            is_template,
+            is_special,
+            qualifiers,
+            template_args: args,
+        })
+    }
+
+    /// A name local to a function, like a static variable in it.
+    fn local_name(&mut self) -> Option<Name> {
+        self.eat(b'Z').then_some(())?;
+        // The function is written without its return type, like `c++filt` does
+        let function = self.encoding(false)?;
+        self.eat(b'E').then_some(())?;
+        if self.eat(b's') {
+            self.discriminator();
+            return Some(Name::plain(format!("{function}::string literal")));
+        }
+        let entity = self.name()?;
+        self.discriminator();
+        Some(Name {
+            text: format!("{function}::{}", entity.text),
+            ..entity
+        })
+    }
+
+    /// Skips the number telling apart local entities of the same name.
+    fn discriminator(&mut self) {
+        let start = self.at;
+        if self.eat_str(b"__") {
+            if self.number().is_none() || !self.eat(b'_') {
+                self.at = start;
+            }
+        } else if self.eat(b'_') && self.number().is_none() {
+            self.at = start;
+        }
+    }
+
+    /// A name of one component, and whether it names a constructor, destructor or conversion
+    /// operator.
+    fn unqualified_name(&mut self) -> Option<(String, bool)> {
+        let byte = self.peek()?;
+        let name = match byte {
+            b'0'..=b'9' => (self.source_name()?, false),
+            b'C' => {
+                self.at += 1;
+                if self.eat(b'I') {
+                    // An inheriting constructor, naming the base class
+                    self.next()?;
+                    self.type_()?;
+                } else {
+                    self.next().filter(|kind| matches!(kind, b'1'..=b'5'))?;
+                }
+                (self.class_name.clone(), true)
+            }
+            b'D' if matches!(self.peek_at(1), Some(b'0'..=b'5')) => {
+                self.at += 2;
+                (format!("~{}", self.class_name), true)
+            }
+            b'U' => (self.unnamed_type()?, false),
+            b'a'..=b'z' => self.operator_name()?,
+            _ => return None,
+        };
+        let mut name = name;
+        // ABI tags, like `[abi:cxx11]`
+        while self.eat(b'B') {
+            let tag = self.source_name()?;
+            name.0 += &format!("[abi:{tag}]");
+        }
+        Some(name)
+    }
+
+    fn source_name(&mut self) -> Option<String> {
+        let len: usize = self.number()?.parse().ok()?;
+        let name = self.mangled.get(self.at..self.at + len)?;
+        self.at += len;
+        let name = std::str::from_utf8(name).ok()?;
+        let name = if name.starts_with("_GLOBAL__N") {
+            "(anonymous namespace)"
+        } else {
+            name
+        };
+        Some(name.to_owned())
+    }
+
+    /// A lambda or another type without a name.
+    fn unnamed_type(&mut self) -> Option<String> {
+        self.eat(b'U').then_some(())?;
+        let kind = self.next()?;
+        let described = match kind {
+            b't' => "unnamed type".to_owned(),
+            b'l' => {
+                let params = self.params(|rest| rest[0] == b'E')?;
+                self.eat(b'E').then_some(())?;
+                format!("lambda{params}")
+            }
+            _ => return None,
+        };
+        let number = if self.eat(b'_') {
+            1
+        } else {
+            let number: usize = self.number()?.parse().ok()?;
+            self.eat(b'_').then_some(())?;
+            number + 2
+        };
+        Some(format!("{{{described}#{number}}}"))
+    }
+
+    fn operator_name(&mut self) -> Option<(String, bool)> {
+        const OPERATORS: &[(&[u8; 2], &str)] = &[
+            (b"nw", "new"),
+            (b"na", "new[]"),
+            (b"dl", "delete"),
+            (b"da", "delete[]"),
+            (b"ps", "+"),
+            (b"ng", "-"),
+            (b"ad", "&"),
+            (b"de", "*"),
+            (b"co", "~"),
+            (b"pl", "+"),
+            (b"mi", "-"),
+            (b"ml", "*"),
+            (b"dv", "/"),
+            (b"rm", "%"),
+            (b"an", "&"),
+            (b"or", "|"),
+            (b"eo", "^"),
+            (b"aS", "="),
+            (b"pL", "+="),
+            (b"mI", "-="),
+            (b"mL", "*="),
+            (b"dV", "/="),
+            (b"rM", "%="),
+            (b"aN", "&="),
+            (b"oR", "|="),
+            (b"eO", "^="),
+            (b"ls", "<<"),
+            (b"rs", ">>"),
+            (b"lS", "<<="),
+            (b"rS", ">>="),
+            (b"eq", "=="),
+            (b"ne", "!="),
+            (b"lt", "<"),
+            (b"gt", ">"),
+            (b"le", "<="),
+            (b"ge", ">="),
+            (b"ss", "<=>"),
+            (b"nt", "!"),
+            (b"aa", "&&"),
+            (b"oo", "||"),
+            (b"pp", "++"),
+            (b"mm", "--"),
+            (b"cm", ","),
+            (b"pm", "->*"),
+            (b"pt", "->"),
+            (b"cl", "()"),
+            (b"ix", "[]"),
+            (b"qu", "?"),
+        ];
+        let code = self.mangled.get(self.at..self.at + 2)?;
+        self.at += 2;
+        if code == b"cv" {
+            return Some((format!("operator {}", self.type_()?.render()), true));
+        }
+        if code == b"li" {
+            return Some((format!("operator\"\" {}", self.source_name()?), false));
+        }
+        let (_, operator) = OPERATORS.iter().find(|(known, _)| &known[..] == code)?;
+        // Words are set apart from the keyword, symbols are not
+        let space = if operator.starts_with(char::is_alphabetic) {
+            " "
+        } else {
+            ""
+        };
+        Some((format!("operator{space}{operator}"), false))
+    }
+
+    /// The template arguments after an `I` and up to an `E`.
+    fn template_arg_list(&mut self) -> Option<Vec<Type>> {
+        self.eat(b'I').then_some(())?;
+        self.template_args_up_to_end()
+    }
+
+    fn template_args_up_to_end(&mut self) -> Option<Vec<Type>> {
+        let mut args = Vec::new();
+        while !self.eat(b'E') {
+            match self.peek()? {
+                b'L' => args.push(Type::Name(self.literal()?)),
+                b'J' => {
+                    self.at += 1;
+                    args.push(Type::Pack(self.template_args_up_to_end()?));
+                }
+                _ => args.push(self.type_()?),
+            }
+        }
+        Some(args)
+    }
+
+    /// A constant template argument.
+    fn literal(&mut self) -> Option<String> {
+        self.eat(b'L').then_some(())?;
+        if self.eat_str(b"_Z") {
+            // The template arguments of the function named are its own
+            let outer = std::mem::take(&mut self.template_args);
+            let encoding = self.encoding(true);
+            self.template_args = outer;
+            let encoding = encoding?;
+            self.eat(b'E').then_some(())?;
+            return Some(encoding);
+        }
+        let code = self.peek()?;
+        let ty = self.type_()?;
+        let value = self.number()?;
+        self.eat(b'E').then_some(())?;
+        Some(match code {
+            b'b' if value == "0" => "false".to_owned(),
+            b'b' if value == "1" => "true".to_owned(),
+            b'i' => value,
+            b'j' => value + "u",
+            b'l' => value + "l",
+            b'm' => value + "ul",
+            b'x' => value + "ll",
+            b'y' => value + "ull",
+            _ => format!("({}){value}", ty.render()),
+        })
+    }
+
+    fn template_param(&mut self) -> Option<Type> {
+        let index = self.template_param_index()?;
+        self.template_arg(index)
+    }
+
+    /// The index of the template argument a template parameter, like `T0_`, refers to.
+    fn template_param_index(&mut self) -> Option<usize> {
+        self.eat(b'T').then_some(())?;
+        if self.eat(b'_') {
+            return Some(0);
+        }
+        let index: usize = self.number()?.parse().ok()?;
+        self.eat(b'_').then_some(())?;
+        Some(index + 1)
+    }
+
+    fn template_arg(&mut self, index: usize) -> Option<Type> {
+        match self.template_args.get(index)? {
+            Type::Pack(elements) => {
+                self.pack_len.get_or_insert(elements.len());
+                match self.pack_index {
+                    Some(element) => elements.get(element).cloned(),
+                    None => Some(Type::Pack(elements.clone())),
+                }
+            }
+            arg => Some(arg.clone()),
+        }
+    }
+
+    /// The expansion of the pack a pattern, like `T&&` after `Dp`, refers to, written as the
+    /// pattern of each element of the pack.
+    fn pack_expansion(&mut self) -> Option<Type> {
+        let outer = (self.pack_index.take(), self.pack_len.take());
+        let substitutions = self.substitutions.len();
+        let start = self.at;
+        let pattern = self.type_();
+        let end = self.at;
+        let added = self.substitutions.split_off(substitutions);
+        let expansion = match (pattern, self.pack_len) {
+            (Some(pattern), None) => Some(Type::Name(pattern.render() + "...")),
+            (Some(_), Some(len)) => {
+                // Parsed again for each element, which must not add substitutions again
+                let elements = (0..len)
+                    .map(|element| {
+                        self.at = start;
+                        self.pack_index = Some(element);
+                        let ty = self.type_();
+                        self.substitutions.truncate(substitutions);
+                        ty
+                    })
+                    .collect::<Option<Vec<_>>>();
+                elements.map(Type::Pack)
+            }
+            (None, _) => None,
+        };
+        self.at = end;
+        self.substitutions.extend(added);
+        (self.pack_index, self.pack_len) = outer;
+        expansion
+    }
+
+    fn substitution(&mut self) -> Option<Type> {
+        self.eat(b'S').then_some(())?;
+        let standard = match self.peek()? {
+            b'a' => "std::allocator",
+            b'b' => "std::basic_string",
+            b's' => "std::basic_string<char, std::char_traits<char>, std::allocator<char>>",
+            b'i' => "std::basic_istream<char, std::char_traits<char>>",
+            b'o' => "std::basic_ostream<char, std::char_traits<char>>",
+            b'd' => "std::basic_iostream<char, std::char_traits<char>>",
+            _ => {
+                // `S_` is the first, and `S0_` the second, with a number in base 36 after it
+                let mut number = None;
+                while let Some(digit) = self.peek().filter(|&byte| byte != b'_') {
+                    let digit = match digit {
+                        b'0'..=b'9' => digit - b'0',
+                        b'A'..=b'Z' => digit - b'A' + 10,
+                        _ => return None,
+                    };
+                    let value = number.unwrap_or(0) * 36 + usize::from(digit);
+                    (value < self.substitutions.len()).then_some(())?;
+                    number = Some(value);
+                    self.at += 1;
+                }
+                self.eat(b'_').then_some(())?;
+                let index = number.map_or(0, |number| number + 1);
+                return match *self.substitutions.get(index)? {
+                    Type::Param(param) => self.template_arg(param),
+                    ref ty => Some(ty.clone()),
+                };
+            }
+        };
+        self.at += 1;
+        Some(Type::Name(standard.to_owned()))
+    }
+
+    fn type_(&mut self) -> Option<Type> {
+        self.enter(self.substitutions.len())?;
+        let ty = self.unguarded_type();
+        self.depth -= 1;
+        let ty = ty?;
+        (ty.render().len() < MAX_LEN).then_some(ty)
+    }
+
+    fn unguarded_type(&mut self) -> Option<Type> {
+        const BUILTINS: &[(u8, &str)] = &[
+            (b'v', "void"),
+            (b'w', "wchar_t"),
+            (b'b', "bool"),
+            (b'c', "char"),
+            (b'a', "signed char"),
+            (b'h', "unsigned char"),
+            (b's', "short"),
+            (b't', "unsigned short"),
+            (b'i', "int"),
+            (b'j', "unsigned int"),
+            (b'l', "long"),
+            (b'm', "unsigned long"),
+            (b'x', "long long"),
+            (b'y', "unsigned long long"),
+            (b'n', "__int128"),
+            (b'o', "unsigned __int128"),
+            (b'f', "float"),
+            (b'd', "double"),
+            (b'e', "long double"),
+            (b'g', "__float128"),
+            (b'z', "..."),
+        ];
+        const EXTENDED: &[(u8, &str)] = &[
+            (b'n', "decltype(nullptr)"),
+            (b'd', "decimal64"),
+            (b'e', "decimal128"),
+            (b'f', "decimal32"),
+            (b'h', "half"),
+            (b'i', "char32_t"),
+            (b's', "char16_t"),
+            (b'u', "char8_t"),
+            (b'a', "auto"),
+            (b'c', "decltype(auto)"),
+        ];
+        let byte = self.peek()?;
+        if let Some((_, builtin)) = BUILTINS.iter().find(|(code, _)| *code == byte) {
+            self.at += 1;
+            return Some(Type::Name((*builtin).to_owned()));
+        }
+        let ty = match byte {
+            b'D' => {
+                self.at += 1;
+                let code = self.next()?;
+                if let Some((_, builtin)) = EXTENDED.iter().find(|(known, _)| *known == code) {
+                    return Some(Type::Name((*builtin).to_owned()));
+                }
+                match code {
+                    b'F' => {
+                        let bits = self.number()?;
+                        self.eat(b'_').then_some(())?;
+                        return Some(Type::Name(format!("_Float{bits}")));
+                    }
+                    b'p' => self.pack_expansion()?,
+                    _ => return None,
+                }
+            }
+            b'u' => {
+                self.at += 1;
+                return Some(Type::Name(self.source_name()?));
+            }
+            b'r' | b'V' | b'K' => {
+                let mut qualifiers = String::new();
+                for (code, qualifier) in
+                    [(b'r', " restrict"), (b'V', " volatile"), (b'K', " const")]
+                {
+                    if self.eat(code) {
+                        qualifiers.insert_str(0, qualifier);
+                    }
+                }
+                let function = self.peek() == Some(b'F');
+                let ty = self.type_()?;
+                if function {
+                    // A qualified function type, of a method, is substituted as a whole
+                    self.substitutions.pop();
+                }
+                match ty {
+                    // Qualifiers of an array, through template parameters, are of its elements
+                    Type::Array(element, dimension) => {
+                        Type::Array(Box::new(Type::Qualified(element, qualifiers)), dimension)
+                    }
+                    // and those of a qualified type join its own
+                    Type::Qualified(inner, existing) => {
+                        let merged = [" const", " volatile", " restrict"]
+                            .into_iter()
+                            .filter(|qualifier| {
+                                existing.contains(qualifier) || qualifiers.contains(qualifier)
+                            })
+                            .collect();
+                        Type::Qualified(inner, merged)
+                    }
+                    ty => Type::Qualified(Box::new(ty), qualifiers),
+                }
+            }
+            b'P' | b'R' | b'O' => {
+                self.at += 1;
+                let pointer = match byte {
+                    b'P' => "*",
+                    b'R' => "&",
+                    _ => "&&",
+                };
+                match self.type_()? {
+                    // References to references, through template parameters, collapse
+                    Type::Pointer(inner, inner_pointer @ ("&" | "&&")) if pointer != "*" => {
+                        let pointer = if pointer == "&&" { inner_pointer } else { "&" };
+                        Type::Pointer(inner, pointer)
+                    }
+                    ty => Type::Pointer(Box::new(ty), pointer),
+                }
+            }
+            b'F' => {
+                self.at += 1;
+                self.eat(b'Y');
+                let ret = self.type_()?;
+                // A reference qualifier only comes right before the end, `R` and `O` are
+                // reference parameters otherwise
+                let mut params =
+                    self.params(|rest| matches!(rest, [b'E', ..] | [b'R' | b'O', b'E', ..]))?;
+                if self.eat(b'R') {
+                    params.push_str(" &");
+                } else if self.eat(b'O') {
+                    params.push_str(" &&");
+                }
+                self.eat(b'E').then_some(())?;
+                Type::Function {
+                    ret: Box::new(ret),
+                    params,
+                }
+            }
+            b'A' => {
+                self.at += 1;
+                let dimension = if self.peek() == Some(b'_') {
+                    String::new()
+                } else {
+                    self.number()?
+                };
+                self.eat(b'_').then_some(())?;
+                Type::Array(Box::new(self.type_()?), dimension)
+            }
+            b'M' => {
+                self.at += 1;
+                let class = self.type_()?;
+                let member = self.type_()?;
+                Type::MemberPointer {
+                    class: Box::new(class),
+                    member: Box::new(member),
+                }
+            }
+            b'T' => {
+                let index = self.template_param_index()?;
+                let param = self.template_arg(index)?;
+                self.substitutions.push(Type::Param(index));
+                if self.peek() != Some(b'I') {
+                    return Some(param);
+                }
+                let args = self.template_arg_list()?;
+                Type::Name(with_template_args(&param.render(), &args))
+            }
+            b'S' if self.peek_at(1) != Some(b't') => {
+                let substitution = self.substitution()?;
+                if self.peek() != Some(b'I') {
+                    return Some(substitution);
+                }
+                let args = self.template_arg_list()?;
+                Type::Name(with_template_args(&substitution.render(), &args))
+            }
+            b'N' | b'Z' | b'S' | b'0'..=b'9' => Type::Name(self.name()?.text),
+            _ => return None,
+        };
+        self.substitutions.push(ty.clone());
+        Some(ty)
+    }
+}
+
+/// The name of a template, `name`, with its arguments `args`, which are set apart from a `<`
+/// ending the name of an operator by a space.
+fn with_template_args(name: &str, args: &[Type]) -> String {
+    let space = if name.ends_with('<') { " " } else { "" };
+    format!("{name}{space}<{}>", list(args))
+}
+
+/// `types` separated by commas, leaving out empty packs.
+fn list(types: &[Type]) -> String {
+    let rendered: Vec<_> = types
+        .iter()
+        .map(Type::render)
+        .filter(|ty| !ty.is_empty())
+        .collect();
+    rendered.join(", ")
+}
+
+/// The last component of `name`, without its template arguments, like `vector` for
+/// `std::vector<int>`.
+fn unqualified(name: &str) -> &str {
+    let mut name = name;
+    if name.ends_with('>') {
+        let mut depth = 0;
+        for (at, byte) in name.bytes().enumerate().rev() {
+            match byte {
+                b'>' => depth += 1,
+                b'<' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                name = &name[..at];
+                break;
+            }
+        }
+    }
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// A parser of the Rust v0 mangling, at byte `at` of `mangled`, which follows `_R`, writing
+/// the demangled name to `out`.
+struct V0<'a> {
+    mangled: &'a [u8],
+    at: usize,
+    out: String,
+    /// Whether to leave out what is parsed, for the paths of impls, which only tell them apart.
+    skipping: bool,
+    /// How many lifetimes the binders around what is being parsed introduce.
+    bound_lifetimes: u64,
+    depth: usize,
+    /// How many paths, types and constants have been parsed, which back references within
+    /// skipped paths could otherwise make exponential.
+    parsed: usize,
+}
+
+impl<'a> V0<'a> {
+    fn new(mangled: &'a [u8]) -> Self {
+        V0 {
+            mangled,
+            at: 0,
+            out: String::new(),
+            skipping: false,
+            bound_lifetimes: 0,
+            depth: 0,
+            parsed: 0,
+        }
+    }
+
+    fn symbol(mut self) -> Option<String> {
+        // Only the first version of the mangling is known, which has no version number
+        if self.peek()?.is_ascii_digit() {
+            return None;
+        }
+        self.path(true)?;
+        // The crate the function was instantiated in, which demangled names leave out
+        if self.peek().is_some_and(|byte| byte.is_ascii_uppercase()) {
+            self.skipping(|parser| parser.path(false))?;
+        }
+        (self.at == self.mangled.len()).then_some(self.out)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.mangled.get(self.at).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let eaten = self.peek() == Some(byte);
+        self.at += usize::from(eaten);
+        eaten
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.at += 1;
+        Some(byte)
+    }
+
+    fn print(&mut self, text: &str) -> Option<()> {
+        if !self.skipping {
+            self.out.push_str(text);
+        }
+        (self.out.len() < MAX_LEN).then_some(())
+    }
+
+    fn skipping(&mut self, f: impl FnOnce(&mut Self) -> Option<()>) -> Option<()> {
+        let skipping = std::mem::replace(&mut self.skipping, true);
+        let result = f(self);
+        self.skipping = skipping;
+        result
+    }
+
+    /// Parses what `f` does, with a bound on the nesting.
+    fn nested(&mut self, f: impl FnOnce(&mut Self) -> Option<()>) -> Option<()> {
+        self.depth += 1;
+        self.parsed += 1;
+        (self.depth < MAX_DEPTH && self.parsed < MAX_LEN).then_some(())?;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// A `_`-terminated base-62 number, in which `_` alone is 0.
+    fn base62(&mut self) -> Option<u64> {
+        if self.eat(b'_') {
+            return Some(0);
+        }
+        let mut value: u64 = 0;
+        while !self.eat(b'_') {
+            let digit = match self.next()? {
+                byte @ b'0'..=b'9' => byte - b'0',
+                byte @ b'a'..=b'z' => byte - b'a' + 10,
+                byte @ b'A'..=b'Z' => byte - b'A' + 36,
+                _ => return None,
+            };
+            value = value.checked_mul(62)?.checked_add(u64::from(digit))?;
+        }
+        value.checked_add(1)
+    }
+
+    /// The number after `tag`, plus one, or 0 if there is no `tag`.
+    fn optional_base62(&mut self, tag: u8) -> Option<u64> {
+        if self.eat(tag) {
+            self.base62()?.checked_add(1)
+        } else {
+            Some(0)
+        }
+    }
+
+    fn identifier(&mut self) -> Option<&'a str> {
+        // Punycode, for identifiers that are not ASCII
+        if self.peek() == Some(b'u') {
+            return None;
+        }
+        let start = self.at;
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.at += 1;
+        }
+        let digits = std::str::from_utf8(&self.mangled[start..self.at]).ok()?;
+        let len: usize = digits.parse().ok()?;
+        self.eat(b'_');
+        let identifier = self.mangled.get(self.at..self.at + len)?;
+        self.at += len;
+        std::str::from_utf8(identifier).ok()
+    }
+
+    /// Parses what `f` does at the position a back reference points to, which must be before
+    /// the reference.
+    fn backref(&mut self, f: impl FnOnce(&mut Self) -> Option<()>) -> Option<()> {
+        let start = self.at;
+        self.eat(b'B').then_some(())?;
+        let target = usize::try_from(self.base62()?).ok()?;
+        (target < start).then_some(())?;
+        let resume = std::mem::replace(&mut self.at, target);
+        let result = self.nested(f);
+        self.at = resume;
+        result
+    }
+
+    fn path(&mut self, in_value: bool) -> Option<()> {
+        self.nested(|parser| parser.unguarded_path(in_value))
+    }
+
+    fn unguarded_path(&mut self, in_value: bool) -> Option<()> {
+        match self.next()? {
+            b'C' => {
+                self.optional_base62(b's')?;
+                let name = self.identifier()?;
+                self.print(name)
+            }
+            tag @ (b'M' | b'X') => {
+                self.optional_base62(b's')?;
+                self.skipping(|parser| parser.path(false))?;
+                self.print("<")?;
+                self.type_()?;
+                if tag == b'X' {
+                    self.print(" as ")?;
+                    self.path(false)?;
+                }
+                self.print(">")
+            }
+            b'Y' => {
+                self.print("<")?;
+                self.type_()?;
+                self.print(" as ")?;
+                self.path(false)?;
+                self.print(">")
+            }
+            b'N' => {
+                let namespace = self.next()?;
+                self.path(in_value)?;
+                let disambiguator = self.optional_base62(b's')?;
+                let name = self.identifier()?;
+                if namespace.is_ascii_uppercase() {
+                    let kind = match namespace {
+                        b'C' => "closure".to_owned(),
+                        b'S' => "shim".to_owned(),
+                        _ => char::from(namespace).to_string(),
+                    };
+                    let name = if name.is_empty() {
+                        String::new()
+                    } else {
+                        format!(":{name}")
+                    };
+                    self.print(&format!("::{{{kind}{name}#{disambiguator}}}"))
+                } else if name.is_empty() {
+                    Some(())
+                } else {
+                    self.print("::")?;
+                    self.print(name)
+                }
+            }
+            b'I' => {
+                self.path(in_value)?;
+                if in_value {
+                    self.print("::")?;
+                }
+                self.print("<")?;
+                let mut first = true;
+                while !self.eat(b'E') {
+                    if !first {
+                        self.print(", ")?;
+                    }
+                    first = false;
+                    self.generic_arg()?;
+                }
+                self.print(">")
+            }
+            b'B' => {
+                self.at -= 1;
+                self.backref(|parser| parser.path(in_value))
+            }
+            _ => None,
+        }
+    }
+
+    fn generic_arg(&mut self) -> Option<()> {
+        if self.eat(b'L') {
+            let lifetime = self.base62()?;
+            self.lifetime(lifetime)
+        } else if self.eat(b'K') {
+            self.const_()
+        } else {
+            self.type_()
+        }
+    }
+
+    fn lifetime(&mut self, lifetime: u64) -> Option<()> {
+        if lifetime == 0 {
+            return self.print("'_");
+        }
+        let depth = self.bound_lifetimes.checked_sub(lifetime)?;
+        match u8::try_from(depth) {
+            Ok(depth) if depth < 26 => self.print(&format!("'{}", char::from(b'a' + depth))),
+            _ => self.print(&format!("'_{depth}")),
+        }
+    }
+
+    /// Parses the `for<'a, ..>` lifetimes introduced by a binder, if there is one, and then
+    /// what `f` does, in which they are bound.
+    fn binder(&mut self, f: impl FnOnce(&mut Self) -> Option<()>) -> Option<()> {
+        let count = self.optional_base62(b'G')?;
+        if count > 0 {
+            self.print("for<")?;
+            for i in 0..count {
+                if i > 0 {
+                    self.print(", ")?;
+                }
+                self.bound_lifetimes += 1;
+                self.lifetime(1)?;
+            }
+            self.print("> ")?;
+        }
+        let result = f(self);
+        self.bound_lifetimes -= count;
+        result
+    }
+
+    fn type_(&mut self) -> Option<()> {
+        self.nested(|parser| parser.unguarded_type())
+    }
+
+    fn unguarded_type(&mut self) -> Option<()> {
+        let byte = self.peek()?;
+        if let Some(basic) = basic_type(byte) {
+            self.at += 1;
+            return self.print(basic);
+        }
+        match byte {
+            b'R' | b'Q' => {
+                self.at += 1;
+                self.print("&")?;
+                if self.eat(b'L') {
+                    let lifetime = self.base62()?;
+                    if lifetime != 0 {
+                        self.lifetime(lifetime)?;
+                        self.print(" ")?;
+                    }
+                }
+                if byte == b'Q' {
+                    self.print("mut ")?;
+                }
+                self.type_()
+            }
+            b'P' | b'O' => {
+                self.at += 1;
+                self.print(if byte == b'P' { "*const " } else { "*mut " })?;
+                self.type_()
+            }
+            b'A' | b'S' => {
+                self.at += 1;
+                self.print("[")?;
+                self.type_()?;
+                if byte == b'A' {
+                    self.print("; ")?;
+                    self.const_()?;
+                }
+                self.print("]")
+            }
+            b'T' => {
+                self.at += 1;
+                self.print("(")?;
+                let mut count = 0;
+                while !self.eat(b'E') {
+                    if count > 0 {
+                        self.print(", ")?;
+                    }
+                    self.type_()?;
+                    count += 1;
+                }
+                self.print(if count == 1 { ",)" } else { ")" })
+            }
+            b'F' => {
+                self.at += 1;
+                self.binder(|parser| parser.fn_sig())
+            }
+            b'D' => {
+                self.at += 1;
+                self.binder(|parser| parser.dyn_bounds())?;
+                self.eat(b'L').then_some(())?;
+                let lifetime = self.base62()?;
+                if lifetime != 0 {
+                    self.print(" + ")?;
+                    self.lifetime(lifetime)?;
+                }
+                Some(())
+            }
+            b'B' => self.backref(|parser| parser.type_()),
+            _ => self.path(false),
+        }
+    }
+
+    fn fn_sig(&mut self) -> Option<()> {
+        if self.eat(b'U') {
+            self.print("unsafe ")?;
+        }
+        if self.eat(b'K') {
+            let abi = if self.eat(b'C') {
+                "C".to_owned()
+            } else {
+                self.identifier()?.replace('_', "-")
+            };
+            self.print(&format!("extern \"{abi}\" "))?;
+        }
+        self.print("fn(")?;
+        let mut first = true;
+        while !self.eat(b'E') {
+            if !first {
+                self.print(", ")?;
+            }
+            first = false;
+            self.type_()?;
+        }
+        self.print(")")?;
+        if self.eat(b'u') {
+            Some(())
+        } else {
+            self.print(" -> ")?;
+            self.type_()
+        }
+    }
+
+    fn dyn_bounds(&mut self) -> Option<()> {
+        self.print("dyn ")?;
+        let mut first = true;
+        while !self.eat(b'E') {
+            if !first {
+                self.print(" + ")?;
+            }
+            first = false;
+            self.path(false)?;
+            let mut first_binding = true;
+            while self.eat(b'p') {
+                self.print(if first_binding { "<" } else { ", " })?;
+                first_binding = false;
+                let name = self.identifier()?;
+                self.print(name)?;
+                self.print(" = ")?;
+                self.type_()?;
+            }
+            if !first_binding {
+                self.print(">")?;
+            }
+        }
+        Some(())
+    }
+
+    fn const_(&mut self) -> Option<()> {
+        let byte = self.next()?;
+        match byte {
+            b'p' => self.print("_"),
+            b'B' => {
+                self.at -= 1;
+                self.backref(|parser| parser.const_())
+            }
+            b'a' | b's' | b'l' | b'x' | b'n' | b'i' | b'h' | b't' | b'm' | b'y' | b'o' | b'j'
+            | b'b' | b'c' => {
+                let negative = self.eat(b'n');
+                let start = self.at;
+                while self.peek().is_some_and(|byte| byte.is_ascii_hexdigit()) {
+                    self.at += 1;
+                }
+                let hex = std::str::from_utf8(&self.mangled[start..self.at]).ok()?;
+                self.eat(b'_').then_some(())?;
+                let value = u128::from_str_radix(hex, 16).ok();
+                let text = match (byte, value) {
+                    (b'b', Some(0)) => "false".to_owned(),
+                    (b'b', Some(1)) => "true".to_owned(),
+                    (b'c', Some(value)) => {
+                        let c = char::from_u32(u32::try_from(value).ok()?)?;
+                        format!("{:?}", c)
+                    }
+                    (b'b' | b'c', _) => return None,
+                    (_, Some(value)) if negative => format!("-{value}"),
+                    (_, Some(value)) => value.to_string(),
+                    (_, None) => format!("0x{hex}"),
+                };
+                self.print(&text)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The Rust type a v0 basic type code stands for.
+fn basic_type(code: u8) -> Option<&'static str> {
+    Some(match code {
+        b'a' => "i8",
+        b'b' => "bool",
+        b'c' => "char",
+        b'd' => "f64",
+        b'e' => "str",
+        b'f' => "f32",
+        b'h' => "u8",
+        b'i' => "isize",
+        b'j' => "usize",
+        b'l' => "i32",
+        b'm' => "u32",
+        b'n' => "i128",
+        b'o' => "u128",
+        b's' => "i16",
+        b't' => "u16",
+        b'u' => "()",
+        b'v' => "...",
+        b'x' => "i64",
+        b'y' => "u64",
+        b'z' => "!",
+        b'p' => "_",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::demangle;
+
+    #[test]
+    fn test_itanium() {
+        for (mangled, demangled) in [
+            ("_Z3foov", "foo()"),
+            ("_Z3fooic", "foo(int, char)"),
+            ("_ZN3foo3barEv", "foo::bar()"),
+            ("_ZNK3foo3barEv", "foo::bar() const"),
+            (
+                "_ZNSt6vectorIiSaIiEE9push_backERKi",
+                "std::vector<int, std::allocator<int>>::push_back(int const&)",
+            ),
+            (
+                "_ZNSt7__cxx1112basic_stringIcSt11char_traitsIcESaIcEEC1EPKcRKS3_",
+                "std::__cxx11::basic_string<char, std::char_traits<char>, std::allocator<char>>::\
+                 basic_string(char const*, std::allocator<char> const&)",
+            ),
+            (
+                "_ZSt4endlIcSt11char_traitsIcEERSt13basic_ostreamIT_T0_ES6_",
+                "std::basic_ostream<char, std::char_traits<char>>& std::endl<char, \
+                 std::char_traits<char>>(std::basic_ostream<char, std::char_traits<char>>&)",
+            ),
+            (
+                "_ZNKSt5ctypeIcE8do_widenEc",
+                "std::ctype<char>::do_widen(char) const",
+            ),
+            (
+                "_ZN9__gnu_cxx13new_allocatorIcED2Ev",
+                "__gnu_cxx::new_allocator<char>::~new_allocator()",
+            ),
+            ("_ZN3FooC2Ev", "Foo::Foo()"),
+            ("_ZN3FooplERKS_", "Foo::operator+(Foo const&)"),
+            ("_ZN3FoocviEv", "Foo::operator int()"),
+            ("_ZN3FoonwEm", "Foo::operator new(unsigned long)"),
+            ("_ZTV3Foo", "vtable for Foo"),
+            ("_ZTI3Foo", "typeinfo for Foo"),
+            ("_ZTS3Foo", "typeinfo name for Foo"),
+            ("_ZThn8_N3Foo3barEv", "non-virtual thunk to Foo::bar()"),
+            ("_ZZ3foovE1x", "foo()::x"),
+            ("_ZGVZ3foovE1x", "guard variable for foo()::x"),
+            ("_ZL3foov", "foo()"),
+            ("_ZN12_GLOBAL__N_13fooEv", "(anonymous namespace)::foo()"),
+            ("_ZN5Outer5InnerB5cxx11Ev", "Outer::Inner[abi:cxx11]()"),
+            ("_Z1fPFvvE", "f(void (*)())"),
+            ("_Z1fPFivEi", "f(int (*)(), int)"),
+            ("_Z1fRA3_i", "f(int (&) [3])"),
+            ("_Z1fM1AKFvvE", "f(void (A::*)() const)"),
+            ("_Z1fM1Ai", "f(int A::*)"),
+            ("_Z1fPKcz", "f(char const*, ...)"),
+            ("_Z1fIiEvT_", "void f<int>(int)"),
+            ("_Z1fILi3ELb1EEvv", "void f<3, true>()"),
+            (
+                "_ZZ4mainENKUlvE_clEv",
+                "main::{lambda()#1}::operator()() const",
+            ),
+            ("_ZN1A1fEv.cold", "A::f() [clone .cold]"),
+            (
+                "_ZN1A1fEv.isra.0.cold",
+                "A::f() [clone .isra.0] [clone .cold]",
+            ),
+        ] {
+            assert_eq!(demangle(mangled).as_deref(), Some(demangled), "{mangled}");
+        }
+
+        // Names from libstdc++ and LLVM, as `c++filt` demangles them but for `> >`
+        for (mangled, demangled) in [
+            (
+                "_ZStlsIdcSt11char_traitsIcEERSt13basic_ostreamIT0_T1_ES6_RKSt7complexIT_E",
+                "std::basic_ostream<char, std::char_traits<char>>& std::operator<< <double, \
+                 char, std::char_traits<char>>(std::basic_ostream<char, \
+                 std::char_traits<char>>&, std::complex<double> const&)",
+            ),
+            (
+                "_ZSt7getlineIcSt11char_traitsIcESaIcEERSt13basic_istreamIT_T0_ES7_RNSt7__cxx11\
+                 12basic_stringIS4_S5_T1_EES4_",
+                "std::basic_istream<char, std::char_traits<char>>& std::getline<char, \
+                 std::char_traits<char>, std::allocator<char>>(std::basic_istream<char, \
+                 std::char_traits<char>>&, std::__cxx11::basic_string<char, \
+                 std::char_traits<char>, std::allocator<char>>&, char)",
+            ),
+            (
+                "_ZGTtNKSt11logic_error4whatEv",
+                "transaction clone for std::logic_error::what() const",
+            ),
+            (
+                "_ZNSt8ios_base17register_callbackEPFvNS_5eventERS_iEi",
+                "std::ios_base::register_callback(void (*)(std::ios_base::event, \
+                 std::ios_base&, int), int)",
+            ),
+            (
+                "_ZNSt8ios_base7failureB5cxx11C1EPKcRKSt10error_code",
+                "std::ios_base::failure[abi:cxx11]::failure(char const*, \
+                 std::error_code const&)",
+            ),
+            (
+                "_ZNSt8ios_base7failureB5cxx11D0Ev",
+                "std::ios_base::failure[abi:cxx11]::~failure()",
+            ),
+            (
+                "_ZNKSs4findEPKcm",
+                "std::basic_string<char, std::char_traits<char>, \
+                 std::allocator<char>>::find(char const*, unsigned long) const",
+            ),
+            (
+                "_ZNSt6vectorIhSaIhEE12emplace_backIJhEEEvDpOT_",
+                "void std::vector<unsigned char, std::allocator<unsigned char>>::\
+                 emplace_back<unsigned char>(unsigned char&&)",
+            ),
+            (
+                "_ZN4llvm12hash_combineIJhhjEEENS_9hash_codeEDpRKT_",
+                "llvm::hash_code llvm::hash_combine<unsigned char, unsigned char, unsigned int>\
+                 (unsigned char const&, unsigned char const&, unsigned int const&)",
+            ),
+            (
+                "_ZN4llvm11logicalview7LVScope15traverseParentsEMS1_KFbvEMS1_FvvE",
+                "llvm::logicalview::LVScope::traverseParents(bool \
+                 (llvm::logicalview::LVScope::*)() const, void \
+                 (llvm::logicalview::LVScope::*)())",
+            ),
+            (
+                "_ZSt9__find_ifIPKSt10unique_ptrIN4llvm24ScheduleHazardRecognizerESt14default_\
+                 deleteIS2_EEN9__gnu_cxx5__ops10_Iter_predISt7_Mem_fnIMS2_KFbvEEEEET_SG_SG_T0_\
+                 St26random_access_iterator_tag",
+                "std::unique_ptr<llvm::ScheduleHazardRecognizer, \
+                 std::default_delete<llvm::ScheduleHazardRecognizer>> const* \
+                 std::__find_if<std::unique_ptr<llvm::ScheduleHazardRecognizer, \
+                 std::default_delete<llvm::ScheduleHazardRecognizer>> const*, \
+                 __gnu_cxx::__ops::_Iter_pred<std::_Mem_fn<bool \
+                 (llvm::ScheduleHazardRecognizer::*)() const>>>(std::unique_ptr<\
+                 llvm::ScheduleHazardRecognizer, \
+                 std::default_delete<llvm::ScheduleHazardRecognizer>> const*, \
+                 std::unique_ptr<llvm::ScheduleHazardRecognizer, \
+                 std::default_delete<llvm::ScheduleHazardRecognizer>> const*, \
+                 __gnu_cxx::__ops::_Iter_pred<std::_Mem_fn<bool \
+                 (llvm::ScheduleHazardRecognizer::*)() const>>, \
+                 std::random_access_iterator_tag)",
+            ),
+            (
+                "_ZTCN5clang7targets15RISCVTargetInfoE0_NS_10TargetInfoE",
+                "construction vtable for clang::TargetInfo-in-clang::targets::RISCVTargetInfo",
+            ),
+            (
+                "_ZN6icu_726number4impl10MicroPropsUt_D1Ev",
+                "icu_72::number::impl::MicroProps::{unnamed type#1}::~MicroProps()",
+            ),
+            ("_ZTIFvvE", "typeinfo for void ()"),
+            ("_ZTIDn", "typeinfo for decltype(nullptr)"),
+            ("_Z1fPFPFivEvE", "f(int (*(*)())())"),
+            ("_Z1fRA3_A4_Ki", "f(int const (&) [3][4])"),
+            ("_Z1fIRiEvOT_", "void f<int&>(int&)"),
+            ("_Z1fIJEEvDpT_", "void f<>()"),
+            ("_ZZ1fIiEvvE1x", "f<int>()::x"),
+            (
+                "_Z1fP1aP1bP1cP1dP1eP1fP1gP1hP1iP1jP1kP1lP1mP1nP1oP1pP1qP1rP1sP1tS10_",
+                "f(a*, b*, c*, d*, e*, f*, g*, h*, i*, j*, k*, l*, m*, n*, o*, p*, q*, r*, s*, \
+                 t*, s*)",
+            ),
+        ] {
+            assert_eq!(demangle(mangled).as_deref(), Some(demangled), "{mangled}");
+        }
+
+        for unknown in [
+            "malloc",
+            "_Z",
+            "_Z3foov trailing",
+            "_ZN3foo3bar",
+            "_Z1fIiEvT0_",
+            "_Z1fS_",
+            "_Z1fIXadL_Z1gvEEEvv",
+        ] {
+            assert_eq!(demangle(unknown), None, "{unknown}");
+        }
+    }
+
+    #[test]
+    fn test_rust() {
+        for (mangled, demangled) in [
+            (
+                "_ZN3std2io5stdio6_print17h1234567890abcdefE",
+                "std::io::stdio::_print",
+            ),
+            (
+                "_ZN4core3ptr42drop_in_place$LT$alloc..string..String$GT$17h0123456789abcdefE",
+                "core::ptr::drop_in_place<alloc::string::String>",
+            ),
+            (
+                "_ZN71_$LT$Test$u20$$u2b$$u20$$u27$static$u20$as$u20$foo..Bar$LT$Test$GT$$GT$\
+                 3bar17h930b740aa94f1d3aE",
+                "<Test + 'static as foo::Bar<Test>>::bar",
+            ),
+            (
+                "_ZN3foo3bar17h0123456789abcdefE.llvm.1234",
+                "foo::bar [clone .llvm.1234]",
+            ),
+            ("_RNvCs1234_7mycrate3foo", "mycrate::foo"),
+            ("_RNvC6_123foo3bar", "123foo::bar"),
+            (
+                "_RINvCs1234_7mycrate3fooNtB2_3BarE",
+                "mycrate::foo::<mycrate::Bar>",
+            ),
+            (
+                "_RINvCs1234_7mycrate3fooRShTlmEKj3_E",
+                "mycrate::foo::<&[u8], (i32, u32), 3>",
+            ),
+            (
+                "_RNvMNtCs1234_7mycrate3fooNtB2_3Bar3new",
+                "<mycrate::foo::Bar>::new",
+            ),
+            (
+                "_RNvXCs1234_7mycrateNtB2_3BarNtNtCs5678_4core3fmt7Display3fmt",
+                "<mycrate::Bar as core::fmt::Display>::fmt",
+            ),
+            ("_RNCNvCs1234_7mycrate4main0", "mycrate::main::{closure#0}"),
+            (
+                "_RINvCs1234_7mycrate3fooFUKCEuE",
+                "mycrate::foo::<unsafe extern \"C\" fn()>",
+            ),
+        ] {
+            assert_eq!(demangle(mangled).as_deref(), Some(demangled), "{mangled}");
+        }
+
+        for unknown in [
+            "_RNvCs1234_7mycrate",
+            "_RNvCu7mycrate3foo",
+            "_RNvB9_3foo",
+            "_R0C3foo",
+        ] {
+            assert_eq!(demangle(unknown), None, "{unknown}");
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Comparisons of two dependency closures, e.g. of a binary before and after a rebuild.
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::DependencyGraph;
+
+/// A library both closures load, but from different files, as reported in
+/// [`ClosureDiff::changed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChangedLibrary {
+    /// The soname the library is requested by.
+    pub soname: String,
+    /// Where the first closure found it, or `None` if it did not.
+    pub before: Option<PathBuf>,
+    /// Where the second closure found it, or `None` if it did not.
+    pub after: Option<PathBuf>,
+}
+
+/// The differences between two dependency closures, as computed by [`DependencyGraph::diff`].
+/// Libraries are told apart by the soname they are requested by, and each list is in the load
+/// order of the closure its libraries come from.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClosureDiff {
+    /// The libraries only the second closure loads, with where they were found, or `None` for
+    /// those not found and virtual objects.
+    pub added: Vec<(String, Option<PathBuf>)>,
+    /// The libraries only the first closure loads, like [`ClosureDiff::added`].
+    pub removed: Vec<(String, Option<PathBuf>)>,
+    /// The libraries both closures load, but that resolve to different paths, or that only one
+    /// of them found.
+    pub changed: Vec<ChangedLibrary>,
+}
+
+impl ClosureDiff {
+    /// Whether both closures load the same libraries from the same paths.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl DependencyGraph {
+    /// Compares the libraries this graph loads with those `other` loads, e.g. of the same
+    /// binary before and after a rebuild, or of two versions of a package. The analyzed files
+    /// themselves are left out. A soname requested by several objects counts once, with the
+    /// path ld.so loads it from first, as in [`DependencyGraph::to_ldd`].
+    pub fn diff(&self, other: &DependencyGraph) -> ClosureDiff {
+        let before = self.libraries();
+        let after = other.libraries();
+        let after_paths: HashMap<&str, &Option<PathBuf>> = after
+            .iter()
+            .map(|(soname, path)| (soname.as_str(), path))
+            .collect();
+        let mut diff = ClosureDiff::default();
+        for (soname, path) in &before {
+            match after_paths.get(soname.as_str()) {
+                None => diff.removed.push((soname.clone(), path.clone())),
+                Some(&after) if after != path => diff.changed.push(ChangedLibrary {
+                    soname: soname.clone(),
+                    before: path.clone(),
+                    after: after.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        let before_sonames: HashSet<&str> =
+            before.iter().map(|(soname, _)| soname.as_str()).collect();
+        diff.added = after
+            .iter()
+            .filter(|(soname, _)| !before_sonames.contains(soname.as_str()))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /// Each soname the analyzed file loads, in load order, with the path it is first loaded
+    /// from.
+    fn libraries(&self) -> Vec<(String, Option<PathBuf>)> {
+        self.library_nodes()
+            .into_iter()
+            .map(|node| (node.soname.clone(), node.path().map(PathBuf::from)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChangedLibrary;
+    use crate::{DependencyGraph, DependencyNode, ResolveError, Resolved};
+    use std::path::PathBuf;
+
+    /// An app needing each library at its path, or a missing one for `None`.
+    fn graph(libraries: &[(&str, Option<&str>)]) -> DependencyGraph {
+        let mut graph = DependencyGraph::new(DependencyNode::new(
+            "app",
+            Ok(Resolved::Found("/opt/app".into())),
+        ));
+        let root = graph.root();
+        for (soname, path) in libraries {
+            let resolution = path.map(|path| Resolved::Found(PathBuf::from(path))).ok_or(
+                ResolveError::NotFound {
+                    needed_by: PathBuf::from("/opt/app"),
+                },
+            );
+            let node = graph.add_node(DependencyNode::new(*soname, resolution));
+            graph.add_edge(root, node);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = graph(&[
+            ("liba.so.1", Some("/lib/liba.so.1")),
+            ("libb.so.1", Some("/lib/libb.so.1")),
+            ("libgone.so.1", Some("/lib/libgone.so.1")),
+            ("libc.so.6", Some("/lib/libc.so.6")),
+        ]);
+        let after = graph(&[
+            ("libnew.so.1", None),
+            ("liba.so.1", Some("/opt/lib/liba.so.1")),
+            ("libb.so.1", Some("/lib/libb.so.1")),
+            ("libc.so.6", None),
+        ]);
+        let diff = before.diff(&after);
+        assert_eq!(diff.added, [("libnew.so.1".to_owned(), None)]);
+        let gone = Some(PathBuf::from("/lib/libgone.so.1"));
+        assert_eq!(diff.removed, [("libgone.so.1".to_owned(), gone)]);
+        assert_eq!(
+            diff.changed,
+            [
+                ChangedLibrary {
+                    soname: "liba.so.1".to_owned(),
+                    before: Some(PathBuf::from("/lib/liba.so.1")),
+                    after: Some(PathBuf::from("/opt/lib/liba.so.1")),
+                },
+                ChangedLibrary {
+                    soname: "libc.so.6".to_owned(),
+                    before: Some(PathBuf::from("/lib/libc.so.6")),
+                    after: None,
+                },
+            ]
+        );
+        assert!(!diff.is_empty());
+        assert!(before.diff(&before).is_empty());
+    }
+}
@@ -0,0 +1,344 @@
+//! Files read relative to open directory handles with `openat` and its relatives, for
+//! analyzers sandboxed with Landlock or seccomp that cannot look up absolute paths.
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::filesystem::{FileKind, FileMetadata, FileStat, FileSystem};
+use crate::mount_fs::MountFs;
+use crate::{ElfFile, ResolverConfig};
+
+/// A tree of files read through open directory handles, with the handle of its root taken as
+/// `/` and those of other directories mounted at paths of their own, set with
+/// [`ResolverConfig::filesystem`]. Every file is opened relative to a handle with `openat`,
+/// one directory at a time and without following symlinks, which this filesystem follows
+/// within the tree itself, so nothing outside the directories is ever read and no absolute
+/// path is looked up.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use elf_dynamic_lib_getter::{DirFs, ElfFile, ResolverConfig};
+///
+/// // Opened before the sandbox is entered
+/// let root = std::fs::File::open("/srv/rootfs").unwrap();
+/// let fs = DirFs::new(root);
+/// let config = ResolverConfig::new().use_env(false).filesystem(Arc::new(fs));
+/// let libs = ElfFile::with_config("/usr/bin/app", config).get_libs_full_paths();
+/// ```
+#[derive(Debug)]
+pub struct DirFs(MountFs);
+
+/// The files under one directory handle, by paths that have no symlinks along them.
+#[derive(Debug)]
+struct DirHandle(OwnedFd);
+
+impl DirFs {
+    /// The tree under the directory `root`, which is opened for reading or with `O_PATH`.
+    pub fn new<D: Into<OwnedFd>>(root: D) -> Self {
+        DirFs(MountFs::over(Arc::new(DirHandle(root.into()))))
+    }
+
+    /// The tree under the directory at `root`, opening it now.
+    pub fn open<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        Ok(DirFs::new(File::open(root)?))
+    }
+
+    /// Mounts the tree under the directory `dir` at `at`, over what the root has there.
+    pub fn mount<P: Into<PathBuf>, D: Into<OwnedFd>>(self, at: P, dir: D) -> Self {
+        let dir = Arc::new(DirHandle(dir.into()));
+        DirFs(self.0.mount_fs(at, dir, "/"))
+    }
+}
+
+impl FileSystem for DirFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.0.read(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.0.metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.0.read_link(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        self.0.read_dir(path)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        self.0.stat(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.0.exists(path)
+    }
+}
+
+impl ElfFile {
+    /// Creates an [`ElfFile`] for the file at `path` under the directory `dir`, with every
+    /// file read relative to `dir` as a [`DirFs`] rooted there, which `config` is set to read.
+    /// Found libraries are reported by their paths under `dir`, which `path` may or may not
+    /// start with `/`.
+    pub fn at<D, P>(dir: D, path: P, config: ResolverConfig) -> Self
+    where
+        D: Into<OwnedFd>,
+        P: AsRef<Path>,
+    {
+        let config = config.filesystem(Arc::new(DirFs::new(dir)));
+        ElfFile::with_config(Path::new("/").join(path), config)
+    }
+}
+
+impl DirHandle {
+    /// The directory `path` is in, opened by walking down to it, and the name of `path` in
+    /// it, `.` for the root.
+    fn parent(&self, path: &Path) -> io::Result<(Option<OwnedFd>, CString)> {
+        let mut names = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => names.push(name),
+                Component::RootDir | Component::CurDir => {}
+                // Symlinks are followed before, so a parent directory would leave the tree
+                Component::ParentDir | Component::Prefix(_) => {
+                    return Err(io::ErrorKind::PermissionDenied.into())
+                }
+            }
+        }
+        let Some(name) = names.pop() else {
+            return Ok((None, c_name(OsStr::new("."))?));
+        };
+        let mut dir: Option<OwnedFd> = None;
+        for component in names {
+            let flags = libc::O_PATH | libc::O_DIRECTORY;
+            dir = Some(self.open_in(dir.as_ref(), &c_name(component)?, flags)?);
+        }
+        Ok((dir, c_name(name)?))
+    }
+
+    /// Opens `name` in `dir`, or in the root, without following a symlink.
+    fn open_in(&self, dir: Option<&OwnedFd>, name: &CString, flags: i32) -> io::Result<OwnedFd> {
+        let flags = flags | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        // SAFETY: `name` is a C string, and the directory is open for as long as the call
+        let fd = unsafe { libc::openat(self.fd(dir), name.as_ptr(), flags) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `openat` returned a new descriptor, which nothing else owns
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Opens `path` without following a symlink.
+    fn open(&self, path: &Path, flags: i32) -> io::Result<OwnedFd> {
+        let (dir, name) = self.parent(path)?;
+        self.open_in(dir.as_ref(), &name, flags)
+    }
+
+    /// The `stat` of `path`, without following a symlink.
+    fn lstat(&self, path: &Path) -> io::Result<libc::stat> {
+        let (dir, name) = self.parent(path)?;
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        let flags = libc::AT_SYMLINK_NOFOLLOW;
+        // SAFETY: `name` is a C string, and `stat` is written by a successful call
+        let result = unsafe {
+            libc::fstatat(
+                self.fd(dir.as_ref()),
+                name.as_ptr(),
+                stat.as_mut_ptr(),
+                flags,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the call succeeded
+        Ok(unsafe { stat.assume_init() })
+    }
+
+    /// The descriptor of `dir`, or of the root.
+    fn fd(&self, dir: Option<&OwnedFd>) -> RawFd {
+        dir.unwrap_or(&self.0).as_raw_fd()
+    }
+}
+
+impl FileSystem for DirHandle {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        File::from(self.open(path, libc::O_RDONLY)?).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let stat = self.lstat(path)?;
+        let kind = match stat.st_mode & libc::S_IFMT {
+            libc::S_IFDIR => FileKind::Dir,
+            libc::S_IFLNK => FileKind::Symlink,
+            _ => FileKind::File,
+        };
+        Ok(FileMetadata {
+            kind,
+            len: stat.st_size as u64,
+            mode: stat.st_mode & 0o7777,
+        })
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let (dir, name) = self.parent(path)?;
+        let mut buf = vec![0u8; 256];
+        loop {
+            // SAFETY: `name` is a C string, and at most `buf.len()` bytes are written to `buf`
+            let len = unsafe {
+                libc::readlinkat(
+                    self.fd(dir.as_ref()),
+                    name.as_ptr(),
+                    buf.as_mut_ptr().cast(),
+                    buf.len(),
+                )
+            };
+            if len < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // A target that fills the buffer may have been cut short
+            if (len as usize) < buf.len() {
+                buf.truncate(len as usize);
+                return Ok(PathBuf::from(OsString::from_vec(buf)));
+            }
+            buf.resize(buf.len() * 2, 0);
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        let fd = self.open(path, libc::O_RDONLY | libc::O_DIRECTORY)?;
+        // SAFETY: the stream takes over the descriptor, which is closed with it
+        let stream = unsafe { libc::fdopendir(fd.as_raw_fd()) };
+        if stream.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        std::mem::forget(fd);
+        let mut names = Vec::new();
+        loop {
+            // SAFETY: the stream is open, and the entry is read before the next call
+            let entry = unsafe { libc::readdir(stream) };
+            if entry.is_null() {
+                break;
+            }
+            // SAFETY: `d_name` of an entry readdir returned is a C string
+            let name = unsafe { std::ffi::CStr::from_ptr((*entry).d_name.as_ptr()) };
+            let name = name.to_bytes();
+            if name != b"." && name != b".." {
+                names.push(OsStr::from_bytes(name).to_owned());
+            }
+        }
+        // SAFETY: the stream is open, and not used afterwards
+        unsafe { libc::closedir(stream) };
+        Ok(names)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        let stat = self.lstat(path)?;
+        let since_epoch = Duration::new(stat.st_mtime as u64, stat.st_mtime_nsec as u32);
+        Ok(FileStat {
+            len: stat.st_size as u64,
+            mode: stat.st_mode & 0o7777,
+            uid: Some(stat.st_uid),
+            gid: Some(stat.st_gid),
+            modified: (stat.st_mtime >= 0).then(|| SystemTime::UNIX_EPOCH + since_epoch),
+        })
+    }
+}
+
+/// `name` as a C string, an error if it has a NUL byte.
+fn c_name(name: &OsStr) -> io::Result<CString> {
+    CString::new(name.as_bytes()).map_err(|_| io::ErrorKind::InvalidInput.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DirFs;
+    use crate::test_util::ElfBuilder;
+    use crate::{ElfFile, FileKind, FileSystem, ResolverConfig};
+    use std::fs::{self, File};
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_dir_fs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let lib = root.join("usr/lib");
+        fs::create_dir_all(&lib).unwrap();
+        symlink("usr/lib", root.join("lib")).unwrap();
+        // Absolute symlinks lead to the root of the tree, not the host's
+        symlink("/lib/libfoo.so.1", lib.join("libfoo.so")).unwrap();
+        ElfBuilder::new().write(&lib, "libfoo.so.1");
+        // Nothing outside the tree is read through a parent directory or a symlink
+        fs::write(dir.path().join("secret"), "outside").unwrap();
+        symlink(dir.path().join("secret"), root.join("escape")).unwrap();
+
+        let fs = DirFs::open(&root).unwrap();
+        assert_eq!(
+            fs.metadata(Path::new("/lib")).unwrap().kind,
+            FileKind::Symlink
+        );
+        assert_eq!(fs.metadata(Path::new("/")).unwrap().kind, FileKind::Dir);
+        assert!(fs.exists(Path::new("/lib/libfoo.so")));
+        assert_eq!(
+            fs.read(Path::new("/lib/libfoo.so")).unwrap(),
+            fs::read(lib.join("libfoo.so.1")).unwrap()
+        );
+        assert!(fs.stat(Path::new("/lib/libfoo.so")).unwrap().uid.is_some());
+        let mut names = fs.read_dir(Path::new("/usr/lib")).unwrap();
+        names.sort();
+        assert_eq!(names, ["libfoo.so", "libfoo.so.1"]);
+        assert!(fs.read(Path::new("/escape")).is_err());
+        assert!(fs.read(Path::new("/../secret")).is_err());
+
+        let vendor = dir.path().join("vendor");
+        ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .needed("libbar.so.2")
+            .write(&vendor, "app");
+        ElfBuilder::new().write(&vendor, "libbar.so.2");
+        let fs = fs.mount("/opt/app", File::open(&vendor).unwrap());
+        let libs = ElfFile::with_config(
+            "/opt/app/app",
+            ResolverConfig::new()
+                .use_env(false)
+                .without_default_dirs()
+                .with_search_dirs(["/lib", "/opt/app"])
+                .filesystem(std::sync::Arc::new(fs)),
+        )
+        .get_libs_full_paths()
+        .unwrap();
+        assert_eq!(
+            libs,
+            [
+                PathBuf::from("/lib/libfoo.so.1"),
+                PathBuf::from("/opt/app/libbar.so.2")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_at() {
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .runpath("$ORIGIN")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let file = ElfFile::at(File::open(dir.path()).unwrap(), "app", config);
+        assert_eq!(
+            file.get_libs_full_paths().unwrap(),
+            [PathBuf::from("/libfoo.so.1")]
+        );
+    }
+}
@@ -0,0 +1,513 @@
+//! A file remembering what ELF files say about their dependencies across runs.
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::dlopen_note::DlopenFeature;
+use crate::os_str::{OsStrExt, OsStringExt};
+use crate::parse::{Arch, ElfType, OsAbi, ParsedObject};
+use crate::{DlopenPriority, DynamicFlags, DynamicFlags1, Error};
+
+/// The first bytes of a cache file, followed by the version of its format.
+const MAGIC: &[u8; 8] = b"DLFCACHE";
+const VERSION: u32 = 5;
+
+/// Remembers what the ELF files a [`Resolver`](crate::Resolver) reads say about their
+/// dependencies, in a file, so later runs over mostly unchanged systems, like nightly audits
+/// or CI jobs, don't read them again. Set it with
+/// [`ResolverConfig::disk_cache`](crate::ResolverConfig::disk_cache), and
+/// [save](DiskCache::save) it once done. A cache can be shared by resolvers on many threads.
+///
+/// Files are remembered by path, along with their device, inode, size and modification and
+/// change times, and are read again if any of those changed or they are
+/// [invalidated](DiskCache::invalidate). Besides the parsed files, the
+/// headers of candidate libraries checked for their architecture are remembered. Where
+/// libraries were found is not, since a library added to any searched directory would change
+/// it. Files read through a [`FileSystem`](crate::FileSystem) other than the host's, or given
+/// in memory, are not remembered.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use elf_dynamic_lib_getter::{DiskCache, ElfFile, ResolverConfig};
+///
+/// let cache = Arc::new(DiskCache::open("/var/cache/lib-audit.cache")?);
+/// let config = ResolverConfig::new().disk_cache(cache.clone());
+/// let libs = ElfFile::with_config("/usr/bin/ls", config).get_libs_full_paths()?;
+/// cache.save()?;
+/// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct DiskCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<PathBuf, Entry>>,
+}
+
+/// What is remembered about one file.
+#[derive(Debug, Clone)]
+struct Entry {
+    stamp: Stamp,
+    arch: Arch,
+    os_abi: OsAbi,
+    /// The whole file, if it was parsed rather than only checked.
+    object: Option<Arc<ParsedObject>>,
+}
+
+/// The metadata that changes when a file is replaced or modified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Stamp {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    mtime: (i64, i64),
+    ctime: (i64, i64),
+}
+
+impl Stamp {
+    /// The stamp of the file at `path`, following symlinks, or `None` if it can't be read.
+    #[cfg(unix)]
+    pub(crate) fn of(path: &Path) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            size: metadata.size(),
+            mtime: (metadata.mtime(), metadata.mtime_nsec()),
+            ctime: (metadata.ctime(), metadata.ctime_nsec()),
+        })
+    }
+
+    /// The stamp of the file at `path`, which without Unix metadata is only its size and
+    /// modification time.
+    #[cfg(not(unix))]
+    pub(crate) fn of(path: &Path) -> Option<Self> {
+        use std::time::UNIX_EPOCH;
+
+        let metadata = fs::metadata(path).ok()?;
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            dev: 0,
+            ino: 0,
+            size: metadata.len(),
+            mtime: (mtime.as_secs() as i64, i64::from(mtime.subsec_nanos())),
+            ctime: (0, 0),
+        })
+    }
+}
+
+impl DiskCache {
+    /// The cache stored in the file at `path`, or an empty one that will be saved there if
+    /// there is no such file. A file that is not a cache of this version, as after an upgrade,
+    /// also gives an empty cache, to be overwritten.
+    /// # Return Value [Err]
+    /// [`Error::Io`] if the file exists but can't be read.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self, Error> {
+        let path = path.into();
+        let entries = match fs::read(&path) {
+            Ok(data) => decode(&data).unwrap_or_default(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(source) => return Err(Error::Io { path, source }),
+        };
+        Ok(Self {
+            path,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Writes the cache to its file, replacing it as a whole so that a cache being read is
+    /// never partly written. Files that changed or were removed since they were remembered are
+    /// left out.
+    /// # Return Value [Err]
+    /// [`Error::Write`] if the file can't be written.
+    pub fn save(&self) -> Result<(), Error> {
+        let data = {
+            let mut entries = self.lock();
+            entries.retain(|path, entry| Stamp::of(path) == Some(entry.stamp));
+            encode(&entries)
+        };
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        let write_error = |source| Error::Write {
+            path: self.path.clone(),
+            source,
+        };
+        fs::write(&temp, data).map_err(write_error)?;
+        fs::rename(&temp, &self.path).map_err(write_error)
+    }
+
+    /// The file the cache is stored in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of files remembered.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no file is remembered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets the file at `path`, so it is read again by the next resolver needing it even if
+    /// it seems unchanged. Returns whether it was remembered.
+    pub fn invalidate<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.lock().remove(path.as_ref()).is_some()
+    }
+
+    /// Forgets every file.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// The parsed file at `path`, if it was remembered when it had the stamp `stamp` it has
+    /// now.
+    pub(crate) fn object(&self, path: &Path, stamp: Stamp) -> Option<Arc<ParsedObject>> {
+        self.fresh(path, stamp)?.object
+    }
+
+    /// The architecture and OS ABI of the file at `path`, if it was remembered when it had the
+    /// stamp `stamp` it has now.
+    pub(crate) fn header(&self, path: &Path, stamp: Stamp) -> Option<(Arch, OsAbi)> {
+        let entry = self.fresh(path, stamp)?;
+        Some((entry.arch, entry.os_abi))
+    }
+
+    /// Remembers `object`, parsed from `path` when it had the stamp `stamp`.
+    pub(crate) fn insert_object(&self, path: &Path, stamp: Stamp, object: Arc<ParsedObject>) {
+        let entry = Entry {
+            stamp,
+            arch: object.arch,
+            os_abi: object.os_abi,
+            object: Some(object),
+        };
+        self.lock().insert(path.to_owned(), entry);
+    }
+
+    /// Remembers the header of the file at `path`, read when it had the stamp `stamp`, unless
+    /// the whole file is remembered.
+    pub(crate) fn insert_header(&self, path: &Path, stamp: Stamp, (arch, os_abi): (Arch, OsAbi)) {
+        let mut entries = self.lock();
+        let parsed = entries
+            .get(path)
+            .is_some_and(|entry| entry.stamp == stamp && entry.object.is_some());
+        if !parsed {
+            let entry = Entry {
+                stamp,
+                arch,
+                os_abi,
+                object: None,
+            };
+            entries.insert(path.to_owned(), entry);
+        }
+    }
+
+    /// The entry for `path`, if it was remembered with the stamp `stamp`.
+    fn fresh(&self, path: &Path, stamp: Stamp) -> Option<Entry> {
+        let entry = self.lock().get(path).cloned()?;
+        (entry.stamp == stamp).then_some(entry)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, Entry>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// The cache file holding `entries`: the magic and version, the number of entries, then each
+/// entry, with little-endian integers and byte strings prefixed by their length.
+fn encode(entries: &HashMap<PathBuf, Entry>) -> Vec<u8> {
+    let mut out = Writer(MAGIC.to_vec());
+    out.u32(VERSION);
+    out.u64(entries.len() as u64);
+    for (path, entry) in entries {
+        out.bytes(path.as_os_str().as_bytes());
+        let Stamp {
+            dev,
+            ino,
+            size,
+            mtime,
+            ctime,
+        } = entry.stamp;
+        for value in [dev, ino, size] {
+            out.u64(value);
+        }
+        for value in [mtime.0, mtime.1, ctime.0, ctime.1] {
+            out.u64(value as u64);
+        }
+        let Arch {
+            is_64_bit,
+            big_endian,
+            machine,
+        } = entry.arch;
+        out.u16(machine);
+        out.0.push(u8::from(is_64_bit) | u8::from(big_endian) << 1);
+        out.0
+            .extend_from_slice(&[entry.os_abi.os_abi, entry.os_abi.version]);
+        let Some(object) = &entry.object else {
+            out.0.push(0);
+            continue;
+        };
+        out.0.push(1 | u8::from(object.is_static) << 1);
+        out.u16(object.elf_type.e_type());
+        out.u32(object.flags.bits());
+        out.u32(object.flags_1.bits());
+        for string in [
+            &object.interpreter,
+            &object.soname,
+            &object.rpath,
+            &object.runpath,
+        ] {
+            out.optional(string.as_ref().map(|string| string.as_bytes()));
+        }
+        out.optional(object.build_id.as_ref().map(|id| id.as_bytes()));
+        for list in [&object.needed, &object.filters, &object.auxiliary] {
+            out.u64(list.len() as u64);
+            for string in list {
+                out.bytes(string.as_bytes());
+            }
+        }
+        out.u64(object.dlopen.len() as u64);
+        for feature in &object.dlopen {
+            let priority = DlopenPriority::ALL
+                .iter()
+                .position(|&p| p == feature.priority);
+            out.0.push(priority.unwrap_or_default() as u8);
+            out.u64(feature.sonames.len() as u64);
+            for soname in &feature.sonames {
+                out.bytes(soname.as_bytes());
+            }
+        }
+    }
+    out.0
+}
+
+/// The entries of the cache file `data`, or `None` if it is not one of this version.
+fn decode(data: &[u8]) -> Option<HashMap<PathBuf, Entry>> {
+    let mut reader = Reader(data.strip_prefix(MAGIC)?);
+    if reader.u32()? != VERSION {
+        return None;
+    }
+    let count = reader.u64()?;
+    let mut entries = HashMap::new();
+    for _ in 0..count {
+        let path = PathBuf::from(reader.os_string()?);
+        let (dev, ino, size) = (reader.u64()?, reader.u64()?, reader.u64()?);
+        let mut times = [0; 4];
+        for time in &mut times {
+            *time = reader.u64()? as i64;
+        }
+        let stamp = Stamp {
+            dev,
+            ino,
+            size,
+            mtime: (times[0], times[1]),
+            ctime: (times[2], times[3]),
+        };
+        let machine = reader.u16()?;
+        let [class, os_abi, version, parsed] = reader.array()?;
+        let arch = Arch {
+            is_64_bit: class & 1 != 0,
+            big_endian: class & 2 != 0,
+            machine,
+        };
+        let os_abi = OsAbi { os_abi, version };
+        let object = if parsed & 1 != 0 {
+            let elf_type = ElfType::from_e_type(reader.u16()?);
+            let mut object = ParsedObject::new(arch, os_abi, elf_type, None);
+            object.is_static = parsed & 2 != 0;
+            object.flags = DynamicFlags::from_bits_retain(reader.u32()?);
+            object.flags_1 = DynamicFlags1::from_bits_retain(reader.u32()?);
+            object.interpreter = reader.optional()?;
+            object.soname = reader.optional()?;
+            object.rpath = reader.optional()?;
+            object.runpath = reader.optional()?;
+            let build_id = reader.optional()?;
+            object.build_id = build_id.map(|id| id.into_string().ok()).unwrap_or_default();
+            for list in [
+                &mut object.needed,
+                &mut object.filters,
+                &mut object.auxiliary,
+            ] {
+                for _ in 0..reader.u64()? {
+                    list.push(reader.os_string()?);
+                }
+            }
+            for _ in 0..reader.u64()? {
+                let [priority] = reader.array()?;
+                let priority = *DlopenPriority::ALL.get(usize::from(priority))?;
+                let sonames = (0..reader.u64()?)
+                    .map(|_| reader.os_string()?.into_string().ok())
+                    .collect::<Option<_>>()?;
+                object.dlopen.push(DlopenFeature { sonames, priority });
+            }
+            Some(Arc::new(object))
+        } else {
+            None
+        };
+        let entry = Entry {
+            stamp,
+            arch,
+            os_abi,
+            object,
+        };
+        entries.insert(path, entry);
+    }
+    reader.0.is_empty().then_some(entries)
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn optional(&mut self, bytes: Option<&[u8]>) {
+        match bytes {
+            Some(bytes) => {
+                self.0.push(1);
+                self.bytes(bytes);
+            }
+            None => self.0.push(0),
+        }
+    }
+}
+
+/// Reads the values [`Writer`] writes from the front of a slice.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk()?;
+        self.0 = rest;
+        Some(*bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn os_string(&mut self) -> Option<OsString> {
+        let len = usize::try_from(self.u64()?).ok()?;
+        if len > self.0.len() {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(OsString::from_vec(bytes.to_vec()))
+    }
+
+    fn optional(&mut self) -> Option<Option<OsString>> {
+        match self.array()? {
+            [0] => Some(None),
+            [1] => self.os_string().map(Some),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, DiskCache, Stamp};
+    use crate::parse::parse_bytes;
+    use crate::test_util::ElfBuilder;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = ElfBuilder::new()
+            .interpreter("/lib64/ld-linux-x86-64.so.2")
+            .soname("libfoo.so.1")
+            .needed("libbar.so.2")
+            .needed("libbaz.so.3")
+            .runpath("$ORIGIN")
+            .auxiliary("libaux.so.1")
+            .build_id(&[0xab, 0xcd])
+            .build();
+        let lib = dir.path().join("libfoo.so.1");
+        std::fs::write(&lib, &data).unwrap();
+        let object = Arc::new(parse_bytes(&lib, &data).unwrap());
+        let header = dir.path().join("libother.so.1");
+        std::fs::write(&header, ElfBuilder::new().elf32().build()).unwrap();
+
+        let cache = DiskCache::open(dir.path().join("cache")).unwrap();
+        assert!(cache.is_empty());
+        cache.insert_object(&lib, Stamp::of(&lib).unwrap(), object.clone());
+        let elf32 = parse_bytes(&header, &ElfBuilder::new().elf32().build()).unwrap();
+        let elf32 = (elf32.arch, elf32.os_abi);
+        cache.insert_header(&header, Stamp::of(&header).unwrap(), elf32);
+        cache.save().unwrap();
+
+        let reopened = DiskCache::open(cache.path()).unwrap();
+        assert_eq!(reopened.len(), 2);
+        let (lib_stamp, header_stamp) = (Stamp::of(&lib).unwrap(), Stamp::of(&header).unwrap());
+        let cached = reopened.object(&lib, lib_stamp).unwrap();
+        assert_eq!(format!("{cached:?}"), format!("{object:?}"));
+        assert!(reopened.object(&header, header_stamp).is_none());
+        assert_eq!(reopened.header(&header, header_stamp), Some(elf32));
+        assert_eq!(
+            reopened.header(&lib, lib_stamp),
+            Some((object.arch, object.os_abi))
+        );
+        let forgotten = DiskCache::open(cache.path()).unwrap();
+        assert!(forgotten.invalidate(&header));
+        assert!(!forgotten.invalidate(&header));
+        assert_eq!(forgotten.len(), 1);
+
+        // Changed and removed files are read again, and forgotten when saving
+        std::fs::write(&lib, [&data[..], b"\0"].concat()).unwrap();
+        std::fs::remove_file(&header).unwrap();
+        assert!(reopened.object(&lib, Stamp::of(&lib).unwrap()).is_none());
+        reopened.save().unwrap();
+        assert!(DiskCache::open(cache.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_malformed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("libfoo.so.1");
+        let data = ElfBuilder::new().needed("libbar.so.1").build();
+        std::fs::write(&lib, &data).unwrap();
+        let cache = DiskCache::open(dir.path().join("cache")).unwrap();
+        let object = Arc::new(parse_bytes(&lib, &data).unwrap());
+        cache.insert_object(&lib, Stamp::of(&lib).unwrap(), object);
+        let encoded = encode(&cache.lock());
+        assert!(decode(&encoded).is_some());
+        for len in 0..encoded.len() {
+            assert!(decode(&encoded[..len]).is_none());
+        }
+
+        std::fs::write(cache.path(), b"not a cache").unwrap();
+        assert!(DiskCache::open(cache.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! Guessing which libraries objects load at run time with `dlopen`, which their `DT_NEEDED`
+//! entries don't list, from the strings in their read-only data.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::object::{Object, Stream};
+use crate::os_str::OsStrExt;
+use crate::parse::parse_error;
+use crate::{
+    DependencyGraph, DependencyKind, DependencyNode, DlopenPriority, Error, Frame, Resolution,
+    ResolveError, Resolved, Resolver, Traversal,
+};
+
+/// The sections whose strings are scanned, along with those whose names start with these
+/// followed by a dot, like `.rodata.str1.1`.
+const SECTIONS: &[&str] = &[".rodata", ".data.rel.ro"];
+
+/// A library an object may load at run time, as reported by
+/// [`ElfFile::possible_dlopen_libraries`](crate::ElfFile::possible_dlopen_libraries).
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DlopenCandidate {
+    /// The name as the object holds it, a soname like `libfoo.so.1` or a path, with bytes that
+    /// are not UTF-8 replaced with `U+FFFD`.
+    pub name: String,
+    /// The object whose read-only data holds the name.
+    pub needed_by: PathBuf,
+    /// Where `dlopen` would find the library if called by that object, or `None` if it was
+    /// not looked for.
+    pub resolution: Option<Resolution>,
+}
+
+/// The strings looking like the name or path of a shared library in the read-only data of the
+/// ELF file `reader` reads, in order and without repeats. Files without section headers hold
+/// none as far as this is concerned. `path` is only used for error reporting.
+pub(crate) fn library_names<R: Read + Seek>(
+    path: &Path,
+    reader: R,
+) -> Result<Vec<OsString>, Error> {
+    let parse_error = |source| parse_error(path, source);
+    let mut elf = Object::open(Stream(reader)).map_err(parse_error)?;
+    let is_scanned = |name: &[u8]| {
+        SECTIONS.iter().any(|section| {
+            name.strip_prefix(section.as_bytes())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(b"."))
+        })
+    };
+    let scanned = elf.sections_named(is_scanned).map_err(parse_error)?;
+    let mut seen = HashSet::new();
+    let mut names = Vec::new();
+    for shdr in scanned {
+        let data = elf.section_data(&shdr).map_err(parse_error)?;
+        for string in data.split(|&byte| byte == 0) {
+            if looks_like_library(string) && seen.insert(string.to_vec()) {
+                names.push(OsStr::from_bytes(string).to_owned());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Whether `string` looks like the name or path of a shared library: a file name ending in
+/// `.so`, possibly followed by a numeric version like `.so.1.2`, without whitespace, format
+/// directives or wildcards.
+pub(crate) fn looks_like_library(string: &[u8]) -> bool {
+    let is_plain = |&byte: &u8| byte.is_ascii_graphic() && !b"%*?\"'<>|\\".contains(&byte);
+    if !string.iter().all(is_plain) {
+        return false;
+    }
+    let name = match string.iter().rposition(|&byte| byte == b'/') {
+        Some(slash) => &string[slash + 1..],
+        None => string,
+    };
+    let is_version = |rest: &[u8]| {
+        rest.is_empty()
+            || rest.strip_prefix(b".").is_some_and(|version| {
+                version
+                    .split(|&byte| byte == b'.')
+                    .all(|part| !part.is_empty() && part.iter().all(u8::is_ascii_digit))
+            })
+    };
+    let is_stem = |stem: &[u8]| {
+        stem.first().is_some_and(u8::is_ascii_alphanumeric)
+            && stem
+                .iter()
+                .all(|&byte| byte.is_ascii_alphanumeric() || b"_-+.".contains(&byte))
+    };
+    // Any ".so" may end the stem, as in "libfoo.so.so.1"
+    (0..name.len().saturating_sub(2))
+        .rev()
+        .filter(|&start| name[start..].starts_with(b".so"))
+        .any(|start| is_stem(&name[..start]) && is_version(&name[start + 3..]))
+}
+
+impl Resolver {
+    /// The libraries a process would load by calling `dlopen` with each of `sonames` in turn,
+    /// whatever it has loaded before: each name is searched for like
+    /// [`Resolver::resolve_soname`] does, as by an object with no `RPATH` or `RUNPATH`, and
+    /// its closure collected like [`ElfFile::dependency_graph`](crate::ElfFile::dependency_graph)
+    /// does. Names with a slash are taken as paths, as `dlopen` does.
+    ///
+    /// The root of the graph stands for the caller: it is named `dlopen`, is
+    /// [virtual](DependencyKind::Virtual), and has the names as its dependencies, of kind
+    /// [`DependencyKind::Dlopen`] with [`DlopenPriority::Required`]. Names that are not found
+    /// are [`ResolveError::NotFound`] with an empty `needed_by`.
+    pub fn closure_of_sonames<I, S>(&self, sonames: I) -> DependencyGraph
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut root = DependencyNode::new("dlopen", Ok(Resolved::Virtual));
+        root.kind = DependencyKind::Virtual;
+        let graph = DependencyGraph::new(root);
+        let node = graph.root();
+        let mut traversal = Traversal {
+            resolver: self,
+            recursive: self.config().recursive,
+            seen_libs: HashMap::new(),
+            graph,
+            order: self.config().order,
+            queue: VecDeque::new(),
+            bytes_parsed: 0,
+        };
+        let kind = DependencyKind::Dlopen(DlopenPriority::Required);
+        let sonames: Vec<OsString> = sonames.into_iter().map(|s| s.as_ref().to_owned()).collect();
+        let search_dirs = self.search_dirs(&[], &[], self.default_executable());
+        // For a host of an unknown architecture, the first library found sets it, as it
+        // would that of a process loading it
+        let executable = self.default_executable().or_else(|| {
+            sonames.iter().find_map(|soname| {
+                let (path, _) = self.search(soname, &search_dirs, None, false).ok()?;
+                Some(self.load_object(&path, &[], None).ok()?.executable)
+            })
+        });
+        let Some(executable) = executable else {
+            for soname in &sonames {
+                let needed_by = PathBuf::new();
+                let mut child =
+                    DependencyNode::new(soname, Err(ResolveError::NotFound { needed_by }));
+                child.kind = kind;
+                let child = traversal.graph.add_node(child);
+                traversal.graph.add_edge(node, child);
+            }
+            return traversal.graph;
+        };
+        let libs: Vec<_> = sonames
+            .into_iter()
+            .map(|soname| {
+                let lookup = traversal.lookup(&soname, kind, &search_dirs, &[], executable, false);
+                ((soname, kind), lookup)
+            })
+            .collect();
+        traversal.walk_from(Some(Frame {
+            path: PathBuf::new(),
+            node,
+            libs: libs.into_iter(),
+            rpath: Vec::new(),
+            executable,
+            depth: 0,
+        }));
+        traversal.graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{library_names, looks_like_library};
+    use crate::test_util::ElfBuilder;
+    use crate::{DependencyKind, DlopenPriority, ResolveError, Resolver, ResolverConfig};
+    use std::io::Cursor;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_looks_like_library() {
+        let looks = |string: &str| looks_like_library(string.as_bytes());
+        assert!(looks("libfoo.so"));
+        assert!(looks("libGL.so.1"));
+        assert!(looks("ld-linux-x86-64.so.2"));
+        assert!(looks("/usr/lib/plugins/libbar.so.1.2.3"));
+        assert!(looks("libfoo.so.so.1"));
+        assert!(!looks("lib%s.so"));
+        assert!(!looks("failed to load libfoo.so"));
+        assert!(!looks("libfoo.so.1a"));
+        assert!(!looks("libfoo.so."));
+        assert!(!looks(".so"));
+        assert!(!looks("/usr/lib/.so"));
+        assert!(!looks("libfoo.sox"));
+        assert!(!looks("*.so"));
+    }
+
+    #[test]
+    fn test_library_names() {
+        let data = ElfBuilder::new()
+            .rodata(b"usage: %s\0libplugin.so.2\0lib%s.so\0/opt/app/libextra.so\0libplugin.so.2\0")
+            .build();
+        let names = library_names(Path::new("app"), Cursor::new(data)).unwrap();
+        assert_eq!(names, ["libplugin.so.2", "/opt/app/libextra.so"]);
+
+        let data = ElfBuilder::new().without_section_headers().build();
+        let names = library_names(Path::new("app"), Cursor::new(data)).unwrap();
+        assert!(names.is_empty());
+    }
+
+    #[test]
+    fn test_closure_of_sonames() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        let common = ElfBuilder::new().write(&lib, "libcommon.so.1");
+        let python = ElfBuilder::new()
+            .needed("libcommon.so.1")
+            .write(&lib, "libpython3.12.so.1.0");
+        let plugin = ElfBuilder::new()
+            .needed("libcommon.so.1")
+            .needed("libgone.so.1")
+            .write(dir.path().join("plugins"), "libfoo.so");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(&lib);
+        let resolver = Resolver::new(config);
+
+        let plugin_path = plugin.to_str().unwrap();
+        let graph = resolver.closure_of_sonames(["libpython3.12.so.1.0", plugin_path, "libfoo.so"]);
+        let root = graph.node(graph.root());
+        assert_eq!(
+            (root.soname.as_str(), root.kind),
+            ("dlopen", DependencyKind::Virtual)
+        );
+        let nodes: Vec<_> = graph
+            .nodes()
+            .iter()
+            .map(|node| (node.soname.as_str(), node.path()))
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                ("dlopen", None),
+                ("libpython3.12.so.1.0", Some(python.as_path())),
+                ("libcommon.so.1", Some(common.as_path())),
+                (plugin_path, Some(plugin.as_path())),
+                ("libgone.so.1", None),
+                ("libfoo.so", None),
+            ]
+        );
+        let dlopen = DependencyKind::Dlopen(DlopenPriority::Required);
+        let named = graph.dependencies(graph.root());
+        assert!(named.iter().all(|&id| graph.node(id).kind == dlopen));
+        assert!(matches!(
+            &graph.nodes()[5].resolution,
+            Err(ResolveError::NotFound { needed_by }) if *needed_by == PathBuf::new()
+        ));
+        assert!(matches!(
+            &graph.nodes()[4].resolution,
+            Err(ResolveError::NotFound { needed_by }) if *needed_by == plugin
+        ));
+        // The library both need is loaded once
+        let plugin_deps = graph.dependencies(graph.find_path(&plugin).unwrap());
+        assert_eq!(plugin_deps[0], graph.find_path(&common).unwrap());
+    }
+}
@@ -0,0 +1,291 @@
+//! The `.note.dlopen` notes in which programs like systemd declare the libraries they load
+//! with `dlopen`, as described by the ELF dlopen metadata specification at
+//! <https://systemd.io/ELF_DLOPEN_METADATA/>.
+use std::fmt;
+
+/// The owner of the notes.
+pub(crate) const NOTE_OWNER: &str = "FDO";
+
+/// The `n_type` of the notes, `NT_FDO_DLOPEN_METADATA`.
+pub(crate) const NT_FDO_DLOPEN_METADATA: u64 = 0x407c_0c0a;
+
+/// The section the notes are in.
+pub(crate) const SECTION: &str = ".note.dlopen";
+
+/// How much a program needs a library it declares in a `.note.dlopen` note, see
+/// [`DependencyKind::Dlopen`](crate::DependencyKind::Dlopen).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DlopenPriority {
+    /// The program does not work at all without the library.
+    Required,
+    /// The program works without the library, but a feature most users want is missing. This
+    /// is what notes that don't say mean.
+    Recommended,
+    /// The program works without the library, and few users want the feature it provides.
+    Suggested,
+}
+
+impl DlopenPriority {
+    /// Every priority, from the highest.
+    pub(crate) const ALL: [DlopenPriority; 3] = [
+        DlopenPriority::Required,
+        DlopenPriority::Recommended,
+        DlopenPriority::Suggested,
+    ];
+
+    /// The priority as notes name it, like `recommended`.
+    pub fn name(self) -> &'static str {
+        match self {
+            DlopenPriority::Required => "required",
+            DlopenPriority::Recommended => "recommended",
+            DlopenPriority::Suggested => "suggested",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|priority| priority.name() == name)
+    }
+}
+
+impl fmt::Display for DlopenPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One feature declared in a `.note.dlopen` note: the libraries providing it, as alternatives
+/// in order of preference, of which the program loads the first it finds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DlopenFeature {
+    pub sonames: Vec<String>,
+    pub priority: DlopenPriority,
+}
+
+/// The features declared in the descriptor `desc` of a `.note.dlopen` note, a NUL-terminated
+/// JSON array of objects. Like a malformed build ID, a malformed note declares nothing, as it
+/// is not needed to load the file, and so do entries without sonames or with a priority this
+/// crate doesn't know.
+pub(crate) fn parse(desc: &[u8]) -> Vec<DlopenFeature> {
+    let json = desc.split(|&byte| byte == 0).next().unwrap_or_default();
+    let mut parser = Parser { json, at: 0 };
+    let value = parser.value().filter(|_| parser.at_end());
+    let Some(Value::Array(entries)) = value else {
+        return Vec::new();
+    };
+    let feature = |entry: Value| {
+        let Value::Object(fields) = entry else {
+            return None;
+        };
+        let field = |name: &str| fields.iter().find(|(key, _)| key == name).map(|(_, v)| v);
+        let sonames = match field("soname")? {
+            Value::Array(sonames) => sonames
+                .iter()
+                .map(|soname| match soname {
+                    Value::String(soname) if !soname.is_empty() => Some(soname.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?,
+            _ => return None,
+        };
+        let priority = match field("priority") {
+            None => DlopenPriority::Recommended,
+            Some(Value::String(name)) => DlopenPriority::from_name(name)?,
+            Some(_) => return None,
+        };
+        (!sonames.is_empty()).then_some(DlopenFeature { sonames, priority })
+    };
+    entries.into_iter().filter_map(feature).collect()
+}
+
+/// A JSON value, keeping only what the notes use: numbers, booleans and nulls are parsed, but
+/// not kept.
+#[derive(Debug, PartialEq)]
+enum Value {
+    Other,
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+/// A JSON parser over `json`, at byte `at`.
+struct Parser<'a> {
+    json: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.json.get(self.at).is_some_and(u8::is_ascii_whitespace) {
+            self.at += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.at == self.json.len()
+    }
+
+    /// Skips whitespace and `byte`, if it is next.
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let eaten = self.json.get(self.at) == Some(&byte);
+        self.at += usize::from(eaten);
+        eaten
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        match self.json.get(self.at)? {
+            b'"' => self.string().map(Value::String),
+            b'[' => {
+                self.at += 1;
+                let mut values = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        values.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.eat(b',').then_some(())?;
+                    }
+                }
+                Some(Value::Array(values))
+            }
+            b'{' => {
+                self.at += 1;
+                let mut fields = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.eat(b':').then_some(())?;
+                        fields.push((key, self.value()?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        self.eat(b',').then_some(())?;
+                    }
+                }
+                Some(Value::Object(fields))
+            }
+            _ => {
+                // A number or literal, which runs up to the next delimiter
+                let rest = &self.json[self.at..];
+                let len = rest
+                    .iter()
+                    .position(|byte| b",]} \t\r\n".contains(byte))
+                    .unwrap_or(rest.len());
+                let token = &rest[..len];
+                let is_number = |token: &[u8]| {
+                    std::str::from_utf8(token).is_ok_and(|token| token.parse::<f64>().is_ok())
+                };
+                if !matches!(token, b"true" | b"false" | b"null") && !is_number(token) {
+                    return None;
+                }
+                self.at += len;
+                Some(Value::Other)
+            }
+        }
+    }
+
+    /// Parses the string starting at the current byte, which must be its opening quote.
+    fn string(&mut self) -> Option<String> {
+        (self.json.get(self.at) == Some(&b'"')).then_some(())?;
+        self.at += 1;
+        let mut string = Vec::new();
+        loop {
+            let byte = *self.json.get(self.at)?;
+            self.at += 1;
+            match byte {
+                b'"' => return String::from_utf8(string).ok(),
+                b'\\' => {
+                    let escaped = *self.json.get(self.at)?;
+                    self.at += 1;
+                    let unescaped = match escaped {
+                        b'"' | b'\\' | b'/' => escaped as char,
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let high = self.hex4()?;
+                            let code = if (0xd800..0xdc00).contains(&high) {
+                                // A surrogate pair, as JSON writes characters beyond U+FFFF
+                                (self.eat(b'\\') && self.eat(b'u')).then_some(())?;
+                                let low = self.hex4()?;
+                                (0xdc00..0xe000).contains(&low).then_some(())?;
+                                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                            } else {
+                                high
+                            };
+                            char::from_u32(code)?
+                        }
+                        _ => return None,
+                    };
+                    let mut buffer = [0; 4];
+                    string.extend_from_slice(unescaped.encode_utf8(&mut buffer).as_bytes());
+                }
+                0..=0x1f => return None,
+                _ => string.push(byte),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.json.get(self.at..self.at + 4)?;
+        self.at += 4;
+        u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, DlopenFeature, DlopenPriority};
+
+    #[test]
+    fn test_parse() {
+        let desc = br#"[
+            {"feature": "idn", "description": "Support for IDN", "priority": "suggested",
+             "soname": ["libidn2.so.0", "libidn.so.12"]},
+            {"feature":"zstd","soname":["libzstd.so.1"],"version":1.5,"extra":[true,null,{}]},
+            {"feature": "unknown priority", "priority": "optional", "soname": ["libx.so.1"]},
+            {"feature": "no sonames", "soname": []},
+            {"feature": "caf\u00e9 \ud83d\ude00", "priority": "required", "soname": ["libcafe.so"]}
+        ]"#;
+        let mut desc = desc.to_vec();
+        desc.extend_from_slice(b"\0\0\0");
+        let feature = |sonames: &[&str], priority| DlopenFeature {
+            sonames: sonames.iter().map(|soname| soname.to_string()).collect(),
+            priority,
+        };
+        assert_eq!(
+            parse(&desc),
+            [
+                feature(&["libidn2.so.0", "libidn.so.12"], DlopenPriority::Suggested),
+                feature(&["libzstd.so.1"], DlopenPriority::Recommended),
+                feature(&["libcafe.so"], DlopenPriority::Required),
+            ]
+        );
+
+        for malformed in [
+            &b"{\"soname\": [\"libfoo.so.1\"]}"[..],
+            b"[{\"soname\": [\"libfoo.so.1\"]}",
+            b"[{\"soname\": [\"libfoo.so.1\"]}] trailing",
+            b"[{\"soname\": [\"libfoo.so.1\"],}]",
+            b"[{\"soname\": [\"lib\\xfoo.so.1\"]}]",
+            b"[{\"soname\": [\"libfoo.so.1\"], \"version\": 1.2.3}]",
+            b"",
+        ] {
+            assert_eq!(
+                parse(malformed),
+                [],
+                "{}",
+                String::from_utf8_lossy(malformed)
+            );
+        }
+    }
+}
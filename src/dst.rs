@@ -0,0 +1,200 @@
+//! Expansion of the dynamic string tokens (`$ORIGIN`, `$LIB`, `$PLATFORM`) the dynamic linker
+//! substitutes in `RPATH`/`RUNPATH` entries, and of the sysroot prefixes toolchains write.
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use crate::abi::{EM_386, EM_AARCH64, EM_ARM, EM_X86_64};
+use crate::filesystem::FileKind;
+use crate::os_str::{OsStrExt, OsStringExt};
+use crate::parse::Arch;
+use crate::ResolverConfig;
+
+/// The values substituted for each token while analyzing one object.
+pub(crate) struct TokenValues<'a> {
+    /// The directory containing the object, substituted for `$ORIGIN`.
+    pub origin: &'a Path,
+    /// Substituted for `$LIB`.
+    pub lib: &'a str,
+    /// Substituted for `$PLATFORM`.
+    pub platform: &'a str,
+}
+
+/// The `$LIB` and `$PLATFORM` values glibc would use for an object of `arch`. `$LIB` is the
+/// [configured](ResolverConfig::lib_token) one, or follows the layout of the system: Debian
+/// builds glibc with its multiarch directory, others with the directory named by the class.
+/// x32 objects, which are 32-bit for x86-64, have libraries of their own.
+pub(crate) fn lib_and_platform(
+    arch: Arch,
+    config: &ResolverConfig,
+) -> (Cow<'static, str>, &'static str) {
+    let by_class = match (arch.is_64_bit, arch.machine) {
+        (true, _) => "lib64",
+        (false, EM_X86_64) => "libx32",
+        (false, _) => "lib",
+    };
+    let lib = config
+        .lib_token
+        .clone()
+        .or_else(|| multiarch_lib(arch, config))
+        .map_or(Cow::Borrowed(by_class), Cow::Owned);
+    let platform = match arch.machine {
+        EM_X86_64 => "x86_64",
+        EM_386 => "i686",
+        EM_AARCH64 => "aarch64",
+        EM_ARM => "v7l",
+        _ => "",
+    };
+    (lib, platform)
+}
+
+/// `lib/<triplet>` for the first multiarch triplet of `arch` with a directory in `/lib` or
+/// `/usr/lib`, or `None` if the system has no multiarch layout for it.
+fn multiarch_lib(arch: Arch, config: &ResolverConfig) -> Option<String> {
+    let is_dir = |dir: &Path| {
+        let metadata = config.fs().metadata(&config.rebase(dir));
+        metadata.is_ok_and(|metadata| metadata.kind == FileKind::Dir)
+    };
+    let triplet = arch.multiarch_triplets().iter().find(|triplet| {
+        ["/lib", "/usr/lib"]
+            .iter()
+            .any(|lib| is_dir(&Path::new(lib).join(triplet)))
+    })?;
+    Some(format!("lib/{triplet}"))
+}
+
+/// Expands every token in `entry`. Like glibc, tokens may be written as `$NAME` (ending at the
+/// first character that can't be part of a name) or `${NAME}`, and unknown tokens are kept
+/// literally.
+pub(crate) fn expand(entry: &OsStr, values: &TokenValues) -> OsString {
+    let mut expanded = Vec::with_capacity(entry.len());
+    let mut rest = entry.as_bytes();
+    while let Some(start) = rest.iter().position(|&byte| byte == b'$') {
+        expanded.extend_from_slice(&rest[..start]);
+        rest = &rest[start + 1..];
+        let (name, len): (&[u8], usize) = match rest.strip_prefix(b"{") {
+            Some(braced) => match braced.iter().position(|&byte| byte == b'}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => (b"", 0),
+            },
+            None => {
+                let end = rest
+                    .iter()
+                    .position(|&byte| !(byte.is_ascii_alphanumeric() || byte == b'_'))
+                    .unwrap_or(rest.len());
+                (&rest[..end], end)
+            }
+        };
+        let value = match name {
+            b"ORIGIN" => values.origin.as_os_str().as_bytes(),
+            b"LIB" => values.lib.as_bytes(),
+            b"PLATFORM" => values.platform.as_bytes(),
+            _ => {
+                expanded.push(b'$');
+                continue;
+            }
+        };
+        expanded.extend_from_slice(value);
+        rest = &rest[len..];
+    }
+    expanded.extend_from_slice(rest);
+    OsString::from_vec(expanded)
+}
+
+/// The rest of `entry` if it starts with a prefix making it relative to the sysroot, as
+/// toolchains write entries like `=/usr/lib` or `$SYSROOT/usr/lib`, or `None` if it doesn't.
+/// The dynamic linker knows no such prefix, but a sysroot is where the toolchain that wrote
+/// one found the libraries.
+pub(crate) fn strip_sysroot_prefix(entry: &OsStr) -> Option<&OsStr> {
+    let entry = entry.as_bytes();
+    let rest = entry.strip_prefix(b"=").or_else(|| {
+        let rest = entry
+            .strip_prefix(b"${SYSROOT}")
+            .or_else(|| entry.strip_prefix(b"$SYSROOT"))?;
+        (rest.is_empty() || rest.starts_with(b"/")).then_some(rest)
+    })?;
+    Some(OsStr::from_bytes(rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, lib_and_platform, strip_sysroot_prefix, TokenValues};
+    use crate::abi::{EM_386, EM_AARCH64, EM_X86_64};
+    use crate::parse::Arch;
+    use crate::ResolverConfig;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    #[test]
+    fn test_expand() {
+        let values = TokenValues {
+            origin: Path::new("/opt/app/bin"),
+            lib: "lib64",
+            platform: "x86_64",
+        };
+        let expand_str = |entry: &str| expand(OsStr::new(entry), &values);
+        assert_eq!(expand_str("$ORIGIN/../lib"), "/opt/app/bin/../lib");
+        assert_eq!(expand_str("${ORIGIN}/../$LIB"), "/opt/app/bin/../lib64");
+        assert_eq!(expand_str("/usr/lib/$PLATFORM"), "/usr/lib/x86_64");
+        assert_eq!(
+            expand_str("/opt/$ORIGINAL/$HOME/${ORIGIN"),
+            "/opt/$ORIGINAL/$HOME/${ORIGIN"
+        );
+        // Entries, like the origin, can be any bytes
+        let origin = Path::new(OsStr::from_bytes(b"/opt/\xff"));
+        let values = TokenValues { origin, ..values };
+        let entry = OsStr::from_bytes(b"$ORIGIN/\xfe");
+        assert_eq!(expand(entry, &values).as_bytes(), b"/opt/\xff/\xfe");
+    }
+
+    #[test]
+    fn test_lib_and_platform() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ResolverConfig::new().sysroot(dir.path());
+        let arch = |is_64_bit, machine| Arch {
+            is_64_bit,
+            big_endian: false,
+            machine,
+        };
+        let lib = |arch, config: &ResolverConfig| lib_and_platform(arch, config).0.into_owned();
+        let x86_64 = arch(true, EM_X86_64);
+        assert_eq!(
+            lib_and_platform(x86_64, &config),
+            ("lib64".into(), "x86_64")
+        );
+        assert_eq!(lib(arch(false, EM_X86_64), &config), "libx32");
+        assert_eq!(
+            lib_and_platform(arch(false, EM_386), &config),
+            ("lib".into(), "i686")
+        );
+
+        // Debian and its derivatives, with /lib merged into /usr/lib
+        fs::create_dir_all(dir.path().join("usr/lib/x86_64-linux-gnu")).unwrap();
+        fs::create_dir_all(dir.path().join("usr/lib/i386-linux-gnu")).unwrap();
+        assert_eq!(lib(x86_64, &config), "lib/x86_64-linux-gnu");
+        assert_eq!(lib(arch(false, EM_386), &config), "lib/i386-linux-gnu");
+        assert_eq!(lib(arch(true, EM_AARCH64), &config), "lib64");
+        fs::create_dir_all(dir.path().join("lib/aarch64-linux-gnu")).unwrap();
+        assert_eq!(
+            lib(arch(true, EM_AARCH64), &config),
+            "lib/aarch64-linux-gnu"
+        );
+
+        let config = config.lib_token("lib/amd64");
+        assert_eq!(lib(x86_64, &config), "lib/amd64");
+    }
+
+    #[test]
+    fn test_strip_sysroot_prefix() {
+        let strip = |entry: &'static str| strip_sysroot_prefix(OsStr::new(entry));
+        assert_eq!(strip("=/usr/lib"), Some(OsStr::new("/usr/lib")));
+        assert_eq!(strip("$SYSROOT/usr/lib"), Some(OsStr::new("/usr/lib")));
+        assert_eq!(strip("${SYSROOT}/$LIB"), Some(OsStr::new("/$LIB")));
+        assert_eq!(strip("$SYSROOT"), Some(OsStr::new("")));
+        assert_eq!(strip("$SYSROOTS/lib"), None);
+        assert_eq!(strip("/usr/lib"), None);
+        assert_eq!(strip("$ORIGIN/../lib"), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! The `DT_FLAGS` and `DT_FLAGS_1` entries of a file's dynamic section, which change how the
+//! dynamic linker loads it and binds its symbols.
+use std::fmt;
+
+/// The flags of the `DT_FLAGS` entry, as in
+/// [`DynamicInfo::flags`](crate::DynamicInfo::flags). Bits without a name here are kept as
+/// they were.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct DynamicFlags(u32);
+
+bitflags::bitflags! {
+    impl DynamicFlags: u32 {
+        /// `DF_ORIGIN`: the object may use `$ORIGIN`.
+        const ORIGIN = 0x1;
+        /// `DF_SYMBOLIC`: symbols are looked up in the object before the executable.
+        const SYMBOLIC = 0x2;
+        /// `DF_TEXTREL`: relocations may modify a read-only segment.
+        const TEXTREL = 0x4;
+        /// `DF_BIND_NOW`: every symbol is bound at load time, as with `LD_BIND_NOW`.
+        const BIND_NOW = 0x8;
+        /// `DF_STATIC_TLS`: the object uses the static TLS model, so it may fail to be
+        /// `dlopen`ed.
+        const STATIC_TLS = 0x10;
+        const _ = !0;
+    }
+}
+
+/// The flags of the `DT_FLAGS_1` entry, as in
+/// [`DynamicInfo::flags_1`](crate::DynamicInfo::flags_1). Bits without a name here are kept as
+/// they were.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct DynamicFlags1(u32);
+
+bitflags::bitflags! {
+    impl DynamicFlags1: u32 {
+        /// `DF_1_NOW`: every symbol is bound at load time, as linked with `-z now`.
+        const NOW = 0x1;
+        /// `DF_1_GLOBAL`: the object's symbols are available to every object loaded later.
+        const GLOBAL = 0x2;
+        /// `DF_1_GROUP`: the object and its dependencies are a group.
+        const GROUP = 0x4;
+        /// `DF_1_NODELETE`: the object is never unloaded, as linked with `-z nodelete`.
+        const NODELETE = 0x8;
+        /// `DF_1_LOADFLTR`: the object's filtees are loaded right away.
+        const LOADFLTR = 0x10;
+        /// `DF_1_INITFIRST`: the object's initializers run before those of other objects.
+        const INITFIRST = 0x20;
+        /// `DF_1_NOOPEN`: the object can't be `dlopen`ed, as linked with `-z nodlopen`.
+        const NOOPEN = 0x40;
+        /// `DF_1_ORIGIN`: the object may use `$ORIGIN`, as linked with `-z origin`.
+        const ORIGIN = 0x80;
+        /// `DF_1_DIRECT`: the object's symbols are bound directly.
+        const DIRECT = 0x100;
+        /// `DF_1_TRANS`.
+        const TRANS = 0x200;
+        /// `DF_1_INTERPOSE`: the object's symbols come before all others but the executable's,
+        /// as linked with `-z interpose`.
+        const INTERPOSE = 0x400;
+        /// `DF_1_NODEFLIB`: the system directories are not searched for the object's
+        /// dependencies, as linked with `-z nodefaultlib`.
+        const NODEFLIB = 0x800;
+        /// `DF_1_NODUMP`: the object can't be dumped with `dldump`.
+        const NODUMP = 0x1000;
+        /// `DF_1_CONFALT`: the object is a configuration alternative.
+        const CONFALT = 0x2000;
+        /// `DF_1_ENDFILTEE`: the filtee ends the filter search.
+        const ENDFILTEE = 0x4000;
+        /// `DF_1_DISPRELDNE`: displacement relocations were done.
+        const DISPRELDNE = 0x8000;
+        /// `DF_1_DISPRELPND`: displacement relocations are pending.
+        const DISPRELPND = 0x10000;
+        /// `DF_1_NODIRECT`: the object has symbols that can't be bound directly.
+        const NODIRECT = 0x20000;
+        /// `DF_1_IGNMULDEF`.
+        const IGNMULDEF = 0x40000;
+        /// `DF_1_NOKSYMS`.
+        const NOKSYMS = 0x80000;
+        /// `DF_1_NOHDR`.
+        const NOHDR = 0x100000;
+        /// `DF_1_EDITED`: the object was modified after it was linked.
+        const EDITED = 0x200000;
+        /// `DF_1_NORELOC`.
+        const NORELOC = 0x400000;
+        /// `DF_1_SYMINTPOSE`: the object has symbols that interpose others.
+        const SYMINTPOSE = 0x800000;
+        /// `DF_1_GLOBAUDIT`: the executable's audit libraries audit every object.
+        const GLOBAUDIT = 0x1000000;
+        /// `DF_1_SINGLETON`: the object has singleton symbols.
+        const SINGLETON = 0x2000000;
+        /// `DF_1_STUB`: the object is a stub.
+        const STUB = 0x4000000;
+        /// `DF_1_PIE`: the object is a position-independent executable, as linked with `-pie`.
+        const PIE = 0x8000000;
+        const _ = !0;
+    }
+}
+
+impl fmt::Display for DynamicFlags {
+    /// Writes the names of the flags separated by ` | `, like `ORIGIN | BIND_NOW`, with the
+    /// bits left over in hexadecimal.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+impl fmt::Display for DynamicFlags1 {
+    /// Writes the names of the flags like [`DynamicFlags`] does, like `NOW | PIE`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DynamicFlags, DynamicFlags1};
+    use crate::abi::{DF_1_NODEFLIB, DF_1_NOW, DF_1_PIE, DF_BIND_NOW, DF_ORIGIN};
+
+    #[test]
+    fn test_flags() {
+        let flags = DynamicFlags::from_bits_retain((DF_ORIGIN | DF_BIND_NOW) as u32 | 0x100);
+        assert!(flags.contains(DynamicFlags::BIND_NOW));
+        assert_eq!(flags.to_string(), "ORIGIN | BIND_NOW | 0x100");
+        let flags_1 = DynamicFlags1::from_bits_retain((DF_1_NOW | DF_1_NODEFLIB | DF_1_PIE) as u32);
+        assert_eq!(flags_1.to_string(), "NOW | NODEFLIB | PIE");
+        assert_eq!(DynamicFlags1::default().to_string(), "");
+    }
+}
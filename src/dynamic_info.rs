@@ -0,0 +1,39 @@
+//! The raw entries of a file's dynamic section that the dynamic linker resolves libraries from.
+use crate::parse::ParsedObject;
+use crate::{DynamicFlags, DynamicFlags1};
+
+/// The entries of an ELF file's dynamic section that decide which libraries it loads, where
+/// they are searched for and how they are loaded, as written, before `$ORIGIN` and the other
+/// dynamic string tokens are expanded. Bytes that are not UTF-8 are replaced with `U+FFFD`. See
+/// [`ElfFile::dynamic_info`](crate::ElfFile::dynamic_info) and
+/// [`DependencyNode::dynamic`](crate::DependencyNode::dynamic).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DynamicInfo {
+    /// The `DT_NEEDED` entries, in order.
+    pub needed: Vec<String>,
+    /// The `DT_RPATH` string, with its colon-separated directories, if there is one.
+    pub rpath: Option<String>,
+    /// The `DT_RUNPATH` string, with its colon-separated directories, if there is one.
+    pub runpath: Option<String>,
+    /// The `DT_SONAME` entry: the name the file advertises itself under.
+    pub soname: Option<String>,
+    /// The `DT_FLAGS` entry, empty if there is none.
+    pub flags: DynamicFlags,
+    /// The `DT_FLAGS_1` entry, empty if there is none.
+    pub flags_1: DynamicFlags1,
+}
+
+impl DynamicInfo {
+    pub(crate) fn of(object: &ParsedObject) -> Self {
+        let lossy = |string: &std::ffi::OsString| string.to_string_lossy().into_owned();
+        Self {
+            needed: object.needed.iter().map(lossy).collect(),
+            rpath: object.rpath.as_ref().map(lossy),
+            runpath: object.runpath.as_ref().map(lossy),
+            soname: object.soname.as_ref().map(lossy),
+            flags: object.flags,
+            flags_1: object.flags_1,
+        }
+    }
+}
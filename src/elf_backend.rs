@@ -0,0 +1,160 @@
+//! Decoding with the `elf` crate, for the `elf` feature.
+use elf::abi::EI_NIDENT;
+use elf::dynamic::DynamicTable;
+use elf::endian::AnyEndian;
+use elf::file::Class;
+use elf::gnu_symver::{VerDefIterator, VerNeedIterator};
+use elf::note::{Note as ElfNote, NoteAny, NoteGnuBuildId, NoteIterator};
+use elf::section::SectionHeaderTable;
+use elf::segment::SegmentTable;
+use elf::symbol::SymbolTable;
+
+use crate::abi::NT_GNU_BUILD_ID;
+use crate::object::{
+    Backend, FileHeader, Note, ProgramHeader, SectionHeader, Symbol, VersionDef, VersionNeed,
+};
+use crate::ParseError;
+
+/// Decodes with the `elf` crate.
+pub(crate) struct ElfBackend;
+
+impl Backend for ElfBackend {
+    fn file_header(data: &[u8]) -> Result<FileHeader, ParseError> {
+        if data.len() < EI_NIDENT {
+            return Err(ParseError::bad_range(0, EI_NIDENT as u64));
+        }
+        let (ident, tail) = data.split_at(EI_NIDENT);
+        let ident = elf::file::parse_ident::<AnyEndian>(ident).map_err(ParseError::malformed)?;
+        let ehdr = elf::file::FileHeader::parse_tail(ident, tail).map_err(ParseError::malformed)?;
+        Ok(FileHeader {
+            is_64_bit: ehdr.class == Class::ELF64,
+            big_endian: ehdr.endianness == AnyEndian::Big,
+            os_abi: ehdr.osabi,
+            abi_version: ehdr.abiversion,
+            e_type: ehdr.e_type,
+            e_machine: ehdr.e_machine,
+            e_phoff: ehdr.e_phoff,
+            e_shoff: ehdr.e_shoff,
+            e_phentsize: ehdr.e_phentsize,
+            e_phnum: ehdr.e_phnum,
+            e_shentsize: ehdr.e_shentsize,
+            e_shnum: ehdr.e_shnum,
+            e_shstrndx: ehdr.e_shstrndx,
+        })
+    }
+
+    fn section_headers(header: &FileHeader, data: &[u8]) -> Result<Vec<SectionHeader>, ParseError> {
+        let (endian, class) = context(header);
+        let table = SectionHeaderTable::new(endian, class, data);
+        let sections = table.iter().map(|shdr| SectionHeader {
+            sh_name: shdr.sh_name,
+            sh_type: shdr.sh_type,
+            sh_flags: shdr.sh_flags,
+            sh_addr: shdr.sh_addr,
+            sh_offset: shdr.sh_offset,
+            sh_size: shdr.sh_size,
+            sh_link: shdr.sh_link,
+            sh_info: shdr.sh_info,
+            sh_addralign: shdr.sh_addralign,
+            sh_entsize: shdr.sh_entsize,
+        });
+        Ok(sections.collect())
+    }
+
+    fn program_headers(header: &FileHeader, data: &[u8]) -> Result<Vec<ProgramHeader>, ParseError> {
+        let (endian, class) = context(header);
+        let table = SegmentTable::new(endian, class, data);
+        let segments = table.iter().map(|phdr| ProgramHeader {
+            p_type: phdr.p_type,
+            p_flags: phdr.p_flags,
+            p_offset: phdr.p_offset,
+            p_vaddr: phdr.p_vaddr,
+            p_filesz: phdr.p_filesz,
+            p_memsz: phdr.p_memsz,
+            p_align: phdr.p_align,
+        });
+        Ok(segments.collect())
+    }
+
+    fn dynamic(header: &FileHeader, data: &[u8]) -> Vec<(i64, u64)> {
+        let (endian, class) = context(header);
+        let table = DynamicTable::new(endian, class, data);
+        let entries = table.iter().map(|entry| (entry.d_tag, entry.d_val()));
+        entries.collect()
+    }
+
+    fn symbols(header: &FileHeader, data: &[u8]) -> Vec<Symbol> {
+        let (endian, class) = context(header);
+        let table = SymbolTable::new(endian, class, data);
+        let symbols = table.iter().map(|symbol| Symbol {
+            st_name: symbol.st_name,
+            st_info: symbol.st_bind() << 4 | symbol.st_symtype(),
+            st_other: symbol.st_vis(),
+            st_shndx: symbol.st_shndx,
+            st_value: symbol.st_value,
+            st_size: symbol.st_size,
+        });
+        symbols.collect()
+    }
+
+    fn notes<'a>(header: &FileHeader, align: u64, data: &'a [u8]) -> Vec<Note<'a>> {
+        let (endian, class) = context(header);
+        let Ok(align) = usize::try_from(align) else {
+            return Vec::new();
+        };
+        let notes = NoteIterator::new(endian, class, align, data);
+        let notes = notes.filter_map(|note| match note {
+            ElfNote::GnuBuildId(NoteGnuBuildId(id)) => Some(Note {
+                n_type: NT_GNU_BUILD_ID,
+                name: b"GNU",
+                desc: id,
+            }),
+            ElfNote::Unknown(NoteAny { n_type, name, desc }) => Some(Note {
+                n_type,
+                name: name.as_bytes(),
+                desc,
+            }),
+            // Decoded into fields that are not needed here
+            ElfNote::GnuAbiTag(_) => None,
+        });
+        notes.collect()
+    }
+
+    fn version_needs(header: &FileHeader, data: &[u8], count: u64) -> Vec<VersionNeed> {
+        let (endian, class) = context(header);
+        let needs = VerNeedIterator::new(endian, class, count, 0, data);
+        let needs = needs.map(|(need, auxiliaries)| VersionNeed {
+            file: need.vn_file,
+            versions: auxiliaries
+                .map(|aux| (aux.vna_name, aux.vna_flags))
+                .collect(),
+        });
+        needs.collect()
+    }
+
+    fn version_defs(header: &FileHeader, data: &[u8], count: u64) -> Vec<VersionDef> {
+        let (endian, class) = context(header);
+        let defs = VerDefIterator::new(endian, class, count, 0, data);
+        let defs = defs.map(|(def, auxiliaries)| VersionDef {
+            index: def.vd_ndx,
+            flags: def.vd_flags,
+            names: auxiliaries.map(|aux| aux.vda_name).collect(),
+        });
+        defs.collect()
+    }
+}
+
+/// The byte order and class the `elf` crate decodes the structures of the file with.
+fn context(header: &FileHeader) -> (AnyEndian, Class) {
+    let endian = if header.big_endian {
+        AnyEndian::Big
+    } else {
+        AnyEndian::Little
+    };
+    let class = if header.is_64_bit {
+        Class::ELF64
+    } else {
+        Class::ELF32
+    };
+    (endian, class)
+}
@@ -0,0 +1,525 @@
+//! Error types returned while analyzing ELF files.
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::{ElfType, ResourceLimit};
+
+/// Everything that can go wrong while collecting the shared-library dependencies of an ELF file.
+///
+/// With the `serde` feature, errors can be serialized and deserialized. The `source` of
+/// [`Error::Io`] and [`Error::Parse`] is represented by its message, so deserialized sources
+/// keep their message but not their original type.
+#[derive(Debug)]
+pub enum Error {
+    /// The file at `path` could not be read.
+    Io { path: PathBuf, source: io::Error },
+    /// The file at `path` could not be created, as by
+    /// [`ElfFile::bundle_to`](crate::ElfFile::bundle_to).
+    Write { path: PathBuf, source: io::Error },
+    /// The file at `path` is not a well-formed ELF file.
+    Parse { path: PathBuf, source: ParseError },
+    /// The file at `path` has a dynamic array, but no string table for it.
+    MissingDynamic { path: PathBuf },
+    /// The string at `offset` in the dynamic string table of `path`, referenced by its dynamic
+    /// section, is not NUL-terminated before the end of the table. Strings are taken as raw
+    /// bytes, so any other contents are valid.
+    InvalidString { path: PathBuf, offset: usize },
+    /// The dynamic entry with tag `tag` in `path` refers to the string at `offset`, which is
+    /// past the end of the dynamic string table.
+    CorruptDynamicEntry {
+        path: PathBuf,
+        tag: i64,
+        offset: u64,
+    },
+    /// The `ld.so.cache` file at `path` is not in a known format.
+    InvalidCache { path: PathBuf },
+    /// The rpm database at `path` is not an SQLite database with a `Packages` table of
+    /// well-formed package headers.
+    InvalidPackageDb { path: PathBuf },
+    /// The file at `path` is not a well-formed Mach-O file.
+    InvalidMachO { path: PathBuf },
+    /// The file at `path` is not a well-formed PE file.
+    InvalidPe { path: PathBuf },
+    /// The file at `path` is not a `docker save` or OCI image layout archive, or has layers
+    /// compressed other than with gzip, xz or zstd.
+    InvalidImage { path: PathBuf },
+    /// The file at `path` is not a `.deb` or `.rpm` package, or has its files compressed other
+    /// than with gzip.
+    InvalidPackage { path: PathBuf },
+    /// The file at `path` is not a tar archive of a root filesystem, or is compressed other
+    /// than with gzip, xz or zstd.
+    InvalidRootfs { path: PathBuf },
+    /// The file at `path` is not an [`AppImage`](crate::AppImage) with a squashfs image that
+    /// can be read.
+    InvalidAppImage { path: PathBuf },
+    /// The file at `path` is not a [`KernelModule`](crate::KernelModule), or is compressed in a
+    /// way that can't be read.
+    InvalidModule { path: PathBuf },
+    /// The file at `path` is not a core dump listing the files it had mapped, as read by
+    /// [`CoreFile`](crate::CoreFile).
+    InvalidCore { path: PathBuf },
+    /// The file at `path` is not a [`Lockfile`](crate::Lockfile), from line `line` on.
+    InvalidLockfile { path: PathBuf, line: usize },
+    /// The library `soname` needed by `needed_by` was not found in any search directory.
+    Unresolved { soname: String, needed_by: PathBuf },
+    /// The file at `path` has no `DT_RUNPATH` or `DT_RPATH` string that `runpath` could be
+    /// written over, as by [`RunpathRewrite::apply`](crate::RunpathRewrite::apply): none at
+    /// all, only a shorter one, or one that shares its bytes with other strings.
+    RunpathNoSpace { path: PathBuf, runpath: String },
+    /// [`Resolver::resolve_soname`](crate::Resolver::resolve_soname) found no compatible library
+    /// named `soname` at any of the `searched` paths.
+    SonameNotFound {
+        soname: String,
+        searched: Vec<PathBuf>,
+    },
+    /// [`Target::from_triple`](crate::Target::from_triple) does not know the architecture or
+    /// operating system of the target triple `triple`.
+    UnknownTarget { triple: String },
+    /// The file at `path` was analyzed for the [target](crate::ResolverConfig::target)
+    /// `triple`, but is built for another architecture.
+    WrongTarget { path: PathBuf, triple: String },
+    /// The file at `path` is an ELF file the dynamic linker doesn't load, like a relocatable
+    /// object or a core dump, so it has no dynamic dependencies.
+    NotLoadable { path: PathBuf, elf_type: ElfType },
+    /// Reading the file at `path` would go past `limit`, set on the
+    /// [`ResolverConfig`](crate::ResolverConfig) for untrusted files.
+    LimitExceeded { path: PathBuf, limit: ResourceLimit },
+    /// The analysis of the file at `path` was cancelled before it was done, as by
+    /// [`ElfFile::get_libs_full_paths_async`](crate::ElfFile::get_libs_full_paths_async) when
+    /// the tokio runtime shuts down.
+    Cancelled { path: PathBuf },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "could not read {path:?}: {source}"),
+            Error::Write { path, source } => write!(f, "could not write {path:?}: {source}"),
+            Error::Parse { path, source } => {
+                write!(f, "could not parse ELF file {path:?}: {source}")
+            }
+            Error::MissingDynamic { path } => write!(f, "{path:?} has no dynamic string table"),
+            Error::InvalidString { path, offset } => {
+                write!(
+                    f,
+                    "unterminated dynamic string at offset {offset} in {path:?}"
+                )
+            }
+            Error::CorruptDynamicEntry { path, tag, offset } => write!(
+                f,
+                "corrupt dynamic entry with tag {tag:#x} in {path:?}: string offset {offset} is \
+                 out of bounds"
+            ),
+            Error::InvalidCache { path } => write!(f, "{path:?} is not a valid ld.so.cache file"),
+            Error::InvalidPackageDb { path } => {
+                write!(f, "{path:?} is not a valid rpm database")
+            }
+            Error::InvalidMachO { path } => write!(f, "could not parse Mach-O file {path:?}"),
+            Error::InvalidPe { path } => write!(f, "could not parse PE file {path:?}"),
+            Error::InvalidImage { path } => {
+                write!(f, "{path:?} is not a valid container image archive")
+            }
+            Error::InvalidPackage { path } => {
+                write!(f, "{path:?} is not a valid .deb or .rpm package")
+            }
+            Error::InvalidRootfs { path } => {
+                write!(f, "{path:?} is not a valid root filesystem archive")
+            }
+            Error::InvalidAppImage { path } => write!(f, "{path:?} is not a valid AppImage"),
+            Error::InvalidModule { path } => write!(f, "{path:?} is not a valid kernel module"),
+            Error::InvalidCore { path } => {
+                write!(f, "{path:?} is not a core dump with a list of mapped files")
+            }
+            Error::InvalidLockfile { path, line } => {
+                write!(f, "{path:?} is not a valid lockfile at line {line}")
+            }
+            Error::Unresolved { soname, needed_by } => {
+                write!(
+                    f,
+                    "library \"{soname}\" needed by {needed_by:?} was not found"
+                )
+            }
+            Error::RunpathNoSpace { path, runpath } => write!(
+                f,
+                "no room to write RUNPATH \"{runpath}\" into {path:?} in place"
+            ),
+            Error::SonameNotFound { soname, searched } => write!(
+                f,
+                "library \"{soname}\" was not found in {} searched locations",
+                searched.len()
+            ),
+            Error::UnknownTarget { triple } => write!(f, "unknown target triple \"{triple}\""),
+            Error::WrongTarget { path, triple } => {
+                write!(f, "{path:?} is not built for the target \"{triple}\"")
+            }
+            Error::NotLoadable { path, elf_type } => write!(
+                f,
+                "{path:?} is a {elf_type}, which has no dynamic dependencies"
+            ),
+            Error::LimitExceeded { path, limit } => write!(f, "reading {path:?} exceeds {limit}"),
+            Error::Cancelled { path } => write!(f, "the analysis of {path:?} was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::Write { source, .. } => Some(source),
+            Error::Parse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Why a single needed library could not be resolved, as reported by
+/// [`ElfFile::get_libs_resolutions`](crate::ElfFile::get_libs_resolutions).
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResolveError {
+    /// No compatible library with this soname exists in any directory searched for `needed_by`.
+    NotFound {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_path"))]
+        needed_by: PathBuf,
+    },
+    /// The library was found, but reading its own dependencies failed.
+    Load(Error),
+}
+
+impl ResolveError {
+    /// The error that methods returning only paths, like
+    /// [`ElfFile::get_libs_full_paths`](crate::ElfFile::get_libs_full_paths), fail with for the
+    /// library `soname`.
+    pub(crate) fn into_error(self, soname: String) -> Error {
+        match self {
+            ResolveError::NotFound { needed_by } => Error::Unresolved { soname, needed_by },
+            ResolveError::Load(error) => error,
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::NotFound { needed_by } => {
+                write!(f, "not found (needed by {needed_by:?})")
+            }
+            ResolveError::Load(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResolveError::NotFound { .. } => None,
+            ResolveError::Load(error) => Some(error),
+        }
+    }
+}
+
+/// Why a file is not a well-formed ELF file, the `source` of [`Error::Parse`]. It is the same
+/// whichever backend parsed the file, which only contributes its message.
+#[derive(Debug)]
+pub struct ParseError(ParseErrorKind);
+
+#[derive(Debug)]
+enum ParseErrorKind {
+    /// The parts of the file that were needed could not be read.
+    Io(io::Error),
+    /// The file is malformed, as the message describes.
+    Malformed(String),
+}
+
+impl ParseError {
+    /// The error for a file that is malformed as `message` describes.
+    pub(crate) fn malformed(message: impl fmt::Display) -> Self {
+        ParseError(ParseErrorKind::Malformed(message.to_string()))
+    }
+
+    /// The error for a range of `size` bytes at `offset` that is not in the file.
+    pub(crate) fn bad_range(offset: u64, size: u64) -> Self {
+        ParseError::malformed(format_args!(
+            "{size} bytes at offset {offset:#x} are past the end of the file"
+        ))
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        ParseError(ParseErrorKind::Io(error))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            ParseErrorKind::Io(error) => write!(f, "{error}"),
+            ParseErrorKind::Malformed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.0 {
+            ParseErrorKind::Io(error) => error.source(),
+            ParseErrorKind::Malformed(_) => None,
+        }
+    }
+}
+
+/// The serialized form of [`Error`], with sources replaced by their messages.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+enum ErrorRepr {
+    Io {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        message: String,
+    },
+    Write {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        message: String,
+    },
+    Parse {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        message: String,
+    },
+    MissingDynamic {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidString {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        offset: usize,
+    },
+    CorruptDynamicEntry {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        tag: i64,
+        offset: u64,
+    },
+    InvalidCache {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidPackageDb {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidMachO {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidPe {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidImage {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidPackage {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidRootfs {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidAppImage {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidModule {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidCore {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+    InvalidLockfile {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        line: usize,
+    },
+    Unresolved {
+        soname: String,
+        #[serde(with = "crate::serde_path")]
+        needed_by: PathBuf,
+    },
+    RunpathNoSpace {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        runpath: String,
+    },
+    SonameNotFound {
+        soname: String,
+        #[serde(with = "crate::serde_path::vec")]
+        searched: Vec<PathBuf>,
+    },
+    UnknownTarget {
+        triple: String,
+    },
+    WrongTarget {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        triple: String,
+    },
+    NotLoadable {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        elf_type: ElfType,
+    },
+    LimitExceeded {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+        limit: ResourceLimit,
+    },
+    Cancelled {
+        #[serde(with = "crate::serde_path")]
+        path: PathBuf,
+    },
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self {
+            Error::Io { path, source } => ErrorRepr::Io {
+                path: path.clone(),
+                message: source.to_string(),
+            },
+            Error::Write { path, source } => ErrorRepr::Write {
+                path: path.clone(),
+                message: source.to_string(),
+            },
+            Error::Parse { path, source } => ErrorRepr::Parse {
+                path: path.clone(),
+                message: source.to_string(),
+            },
+            Error::MissingDynamic { path } => ErrorRepr::MissingDynamic { path: path.clone() },
+            Error::InvalidString { path, offset } => ErrorRepr::InvalidString {
+                path: path.clone(),
+                offset: *offset,
+            },
+            Error::CorruptDynamicEntry { path, tag, offset } => ErrorRepr::CorruptDynamicEntry {
+                path: path.clone(),
+                tag: *tag,
+                offset: *offset,
+            },
+            Error::InvalidCache { path } => ErrorRepr::InvalidCache { path: path.clone() },
+            Error::InvalidPackageDb { path } => ErrorRepr::InvalidPackageDb { path: path.clone() },
+            Error::InvalidMachO { path } => ErrorRepr::InvalidMachO { path: path.clone() },
+            Error::InvalidPe { path } => ErrorRepr::InvalidPe { path: path.clone() },
+            Error::InvalidImage { path } => ErrorRepr::InvalidImage { path: path.clone() },
+            Error::InvalidPackage { path } => ErrorRepr::InvalidPackage { path: path.clone() },
+            Error::InvalidRootfs { path } => ErrorRepr::InvalidRootfs { path: path.clone() },
+            Error::InvalidAppImage { path } => ErrorRepr::InvalidAppImage { path: path.clone() },
+            Error::InvalidModule { path } => ErrorRepr::InvalidModule { path: path.clone() },
+            Error::InvalidCore { path } => ErrorRepr::InvalidCore { path: path.clone() },
+            Error::InvalidLockfile { path, line } => ErrorRepr::InvalidLockfile {
+                path: path.clone(),
+                line: *line,
+            },
+            Error::Unresolved { soname, needed_by } => ErrorRepr::Unresolved {
+                soname: soname.clone(),
+                needed_by: needed_by.clone(),
+            },
+            Error::RunpathNoSpace { path, runpath } => ErrorRepr::RunpathNoSpace {
+                path: path.clone(),
+                runpath: runpath.clone(),
+            },
+            Error::SonameNotFound { soname, searched } => ErrorRepr::SonameNotFound {
+                soname: soname.clone(),
+                searched: searched.clone(),
+            },
+            Error::UnknownTarget { triple } => ErrorRepr::UnknownTarget {
+                triple: triple.clone(),
+            },
+            Error::WrongTarget { path, triple } => ErrorRepr::WrongTarget {
+                path: path.clone(),
+                triple: triple.clone(),
+            },
+            Error::NotLoadable { path, elf_type } => ErrorRepr::NotLoadable {
+                path: path.clone(),
+                elf_type: *elf_type,
+            },
+            Error::LimitExceeded { path, limit } => ErrorRepr::LimitExceeded {
+                path: path.clone(),
+                limit: *limit,
+            },
+            Error::Cancelled { path } => ErrorRepr::Cancelled { path: path.clone() },
+        };
+        repr.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Error {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match ErrorRepr::deserialize(deserializer)? {
+            ErrorRepr::Io { path, message } => Error::Io {
+                path,
+                source: io::Error::other(message),
+            },
+            ErrorRepr::Write { path, message } => Error::Write {
+                path,
+                source: io::Error::other(message),
+            },
+            ErrorRepr::Parse { path, message } => Error::Parse {
+                path,
+                source: ParseError::malformed(message),
+            },
+            ErrorRepr::MissingDynamic { path } => Error::MissingDynamic { path },
+            ErrorRepr::InvalidString { path, offset } => Error::InvalidString { path, offset },
+            ErrorRepr::CorruptDynamicEntry { path, tag, offset } => {
+                Error::CorruptDynamicEntry { path, tag, offset }
+            }
+            ErrorRepr::InvalidCache { path } => Error::InvalidCache { path },
+            ErrorRepr::InvalidPackageDb { path } => Error::InvalidPackageDb { path },
+            ErrorRepr::InvalidMachO { path } => Error::InvalidMachO { path },
+            ErrorRepr::InvalidPe { path } => Error::InvalidPe { path },
+            ErrorRepr::InvalidImage { path } => Error::InvalidImage { path },
+            ErrorRepr::InvalidPackage { path } => Error::InvalidPackage { path },
+            ErrorRepr::InvalidRootfs { path } => Error::InvalidRootfs { path },
+            ErrorRepr::InvalidAppImage { path } => Error::InvalidAppImage { path },
+            ErrorRepr::InvalidModule { path } => Error::InvalidModule { path },
+            ErrorRepr::InvalidCore { path } => Error::InvalidCore { path },
+            ErrorRepr::InvalidLockfile { path, line } => Error::InvalidLockfile { path, line },
+            ErrorRepr::Unresolved { soname, needed_by } => Error::Unresolved { soname, needed_by },
+            ErrorRepr::RunpathNoSpace { path, runpath } => Error::RunpathNoSpace { path, runpath },
+            ErrorRepr::SonameNotFound { soname, searched } => {
+                Error::SonameNotFound { soname, searched }
+            }
+            ErrorRepr::UnknownTarget { triple } => Error::UnknownTarget { triple },
+            ErrorRepr::WrongTarget { path, triple } => Error::WrongTarget { path, triple },
+            ErrorRepr::NotLoadable { path, elf_type } => Error::NotLoadable { path, elf_type },
+            ErrorRepr::LimitExceeded { path, limit } => Error::LimitExceeded { path, limit },
+            ErrorRepr::Cancelled { path } => Error::Cancelled { path },
+        })
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::Error;
+    use crate::ElfFile;
+
+    #[test]
+    fn test_serde_keeps_message() {
+        let error = ElfFile::new("/nonexistent/elf/file")
+            .get_libs_full_paths()
+            .unwrap_err();
+        let json = serde_json::to_string(&error).unwrap();
+        let deserialized: Error = serde_json::from_str(&json).unwrap();
+        assert!(matches!(deserialized, Error::Io { .. }));
+        assert_eq!(deserialized.to_string(), error.to_string());
+    }
+}
@@ -0,0 +1,272 @@
+//! A C interface, for the `ffi` feature, declared in `include/elf_dynamic_lib_getter.h`.
+//! The C libraries are built with `cargo rustc --release --features ffi --crate-type cdylib`,
+//! or `staticlib`.
+//!
+//! Strings are NUL-terminated bytes, as paths are on Unix, and every string a result holds
+//! lives as long as the result.
+use std::ffi::{c_char, CStr, CString, OsStr};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::Arc;
+
+use crate::{ElfFile, Resolver, ResolverConfig};
+
+/// A resolver shared by analyses, created by [`dlf_resolver_new`].
+pub struct DlfResolver(Arc<Resolver>);
+
+/// The libraries of an analyzed file, or why it could not be analyzed, as returned by
+/// [`dlf_analyze`].
+pub struct DlfResult {
+    error: Option<CString>,
+    entries: Vec<Entry>,
+}
+
+/// A library of a [`DlfResult`], as its strings are handed out.
+struct Entry {
+    soname: CString,
+    path: Option<CString>,
+    error: Option<CString>,
+}
+
+/// A library of the closure of an analyzed file, as filled in by [`dlf_result_get`].
+#[repr(C)]
+pub struct DlfEntry {
+    /// The name the library was requested by.
+    pub soname: *const c_char,
+    /// Where it was found, or null if it was not, or is the vDSO or an ignored library.
+    pub path: *const c_char,
+    /// Why it was not found, or null if it was, or is the vDSO or an ignored library.
+    pub error: *const c_char,
+}
+
+/// Creates a resolver searching for libraries as if `sysroot` were mounted at `/`, or in the
+/// host's directories if `sysroot` is null, and using `LD_LIBRARY_PATH` if `use_env`. A
+/// resolver can be used from several threads at once, and remembers the files it parsed.
+/// Free it with [`dlf_resolver_free`].
+///
+/// # Safety
+/// `sysroot` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dlf_resolver_new(
+    sysroot: *const c_char,
+    use_env: bool,
+) -> *mut DlfResolver {
+    let mut config = ResolverConfig::new().use_env(use_env);
+    if !sysroot.is_null() {
+        // SAFETY: the caller passes a NUL-terminated string
+        let sysroot = unsafe { CStr::from_ptr(sysroot) };
+        config = config.sysroot(OsStr::from_bytes(sysroot.to_bytes()));
+    }
+    let resolver = DlfResolver(Arc::new(Resolver::new(config)));
+    Box::into_raw(Box::new(resolver))
+}
+
+/// Frees a resolver created by [`dlf_resolver_new`]. Results of analyses done with it stay
+/// valid.
+///
+/// # Safety
+/// `resolver` must be null or a resolver from [`dlf_resolver_new`] not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dlf_resolver_free(resolver: *mut DlfResolver) {
+    if !resolver.is_null() {
+        // SAFETY: the caller passes a resolver from `dlf_resolver_new`, once
+        drop(unsafe { Box::from_raw(resolver) });
+    }
+}
+
+/// Resolves the libraries the ELF file at `path` needs, recursively, with `resolver`. Returns
+/// null only if `resolver` or `path` is null; if the file can't be analyzed, the result holds
+/// the [error](dlf_result_error) instead. Free the result with [`dlf_result_free`].
+///
+/// # Safety
+/// `resolver` must be null or a resolver from [`dlf_resolver_new`] not freed yet, and `path`
+/// null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dlf_analyze(
+    resolver: *const DlfResolver,
+    path: *const c_char,
+) -> *mut DlfResult {
+    if resolver.is_null() || path.is_null() {
+        return ptr::null_mut();
+    }
+    // SAFETY: the caller passes a live resolver and a NUL-terminated string
+    let (resolver, path) = unsafe { (&*resolver, CStr::from_ptr(path)) };
+    let path = PathBuf::from(OsStr::from_bytes(path.to_bytes()));
+    let elf_file = ElfFile::with_resolver(path, resolver.0.clone());
+    let result = match elf_file.get_libs_resolutions() {
+        Ok(resolutions) => DlfResult {
+            error: None,
+            entries: resolutions
+                .into_iter()
+                .map(|(soname, resolution)| {
+                    let (path, error) = match resolution {
+                        Ok(resolved) => {
+                            let path = resolved.path().map(|path| path.as_os_str().as_bytes());
+                            (path.map(c_string), None)
+                        }
+                        Err(error) => (None, Some(c_string(error.to_string().as_bytes()))),
+                    };
+                    Entry {
+                        soname: c_string(soname.as_bytes()),
+                        path,
+                        error,
+                    }
+                })
+                .collect(),
+        },
+        Err(error) => DlfResult {
+            error: Some(c_string(error.to_string().as_bytes())),
+            entries: Vec::new(),
+        },
+    };
+    Box::into_raw(Box::new(result))
+}
+
+/// Why the file of `result` could not be analyzed, or null if it was.
+///
+/// # Safety
+/// `result` must be a result from [`dlf_analyze`] not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dlf_result_error(result: *const DlfResult) -> *const c_char {
+    // SAFETY: the caller passes a live result
+    let result = unsafe { &*result };
+    result.error.as_deref().map_or(ptr::null(), CStr::as_ptr)
+}
+
+/// The number of libraries in `result`, in the order they were encountered, each once.
+///
+/// # Safety
+/// `result` must be a result from [`dlf_analyze`] not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dlf_result_len(result: *const DlfResult) -> usize {
+    // SAFETY: the caller passes a live result
+    unsafe { &*result }.entries.len()
+}
+
+/// Fills in `entry` with the library at `index` of `result`. Returns `false`, leaving `entry`
+/// alone, if `index` is out of bounds.
+///
+/// # Safety
+/// `result` must be a result from [`dlf_analyze`] not freed yet, and `entry` point to a
+/// writable `dlf_entry`.
+#[no_mangle]
+pub unsafe extern "C" fn dlf_result_get(
+    result: *const DlfResult,
+    index: usize,
+    entry: *mut DlfEntry,
+) -> bool {
+    // SAFETY: the caller passes a live result
+    let Some(library) = unsafe { &*result }.entries.get(index) else {
+        return false;
+    };
+    let optional = |string: &Option<CString>| string.as_deref().map_or(ptr::null(), CStr::as_ptr);
+    let library = DlfEntry {
+        soname: library.soname.as_ptr(),
+        path: optional(&library.path),
+        error: optional(&library.error),
+    };
+    // SAFETY: the caller passes a writable entry
+    unsafe { entry.write(library) };
+    true
+}
+
+/// Frees a result from [`dlf_analyze`], and every string it holds.
+///
+/// # Safety
+/// `result` must be null or a result from [`dlf_analyze`] not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dlf_result_free(result: *mut DlfResult) {
+    if !result.is_null() {
+        // SAFETY: the caller passes a result from `dlf_analyze`, once
+        drop(unsafe { Box::from_raw(result) });
+    }
+}
+
+/// `bytes` as a C string, which paths, sonames and messages only fail to be if they hold a
+/// NUL byte, to be cut at.
+fn c_string(bytes: &[u8]) -> CString {
+    let end = bytes
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(bytes.len());
+    CString::new(&bytes[..end]).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        dlf_analyze, dlf_resolver_free, dlf_resolver_new, dlf_result_error, dlf_result_free,
+        dlf_result_get, dlf_result_len, DlfEntry,
+    };
+    use crate::test_util::ElfBuilder;
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::ptr;
+
+    /// The string at `string`, or `None` if it is null.
+    fn read(string: *const std::ffi::c_char) -> Option<String> {
+        // SAFETY: the strings of a live result are NUL-terminated
+        (!string.is_null()).then(|| unsafe { CStr::from_ptr(string) }.to_string_lossy().into())
+    }
+
+    #[test]
+    fn test_analyze() {
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .runpath("$ORIGIN")
+            .needed("libfoo.so.1")
+            .needed("libmissing.so.1")
+            .write(dir.path(), "app");
+        let path = |path: &std::path::Path| CString::new(path.as_os_str().as_bytes()).unwrap();
+
+        // SAFETY: every pointer passed is live, and every result freed once
+        unsafe {
+            let resolver = dlf_resolver_new(ptr::null(), false);
+            let result = dlf_analyze(resolver, path(&root).as_ptr());
+            dlf_resolver_free(resolver);
+            assert!(dlf_result_error(result).is_null());
+            assert_eq!(dlf_result_len(result), 2);
+            let mut entry = DlfEntry {
+                soname: ptr::null(),
+                path: ptr::null(),
+                error: ptr::null(),
+            };
+            assert!(dlf_result_get(result, 0, &mut entry));
+            assert_eq!(read(entry.soname).as_deref(), Some("libfoo.so.1"));
+            assert_eq!(read(entry.path), libfoo.to_str().map(String::from));
+            assert!(entry.error.is_null());
+            assert!(dlf_result_get(result, 1, &mut entry));
+            assert_eq!(read(entry.soname).as_deref(), Some("libmissing.so.1"));
+            assert!(entry.path.is_null());
+            assert!(read(entry.error).unwrap().starts_with("not found"));
+            assert!(!dlf_result_get(result, 2, &mut entry));
+            dlf_result_free(result);
+
+            let resolver = dlf_resolver_new(path(dir.path()).as_ptr(), false);
+            let result = dlf_analyze(resolver, c"/nonexistent".as_ptr());
+            assert!(read(dlf_result_error(result)).is_some());
+            assert_eq!(dlf_result_len(result), 0);
+            dlf_result_free(result);
+            assert!(dlf_analyze(resolver, ptr::null()).is_null());
+            dlf_resolver_free(resolver);
+        }
+    }
+
+    #[test]
+    fn test_header_declares_every_function() {
+        let header = include_str!("../include/elf_dynamic_lib_getter.h");
+        let source = include_str!("ffi.rs");
+        let functions = source
+            .lines()
+            .filter_map(|line| line.strip_prefix("pub unsafe extern \"C\" fn "))
+            .map(|line| &line[..line.find('(').unwrap()]);
+        let mut count = 0;
+        for function in functions {
+            assert!(header.contains(&format!("{function}(")), "{function}");
+            count += 1;
+        }
+        assert_eq!(count, 7);
+    }
+}
@@ -0,0 +1,244 @@
+//! The files resolution reads, which are the host's unless they come from somewhere else, like
+//! the layers of a container image.
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::sysroot::MAX_SYMLINKS;
+
+/// What a path names, without following it if it is a symlink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FileKind {
+    /// A regular file, or a device or other special file.
+    File,
+    Dir,
+    Symlink,
+}
+
+/// What resolution needs to know about a path besides its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileMetadata {
+    pub kind: FileKind,
+    /// The size in bytes, of the contents of a file or the target of a symlink.
+    pub len: u64,
+    /// The permission bits, like `st_mode & 07777`, of which resolution only looks at the
+    /// set-user-ID and set-group-ID bits.
+    pub mode: u32,
+}
+
+/// What [`ResolverConfig::stat_files`](crate::ResolverConfig::stat_files) records of a file,
+/// like `stat` has it. What a [`FileSystem`] does not know is `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileStat {
+    /// The size of the file in bytes.
+    pub len: u64,
+    /// The permission bits, like `st_mode & 07777`.
+    pub mode: u32,
+    /// The user ID of the owner.
+    pub uid: Option<u32>,
+    /// The group ID of the owner.
+    pub gid: Option<u32>,
+    /// When the contents were last modified.
+    pub modified: Option<SystemTime>,
+}
+
+/// A tree of files to resolve libraries in, set with
+/// [`ResolverConfig::filesystem`](crate::ResolverConfig::filesystem). Every file resolution
+/// reads, from the analyzed file to `ld.so.conf` and the libraries in each search directory,
+/// is read through it, so binaries can be analyzed in archives, remote stores or in-memory
+/// trees without extracting them. Paths are absolute, and symlinks in the directories along a
+/// path are followed, as the kernel does; [`HostFs`] reads the host's files.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use std::ffi::OsString;
+/// use std::io::{self, Read, Seek, SeekFrom};
+/// use std::path::{Path, PathBuf};
+/// use elf_dynamic_lib_getter::{FileKind, FileMetadata, FileSystem};
+///
+/// /// Files in memory, with no directories but `/` and no symlinks.
+/// #[derive(Debug)]
+/// struct Flat(HashMap<PathBuf, Vec<u8>>);
+///
+/// impl FileSystem for Flat {
+///     fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+///         self.0.get(path).cloned().ok_or_else(|| io::ErrorKind::NotFound.into())
+///     }
+///
+///     fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+///         let kind = if path == Path::new("/") {
+///             FileKind::Dir
+///         } else if self.0.contains_key(path) {
+///             FileKind::File
+///         } else {
+///             return Err(io::ErrorKind::NotFound.into());
+///         };
+///         let len = self.0.get(path).map_or(0, |data| data.len() as u64);
+///         Ok(FileMetadata { kind, len, mode: 0o755 })
+///     }
+///
+///     fn read_link(&self, _: &Path) -> io::Result<PathBuf> {
+///         Err(io::ErrorKind::InvalidInput.into())
+///     }
+///
+///     fn read_dir(&self, _: &Path) -> io::Result<Vec<OsString>> {
+///         Ok(self.0.keys().filter_map(|path| Some(path.file_name()?.to_owned())).collect())
+///     }
+/// }
+/// ```
+pub trait FileSystem: fmt::Debug + Send + Sync {
+    /// The contents of the file at `path`, following symlinks.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Up to `len` bytes of the file at `path` from `offset` on, following symlinks, fewer
+    /// only where the file ends. Resolution reads the headers of the files it comes across
+    /// while searching with it, to tell whether they can be loaded. The default implementation
+    /// [reads](FileSystem::read) the whole file, which file systems that can read part of one,
+    /// like remote ones, should not.
+    fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        Ok(slice_at(&self.read(path)?, offset, len).to_vec())
+    }
+
+    /// The metadata of `path`, without following a final symlink, like `lstat`.
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    /// The target of the symlink at `path`.
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// The names of the entries in the directory at `path`, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>>;
+
+    /// The [`FileStat`] of the file at `path`, following symlinks, like `stat`. The default
+    /// implementation follows them like [`FileSystem::exists`] and has only the size and
+    /// permissions of [`FileSystem::metadata`].
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        let mut path = path.to_owned();
+        for _ in 0..=MAX_SYMLINKS {
+            let metadata = self.metadata(&path)?;
+            if metadata.kind != FileKind::Symlink {
+                return Ok(FileStat {
+                    len: metadata.len,
+                    mode: metadata.mode,
+                    uid: None,
+                    gid: None,
+                    modified: None,
+                });
+            }
+            let target = self.read_link(&path)?;
+            path = path.parent().unwrap_or(Path::new("/")).join(target);
+        }
+        Err(io::Error::other("too many levels of symbolic links"))
+    }
+
+    /// Whether `path` names a file or directory, following symlinks. The default
+    /// implementation follows them with [`FileSystem::metadata`] and
+    /// [`FileSystem::read_link`].
+    fn exists(&self, path: &Path) -> bool {
+        let mut path = path.to_owned();
+        for _ in 0..=MAX_SYMLINKS {
+            match self.metadata(&path) {
+                Ok(metadata) if metadata.kind == FileKind::Symlink => {
+                    let Ok(target) = self.read_link(&path) else {
+                        return false;
+                    };
+                    path = path.parent().unwrap_or(Path::new("/")).join(target);
+                }
+                Ok(_) => return true,
+                Err(_) => return false,
+            }
+        }
+        false
+    }
+}
+
+/// Up to `len` bytes of `data` from `offset` on, like [`FileSystem::read_at`] reads them.
+pub(crate) fn slice_at(data: &[u8], offset: u64, len: usize) -> &[u8] {
+    let start = usize::try_from(offset).map_or(data.len(), |start| start.min(data.len()));
+    &data[start..start.saturating_add(len).min(data.len())]
+}
+
+/// The host's files, read with [`std::fs`]. This is the [`FileSystem`] used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HostFs;
+
+impl FileSystem for HostFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = Vec::new();
+        file.take(len as u64).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = fs::symlink_metadata(path)?;
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_dir() {
+            FileKind::Dir
+        } else {
+            FileKind::File
+        };
+        #[cfg(unix)]
+        let mode = std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()) & 0o7777;
+        // Without Unix permissions, files are taken to be neither set-ID nor world-writable
+        #[cfg(not(unix))]
+        let mode = if metadata.permissions().readonly() {
+            0o555
+        } else {
+            0o755
+        };
+        Ok(FileMetadata {
+            kind,
+            len: metadata.len(),
+            mode,
+        })
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect()
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        let metadata = fs::metadata(path)?;
+        #[cfg(unix)]
+        let (mode, uid, gid) = {
+            use std::os::unix::fs::MetadataExt;
+            (
+                metadata.mode() & 0o7777,
+                Some(metadata.uid()),
+                Some(metadata.gid()),
+            )
+        };
+        #[cfg(not(unix))]
+        let (mode, uid, gid) = (self.metadata(path)?.mode, None, None);
+        Ok(FileStat {
+            len: metadata.len(),
+            mode,
+            uid,
+            gid,
+            modified: metadata.modified().ok(),
+        })
+    }
+}
@@ -0,0 +1,251 @@
+//! The sandboxes Flatpak runs apps in, which only have the files of the app at `/app` and
+//! those of its runtime at `/usr`, so what an app needs can be checked against its runtime.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::filesystem::{FileSystem, HostFs};
+use crate::mount_fs::MountFs;
+use crate::{sysroot, DependencyGraph, ElfFile, Error, ResolverConfig};
+
+/// The `ld.so.conf` Flatpak generates the `ld.so.cache` of a sandbox from: the directories of
+/// the app's extensions, those the app configures in `/app/etc/ld.so.conf`, `/app/lib`, and
+/// the directories of the runtime's extensions.
+const LD_SO_CONF: &[u8] = b"include /run/flatpak/ld.so.conf.d/app-*.conf\n\
+    include /app/etc/ld.so.conf\n\
+    /app/lib\n\
+    include /run/flatpak/ld.so.conf.d/runtime-*.conf\n";
+
+/// The directories of the runtime that the sandbox also has at `/`, as links into `/usr`.
+const MERGED_DIRS: [&str; 5] = ["bin", "lib", "lib32", "lib64", "sbin"];
+
+/// Which part of a Flatpak sandbox a file comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlatpakSource {
+    /// The app itself, mounted at `/app`: a library the app bundles.
+    App,
+    /// The runtime, mounted at `/usr`.
+    Runtime,
+}
+
+/// The files a Flatpak app sees in its sandbox: the `files` directory of the app at `/app`,
+/// the `files` directory of its runtime at `/usr`, and `/lib` and the other directories of the
+/// runtime linked from `/` as Flatpak links them. Libraries are searched for as in the sandbox,
+/// in the directories of the `ld.so.conf` Flatpak generates, so `/app/lib` comes before the
+/// directories of the runtime. Extensions are not mounted.
+///
+/// ```no_run
+/// use elf_dynamic_lib_getter::{FlatpakSandbox, ResolverConfig};
+///
+/// let flatpak = "/var/lib/flatpak";
+/// let sandbox = FlatpakSandbox::open(
+///     format!("{flatpak}/app/org.example.App/x86_64/stable/active/files"),
+///     format!("{flatpak}/runtime/org.freedesktop.Platform/x86_64/23.08/active/files"),
+/// )?;
+/// let graph = sandbox.dependency_graph("/app/bin/example", ResolverConfig::new())?;
+/// for node in graph.nodes().iter().filter(|node| node.resolution.is_err()) {
+///     println!("neither bundled nor in the runtime: {}", node.soname);
+/// }
+/// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct FlatpakSandbox {
+    app: PathBuf,
+    runtime: PathBuf,
+    fs: Arc<MountFs>,
+}
+
+impl FlatpakSandbox {
+    /// Sets up the sandbox of the app whose `files` directory is `app`, for the runtime whose
+    /// `files` directory is `runtime`.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if `app` or `runtime` is not a directory.
+    pub fn open<A: Into<PathBuf>, R: Into<PathBuf>>(app: A, runtime: R) -> Result<Self, Error> {
+        let (app, runtime) = (app.into(), runtime.into());
+        for dir in [&app, &runtime] {
+            match fs::metadata(dir) {
+                Ok(metadata) if metadata.is_dir() => {}
+                Ok(_) => {
+                    return Err(Error::Io {
+                        path: dir.clone(),
+                        source: io::Error::other("not a directory"),
+                    })
+                }
+                Err(source) => {
+                    return Err(Error::Io {
+                        path: dir.clone(),
+                        source,
+                    })
+                }
+            }
+        }
+        let mut fs = MountFs::empty(Arc::new(HostFs))
+            .mount("/app", &app)
+            .mount("/usr", &runtime)
+            .file("/etc/ld.so.conf", LD_SO_CONF);
+        for dir in MERGED_DIRS {
+            if runtime.join(dir).is_dir() {
+                fs = fs.symlink(Path::new("/").join(dir), Path::new("usr").join(dir));
+            }
+        }
+        Ok(FlatpakSandbox {
+            app,
+            runtime,
+            fs: Arc::new(fs),
+        })
+    }
+
+    /// The `files` directory of the app.
+    pub fn app(&self) -> &Path {
+        &self.app
+    }
+
+    /// The `files` directory of the runtime.
+    pub fn runtime(&self) -> &Path {
+        &self.runtime
+    }
+
+    /// Whether the sandbox has a file or directory at `path`, following symlinks.
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.fs.exists(&Path::new("/").join(path))
+    }
+
+    /// The contents of the file at `path` in the sandbox, following symlinks.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if the sandbox has no such file, or it can't be read.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, Error> {
+        let path = Path::new("/").join(path);
+        self.fs
+            .read(&path)
+            .map_err(|source| Error::Io { path, source })
+    }
+
+    /// Whether the file at `path` in the sandbox, following symlinks, comes from the app or
+    /// from the runtime. `None` if there is no such file, or it is one of the sandbox.
+    pub fn source<P: AsRef<Path>>(&self, path: P) -> Option<FlatpakSource> {
+        let path = sysroot::resolve_in(&*self.fs, Path::new("/"), &Path::new("/").join(path))?;
+        if path.starts_with("/app") {
+            Some(FlatpakSource::App)
+        } else if path.starts_with("/usr") {
+            Some(FlatpakSource::Runtime)
+        } else {
+            None
+        }
+    }
+
+    /// Builds the [`DependencyGraph`] of the executable or library at `path` in the sandbox,
+    /// like `/app/bin/example`, as [`ElfFile::dependency_graph`] does, according to `config`.
+    /// Every file is looked up in the sandbox, so a library the graph leaves unresolved is one
+    /// neither the app bundles nor the runtime provides. `LD_LIBRARY_PATH` and `LD_PRELOAD` are
+    /// not used, and any [sysroot](ResolverConfig::sysroot) is replaced by the sandbox.
+    ///
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn dependency_graph<P: AsRef<Path>>(
+        &self,
+        path: P,
+        config: ResolverConfig,
+    ) -> Result<DependencyGraph, Error> {
+        let config = config
+            .use_env(false)
+            .sysroot("/")
+            .filesystem(self.fs.clone());
+        ElfFile::with_config(Path::new("/").join(path), config).dependency_graph()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlatpakSandbox, FlatpakSource};
+    use crate::test_util::ElfBuilder;
+    use crate::{Error, ResolverConfig, SearchRule};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+
+    #[test]
+    fn test_flatpak() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, runtime) = (dir.path().join("app"), dir.path().join("runtime"));
+        ElfBuilder::new()
+            .needed("libbundled.so.1")
+            .needed("libextra.so.1")
+            .needed("libssl.so.3")
+            .needed("libz.so.1")
+            .needed("libmissing.so.1")
+            .write(app.join("bin"), "example");
+        ElfBuilder::new().write(app.join("lib"), "libbundled.so.1");
+        ElfBuilder::new().write(app.join("lib"), "libssl.so.3");
+        ElfBuilder::new().write(app.join("lib/extra"), "libextra.so.1");
+        fs::create_dir_all(app.join("etc")).unwrap();
+        fs::write(app.join("etc/ld.so.conf"), "/app/lib/extra\n").unwrap();
+        ElfBuilder::new().write(runtime.join("lib"), "libssl.so.3");
+        ElfBuilder::new().write(runtime.join("lib"), "libz.so.1.3");
+        // Absolute symlinks point into the sandbox, not the host
+        symlink("/lib/libz.so.1.3", runtime.join("lib/libz.so.1")).unwrap();
+
+        let sandbox = FlatpakSandbox::open(&app, &runtime).unwrap();
+        assert_eq!(sandbox.app(), app);
+        assert!(sandbox.exists("/usr/lib/libz.so.1"));
+        assert!(sandbox.exists("/lib/libz.so.1"));
+        assert!(!sandbox.exists("/app/lib/libz.so.1"));
+        assert_eq!(
+            sandbox.read("/app/etc/ld.so.conf").unwrap(),
+            b"/app/lib/extra\n"
+        );
+        assert_eq!(
+            sandbox.source("/lib/libz.so.1"),
+            Some(FlatpakSource::Runtime)
+        );
+        assert_eq!(
+            sandbox.source("app/lib/libssl.so.3"),
+            Some(FlatpakSource::App)
+        );
+        assert_eq!(sandbox.source("/etc/ld.so.conf"), None);
+
+        let graph = sandbox
+            .dependency_graph("/app/bin/example", ResolverConfig::new())
+            .unwrap();
+        let found: Vec<_> = graph.nodes()[1..]
+            .iter()
+            .map(|node| (node.soname.as_str(), node.path(), node.found_by.clone()))
+            .collect();
+        let configured = Some(SearchRule::ConfiguredDir);
+        assert_eq!(
+            found,
+            [
+                (
+                    "libbundled.so.1",
+                    Some(Path::new("/app/lib/libbundled.so.1")),
+                    configured.clone()
+                ),
+                (
+                    "libextra.so.1",
+                    Some(Path::new("/app/lib/extra/libextra.so.1")),
+                    configured.clone()
+                ),
+                // The app's own libraries come first
+                (
+                    "libssl.so.3",
+                    Some(Path::new("/app/lib/libssl.so.3")),
+                    configured
+                ),
+                (
+                    "libz.so.1",
+                    Some(Path::new("/usr/lib/libz.so.1")),
+                    Some(SearchRule::DefaultDir)
+                ),
+                ("libmissing.so.1", None, None),
+            ]
+        );
+
+        let open = FlatpakSandbox::open(&app, dir.path().join("missing"));
+        assert!(matches!(open, Err(Error::Io { .. })));
+        let open = FlatpakSandbox::open(app.join("bin/example"), &runtime);
+        assert!(matches!(open, Err(Error::Io { .. })));
+    }
+}
@@ -0,0 +1,461 @@
+//! Text renderings of a [`DependencyGraph`].
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::{
+    DependencyGraph, DependencyNode, ResolveError, Resolved, SearchRule, LD_SO_CACHE_PATH,
+};
+
+impl DependencyGraph {
+    /// Renders the graph in the Graphviz DOT language, with one node per library labelled with
+    /// its soname and path, and an edge for each `DT_NEEDED` entry. Unresolved libraries are
+    /// drawn dashed.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n");
+        for (id, node) in self.node_ids().zip(self.nodes()) {
+            let style = if node.resolution.is_ok() {
+                ""
+            } else {
+                ", style=dashed"
+            };
+            let label = format!("{}\n{}", node.soname, describe(node));
+            let _ = writeln!(dot, "    n{} [label={}{style}];", id.index(), quote(&label));
+        }
+        for (from, to) in self.edges() {
+            let _ = writeln!(dot, "    n{} -> n{};", from.index(), to.index());
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as an indented tree like `lddtree` does, with one
+    /// `soname => path` line per library under each object that needs it. The dependencies of
+    /// a library are only listed under its first occurrence.
+    pub fn to_tree(&self) -> String {
+        let root = self.node(self.root());
+        let mut tree = format!(
+            "{}\n",
+            root.path().unwrap_or(root.soname.as_ref()).display()
+        );
+        let mut expanded = HashSet::from([self.root()]);
+        let mut stack: Vec<_> = self
+            .dependencies(self.root())
+            .iter()
+            .rev()
+            .map(|&id| (id, 1))
+            .collect();
+        while let Some((id, depth)) = stack.pop() {
+            let node = self.node(id);
+            let target = match &node.resolution {
+                Ok(Resolved::Found(path)) => path.display().to_string(),
+                Ok(Resolved::Virtual) => "virtual".to_owned(),
+                Ok(Resolved::Ignored) => "ignored".to_owned(),
+                Err(ResolveError::NotFound { .. }) => "not found".to_owned(),
+                Err(error) => format!("error: {error}"),
+            };
+            let _ = writeln!(
+                tree,
+                "{:indent$}{} => {target}",
+                "",
+                node.soname,
+                indent = depth * 4
+            );
+            if expanded.insert(id) {
+                stack.extend(
+                    self.dependencies(id)
+                        .iter()
+                        .rev()
+                        .map(|&id| (id, depth + 1)),
+                );
+            }
+        }
+        tree
+    }
+
+    /// Renders the graph like `ldd` does, minus the load addresses: one tab-indented
+    /// `soname => path` line per library in load order, or `soname => not found` if it could not
+    /// be loaded, `soname => ignored` for [ignored](crate::ResolverConfig::ignore_sonames) ones,
+    /// and just the soname for virtual objects like the vDSO. Each soname is listed once and
+    /// the analyzed file itself is left out. The
+    /// [candidates](crate::ResolverConfig::exhaustive_search) of a library, if any, follow its
+    /// line, indented further, the one it resolved to marked with a `*`.
+    pub fn to_ldd(&self) -> String {
+        let mut ldd = String::new();
+        let mut listed = HashSet::new();
+        let mut queue: VecDeque<_> = self.dependencies(self.root()).iter().collect();
+        while let Some(&id) = queue.pop_front() {
+            let node = self.node(id);
+            if !listed.insert(&node.soname) {
+                continue;
+            }
+            match node.path() {
+                Some(path) => {
+                    let _ = writeln!(ldd, "\t{} => {}", node.soname, path.display());
+                }
+                // Like ldd, objects not loaded from a file get no arrow
+                None if matches!(node.resolution, Ok(Resolved::Virtual)) => {
+                    let _ = writeln!(ldd, "\t{}", node.soname);
+                }
+                None if matches!(node.resolution, Ok(Resolved::Ignored)) => {
+                    let _ = writeln!(ldd, "\t{} => ignored", node.soname);
+                }
+                None => {
+                    let _ = writeln!(ldd, "\t{} => not found", node.soname);
+                }
+            }
+            for candidate in &node.candidates {
+                let marker = if candidate.chosen { '*' } else { ' ' };
+                let path = candidate.path.display();
+                let suffix = if candidate.compatible {
+                    ""
+                } else {
+                    " (incompatible)"
+                };
+                let _ = writeln!(ldd, "\t\t{marker} {path}{suffix}");
+            }
+            queue.extend(self.dependencies(id));
+        }
+        ldd
+    }
+
+    /// Renders the [search trace](crate::ResolverConfig::trace_search) of each library in load
+    /// order, like `LD_DEBUG=libs` shows the searches of the dynamic linker: a
+    /// `find library=` line, then each list of directories searched with where it comes from,
+    /// and the paths tried in them, each with what was there. Libraries that were not searched
+    /// for are left out.
+    pub fn to_search_trace(&self) -> String {
+        let mut trace = String::new();
+        for id in self.load_order() {
+            let node = self.node(id);
+            if node.search_trace.is_empty() {
+                continue;
+            }
+            let _ = writeln!(trace, "find library={}; searching", node.soname);
+            let mut steps = node.search_trace.as_slice();
+            while let Some(first) = steps.first() {
+                let same_rule = steps
+                    .iter()
+                    .take_while(|step| step.found_by == first.found_by)
+                    .count();
+                let (group, rest) = steps.split_at(same_rule);
+                if first.found_by == SearchRule::Cache {
+                    let _ = writeln!(trace, " search cache={LD_SO_CACHE_PATH}");
+                }
+                if let Some(from) = search_list(&first.found_by) {
+                    let mut dirs: Vec<&Path> = Vec::new();
+                    for dir in group.iter().filter_map(|step| step.path.parent()) {
+                        if !dirs.contains(&dir) {
+                            dirs.push(dir);
+                        }
+                    }
+                    let dirs: Vec<_> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+                    let _ = writeln!(trace, " search path={}\t\t({from})", dirs.join(":"));
+                }
+                for step in group {
+                    let path = step.path.display();
+                    let _ = writeln!(trace, "  trying file={path} ({})", step.result);
+                }
+                steps = rest;
+            }
+            trace.push('\n');
+        }
+        trace
+    }
+
+    /// Renders the [search path](crate::ResolverConfig::record_search_paths) of each object in
+    /// load order: its path, then each place searched for its dependencies on a line of its
+    /// own, with where it comes from. Objects whose dependencies were not searched for are left
+    /// out.
+    pub fn to_search_paths(&self) -> String {
+        let mut out = String::new();
+        for id in self.load_order() {
+            let node = self.node(id);
+            if node.search_path.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "{}:", describe(node));
+            for entry in &node.search_path {
+                let from = match &entry.found_by {
+                    SearchRule::Cache => Some("cache".to_owned()),
+                    rule => search_list(rule),
+                };
+                let path = entry.path.display();
+                let _ = writeln!(out, "  {path} ({})", from.unwrap_or_default());
+            }
+        }
+        out
+    }
+
+    /// Renders the analyzed file and every library found for it as a Makefile depfile, which
+    /// Make and Ninja read to re-run the step building `target` when any of them changes, like
+    /// the depfiles `gcc -MD -MP` writes: a `target:` rule needing each file, then an empty rule
+    /// for each library, so a library that goes away re-runs the step instead of failing the
+    /// build. Libraries that were not found are left out, as there is no file to watch.
+    pub fn to_depfile(&self, target: &str) -> String {
+        let mut listed = HashSet::new();
+        let paths: Vec<&Path> = self
+            .nodes()
+            .iter()
+            .filter_map(DependencyNode::path)
+            .filter(|path| listed.insert(*path))
+            .collect();
+        let mut depfile = make_escape(target) + ":";
+        for path in &paths {
+            let _ = write!(depfile, " \\\n {}", make_escape(&path.to_string_lossy()));
+        }
+        depfile.push('\n');
+        for path in paths.iter().skip(1) {
+            let _ = writeln!(depfile, "\n{}:", make_escape(&path.to_string_lossy()));
+        }
+        depfile
+    }
+}
+
+/// The resolved path of `node`, or why it has none.
+/// Where the directories searched by `rule` come from, as `LD_DEBUG=libs` names the list,
+/// `None` for the rules that don't search directories.
+fn search_list(rule: &SearchRule) -> Option<String> {
+    match rule {
+        // Paths and overrides are tried without searching, and the cache has no directories
+        SearchRule::Path | SearchRule::Override | SearchRule::Cache => None,
+        SearchRule::Rpath { owner } => Some(format!("RPATH from file {}", owner.display())),
+        SearchRule::Runpath { owner } => Some(format!("RUNPATH from file {}", owner.display())),
+        SearchRule::LdLibraryPath => Some("LD_LIBRARY_PATH".to_owned()),
+        SearchRule::SearchDir => Some("custom search path".to_owned()),
+        SearchRule::ConfiguredDir => Some("configured search path".to_owned()),
+        SearchRule::DefaultDir => Some("system search path".to_owned()),
+    }
+}
+
+fn describe(node: &DependencyNode) -> String {
+    match &node.resolution {
+        Ok(Resolved::Found(path)) => path.display().to_string(),
+        Ok(Resolved::Virtual) => "provided by the kernel".to_owned(),
+        Ok(Resolved::Ignored) => "ignored".to_owned(),
+        Err(error) => error.to_string(),
+    }
+}
+
+/// Quotes `string` as a DOT string literal.
+fn quote(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Escapes `path` for a Makefile rule, as gcc does in depfiles: spaces and `#` get a
+/// backslash, and `$` is doubled.
+fn make_escape(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            ' ' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '$' => escaped.push_str("$$"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Candidate, DependencyGraph, DependencyKind, DependencyNode, ProbeResult, ResolveError,
+        Resolved, SearchPathEntry, SearchRule, SearchStep,
+    };
+    use std::path::PathBuf;
+
+    /// An app needing libfoo, which is also needed by libbar, and a missing library, with the
+    /// vDSO.
+    fn test_graph() -> DependencyGraph {
+        let path = |path: &str| Ok(Resolved::Found(PathBuf::from(path)));
+        let mut graph = DependencyGraph::new(DependencyNode::new("app", path("/opt/app")));
+        let root = graph.root();
+        let foo = graph.add_node(DependencyNode::new("libfoo.so.1", path("/lib/libfoo.so.1")));
+        let bar = graph.add_node(DependencyNode::new("libbar.so.1", path("/lib/libbar.so.1")));
+        let not_found = ResolveError::NotFound {
+            needed_by: PathBuf::from("/opt/app"),
+        };
+        let missing = graph.add_node(DependencyNode::new("libmissing.so.1", Err(not_found)));
+        let mut vdso = DependencyNode::new("linux-vdso.so.1", Ok(Resolved::Virtual));
+        vdso.kind = DependencyKind::Virtual;
+        let vdso = graph.add_node(vdso);
+        graph.add_edge(root, vdso);
+        graph.add_edge(root, foo);
+        graph.add_edge(root, bar);
+        graph.add_edge(bar, foo);
+        graph.add_edge(root, missing);
+        graph
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let expected = r#"digraph dependencies {
+    n0 [label="app\n/opt/app"];
+    n1 [label="libfoo.so.1\n/lib/libfoo.so.1"];
+    n2 [label="libbar.so.1\n/lib/libbar.so.1"];
+    n3 [label="libmissing.so.1\nnot found (needed by \"/opt/app\")", style=dashed];
+    n4 [label="linux-vdso.so.1\nprovided by the kernel"];
+    n0 -> n4;
+    n0 -> n1;
+    n0 -> n2;
+    n0 -> n3;
+    n2 -> n1;
+}
+"#;
+        assert_eq!(test_graph().to_dot(), expected);
+    }
+
+    #[test]
+    fn test_to_tree() {
+        let expected = "\
+/opt/app
+    linux-vdso.so.1 => virtual
+    libfoo.so.1 => /lib/libfoo.so.1
+    libbar.so.1 => /lib/libbar.so.1
+        libfoo.so.1 => /lib/libfoo.so.1
+    libmissing.so.1 => not found
+";
+        assert_eq!(test_graph().to_tree(), expected);
+    }
+
+    #[test]
+    fn test_to_ldd() {
+        let expected = "\
+\tlinux-vdso.so.1
+\tlibfoo.so.1 => /lib/libfoo.so.1
+\tlibbar.so.1 => /lib/libbar.so.1
+\tlibmissing.so.1 => not found
+";
+        assert_eq!(test_graph().to_ldd(), expected);
+
+        let mut graph = test_graph();
+        let foo = graph.find_path("/lib/libfoo.so.1").unwrap();
+        let candidate = |path: &str, compatible, chosen| Candidate {
+            path: PathBuf::from(path),
+            found_by: SearchRule::DefaultDir,
+            compatible,
+            chosen,
+        };
+        graph.node_mut(foo).candidates = vec![
+            candidate("/lib32/libfoo.so.1", false, false),
+            candidate("/lib/libfoo.so.1", true, true),
+            candidate("/usr/lib/libfoo.so.1", true, false),
+        ];
+        let expected = "\
+\tlinux-vdso.so.1
+\tlibfoo.so.1 => /lib/libfoo.so.1
+\t\t  /lib32/libfoo.so.1 (incompatible)
+\t\t* /lib/libfoo.so.1
+\t\t  /usr/lib/libfoo.so.1
+\tlibbar.so.1 => /lib/libbar.so.1
+\tlibmissing.so.1 => not found
+";
+        assert_eq!(graph.to_ldd(), expected);
+    }
+
+    #[test]
+    fn test_to_depfile() {
+        let expected = "\
+out/app\\ bundle.tar: \\
+ /opt/app \\
+ /lib/libfoo.so.1 \\
+ /lib/libbar.so.1
+
+/lib/libfoo.so.1:
+
+/lib/libbar.so.1:
+";
+        assert_eq!(test_graph().to_depfile("out/app bundle.tar"), expected);
+
+        let path = |path: &str| Ok(Resolved::Found(PathBuf::from(path)));
+        let graph = DependencyGraph::new(DependencyNode::new("app", path("/opt/$app#1")));
+        assert_eq!(graph.to_depfile("out"), "out: \\\n /opt/$$app\\#1\n");
+    }
+
+    #[test]
+    fn test_to_search_trace() {
+        let mut graph = test_graph();
+        let step = |path: &str, found_by, result| SearchStep {
+            path: PathBuf::from(path),
+            found_by,
+            result,
+        };
+        let rpath = SearchRule::Rpath {
+            owner: PathBuf::from("/opt/app"),
+        };
+        let foo = graph.find_path("/lib/libfoo.so.1").unwrap();
+        graph.node_mut(foo).search_trace = vec![
+            step("/opt/lib/libfoo.so.1", rpath.clone(), ProbeResult::NotFound),
+            step("/opt/lib32/libfoo.so.1", rpath, ProbeResult::WrongClass),
+            step("/lib/libfoo.so.1", SearchRule::Cache, ProbeResult::Found),
+        ];
+        let bar = graph.find_path("/lib/libbar.so.1").unwrap();
+        let path = step(
+            "/lib/libbar.so.1",
+            SearchRule::DefaultDir,
+            ProbeResult::Found,
+        );
+        graph.node_mut(bar).search_trace = vec![path];
+        let expected = "\
+find library=libfoo.so.1; searching
+ search path=/opt/lib:/opt/lib32\t\t(RPATH from file /opt/app)
+  trying file=/opt/lib/libfoo.so.1 (not found)
+  trying file=/opt/lib32/libfoo.so.1 (wrong class)
+ search cache=/etc/ld.so.cache
+  trying file=/lib/libfoo.so.1 (found)
+
+find library=libbar.so.1; searching
+ search path=/lib\t\t(system search path)
+  trying file=/lib/libbar.so.1 (found)
+
+";
+        assert_eq!(graph.to_search_trace(), expected);
+    }
+
+    #[test]
+    fn test_to_search_paths() {
+        let mut graph = test_graph();
+        let entry = |path: &str, found_by| SearchPathEntry {
+            path: PathBuf::from(path),
+            found_by,
+        };
+        let root = graph.root();
+        graph.node_mut(root).search_path = vec![
+            entry(
+                "/opt/lib",
+                SearchRule::Runpath {
+                    owner: PathBuf::from("/opt/app"),
+                },
+            ),
+            entry("/etc/ld.so.cache", SearchRule::Cache),
+            entry("/lib", SearchRule::DefaultDir),
+        ];
+        let bar = graph.find_path("/lib/libbar.so.1").unwrap();
+        graph.node_mut(bar).search_path = vec![entry("/lib", SearchRule::DefaultDir)];
+        let expected = "\
+/opt/app:
+  /opt/lib (RUNPATH from file /opt/app)
+  /etc/ld.so.cache (cache)
+  /lib (system search path)
+/lib/libbar.so.1:
+  /lib (system search path)
+";
+        assert_eq!(graph.to_search_paths(), expected);
+    }
+}
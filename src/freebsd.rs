@@ -0,0 +1,80 @@
+//! The search path of FreeBSD's dynamic linker, which reads the directories configured with
+//! `ldconfig` from a hints file rather than from `ld.so.conf` and a cache.
+use std::path::PathBuf;
+
+/// The location of the hints file.
+pub(crate) const HINTS_PATH: &str = "/var/run/ld-elf.so.hints";
+/// The location of the hints file for 32-bit executables on 64-bit systems.
+pub(crate) const HINTS_PATH_32: &str = "/var/run/ld-elf32.so.hints";
+/// The directories searched after those in the hints file.
+pub(crate) const DEFAULT_SEARCH_DIRS: [&str; 2] = ["/lib", "/usr/lib"];
+/// The directories searched after those in the 32-bit hints file.
+pub(crate) const DEFAULT_SEARCH_DIRS_32: [&str; 1] = ["/usr/lib32"];
+
+/// `"Ehnt"`, the magic number at the start of a hints file.
+const HINTS_MAGIC: u32 = 0x746e6845;
+/// The size of the header, which is followed by the string table.
+const HEADER_SIZE: usize = 128;
+
+/// Reads the directory list from the contents of a hints file, which may be in either byte
+/// order. Returns `None` if `data` is not a valid hints file.
+pub(crate) fn parse_hints(data: &[u8]) -> Option<Vec<PathBuf>> {
+    let header = data.get(..HEADER_SIZE)?;
+    let magic = header[..4].try_into().ok()?;
+    let read: fn([u8; 4]) -> u32 = if u32::from_le_bytes(magic) == HINTS_MAGIC {
+        u32::from_le_bytes
+    } else if u32::from_be_bytes(magic) == HINTS_MAGIC {
+        u32::from_be_bytes
+    } else {
+        return None;
+    };
+    let field = |index: usize| -> Option<usize> {
+        let bytes = header.get(index * 4..index * 4 + 4)?.try_into().ok()?;
+        usize::try_from(read(bytes)).ok()
+    };
+    let (version, strtab, strsize) = (field(1)?, field(2)?, field(3)?);
+    let (dirlist, dirlist_len) = (field(4)?, field(5)?);
+    if version != 1 || dirlist.checked_add(dirlist_len)? > strsize {
+        return None;
+    }
+    let start = strtab.checked_add(dirlist)?;
+    let dirs = data.get(start..start.checked_add(dirlist_len)?)?;
+    let dirs = std::str::from_utf8(dirs).ok()?;
+    Some(
+        dirs.split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+    )
+}
+
+/// Builds a little-endian hints file listing `dirs`.
+#[cfg(test)]
+pub(crate) fn build_hints(dirs: &str) -> Vec<u8> {
+    let fields = [HINTS_MAGIC, 1, HEADER_SIZE as u32, dirs.len() as u32 + 1, 0];
+    let mut data: Vec<u8> = fields
+        .iter()
+        .flat_map(|field| field.to_le_bytes())
+        .collect();
+    data.extend_from_slice(&(dirs.len() as u32).to_le_bytes());
+    data.resize(HEADER_SIZE, 0);
+    data.extend_from_slice(dirs.as_bytes());
+    data.push(0);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_hints, parse_hints};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_hints() {
+        let data = build_hints("/lib:/usr/lib:/usr/local/lib");
+        let expected = ["/lib", "/usr/lib", "/usr/local/lib"].map(PathBuf::from);
+        assert_eq!(parse_hints(&data).unwrap(), expected);
+        assert_eq!(parse_hints(&data[..64]), None);
+        assert_eq!(parse_hints(&data[..data.len() - 4]), None);
+        assert_eq!(parse_hints(b"not a hints file"), None);
+    }
+}
@@ -0,0 +1,292 @@
+//! Decoding with `goblin` instead of the `elf` crate, for the `goblin` feature.
+use goblin::container::{Container, Ctx, Endian};
+use goblin::elf::dynamic::Dynamic;
+use goblin::elf::header::{EI_ABIVERSION, EI_CLASS, EI_DATA, EI_OSABI, ELFCLASS64, ELFDATA2MSB};
+use goblin::elf::note::NoteDataIterator;
+use goblin::elf::program_header::ProgramHeader as GoblinProgramHeader;
+use goblin::elf::section_header::SectionHeader as GoblinSectionHeader;
+use goblin::elf::sym::Sym;
+use goblin::elf::symver::{VerdefSection, VerneedSection};
+use goblin::elf::Elf;
+
+use crate::abi::{PT_DYNAMIC, SHT_GNU_VERDEF, SHT_GNU_VERNEED};
+use crate::object::{
+    Backend, FileHeader, Note, ProgramHeader, SectionHeader, Symbol, VersionDef, VersionNeed,
+};
+use crate::ParseError;
+
+/// Decodes with `goblin`. Like the `elf` backend, only the parts of files that are needed are
+/// read, as [`Object`](crate::object::Object) hands each structure over on its own.
+pub(crate) struct GoblinBackend;
+
+impl Backend for GoblinBackend {
+    fn file_header(data: &[u8]) -> Result<FileHeader, ParseError> {
+        let ehdr = Elf::parse_header(data).map_err(ParseError::malformed)?;
+        Ok(FileHeader {
+            is_64_bit: ehdr.e_ident[EI_CLASS] == ELFCLASS64,
+            big_endian: ehdr.e_ident[EI_DATA] == ELFDATA2MSB,
+            os_abi: ehdr.e_ident[EI_OSABI],
+            abi_version: ehdr.e_ident[EI_ABIVERSION],
+            e_type: ehdr.e_type,
+            e_machine: ehdr.e_machine,
+            e_phoff: ehdr.e_phoff,
+            e_shoff: ehdr.e_shoff,
+            e_phentsize: ehdr.e_phentsize,
+            e_phnum: ehdr.e_phnum,
+            e_shentsize: ehdr.e_shentsize,
+            e_shnum: ehdr.e_shnum,
+            e_shstrndx: ehdr.e_shstrndx,
+        })
+    }
+
+    fn section_headers(header: &FileHeader, data: &[u8]) -> Result<Vec<SectionHeader>, ParseError> {
+        let ctx = context(header);
+        let count = data.len() / GoblinSectionHeader::size(ctx);
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let sections =
+            GoblinSectionHeader::parse_from(data, 0, count, ctx).map_err(ParseError::malformed)?;
+        let sections = sections.into_iter().map(|shdr| SectionHeader {
+            sh_name: shdr.sh_name as u32,
+            sh_type: shdr.sh_type,
+            sh_flags: shdr.sh_flags,
+            sh_addr: shdr.sh_addr,
+            sh_offset: shdr.sh_offset,
+            sh_size: shdr.sh_size,
+            sh_link: shdr.sh_link,
+            sh_info: shdr.sh_info,
+            sh_addralign: shdr.sh_addralign,
+            sh_entsize: shdr.sh_entsize,
+        });
+        Ok(sections.collect())
+    }
+
+    fn program_headers(header: &FileHeader, data: &[u8]) -> Result<Vec<ProgramHeader>, ParseError> {
+        let ctx = context(header);
+        let count = data.len() / GoblinProgramHeader::size(ctx);
+        let segments =
+            GoblinProgramHeader::parse(data, 0, count, ctx).map_err(ParseError::malformed)?;
+        let segments = segments.into_iter().map(|phdr| ProgramHeader {
+            p_type: phdr.p_type,
+            p_flags: phdr.p_flags,
+            p_offset: phdr.p_offset,
+            p_vaddr: phdr.p_vaddr,
+            p_filesz: phdr.p_filesz,
+            p_memsz: phdr.p_memsz,
+            p_align: phdr.p_align,
+        });
+        Ok(segments.collect())
+    }
+
+    fn dynamic(header: &FileHeader, data: &[u8]) -> Vec<(i64, u64)> {
+        // `goblin` finds the dynamic array through its program header only
+        let phdr = GoblinProgramHeader {
+            p_type: PT_DYNAMIC,
+            p_filesz: data.len() as u64,
+            ..GoblinProgramHeader::default()
+        };
+        let dynamic = Dynamic::parse(data, &[phdr], context(header));
+        let entries = dynamic.ok().flatten().map(|dynamic| dynamic.dyns);
+        let entries = entries.into_iter().flatten();
+        entries
+            .map(|entry| (entry.d_tag as i64, entry.d_val))
+            .collect()
+    }
+
+    fn symbols(header: &FileHeader, data: &[u8]) -> Vec<Symbol> {
+        let ctx = context(header);
+        let count = data.len() / Sym::size(ctx.container);
+        let symbols = Sym::parse(data, 0, count, ctx).unwrap_or_default();
+        let symbols = symbols.into_iter().map(|symbol| Symbol {
+            st_name: symbol.st_name as u32,
+            st_info: symbol.st_info,
+            st_other: symbol.st_visibility(),
+            st_shndx: symbol.st_shndx as u16,
+            st_value: symbol.st_value,
+            st_size: symbol.st_size,
+        });
+        symbols.collect()
+    }
+
+    fn notes<'a>(header: &FileHeader, align: u64, data: &'a [u8]) -> Vec<Note<'a>> {
+        let Ok(align) = usize::try_from(align) else {
+            return Vec::new();
+        };
+        let notes = NoteDataIterator {
+            data,
+            size: data.len(),
+            offset: 0,
+            ctx: (align, context(header)),
+        };
+        // A malformed note ends the iteration, as its size can't be trusted
+        let notes = notes.map_while(Result::ok).map(|note| Note {
+            n_type: note.n_type.into(),
+            name: note.name.as_bytes(),
+            desc: note.desc,
+        });
+        notes.collect()
+    }
+
+    fn version_needs(header: &FileHeader, data: &[u8], count: u64) -> Vec<VersionNeed> {
+        let shdr = version_section(SHT_GNU_VERNEED, data, count);
+        let section = VerneedSection::parse(data, &[shdr], context(header));
+        let Ok(Some(section)) = section else {
+            return Vec::new();
+        };
+        let needs = section.iter().map(|need| VersionNeed {
+            file: need.vn_file as u32,
+            versions: need
+                .iter()
+                .map(|aux| (aux.vna_name as u32, aux.vna_flags))
+                .collect(),
+        });
+        needs.collect()
+    }
+
+    fn version_defs(header: &FileHeader, data: &[u8], count: u64) -> Vec<VersionDef> {
+        let shdr = version_section(SHT_GNU_VERDEF, data, count);
+        let section = VerdefSection::parse(data, &[shdr], context(header));
+        let Ok(Some(section)) = section else {
+            return Vec::new();
+        };
+        let defs = section.iter().map(|def| VersionDef {
+            index: def.vd_ndx,
+            flags: def.vd_flags,
+            names: def.iter().map(|aux| aux.vda_name as u32).collect(),
+        });
+        defs.collect()
+    }
+}
+
+/// The layout `goblin` decodes the structures of the file with.
+fn context(header: &FileHeader) -> Ctx {
+    let container = if header.is_64_bit {
+        Container::Big
+    } else {
+        Container::Little
+    };
+    Ctx::new(container, Endian::from(!header.big_endian))
+}
+
+/// A header for the version section `data` of type `sh_type`, with `count` entries, which is
+/// how `goblin` finds version sections.
+fn version_section(sh_type: u32, data: &[u8], count: u64) -> GoblinSectionHeader {
+    GoblinSectionHeader {
+        sh_type,
+        sh_size: data.len() as u64,
+        sh_info: count as u32,
+        ..GoblinSectionHeader::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "elf")]
+    use super::GoblinBackend;
+    #[cfg(feature = "elf")]
+    use crate::abi::{
+        PT_DYNAMIC, PT_NOTE, SHT_DYNAMIC, SHT_DYNSYM, SHT_GNU_VERDEF, SHT_GNU_VERNEED, SHT_NOTE,
+        SHT_SYMTAB,
+    };
+    #[cfg(feature = "elf")]
+    use crate::elf_backend::ElfBackend;
+    #[cfg(feature = "elf")]
+    use crate::object::Backend;
+    use crate::object::{Object, Stream};
+    use crate::test_util::ElfBuilder;
+    use std::io::Cursor;
+
+    /// What `B` decodes the parts of the file `data` into, in the order `Object` reads them.
+    #[cfg(feature = "elf")]
+    fn decode<B: Backend>(data: &[u8]) -> String {
+        let slice = |offset: u64, size: u64| {
+            let start = offset as usize;
+            data.get(start..start + size as usize).unwrap_or_default()
+        };
+        let Ok(header) = B::file_header(&data[..data.len().min(64)]) else {
+            return "not ELF".to_owned();
+        };
+        let entsize = |size: u16| u64::from(size);
+        let sections = B::section_headers(
+            &header,
+            slice(
+                header.e_shoff,
+                entsize(header.e_shentsize) * u64::from(header.e_shnum),
+            ),
+        );
+        let segments = B::program_headers(
+            &header,
+            slice(
+                header.e_phoff,
+                entsize(header.e_phentsize) * u64::from(header.e_phnum),
+            ),
+        );
+        let mut decoded = format!("{header:?} {sections:?} {segments:?}");
+        for shdr in sections.unwrap_or_default() {
+            let data = slice(shdr.sh_offset, shdr.sh_size);
+            let count = shdr.sh_info.into();
+            let contents = match shdr.sh_type {
+                SHT_DYNAMIC => format!("{:?}", B::dynamic(&header, data)),
+                SHT_SYMTAB | SHT_DYNSYM => format!("{:?}", B::symbols(&header, data)),
+                SHT_NOTE => format!("{:?}", B::notes(&header, shdr.sh_addralign, data)),
+                SHT_GNU_VERNEED => format!("{:?}", B::version_needs(&header, data, count)),
+                SHT_GNU_VERDEF => format!("{:?}", B::version_defs(&header, data, count)),
+                _ => continue,
+            };
+            decoded.push_str(&contents);
+        }
+        for phdr in segments.unwrap_or_default() {
+            let data = slice(phdr.p_offset, phdr.p_filesz);
+            let contents = match phdr.p_type {
+                PT_DYNAMIC => format!("{:?}", B::dynamic(&header, data)),
+                PT_NOTE => format!("{:?}", B::notes(&header, phdr.p_align, data)),
+                _ => continue,
+            };
+            decoded.push_str(&contents);
+        }
+        decoded
+    }
+
+    #[test]
+    #[cfg(feature = "elf")]
+    fn test_backends_agree() {
+        let builder = ElfBuilder::new()
+            .interpreter("/lib64/ld-linux-x86-64.so.2")
+            .soname("libfoo.so.1")
+            .needed("libbar.so.2")
+            .rpath("$ORIGIN/../lib")
+            .runpath("/opt/foo")
+            .build_id(&[0xde, 0xad, 0xbe, 0xef])
+            .imports("bar")
+            .defines_versioned("foo", "FOO_1.0", true)
+            .needs_version("libbar.so.2", "BAR_1.0")
+            .defines_version("FOO_1.0")
+            .dlopen_note(r#"[{"priority":"suggested","soname":["libfoo.so.1"]}]"#);
+        for builder in [
+            builder.clone(),
+            builder.clone().elf32(),
+            builder.clone().without_section_headers(),
+            builder.without_dynamic_section(),
+        ] {
+            let data = builder.build();
+            assert_eq!(decode::<GoblinBackend>(&data), decode::<ElfBackend>(&data));
+        }
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let data = ElfBuilder::new().needed("libfoo.so.1").build();
+        let parse = |data: &[u8]| {
+            let _ = Object::open(Stream(Cursor::new(data))).and_then(|mut object| object.dynamic());
+        };
+        for len in 0..data.len() {
+            parse(&data[..len]);
+        }
+        for index in 0..data.len() {
+            let mut corrupted = data.clone();
+            corrupted[index] = 0xff;
+            parse(&corrupted);
+        }
+    }
+}
@@ -0,0 +1,670 @@
+//! The graph returned by [`ElfFile::dependency_graph`](crate::ElfFile::dependency_graph).
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    DlopenPriority, DynamicInfo, ElfHeader, FileStat, Rejection, Resolution, ResourceLimit,
+    SearchPathEntry, SearchStep, StorePath,
+};
+
+/// Identifies a node within a [`DependencyGraph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct NodeId(pub(crate) usize);
+
+impl NodeId {
+    /// The position of this node in [`DependencyGraph::nodes`].
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// How an object refers to a library it depends on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DependencyKind {
+    /// A `DT_NEEDED` entry.
+    #[default]
+    Needed,
+    /// A `DT_FILTER` entry: the library provides the symbols of the filter object that names it.
+    Filter,
+    /// A `DT_AUXILIARY` entry: like [`DependencyKind::Filter`], but optional, so a missing
+    /// library is left out instead of being reported.
+    Auxiliary,
+    /// A library ld.so loads before the executable's own dependencies, e.g. one listed in
+    /// [`LD_SO_PRELOAD_PATH`](crate::LD_SO_PRELOAD_PATH). Preloads appear as dependencies of the
+    /// analyzed file.
+    Preload,
+    /// A library in one of the [plugin directories](crate::ResolverConfig::plugin_dirs), which
+    /// the analyzed file is taken to load at run time. Plugins appear as dependencies of the
+    /// analyzed file, named by their paths.
+    Plugin,
+    /// A Mach-O `LC_LOAD_WEAK_DYLIB` load command: the library is optional, so a missing one
+    /// is left out instead of being reported.
+    Weak,
+    /// A PE delay-load import: Windows loads the DLL the first time one of its functions is
+    /// called, rather than with the importing file.
+    DelayLoad,
+    /// The program interpreter named by the `PT_INTERP` of the analyzed file, listed with
+    /// [`ResolverConfig::include_interpreter`](crate::ResolverConfig::include_interpreter).
+    /// The kernel maps it into the process along with the executable, so it is loaded even if
+    /// no `DT_NEEDED` entry names it.
+    Interpreter,
+    /// An object the kernel maps into every process, like the vDSO, listed with
+    /// [`ResolverConfig::virtual_objects`](crate::ResolverConfig::virtual_objects). It is never
+    /// searched for, and its resolution is [`Resolved::Virtual`](crate::Resolved::Virtual).
+    Virtual,
+    /// A library the object declares in a `.note.dlopen` note that it loads at run time with
+    /// `dlopen`, listed with [`ResolverConfig::dlopen_notes`](crate::ResolverConfig::dlopen_notes).
+    /// Unless the priority is [required](DlopenPriority::Required), a missing library is left
+    /// out instead of being reported, as the program works without it.
+    Dlopen(DlopenPriority),
+}
+
+impl DependencyKind {
+    /// Whether a missing library of this kind is left out instead of being reported.
+    pub(crate) fn is_optional(self) -> bool {
+        match self {
+            DependencyKind::Auxiliary => true,
+            DependencyKind::Dlopen(priority) => priority != DlopenPriority::Required,
+            _ => false,
+        }
+    }
+}
+
+/// The rule of the dynamic linker's search order that found a library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SearchRule {
+    /// The name contains a slash, so it was used as a path rather than searched for.
+    Path,
+    /// A `DT_RPATH` entry of `owner`, which is the object that needed the library or one that
+    /// (transitively) loaded it.
+    Rpath {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_path"))]
+        owner: PathBuf,
+    },
+    /// An `LD_LIBRARY_PATH` entry.
+    LdLibraryPath,
+    /// A `DT_RUNPATH` entry of `owner`. With musl, which passes `RUNPATH`s on like `RPATH`s,
+    /// `owner` may also be an object that loaded the one needing the library.
+    Runpath {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_path"))]
+        owner: PathBuf,
+    },
+    /// A [custom search directory](crate::ResolverConfig::with_search_dirs).
+    SearchDir,
+    /// An entry of the `ld.so.cache`.
+    Cache,
+    /// A directory from the system's linker configuration: `ld.so.conf`, musl's path file,
+    /// FreeBSD's hints file or Android's `ld.config.txt`.
+    ConfiguredDir,
+    /// A directory the dynamic linker searches without being configured to, like `/usr/lib`.
+    DefaultDir,
+    /// A [soname override](crate::ResolverConfig::soname_overrides), which takes the place of
+    /// the search.
+    Override,
+}
+
+impl SearchRule {
+    /// Whether the rule finds the libraries of the system, in the directories the dynamic
+    /// linker searches for every program, rather than ones a program ships or is pointed to.
+    pub fn is_system(&self) -> bool {
+        matches!(
+            self,
+            SearchRule::Cache | SearchRule::ConfiguredDir | SearchRule::DefaultDir
+        )
+    }
+}
+
+/// A file by the name of a library, found while searching for it with
+/// [`ResolverConfig::exhaustive_search`](crate::ResolverConfig::exhaustive_search).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Candidate {
+    /// The path the file was found at.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_path"))]
+    pub path: PathBuf,
+    /// Which rule found the file.
+    pub found_by: SearchRule,
+    /// Whether the file could be loaded, being built for the architecture (and operating
+    /// system ABI) of the executable. ld.so skips files that can't.
+    pub compatible: bool,
+    /// Whether this is the file the library resolved to, the first compatible one.
+    pub chosen: bool,
+}
+
+/// A single object in a [`DependencyGraph`].
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DependencyNode {
+    /// The `DT_NEEDED` (or filtee) name this library was requested by. For the root node this
+    /// is the file name of the analyzed file. Bytes that are not UTF-8 are replaced with
+    /// `U+FFFD`, but the library is still searched for by its exact name.
+    pub soname: String,
+    /// How the object that first led to this library refers to it.
+    pub kind: DependencyKind,
+    /// Where the library was found, or why it could not be.
+    pub resolution: Resolution,
+    /// The symlinks followed from the path the library was found at to the file itself: the
+    /// target of that path, the target of the target, and so on, the last being the library
+    /// file. Empty if the library was not found through a symlink. `libfoo.so.1` usually points
+    /// at `libfoo.so.1.2.3`, the only link, so copying the found path and its links reproduces
+    /// how the library is installed.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_path::vec"))]
+    pub links: Vec<PathBuf>,
+    /// Which rule found the library, to tell why it was picked over others of the same name.
+    /// `None` for the root, for unresolved libraries, and for Mach-O and PE files.
+    pub found_by: Option<SearchRule>,
+    /// The library's own `DT_SONAME`, the name it advertises, which is normally the name it
+    /// was requested by. `None` if it has none, if it was not read because the graph is not
+    /// [recursive](crate::ResolverConfig::recursive), and for Mach-O and PE files. See
+    /// [`DependencyNode::is_misnamed`].
+    pub dt_soname: Option<String>,
+    /// Whether the library's own dependencies were left out because it is at the
+    /// [maximum depth](crate::ResolverConfig::max_depth) or by the
+    /// [traversal filter](crate::ResolverConfig::traversal_filter).
+    pub truncated: bool,
+    /// The digest of the contents of the file, with
+    /// [`ResolverConfig::content_digest`](crate::ResolverConfig::content_digest). `None`
+    /// without it, for unresolved libraries, and for files that could not be read.
+    pub digest: Option<String>,
+    /// The size, modification time, owner and permissions of the file when it was found, with
+    /// [`ResolverConfig::stat_files`](crate::ResolverConfig::stat_files). `None` without it,
+    /// for unresolved libraries, and for files that could not be read.
+    pub stat: Option<FileStat>,
+    /// The GNU build ID of the file, from its `.note.gnu.build-id`, in lowercase hexadecimal:
+    /// the key symbol servers and debuginfod find its debug information by. `None` if it has
+    /// none, when [`DependencyNode::dt_soname`] would not be read, and for Mach-O and PE files.
+    pub build_id: Option<String>,
+    /// What the library's ELF header says it is and what it is built for. `None` when
+    /// [`DependencyNode::dt_soname`] would not be read, and for Mach-O and PE files.
+    pub header: Option<ElfHeader>,
+    /// The raw `DT_NEEDED`, `DT_RPATH`, `DT_RUNPATH` and `DT_SONAME` entries of the library,
+    /// as written. `None` when [`DependencyNode::dt_soname`] would not be read, and for Mach-O
+    /// and PE files.
+    pub dynamic: Option<DynamicInfo>,
+    /// With a [store](crate::ResolverConfig::store), the store path of the package the file
+    /// is in. `None` otherwise, for unresolved libraries, and for files outside the store.
+    pub store_path: Option<StorePath>,
+    /// Whether the library is built for another operating system ABI than the analyzed file,
+    /// which is only accepted with [`OsAbiCheck::Warn`](crate::OsAbiCheck::Warn). Always
+    /// `false` when [`DependencyNode::dt_soname`] would not be read.
+    pub os_abi_mismatch: bool,
+    /// With [`ResolverConfig::exhaustive_search`](crate::ResolverConfig::exhaustive_search),
+    /// every file by the library's name in the directories searched for it, in search order.
+    /// Empty otherwise, for the root, and for Mach-O and PE files.
+    pub candidates: Vec<Candidate>,
+    /// With [`ResolverConfig::trace_search`](crate::ResolverConfig::trace_search), every path
+    /// probed for the library in search order, up to the one it resolved to. Empty otherwise,
+    /// for the root, and for Mach-O and PE files.
+    pub search_trace: Vec<SearchStep>,
+    /// With [`ResolverConfig::record_search_paths`](crate::ResolverConfig::record_search_paths),
+    /// the directories searched for the object's own dependencies in search order, with the
+    /// `RPATH` and `RUNPATH` tokens like `$ORIGIN` expanded: the `RPATH` chain,
+    /// `LD_LIBRARY_PATH` and `RUNPATH` as its C library orders them, the custom search
+    /// directories, then the `ld.so.cache` and the system directories unless the object has
+    /// `DF_1_NODEFLIB`. Their `glibc-hwcaps` subdirectories are searched first. Empty
+    /// otherwise, for objects whose dependencies were not searched for, and for Mach-O and PE
+    /// files.
+    pub search_path: Vec<SearchPathEntry>,
+    /// If the library was not found, why each path tried for it was passed over: the
+    /// directories to search that don't exist, and the files by its name that exist but can't
+    /// be loaded, in search order. Empty otherwise, and for Mach-O and PE files.
+    pub rejections: Vec<Rejection>,
+}
+
+impl DependencyNode {
+    pub(crate) fn new<S: AsRef<OsStr> + ?Sized>(soname: &S, resolution: Resolution) -> Self {
+        Self {
+            soname: soname.as_ref().to_string_lossy().into_owned(),
+            kind: DependencyKind::Needed,
+            resolution,
+            links: Vec::new(),
+            found_by: None,
+            dt_soname: None,
+            truncated: false,
+            digest: None,
+            stat: None,
+            build_id: None,
+            header: None,
+            dynamic: None,
+            store_path: None,
+            os_abi_mismatch: false,
+            candidates: Vec::new(),
+            search_trace: Vec::new(),
+            search_path: Vec::new(),
+            rejections: Vec::new(),
+        }
+    }
+
+    /// Whether the library advertises a different `DT_SONAME` than the name it was requested
+    /// by, as for a library copied or renamed by hand. ld.so loads it all the same, but the
+    /// mismatch usually means the file is not the library that was meant. Libraries requested
+    /// by path, and the root, are never misnamed.
+    pub fn is_misnamed(&self) -> bool {
+        let requested_by_path = matches!(self.found_by, None | Some(SearchRule::Path));
+        match &self.dt_soname {
+            Some(dt_soname) => !requested_by_path && *dt_soname != self.soname,
+            None => false,
+        }
+    }
+
+    /// The path this node was resolved to, if it was found.
+    pub fn path(&self) -> Option<&Path> {
+        self.resolution.as_ref().ok()?.path()
+    }
+}
+
+/// The shared-library dependencies of an ELF file, with one node per distinct library and an
+/// edge for every `DT_NEEDED` relationship.
+///
+/// Libraries are deduplicated by resolved path, so a library needed by several objects has
+/// several incoming edges. A soname that could not be resolved gets a separate node for each
+/// object that needed it.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DependencyGraph {
+    nodes: Vec<DependencyNode>,
+    dependencies: Vec<Vec<NodeId>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_path::option"))]
+    interpreter: Option<PathBuf>,
+    interrupted: bool,
+    exceeded_limit: Option<ResourceLimit>,
+}
+
+impl DependencyGraph {
+    pub(crate) fn new(root: DependencyNode) -> Self {
+        Self {
+            nodes: vec![root],
+            dependencies: vec![Vec::new()],
+            interpreter: None,
+            interrupted: false,
+            exceeded_limit: None,
+        }
+    }
+
+    pub(crate) fn set_interpreter(&mut self, interpreter: Option<PathBuf>) {
+        self.interpreter = interpreter;
+    }
+
+    pub(crate) fn set_interrupted(&mut self) {
+        self.interrupted = true;
+    }
+
+    pub(crate) fn set_exceeded_limit(&mut self, limit: ResourceLimit) {
+        self.exceeded_limit = Some(limit);
+    }
+
+    pub(crate) fn add_node(&mut self, node: DependencyNode) -> NodeId {
+        self.nodes.push(node);
+        self.dependencies.push(Vec::new());
+        NodeId(self.nodes.len() - 1)
+    }
+
+    pub(crate) fn add_edge(&mut self, from: NodeId, to: NodeId) {
+        self.dependencies[from.0].push(to);
+    }
+
+    pub(crate) fn node_mut(&mut self, id: NodeId) -> &mut DependencyNode {
+        &mut self.nodes[id.0]
+    }
+
+    pub(crate) fn into_nodes(self) -> Vec<DependencyNode> {
+        self.nodes
+    }
+
+    /// Takes the graph apart into its nodes and, at the same positions, the libraries each
+    /// directly needs, in `DT_NEEDED` order, to hand it to a graph library without copying the
+    /// nodes. With the `petgraph` feature, a graph converts into a `petgraph::Graph` directly.
+    pub fn into_adjacency_list(self) -> (Vec<DependencyNode>, Vec<Vec<NodeId>>) {
+        (self.nodes, self.dependencies)
+    }
+
+    /// The analyzed file itself.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// The program interpreter the analyzed file asks for, see
+    /// [`ElfFile::interpreter`](crate::ElfFile::interpreter).
+    pub fn interpreter(&self) -> Option<&Path> {
+        self.interpreter.as_deref()
+    }
+
+    /// Whether collecting dependencies was stopped by the
+    /// [cancel flag](crate::ResolverConfig::cancel_flag) or the
+    /// [deadline](crate::ResolverConfig::deadline) before the closure was complete. The graph
+    /// then holds what was collected until then: libraries whose own dependencies were not
+    /// collected yet have none, and may be [truncated](DependencyNode::truncated).
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// The [`ResourceLimit`] that stopped collecting dependencies before the closure was
+    /// complete, [`ResourceLimit::Dependencies`] or [`ResourceLimit::BytesParsed`], if one did.
+    /// The graph then holds what was collected until then, as when it is
+    /// [interrupted](DependencyGraph::is_interrupted).
+    pub fn exceeded_limit(&self) -> Option<ResourceLimit> {
+        self.exceeded_limit
+    }
+
+    /// Returns the node with the given id.
+    pub fn node(&self, id: NodeId) -> &DependencyNode {
+        &self.nodes[id.0]
+    }
+
+    /// All nodes, starting with the root, in the order they were discovered.
+    pub fn nodes(&self) -> &[DependencyNode] {
+        &self.nodes
+    }
+
+    /// The ids of all nodes, in the same order as [`DependencyGraph::nodes`].
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.nodes.len()).map(NodeId)
+    }
+
+    /// The libraries `id` directly needs, in `DT_NEEDED` order.
+    pub fn dependencies(&self, id: NodeId) -> &[NodeId] {
+        &self.dependencies[id.0]
+    }
+
+    /// The objects that directly need `id`.
+    pub fn dependents(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.edges()
+            .filter(move |&(_, to)| to == id)
+            .map(|(from, _)| from)
+    }
+
+    /// The shortest chain of objects through which the root needs `id`: the root, the library it
+    /// needs, and so on, ending with `id`. Among chains of the same length, the one taking the
+    /// earliest `DT_NEEDED` entries wins. Answers why a library is in the closure at all, while
+    /// [`DependencyGraph::dependents`] lists every object that needs it. `None` if nothing
+    /// leads to `id`, which for a graph built by this crate only happens for invalid ids.
+    pub fn needed_by_chain(&self, id: NodeId) -> Option<Vec<NodeId>> {
+        // Breadth-first from the root, remembering through which object each node was reached
+        let mut reached_from: Vec<Option<NodeId>> = vec![None; self.nodes.len()];
+        let mut queue = VecDeque::from([self.root()]);
+        let mut visited = vec![false; self.nodes.len()];
+        visited[self.root().0] = true;
+        while let Some(node) = queue.pop_front() {
+            if node == id {
+                let mut chain = vec![id];
+                while let Some(parent) = reached_from[chain[chain.len() - 1].0] {
+                    chain.push(parent);
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+            for &dependency in &self.dependencies[node.0] {
+                if !visited[dependency.0] {
+                    visited[dependency.0] = true;
+                    reached_from[dependency.0] = Some(node);
+                    queue.push_back(dependency);
+                }
+            }
+        }
+        None
+    }
+
+    /// The root and every node it leads to, breadth-first, which is the order ld.so loads
+    /// libraries in and searches them for symbols.
+    pub fn load_order(&self) -> Vec<NodeId> {
+        let mut order = vec![self.root()];
+        let mut visited = vec![false; self.nodes.len()];
+        visited[self.root().0] = true;
+        let mut next = 0;
+        while let Some(&node) = order.get(next) {
+            next += 1;
+            for &dependency in &self.dependencies[node.0] {
+                if !visited[dependency.0] {
+                    visited[dependency.0] = true;
+                    order.push(dependency);
+                }
+            }
+        }
+        order
+    }
+
+    /// The node each soname the analyzed file loads is first loaded from, in load order,
+    /// leaving out the analyzed file itself.
+    pub(crate) fn library_nodes(&self) -> Vec<&DependencyNode> {
+        let mut libraries: Vec<&DependencyNode> = Vec::new();
+        for id in self.load_order().into_iter().skip(1) {
+            let node = self.node(id);
+            if !libraries
+                .iter()
+                .any(|library| library.soname == node.soname)
+            {
+                libraries.push(node);
+            }
+        }
+        libraries
+    }
+
+    /// Every `(needed by, needed)` edge in the graph.
+    pub fn edges(&self) -> impl Iterator<Item = (NodeId, NodeId)> + '_ {
+        self.dependencies
+            .iter()
+            .enumerate()
+            .flat_map(|(from, to)| to.iter().map(move |&to| (NodeId(from), to)))
+    }
+
+    /// The edges that close a cycle: those from a library back to one that (transitively)
+    /// needs it, found by a depth-first walk from the root in `DT_NEEDED` order. Without them
+    /// the graph is acyclic.
+    pub fn back_edges(&self) -> Vec<(NodeId, NodeId)> {
+        self.walk_cycles().0
+    }
+
+    /// The dependency cycles in the graph, one per [back edge](DependencyGraph::back_edges).
+    /// Each cycle lists its members starting with the library the back edge points to, each
+    /// needing the next, with the last needing the first again. A library that needs itself is
+    /// a cycle of one.
+    pub fn cycles(&self) -> Vec<Vec<NodeId>> {
+        self.walk_cycles().1
+    }
+
+    fn walk_cycles(&self) -> (Vec<(NodeId, NodeId)>, Vec<Vec<NodeId>>) {
+        let mut back_edges = Vec::new();
+        let mut cycles = Vec::new();
+        let mut visited = vec![false; self.nodes.len()];
+        // The path from the root to the current node, with the next dependency to look at
+        let mut stack = vec![(self.root(), 0)];
+        visited[self.root().0] = true;
+        while let Some((node, next)) = stack.last_mut() {
+            let Some(&dependency) = self.dependencies[node.0].get(*next) else {
+                stack.pop();
+                continue;
+            };
+            let node = *node;
+            *next += 1;
+            if let Some(start) = stack.iter().position(|&(id, _)| id == dependency) {
+                back_edges.push((node, dependency));
+                cycles.push(stack[start..].iter().map(|&(id, _)| id).collect());
+            } else if !visited[dependency.0] {
+                visited[dependency.0] = true;
+                stack.push((dependency, 0));
+            }
+        }
+        (back_edges, cycles)
+    }
+
+    /// The sonames that resolved to more than one file, as the nodes of those files in the order
+    /// they were added, which happens when the search paths of the objects needing a library
+    /// differ, e.g. in their `DT_RUNPATH`. ld.so loads a soname once, so all but one of the
+    /// objects end up with a library they were not shipped with, which usually points to a
+    /// deployment bug. Unless the graph was built with
+    /// [`ResolverConfig::canonicalize_paths`](crate::ResolverConfig::canonicalize_paths), two
+    /// paths to the same file count as different files.
+    pub fn soname_conflicts(&self) -> Vec<Vec<NodeId>> {
+        let mut conflicts: Vec<Vec<NodeId>> = Vec::new();
+        for id in self.node_ids().skip(1) {
+            let node = self.node(id);
+            if node.path().is_none() {
+                continue;
+            }
+            match conflicts
+                .iter_mut()
+                .find(|nodes| self.node(nodes[0]).soname == node.soname)
+            {
+                Some(nodes) => nodes.push(id),
+                None => conflicts.push(vec![id]),
+            }
+        }
+        conflicts.retain(|nodes| nodes.len() > 1);
+        conflicts
+    }
+
+    /// Looks up the node a path was resolved to.
+    pub fn find_path<P: AsRef<Path>>(&self, path: P) -> Option<NodeId> {
+        let path = path.as_ref();
+        self.nodes
+            .iter()
+            .position(|node| node.path() == Some(path))
+            .map(NodeId)
+    }
+}
+
+/// With the `petgraph` feature, the graph with a node per library, at the
+/// [index](NodeId::index) of its id, and an edge from every object to each library it needs,
+/// for the algorithms of `petgraph`, like strongly connected components or dominators.
+#[cfg(feature = "petgraph")]
+impl From<DependencyGraph> for petgraph::Graph<DependencyNode, ()> {
+    fn from(graph: DependencyGraph) -> Self {
+        use petgraph::graph::NodeIndex;
+
+        let (nodes, dependencies) = graph.into_adjacency_list();
+        let edge_count = dependencies.iter().map(Vec::len).sum();
+        let mut petgraph = Self::with_capacity(nodes.len(), edge_count);
+        for node in nodes {
+            petgraph.add_node(node);
+        }
+        for (from, to) in dependencies.iter().enumerate() {
+            for to in to {
+                petgraph.add_edge(NodeIndex::new(from), NodeIndex::new(to.index()), ());
+            }
+        }
+        petgraph
+    }
+}
+
+#[cfg(all(test, any(feature = "serde", feature = "petgraph")))]
+mod tests {
+    #[cfg(feature = "petgraph")]
+    use super::DependencyNode;
+    use crate::test_util::ElfBuilder;
+    use crate::ElfFile;
+    #[cfg(feature = "serde")]
+    use crate::{DependencyGraph, ResolveError, ResolverConfig};
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = ElfBuilder::new()
+            .needed("libc.so.6")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .write(dir.path(), "app");
+        let graph = ElfFile::new(&root).dependency_graph().unwrap();
+
+        let json = serde_json::to_string(&graph).unwrap();
+        let deserialized: DependencyGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), json);
+        let missing = deserialized
+            .nodes()
+            .iter()
+            .find(|node| node.soname == "libdyn-lib-finder-missing.so.1")
+            .unwrap();
+        assert!(matches!(
+            &missing.resolution,
+            Err(ResolveError::NotFound { needed_by }) if needed_by == &root
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_non_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().join(OsStr::from_bytes(b"dir\xff"));
+        let lib = ElfBuilder::new().write(&lib_dir, "libfoo.so");
+        let root = ElfBuilder::new()
+            .needed("libfoo.so")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(&lib_dir);
+        let graph = ElfFile::with_config(&root, config)
+            .dependency_graph()
+            .unwrap();
+        assert_eq!(graph.nodes()[1].path(), Some(lib.as_path()));
+
+        // The path is written as its bytes, and read back intact
+        let json = serde_json::to_string(&graph).unwrap();
+        let deserialized: DependencyGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.nodes()[1].path(), Some(lib.as_path()));
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), json);
+    }
+
+    #[cfg(feature = "petgraph")]
+    #[test]
+    fn test_petgraph() {
+        use petgraph::algo::{dominators, tarjan_scc};
+        use petgraph::graph::NodeIndex;
+
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().to_str().unwrap();
+        // liba and libb need each other, and both need libc
+        for (name, needed) in [("liba.so.1", "libb.so.1"), ("libb.so.1", "liba.so.1")] {
+            ElfBuilder::new()
+                .runpath(lib_dir)
+                .needed(needed)
+                .needed("libc.so.1")
+                .write(dir.path(), name);
+        }
+        ElfBuilder::new().write(dir.path(), "libc.so.1");
+        let root = ElfBuilder::new()
+            .runpath(lib_dir)
+            .needed("liba.so.1")
+            .write(dir.path(), "app");
+
+        let graph = ElfFile::new(&root).dependency_graph().unwrap();
+        let edges: Vec<_> = graph
+            .edges()
+            .map(|(from, to)| (from.index(), to.index()))
+            .collect();
+        let (a, b, c) = (1, 2, 3);
+        let petgraph = petgraph::Graph::<DependencyNode, ()>::from(graph);
+        assert_eq!(petgraph[NodeIndex::new(a)].soname, "liba.so.1");
+        assert_eq!(petgraph[NodeIndex::new(c)].soname, "libc.so.1");
+        let petgraph_edges = petgraph
+            .edge_indices()
+            .map(|edge| petgraph.edge_endpoints(edge).unwrap())
+            .map(|(from, to)| (from.index(), to.index()));
+        assert!(petgraph_edges.eq(edges));
+
+        let mut components: Vec<Vec<_>> = tarjan_scc(&petgraph)
+            .into_iter()
+            .map(|component| {
+                let mut component: Vec<_> = component.iter().map(|node| node.index()).collect();
+                component.sort();
+                component
+            })
+            .collect();
+        components.sort();
+        assert_eq!(components, [vec![0], vec![a, b], vec![c]]);
+        // Every path from the root to libc goes through liba
+        let dominators = dominators::simple_fast(&petgraph, NodeIndex::new(0));
+        let immediate = dominators.immediate_dominator(NodeIndex::new(c));
+        assert_eq!(immediate, Some(NodeIndex::new(a)));
+    }
+}
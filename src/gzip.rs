@@ -0,0 +1,150 @@
+//! Decoding of gzip files (RFC 1952) and zlib streams (RFC 1950), as used for the layers of
+//! container images and the blocks of squashfs images, with `flate2` when the `gzip` feature
+//! is enabled.
+use std::io;
+
+#[cfg(feature = "gzip")]
+use flate2::bufread::{GzDecoder, ZlibDecoder};
+
+#[cfg(feature = "gzip")]
+use crate::archive_fs::{decompress_into, malformed};
+
+const MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Whether `data` starts like a gzip file.
+pub(crate) fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Decompresses the gzip file `data`, including every member of one made by concatenating
+/// several, to up to `limit` bytes. Returns an [`io::ErrorKind::InvalidData`] error if it is
+/// malformed, truncated or fails its checksums, or an [`io::ErrorKind::FileTooLarge`] error if
+/// it decompresses to more.
+#[cfg(feature = "gzip")]
+pub(crate) fn decompress(data: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut rest = data;
+    // Trailing padding after the last member is ignored, as gzip does
+    while is_gzip(rest) {
+        let mut decoder = GzDecoder::new(rest);
+        decompress_into(&mut decoder, "gzip", &mut output, limit)?;
+        rest = decoder.into_inner();
+    }
+    if rest.len() == data.len() {
+        return Err(malformed("gzip"));
+    }
+    Ok(output)
+}
+
+/// Decompresses the zlib stream `data` to up to `limit` bytes. Returns an
+/// [`io::ErrorKind::InvalidData`] error if it is malformed, truncated, needs a preset
+/// dictionary or fails its checksum, or an [`io::ErrorKind::FileTooLarge`] error if it
+/// decompresses to more.
+#[cfg(feature = "gzip")]
+pub(crate) fn decompress_zlib(data: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    decompress_into(ZlibDecoder::new(data), "zlib", &mut output, limit)?;
+    Ok(output)
+}
+
+/// Without the `gzip` feature, an [`io::ErrorKind::Unsupported`] error.
+#[cfg(not(feature = "gzip"))]
+pub(crate) fn decompress(_data: &[u8], _limit: u64) -> io::Result<Vec<u8>> {
+    Err(unsupported())
+}
+
+/// Without the `gzip` feature, an [`io::ErrorKind::Unsupported`] error.
+#[cfg(not(feature = "gzip"))]
+pub(crate) fn decompress_zlib(_data: &[u8], _limit: u64) -> io::Result<Vec<u8>> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "gzip"))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "decompressing gzip streams needs the `gzip` feature",
+    )
+}
+
+#[cfg(all(test, feature = "gzip"))]
+mod tests {
+    use std::io;
+
+    use super::{decompress, decompress_zlib};
+    use crate::test_util::gzip_zeros;
+    /// `expected()`, as compressed by `gzip -9`, with dynamic Huffman codes.
+    const GZIP: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x6d, 0xd0, 0x3b, 0x0a, 0x80,
+        0x30, 0x10, 0x45, 0xd1, 0xde, 0x55, 0x64, 0x05, 0x21, 0x2f, 0x99, 0xfc, 0x5c, 0x8e, 0xa0,
+        0x10, 0x08, 0xa6, 0x70, 0xff, 0x20, 0x32, 0xcd, 0x08, 0xaf, 0x3d, 0xd5, 0xe5, 0xce, 0x71,
+        0x9f, 0x2e, 0xec, 0x6e, 0x8e, 0xe3, 0x5a, 0xcb, 0x3f, 0xcb, 0x87, 0x6d, 0x7e, 0x06, 0x6b,
+        0x50, 0x8b, 0xd6, 0xa2, 0x5a, 0xb2, 0x96, 0xd4, 0xc4, 0x9a, 0xa8, 0x65, 0x6b, 0x59, 0xad,
+        0x58, 0x2b, 0x6a, 0x95, 0xb4, 0x34, 0xd2, 0xd2, 0x49, 0x0b, 0x02, 0x89, 0x01, 0x48, 0x0d,
+        0x22, 0xc9, 0x41, 0x22, 0x3d, 0x10, 0x36, 0x27, 0x93, 0x22, 0x14, 0x96, 0x54, 0x59, 0x52,
+        0x63, 0x49, 0xfd, 0x9f, 0xf4, 0x02, 0xd2, 0xcb, 0x15, 0xfe, 0x9a, 0x01, 0x00, 0x00,
+    ];
+
+    fn expected() -> Vec<u8> {
+        let lines = (0..20).map(|i| format!("line {i}: libfoo.so.{}\n", i % 7));
+        lines.collect::<String>().into_bytes()
+    }
+
+    #[test]
+    fn test_decompress() {
+        assert_eq!(decompress(GZIP, u64::MAX).unwrap(), expected());
+        let stored = [
+            0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3, 1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c', 0xc2, 0x41,
+            0x24, 0x35, 3, 0, 0, 0,
+        ];
+        assert_eq!(decompress(&stored, u64::MAX).unwrap(), b"abc");
+        let fixed = [
+            0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 2, 3, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40,
+            0x27, 1, 0xe3, 0x51, 0x3d, 0x8d, 0x17, 0, 0, 0,
+        ];
+        assert_eq!(
+            decompress(&fixed, u64::MAX).unwrap(),
+            b"hello hello hello hello"
+        );
+        // Members can be concatenated, and the checksum must match
+        let twice = [&stored[..], &stored[..]].concat();
+        assert_eq!(decompress(&twice, u64::MAX).unwrap(), b"abcabc");
+        let mut corrupt = stored;
+        corrupt[15] = b'x';
+        assert!(decompress(&corrupt, u64::MAX).is_err());
+        assert!(decompress(&GZIP[..GZIP.len() - 20], u64::MAX).is_err());
+        assert!(decompress(b"not gzip", u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_decompress_zlib() {
+        // The same DEFLATE data, as compressed by zlib
+        let checksum = [0x63, 0x89, 0x7f, 0xe2];
+        let zlib = [&[0x78, 0xda][..], &GZIP[10..GZIP.len() - 8], &checksum].concat();
+        assert_eq!(decompress_zlib(&zlib, u64::MAX).unwrap(), expected());
+        let stored = [
+            0x78, 1, 1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c', 0x02, 0x4d, 0x01, 0x27,
+        ];
+        assert_eq!(decompress_zlib(&stored, u64::MAX).unwrap(), b"abc");
+        let mut corrupt = stored;
+        corrupt[9] = b'x';
+        assert!(decompress_zlib(&corrupt, u64::MAX).is_err());
+        assert!(decompress_zlib(&stored[..12], u64::MAX).is_err());
+        assert!(decompress_zlib(&[0x78, 0x02], u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_decompress_limit() {
+        // A few kilobytes of gzip can decompress to a megabyte
+        let zeros = gzip_zeros(1 << 20);
+        assert!(zeros.len() < 8 << 10);
+        assert_eq!(decompress(&zeros, 1 << 20).unwrap(), vec![0; 1 << 20]);
+        let error = decompress(&zeros, 1000).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::FileTooLarge);
+        let stored = [
+            0x78, 1, 1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c', 0x02, 0x4d, 0x01, 0x27,
+        ];
+        let error = decompress_zlib(&stored, 2).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::FileTooLarge);
+    }
+}
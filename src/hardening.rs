@@ -0,0 +1,334 @@
+//! Audits of the exploit mitigations each object of a closure was built with, like `checksec`
+//! reports for a single file.
+use std::fmt::Write;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::abi::{
+    DF_1_NOW, DF_1_PIE, DF_BIND_NOW, DT_BIND_NOW, DT_FLAGS, DT_FLAGS_1, DT_SONAME, PF_X,
+    PT_GNU_RELRO, PT_GNU_STACK, PT_INTERP, SHT_DYNSYM, SHT_SYMTAB,
+};
+use crate::object::{Object, Stream, SymbolTable};
+use crate::parse::{c_str, parse_error};
+use crate::{ElfType, Error};
+
+/// Symbols only objects compiled with stack protectors refer to.
+const CANARY_SYMBOLS: &[&[u8]] = &[
+    b"__stack_chk_fail",
+    b"__stack_chk_fail_local",
+    b"__stack_chk_guard",
+    b"__intel_security_cookie",
+];
+
+/// How much of an object is made read-only after relocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Relro {
+    /// No `PT_GNU_RELRO` segment: the GOT stays writable.
+    None,
+    /// A `PT_GNU_RELRO` segment, but lazy binding leaves the PLT part of the GOT writable.
+    Partial,
+    /// A `PT_GNU_RELRO` segment with immediate binding, so the whole GOT is read-only.
+    Full,
+}
+
+/// Whether an object can be loaded at a random address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Pie {
+    /// An `ET_EXEC` executable, which is always loaded at the same address.
+    Disabled,
+    /// A position-independent executable.
+    Enabled,
+    /// A shared library, which is position-independent as every library is.
+    SharedObject,
+}
+
+impl Pie {
+    /// How a loadable object of type `elf_type` is position-independent, given whether its
+    /// `DT_FLAGS_1` has `DF_1_PIE`, whether it has a `PT_INTERP` and whether it has a
+    /// `DT_SONAME`.
+    pub(crate) fn of(
+        elf_type: ElfType,
+        pie_flag: bool,
+        has_interpreter: bool,
+        has_soname: bool,
+    ) -> Self {
+        // Older linkers don't set DF_1_PIE, so a PT_INTERP marks a PIE too, unless the file has
+        // a DT_SONAME, as libraries that can be run, like libc.so.6, have
+        if elf_type == ElfType::Executable {
+            Pie::Disabled
+        } else if pie_flag || (has_interpreter && !has_soname) {
+            Pie::Enabled
+        } else {
+            Pie::SharedObject
+        }
+    }
+}
+
+/// The exploit mitigations of one object, as `checksec` reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hardening {
+    /// How much of the GOT is read-only.
+    pub relro: Relro,
+    /// Whether the object is position-independent, and how.
+    pub pie: Pie,
+    /// Whether the stack is not executable: a `PT_GNU_STACK` segment without `PF_X`. Without
+    /// one, the stack of a process loading the object is executable.
+    pub nx: bool,
+    /// Whether the object refers to `__stack_chk_fail` or the other symbols of stack
+    /// protectors. Objects compiled with them but with no function that needs one look the
+    /// same as those compiled without.
+    pub stack_canary: bool,
+    /// Whether all symbols are bound at load time, with `DT_BIND_NOW` or the `BIND_NOW` and
+    /// `NOW` flags, as `-z now` links.
+    pub bind_now: bool,
+}
+
+impl Hardening {
+    /// Whether every mitigation is on. The `pie` of a shared library counts as on.
+    pub fn is_hardened(&self) -> bool {
+        self.relro == Relro::Full
+            && self.pie != Pie::Disabled
+            && self.nx
+            && self.stack_canary
+            && self.bind_now
+    }
+}
+
+/// The mitigations of a file and of each library in its closure, as reported by
+/// [`ElfFile::hardening_report`](crate::ElfFile::hardening_report).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HardeningReport {
+    /// The analyzed file, then the resolved libraries in load order.
+    pub objects: Vec<(PathBuf, Hardening)>,
+}
+
+impl HardeningReport {
+    /// The weakest of each mitigation across the closure, which is what a process loading it
+    /// ends up with: e.g. a single library without `PT_GNU_STACK` makes the stack executable.
+    /// The `pie` is that of the analyzed file. `None` if the report is empty.
+    pub fn weakest(&self) -> Option<Hardening> {
+        let ((_, first), rest) = self.objects.split_first()?;
+        let mut weakest = *first;
+        for (_, hardening) in rest {
+            weakest.relro = weakest.relro.min(hardening.relro);
+            weakest.nx &= hardening.nx;
+            weakest.stack_canary &= hardening.stack_canary;
+            weakest.bind_now &= hardening.bind_now;
+        }
+        Some(weakest)
+    }
+
+    /// The objects that lack some mitigation, see [`Hardening::is_hardened`].
+    pub fn unhardened(&self) -> impl Iterator<Item = &(PathBuf, Hardening)> {
+        self.objects
+            .iter()
+            .filter(|(_, hardening)| !hardening.is_hardened())
+    }
+
+    /// Renders the report as a table like `checksec --file` prints, with one line per object
+    /// under a header line.
+    pub fn to_table(&self) -> String {
+        let mut table = format!(
+            "{:<13} {:<13} {:<12} {:<15} {:<9} FILE\n",
+            "RELRO", "PIE", "NX", "STACK CANARY", "BIND_NOW"
+        );
+        for (path, hardening) in &self.objects {
+            let relro = match hardening.relro {
+                Relro::None => "No RELRO",
+                Relro::Partial => "Partial RELRO",
+                Relro::Full => "Full RELRO",
+            };
+            let pie = match hardening.pie {
+                Pie::Disabled => "No PIE",
+                Pie::Enabled => "PIE enabled",
+                Pie::SharedObject => "DSO",
+            };
+            let nx = if hardening.nx {
+                "NX enabled"
+            } else {
+                "NX disabled"
+            };
+            let canary = if hardening.stack_canary {
+                "Canary found"
+            } else {
+                "No canary found"
+            };
+            let bind_now = if hardening.bind_now { "Yes" } else { "No" };
+            let _ = writeln!(
+                table,
+                "{relro:<13} {pie:<13} {nx:<12} {canary:<15} {bind_now:<9} {}",
+                path.display()
+            );
+        }
+        table
+    }
+}
+
+/// Reads the mitigations of the ELF file `reader` reads from its program headers, dynamic
+/// array and symbol tables; `path` is only used for error reporting.
+pub(crate) fn read_hardening_from<R: Read + Seek>(
+    path: &Path,
+    reader: R,
+) -> Result<Hardening, Error> {
+    let parse_error = |source| parse_error(path, source);
+    let mut elf = Object::open(Stream(reader)).map_err(parse_error)?;
+    let segment = |p_type| elf.segments.iter().find(|phdr| phdr.p_type == p_type);
+    let has_relro = segment(PT_GNU_RELRO).is_some();
+    let has_interpreter = segment(PT_INTERP).is_some();
+    let nx = segment(PT_GNU_STACK).is_some_and(|phdr| phdr.p_flags & PF_X == 0);
+
+    let flag = |value: u64, flag: i64| value & flag as u64 != 0;
+    let (mut bind_now, mut pie_flag, mut has_soname) = (false, false, false);
+    if let Some(dynamic) = elf.dynamic().map_err(parse_error)? {
+        for entry in dynamic {
+            match entry {
+                (DT_BIND_NOW, _) => bind_now = true,
+                (DT_SONAME, _) => has_soname = true,
+                (DT_FLAGS, value) => bind_now |= flag(value, DF_BIND_NOW),
+                (DT_FLAGS_1, value) => {
+                    bind_now |= flag(value, DF_1_NOW);
+                    pie_flag = flag(value, DF_1_PIE);
+                }
+                _ => {}
+            }
+        }
+    }
+    let has_canary = |(symbols, strings): SymbolTable| {
+        symbols.iter().any(|symbol| {
+            let name = usize::try_from(symbol.st_name)
+                .ok()
+                .and_then(|offset| strings.get(offset..));
+            CANARY_SYMBOLS.contains(&name.and_then(c_str).unwrap_or_default())
+        })
+    };
+    // Static executables only have the full symbol table, if they weren't stripped
+    let mut stack_canary = match elf.symbols(SHT_DYNSYM).map_err(parse_error)? {
+        Some(table) => has_canary(table),
+        None => false,
+    };
+    if !stack_canary {
+        if let Some(table) = elf.symbols(SHT_SYMTAB).map_err(parse_error)? {
+            stack_canary = has_canary(table);
+        }
+    }
+
+    let elf_type = ElfType::from_e_type(elf.header.e_type);
+    let pie = Pie::of(elf_type, pie_flag, has_interpreter, has_soname);
+    let relro = match (has_relro, bind_now) {
+        (false, _) => Relro::None,
+        (true, false) => Relro::Partial,
+        (true, true) => Relro::Full,
+    };
+    Ok(Hardening {
+        relro,
+        pie,
+        nx,
+        stack_canary,
+        bind_now,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_hardening_from, Hardening, HardeningReport, Pie, Relro};
+    use crate::abi::{
+        DF_1_NOW, DF_1_PIE, DF_BIND_NOW, DT_FLAGS, PF_R, PF_W, PF_X, PT_GNU_RELRO, PT_GNU_STACK,
+    };
+    use crate::test_util::ElfBuilder;
+    use std::path::PathBuf;
+
+    const HARDENED: Hardening = Hardening {
+        relro: Relro::Full,
+        pie: Pie::SharedObject,
+        nx: true,
+        stack_canary: true,
+        bind_now: true,
+    };
+
+    #[test]
+    fn test_read_hardening() {
+        let dir = tempfile::tempdir().unwrap();
+        let read = |builder: ElfBuilder, name: &str| {
+            let path = builder.write(dir.path(), name);
+            read_hardening_from(&path, std::fs::File::open(&path).unwrap()).unwrap()
+        };
+        let hardened = ElfBuilder::new()
+            .segment(PT_GNU_RELRO, PF_R)
+            .segment(PT_GNU_STACK, PF_R | PF_W)
+            .value(DT_FLAGS, DF_BIND_NOW as u64)
+            .imports("__stack_chk_fail");
+        assert_eq!(read(hardened, "libhardened.so"), HARDENED);
+        let bare = Hardening {
+            relro: Relro::None,
+            pie: Pie::SharedObject,
+            nx: false,
+            stack_canary: false,
+            bind_now: false,
+        };
+        assert_eq!(read(ElfBuilder::new(), "libbare.so"), bare);
+        let position_dependent = ElfBuilder::new()
+            .position_dependent()
+            .interpreter("/lib64/ld-linux-x86-64.so.2")
+            .segment(PT_GNU_RELRO, PF_R)
+            .segment(PT_GNU_STACK, PF_R | PF_W | PF_X);
+        let expected = Hardening {
+            relro: Relro::Partial,
+            pie: Pie::Disabled,
+            ..bare
+        };
+        assert_eq!(read(position_dependent, "exec"), expected);
+        let pie = ElfBuilder::new().flags_1(DF_1_PIE | DF_1_NOW);
+        let expected = Hardening {
+            pie: Pie::Enabled,
+            bind_now: true,
+            ..bare
+        };
+        assert_eq!(read(pie, "pie"), expected);
+        let runnable = ElfBuilder::new()
+            .soname("libc.so.6")
+            .interpreter("/lib64/ld-linux-x86-64.so.2");
+        assert_eq!(read(runnable, "libc.so.6").pie, Pie::SharedObject);
+    }
+
+    #[test]
+    fn test_weakest() {
+        let executable = |hardening| Hardening {
+            pie: Pie::Enabled,
+            ..hardening
+        };
+        let exec_stack = Hardening {
+            nx: false,
+            relro: Relro::Partial,
+            ..HARDENED
+        };
+        let report = HardeningReport {
+            objects: vec![
+                (PathBuf::from("/bin/app"), executable(HARDENED)),
+                (PathBuf::from("/lib/libold.so.1"), exec_stack),
+                (PathBuf::from("/lib/libc.so.6"), HARDENED),
+            ],
+        };
+        let weakest = report.weakest().unwrap();
+        // The stack of the process is executable, but the executable itself is still a PIE
+        assert_eq!(weakest, executable(exec_stack));
+        let unhardened: Vec<_> = report.unhardened().map(|(path, _)| path).collect();
+        assert_eq!(unhardened, [&PathBuf::from("/lib/libold.so.1")]);
+        let table = report.to_table();
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("RELRO"));
+        assert!(lines[2].starts_with("Partial RELRO DSO           NX disabled"));
+        assert!(lines[2].ends_with(" /lib/libold.so.1"));
+        assert_eq!(
+            HardeningReport {
+                objects: Vec::new()
+            }
+            .weakest(),
+            None
+        );
+    }
+}
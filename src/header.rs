@@ -0,0 +1,344 @@
+//! Typed summaries of ELF headers, for tools that display what a file and its libraries are
+//! built for without parsing them again.
+use std::fmt;
+
+use crate::abi::{
+    ELFOSABI_FREEBSD, ELFOSABI_GNU, ELFOSABI_NETBSD, ELFOSABI_OPENBSD, ELFOSABI_SOLARIS,
+    ELFOSABI_SYSV, EM_386, EM_AARCH64, EM_ARM, EM_BPF, EM_LOONGARCH, EM_MIPS, EM_PPC, EM_PPC64,
+    EM_RISCV, EM_S390, EM_SPARC, EM_SPARCV9, EM_X86_64,
+};
+use crate::parse::{Arch, ParsedObject};
+use crate::{DynamicFlags1, ElfType, Pie};
+
+/// The `e_machine`s [`Machine`] names, with the names it displays.
+const MACHINES: [(Machine, u16, &str); 13] = [
+    (Machine::X86, EM_386, "Intel 80386"),
+    (Machine::X86_64, EM_X86_64, "x86-64"),
+    (Machine::Arm, EM_ARM, "ARM"),
+    (Machine::AArch64, EM_AARCH64, "ARM aarch64"),
+    (Machine::RiscV, EM_RISCV, "RISC-V"),
+    (Machine::PowerPc, EM_PPC, "PowerPC"),
+    (Machine::PowerPc64, EM_PPC64, "64-bit PowerPC"),
+    (Machine::S390, EM_S390, "IBM S/390"),
+    (Machine::Mips, EM_MIPS, "MIPS"),
+    (Machine::Sparc, EM_SPARC, "SPARC"),
+    (Machine::SparcV9, EM_SPARCV9, "SPARC V9"),
+    (Machine::LoongArch, EM_LOONGARCH, "LoongArch"),
+    (Machine::Bpf, EM_BPF, "eBPF"),
+];
+
+/// The `EI_OSABI`s [`OsAbiKind`] names, with the names it displays.
+const OS_ABIS: [(OsAbiKind, u8, &str); 6] = [
+    (OsAbiKind::SystemV, ELFOSABI_SYSV, "SYSV"),
+    (OsAbiKind::Gnu, ELFOSABI_GNU, "GNU/Linux"),
+    (OsAbiKind::FreeBsd, ELFOSABI_FREEBSD, "FreeBSD"),
+    (OsAbiKind::NetBsd, ELFOSABI_NETBSD, "NetBSD"),
+    (OsAbiKind::OpenBsd, ELFOSABI_OPENBSD, "OpenBSD"),
+    (OsAbiKind::Solaris, ELFOSABI_SOLARIS, "Solaris"),
+];
+
+/// What the ELF header of a file says it is and what it is built for, as reported by
+/// [`ElfFile::header`](crate::ElfFile::header) and for each library in
+/// [`DependencyNode::header`](crate::DependencyNode::header). Displayed like `file` describes
+/// files, e.g. `ELF 64-bit LSB pie executable, x86-64, SYSV`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ElfHeader {
+    /// Whether the file is 32- or 64-bit.
+    pub class: ElfClass,
+    /// The byte order of the file.
+    pub endianness: Endianness,
+    /// The architecture the file is built for, from its `e_machine`.
+    pub machine: Machine,
+    /// The type of the file, from its `e_type`.
+    pub elf_type: ElfType,
+    /// The operating system ABI the file is built for, from its `EI_OSABI`.
+    pub os_abi: OsAbiKind,
+    /// The `EI_ABIVERSION` byte, whose meaning depends on the `EI_OSABI`.
+    pub abi_version: u8,
+    /// Whether the file is position-independent, and how, as in
+    /// [`Hardening::pie`](crate::Hardening::pie). `None` for files the dynamic linker doesn't
+    /// load, like relocatable objects.
+    pub pie: Option<Pie>,
+}
+
+impl ElfHeader {
+    pub(crate) fn of(object: &ParsedObject) -> Self {
+        let arch = object.arch;
+        let pie = object.elf_type.is_loadable().then(|| {
+            Pie::of(
+                object.elf_type,
+                object.flags_1.contains(DynamicFlags1::PIE),
+                object.interpreter.is_some(),
+                object.soname.is_some(),
+            )
+        });
+        Self {
+            class: ElfClass::of(arch),
+            endianness: Endianness::of(arch),
+            machine: Machine::from_e_machine(arch.machine),
+            elf_type: object.elf_type,
+            os_abi: OsAbiKind::from_os_abi(object.os_abi.os_abi),
+            abi_version: object.os_abi.version,
+            pie,
+        }
+    }
+}
+
+impl fmt::Display for ElfHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = match self.class {
+            ElfClass::Elf32 => 32,
+            ElfClass::Elf64 => 64,
+        };
+        let order = match self.endianness {
+            Endianness::Little => "LSB",
+            Endianness::Big => "MSB",
+        };
+        let kind = match (self.elf_type, self.pie) {
+            (ElfType::SharedObject, Some(Pie::Enabled)) => "pie executable".to_owned(),
+            (ElfType::Relocatable, _) => "relocatable".to_owned(),
+            (ElfType::Executable, _) => "executable".to_owned(),
+            (ElfType::SharedObject, _) => "shared object".to_owned(),
+            (ElfType::Core, _) => "core file".to_owned(),
+            (ElfType::Other(e_type), _) => format!("type {e_type:#x}"),
+        };
+        write!(
+            f,
+            "ELF {bits}-bit {order} {kind}, {}, {}",
+            self.machine, self.os_abi
+        )
+    }
+}
+
+/// Whether an ELF file is 32- or 64-bit, from its `EI_CLASS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ElfClass {
+    /// `ELFCLASS32`.
+    Elf32,
+    /// `ELFCLASS64`.
+    Elf64,
+}
+
+impl ElfClass {
+    pub(crate) fn of(arch: Arch) -> Self {
+        if arch.is_64_bit {
+            ElfClass::Elf64
+        } else {
+            ElfClass::Elf32
+        }
+    }
+}
+
+/// The byte order of an ELF file, from its `EI_DATA`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Endianness {
+    /// `ELFDATA2LSB`.
+    Little,
+    /// `ELFDATA2MSB`.
+    Big,
+}
+
+impl Endianness {
+    pub(crate) fn of(arch: Arch) -> Self {
+        if arch.big_endian {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        }
+    }
+}
+
+/// The architecture an ELF file is built for, by its `e_machine`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Machine {
+    /// `EM_386`: 32-bit x86.
+    X86,
+    /// `EM_X86_64`, which 64-bit and x32 files share.
+    X86_64,
+    /// `EM_ARM`: 32-bit ARM.
+    Arm,
+    /// `EM_AARCH64`: 64-bit ARM.
+    AArch64,
+    /// `EM_RISCV`, which 32- and 64-bit files share.
+    RiscV,
+    /// `EM_PPC`: 32-bit PowerPC.
+    PowerPc,
+    /// `EM_PPC64`: 64-bit PowerPC.
+    PowerPc64,
+    /// `EM_S390`, which 31-bit S/390 and 64-bit z/Architecture files share.
+    S390,
+    /// `EM_MIPS`.
+    Mips,
+    /// `EM_SPARC`: 32-bit SPARC.
+    Sparc,
+    /// `EM_SPARCV9`: 64-bit SPARC.
+    SparcV9,
+    /// `EM_LOONGARCH`.
+    LoongArch,
+    /// `EM_BPF`.
+    Bpf,
+    /// Any other `e_machine`.
+    Other(u16),
+}
+
+impl Machine {
+    /// The architecture with the `e_machine` value `e_machine`.
+    pub fn from_e_machine(e_machine: u16) -> Self {
+        let known = MACHINES.iter().find(|&&(_, value, _)| value == e_machine);
+        known.map_or(Machine::Other(e_machine), |&(machine, ..)| machine)
+    }
+
+    /// The `e_machine` value of this architecture.
+    pub fn e_machine(self) -> u16 {
+        match self {
+            Machine::Other(e_machine) => e_machine,
+            machine => {
+                MACHINES
+                    .iter()
+                    .find(|(known, ..)| *known == machine)
+                    .unwrap()
+                    .1
+            }
+        }
+    }
+}
+
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match MACHINES.iter().find(|(known, ..)| known == self) {
+            Some((_, _, name)) => f.write_str(name),
+            None => write!(f, "machine {:#x}", self.e_machine()),
+        }
+    }
+}
+
+/// The operating system ABI an ELF file is built for, by its `EI_OSABI`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OsAbiKind {
+    /// `ELFOSABI_SYSV` (`ELFOSABI_NONE`): no particular operating system, which is what most
+    /// Linux files say.
+    SystemV,
+    /// `ELFOSABI_GNU` (`ELFOSABI_LINUX`): Linux with GNU extensions, like `STT_GNU_IFUNC`
+    /// symbols.
+    Gnu,
+    /// `ELFOSABI_FREEBSD`.
+    FreeBsd,
+    /// `ELFOSABI_NETBSD`.
+    NetBsd,
+    /// `ELFOSABI_OPENBSD`.
+    OpenBsd,
+    /// `ELFOSABI_SOLARIS`.
+    Solaris,
+    /// Any other `EI_OSABI`.
+    Other(u8),
+}
+
+impl OsAbiKind {
+    /// The ABI with the `EI_OSABI` value `os_abi`.
+    pub fn from_os_abi(os_abi: u8) -> Self {
+        let known = OS_ABIS.iter().find(|&&(_, value, _)| value == os_abi);
+        known.map_or(OsAbiKind::Other(os_abi), |&(kind, ..)| kind)
+    }
+
+    /// The `EI_OSABI` value of this ABI.
+    pub fn os_abi(self) -> u8 {
+        match self {
+            OsAbiKind::Other(os_abi) => os_abi,
+            kind => OS_ABIS.iter().find(|(known, ..)| *known == kind).unwrap().1,
+        }
+    }
+}
+
+impl fmt::Display for OsAbiKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match OS_ABIS.iter().find(|(known, ..)| known == self) {
+            Some((_, _, name)) => f.write_str(name),
+            None => write!(f, "OS ABI {}", self.os_abi()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ElfClass, ElfHeader, Endianness, Machine, OsAbiKind};
+    use crate::abi::{DF_1_PIE, EM_AARCH64, ET_EXEC, ET_REL};
+    use crate::test_util::ElfBuilder;
+    use crate::{ElfFile, ElfType, Pie};
+
+    #[test]
+    fn test_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = ElfBuilder::new()
+            .soname("libfoo.so.1")
+            .write(dir.path(), "libfoo.so.1");
+        let pie = ElfBuilder::new()
+            .flags_1(DF_1_PIE)
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libfoo.so.1")
+            .write(dir.path(), "pie");
+        let header = ElfFile::new(&pie).header().unwrap();
+        assert_eq!(
+            header,
+            ElfHeader {
+                class: ElfClass::Elf64,
+                endianness: Endianness::Little,
+                machine: Machine::X86_64,
+                elf_type: ElfType::SharedObject,
+                os_abi: OsAbiKind::SystemV,
+                abi_version: 0,
+                pie: Some(Pie::Enabled),
+            }
+        );
+        assert_eq!(
+            header.to_string(),
+            "ELF 64-bit LSB pie executable, x86-64, SYSV"
+        );
+        // The same for the root in the graph, and each library
+        let graph = ElfFile::new(&pie).dependency_graph().unwrap();
+        assert_eq!(graph.node(graph.root()).header, Some(header));
+        let lib = graph.node(graph.find_path(&lib).unwrap()).header.unwrap();
+        assert_eq!(lib.pie, Some(Pie::SharedObject));
+        assert_eq!(
+            lib.to_string(),
+            "ELF 64-bit LSB shared object, x86-64, SYSV"
+        );
+
+        let exec = ElfBuilder::new()
+            .elf32()
+            .e_type(ET_EXEC)
+            .os_abi(9, 0)
+            .write(dir.path(), "exec");
+        let header = ElfFile::new(&exec).header().unwrap();
+        assert_eq!(
+            (header.class, header.pie),
+            (ElfClass::Elf32, Some(Pie::Disabled))
+        );
+        assert_eq!(
+            header.to_string(),
+            "ELF 32-bit LSB executable, x86-64, FreeBSD"
+        );
+        // Unlike the dependencies, the header of an object the dynamic linker doesn't load
+        let object = ElfBuilder::new().e_type(ET_REL).write(dir.path(), "foo.o");
+        let header = ElfFile::new(&object).header().unwrap();
+        assert_eq!(header.pie, None);
+        assert_eq!(
+            header.to_string(),
+            "ELF 64-bit LSB relocatable, x86-64, SYSV"
+        );
+    }
+
+    #[test]
+    fn test_machine() {
+        assert_eq!(Machine::from_e_machine(EM_AARCH64), Machine::AArch64);
+        assert_eq!(Machine::AArch64.e_machine(), EM_AARCH64);
+        assert_eq!(Machine::from_e_machine(0x1234), Machine::Other(0x1234));
+        assert_eq!(Machine::Other(0x1234).to_string(), "machine 0x1234");
+        assert_eq!(OsAbiKind::from_os_abi(3), OsAbiKind::Gnu);
+        assert_eq!(OsAbiKind::Other(200).os_abi(), 200);
+    }
+}
@@ -0,0 +1,108 @@
+//! The `glibc-hwcaps` subdirectories ld.so probes for libraries optimized for the running CPU,
+//! and the legacy hardware capability subdirectories older versions probe.
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// The name of the directory holding the subdirectories within each search directory.
+const HWCAPS_DIR: &str = "glibc-hwcaps";
+
+/// The `glibc-hwcaps` subdirectories the host CPU supports, best first. Only x86-64 levels are
+/// detected.
+#[cfg(target_arch = "x86_64")]
+pub(crate) fn host_subdirs() -> Vec<String> {
+    use std::arch::is_x86_feature_detected as has;
+
+    let v2 = has!("cmpxchg16b")
+        && has!("popcnt")
+        && has!("sse3")
+        && has!("ssse3")
+        && has!("sse4.1")
+        && has!("sse4.2");
+    let v3 = v2
+        && has!("avx")
+        && has!("avx2")
+        && has!("bmi1")
+        && has!("bmi2")
+        && has!("f16c")
+        && has!("fma")
+        && has!("lzcnt")
+        && has!("movbe")
+        && has!("xsave");
+    let v4 = v3
+        && has!("avx512f")
+        && has!("avx512bw")
+        && has!("avx512cd")
+        && has!("avx512dq")
+        && has!("avx512vl");
+    [(v4, "x86-64-v4"), (v3, "x86-64-v3"), (v2, "x86-64-v2")]
+        .into_iter()
+        .filter(|&(supported, _)| supported)
+        .map(|(_, name)| name.to_owned())
+        .collect()
+}
+
+/// The `glibc-hwcaps` subdirectories the host CPU supports, best first. Only x86-64 levels are
+/// detected.
+#[cfg(not(target_arch = "x86_64"))]
+pub(crate) fn host_subdirs() -> Vec<String> {
+    Vec::new()
+}
+
+/// The legacy subdirectories glibc before 2.37 probes for the capability names `caps`, most
+/// specific first, as relative paths: every combination of them in order, from all of them
+/// down to one, like `tls/haswell/x86_64`, `tls/haswell`, `tls/x86_64`, `tls`, `haswell/x86_64`
+/// and so on for `["tls", "haswell", "x86_64"]`.
+pub(crate) fn legacy_subdirs(caps: &[String]) -> Vec<PathBuf> {
+    // The first name is the most significant bit of the mask of the names in a subdirectory
+    let count = u32::try_from(caps.len())
+        .unwrap_or(u32::MAX)
+        .min(usize::BITS - 1);
+    (1..1usize << count)
+        .rev()
+        .map(|mask| {
+            let names = caps.iter().take(count as usize).enumerate();
+            let names = names.filter(|&(i, _)| mask & (1 << (count as usize - 1 - i)) != 0);
+            names.map(|(_, name)| name).collect()
+        })
+        .collect()
+}
+
+/// The paths ld.so tries for `soname` in `dir`: in each of the `subdirs` of its `glibc-hwcaps`
+/// directory, then in each of the `legacy` subdirectories, then in `dir` itself.
+pub(crate) fn candidates(
+    dir: &Path,
+    subdirs: &[String],
+    legacy: &[PathBuf],
+    soname: &OsStr,
+) -> Vec<PathBuf> {
+    let hwcaps_dir = dir.join(HWCAPS_DIR);
+    let mut candidates: Vec<_> = subdirs
+        .iter()
+        .map(|subdir| hwcaps_dir.join(subdir).join(soname))
+        .collect();
+    candidates.extend(legacy.iter().map(|subdir| dir.join(subdir).join(soname)));
+    candidates.push(dir.join(soname));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::legacy_subdirs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_legacy_subdirs() {
+        let caps = ["tls", "haswell", "x86_64"].map(String::from);
+        let subdirs = [
+            "tls/haswell/x86_64",
+            "tls/haswell",
+            "tls/x86_64",
+            "tls",
+            "haswell/x86_64",
+            "haswell",
+            "x86_64",
+        ];
+        assert_eq!(legacy_subdirs(&caps), subdirs.map(PathBuf::from));
+        assert!(legacy_subdirs(&[]).is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! Closures a [`Resolver`] keeps track of, to resolve again only those a changed file may
+//! change, for long-running processes that watch binaries or answer queries about them.
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{DependencyGraph, DependencyKind, ElfFile, Error, Resolver};
+
+/// The closures tracked by a [`Resolver`], and the files invalidated since they were last
+/// resolved again.
+#[derive(Debug, Default)]
+pub(crate) struct Tracking {
+    closures: Vec<Tracked>,
+    invalidated: HashSet<PathBuf>,
+}
+
+/// The closure of a binary, by what it may change with.
+#[derive(Debug)]
+struct Tracked {
+    root: PathBuf,
+    /// The binary and the files its libraries were loaded from, with the symlinks to them.
+    files: HashSet<PathBuf>,
+    /// The file names the libraries are searched for by.
+    names: HashSet<OsString>,
+}
+
+impl Tracking {
+    /// Notes that the file at `path` changed, if a closure is tracked at all.
+    pub(crate) fn invalidate(&mut self, path: &Path) {
+        if !self.closures.is_empty() {
+            self.invalidated.insert(path.to_owned());
+        }
+    }
+}
+
+impl Tracked {
+    /// The closure of `root`, which is `graph`, or has none if it could not be resolved.
+    fn new(root: &Path, graph: Option<&DependencyGraph>) -> Self {
+        let mut tracked = Tracked {
+            root: root.to_owned(),
+            files: HashSet::from([root.to_owned()]),
+            names: HashSet::new(),
+        };
+        let nodes = graph.map(|graph| graph.library_nodes()).unwrap_or_default();
+        for node in nodes {
+            tracked.files.extend(node.path().map(PathBuf::from));
+            tracked.files.extend(node.links.iter().cloned());
+            if node.kind != DependencyKind::Virtual {
+                let name = Path::new(&node.soname).file_name();
+                tracked.names.extend(name.map(OsString::from));
+            }
+        }
+        tracked
+    }
+
+    /// Whether the closure may change with the file at `path`: if it is one of its files, or
+    /// a library is searched for by its name, so it may now be found there.
+    fn references(&self, path: &Path) -> bool {
+        self.files.contains(path)
+            || path
+                .file_name()
+                .is_some_and(|name| self.names.contains(name))
+    }
+}
+
+impl Resolver {
+    /// Resolves the closure of the file at `path` like
+    /// [`ElfFile::dependency_graph`](crate::ElfFile::dependency_graph), and keeps track of
+    /// it, for [`Resolver::recompute_affected`] to resolve it again once a file it may change
+    /// with is [invalidated](Resolver::invalidate). Tracking a file again replaces its
+    /// closure. A file that can't be analyzed is still tracked, to be analyzed again once it
+    /// is invalidated.
+    pub fn track<P: AsRef<Path>>(self: &Arc<Self>, path: P) -> Result<DependencyGraph, Error> {
+        let path = path.as_ref();
+        let graph = ElfFile::with_resolver(path, self.clone()).dependency_graph();
+        let tracked = Tracked::new(path, graph.as_ref().ok());
+        let mut tracking = self.tracking();
+        match tracking
+            .closures
+            .iter_mut()
+            .find(|closure| closure.root == path)
+        {
+            Some(closure) => *closure = tracked,
+            None => tracking.closures.push(tracked),
+        }
+        graph
+    }
+
+    /// Stops tracking the closure of the file at `path`. Returns whether it was tracked.
+    pub fn untrack<P: AsRef<Path>>(&self, path: P) -> bool {
+        let mut tracking = self.tracking();
+        let len = tracking.closures.len();
+        tracking
+            .closures
+            .retain(|closure| closure.root != path.as_ref());
+        tracking.closures.len() != len
+    }
+
+    /// The files whose closures are [tracked](Resolver::track), in the order they were first
+    /// tracked.
+    pub fn tracked(&self) -> Vec<PathBuf> {
+        let tracking = self.tracking();
+        tracking
+            .closures
+            .iter()
+            .map(|closure| closure.root.clone())
+            .collect()
+    }
+
+    /// Resolves again the [tracked](Resolver::track) closures that a file
+    /// [invalidated](Resolver::invalidate) since the last call may have changed, and returns
+    /// them in the order they were tracked. A closure is affected by the files it was
+    /// resolved from, the binary, its libraries and the symlinks to them, and by files with
+    /// the name one of its libraries is searched for by, like a library installed where it
+    /// was missing, or in a directory searched before the one it was found in.
+    ///
+    /// Only the invalidated files are parsed again: every other file of an affected closure
+    /// is still known, and closures that are not affected are not resolved at all. Changes to
+    /// the system configuration, like the `ld.so.cache`, affect every closure, and need a new
+    /// resolver.
+    pub fn recompute_affected(self: &Arc<Self>) -> Vec<(PathBuf, Result<DependencyGraph, Error>)> {
+        let affected: Vec<PathBuf> = {
+            let mut tracking = self.tracking();
+            let invalidated = std::mem::take(&mut tracking.invalidated);
+            let closures = tracking.closures.iter();
+            let closures =
+                closures.filter(|closure| invalidated.iter().any(|path| closure.references(path)));
+            closures.map(|closure| closure.root.clone()).collect()
+        };
+        // Resolved without holding the lock, so other closures can be tracked meanwhile
+        let graphs: Vec<_> = affected
+            .into_iter()
+            .map(|root| {
+                let graph = ElfFile::with_resolver(&root, self.clone()).dependency_graph();
+                (root, graph)
+            })
+            .collect();
+        let mut tracking = self.tracking();
+        for (root, graph) in &graphs {
+            let closure = tracking
+                .closures
+                .iter_mut()
+                .find(|closure| closure.root == *root);
+            // Unless it stopped being tracked meanwhile
+            if let Some(closure) = closure {
+                *closure = Tracked::new(root, graph.as_ref().ok());
+            }
+        }
+        graphs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ElfBuilder;
+    use crate::{Resolver, ResolverConfig};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_recompute_affected() {
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        ElfBuilder::new().write(dir.path(), "libbar.so.1");
+        let foo_app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .write(dir.path(), "foo-app");
+        let bar_app = ElfBuilder::new()
+            .needed("libbar.so.1")
+            .write(dir.path(), "bar-app");
+        let broken_app = ElfBuilder::new()
+            .needed("libgone.so.1")
+            .write(dir.path(), "broken-app");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path());
+        let resolver = Arc::new(Resolver::new(config));
+        for app in [&foo_app, &bar_app, &broken_app] {
+            resolver.track(app).unwrap();
+        }
+        assert!(resolver.track(dir.path().join("nonexistent")).is_err());
+        assert_eq!(resolver.tracked().len(), 4);
+        assert!(resolver.untrack(dir.path().join("nonexistent")));
+        assert!(resolver.recompute_affected().is_empty());
+
+        // libfoo now needs libbar, and only it is parsed again
+        ElfBuilder::new()
+            .needed("libbar.so.1")
+            .write(dir.path(), "libfoo.so.1");
+        resolver.invalidate(&libfoo);
+        let parsed = resolver.metrics().files_parsed;
+        let recomputed = resolver.recompute_affected();
+        assert_eq!(recomputed.len(), 1);
+        let (root, graph) = &recomputed[0];
+        assert_eq!(root, &foo_app);
+        let sonames: Vec<_> = graph
+            .as_ref()
+            .unwrap()
+            .nodes()
+            .iter()
+            .map(|node| &node.soname)
+            .collect();
+        assert_eq!(sonames, ["foo-app", "libfoo.so.1", "libbar.so.1"]);
+        assert_eq!(resolver.metrics().files_parsed, parsed + 1);
+        assert!(resolver.recompute_affected().is_empty());
+
+        // Installing the missing library affects the closure missing it
+        let libgone = ElfBuilder::new().write(dir.path(), "libgone.so.1");
+        resolver.invalidate(&libgone);
+        resolver.invalidate(dir.path().join("unrelated"));
+        let recomputed = resolver.recompute_affected();
+        let roots: Vec<&Path> = recomputed.iter().map(|(root, _)| root.as_path()).collect();
+        assert_eq!(roots, [broken_app.as_path()]);
+        let graph = recomputed[0].1.as_ref().unwrap();
+        assert_eq!(graph.nodes()[1].path(), Some(libgone.as_path()));
+
+        // libbar is now in the closures of both other apps
+        resolver.invalidate(dir.path().join("libbar.so.1"));
+        let recomputed = resolver.recompute_affected();
+        let roots: Vec<&Path> = recomputed.iter().map(|(root, _)| root.as_path()).collect();
+        assert_eq!(roots, [foo_app.as_path(), bar_app.as_path()]);
+    }
+}
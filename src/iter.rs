@@ -0,0 +1,629 @@
+//! Lazy, on-demand traversal of the dependency closure.
+use std::collections::{HashSet, VecDeque};
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::resolver::{Executable, LoadedObject};
+use crate::{
+    DependencyKind, DynamicInfo, ElfHeader, FileStat, Rejection, Resolution, ResolveError,
+    Resolved, Resolver, ResourceLimit, SearchRule, StorePath,
+};
+
+/// The order in which [`Dependencies`] visits libraries, and in which the closure is walked
+/// with [`ResolverConfig::traversal_order`](crate::ResolverConfig::traversal_order).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TraversalOrder {
+    /// Each library's own dependencies come right after it, as in
+    /// [`ElfFile::get_libs_resolutions`](crate::ElfFile::get_libs_resolutions).
+    #[default]
+    DepthFirst,
+    /// All dependencies at one depth come before any at the next.
+    BreadthFirst,
+}
+
+/// A needed library, as reported by [`Dependencies`].
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dependency {
+    /// The `DT_NEEDED` (or filtee) entry, with bytes that are not UTF-8 replaced with `U+FFFD`.
+    pub soname: String,
+    /// How `needed_by` refers to the library.
+    pub kind: DependencyKind,
+    pub resolution: Resolution,
+    /// The symlinks followed from the path the library was found at, see
+    /// [`DependencyNode::links`](crate::DependencyNode::links).
+    pub links: Vec<PathBuf>,
+    /// Which rule found the library, see
+    /// [`DependencyNode::found_by`](crate::DependencyNode::found_by).
+    pub found_by: Option<SearchRule>,
+    /// The library's own `DT_SONAME`, see
+    /// [`DependencyNode::dt_soname`](crate::DependencyNode::dt_soname).
+    pub dt_soname: Option<String>,
+    /// The object whose entry this is.
+    pub needed_by: PathBuf,
+    /// 1 for the analyzed file's own dependencies, 2 for theirs, and so on.
+    pub depth: usize,
+    /// Whether the library's own dependencies were left out because it is at the
+    /// [maximum depth](crate::ResolverConfig::max_depth) or by the
+    /// [traversal filter](crate::ResolverConfig::traversal_filter).
+    pub truncated: bool,
+    /// See [`DependencyNode::digest`](crate::DependencyNode::digest).
+    pub digest: Option<String>,
+    /// See [`DependencyNode::stat`](crate::DependencyNode::stat).
+    pub stat: Option<FileStat>,
+    /// The library's GNU build ID, see
+    /// [`DependencyNode::build_id`](crate::DependencyNode::build_id).
+    pub build_id: Option<String>,
+    /// See [`DependencyNode::header`](crate::DependencyNode::header).
+    pub header: Option<ElfHeader>,
+    /// See [`DependencyNode::dynamic`](crate::DependencyNode::dynamic).
+    pub dynamic: Option<DynamicInfo>,
+    /// See [`DependencyNode::store_path`](crate::DependencyNode::store_path).
+    pub store_path: Option<StorePath>,
+    /// See [`DependencyNode::os_abi_mismatch`](crate::DependencyNode::os_abi_mismatch).
+    pub os_abi_mismatch: bool,
+    /// Why the paths tried for the library were passed over, if it was not found, see
+    /// [`DependencyNode::rejections`](crate::DependencyNode::rejections).
+    pub rejections: Vec<Rejection>,
+}
+
+/// What a [`DependencyVisitor`] wants to happen after visiting a library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitControl {
+    /// Go on, including into the library's own dependencies.
+    Continue,
+    /// Go on, but don't visit the library's own dependencies through this library.
+    SkipDependencies,
+    /// End the walk.
+    Stop,
+}
+
+/// Whether the dependencies of a library are collected, as decided by the
+/// [traversal filter](crate::ResolverConfig::traversal_filter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraversalDecision {
+    /// Collect the library's own dependencies.
+    Descend,
+    /// Report the library, but leave out its own dependencies, as at the
+    /// [maximum depth](crate::ResolverConfig::max_depth).
+    SkipSubtree,
+}
+
+/// A function deciding, from the soname, path and depth of a found library, whether its
+/// dependencies are collected.
+type FilterFn = dyn Fn(&str, &Path, usize) -> TraversalDecision + Send + Sync;
+
+/// The [traversal filter](crate::ResolverConfig::traversal_filter) of a configuration.
+#[derive(Clone)]
+pub(crate) struct TraversalFilter(pub(crate) Arc<FilterFn>);
+
+impl fmt::Debug for TraversalFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TraversalFilter(..)")
+    }
+}
+
+/// Receives the libraries of a closure from [`ElfFile::walk`](crate::ElfFile::walk).
+pub trait DependencyVisitor {
+    /// Called for every needed library in depth-first order, with the same libraries
+    /// [`Dependencies`] reports, whether or not it could be resolved.
+    fn visit(&mut self, dependency: &Dependency) -> VisitControl;
+}
+
+impl<F: FnMut(&Dependency) -> VisitControl> DependencyVisitor for F {
+    fn visit(&mut self, dependency: &Dependency) -> VisitControl {
+        self(dependency)
+    }
+}
+
+/// An iterator over the dependency closure of an ELF file, created by
+/// [`ElfFile::iter_dependencies`](crate::ElfFile::iter_dependencies). Each library is only
+/// read once it is reached, so stopping early skips the work for the rest of the closure. Like
+/// [`ElfFile::get_libs_resolutions`](crate::ElfFile::get_libs_resolutions), each resolved
+/// library is reported once, while a missing one is reported for every object that needs it.
+pub struct Dependencies {
+    resolver: Arc<Resolver>,
+    order: TraversalOrder,
+    executable: Executable,
+    seen: HashSet<PathBuf>,
+    pending: VecDeque<Pending>,
+    /// The library last returned, whose dependencies are queued on the next call to `next`.
+    deferred: Option<(PathBuf, LoadedObject, usize)>,
+    interrupted: bool,
+    exceeded_limit: Option<ResourceLimit>,
+    /// The number of dependencies returned so far.
+    returned: usize,
+    /// The size of the files read so far, the analyzed file included.
+    pub(crate) bytes_parsed: u64,
+}
+
+/// A `DT_NEEDED` entry waiting to be resolved.
+struct Pending {
+    soname: OsString,
+    kind: DependencyKind,
+    parent: Arc<Parent>,
+    depth: usize,
+}
+
+/// What the dependencies of an object need to know about it.
+struct Parent {
+    path: PathBuf,
+    rpath: Vec<(PathBuf, SearchRule)>,
+    search_dirs: Vec<(PathBuf, SearchRule)>,
+    nodeflib: bool,
+}
+
+impl Dependencies {
+    /// Walks the closure of the analyzed file at `path`, already loaded as `root`.
+    pub(crate) fn new(
+        resolver: Arc<Resolver>,
+        path: &Path,
+        root: LoadedObject,
+        order: TraversalOrder,
+    ) -> Self {
+        let identity = resolver.config().identity(path);
+        let mut dependencies = Self {
+            resolver,
+            order,
+            executable: root.executable,
+            seen: HashSet::from([identity]),
+            pending: VecDeque::new(),
+            deferred: None,
+            interrupted: false,
+            exceeded_limit: None,
+            returned: 0,
+            bytes_parsed: 0,
+        };
+        dependencies.enqueue(path, root, 1);
+        dependencies
+    }
+
+    /// Leaves out the dependencies of the library last returned by [`Iterator::next`], unless
+    /// they are also reached through another library.
+    pub fn skip_dependencies(&mut self) {
+        self.deferred = None;
+    }
+
+    /// Whether the iterator ended early, by the
+    /// [cancel flag](crate::ResolverConfig::cancel_flag) or the
+    /// [deadline](crate::ResolverConfig::deadline), rather than at the end of the closure.
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// The [`ResourceLimit`] the iterator ended early at, as
+    /// [`DependencyGraph::exceeded_limit`](crate::DependencyGraph::exceeded_limit) reports it
+    /// for graphs.
+    pub fn exceeded_limit(&self) -> Option<ResourceLimit> {
+        self.exceeded_limit
+    }
+
+    /// Ends the iteration at `limit`.
+    fn stop_at(&mut self, limit: ResourceLimit) -> Option<Dependency> {
+        self.exceeded_limit = Some(limit);
+        self.deferred = None;
+        self.pending.clear();
+        None
+    }
+
+    /// Queues the `DT_NEEDED` entries of `object`, which was found at `path`.
+    fn enqueue(&mut self, path: &Path, object: LoadedObject, depth: usize) {
+        let parent = Arc::new(Parent {
+            path: path.to_owned(),
+            rpath: object.rpath,
+            search_dirs: object.search_dirs,
+            nodeflib: object.nodeflib,
+        });
+        let pending = object
+            .dependencies
+            .into_iter()
+            .map(|(soname, kind)| Pending {
+                soname,
+                kind,
+                parent: parent.clone(),
+                depth,
+            });
+        match self.order {
+            // Keep the children in order at the front of the queue
+            TraversalOrder::DepthFirst => {
+                for pending in pending.rev() {
+                    self.pending.push_front(pending);
+                }
+            }
+            TraversalOrder::BreadthFirst => self.pending.extend(pending),
+        }
+    }
+}
+
+impl Iterator for Dependencies {
+    type Item = Dependency;
+
+    fn next(&mut self) -> Option<Dependency> {
+        if let Some((path, object, depth)) = self.deferred.take() {
+            self.enqueue(&path, object, depth);
+        }
+        while let Some(pending) = self.pending.pop_front() {
+            if self.resolver.config().is_interrupted() {
+                self.interrupted = true;
+                self.pending.clear();
+                return None;
+            }
+            let config = self.resolver.config();
+            if let Some(max) = config.max_dependencies.filter(|&max| self.returned >= max) {
+                return self.stop_at(ResourceLimit::Dependencies(max));
+            }
+            let parent = pending.parent;
+            let unsearched = match pending.kind {
+                DependencyKind::Virtual => Some(Resolved::Virtual),
+                _ if self.resolver.config().is_ignored(&pending.soname) => Some(Resolved::Ignored),
+                _ => None,
+            };
+            if let Some(resolved) = unsearched {
+                self.returned += 1;
+                return Some(Dependency {
+                    soname: pending.soname.to_string_lossy().into_owned(),
+                    kind: pending.kind,
+                    resolution: Ok(resolved),
+                    links: Vec::new(),
+                    found_by: None,
+                    dt_soname: None,
+                    needed_by: parent.path.clone(),
+                    depth: pending.depth,
+                    truncated: false,
+                    digest: None,
+                    stat: None,
+                    build_id: None,
+                    header: None,
+                    dynamic: None,
+                    store_path: None,
+                    os_abi_mismatch: false,
+                    rejections: Vec::new(),
+                });
+            }
+            let search = self.resolver.search(
+                &pending.soname,
+                &parent.search_dirs,
+                Some(self.executable),
+                parent.nodeflib,
+            );
+            let config = self.resolver.config();
+            let (links, found_by) = match &search {
+                Ok((path, rule)) => (config.symlink_chain(path), Some(rule.clone())),
+                Err(_) => (Vec::new(), None),
+            };
+            let search = search.map(|(path, _)| (config.identity(&path), path));
+            let store_path = match &search {
+                Ok((identity, _)) => config.store_path(identity),
+                Err(_) => None,
+            };
+            let mut truncated = false;
+            let mut dt_soname = None;
+            let mut build_id = None;
+            let mut header = None;
+            let mut dynamic = None;
+            let mut os_abi_mismatch = false;
+            let mut rejections = Vec::new();
+            let (digest, stat) = match &search {
+                Ok((identity, path)) if !self.seen.contains(identity) => {
+                    (self.resolver.digest(path), self.resolver.stat(path))
+                }
+                _ => (None, None),
+            };
+            let resolution = match search {
+                // Each library is reported and searched for dependencies only the first time
+                Ok((identity, _)) if !self.seen.insert(identity.clone()) => continue,
+                Ok((identity, path)) if config.recursive => {
+                    if let Some(max) = config.max_bytes_parsed {
+                        let size = self.resolver.file_size(&path).unwrap_or_default();
+                        if self.bytes_parsed + size > max {
+                            return self.stop_at(ResourceLimit::BytesParsed(max));
+                        }
+                        self.bytes_parsed += size;
+                    }
+                    let executable = Some(self.executable);
+                    let object = self.resolver.load_object(&path, &parent.rpath, executable);
+                    if let Ok(object) = &object {
+                        let soname = object.soname.as_ref();
+                        dt_soname = soname.map(|soname| soname.to_string_lossy().into_owned());
+                        build_id = object.build_id.clone();
+                        header = Some(object.header);
+                        dynamic = Some(object.dynamic.clone());
+                        os_abi_mismatch = object.os_abi_mismatch;
+                    }
+                    match object {
+                        Ok(object)
+                            if config.stops_at(
+                                &pending.soname.to_string_lossy(),
+                                &path,
+                                pending.depth,
+                            ) =>
+                        {
+                            truncated = !object.dependencies.is_empty();
+                            Ok(identity)
+                        }
+                        Ok(object) => {
+                            self.deferred = Some((path, object, pending.depth + 1));
+                            Ok(identity)
+                        }
+                        Err(error) => Err(ResolveError::Load(error)),
+                    }
+                }
+                Ok((identity, _)) => Ok(identity),
+                // Like ld.so, quietly skip auxiliary filtees that aren't there, and optional
+                // libraries the program declares it may load
+                Err(_) if pending.kind.is_optional() => continue,
+                Err(_) => {
+                    rejections = self.resolver.rejections(
+                        &pending.soname,
+                        &parent.search_dirs,
+                        Some(self.executable),
+                        parent.nodeflib,
+                    );
+                    Err(ResolveError::NotFound {
+                        needed_by: parent.path.clone(),
+                    })
+                }
+            };
+            self.returned += 1;
+            return Some(Dependency {
+                soname: pending.soname.to_string_lossy().into_owned(),
+                kind: pending.kind,
+                resolution: resolution.map(Resolved::Found),
+                links,
+                found_by,
+                dt_soname,
+                needed_by: parent.path.clone(),
+                depth: pending.depth,
+                truncated,
+                digest,
+                stat,
+                build_id,
+                header,
+                dynamic,
+                store_path,
+                os_abi_mismatch,
+                rejections,
+            });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TraversalDecision, TraversalOrder, VisitControl};
+    use crate::test_util::ElfBuilder;
+    use crate::{Dependency, ElfFile, Resolver, ResolverConfig};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    #[test]
+    fn test_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let (elf_file, root) = test_tree(dir.path());
+        let visit = |order| -> Vec<(String, usize)> {
+            let dependencies = elf_file.iter_dependencies(order).unwrap();
+            dependencies
+                .map(|dependency| (dependency.soname, dependency.depth))
+                .collect()
+        };
+        let depth_first = visit(TraversalOrder::DepthFirst);
+        let breadth_first = visit(TraversalOrder::BreadthFirst);
+        let expected = [
+            ("liba.so", 1),
+            ("libc1.so", 2),
+            ("libb.so", 1),
+            ("libdyn-lib-finder-missing.so", 1),
+        ];
+        assert_eq!(
+            depth_first,
+            expected.map(|(soname, depth)| (soname.to_owned(), depth))
+        );
+        let expected = [
+            ("liba.so", 1),
+            ("libb.so", 1),
+            ("libdyn-lib-finder-missing.so", 1),
+            ("libc1.so", 2),
+        ];
+        assert_eq!(
+            breadth_first,
+            expected.map(|(soname, depth)| (soname.to_owned(), depth))
+        );
+
+        // The closure matches the eager API
+        let resolutions = elf_file.get_libs_resolutions().unwrap();
+        assert!(resolutions
+            .iter()
+            .map(|(soname, _)| soname)
+            .eq(depth_first.iter().map(|(soname, _)| soname)));
+
+        // Stopping early
+        let first = elf_file
+            .iter_dependencies(TraversalOrder::BreadthFirst)
+            .unwrap()
+            .next();
+        assert_eq!(first.unwrap().needed_by, root);
+    }
+
+    #[test]
+    fn test_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        let (elf_file, _) = test_tree(dir.path());
+        let mut visited = Vec::new();
+        elf_file
+            .walk(&mut |dependency: &Dependency| {
+                visited.push(dependency.soname.clone());
+                match dependency.soname.as_str() {
+                    "liba.so" => VisitControl::SkipDependencies,
+                    "libb.so" => VisitControl::Stop,
+                    _ => VisitControl::Continue,
+                }
+            })
+            .unwrap();
+        assert_eq!(visited, ["liba.so", "libb.so"]);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, root) = test_tree(dir.path());
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path()])
+            .without_default_dirs()
+            .use_env(false)
+            .max_depth(1);
+        let elf_file = ElfFile::with_config(root, config);
+        let graph = elf_file.dependency_graph().unwrap();
+        let truncated: Vec<_> = graph
+            .nodes()
+            .iter()
+            .map(|node| (node.soname.as_str(), node.truncated))
+            .collect();
+        let expected = [
+            ("app", false),
+            ("liba.so", true),
+            ("libb.so", true),
+            ("libdyn-lib-finder-missing.so", false),
+        ];
+        assert_eq!(truncated, expected);
+        let dependencies = elf_file
+            .iter_dependencies(TraversalOrder::DepthFirst)
+            .unwrap();
+        assert!(dependencies
+            .map(|dependency| (dependency.soname, dependency.truncated))
+            .eq(expected[1..]
+                .iter()
+                .map(|&(soname, truncated)| (soname.to_owned(), truncated))));
+    }
+
+    #[test]
+    fn test_traversal_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, root) = test_tree(dir.path());
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let filter_asked = asked.clone();
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path()])
+            .without_default_dirs()
+            .use_env(false)
+            .traversal_filter(move |soname, path, depth| {
+                filter_asked
+                    .lock()
+                    .unwrap()
+                    .push((soname.to_owned(), depth));
+                assert_eq!(path.file_name().unwrap(), soname);
+                match soname {
+                    "liba.so" => TraversalDecision::SkipSubtree,
+                    _ => TraversalDecision::Descend,
+                }
+            });
+        let elf_file = ElfFile::with_config(root, config);
+        let graph = elf_file.dependency_graph().unwrap();
+        let truncated: Vec<_> = graph
+            .nodes()
+            .iter()
+            .map(|node| (node.soname.as_str(), node.truncated))
+            .collect();
+        // libc1 is only needed below liba
+        let expected = [
+            ("app", false),
+            ("liba.so", true),
+            ("libb.so", false),
+            ("libdyn-lib-finder-missing.so", false),
+        ];
+        assert_eq!(truncated, expected);
+        let asked = std::mem::take(&mut *asked.lock().unwrap());
+        assert_eq!(
+            asked,
+            [("liba.so".to_owned(), 1), ("libb.so".to_owned(), 1)]
+        );
+        let dependencies = elf_file
+            .iter_dependencies(TraversalOrder::DepthFirst)
+            .unwrap();
+        assert!(dependencies
+            .map(|dependency| (dependency.soname, dependency.truncated))
+            .eq(expected[1..]
+                .iter()
+                .map(|&(soname, truncated)| (soname.to_owned(), truncated))));
+    }
+
+    #[test]
+    fn test_interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, root) = test_tree(dir.path());
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path()])
+            .without_default_dirs()
+            .use_env(false);
+        // Cancelled while liba is read
+        let cancel = Arc::new(AtomicBool::new(false));
+        let filter_cancel = cancel.clone();
+        let cancelled = config.clone().cancel_flag(cancel.clone()).traversal_filter(
+            move |soname, _path, _depth| {
+                if soname == "liba.so" {
+                    filter_cancel.store(true, Ordering::Relaxed);
+                }
+                TraversalDecision::Descend
+            },
+        );
+        let elf_file = ElfFile::with_config(&root, cancelled);
+        let graph = elf_file.dependency_graph().unwrap();
+        assert!(graph.is_interrupted());
+        let truncated: Vec<_> = graph
+            .nodes()
+            .iter()
+            .map(|node| (node.soname.as_str(), node.truncated))
+            .collect();
+        assert_eq!(
+            truncated,
+            [("app", false), ("liba.so", true), ("libb.so", false)]
+        );
+        cancel.store(false, Ordering::Relaxed);
+        let mut dependencies = elf_file
+            .iter_dependencies(TraversalOrder::DepthFirst)
+            .unwrap();
+        assert_eq!(dependencies.next().unwrap().soname, "liba.so");
+        assert!(!dependencies.is_interrupted());
+        assert!(dependencies.next().is_none());
+        assert!(dependencies.is_interrupted());
+
+        let late = config.clone().deadline(Instant::now());
+        let graph = ElfFile::with_config(&root, late.clone())
+            .dependency_graph()
+            .unwrap();
+        assert!(graph.is_interrupted());
+        assert_eq!(graph.nodes().len(), 1);
+        assert!(graph.node(graph.root()).truncated);
+        let report = Arc::new(Resolver::new(late)).audit([dir.path()]);
+        assert!(report.interrupted);
+        assert_eq!(report.scanned, 0);
+        assert!(!report.is_clean());
+
+        let graph = ElfFile::with_config(&root, config)
+            .dependency_graph()
+            .unwrap();
+        assert!(!graph.is_interrupted());
+    }
+
+    /// An app needing liba (which needs libc1), libb (which needs liba) and a missing library.
+    fn test_tree(dir: &Path) -> (ElfFile, PathBuf) {
+        ElfBuilder::new().needed("libc1.so").write(dir, "liba.so");
+        ElfBuilder::new().needed("liba.so").write(dir, "libb.so");
+        ElfBuilder::new().write(dir, "libc1.so");
+        let root = ElfBuilder::new()
+            .needed("liba.so")
+            .needed("libb.so")
+            .needed("libdyn-lib-finder-missing.so")
+            .write(dir, "app");
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir])
+            .without_default_dirs()
+            .use_env(false);
+        (ElfFile::with_config(&root, config), root)
+    }
+}
@@ -0,0 +1,434 @@
+//! Dependency resolution for Linux kernel modules, which name the modules they need in their
+//! `.modinfo` section rather than in a dynamic section, and are found by name among the
+//! modules of a kernel release.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::archive_fs::compression;
+use crate::filesystem::{FileKind, FileSystem};
+use crate::object::Object;
+use crate::parse::parse_error;
+use crate::{
+    DependencyGraph, DependencyNode, Error, Resolution, ResolveError, Resolved, ResolverConfig,
+};
+
+/// The directory the modules of each kernel release are installed in, by release.
+const MODULES_DIR: &str = "/lib/modules";
+/// The extensions of module files, as kmod compresses them.
+const EXTENSIONS: [&str; 4] = [".ko", ".ko.gz", ".ko.xz", ".ko.zst"];
+
+/// What a kernel module declares about itself in its `.modinfo` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModInfo {
+    /// The name of the module, from `name=`, or else its file name without its extension.
+    pub name: String,
+    /// The names of the modules it needs loaded first, from `depends=`.
+    pub depends: Vec<String>,
+    /// The kernel release and options it was built for, from `vermagic=`, like
+    /// `6.1.0-18-amd64 SMP preempt mod_unload modversions`.
+    pub vermagic: Option<String>,
+    /// Every `key=value` entry, in order, including the keys that appear more than once like
+    /// `alias` and `firmware`.
+    pub fields: Vec<(String, String)>,
+}
+
+impl ModInfo {
+    /// The values of the key `key`, in order.
+    pub fn values<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        let fields = self.fields.iter().filter(move |(field, _)| field == key);
+        fields.map(|(_, value)| value.as_str())
+    }
+}
+
+/// Represents a Linux kernel module on disk, a relocatable ELF file named `*.ko`, possibly
+/// compressed as `*.ko.gz`, `*.ko.xz` or `*.ko.zst`, and provides the same dependency queries
+/// as [`ElfFile`](crate::ElfFile).
+///
+/// The modules a module depends on are those its `.modinfo` section lists in `depends=`. They
+/// are found by name in `/lib/modules/<release>`, inside the [`ResolverConfig::sysroot`],
+/// where `<release>` is the first word of the module's `vermagic=` unless
+/// [`KernelModule::kernel_release`] sets another: at the paths `modules.dep` lists if it is
+/// there, or else anywhere in that directory. Modules listed in `modules.builtin` are built
+/// into the kernel, and resolve to [`Resolved::Virtual`]. Names are compared as the kernel
+/// does, with `-` and `_` the same.
+pub struct KernelModule {
+    path: PathBuf,
+    config: ResolverConfig,
+    release: Option<String>,
+}
+
+impl KernelModule {
+    /// Creates a [`KernelModule`] instance from [`AsRef<Path>`]
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        KernelModule::with_config(path, ResolverConfig::default())
+    }
+
+    /// Creates a [`KernelModule`] instance that resolves dependencies according to `config`
+    pub fn with_config<P: AsRef<Path>>(path: P, config: ResolverConfig) -> Self {
+        let path = path.as_ref().to_owned();
+        Self {
+            path,
+            config,
+            release: None,
+        }
+    }
+
+    /// Looks dependencies up among the modules of the kernel release `release`, such as the
+    /// output of `uname -r`, rather than of the release the module was built for.
+    pub fn kernel_release(mut self, release: &str) -> Self {
+        self.release = Some(release.to_owned());
+        self
+    }
+
+    /// Like [`ElfFile::get_libs_full_paths`](crate::ElfFile::get_libs_full_paths): the paths
+    /// of all dependencies, or [`Error::Unresolved`] if one of them is missing. Modules built
+    /// into the kernel are left out.
+    pub fn get_libs_full_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        self.get_libs_resolutions()?
+            .into_iter()
+            .filter_map(|(name, resolution)| match resolution {
+                Ok(resolved) => resolved.into_path().map(Ok),
+                Err(error) => Some(Err(error.into_error(name))),
+            })
+            .collect()
+    }
+
+    /// Like [`ElfFile::get_libs_resolutions`](crate::ElfFile::get_libs_resolutions): every
+    /// module name in the order it was encountered, paired with where it was found.
+    pub fn get_libs_resolutions(&self) -> Result<Vec<(String, Resolution)>, Error> {
+        let nodes = self.dependency_graph()?.into_nodes();
+        Ok(nodes
+            .into_iter()
+            .skip(1)
+            .map(|node| (node.soname, node.resolution))
+            .collect())
+    }
+
+    /// Reads the `.modinfo` section of this module.
+    pub fn modinfo(&self) -> Result<ModInfo, Error> {
+        read_modinfo(&self.config, &self.path)
+    }
+
+    /// Builds the full [`DependencyGraph`] of this module, breadth-first. The soname of each
+    /// node is the module name it was needed by.
+    /// # Return Value [Err]
+    /// Only returned when the module itself cannot be analyzed.
+    pub fn dependency_graph(&self) -> Result<DependencyGraph, Error> {
+        let modinfo = self.modinfo()?;
+        let config = &self.config;
+        let release = self.release.as_deref().or_else(|| {
+            let vermagic = modinfo.vermagic.as_deref()?;
+            vermagic.split_whitespace().next()
+        });
+        let modules = match release {
+            Some(release) => {
+                let dir = config.system_file(&Path::new(MODULES_DIR).join(release));
+                Modules::read(config, &dir)
+            }
+            None => Modules::default(),
+        };
+
+        let file_name = self.path.file_name().unwrap_or(self.path.as_os_str());
+        let resolution = Ok(Resolved::Found(self.path.clone()));
+        let mut graph = DependencyGraph::new(DependencyNode::new(file_name, resolution));
+        let mut seen = HashMap::from([(normalize(&modinfo.name), graph.root())]);
+        let mut queue = VecDeque::from([(graph.root(), self.path.clone(), modinfo, 0)]);
+        while let Some((node, path, modinfo, depth)) = queue.pop_front() {
+            if config.is_interrupted() {
+                break;
+            }
+            if config.stops_at(&graph.node(node).soname, &path, depth) {
+                graph.node_mut(node).truncated = !modinfo.depends.is_empty();
+                continue;
+            }
+            for name in &modinfo.depends {
+                let key = normalize(name);
+                if let Some(&seen) = seen.get(&key) {
+                    graph.add_edge(node, seen);
+                    continue;
+                }
+                let found = modules
+                    .paths
+                    .get(&key)
+                    .filter(|_| !modules.builtin.contains(&key));
+                let resolution = match found {
+                    Some(found) => Ok(Resolved::Found(config.identity(found))),
+                    None if modules.builtin.contains(&key) => Ok(Resolved::Virtual),
+                    None => Err(ResolveError::NotFound {
+                        needed_by: path.clone(),
+                    }),
+                };
+                let child = graph.add_node(DependencyNode::new(name, resolution));
+                graph.add_edge(node, child);
+                seen.insert(key, child);
+                let Some(found) = found.filter(|_| config.recursive) else {
+                    continue;
+                };
+                match read_modinfo(config, found) {
+                    Ok(modinfo) => queue.push_back((child, found.clone(), modinfo, depth + 1)),
+                    Err(error) => graph.node_mut(child).resolution = Err(ResolveError::Load(error)),
+                }
+            }
+        }
+        Ok(graph)
+    }
+}
+
+/// The modules of a kernel release, in its directory of `/lib/modules`.
+#[derive(Debug, Default)]
+struct Modules {
+    /// The paths of the module files, by their normalized names.
+    paths: HashMap<String, PathBuf>,
+    /// The normalized names of the modules built into the kernel, from `modules.builtin`.
+    builtin: HashSet<String>,
+}
+
+impl Modules {
+    /// Reads the modules in `dir`, from `modules.dep` if it is there and by walking `dir`
+    /// otherwise.
+    fn read(config: &ResolverConfig, dir: &Path) -> Self {
+        let fs = config.fs();
+        let mut modules = Modules::default();
+        match fs.read(&dir.join("modules.dep")) {
+            Ok(dep) => {
+                // `<path>: <path of each dependency>`, relative to `dir` since kmod 3
+                for line in String::from_utf8_lossy(&dep).lines() {
+                    let Some((path, _)) = line.split_once(':') else {
+                        continue;
+                    };
+                    let path = Path::new(path.trim());
+                    let path = match path.is_absolute() {
+                        true => config.rebase(path),
+                        false => dir.join(path),
+                    };
+                    if let Some(name) = module_name(&path) {
+                        modules.paths.entry(name).or_insert(path);
+                    }
+                }
+            }
+            Err(_) => modules.walk(fs, dir),
+        }
+        if let Ok(builtin) = fs.read(&dir.join("modules.builtin")) {
+            let lines = String::from_utf8_lossy(&builtin);
+            let names = lines
+                .lines()
+                .filter_map(|line| module_name(Path::new(line.trim())));
+            modules.builtin.extend(names);
+        }
+        modules
+    }
+
+    /// Adds the module files in `dir` and its subdirectories, without following symlinks like
+    /// the `build` and `source` links to the kernel's sources.
+    fn walk(&mut self, fs: &dyn FileSystem, dir: &Path) {
+        let Ok(mut names) = fs.read_dir(dir) else {
+            return;
+        };
+        names.sort();
+        for name in names {
+            let path = dir.join(name);
+            match fs.metadata(&path).map(|metadata| metadata.kind) {
+                Ok(FileKind::Dir) => self.walk(fs, &path),
+                Ok(FileKind::File) => {
+                    if let Some(name) = module_name(&path) {
+                        self.paths.entry(name).or_insert(path);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The normalized name of the module file at `path`, or `None` if it is not one.
+fn module_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let name = EXTENSIONS
+        .iter()
+        .find_map(|extension| file_name.strip_suffix(extension))?;
+    Some(normalize(name))
+}
+
+/// The module name `name` with its dashes replaced by underscores, as the kernel compares them.
+fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Reads the `.modinfo` section of the module at `path`, decompressing it first if needed,
+/// up to the [maximum decompressed size](ResolverConfig::max_decompressed_size) of `config`.
+fn read_modinfo(config: &ResolverConfig, path: &Path) -> Result<ModInfo, Error> {
+    let invalid = || Error::InvalidModule {
+        path: path.to_owned(),
+    };
+    let parse_error = |source| parse_error(path, source);
+    let io_error = |source| Error::Io {
+        path: path.to_owned(),
+        source,
+    };
+    let data = config.fs().read(path).map_err(io_error)?;
+    let data = decompress(data, config.decompressed_size_limit()).map_err(|source| {
+        config.decompression_error(path, source, |source| match source.kind() {
+            io::ErrorKind::InvalidData => invalid(),
+            _ => io_error(source),
+        })
+    })?;
+    let mut elf = Object::open(&data[..]).map_err(parse_error)?;
+    let shdr = elf
+        .section_by_name(".modinfo")
+        .map_err(parse_error)?
+        .ok_or_else(invalid)?;
+    let contents = elf.section_data(&shdr).map_err(parse_error)?;
+    let fields: Vec<(String, String)> = contents
+        .split(|&byte| byte == 0)
+        .filter_map(|entry| {
+            let (key, value) = std::str::from_utf8(entry).ok()?.split_once('=')?;
+            Some((key.to_owned(), value.to_owned()))
+        })
+        .collect();
+    let value = |key: &str| {
+        let field = fields.iter().find(|(field, _)| field == key);
+        field.map(|(_, value)| value.clone())
+    };
+    let name = match value("name") {
+        Some(name) => name,
+        None => module_name(path).ok_or_else(invalid)?,
+    };
+    let depends = value("depends").unwrap_or_default();
+    let depends = depends.split(',').filter(|name| !name.is_empty());
+    Ok(ModInfo {
+        name,
+        depends: depends.map(str::to_owned).collect(),
+        vermagic: value("vermagic"),
+        fields,
+    })
+}
+
+/// `data` decompressed to up to `limit` bytes if it is a gzip, xz or zstd file, and as it is
+/// otherwise.
+fn decompress(data: Vec<u8>, limit: u64) -> io::Result<Vec<u8>> {
+    match compression(&data) {
+        Some(decompress) => decompress(&data, limit),
+        None => Ok(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KernelModule;
+    use crate::abi::ET_REL;
+    use crate::test_util::{gzip, write_file, xz, zstd, ElfBuilder};
+    use crate::{Error, ResolveError, Resolved, ResolverConfig, ResourceLimit};
+
+    fn module(name: &str, depends: &str) -> ElfBuilder {
+        ElfBuilder::new()
+            .e_type(ET_REL)
+            .without_dynamic_section()
+            .modinfo("name", name)
+            .modinfo("depends", depends)
+            .modinfo("vermagic", "6.1.0-test SMP preempt mod_unload")
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(all(feature = "gzip", feature = "xz", feature = "zstd")),
+        ignore = "needs the `gzip`, `xz` and `zstd` features"
+    )]
+    fn test_kernel_module() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("lib/modules/6.1.0-test");
+        let net = dir.join("kernel/net");
+        let nat = module("nf-nat", "nf_conntrack,libcrc32c,missing").build();
+        let path = write_file(&net, "nf-nat.ko", &nat);
+        let conntrack = module("nf_conntrack", "nf_defrag_ipv4").build();
+        let conntrack = write_file(&net, "nf_conntrack.ko.gz", &gzip(&conntrack));
+        let defrag = write_file(
+            &net,
+            "nf_defrag_ipv4.ko",
+            &module("nf_defrag_ipv4", "").build(),
+        );
+        write_file(&dir.join("kernel/lib"), "libcrc32c.ko", b"not a module");
+        write_file(&dir, "modules.builtin", b"kernel/lib/libcrc32c.ko\n");
+        let config = ResolverConfig::new().sysroot(root.path());
+
+        let limited = config.clone().max_decompressed_size(100);
+        let modinfo = KernelModule::with_config(&conntrack, limited).modinfo();
+        assert!(matches!(
+            modinfo,
+            Err(Error::LimitExceeded {
+                limit: ResourceLimit::DecompressedSize(100),
+                ..
+            })
+        ));
+
+        let modinfo = KernelModule::with_config(&path, config.clone())
+            .modinfo()
+            .unwrap();
+        assert_eq!(modinfo.name, "nf-nat");
+        assert_eq!(modinfo.depends, ["nf_conntrack", "libcrc32c", "missing"]);
+        assert_eq!(
+            modinfo.vermagic.as_deref(),
+            Some("6.1.0-test SMP preempt mod_unload")
+        );
+        assert_eq!(modinfo.values("depends").count(), 1);
+
+        // Without `modules.dep`, by walking the release's directory
+        let resolutions = KernelModule::with_config(&path, config.clone())
+            .get_libs_resolutions()
+            .unwrap();
+        let names: Vec<_> = resolutions.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["nf_conntrack", "libcrc32c", "missing", "nf_defrag_ipv4"]
+        );
+        assert_eq!(
+            resolutions[0].1.as_ref().unwrap().path(),
+            Some(conntrack.as_path())
+        );
+        assert_eq!(resolutions[1].1.as_ref().unwrap(), &Resolved::Virtual);
+        assert!(matches!(
+            &resolutions[2].1,
+            Err(ResolveError::NotFound { needed_by }) if needed_by == &path
+        ));
+        assert_eq!(
+            resolutions[3].1.as_ref().unwrap().path(),
+            Some(defrag.as_path())
+        );
+
+        // Where `modules.dep` says, for another release than the module was built for
+        let other = root.path().join("lib/modules/6.2.0-other");
+        let moved = write_file(
+            &other.join("extra"),
+            "nf_conntrack.ko",
+            &module("x", "").build(),
+        );
+        let dep = "extra/nf_conntrack.ko:\nkernel/net/nf-nat.ko: extra/nf_conntrack.ko\n";
+        write_file(&other, "modules.dep", dep.as_bytes());
+        let resolutions = KernelModule::with_config(&path, config.clone())
+            .kernel_release("6.2.0-other")
+            .get_libs_resolutions()
+            .unwrap();
+        assert_eq!(
+            resolutions[0].1.as_ref().unwrap().path(),
+            Some(moved.as_path())
+        );
+        assert!(matches!(
+            resolutions[1].1,
+            Err(ResolveError::NotFound { .. })
+        ));
+        assert_eq!(resolutions.len(), 3);
+
+        // Modules compressed with xz and zstd, as kmod installs them
+        let nat = module("nf-nat", "nf_conntrack").build();
+        for (name, compressed) in [("nf-nat.ko.xz", xz(&nat)), ("nf-nat.ko.zst", zstd(&nat))] {
+            let path = write_file(&net, name, &compressed);
+            let module = KernelModule::with_config(&path, config.clone());
+            assert_eq!(module.modinfo().unwrap().depends, ["nf_conntrack"]);
+            let paths = module.get_libs_full_paths().unwrap();
+            assert_eq!(paths, [conntrack.clone(), defrag.clone()]);
+        }
+        let path = write_file(&net, "bad.ko.xz", &xz(&nat)[..40]);
+        let module = KernelModule::with_config(&path, config);
+        assert!(matches!(module.modinfo(), Err(Error::InvalidModule { .. })));
+    }
+}
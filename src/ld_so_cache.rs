@@ -0,0 +1,252 @@
+//! Parser for glibc's `/etc/ld.so.cache`, as written by `ldconfig`.
+//!
+//! Two layouts exist: the old libc5-compatible format (magic `ld.so-1.7.0`) and the new format
+//! (magic `glibc-ld.so.cache1.1`). Older `ldconfig` versions write the new format embedded after
+//! an old-format table; in that case only the new table is used.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+const OLD_MAGIC: &[u8] = b"ld.so-1.7.0";
+const NEW_MAGIC: &[u8] = b"glibc-ld.so.cache1.1";
+const OLD_HEADER_SIZE: usize = 16;
+const OLD_ENTRY_SIZE: usize = 12;
+const NEW_HEADER_SIZE: usize = 48;
+const NEW_ENTRY_SIZE: usize = 24;
+/// Value of the new header's `flags` field when the cache was written on a big-endian machine.
+const FLAGS_BIG_ENDIAN: u8 = 3;
+
+/// The default location of the cache.
+pub const LD_SO_CACHE_PATH: &str = "/etc/ld.so.cache";
+
+/// A single soname-to-path mapping from the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheEntry {
+    /// The soname the library is registered under.
+    pub soname: String,
+    /// The full path of the library.
+    pub path: PathBuf,
+    /// The `FLAG_*` bits describing the library's ABI (e.g. `0x0303` for x86-64 ELF libc6).
+    pub flags: u32,
+    /// The hardware capabilities the library requires; `0` for baseline libraries.
+    pub hwcap: u64,
+}
+
+/// The parsed contents of an `ld.so.cache` file.
+#[derive(Debug, Clone, Default)]
+pub struct LdSoCache {
+    entries: Vec<CacheEntry>,
+}
+
+impl LdSoCache {
+    /// Reads and parses the cache file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|source| Error::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        LdSoCache::parse(&data).ok_or_else(|| Error::InvalidCache {
+            path: path.to_owned(),
+        })
+    }
+
+    /// Parses cache contents, returning [`None`] if they are not in either known layout.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.starts_with(NEW_MAGIC) {
+            return parse_new(data);
+        }
+        if !data.starts_with(OLD_MAGIC) {
+            return None;
+        }
+        let count = read_u32(data, OLD_MAGIC.len() + 1, false)? as usize;
+        let strings_start = OLD_HEADER_SIZE.checked_add(count.checked_mul(OLD_ENTRY_SIZE)?)?;
+        // A new-format table may follow the old one, aligned to 8 bytes
+        let new_start = strings_start.next_multiple_of(8);
+        if data
+            .get(new_start..)
+            .is_some_and(|rest| rest.starts_with(NEW_MAGIC))
+        {
+            return parse_new(&data[new_start..]);
+        }
+        let mut entries = Vec::with_capacity(count.min(data.len() / OLD_ENTRY_SIZE));
+        for index in 0..count {
+            let entry = OLD_HEADER_SIZE + index * OLD_ENTRY_SIZE;
+            let flags = read_u32(data, entry, false)?;
+            let key = read_u32(data, entry + 4, false)? as usize;
+            let value = read_u32(data, entry + 8, false)? as usize;
+            entries.push(CacheEntry {
+                soname: read_str(data, strings_start.checked_add(key)?)?.to_owned(),
+                path: PathBuf::from(read_str(data, strings_start.checked_add(value)?)?),
+                flags,
+                hwcap: 0,
+            });
+        }
+        Some(Self { entries })
+    }
+
+    /// All entries, in the order `ldconfig` wrote them (which is the order `ld.so` prefers them).
+    pub fn entries(&self) -> &[CacheEntry] {
+        &self.entries
+    }
+
+    /// The baseline (non-hwcap) paths registered for `soname`, most preferred first.
+    pub fn lookup<'a>(&'a self, soname: &'a str) -> impl Iterator<Item = &'a Path> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.soname == soname && entry.hwcap == 0)
+            .map(|entry| entry.path.as_path())
+    }
+}
+
+fn parse_new(data: &[u8]) -> Option<LdSoCache> {
+    let big_endian = *data.get(NEW_MAGIC.len() + 8)? == FLAGS_BIG_ENDIAN;
+    let count = read_u32(data, NEW_MAGIC.len(), big_endian)? as usize;
+    let mut entries = Vec::with_capacity(count.min(data.len() / NEW_ENTRY_SIZE));
+    for index in 0..count {
+        let entry = NEW_HEADER_SIZE.checked_add(index.checked_mul(NEW_ENTRY_SIZE)?)?;
+        let flags = read_u32(data, entry, big_endian)?;
+        // Keys and values are offsets from the start of the new-format header
+        let key = read_u32(data, entry + 4, big_endian)? as usize;
+        let value = read_u32(data, entry + 8, big_endian)? as usize;
+        let hwcap = read_u64(data, entry + 16, big_endian)?;
+        entries.push(CacheEntry {
+            soname: read_str(data, key)?.to_owned(),
+            path: PathBuf::from(read_str(data, value)?),
+            flags,
+            hwcap,
+        });
+    }
+    Some(LdSoCache { entries })
+}
+
+fn read_u32(data: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+fn read_u64(data: &[u8], offset: usize, big_endian: bool) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?.try_into().ok()?;
+    Some(if big_endian {
+        u64::from_be_bytes(bytes)
+    } else {
+        u64::from_le_bytes(bytes)
+    })
+}
+
+fn read_str(data: &[u8], offset: usize) -> Option<&str> {
+    crate::parse::u8_slice_to_str(data.get(offset..)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LdSoCache;
+    use std::path::Path;
+
+    /// Builds an old-format cache, optionally followed by a new-format table.
+    fn old_cache(entries: &[(&str, &str)], new_table: Option<&[u8]>) -> Vec<u8> {
+        let mut data = b"ld.so-1.7.0\0".to_vec();
+        data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        let mut strings = Vec::new();
+        for (soname, path) in entries {
+            data.extend_from_slice(&1u32.to_le_bytes());
+            data.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+            strings.extend_from_slice(soname.as_bytes());
+            strings.push(0);
+            data.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+            strings.extend_from_slice(path.as_bytes());
+            strings.push(0);
+        }
+        if let Some(new_table) = new_table {
+            data.resize(data.len().next_multiple_of(8), 0);
+            data.extend_from_slice(new_table);
+        } else {
+            data.extend_from_slice(&strings);
+        }
+        data
+    }
+
+    fn new_cache(entries: &[(&str, &str, u64)]) -> Vec<u8> {
+        let mut data = b"glibc-ld.so.cache1.1".to_vec();
+        data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        data.resize(48, 0);
+        let mut strings = Vec::new();
+        let strings_start = 48 + entries.len() * 24;
+        for (soname, path, hwcap) in entries {
+            data.extend_from_slice(&0x0303u32.to_le_bytes());
+            data.extend_from_slice(&((strings_start + strings.len()) as u32).to_le_bytes());
+            strings.extend_from_slice(soname.as_bytes());
+            strings.push(0);
+            data.extend_from_slice(&((strings_start + strings.len()) as u32).to_le_bytes());
+            strings.extend_from_slice(path.as_bytes());
+            strings.push(0);
+            data.extend_from_slice(&0u32.to_le_bytes());
+            data.extend_from_slice(&hwcap.to_le_bytes());
+        }
+        data.extend_from_slice(&strings);
+        data
+    }
+
+    #[test]
+    fn test_old_format() {
+        let cache =
+            LdSoCache::parse(&old_cache(&[("libfoo.so.1", "/opt/lib/libfoo.so.1")], None)).unwrap();
+        assert_eq!(
+            cache.lookup("libfoo.so.1").collect::<Vec<_>>(),
+            [Path::new("/opt/lib/libfoo.so.1")]
+        );
+        assert_eq!(cache.lookup("libbar.so.1").count(), 0);
+    }
+
+    #[test]
+    fn test_new_format() {
+        let data = new_cache(&[
+            (
+                "libfoo.so.1",
+                "/usr/lib/glibc-hwcaps/x86-64-v3/libfoo.so.1",
+                1 << 62,
+            ),
+            ("libfoo.so.1", "/opt/lib/libfoo.so.1", 0),
+            ("libfoo.so.1", "/usr/lib/libfoo.so.1", 0),
+        ]);
+        let cache = LdSoCache::parse(&data).unwrap();
+        let paths: Vec<_> = cache.lookup("libfoo.so.1").collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("/opt/lib/libfoo.so.1"),
+                Path::new("/usr/lib/libfoo.so.1")
+            ]
+        );
+
+        // The same table embedded after an (empty) old-format one
+        let compat = LdSoCache::parse(&old_cache(&[], Some(&data))).unwrap();
+        assert_eq!(compat.entries(), cache.entries());
+    }
+
+    #[test]
+    fn test_system_cache() {
+        let cache = LdSoCache::load(super::LD_SO_CACHE_PATH).unwrap();
+        assert!(cache
+            .lookup("libc.so.6")
+            .any(|path| path.ends_with("libc.so.6")));
+    }
+
+    #[test]
+    fn test_truncated_cache() {
+        let mut data = new_cache(&[("libfoo.so.1", "/opt/lib/libfoo.so.1", 0)]);
+        data.truncate(60);
+        assert!(LdSoCache::parse(&data).is_none());
+        assert!(LdSoCache::parse(b"not a cache").is_none());
+        // An entry count far beyond the end of the data
+        let mut data = old_cache(&[], None);
+        data[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(LdSoCache::parse(&data).is_none());
+    }
+}
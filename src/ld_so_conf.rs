@@ -0,0 +1,248 @@
+//! Parser for `/etc/ld.so.conf`, the list of directories `ldconfig` builds its cache from.
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::filesystem::{FileSystem, HostFs};
+use crate::{sysroot, Error};
+
+/// The default location of the configuration file.
+pub const LD_SO_CONF_PATH: &str = "/etc/ld.so.conf";
+
+/// The library directories declared by an `ld.so.conf` file and the files it includes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LdSoConf {
+    dirs: Vec<PathBuf>,
+    /// The files read, or tried, and the directories wildcards were expanded in.
+    sources: Vec<PathBuf>,
+}
+
+impl LdSoConf {
+    /// Reads the configuration file at `path`, following its `include` directives. Included
+    /// files that do not exist or cannot be read are skipped, as `ldconfig` does.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::load_in_sysroot("/", path)
+    }
+
+    /// Reads the configuration file at `path` inside the root filesystem `sysroot`, such as
+    /// [`LD_SO_CONF_PATH`]. Absolute `include` patterns are looked up inside `sysroot` too, while
+    /// the declared directories are returned as written, relative to `sysroot`.
+    pub fn load_in_sysroot<R, P>(sysroot: R, path: P) -> Result<Self, Error>
+    where
+        R: AsRef<Path>,
+        P: AsRef<Path>,
+    {
+        Self::load_in(&HostFs, sysroot.as_ref(), path.as_ref())
+    }
+
+    /// Like [`LdSoConf::load_in_sysroot`], but reads the files from `fs`.
+    pub(crate) fn load_in(fs: &dyn FileSystem, root: &Path, path: &Path) -> Result<Self, Error> {
+        let path = &in_root(fs, root, &sysroot::rebase(root, path));
+        let contents = read_to_string(fs, path).map_err(|source| Error::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let mut conf = LdSoConf {
+            sources: vec![path.to_owned()],
+            ..LdSoConf::default()
+        };
+        let mut visited = HashSet::from([path.to_owned()]);
+        conf.parse(fs, root, path, &contents, &mut visited);
+        Ok(conf)
+    }
+
+    /// The declared directories, in file order with duplicates removed.
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// The files and directories the declared directories were read from: a change to any of
+    /// them may change the declared directories.
+    pub(crate) fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    fn parse(
+        &mut self,
+        fs: &dyn FileSystem,
+        root: &Path,
+        path: &Path,
+        contents: &str,
+        visited: &mut HashSet<PathBuf>,
+    ) {
+        for line in contents.lines() {
+            // Strip comments
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if let Some(patterns) = line
+                .strip_prefix("include")
+                .filter(|rest| rest.starts_with(char::is_whitespace))
+            {
+                for pattern in patterns.split_whitespace() {
+                    // Relative patterns are relative to the including file's directory
+                    let pattern = match Path::new(pattern) {
+                        pattern if pattern.is_absolute() => sysroot::rebase(root, pattern),
+                        pattern => path.parent().unwrap_or(root).join(pattern),
+                    };
+                    let dir = in_root(fs, root, pattern.parent().unwrap_or(root));
+                    let pattern = dir.join(pattern.file_name().unwrap_or_default());
+                    if has_wildcards(&pattern) {
+                        self.sources.push(dir);
+                    }
+                    let included = expand_glob(fs, &pattern);
+                    for included in included.iter().map(|included| in_root(fs, root, included)) {
+                        if !visited.insert(included.clone()) {
+                            continue;
+                        }
+                        self.sources.push(included.clone());
+                        if let Ok(contents) = read_to_string(fs, &included) {
+                            self.parse(fs, root, &included, &contents, visited);
+                        }
+                    }
+                }
+            } else if line.starts_with("hwcap") {
+                // Legacy hardware capability declarations don't name directories
+            } else {
+                for dir in line
+                    .split([' ', '\t', ',', ':'])
+                    .filter(|dir| !dir.is_empty())
+                {
+                    // Old-style "dir=type" entries carry a library type suffix
+                    let dir = PathBuf::from(dir.split('=').next().unwrap_or(dir));
+                    if !self.dirs.contains(&dir) {
+                        self.dirs.push(dir);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The file at `path`, which lies inside `root`, with the symlinks along it followed inside
+/// `root`, so that a sysroot's configuration never includes the host's. `path` itself if it
+/// does not exist.
+fn in_root(fs: &dyn FileSystem, root: &Path, path: &Path) -> PathBuf {
+    sysroot::resolve_in(fs, root, path).unwrap_or_else(|| path.to_owned())
+}
+
+/// Expands `*` and `?` wildcards in the final component of `pattern`, returning matching paths
+/// in sorted order. Patterns without wildcards are returned as-is.
+fn expand_glob(fs: &dyn FileSystem, pattern: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (pattern.parent(), pattern.file_name()) else {
+        return Vec::new();
+    };
+    if !has_wildcards(pattern) {
+        return vec![pattern.to_owned()];
+    }
+    let name = name.as_encoded_bytes();
+    let Ok(entries) = fs.read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut matches: Vec<PathBuf> = entries
+        .into_iter()
+        .filter(|entry| wildcard_match(name, entry.as_encoded_bytes()))
+        .map(|entry| dir.join(entry))
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Whether the final component of `pattern` has `*` or `?` wildcards.
+fn has_wildcards(pattern: &Path) -> bool {
+    let name = pattern.file_name().unwrap_or_default().as_encoded_bytes();
+    name.iter().any(|&b| b == b'*' || b == b'?')
+}
+
+/// The contents of the text file at `path` in `fs`.
+fn read_to_string(fs: &dyn FileSystem, path: &Path) -> io::Result<String> {
+    String::from_utf8(fs.read(path)?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any bytes and `?` any single one.
+pub(crate) fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LdSoConf;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("ld.so.conf.d");
+        fs::create_dir(&conf_d).unwrap();
+        fs::write(conf_d.join("b.conf"), "/opt/b/lib\n").unwrap();
+        fs::write(
+            conf_d.join("a.conf"),
+            "# CUDA\n/opt/cuda/lib64 /opt/a/lib\n",
+        )
+        .unwrap();
+        fs::write(conf_d.join("ignored.txt"), "/opt/ignored\n").unwrap();
+        // Relative include, plus a cycle back to the top-level file
+        fs::write(conf_d.join("c.conf"), "include ../ld.so.conf\n").unwrap();
+        let conf = dir.path().join("ld.so.conf");
+        fs::write(
+            &conf,
+            "/usr/local/lib\ninclude ld.so.conf.d/*.conf\nhwcap 0 nosegneg\n/usr/lib=libc6\n",
+        )
+        .unwrap();
+
+        let conf = LdSoConf::load(&conf).unwrap();
+        let expected: Vec<PathBuf> = [
+            "/usr/local/lib",
+            "/opt/cuda/lib64",
+            "/opt/a/lib",
+            "/opt/b/lib",
+            "/usr/lib",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(conf.dirs(), expected);
+    }
+
+    #[test]
+    fn test_absolute_include_in_sysroot() {
+        let root = tempfile::tempdir().unwrap();
+        let conf_d = root.path().join("etc/ld.so.conf.d");
+        fs::create_dir_all(&conf_d).unwrap();
+        fs::write(conf_d.join("vendor.conf"), "/opt/vendor/lib\n").unwrap();
+        fs::write(
+            root.path().join("etc/ld.so.conf"),
+            "include /etc/ld.so.conf.d/*.conf\n",
+        )
+        .unwrap();
+
+        let conf = LdSoConf::load_in_sysroot(root.path(), "/etc/ld.so.conf").unwrap();
+        assert_eq!(conf.dirs(), [PathBuf::from("/opt/vendor/lib")]);
+    }
+
+    #[test]
+    fn test_symlinks_in_sysroot() {
+        // Absolute symlinks, which would lead to the host's files if followed there
+        let root = tempfile::tempdir().unwrap();
+        let usr_etc = root.path().join("usr/etc");
+        fs::create_dir_all(usr_etc.join("ld.so.conf.d")).unwrap();
+        fs::write(usr_etc.join("ld.so.conf"), "include /etc/ld.so.conf.d/*.conf\n").unwrap();
+        fs::write(usr_etc.join("ld.so.conf.d/vendor.conf"), "/opt/vendor/lib\n").unwrap();
+        fs::create_dir(root.path().join("etc")).unwrap();
+        symlink("/usr/etc/ld.so.conf", root.path().join("etc/ld.so.conf")).unwrap();
+        symlink("/usr/etc/ld.so.conf.d", root.path().join("etc/ld.so.conf.d")).unwrap();
+
+        let conf = LdSoConf::load_in_sysroot(root.path(), "/etc/ld.so.conf").unwrap();
+        assert_eq!(conf.dirs(), [PathBuf::from("/opt/vendor/lib")]);
+    }
+}
@@ -1,179 +1,1603 @@
 //! # What is this?
 //! This is a library crate designed to function like `ldd`.
+use std::ffi::{OsStr, OsString};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::path::{PathBuf, Path};
 
-use elf::ElfBytes;
-use elf::endian::AnyEndian;
-use elf::abi::{DT_NEEDED, DT_RUNPATH, DT_RPATH};
-use elf::file::Class::*;
-use std::fs;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
+use std::fs::File;
+use std::sync::Arc;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use parse::ParsedObject;
+use resolver::{Executable, LoadedObject, SearchOutcome, SearchRecord};
+use symbols::DynamicSymbols;
+
+#[cfg(not(any(feature = "elf", feature = "goblin")))]
+compile_error!("one of the `elf` and `goblin` features must be enabled to parse ELF files");
+
+// The event macros must be defined before the modules using them
+#[macro_use]
+mod trace;
+
+mod abi;
+mod android;
+mod appimage;
+mod archive_fs;
+#[cfg(feature = "tokio")]
+mod async_io;
+mod audit;
+mod batch;
+mod build_check;
+mod bundle;
+mod config;
+#[cfg(unix)]
+mod coredump;
+mod cpio;
+mod csv;
+#[cfg(all(unix, feature = "daemon"))]
+mod daemon;
+mod debuginfo;
+#[cfg(feature = "demangle")]
+mod demangle;
+mod diff;
+mod digest;
+#[cfg(feature = "openat")]
+mod dir_fs;
+mod disk_cache;
+mod dlopen;
+mod dlopen_note;
+mod dst;
+mod dynamic_flags;
+mod dynamic_info;
+#[cfg(all(feature = "elf", any(not(feature = "goblin"), test)))]
+mod elf_backend;
+mod error;
+#[cfg(all(unix, feature = "ffi"))]
+pub mod ffi;
+mod filesystem;
+mod flatpak;
+mod format;
+mod freebsd;
+#[cfg(feature = "goblin")]
+mod goblin_backend;
+mod graph;
+mod gzip;
+mod hardening;
+mod header;
+mod hwcaps;
+mod incremental;
+mod iter;
+mod kmod;
+mod ld_so_cache;
+mod ld_so_conf;
+mod lint;
+mod lockfile;
+mod macho;
+mod metrics;
+mod mount_fs;
+mod musl;
+#[cfg(feature = "ndjson")]
+mod ndjson;
+mod object;
+#[cfg(feature = "oci")]
+mod oci;
+mod os_str;
+mod package_set;
+mod packages;
+mod parse;
+mod patch;
+mod pe;
+mod portability;
+mod preload;
+mod process;
+#[cfg(feature = "python")]
+mod python;
+mod removal;
+mod resolver;
+mod reverse_index;
+mod rootfs;
+mod runpath_lint;
+#[cfg(feature = "sbom")]
+mod sbom;
+mod search_trace;
+#[cfg(feature = "serde")]
+mod serde_path;
+mod sftp_fs;
+mod sqlite;
+mod squashfs;
+mod stats;
+mod store;
+mod symbols;
+mod sysroot;
+mod tar;
+mod target;
+#[cfg(all(unix, feature = "watch"))]
+mod watch;
+mod xz;
+mod zstd;
+
+pub use appimage::AppImage;
+pub use audit::AuditReport;
+pub use batch::{BatchAnalysis, MergedClosure, SharedLibrary, SharingStats};
+pub use build_check::{BuildCheck, BuildProblem};
+pub use bundle::{BundleMode, RunpathRewrite};
+pub use config::{OsAbiCheck, Platform, ResolverConfig, ResourceLimit};
+#[cfg(unix)]
+pub use coredump::{CoreFile, CoreLibrary, MappedRange};
+#[cfg(all(unix, feature = "daemon"))]
+pub use daemon::Daemon;
+pub use debuginfo::{DebugInfo, DebugInfoLocator};
+#[cfg(feature = "demangle")]
+pub use demangle::demangle;
+pub use diff::{ChangedLibrary, ClosureDiff};
+pub use digest::ContentDigest;
+#[cfg(feature = "openat")]
+pub use dir_fs::DirFs;
+pub use disk_cache::DiskCache;
+pub use dlopen::DlopenCandidate;
+pub use dlopen_note::DlopenPriority;
+pub use dynamic_flags::{DynamicFlags, DynamicFlags1};
+pub use dynamic_info::DynamicInfo;
+pub use error::{Error, ParseError, ResolveError};
+pub use filesystem::{FileKind, FileMetadata, FileStat, FileSystem, HostFs};
+pub use flatpak::{FlatpakSandbox, FlatpakSource};
+pub use graph::{Candidate, DependencyGraph, DependencyKind, DependencyNode, NodeId, SearchRule};
+pub use hardening::{Hardening, HardeningReport, Pie, Relro};
+pub use header::{ElfClass, ElfHeader, Endianness, Machine, OsAbiKind};
+pub use iter::{
+    Dependencies, Dependency, DependencyVisitor, TraversalDecision, TraversalOrder, VisitControl,
+};
+pub use kmod::{KernelModule, ModInfo};
+pub use ld_so_cache::{CacheEntry, LdSoCache, LD_SO_CACHE_PATH};
+pub use ld_so_conf::{LdSoConf, LD_SO_CONF_PATH};
+pub use lint::{Lint, LintKind};
+pub use lockfile::{LockDrift, LockedLibrary, Lockfile};
+pub use macho::MachOFile;
+pub use metrics::ResolutionMetrics;
+#[cfg(feature = "oci")]
+pub use oci::OciImage;
+pub use package_set::PackageSet;
+pub use packages::PackageDb;
+pub use parse::ElfType;
+pub use pe::PeFile;
+pub use portability::{DifferingLibrary, PortabilityReport};
+pub use preload::LD_SO_PRELOAD_PATH;
+pub use process::{MappedLibrary, ProcessLibs, RuntimeComparison};
+pub use removal::RemovalImpact;
+pub use resolver::Resolver;
+pub use reverse_index::ReverseIndex;
+pub use rootfs::RootfsArchive;
+pub use runpath_lint::{RunpathFinding, RunpathIssue, RunpathTag};
+#[cfg(feature = "sbom")]
+pub use sbom::SbomFormat;
+pub use search_trace::{ProbeResult, Rejection, RejectionReason, SearchPathEntry, SearchStep};
+pub use sftp_fs::SftpFs;
+pub use stats::ClosureStats;
+pub use store::{StorePath, GUIX_STORE_DIR, NIX_STORE_DIR};
+pub use symbols::{
+    ExportedSymbol, ExportedSymbols, GlibcRequirement, LibstdcxxRequirements, MissingVersion,
+    SymbolBinding, SymbolConflict, SymbolProvider, UndefinedSymbol, UnusedDependency,
+    WeakDependency,
+};
+pub use target::Target;
+#[cfg(all(unix, feature = "watch"))]
+pub use watch::{WatchEvent, Watcher};
+
+#[cfg(test)]
+mod test_util;
+
+/// The outcome of resolving a single needed soname.
+pub type Resolution = Result<Resolved, ResolveError>;
+
+/// What a needed soname resolved to, when it did not fail to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Resolved {
+    /// The library was found at this path.
+    Found(#[cfg_attr(feature = "serde", serde(with = "crate::serde_path"))] PathBuf),
+    /// The object is provided by the kernel and never exists on disk, like the vDSO, see
+    /// [`DependencyKind::Virtual`].
+    Virtual,
+    /// The library was not searched for, as its soname is
+    /// [ignored](ResolverConfig::ignore_sonames).
+    Ignored,
+}
+
+impl Resolved {
+    /// The path the library was found at, if it is on disk.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Resolved::Found(path) => Some(path),
+            Resolved::Virtual | Resolved::Ignored => None,
+        }
+    }
+
+    /// Like [`Resolved::path`], taking ownership.
+    pub fn into_path(self) -> Option<PathBuf> {
+        match self {
+            Resolved::Found(path) => Some(path),
+            Resolved::Virtual | Resolved::Ignored => None,
+        }
+    }
+}
 
 /// Represents an ELF file on disk and provides the method [`ElfFile::get_libs_full_paths`] to
 /// recursively get ELF-header-declared shared-library dependencies.
 pub struct ElfFile {
     path: PathBuf,
+    resolver: Arc<Resolver>,
+    /// The contents of the file, if it was given in memory rather than read from `path`.
+    contents: Option<Arc<[u8]>>,
 }
 
 impl ElfFile {
     /// Creates an [`ElfFile`] instance from [`AsRef<Path>`]
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        ElfFile::with_config(path, ResolverConfig::default())
+    }
+
+    /// Creates an [`ElfFile`] instance that resolves dependencies according to `config`
+    pub fn with_config<P: AsRef<Path>>(path: P, config: ResolverConfig) -> Self {
+        ElfFile::with_resolver(path, Arc::new(Resolver::new(config)))
+    }
+
+    /// Creates an [`ElfFile`] instance that resolves dependencies with `resolver`, which may
+    /// be shared with other instances to avoid reading the same libraries again
+    pub fn with_resolver<P: AsRef<Path>>(path: P, resolver: Arc<Resolver>) -> Self {
         let path = path.as_ref().to_owned();
-        Self { path }
+        Self {
+            path,
+            resolver,
+            contents: None,
+        }
+    }
+
+    /// Creates an [`ElfFile`] instance for the ELF file `data` holds, such as an archive member,
+    /// the contents of a memfd or a downloaded blob, whose dependencies are resolved according
+    /// to `config` as for a file at `path`. Nothing is read from `path`: it stands for the file
+    /// in the dependency graph, and gives the directory `$ORIGIN` expands to.
+    pub fn from_bytes<P, D>(path: P, data: D, config: ResolverConfig) -> Self
+    where
+        P: AsRef<Path>,
+        D: Into<Arc<[u8]>>,
+    {
+        Self {
+            contents: Some(data.into()),
+            ..ElfFile::with_config(path, config)
+        }
+    }
+
+    /// Like [`ElfFile::from_bytes`], for the ELF file `reader` reads from its start.
+    /// # Return Value [Err]
+    /// [`Error::Io`], naming `path`, if `reader` can't be read, or [`Error::LimitExceeded`] if
+    /// it reads more than the [maximum file size](ResolverConfig::max_file_size), which it
+    /// stops reading at.
+    pub fn from_reader<P, R>(path: P, mut reader: R, config: ResolverConfig) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        R: Read + Seek,
+    {
+        let io_error = |source| Error::Io {
+            path: path.as_ref().to_owned(),
+            source,
+        };
+        reader.seek(SeekFrom::Start(0)).map_err(io_error)?;
+        let mut data = Vec::new();
+        // One byte past the limit tells a file over it from one right at it
+        let limit = config.max_file_size.map_or(u64::MAX, |max| max.saturating_add(1));
+        reader.take(limit).read_to_end(&mut data).map_err(io_error)?;
+        config.check_file_size(path.as_ref(), data.len() as u64)?;
+        Ok(ElfFile::from_bytes(path, data, config))
+    }
+
+    /// Like [`ElfFile::from_reader`], for the ELF file `file` has open, such as one passed to a
+    /// sandboxed process that cannot open `path` itself. Its libraries are still looked up
+    /// by path, unless `config` reads files from elsewhere, like a `DirFs` of the `openat`
+    /// feature over directories opened beforehand.
+    /// # Return Value [Err]
+    /// As for [`ElfFile::from_reader`].
+    pub fn from_file<P>(path: P, file: File, config: ResolverConfig) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        ElfFile::from_reader(path, file, config)
+    }
+
+    /// The work done to analyze this file so far, as counted by its [`Resolver`]: with a
+    /// resolver shared through [`ElfFile::with_resolver`], the work of every analysis it did.
+    pub fn metrics(&self) -> ResolutionMetrics {
+        self.resolver.metrics()
     }
 
-    /// # Return Value [None]
-    /// A return value of [`Option::None`] means some library was not found for some reason 
-    /// ## TODO: Make return type `Vec<(String, Result<PathBuf, CustomErrorTypeOrJustStr>)>`).
-    /// # Return Value [Some]
-    /// The value contained in the returned [`Option::Some`] is a vector of the paths to all found
+    /// # Return Value [Err]
+    /// A return value of [`Result::Err`] describes why the dependencies could not be collected,
+    /// e.g. [`Error::Unresolved`] when some library was not found in any search directory. Use
+    /// [`ElfFile::get_libs_resolutions`] to keep going past missing libraries.
+    /// # Return Value [Ok]
+    /// The value contained in the returned [`Result::Ok`] is a vector of the paths to all found
     /// shared-library dependencies on disk.
     /// # Paths Searched
+    /// In the same order as the dynamic linker:
+    /// - ELF `RPATH`, unless the file also has a `RUNPATH`
     /// - All valid directories in `LD_LIBRARY_PATH` environment variable
-    /// - ELF `RPATH`
     /// - ELF `RUNPATH`
+    /// - Directories added with [`ResolverConfig::append_search_dir`]
+    /// - Libraries registered in `/etc/ld.so.cache`
+    /// - Directories listed in `/etc/ld.so.conf` and the files it includes
     /// - `/usr/lib`
-    /// - `/lib64`
-    /// - `/lib/x86_64-linux-gnu`
+    /// - `/lib64` (`/lib32` for 32-bit files)
+    /// - The `/lib` multiarch directory of the file's architecture, e.g. `/lib/x86_64-linux-gnu`
     /// - `/lib`
-    /// - `/usr/lib64`
-    pub fn get_libs_full_paths(&self) -> Option<Vec<PathBuf>> {
-        let mut seen_libs = HashSet::new();
-        let mut lib_paths = Vec::new();
-        // Add the initial path to seen_libs
-        seen_libs.insert(self.path.clone());
-        ElfFile::collect_libs(&self.path, &mut seen_libs, &mut lib_paths)
-            .map(|_| lib_paths)
-    }
-
-    fn collect_libs(
-        path: &Path,
-        seen_libs: &mut HashSet<PathBuf>,
-        lib_paths: &mut Vec<PathBuf>,
-    ) -> Option<()> {
-        // Read the ELF file
-        let elf_file_data = fs::read(path).expect("Could not read ELF file with std::fs::read");
-        let elf = ElfBytes::<AnyEndian>::minimal_parse(elf_file_data.as_slice())
-            .expect("ElfBytes::minimal_parse failed");
-
-        // Determine if ELF file is 32-bit or 64-bit
-        let is_64_bit = match elf.ehdr.class {
-            ELF64 => true,
-            ELF32 => false,
-        };
-
-        // First, get the slice of bytes for the `.dynstr` section (which `.dynamic` will index)
-        let elf_dynstr_header = elf
-            .section_header_by_name(".dynstr")
-            .expect("Error parsing ELF header")
-            .expect("The ELF file should have a \".dynstr\" section");
-        let dynstr_offset = elf_dynstr_header.sh_offset as usize;
-        let dynstr_size = elf_dynstr_header.sh_size as usize;
-        let dynstr_bytes = &elf_file_data[dynstr_offset..(dynstr_offset + dynstr_size)];
-
-        // Directories in which to search for libraries
-        let mut search_dirs: Vec<PathBuf> = [
-            "/usr/lib",
-            "/lib64",
-            "/lib/x86_64-linux-gnu",
-            "/lib",
-            "/usr/lib64",
-        ]
-        .into_iter()
-        .map(PathBuf::from)
-        .collect();
-
-        if let Ok(ld_library_path_var) = env::var("LD_LIBRARY_PATH") {
-            for lib_path_str in ld_library_path_var.split(':') {
-                let lib_path = PathBuf::from(lib_path_str);
-                if lib_path.exists() {
-                    search_dirs.push(lib_path);
+    /// - `/usr/lib64` (`/usr/lib32` for 32-bit files)
+    /// - The `/usr/lib` multiarch directory, e.g. `/usr/lib/x86_64-linux-gnu`
+    ///
+    /// With [`ResolverConfig::sysroot`], everything but `LD_LIBRARY_PATH` and the custom
+    /// directories is searched inside the sysroot. For set-user-ID and set-group-ID files,
+    /// `LD_LIBRARY_PATH` is skipped (see [`ResolverConfig::secure_execution`]). The dependencies
+    /// of objects linked with `-z nodefaultlib` are not searched for in the `ld.so.cache`, the
+    /// `ld.so.conf` directories or the default directories. Each directory is searched after
+    /// its `glibc-hwcaps` subdirectories (see [`ResolverConfig::hwcaps`]).
+    pub fn get_libs_full_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        let libs = self.get_libs_with_sonames()?;
+        Ok(libs.into_iter().map(|(_, path)| path).collect())
+    }
+
+    /// Like [`ElfFile::get_libs_full_paths`], but pairs each path with the `DT_NEEDED` soname it
+    /// was found for, in the same order.
+    pub fn get_libs_with_sonames(&self) -> Result<Vec<(String, PathBuf)>, Error> {
+        self.get_libs_resolutions()?
+            .into_iter()
+            .filter_map(|(soname, resolution)| match resolution {
+                Ok(resolved) => resolved.into_path().map(|path| Ok((soname, path))),
+                Err(error) => Some(Err(error.into_error(soname))),
+            })
+            .collect()
+    }
+
+    /// Like [`ElfFile::get_libs_full_paths`], but keeps going when a library is missing.
+    /// # Return Value [Ok]
+    /// Every needed soname in the order it was encountered, paired with either the path it
+    /// resolved to or the reason it could not be resolved. Each resolved library appears once
+    /// and is itself searched for dependencies.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn get_libs_resolutions(&self) -> Result<Vec<(String, Resolution)>, Error> {
+        let nodes = self.dependency_graph()?.into_nodes();
+        Ok(nodes.into_iter().skip(1).map(|node| (node.soname, node.resolution)).collect())
+    }
+
+    /// Whether this file is statically linked, i.e. has no dynamic section. Statically linked
+    /// files simply report no dependencies from the other methods. Note that static-pie
+    /// executables keep a dynamic section for self-relocation, and so are not reported as
+    /// static here even though they need no libraries either.
+    pub fn is_statically_linked(&self) -> Result<bool, Error> {
+        self.parse_loadable().map(|object| object.is_static)
+    }
+
+    /// The type of this file from its ELF header, like [`ElfType::Executable`], which unlike the
+    /// other methods also works on files the dynamic linker doesn't load, such as relocatable
+    /// objects and core dumps. Those fail everywhere else with [`Error::NotLoadable`].
+    pub fn elf_type(&self) -> Result<ElfType, Error> {
+        self.parse().map(|object| object.elf_type)
+    }
+
+    /// What the ELF header of this file says it is and what it is built for: its class, byte
+    /// order, architecture, type and OS ABI, and whether it is position-independent. Like
+    /// [`ElfFile::elf_type`], this also works on files the dynamic linker doesn't load. See
+    /// [`DependencyNode::header`] for those of its libraries.
+    pub fn header(&self) -> Result<ElfHeader, Error> {
+        self.parse().map(|object| ElfHeader::of(&object))
+    }
+
+    /// The program interpreter (dynamic linker) this file asks for in its `PT_INTERP` segment,
+    /// such as `/lib64/ld-linux-x86-64.so.2`. Shared libraries usually have none.
+    pub fn interpreter(&self) -> Result<Option<PathBuf>, Error> {
+        let object = self.parse_loadable()?;
+        Ok(object.interpreter.as_ref().map(PathBuf::from))
+    }
+
+    /// The raw `DT_NEEDED` entries of this file, in order. Only the file itself is read, so this
+    /// works even when the libraries aren't installed on the analyzing machine. Bytes that are
+    /// not UTF-8 are replaced with `U+FFFD`.
+    pub fn needed_sonames(&self) -> Result<Vec<String>, Error> {
+        let object = self.parse_loadable()?;
+        Ok(object.needed.iter().map(|soname| soname.to_string_lossy().into_owned()).collect())
+    }
+
+    /// The raw `DT_NEEDED`, `DT_RPATH`, `DT_RUNPATH` and `DT_SONAME` entries of this file, as
+    /// the linker wrote them. Like [`ElfFile::needed_sonames`], only the file itself is read. See
+    /// [`DependencyNode::dynamic`] for those of its libraries.
+    pub fn dynamic_info(&self) -> Result<DynamicInfo, Error> {
+        self.parse_loadable().map(|object| DynamicInfo::of(&object))
+    }
+
+    /// Like [`ElfFile::get_libs_resolutions`], but resolves only this file's own `DT_NEEDED`
+    /// entries, in order, without looking into the dependencies of each library.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn direct_dependencies(&self) -> Result<Vec<(String, Resolution)>, Error> {
+        // Without recursion, the graph holds the root followed by its own dependencies
+        let nodes = self.build_graph(false)?.into_nodes();
+        Ok(nodes.into_iter().skip(1).map(|node| (node.soname, node.resolution)).collect())
+    }
+
+    /// Lazily walks the dependency closure of this file in the given `order`, reading each
+    /// library only once the iterator reaches it.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn iter_dependencies(&self, order: TraversalOrder) -> Result<Dependencies, Error> {
+        let root = self.load()?;
+        let mut dependencies = Dependencies::new(self.resolver.clone(), &self.path, root, order);
+        dependencies.bytes_parsed = self.file_size(&self.path).unwrap_or_default();
+        Ok(dependencies)
+    }
+
+    /// Walks the dependency closure of this file depth-first, calling `visitor` for each needed
+    /// library. The visitor decides whether to go into each library's own dependencies and
+    /// whether to go on at all.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn walk(&self, visitor: &mut impl DependencyVisitor) -> Result<(), Error> {
+        let mut dependencies = self.iter_dependencies(TraversalOrder::DepthFirst)?;
+        while let Some(dependency) = dependencies.next() {
+            match visitor.visit(&dependency) {
+                VisitControl::Continue => {}
+                VisitControl::SkipDependencies => dependencies.skip_dependencies(),
+                VisitControl::Stop => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// The dependency cycles in the closure of this file, as the paths of their members, see
+    /// [`DependencyGraph::cycles`]. ld.so loads each library of a cycle once, so cycles are
+    /// not an error, but they make initialization order depend on which member is reached first.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn dependency_cycles(&self) -> Result<Vec<Vec<PathBuf>>, Error> {
+        let graph = self.dependency_graph()?;
+        let path = |&id: &NodeId| graph.node(id).path().map(Path::to_path_buf);
+        Ok(graph.cycles().iter().map(|cycle| cycle.iter().filter_map(path).collect()).collect())
+    }
+
+    /// The sonames that resolve to more than one file in the closure of this file, each with
+    /// those files, see [`DependencyGraph::soname_conflicts`]. Paths that lead to the same file
+    /// count as one, even without [`ResolverConfig::canonicalize_paths`].
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn soname_conflicts(&self) -> Result<Vec<(String, Vec<PathBuf>)>, Error> {
+        let graph = self.dependency_graph()?;
+        let config = self.resolver.config();
+        let mut conflicts = Vec::new();
+        for nodes in graph.soname_conflicts() {
+            let mut files = HashSet::new();
+            let paths: Vec<PathBuf> = nodes
+                .iter()
+                .filter_map(|&id| graph.node(id).path())
+                .filter(|path| files.insert(config.canonical(path)))
+                .map(Path::to_path_buf)
+                .collect();
+            if paths.len() > 1 {
+                conflicts.push((graph.node(nodes[0]).soname.clone(), paths));
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// Copies this file, every library in its closure and its program interpreter into `dir`,
+    /// each at the path it has on this system below `dir`, or in the sysroot with
+    /// [`ResolverConfig::sysroot`]. The symlinks leading to each file come along, like
+    /// `libfoo.so.1` pointing to `libfoo.so.1.2.3` or `/lib` pointing to `usr/lib`, with
+    /// absolute ones made relative so that `dir` can be moved. Using `dir` as a sysroot, a
+    /// chroot or the root of a container image then finds the same libraries, except those
+    /// found through `LD_LIBRARY_PATH` or the custom search directories, or through the
+    /// `ld.so.cache` entries of directories ld.so does not search by default. Files already in
+    /// `dir` are kept. [Ignored](ResolverConfig::ignore_sonames) libraries are left out, as is
+    /// the interpreter if its file name is ignored.
+    /// # Return Value [Err]
+    /// Returned before anything is copied if the file cannot be analyzed, or with
+    /// [`Error::Unresolved`] if a library or the interpreter cannot be found. Returned with
+    /// [`Error::Write`] if a file cannot be put into `dir`.
+    pub fn bundle_to<P: AsRef<Path>>(&self, dir: P, mode: BundleMode) -> Result<(), Error> {
+        let config = self.resolver.config();
+        let graph = self.bundle_graph()?;
+        let is_ignored = |path: &Path| path.file_name().is_some_and(|name| config.is_ignored(name));
+        let interpreter = graph.interpreter().filter(|path| !is_ignored(path));
+        let interpreter = interpreter.map(Path::to_path_buf);
+        let mut files = Vec::new();
+        for node in graph.into_nodes() {
+            match node.resolution {
+                Ok(resolved) => files.extend(resolved.into_path()),
+                Err(error) => return Err(error.into_error(node.soname)),
+            }
+        }
+        if let Some(interpreter) = interpreter {
+            // Like any other needed file, the interpreter is looked for in the sysroot
+            let path = config.rebase(&interpreter);
+            if self.resolver.locate(&path).is_none() {
+                let soname = interpreter.to_string_lossy().into_owned();
+                return Err(Error::Unresolved { soname, needed_by: self.path.clone() });
+            }
+            files.push(path);
+        }
+
+        for path in files {
+            let path = std::path::absolute(&path).unwrap_or(path);
+            bundle::place(bundle::bundle_root(config, &path), &path, dir.as_ref(), mode)?;
+        }
+        Ok(())
+    }
+
+    /// Plans the `RUNPATH` each file copied by [`ElfFile::bundle_to`] needs to find its
+    /// libraries inside the bundle wherever it is moved, like `$ORIGIN/../lib`, so it runs
+    /// without a chroot or sysroot. Each file with libraries to search for gets the directories
+    /// they are in, relative to its own, in load order. Libraries named by a path and the
+    /// program interpreter, whose `PT_INTERP` stays absolute, are not covered. Feed each entry
+    /// to `patchelf --set-rpath` or write it into the bundle with [`RunpathRewrite::apply`].
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn runpath_plan(&self) -> Result<Vec<RunpathRewrite>, Error> {
+        let graph = self.bundle_graph()?;
+        Ok(bundle::runpath_plan(&graph, self.resolver.config()))
+    }
+
+    /// The dependency graph with each library at the path it was found at, even if the
+    /// configuration canonicalizes paths, since that leaves out the symlinks it went through.
+    fn bundle_graph(&self) -> Result<DependencyGraph, Error> {
+        let config = self.resolver.config();
+        if config.canonicalize {
+            let config = config.clone().canonicalize_paths(false);
+            self.reconfigured(config).dependency_graph()
+        } else {
+            self.dependency_graph()
+        }
+    }
+
+    /// Looks up the OS package that installed each library in the closure of this file, listed
+    /// like [`ElfFile::get_libs_full_paths`] does, with `None` for libraries no package owns,
+    /// like those built locally.
+    /// # Return Value [Err]
+    /// Returned if the file cannot be analyzed or one of its libraries cannot be found, like
+    /// [`ElfFile::get_libs_full_paths`].
+    pub fn package_owners(
+        &self,
+        packages: &PackageDb,
+    ) -> Result<Vec<(PathBuf, Option<String>)>, Error> {
+        let libs = self.get_libs_full_paths()?;
+        let owners = libs.into_iter().map(|path| {
+            let owner = packages.owner(&path).map(str::to_owned);
+            (path, owner)
+        });
+        Ok(owners.collect())
+    }
+
+    /// The packages that must be installed for this file to run: those owning its libraries,
+    /// then the one owning its program interpreter, each listed once.
+    /// # Return Value [Err]
+    /// Returned like [`ElfFile::package_owners`].
+    pub fn required_packages(&self, packages: &PackageDb) -> Result<Vec<String>, Error> {
+        let mut owners: Vec<String> = self
+            .package_owners(packages)?
+            .into_iter()
+            .filter_map(|(_, owner)| owner)
+            .collect();
+        if let Some(interpreter) = self.interpreter()? {
+            let interpreter = self.resolver.config().rebase(&interpreter);
+            owners.extend(packages.owner(interpreter).map(str::to_owned));
+        }
+        let mut listed = HashSet::new();
+        owners.retain(|owner| listed.insert(owner.clone()));
+        Ok(owners)
+    }
+
+    /// Looks up the separate debug information of this file and of each library in its closure,
+    /// in load order, by the GNU build ID of each, for debuggers and profilers to symbolize
+    /// them. Every library is read for its build ID, even if the configuration is not
+    /// [recursive](ResolverConfig::recursive). Objects without a build ID, or whose debug
+    /// information can't be found, have `None`; unresolved libraries are left out.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn debug_info(
+        &self,
+        locator: &DebugInfoLocator,
+    ) -> Result<Vec<(PathBuf, Option<DebugInfo>)>, Error> {
+        let graph = self.build_graph(true)?;
+        let debug_info = graph.load_order().into_iter().filter_map(|id| {
+            let node = graph.node(id);
+            let info = node.build_id.as_deref().and_then(|id| locator.locate(id));
+            Some((node.path()?.to_owned(), info))
+        });
+        Ok(debug_info.collect())
+    }
+
+    /// Audits the exploit mitigations of this file and of each library in its closure, in load
+    /// order, like `checksec` does for one file: RELRO, PIE, a non-executable stack, stack
+    /// canaries and immediate binding. See [`HardeningReport::weakest`] for what the process
+    /// loading them all gets.
+    /// # Return Value [Err]
+    /// Returned if the file cannot be analyzed, or if one of its libraries can no longer be
+    /// read.
+    pub fn hardening_report(&self) -> Result<HardeningReport, Error> {
+        let graph = self.dependency_graph()?;
+        let mut objects = Vec::new();
+        for id in graph.load_order() {
+            let Some(path) = graph.node(id).path() else {
+                continue;
+            };
+            let hardening = match self.contents_at(path) {
+                Some(data) => hardening::read_hardening_from(path, Cursor::new(data))?,
+                None => hardening::read_hardening_from(path, self.resolver.open_in_sysroot(path)?)?,
+            };
+            objects.push((path.to_owned(), hardening));
+        }
+        Ok(HardeningReport { objects })
+    }
+
+    /// Lists the dangerous `RPATH` and `RUNPATH` entries of this file and of each library in its
+    /// closure, in load order: relative and empty entries, which ld.so takes relative to the
+    /// working directory, directories every user can write to, and entries below `/tmp` and
+    /// the other temporary directories. Any of them lets other users plant libraries the
+    /// program loads, so CI can fail builds that ship one. Dangerous entries of the
+    /// `LD_LIBRARY_PATH` the file would be run with are listed first, tagged
+    /// [`RunpathTag::LdLibraryPath`].
+    /// # Return Value [Err]
+    /// Returned if the file cannot be analyzed, or if one of its libraries can no longer be
+    /// read.
+    pub fn insecure_runpaths(&self) -> Result<Vec<RunpathFinding>, Error> {
+        let graph = self.dependency_graph()?;
+        let mut findings = Vec::new();
+        for id in graph.load_order() {
+            let Some(path) = graph.node(id).path() else {
+                continue;
+            };
+            let object = self.resolver.parse(&self.resolver.real_path(path))?;
+            let config = self.resolver.config();
+            // The executable is run with the LD_LIBRARY_PATH, whose findings come first
+            if id == graph.root() {
+                findings.extend(runpath_lint::lint_ld_library_path(path, &object, config));
+            }
+            findings.extend(runpath_lint::lint(path, &object, config));
+        }
+        Ok(findings)
+    }
+
+    /// Lists the non-fatal findings about this file and each library in its closure, in load
+    /// order: uses of the deprecated `DT_RPATH`, objects with both a `DT_RPATH` and a
+    /// `DT_RUNPATH`, sonames listed more than once in `DT_NEEDED` and run path directories that
+    /// don't exist. None of them stops the program from loading, but CI can fail builds on the
+    /// [`LintKind`]s it cares about.
+    /// # Return Value [Err]
+    /// Returned if the file cannot be analyzed, or if one of its libraries can no longer be
+    /// read.
+    pub fn lints(&self) -> Result<Vec<Lint>, Error> {
+        let graph = self.dependency_graph()?;
+        let mut lints = Vec::new();
+        for id in graph.load_order() {
+            let Some(path) = graph.node(id).path() else {
+                continue;
+            };
+            let object = match self.contents_at(path) {
+                Some(_) => self.parse()?,
+                None => self.resolver.parse(&self.resolver.real_path(path))?,
+            };
+            lints.extend(lint::lint(path, &object, self.resolver.config()));
+        }
+        Ok(lints)
+    }
+
+    /// Computes the size of this file and of each library in its closure, their total, how many
+    /// there are and how deep the closure goes, e.g. to budget the size of a container image.
+    /// Sizes are those of the files, with symlinks followed.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn closure_stats(&self) -> Result<ClosureStats, Error> {
+        let graph = self.dependency_graph()?;
+        Ok(stats::closure_stats(&graph, |path| self.file_size(path)))
+    }
+
+    /// Renders the closure of this file as CSV, with a header row and then one row per
+    /// resolved library in load order: the path of this file, the soname the library was
+    /// loaded as, its path, which [rule](SearchRule) found it (like `runpath` or `cache`), the
+    /// size of the file and its digest, if [`ResolverConfig::content_digest`] is configured.
+    /// Fields are quoted as RFC 4180 has it, for spreadsheets to read.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn to_csv(&self) -> Result<String, Error> {
+        let graph = self.dependency_graph()?;
+        Ok(csv::to_csv(&graph, |path| self.file_size(path)))
+    }
+
+    /// Renders this file and each library in its closure as a software bill of materials in
+    /// `format`, one package or component per file with its path, the soname it was loaded as
+    /// and its SHA-256 digest, along with which file needs which. Libraries that can't be found
+    /// are left out, so check [`ElfFile::get_libs_resolutions`] for a complete inventory.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    #[cfg(feature = "sbom")]
+    pub fn to_sbom(&self, format: SbomFormat) -> Result<String, Error> {
+        let graph = self.sha256_dependency_graph()?;
+        Ok(sbom::to_sbom(&graph, format, std::time::SystemTime::now()))
+    }
+
+    /// Records where each library in this file's closure is found and its SHA-256 digest in a
+    /// [`Lockfile`], as by [`DependencyGraph::to_lockfile`], to check later with
+    /// [`ElfFile::verify_against_lockfile`].
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn to_lockfile(&self) -> Result<Lockfile, Error> {
+        Ok(self.sha256_dependency_graph()?.to_lockfile())
+    }
+
+    /// Compares the closure of this file with the one recorded in `lockfile` by
+    /// [`ElfFile::to_lockfile`], returning every library added, removed, found elsewhere or
+    /// modified since, as by [`DependencyGraph::verify_against_lockfile`].
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn verify_against_lockfile(&self, lockfile: &Lockfile) -> Result<Vec<LockDrift>, Error> {
+        Ok(self
+            .sha256_dependency_graph()?
+            .verify_against_lockfile(lockfile))
+    }
+
+    /// Resolves the dependencies of this file on two systems, configured by `first` and
+    /// `second`, like the root filesystems of two distribution releases as
+    /// [sysroots](ResolverConfig::sysroot), and reports the libraries either lacks and those
+    /// they find differently, to check the file runs on both before deploying it. Paths are
+    /// compared inside the sysroots, and contents with [`ResolverConfig::content_digest`] set
+    /// on both.
+    ///
+    /// ```no_run
+    /// use elf_dynamic_lib_getter::{ElfFile, ResolverConfig};
+    ///
+    /// let config = |root| ResolverConfig::new().use_env(false).sysroot(root);
+    /// let file = ElfFile::new("target/release/app");
+    /// let report = file.compare_systems(config("/srv/ubuntu-22.04"), config("/srv/debian-12"))?;
+    /// for soname in &report.missing_on_second {
+    ///     println!("{soname} is missing on Debian 12");
+    /// }
+    /// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+    /// ```
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed on either system.
+    pub fn compare_systems(
+        &self,
+        first: ResolverConfig,
+        second: ResolverConfig,
+    ) -> Result<PortabilityReport, Error> {
+        let (first, second) = (self.reconfigured(first), self.reconfigured(second));
+        let (first_graph, second_graph) = (first.dependency_graph()?, second.dependency_graph()?);
+        Ok(portability::compare(
+            (&first_graph, first.resolver.config()),
+            (&second_graph, second.resolver.config()),
+        ))
+    }
+
+    /// The dependency graph of this file, with the SHA-256 digest of each file whatever
+    /// [`ResolverConfig::content_digest`] is configured.
+    fn sha256_dependency_graph(&self) -> Result<DependencyGraph, Error> {
+        let config = self.resolver.config();
+        if matches!(config.content_digest, Some(ContentDigest::Sha256)) {
+            self.dependency_graph()
+        } else {
+            let config = config.clone().content_digest(ContentDigest::Sha256);
+            self.reconfigured(config).dependency_graph()
+        }
+    }
+
+    /// Checks that every symbol this file and its libraries import is exported by one of them or
+    /// by the program interpreter, like `ldd -r`, catching broken installs where each library
+    /// is found but one is too old to provide a symbol. Weak references may stay undefined,
+    /// symbol versions are not compared, and files without section headers are taken to import
+    /// and export nothing. Symbols that a missing library would have provided are reported.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn undefined_symbols(&self) -> Result<Vec<UndefinedSymbol>, Error> {
+        Ok(symbols::undefined_symbols(&self.closure_symbols()?))
+    }
+
+    /// Looks `name` up in the dynamic symbol tables of this file, its libraries and the program
+    /// interpreter, returning every object that exports it, in load order. References bind to
+    /// the first default definition, so more than one provider explains which of several
+    /// clashing libraries wins.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn find_symbol(&self, name: &str) -> Result<Vec<SymbolProvider>, Error> {
+        Ok(symbols::find_symbol(&self.closure_symbols()?, name))
+    }
+
+    /// Lists the dynamic symbols this file, each library in its closure and the program
+    /// interpreter export, with their versions and bindings, in load order, like `nm -D
+    /// --defined-only` does for each. Local and hidden symbols are left out, as nothing else can
+    /// bind to them, and so are libraries that could not be found or read.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn exported_symbols(&self) -> Result<Vec<ExportedSymbols>, Error> {
+        Ok(symbols::exported_symbols(self.closure_symbols()?))
+    }
+
+    /// Lists the symbols that more than one object in the closure of this file, including the
+    /// program interpreter, exports, each with its providers in load order, the first of which
+    /// wins, like [`ElfFile::find_symbol`] would for each. This finds the crashes of a process
+    /// that ends up with two copies of a library, like two releases of libjpeg, before they
+    /// happen.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn symbol_conflicts(&self) -> Result<Vec<SymbolConflict>, Error> {
+        Ok(symbols::symbol_conflicts(&self.closure_symbols()?))
+    }
+
+    /// Lists the `DT_NEEDED` entries of this file and its libraries that name a library the
+    /// object imports no symbols or symbol versions from, like `ldd -u` does, so overlinking can
+    /// be trimmed, e.g. by linking with `--as-needed`. A library counts as used if it exports
+    /// anything the object imports, even if an earlier library in load order provides it too.
+    /// Libraries that could not be found or read, and objects without a `.dynsym` table, are
+    /// left out.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn unused_dependencies(&self) -> Result<Vec<UnusedDependency>, Error> {
+        let graph = self.dependency_graph()?;
+        let symbols = self.read_symbols(&graph)?;
+        Ok(symbols::unused_dependencies(&graph, &symbols))
+    }
+
+    /// Lists the `DT_NEEDED` entries of this file and its libraries that name a library the
+    /// object only imports weak symbols from, which it is prepared to find undefined, so reports
+    /// of missing libraries can tell hard failures from soft ones. A missing library is listed
+    /// if everything else the object strongly imports is found elsewhere in the closure, as
+    /// then it can at most have provided weak symbols. ld.so still refuses to run a program
+    /// with a missing library, but such a one can be dropped from the link or replaced by an
+    /// empty stub. Libraries the object requires symbol versions from are never weak.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn weak_dependencies(&self) -> Result<Vec<WeakDependency>, Error> {
+        let graph = self.dependency_graph()?;
+        let symbols = self.read_symbols(&graph)?;
+        let interpreter = self.interpreter_symbols(&graph);
+        let interpreter = interpreter.as_ref().map(|(_, symbols)| symbols);
+        Ok(symbols::weak_dependencies(&graph, &symbols, interpreter))
+    }
+
+    /// Reads the dynamic symbols of every object that could be read in the closure of this
+    /// file, in load order, followed by the program interpreter.
+    fn closure_symbols(&self) -> Result<Vec<(PathBuf, DynamicSymbols)>, Error> {
+        let graph = self.dependency_graph()?;
+        let mut symbols = self.read_symbols(&graph)?;
+        let mut objects: Vec<_> = graph
+            .load_order()
+            .into_iter()
+            .filter_map(|id| Some((graph.node(id).path()?.to_owned(), symbols[id.index()].take()?)))
+            .collect();
+        // The interpreter is in the graph already if it was included in the closure
+        let interpreter = self.interpreter_symbols(&graph);
+        let config = self.resolver.config();
+        let in_graph = |path: &Path| graph.find_path(config.identity(path)).is_some();
+        objects.extend(interpreter.filter(|(path, _)| !in_graph(path)));
+        Ok(objects)
+    }
+
+    /// Reads the dynamic symbols of the program interpreter of `graph`, if it can be read.
+    fn interpreter_symbols(&self, graph: &DependencyGraph) -> Option<(PathBuf, DynamicSymbols)> {
+        let interpreter = self.resolver.config().rebase(graph.interpreter()?);
+        let symbols = self.read_symbols_at(&interpreter).ok()?;
+        Some((interpreter, symbols))
+    }
+
+    /// Finds the highest `GLIBCXX_` and `CXXABI_` symbol versions this file and the libraries in
+    /// its closure require, and checks that the `libstdc++.so.6` found for them defines the
+    /// versions each requires, catching "version `GLIBCXX_3.4.30' not found" before the
+    /// program is run. Libraries that could not be found or read are left out.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn libstdcxx_requirements(&self) -> Result<LibstdcxxRequirements, Error> {
+        let graph = self.dependency_graph()?;
+        let symbols = self.read_symbols(&graph)?;
+        Ok(symbols::libstdcxx_requirements(&graph, &symbols))
+    }
+
+    /// Finds the oldest glibc this file runs on, from the highest `GLIBC_x.y` symbol version it
+    /// requires, and with `dependencies`, that the libraries in its closure which are not
+    /// [system libraries](SearchRule::is_system) require, as those ship with the program and
+    /// must run on the same systems. `None` if nothing requires a numbered glibc version, as
+    /// for programs built against other C libraries.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    /// # Example
+    /// ```no_run
+    /// use elf_dynamic_lib_getter::ElfFile;
+    ///
+    /// if let Some(glibc) = ElfFile::new("/usr/bin/app").minimum_glibc(true).unwrap() {
+    ///     println!("this program needs {glibc}");
+    /// }
+    /// ```
+    pub fn minimum_glibc(&self, dependencies: bool) -> Result<Option<GlibcRequirement>, Error> {
+        Ok(symbols::minimum_glibc(&self.shipped_symbols(dependencies)?))
+    }
+
+    /// The dynamic symbols of this file and, with `dependencies`, of the libraries in its
+    /// closure that are not system libraries, in load order. Libraries that can't be read are
+    /// left out.
+    fn shipped_symbols(&self, dependencies: bool) -> Result<Vec<(PathBuf, DynamicSymbols)>, Error> {
+        let mut objects = vec![(self.path.clone(), self.read_symbols_at(&self.path)?)];
+        if !dependencies {
+            return Ok(objects);
+        }
+        let graph = self.dependency_graph()?;
+        for id in graph.load_order().into_iter().skip(1) {
+            let node = graph.node(id);
+            let (Some(path), Some(rule)) = (node.path(), &node.found_by) else {
+                continue;
+            };
+            if !rule.is_system() {
+                if let Ok(symbols) = self.read_symbols_at(path) {
+                    objects.push((path.to_owned(), symbols));
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Checks that every library in the closure of this file defines the symbol versions that
+    /// the objects needing it require, as listed in their `.gnu.version_r` sections, like
+    /// ld.so does before running anything. Weak version requirements, which ld.so only warns
+    /// about, are left out, as are libraries that could not be found or read.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn missing_versions(&self) -> Result<Vec<MissingVersion>, Error> {
+        let graph = self.dependency_graph()?;
+        let symbols = self.read_symbols(&graph)?;
+        Ok(symbols::missing_versions(&graph, &symbols))
+    }
+
+    /// Guesses which libraries this file and its libraries load at run time with `dlopen`, such
+    /// as plugins, which their `DT_NEEDED` entries don't list: every string in the `.rodata` and
+    /// `.data.rel.ro` sections of each object that looks like the name or path of a shared
+    /// library, like `libfoo.so.1`, in load order, other than those of libraries already in
+    /// the closure, which `dlopen` would just return. Names built at run time are missed and
+    /// strings that only look like library names are reported, so these are possible
+    /// libraries, not certain ones. With `resolve`, each is also searched for as `dlopen` would
+    /// when called by the object holding it, with its `RUNPATH`, or its and the executable's
+    /// `RPATH`, but the dependencies of those found are not collected. Objects that cannot be
+    /// read or have no section headers are left out.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn possible_dlopen_libraries(&self, resolve: bool) -> Result<Vec<DlopenCandidate>, Error> {
+        let graph = self.dependency_graph()?;
+        let root = self.load()?;
+        let config = self.resolver.config();
+        let loaded: HashSet<&str> = graph
+            .nodes()
+            .iter()
+            .skip(1)
+            .flat_map(|node| [Some(node.soname.as_str()), node.dt_soname.as_deref()])
+            .flatten()
+            .collect();
+        let mut candidates = Vec::new();
+        for id in graph.load_order() {
+            let Some(path) = graph.node(id).path() else {
+                continue;
+            };
+            let names = match self.dlopen_names_at(path) {
+                Ok(names) => names,
+                Err(error) if id == graph.root() => return Err(error),
+                Err(_) => continue,
+            };
+            let object = if resolve && id != graph.root() {
+                match self.resolver.load_object(path, &root.rpath, Some(root.executable)) {
+                    Ok(object) => Some(object),
+                    Err(_) => continue,
                 }
+            } else {
+                None
+            };
+            let object = object.as_ref().or(Some(&root).filter(|_| resolve));
+            for name in names {
+                let name_lossy = name.to_string_lossy().into_owned();
+                if loaded.contains(name_lossy.as_str()) {
+                    continue;
+                }
+                let resolution = object.map(|object| {
+                    let executable = Some(root.executable);
+                    self.resolver
+                        .search(&name, &object.search_dirs, executable, object.nodeflib)
+                        .map(|(found, _)| Resolved::Found(config.identity(&found)))
+                        .map_err(|_| ResolveError::NotFound { needed_by: path.to_owned() })
+                });
+                candidates.push(DlopenCandidate {
+                    name: name_lossy,
+                    needed_by: path.to_owned(),
+                    resolution,
+                });
+            }
+        }
+        Ok(candidates)
+    }
+
+    /// The strings looking like library names in the read-only data of the object at `path`.
+    fn dlopen_names_at(&self, path: &Path) -> Result<Vec<OsString>, Error> {
+        if let Some(data) = self.contents_at(path) {
+            return dlopen::library_names(path, Cursor::new(data));
+        }
+        dlopen::library_names(path, self.resolver.open_in_sysroot(path)?)
+    }
+
+    /// Reads the dynamic symbols of every resolved node of `graph`, by node index. Only failing
+    /// to read the root is an error.
+    fn read_symbols(&self, graph: &DependencyGraph) -> Result<Vec<Option<DynamicSymbols>>, Error> {
+        let mut symbols = Vec::new();
+        for node in graph.nodes() {
+            let Some(path) = node.path() else {
+                symbols.push(None);
+                continue;
+            };
+            match self.read_symbols_at(path) {
+                Ok(object) => symbols.push(Some(object)),
+                Err(error) if symbols.is_empty() => return Err(error),
+                Err(_) => symbols.push(None),
             }
         }
+        Ok(symbols)
+    }
+
+    fn read_symbols_at(&self, path: &Path) -> Result<DynamicSymbols, Error> {
+        if let Some(data) = self.contents_at(path) {
+            return symbols::read_symbols_from(path, Cursor::new(data));
+        }
+        symbols::read_symbols_from(path, self.resolver.open_in_sysroot(path)?)
+    }
+
+    /// Builds the full [`DependencyGraph`] of this file, searching the same paths as
+    /// [`ElfFile::get_libs_full_paths`]. Missing libraries become unresolved nodes rather than
+    /// errors. With the `rayon` feature, the libraries needed by each object are searched for and
+    /// read concurrently, and the graph is the same as without it.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn dependency_graph(&self) -> Result<DependencyGraph, Error> {
+        self.build_graph(self.resolver.config().recursive)
+    }
 
-        // Get the `.dynamic` section and process DT_NEEDED libraries
-        let mut libs = Vec::new();
-        let dynamic = elf
-            .dynamic()
-            .expect("Failed to parse ELF header!")
-            .expect("ELF header must have a \".dynamic\" section!");
-        for entry in dynamic {
-            match entry.d_tag {
-                DT_NEEDED => {
-                    // This is a needed shared library!
-                    let offset = entry.d_val() as usize;
-                    libs.push(u8_slice_to_str(&dynstr_bytes[offset..])?.to_owned());
+    /// Whether every library this file needs, and every library those need, would be found
+    /// and loaded, as a quick health check for startup probes. Unlike
+    /// [`ElfFile::dependency_graph`], it records nothing and stops at the first library that
+    /// would not be. With a [shared](ElfFile::with_resolver) resolver, the libraries system
+    /// libraries need are not searched for again once a check found them all, until
+    /// [`Resolver::invalidate`] or [`Resolver::clear_cache`]. Libraries that may be missing,
+    /// like auxiliary filtees, and [ignored](ResolverConfig::ignore_sonames) ones are not
+    /// needed, and the [maximum depth](ResolverConfig::max_depth) and
+    /// [traversal filter](ResolverConfig::traversal_filter) are honored as in the graph.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn all_deps_resolvable(&self) -> Result<bool, Error> {
+        let resolver = &*self.resolver;
+        let config = resolver.config();
+        let file_name = self.path.file_name().unwrap_or(self.path.as_os_str());
+        let root = self.load()?;
+        // Every library must load into the executable, not just into its parent
+        let executable = root.executable;
+        let mut seen = HashSet::from([config.identity(&self.path)]);
+        let mut stack = vec![(file_name.to_owned(), self.path.clone(), root, 0)];
+        // Libraries whose dependencies were skipped are not known to have them all
+        let mut pruned = !config.recursive;
+        let mut verified = Vec::new();
+        while let Some((soname, path, object, depth)) = stack.pop() {
+            if config.stops_at(&soname.to_string_lossy(), &path, depth) {
+                pruned = true;
+                continue;
+            }
+            for (lib, kind) in object.dependencies {
+                if kind == DependencyKind::Virtual || config.is_ignored(&lib) {
+                    continue;
+                }
+                let dirs = &object.search_dirs;
+                let found = resolver.search(&lib, dirs, Some(executable), object.nodeflib);
+                let (lib_path, found_by) = match found {
+                    Ok(found) => found,
+                    Err(_) if kind.is_optional() => continue,
+                    Err(_) => return Ok(false),
+                };
+                if !config.recursive || !seen.insert(config.identity(&lib_path)) {
+                    continue;
                 }
-                DT_RPATH | DT_RUNPATH => {
-                    let offset = entry.d_val() as usize;
-                    let paths_str =
-                        u8_slice_to_str(&dynstr_bytes[offset..]).expect("Invalid RPATH/RUNPATH string");
-                    for path in paths_str.split(':') {
-                        search_dirs.push(PathBuf::from(path));
-                    }
+                // Without an RPATH passed on, a system library needs the same libraries
+                // whichever object needs it
+                let system = object.rpath.is_empty()
+                    && matches!(
+                        found_by,
+                        SearchRule::Cache | SearchRule::ConfiguredDir | SearchRule::DefaultDir
+                    );
+                if system && resolver.is_verified(&lib_path, executable) {
+                    continue;
                 }
-                _ => (),
-            }
-        }
-
-        for lib in libs.iter() {
-            let mut found = false;
-            for dir in search_dirs.iter() {
-                let possible_lib_path = dir.join(lib);
-                if possible_lib_path.exists() && verify_arch(&possible_lib_path, is_64_bit) {
-                    // Check if we've already processed this library
-                    if seen_libs.contains(&possible_lib_path) {
-                        found = true;
-                        break;
-                    }
-                    // Add to seen_libs
-                    seen_libs.insert(possible_lib_path.clone());
-                    lib_paths.push(possible_lib_path.clone());
-                    found = true;
-                    // Recurse into the library
-                    ElfFile::collect_libs(&possible_lib_path, seen_libs, lib_paths)?;
-                    break;
+                let Ok(loaded) = resolver.load_object(&lib_path, &object.rpath, Some(executable))
+                else {
+                    return Ok(false);
+                };
+                if system {
+                    verified.push(lib_path.clone());
                 }
+                stack.push((lib, lib_path, loaded, depth + 1));
+            }
+        }
+        if !pruned {
+            resolver.mark_verified(verified, executable);
+        }
+        Ok(true)
+    }
+
+    fn build_graph(&self, recursive: bool) -> Result<DependencyGraph, Error> {
+        let file_name = self.path.file_name().unwrap_or(self.path.as_os_str());
+        let resolution = Ok(Resolved::Found(self.path.clone()));
+        let graph = DependencyGraph::new(DependencyNode::new(file_name, resolution));
+        let root = graph.root();
+        let resolver = &self.resolver;
+        let mut traversal = Traversal {
+            resolver,
+            recursive,
+            seen_libs: HashMap::new(),
+            graph,
+            order: resolver.config().order,
+            queue: VecDeque::new(),
+            bytes_parsed: self.file_size(&self.path).unwrap_or_default(),
+        };
+        // Add the initial path to seen_libs
+        traversal.seen_libs.insert(resolver.config().identity(&self.path), root);
+        let object = self.load()?;
+        traversal.graph.set_interpreter(object.interpreter.clone().map(PathBuf::from));
+        let digest = resolver.config().content_digest.as_ref();
+        traversal.graph.node_mut(root).digest = match &self.contents {
+            Some(data) => digest.map(|digest| digest.digest(data)),
+            None => resolver.digest(&self.path),
+        };
+        traversal.graph.node_mut(root).stat = resolver.stat(&self.path);
+        traversal.graph.node_mut(root).store_path = resolver.config().store_path(&self.path);
+        traversal.walk(&self.path, root, object, 0);
+        Ok(traversal.graph)
+    }
+
+    /// Parses this file, from memory if it was given there.
+    fn parse(&self) -> Result<Arc<ParsedObject>, Error> {
+        match &self.contents {
+            Some(data) => {
+                let config = self.resolver.config();
+                config.check_file_size(&self.path, data.len() as u64)?;
+                Ok(Arc::new(parse::parse_bytes(&self.path, data)?))
             }
-            if !found {
-                // Failed to find `lib` anywhere!
-                return None;
+            None => self.resolver.parse(&self.path),
+        }
+    }
+
+    /// Like [`ElfFile::parse`], but fails with [`Error::NotLoadable`] for files the dynamic
+    /// linker doesn't load.
+    fn parse_loadable(&self) -> Result<Arc<ParsedObject>, Error> {
+        let object = self.parse()?;
+        if !object.elf_type.is_loadable() {
+            return Err(Error::NotLoadable {
+                path: self.path.clone(),
+                elf_type: object.elf_type,
+            });
+        }
+        Ok(object)
+    }
+
+    /// Reads this file and works out where to search for its dependencies.
+    fn load(&self) -> Result<LoadedObject, Error> {
+        match &self.contents {
+            Some(_) => {
+                let object = self.parse()?;
+                self.resolver.load_parsed(&self.path, self.path.clone(), object, &[], None)
             }
+            None => self.resolver.load_object(&self.path, &[], None),
         }
-        Some(())
     }
-}
 
-fn u8_slice_to_str(c_str: &[u8]) -> Option<&str> {
-    // Find null terminator
-    if let Some(end) = c_str.iter().position(|&b| b == b'\0') {
-        // Create c string slice
-        let slice = &c_str[..end];
-        std::str::from_utf8(slice).ok()
-    } else {
-        None
+    /// The size of the file at `path`, this one or one of its dependencies, with symlinks
+    /// followed.
+    fn file_size(&self, path: &Path) -> Option<u64> {
+        if let Some(data) = self.contents_at(path) {
+            return Some(data.len() as u64);
+        }
+        self.resolver.file_size(path)
+    }
+
+    /// The contents of this file if it was given in memory and `path` is its path.
+    fn contents_at(&self, path: &Path) -> Option<&[u8]> {
+        self.contents.as_deref().filter(|_| path == self.path)
+    }
+
+    /// This file, with its dependencies resolved according to `config` instead.
+    fn reconfigured(&self, config: ResolverConfig) -> ElfFile {
+        ElfFile {
+            contents: self.contents.clone(),
+            ..ElfFile::with_config(&self.path, config)
+        }
     }
 }
 
-fn verify_arch(lib_path: &Path, is_64_bit_executable: bool) -> bool {
-    if let Ok(lib_data) = fs::read(lib_path) {
-        if let Ok(lib_elf) = ElfBytes::<AnyEndian>::minimal_parse(lib_data.as_slice()) {
-            let lib_header = lib_elf.ehdr;
-            matches!((lib_header.class, is_64_bit_executable), (ELF64, true) | (ELF32, false))
+/// Directories searched after `LD_LIBRARY_PATH`, `RPATH`/`RUNPATH`, the `ld.so.cache` and the
+/// `ld.so.conf` directories, for libraries of `arch` (the host's, if `None`).
+pub(crate) fn default_search_dirs(arch: Option<parse::Arch>) -> Vec<PathBuf> {
+    let arch = arch.or_else(parse::Arch::host);
+    let bits = match arch {
+        Some(arch) if arch.is_x32() => "x32",
+        Some(arch) if !arch.is_64_bit => "32",
+        _ => "64",
+    };
+    let triplets = arch.map_or(&[][..], |arch| arch.multiarch_triplets());
+    let mut dirs = vec![PathBuf::from("/usr/lib"), PathBuf::from(format!("/lib{bits}"))];
+    dirs.extend(triplets.iter().map(|triplet| Path::new("/lib").join(triplet)));
+    dirs.push(PathBuf::from("/lib"));
+    dirs.push(PathBuf::from(format!("/usr/lib{bits}")));
+    dirs.extend(triplets.iter().map(|triplet| Path::new("/usr/lib").join(triplet)));
+    dirs
+}
+
+/// State shared across the walk of a single [`ElfFile`]. The walk is a loop over explicit
+/// stacks and queues rather than recursion, so however long a chain of dependencies is, it
+/// can't overflow the stack.
+struct Traversal<'a> {
+    resolver: &'a Resolver,
+    /// Whether to collect the dependencies of dependencies.
+    recursive: bool,
+    seen_libs: HashMap<PathBuf, NodeId>,
+    graph: DependencyGraph,
+    order: TraversalOrder,
+    /// The libraries whose dependencies are still to be collected, when walking the closure
+    /// breadth-first.
+    queue: VecDeque<Pending>,
+    /// The size of the files read for the graph so far, the root included.
+    bytes_parsed: u64,
+}
+
+/// A library whose dependencies are to be collected.
+struct Pending {
+    path: PathBuf,
+    node: NodeId,
+    /// The library, if it was read ahead of time.
+    object: Option<Result<LoadedObject, Error>>,
+    /// The `RPATH` chain of the object that needed it.
+    rpath: Vec<(PathBuf, SearchRule)>,
+    executable: Executable,
+    depth: usize,
+}
+
+/// An object whose dependencies are being added to the graph, one at a time.
+struct Frame {
+    path: PathBuf,
+    node: NodeId,
+    /// The dependencies left to add, with where they were found.
+    libs: std::vec::IntoIter<((OsString, DependencyKind), Lookup)>,
+    /// The `RPATH` chain to pass on to the dependencies.
+    rpath: Vec<(PathBuf, SearchRule)>,
+    executable: Executable,
+    depth: usize,
+}
+
+impl Traversal<'_> {
+    /// Collects the closure of `object`, read from `path`, into `node`, which is `depth`
+    /// levels below the root. Depth-first, the dependencies of each library are collected
+    /// right after it is found, before its siblings; breadth-first, after all of them.
+    fn walk(&mut self, path: &Path, node: NodeId, object: LoadedObject, depth: usize) {
+        let frame = self.start(path.to_owned(), node, object, depth);
+        self.walk_from(frame);
+    }
+
+    /// Like [`Traversal::walk`], for the object whose dependencies were searched for into
+    /// `frame`.
+    fn walk_from(&mut self, frame: Option<Frame>) {
+        let mut stack = Vec::from_iter(frame);
+        let order = self.order;
+        loop {
+            while let Some(frame) = stack.last_mut() {
+                if self.graph.exceeded_limit().is_some() {
+                    return;
+                }
+                let Some(((lib, kind), lookup)) = frame.libs.next() else {
+                    stack.pop();
+                    continue;
+                };
+                let Some(pending) = self.add_lib(frame, &lib, kind, lookup) else {
+                    continue;
+                };
+                match order {
+                    TraversalOrder::DepthFirst if self.interrupted() => return,
+                    TraversalOrder::DepthFirst => stack.extend(self.visit(pending)),
+                    TraversalOrder::BreadthFirst => self.queue.push_back(pending),
+                }
+            }
+            let Some(pending) = self.queue.pop_front() else {
+                break;
+            };
+            if self.interrupted() {
+                return;
+            }
+            stack.extend(self.visit(pending));
+        }
+    }
+
+    /// Whether to stop collecting dependencies, by the cancel flag or the deadline, which then
+    /// marks the graph as interrupted, or because a resource limit was reached.
+    fn interrupted(&mut self) -> bool {
+        let interrupted = self.resolver.config().is_interrupted();
+        if interrupted {
+            self.graph.set_interrupted();
+        }
+        interrupted || self.graph.exceeded_limit().is_some()
+    }
+
+    /// Whether the graph already holds the maximum number of dependencies, which then records
+    /// the limit in it.
+    fn at_max_dependencies(&mut self) -> bool {
+        let Some(max) = self.resolver.config().max_dependencies else {
+            return false;
+        };
+        // The root is not one of them
+        let full = self.graph.nodes().len() > max;
+        if full {
+            self.graph.set_exceeded_limit(ResourceLimit::Dependencies(max));
+        }
+        full
+    }
+
+    /// Counts the library at `path` as read, unless that takes the size of the files read past
+    /// the maximum, which then records the limit in the graph and returns `false`.
+    fn count_bytes(&mut self, path: &Path) -> bool {
+        let Some(max) = self.resolver.config().max_bytes_parsed else {
+            return true;
+        };
+        let bytes = self.bytes_parsed + self.resolver.file_size(path).unwrap_or_default();
+        if bytes > max {
+            self.graph.set_exceeded_limit(ResourceLimit::BytesParsed(max));
+            return false;
+        }
+        self.bytes_parsed = bytes;
+        true
+    }
+
+    /// Records what `object`, read from `path`, says about itself in `node`, and searches for
+    /// its dependencies. Returns `None` if they are not to be added, at the maximum depth or by
+    /// the traversal filter.
+    fn start(
+        &mut self,
+        path: PathBuf,
+        node: NodeId,
+        object: LoadedObject,
+        depth: usize,
+    ) -> Option<Frame> {
+        // Libraries must match the architecture of the executable, not just of their parent
+        let config = self.resolver.config();
+        let executable = object.executable;
+        let (libs, rpath, search_dirs) = (object.dependencies, object.rpath, object.search_dirs);
+        let nodeflib = object.nodeflib;
+        let dt_soname = object.soname.as_ref().map(|soname| soname.to_string_lossy().into_owned());
+        self.graph.node_mut(node).dt_soname = dt_soname;
+        self.graph.node_mut(node).build_id = object.build_id.clone();
+        self.graph.node_mut(node).header = Some(object.header);
+        self.graph.node_mut(node).dynamic = Some(object.dynamic);
+        self.graph.node_mut(node).os_abi_mismatch = object.os_abi_mismatch;
+        if config.stops_at(&self.graph.node(node).soname, &path, depth) || self.interrupted() {
+            // Only note that there was more to collect
+            self.graph.node_mut(node).truncated = !libs.is_empty();
+            return None;
+        }
+        if config.search_paths {
+            let search_path = self.resolver.search_path(&search_dirs, executable, nodeflib);
+            self.graph.node_mut(node).search_path = search_path;
+        }
+
+        // Search for every library up front, and with the `rayon` feature also read the new
+        // ones, so siblings are handled concurrently
+        let lookup = |(lib, kind): &(OsString, DependencyKind)| {
+            self.lookup(lib, *kind, &search_dirs, &rpath, executable, nodeflib)
+        };
+        #[cfg(feature = "rayon")]
+        let lookups: Vec<Lookup> = libs.par_iter().map(lookup).collect();
+        #[cfg(not(feature = "rayon"))]
+        let lookups: Vec<Lookup> = libs.iter().map(lookup).collect();
+        let libs: Vec<_> = libs.into_iter().zip(lookups).collect();
+        Some(Frame { path, node, libs: libs.into_iter(), rpath, executable, depth })
+    }
+
+    /// Searches for the dependency `lib` of an object loaded by `executable`, in `search_dirs`
+    /// and the system directories unless `nodeflib`, and with the `rayon` feature also reads
+    /// it if it is new, passing on the `RPATH` chain `rpath`.
+    fn lookup(
+        &self,
+        lib: &OsStr,
+        kind: DependencyKind,
+        search_dirs: &[(PathBuf, SearchRule)],
+        rpath: &[(PathBuf, SearchRule)],
+        executable: Executable,
+        nodeflib: bool,
+    ) -> Lookup {
+        let config = self.resolver.config();
+        if kind == DependencyKind::Virtual || config.is_ignored(lib) {
+            let record = SearchRecord::default();
+            let (object, digest, stat) = (None, None, None);
+            return Lookup { found: Err(Vec::new()), object, digest, stat, record };
+        }
+        let mut record = SearchRecord {
+            exhaustive: config.exhaustive_search,
+            trace: config.trace_search,
+            ..SearchRecord::default()
+        };
+        let (dirs, executable) = (search_dirs, Some(executable));
+        let found = if record.exhaustive || record.trace {
+            self.resolver.search_recorded(lib, dirs, executable, nodeflib, &mut record)
         } else {
-            false
+            self.resolver.search(lib, dirs, executable, nodeflib)
+        };
+        if found.is_err() && !kind.is_optional() {
+            record.rejections = self.resolver.rejections(lib, dirs, executable, nodeflib);
+        }
+        let new = |lib_path| !self.seen_libs.contains_key(&config.identity(lib_path));
+        let (digest, stat) = match &found {
+            Ok((lib_path, _)) if new(lib_path) => {
+                (self.resolver.digest(lib_path), self.resolver.stat(lib_path))
+            }
+            _ => (None, None),
+        };
+        let object = match &found {
+            // Unless the files read are counted, which must happen in order
+            Ok((lib_path, _))
+                if cfg!(feature = "rayon")
+                    && self.recursive
+                    && config.max_bytes_parsed.is_none()
+                    && new(lib_path) =>
+            {
+                Some(self.resolver.load_object(lib_path, rpath, executable))
+            }
+            _ => None,
+        };
+        Lookup { found, object, digest, stat, record }
+    }
+
+    /// Adds the dependency `lib` of the object of `frame`, found as `lookup` says. Returns the
+    /// library if it is new and its own dependencies are to be collected.
+    fn add_lib(
+        &mut self,
+        frame: &Frame,
+        lib: &OsStr,
+        kind: DependencyKind,
+        lookup: Lookup,
+    ) -> Option<Pending> {
+        let config = self.resolver.config();
+        let node = frame.node;
+        match lookup.found {
+            // Check if we've already processed this library
+            Ok((lib_path, found_by)) => {
+                let identity = config.identity(&lib_path);
+                if let Some(&seen) = self.seen_libs.get(&identity) {
+                    self.graph.add_edge(node, seen);
+                    return None;
+                }
+                if self.at_max_dependencies() {
+                    return None;
+                }
+                let mut child = DependencyNode::new(lib, Ok(Resolved::Found(identity.clone())));
+                child.kind = kind;
+                child.links = config.symlink_chain(&lib_path);
+                child.found_by = Some(found_by);
+                child.digest = lookup.digest;
+                child.stat = lookup.stat;
+                child.store_path = config.store_path(&identity);
+                child.candidates = lookup.record.candidates;
+                child.search_trace = lookup.record.steps;
+                let child = self.graph.add_node(child);
+                self.graph.add_edge(node, child);
+                // Add to seen_libs
+                self.seen_libs.insert(identity, child);
+                self.recursive.then(|| Pending {
+                    path: lib_path,
+                    node: child,
+                    object: lookup.object,
+                    rpath: frame.rpath.clone(),
+                    executable: frame.executable,
+                    depth: frame.depth + 1,
+                })
+            }
+            // Like ld.so, quietly skip auxiliary filtees that aren't there, and optional
+            // libraries the program declares it may load
+            Err(_) if kind.is_optional() && !config.is_ignored(lib) => None,
+            Err(_) if self.at_max_dependencies() => None,
+            Err(_) => {
+                // Failed to find `lib` anywhere, unless it is not to be found at all
+                let resolution = match kind {
+                    DependencyKind::Virtual => Ok(Resolved::Virtual),
+                    _ if config.is_ignored(lib) => Ok(Resolved::Ignored),
+                    _ => Err(ResolveError::NotFound { needed_by: frame.path.clone() }),
+                };
+                let mut child = DependencyNode::new(lib, resolution);
+                child.kind = kind;
+                child.candidates = lookup.record.candidates;
+                child.search_trace = lookup.record.steps;
+                child.rejections = lookup.record.rejections;
+                let child = self.graph.add_node(child);
+                self.graph.add_edge(node, child);
+                None
+            }
+        }
+    }
+
+    /// Reads `pending` and searches for its dependencies, recording a failure to read it in
+    /// its node. Returns `None` without reading it if that would exceed the maximum bytes
+    /// parsed.
+    fn visit(&mut self, pending: Pending) -> Option<Frame> {
+        let Pending { path, node, object, rpath, executable, depth } = pending;
+        if !self.count_bytes(&path) {
+            return None;
+        }
+        let object =
+            object.unwrap_or_else(|| self.resolver.load_object(&path, &rpath, Some(executable)));
+        match object {
+            Ok(object) => self.start(path, node, object, depth),
+            Err(error) => {
+                self.graph.node_mut(node).resolution = Err(ResolveError::Load(error));
+                None
+            }
+        }
+    }
+}
+
+/// The outcome of searching for one `DT_NEEDED` entry.
+struct Lookup {
+    found: SearchOutcome,
+    /// The library read ahead of time, if it was.
+    object: Option<Result<LoadedObject, Error>>,
+    /// The digest of the library, computed along with the search.
+    digest: Option<String>,
+    /// The stat of the library, taken along with the search.
+    stat: Option<FileStat>,
+    /// What was noted down about the search besides its outcome.
+    record: SearchRecord,
+}
+
+/// Orders the per-object search directories the way ld.so does: `DT_RPATH` (only when there is
+/// no `DT_RUNPATH`), then `LD_LIBRARY_PATH`, then `DT_RUNPATH`. `LD_LIBRARY_PATH` entries that
+/// don't exist in `fs` are skipped, and so are empty ones unless `empty_as_cwd`, which makes
+/// them the working directory.
+pub(crate) fn search_order(
+    fs: &dyn FileSystem,
+    rpath: &[(PathBuf, SearchRule)],
+    ld_library_path: Option<&OsStr>,
+    empty_as_cwd: bool,
+    runpath: &[(PathBuf, SearchRule)],
+) -> Vec<(PathBuf, SearchRule)> {
+    let mut search_dirs = Vec::new();
+    if runpath.is_empty() {
+        search_dirs.extend_from_slice(rpath);
+    }
+    // Like ld.so, ignore an LD_LIBRARY_PATH that is set but empty
+    if let Some(ld_library_path_var) = ld_library_path.filter(|var| !var.is_empty()) {
+        for mut lib_path in env::split_paths(ld_library_path_var) {
+            if lib_path.as_os_str().is_empty() && empty_as_cwd {
+                lib_path = PathBuf::from(".");
+            }
+            if fs.exists(&lib_path) {
+                search_dirs.push((lib_path, SearchRule::LdLibraryPath));
+            }
         }
-    } else {
-        false
     }
+    search_dirs.extend_from_slice(runpath);
+    search_dirs
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ElfFile;
-    use std::path::PathBuf;
+    use super::{
+        search_order, BundleMode, ContentDigest, DebugInfo, DebugInfoLocator, DependencyGraph,
+        DependencyKind, DependencyNode, DiskCache, DlopenPriority, DynamicFlags, DynamicFlags1,
+        DynamicInfo, ElfFile, ElfType, Error, FileStat, HostFs, LintKind, LockDrift, NodeId,
+        OsAbiCheck, Pie, Platform, ProbeResult, ResolutionMetrics, ResolveError, Resolved,
+        Resolver, ResolverConfig, ResourceLimit, RunpathIssue, RunpathRewrite, RunpathTag,
+        SearchRule, SymbolBinding, Target, TraversalOrder, NIX_STORE_DIR,
+    };
+    use crate::test_util::{ElfBuilder, MemoryFs};
+    use std::ffi::{OsStr, OsString};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
 
     #[test]
     fn test_libc_dependencies() {
@@ -187,6 +1611,2676 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_unreadable_file() {
+        let elf_file = ElfFile::new("/nonexistent/elf/file");
+        assert!(matches!(elf_file.get_libs_full_paths(), Err(Error::Io { .. })));
+    }
+
+    #[test]
+    fn test_resolutions_continue_past_missing_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = ElfBuilder::new()
+            .needed("libdyn-lib-finder-missing.so.1")
+            .needed("libc.so.6")
+            .write(dir.path(), "root");
+        let elf_file = ElfFile::new(&root);
+
+        let resolutions = elf_file.get_libs_resolutions().unwrap();
+        let (soname, resolution) = &resolutions[0];
+        assert_eq!(soname, "libdyn-lib-finder-missing.so.1");
+        assert!(matches!(resolution, Err(ResolveError::NotFound { needed_by }) if needed_by == &root));
+        assert!(resolutions.iter().any(|(soname, resolution)| soname == "libc.so.6" && resolution.is_ok()));
+
+        assert!(matches!(
+            elf_file.get_libs_full_paths(),
+            Err(Error::Unresolved { soname, .. }) if soname == "libdyn-lib-finder-missing.so.1"
+        ));
+    }
+
+    #[test]
+    fn test_dependency_graph_shares_nodes() {
+        let graph = ElfFile::new("/usr/bin/ls").dependency_graph().unwrap();
+        let root = graph.root();
+        assert_eq!(graph.node(root).path(), Some(PathBuf::from("/usr/bin/ls").as_path()));
+
+        // libc is needed by `ls` and by its other dependencies, but appears only once
+        let libc = graph.find_path("/lib/x86_64-linux-gnu/libc.so.6").expect("libc not in graph");
+        assert!(graph.dependencies(root).contains(&libc));
+        assert!(graph.dependents(libc).count() > 1);
+        assert_eq!(graph.nodes().iter().filter(|node| node.soname == "libc.so.6").count(), 1);
+    }
+
+    #[test]
+    fn test_dependency_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().to_str().unwrap();
+        let liba = ElfBuilder::new()
+            .runpath(lib_dir)
+            .needed("libb.so.1")
+            .write(dir.path(), "liba.so.1");
+        let libb = ElfBuilder::new()
+            .runpath(lib_dir)
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .write(dir.path(), "libb.so.1");
+        let root = ElfBuilder::new()
+            .runpath(lib_dir)
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .write(dir.path(), "app");
+
+        let graph = ElfFile::new(&root).dependency_graph().unwrap();
+        let (a, b) = (graph.find_path(&liba).unwrap(), graph.find_path(&libb).unwrap());
+        // The root needing libb too does not close a cycle
+        assert_eq!(graph.back_edges(), [(b, a), (b, b)]);
+        assert_eq!(graph.cycles(), [vec![a, b], vec![b]]);
+        let cycles = ElfFile::new(&root).dependency_cycles().unwrap();
+        assert_eq!(cycles, [vec![liba, libb.clone()], vec![libb]]);
+        let edges: Vec<_> = graph.edges().collect();
+        let (nodes, adjacency) = graph.into_adjacency_list();
+        assert_eq!(nodes.len(), 3);
+        let adjacency_edges = adjacency
+            .iter()
+            .enumerate()
+            .flat_map(|(from, to)| to.iter().map(move |&to| (from, to.index())));
+        assert!(adjacency_edges.eq(edges.iter().map(|&(from, to)| (from.index(), to.index()))));
+    }
+
+    #[test]
+    fn test_soname_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let opt = dir.path().join("opt");
+        let lib = dir.path().join("lib");
+        let link = dir.path().join("link");
+        std::fs::create_dir(&opt).unwrap();
+        std::fs::create_dir(&lib).unwrap();
+        std::os::unix::fs::symlink(&opt, &link).unwrap();
+        let opt_libz = ElfBuilder::new().write(&opt, "libz.so.1");
+        let lib_libz = ElfBuilder::new().write(&lib, "libz.so.1");
+        for (name, runpath) in [("liba.so.1", &opt), ("libb.so.1", &lib), ("libc.so.1", &link)] {
+            ElfBuilder::new()
+                .runpath(runpath.to_str().unwrap())
+                .needed("libz.so.1")
+                .write(dir.path(), name);
+        }
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .needed("libc.so.1")
+            .write(dir.path(), "app");
+
+        let graph = ElfFile::new(&root).dependency_graph().unwrap();
+        let libz = |path: &PathBuf| graph.find_path(path).unwrap();
+        let via_link = libz(&link.join("libz.so.1"));
+        assert_eq!(graph.soname_conflicts(), [vec![libz(&opt_libz), libz(&lib_libz), via_link]]);
+        // libc finds the same file as liba through the symlink
+        let conflicts = ElfFile::new(&root).soname_conflicts().unwrap();
+        assert_eq!(conflicts, [("libz.so.1".to_owned(), vec![opt_libz, lib_libz])]);
+    }
+
+    #[test]
+    fn test_needed_by_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().to_str().unwrap();
+        let icu = ElfBuilder::new().write(dir.path(), "libicu.so.1");
+        for name in ["libxml.so.1", "libharfbuzz.so.1"] {
+            ElfBuilder::new()
+                .runpath(lib_dir)
+                .needed("libicu.so.1")
+                .write(dir.path(), name);
+        }
+        let root = ElfBuilder::new()
+            .runpath(lib_dir)
+            .needed("libxml.so.1")
+            .needed("libharfbuzz.so.1")
+            .write(dir.path(), "app");
+
+        let graph = ElfFile::new(&root).dependency_graph().unwrap();
+        let icu = graph.find_path(icu).unwrap();
+        let xml = graph.find_path(dir.path().join("libxml.so.1")).unwrap();
+        let harfbuzz = graph.find_path(dir.path().join("libharfbuzz.so.1")).unwrap();
+        assert_eq!(graph.needed_by_chain(icu), Some(vec![graph.root(), xml, icu]));
+        assert_eq!(graph.needed_by_chain(graph.root()), Some(vec![graph.root()]));
+        assert_eq!(graph.dependents(icu).collect::<Vec<_>>(), [xml, harfbuzz]);
+    }
+
+    #[test]
+    fn test_undefined_symbols() {
+        let dir = tempfile::tempdir().unwrap();
+        let liba = ElfBuilder::new()
+            .defines("foo")
+            .imports("qux")
+            .imports("quux")
+            .write(dir.path(), "liba.so.1");
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("liba.so.1")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .imports("foo")
+            .imports("bar")
+            .imports_weak("baz")
+            .defines("qux")
+            .write(dir.path(), "app");
+
+        let undefined = ElfFile::new(&root).undefined_symbols().unwrap();
+        let undefined: Vec<_> = undefined
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.needed_by.as_path()))
+            .collect();
+        // liba finds qux in the executable, like ld.so would
+        assert_eq!(undefined, [("bar", root.as_path()), ("quux", liba.as_path())]);
+        assert!(ElfFile::new("/usr/bin/ls").undefined_symbols().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_symbol() {
+        let dir = tempfile::tempdir().unwrap();
+        let libb = ElfBuilder::new()
+            .defines_versioned("foo", "B_1.0", false)
+            .defines_versioned("foo", "B_2.0", true)
+            .write(dir.path(), "libb.so.1");
+        let liba = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libb.so.1")
+            .defines_weak("foo")
+            .write(dir.path(), "liba.so.1");
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("liba.so.1")
+            .imports("foo")
+            .write(dir.path(), "app");
+
+        let providers = ElfFile::new(&root).find_symbol("foo").unwrap();
+        let providers: Vec<_> = providers
+            .iter()
+            .map(|provider| {
+                let version = provider.version.as_deref();
+                (provider.path.as_path(), version, provider.default_version, provider.binding)
+            })
+            .collect();
+        assert_eq!(
+            providers,
+            [
+                (liba.as_path(), None, true, SymbolBinding::Weak),
+                (libb.as_path(), Some("B_1.0"), false, SymbolBinding::Global),
+                (libb.as_path(), Some("B_2.0"), true, SymbolBinding::Global),
+            ]
+        );
+        assert!(ElfFile::new(&root).find_symbol("bar").unwrap().is_empty());
+        // The interpreter is searched too
+        let malloc = ElfFile::new("/usr/bin/ls").find_symbol("malloc").unwrap();
+        assert!(!malloc.is_empty());
+        assert!(malloc.iter().all(|provider| provider.version.is_some()));
+    }
+
+    #[test]
+    fn test_symbol_hash_tables() {
+        for elf32 in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let class = |builder: ElfBuilder| if elf32 { builder.elf32() } else { builder };
+            let libg = class(ElfBuilder::new())
+                .gnu_hash()
+                .imports("baz")
+                .defines("foo")
+                .defines_versioned("bar", "G_1.0", false)
+                .defines_versioned("bar", "G_2.0", true)
+                .write(dir.path(), "libg.so.1");
+            let libs = class(ElfBuilder::new())
+                .sysv_hash()
+                .defines("qux")
+                .defines("baz")
+                .write(dir.path(), "libs.so.1");
+            let root = class(ElfBuilder::new())
+                .runpath(dir.path().to_str().unwrap())
+                .needed("libg.so.1")
+                .needed("libs.so.1")
+                .imports("foo")
+                .imports("qux")
+                .write(dir.path(), "app");
+            let file = ElfFile::new(&root);
+
+            let paths = |name| {
+                let providers = file.find_symbol(name).unwrap();
+                let paths = providers.into_iter().map(|provider| provider.path);
+                paths.collect::<Vec<_>>()
+            };
+            assert_eq!(paths("foo"), [libg.as_path()]);
+            assert_eq!(paths("bar"), [libg.as_path(), libg.as_path()]);
+            // libg only imports baz
+            assert_eq!(paths("baz"), [libs.as_path()]);
+            assert_eq!(paths("qux"), [libs.as_path()]);
+            assert!(paths("quux").is_empty());
+            assert!(file.undefined_symbols().unwrap().is_empty());
+            assert!(file.unused_dependencies().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_symbol_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let libb = ElfBuilder::new()
+            .defines_versioned("foo", "B_2.0", true)
+            .defines("bar")
+            .defines_versioned("baz", "B_1.0", false)
+            .write(dir.path(), "libb.so.1");
+        let liba = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libb.so.1")
+            .defines("foo")
+            .defines_weak("bar")
+            .defines("qux")
+            .write(dir.path(), "liba.so.1");
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("liba.so.1")
+            .imports("foo")
+            .defines("baz")
+            .write(dir.path(), "app");
+
+        let conflicts = ElfFile::new(&root).symbol_conflicts().unwrap();
+        let conflicts: Vec<_> = conflicts
+            .iter()
+            .map(|conflict| {
+                let providers: Vec<_> = conflict
+                    .providers
+                    .iter()
+                    .map(|provider| (provider.path.as_path(), provider.binding))
+                    .collect();
+                (conflict.name.as_str(), providers)
+            })
+            .collect();
+        // The old version of baz in libb doesn't clash with the app's
+        let (liba, libb) = (liba.as_path(), libb.as_path());
+        assert_eq!(
+            conflicts,
+            [
+                ("foo", vec![(liba, SymbolBinding::Global), (libb, SymbolBinding::Global)]),
+                ("bar", vec![(liba, SymbolBinding::Weak), (libb, SymbolBinding::Global)]),
+            ]
+        );
+        let conflicts = ElfFile::new(&root).symbol_conflicts().unwrap();
+        assert_eq!(conflicts[1].winner().path, liba);
+    }
+
+    #[test]
+    fn test_exported_symbols() {
+        let dir = tempfile::tempdir().unwrap();
+        let libb = ElfBuilder::new()
+            .defines_versioned("foo", "B_1.0", false)
+            .defines_versioned("foo", "B_2.0", true)
+            .write(dir.path(), "libb.so.1");
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libb.so.1")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .imports("foo")
+            .defines_weak("bar")
+            .write(dir.path(), "app");
+
+        let exported = ElfFile::new(&root).exported_symbols().unwrap();
+        let exported: Vec<_> = exported
+            .iter()
+            .map(|object| {
+                let symbols: Vec<_> = object
+                    .symbols
+                    .iter()
+                    .map(|symbol| {
+                        let version = symbol.version.as_deref();
+                        (symbol.name.as_str(), version, symbol.default_version, symbol.binding)
+                    })
+                    .collect();
+                (object.path.as_path(), symbols)
+            })
+            .collect();
+        // The imported foo is not exported by the app
+        assert_eq!(
+            exported,
+            [
+                (root.as_path(), vec![("bar", None, true, SymbolBinding::Weak)]),
+                (
+                    libb.as_path(),
+                    vec![
+                        ("foo", Some("B_1.0"), false, SymbolBinding::Global),
+                        ("foo", Some("B_2.0"), true, SymbolBinding::Global),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new()
+            .defines_version("C_1.0")
+            .write(dir.path(), "libc.so.1");
+        let libb = ElfBuilder::new().defines("bar").write(dir.path(), "libb.so.1");
+        ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libb.so.1")
+            .defines("foo")
+            .imports_weak("bar")
+            .write(dir.path(), "liba.so.1");
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .needed("libc.so.1")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .needs_version("libc.so.1", "C_1.0")
+            .imports("foo")
+            .write(dir.path(), "app");
+
+        let unused = ElfFile::new(&root).unused_dependencies().unwrap();
+        let unused: Vec<_> = unused
+            .iter()
+            .map(|unused| {
+                (unused.soname.as_str(), unused.path.as_path(), unused.needed_by.as_path())
+            })
+            .collect();
+        // liba uses libb, and the executable uses libc for its symbol version alone
+        assert_eq!(unused, [("libb.so.1", libb.as_path(), root.as_path())]);
+    }
+
+    #[test]
+    fn test_weak_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().to_str().unwrap();
+        ElfBuilder::new().defines("foo").write(dir.path(), "libstrong.so.1");
+        let weak = ElfBuilder::new().defines("bar").write(dir.path(), "libweak.so.1");
+        ElfBuilder::new().defines("bar").write(dir.path(), "libversioned.so.1");
+        let root = ElfBuilder::new()
+            .runpath(lib_dir)
+            .needed("libstrong.so.1")
+            .needed("libweak.so.1")
+            .needed("libversioned.so.1")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .needs_version("libversioned.so.1", "V_1.0")
+            .imports("foo")
+            .imports_weak("bar")
+            .write(dir.path(), "app");
+
+        let weak_dependencies = |root: &PathBuf| -> Vec<(String, Option<PathBuf>)> {
+            let weak = ElfFile::new(root).weak_dependencies().unwrap();
+            weak.into_iter()
+                .map(|dependency| (dependency.soname, dependency.path))
+                .collect()
+        };
+        // libversioned provides only the weak bar too, but a symbol version as well
+        let expected = [
+            ("libweak.so.1".to_owned(), Some(weak.clone())),
+            ("libdyn-lib-finder-missing.so.1".to_owned(), None),
+        ];
+        assert_eq!(weak_dependencies(&root), expected);
+
+        // A missing library may have been meant to provide a strong import that nothing does
+        let root = ElfBuilder::new()
+            .runpath(lib_dir)
+            .needed("libdyn-lib-finder-missing.so.1")
+            .imports("baz")
+            .write(dir.path(), "app2");
+        assert!(weak_dependencies(&root).is_empty());
+    }
+
+    #[test]
+    fn test_missing_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new()
+            .defines_version("FOO_1.0")
+            .defines_version("FOO_2.0")
+            .write(dir.path(), "libfoo.so.1");
+        let libbar = ElfBuilder::new().write(dir.path(), "libbar.so.1");
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .needs_version("libfoo.so.1", "FOO_1.0")
+            .needs_version("libfoo.so.1", "FOO_3.0")
+            .needs_version("libbar.so.1", "BAR_1.0")
+            .write(dir.path(), "app");
+
+        let missing = ElfFile::new(&root).missing_versions().unwrap();
+        let missing: Vec<_> = missing
+            .iter()
+            .map(|missing| (missing.version.as_str(), missing.soname.as_str(), &missing.provider))
+            .collect();
+        assert_eq!(
+            missing,
+            [("FOO_3.0", "libfoo.so.1", &libfoo), ("BAR_1.0", "libbar.so.1", &libbar)]
+        );
+        assert!(ElfFile::new("/usr/bin/ls").missing_versions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_libstdcxx_requirements() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        let libstdcxx = ElfBuilder::new()
+            .defines_version("GLIBCXX_3.4")
+            .defines_version("GLIBCXX_3.4.29")
+            .defines_version("CXXABI_1.3")
+            .write(&lib, "libstdc++.so.6");
+        let libfoo = ElfBuilder::new()
+            .runpath("$ORIGIN")
+            .needed("libstdc++.so.6")
+            .needs_version("libstdc++.so.6", "GLIBCXX_3.4.30")
+            .needs_version("libstdc++.so.6", "CXXABI_1.3.13")
+            .write(&lib, "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .runpath(lib.to_str().unwrap())
+            .needed("libfoo.so.1")
+            .needed("libstdc++.so.6")
+            .needs_version("libstdc++.so.6", "GLIBCXX_3.4.29")
+            .needs_version("libstdc++.so.6", "CXXABI_1.3")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let requirements = ElfFile::with_config(&root, config.clone())
+            .libstdcxx_requirements()
+            .unwrap();
+        assert_eq!(requirements.glibcxx.as_deref(), Some("3.4.30"));
+        assert_eq!(requirements.cxxabi.as_deref(), Some("1.3.13"));
+        assert_eq!(requirements.libstdcxx.as_ref(), Some(&libstdcxx));
+        let missing: Vec<_> = requirements
+            .missing
+            .iter()
+            .map(|missing| (missing.version.as_str(), &missing.needed_by))
+            .collect();
+        assert_eq!(missing, [("GLIBCXX_3.4.30", &libfoo), ("CXXABI_1.3.13", &libfoo)]);
+        assert!(!requirements.is_satisfied());
+
+        // Requiring versions of a libstdc++ that is not found is not satisfied either
+        std::fs::remove_file(&libfoo).unwrap();
+        std::fs::remove_file(&libstdcxx).unwrap();
+        let requirements = ElfFile::with_config(&root, config.clone())
+            .libstdcxx_requirements()
+            .unwrap();
+        assert_eq!(requirements.glibcxx.as_deref(), Some("3.4.29"));
+        assert!(requirements.missing.is_empty());
+        assert!(!requirements.is_satisfied());
+        let c = ElfBuilder::new().write(dir.path(), "c-app");
+        assert!(ElfFile::with_config(c, config).libstdcxx_requirements().unwrap().is_satisfied());
+    }
+
+    #[test]
+    fn test_all_deps_resolvable() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let libdeep = ElfBuilder::new().write(sysroot.join("usr/lib"), "libdeep.so.1");
+        ElfBuilder::new()
+            .needed("libdeep.so.1")
+            .write(sysroot.join("usr/lib"), "libsystem.so.1");
+        ElfBuilder::new()
+            .needed("libgone.so.1")
+            .write(sysroot.join("opt/lib"), "libbroken.so.1");
+        let app = ElfBuilder::new()
+            .needed("libsystem.so.1")
+            .auxiliary("libmaybe.so.1")
+            .write(sysroot.join("opt/bin"), "app");
+        let broken = ElfBuilder::new()
+            .runpath("/opt/lib")
+            .needed("libsystem.so.1")
+            .needed("libbroken.so.1")
+            .write(sysroot.join("opt/bin"), "broken");
+        let config = ResolverConfig::new().use_env(false).sysroot(sysroot);
+        let resolver = Arc::new(Resolver::new(config));
+        let check = |path: &Path| {
+            let file = ElfFile::with_resolver(path, resolver.clone());
+            file.all_deps_resolvable().unwrap()
+        };
+
+        assert!(check(&app));
+        assert!(!check(&broken));
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .sysroot(sysroot)
+            .ignore_sonames(["libgone.so.1"]);
+        assert!(ElfFile::with_config(&broken, config).all_deps_resolvable().unwrap());
+        // libsystem had all of its libraries, so they are not searched for again
+        std::fs::remove_file(&libdeep).unwrap();
+        assert!(check(&app));
+        resolver.clear_cache();
+        assert!(!check(&app));
+        let missing = sysroot.join("missing");
+        let error = ElfFile::new(&missing).all_deps_resolvable().unwrap_err();
+        assert!(matches!(error, Error::Io { path, .. } if path == missing));
+    }
+
+    #[test]
+    fn test_minimum_glibc() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        ElfBuilder::new().write(sysroot.join("usr/lib"), "libc.so.6");
+        // Libraries of the system are built for it, so their requirements don't count
+        ElfBuilder::new()
+            .needed("libc.so.6")
+            .needs_version("libc.so.6", "GLIBC_2.38")
+            .write(sysroot.join("usr/lib"), "libsystem.so.1");
+        let libshipped = ElfBuilder::new()
+            .needed("libm.so.6")
+            .needs_version("libm.so.6", "GLIBC_2.29")
+            .needs_version("libm.so.6", "GLIBC_2.3.4")
+            .write(sysroot.join("opt/app/lib"), "libshipped.so.1");
+        let root = ElfBuilder::new()
+            .runpath("/opt/app/lib")
+            .needed("libshipped.so.1")
+            .needed("libsystem.so.1")
+            .needed("libc.so.6")
+            .needs_version("libc.so.6", "GLIBC_2.3.4")
+            .needs_version("libc.so.6", "GLIBC_2.17")
+            .needs_version("libc.so.6", "GLIBC_PRIVATE")
+            .write(sysroot.join("opt/app/bin"), "app");
+
+        let config = ResolverConfig::new().use_env(false).sysroot(sysroot);
+        let elf_file = ElfFile::with_config(&root, config);
+        let glibc = elf_file.minimum_glibc(false).unwrap().unwrap();
+        assert_eq!((glibc.version.as_str(), glibc.soname.as_str()), ("2.17", "libc.so.6"));
+        assert_eq!(glibc.needed_by, root);
+        assert_eq!(glibc.to_string(), "glibc >= 2.17");
+        let glibc = elf_file.minimum_glibc(true).unwrap().unwrap();
+        assert_eq!((glibc.version.as_str(), glibc.soname.as_str()), ("2.29", "libm.so.6"));
+        assert_eq!(glibc.needed_by, libshipped);
+
+        let library = ElfBuilder::new().write(dir.path(), "libplain.so");
+        assert_eq!(ElfFile::new(library).minimum_glibc(true).unwrap(), None);
+    }
+
+    #[test]
+    fn test_origin_rpath() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = ElfBuilder::new().write(dir.path().join("lib64"), "libfoo.so.1");
+        let lib = lib.canonicalize().unwrap();
+        let rpath_root = ElfBuilder::new()
+            .rpath("$ORIGIN/../$LIB")
+            .needed("libfoo.so.1")
+            .write(dir.path().join("bin"), "rpath-app");
+        let runpath_root = ElfBuilder::new()
+            .runpath("${ORIGIN}/../lib64")
+            .needed("libfoo.so.1")
+            .write(dir.path().join("bin"), "runpath-app");
+
+        // Whatever the layout of the host
+        let config = ResolverConfig::new().lib_token("lib64");
+        for root in [rpath_root, runpath_root] {
+            let libs = ElfFile::with_config(root, config.clone()).get_libs_full_paths().unwrap();
+            assert!(libs.iter().any(|found| found.canonicalize().unwrap() == lib));
+        }
+    }
+
+    #[test]
+    fn test_multiarch_lib_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let libfoo =
+            ElfBuilder::new().write(sysroot.join("usr/lib/x86_64-linux-gnu"), "libfoo.so.1");
+        ElfBuilder::new().write(sysroot.join("opt/app/lib/x86_64-linux-gnu"), "libbar.so.1");
+        let libbar = sysroot.join("opt/app/bin/../lib/x86_64-linux-gnu/libbar.so.1");
+        let root = ElfBuilder::new()
+            .runpath("$ORIGIN/../$LIB:/usr/$LIB")
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .write(sysroot.join("opt/app/bin"), "app");
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .sysroot(sysroot);
+        let libs = ElfFile::with_config(root, config).get_libs_full_paths().unwrap();
+        assert_eq!(libs, [libfoo, libbar]);
+    }
+
+    #[test]
+    fn test_secure_execution() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new().write(dir.path().join("lib"), "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .rpath("$ORIGIN/lib")
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+        let resolve = |config: ResolverConfig| {
+            let config = config.without_default_dirs().use_env(false);
+            ElfFile::with_config(&root, config).get_libs_resolutions().unwrap()
+        };
+        let found = |resolutions: Vec<(String, crate::Resolution)>| resolutions[0].1.is_ok();
+
+        assert!(found(resolve(ResolverConfig::new())));
+        assert!(!found(resolve(ResolverConfig::new().secure_execution(true))));
+        // Detected from the set-user-ID bit
+        std::fs::set_permissions(&root, std::fs::Permissions::from_mode(0o4755)).unwrap();
+        assert!(!found(resolve(ResolverConfig::new())));
+        assert!(found(resolve(ResolverConfig::new().secure_execution(false))));
+    }
+
+    #[test]
+    fn test_nodeflib() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = || ResolverConfig::new().append_search_dir(dir.path().join("vendor"));
+        ElfBuilder::new().write(dir.path().join("vendor"), "libvendor.so.1");
+        let root = ElfBuilder::new()
+            .flags_1(crate::abi::DF_1_NODEFLIB)
+            .needed("libvendor.so.1")
+            .needed("libc.so.6")
+            .write(dir.path(), "app");
+        let resolutions = ElfFile::with_config(&root, config()).get_libs_resolutions().unwrap();
+        assert_eq!(resolutions.len(), 2);
+        assert!(resolutions[0].1.is_ok());
+        assert!(matches!(&resolutions[1], (soname, Err(_)) if soname == "libc.so.6"));
+
+        // Without the flag, libc.so.6 and its own dependencies are found in the system dirs
+        let root = ElfBuilder::new().needed("libc.so.6").write(dir.path(), "app");
+        let resolutions = ElfFile::with_config(&root, config()).get_libs_resolutions().unwrap();
+        assert!(resolutions.iter().all(|(_, resolution)| resolution.is_ok()));
+    }
+
+    #[test]
+    fn test_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new().write(dir.path(), "libfiltee.so.1");
+        ElfBuilder::new().write(dir.path(), "libaux.so.1");
+        ElfBuilder::new()
+            .filter("libfiltee.so.1")
+            .auxiliary("libaux.so.1:libdyn-lib-finder-missing.so.1")
+            .write(dir.path(), "libfilter.so.1");
+        let root = ElfBuilder::new().needed("libfilter.so.1").write(dir.path(), "app");
+        let config = ResolverConfig::new().with_search_dirs([dir.path()]).use_env(false);
+        let elf_file = ElfFile::with_config(root, config.without_default_dirs());
+
+        // The missing auxiliary filtee is left out
+        let expected = [
+            ("libfilter.so.1", DependencyKind::Needed),
+            ("libfiltee.so.1", DependencyKind::Filter),
+            ("libaux.so.1", DependencyKind::Auxiliary),
+        ];
+        let graph = elf_file.dependency_graph().unwrap();
+        let nodes: Vec<_> = graph.nodes()[1..]
+            .iter()
+            .map(|node| (node.soname.as_str(), node.kind))
+            .collect();
+        assert_eq!(nodes, expected);
+        assert!(graph.nodes().iter().all(|node| node.resolution.is_ok()));
+        let dependencies: Vec<_> = elf_file
+            .iter_dependencies(TraversalOrder::DepthFirst)
+            .unwrap()
+            .map(|dependency| (dependency.soname, dependency.kind))
+            .collect();
+        assert_eq!(dependencies, expected.map(|(soname, kind)| (soname.to_owned(), kind)));
+    }
+
+    #[test]
+    fn test_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().join(OsStr::from_bytes(b"lib\xff"));
+        let soname = OsStr::from_bytes(b"libf\xfeo.so.1");
+        let lib = ElfBuilder::new().write(&lib_dir, soname);
+        let rpath = OsStr::from_bytes(b"$ORIGIN/lib\xff");
+        let root = ElfBuilder::new().needed(soname).rpath(rpath).write(dir.path(), "app");
+
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let elf_file = ElfFile::with_config(root, config);
+        assert_eq!(elf_file.needed_sonames().unwrap(), ["libf\u{fffd}o.so.1"]);
+        assert_eq!(elf_file.get_libs_full_paths().unwrap(), [lib]);
+        // LD_LIBRARY_PATH entries need not be UTF-8 either
+        let order = search_order(&HostFs, &[], Some(lib_dir.as_os_str()), true, &[]);
+        assert_eq!(order, [(lib_dir, SearchRule::LdLibraryPath)]);
+    }
+
+    #[test]
+    fn test_search_order_tiers() {
+        let dir = tempfile::tempdir().unwrap();
+        let [rpath, env, runpath] = ["rpath", "env", "runpath"].map(|name| {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+            dir.path().join(name)
+        });
+        let owner = dir.path().join("app");
+        let rpath = [(rpath, SearchRule::Rpath { owner: owner.clone() })];
+        let runpath = [(runpath, SearchRule::Runpath { owner })];
+        let env_var = env.as_os_str();
+        let env = (env.clone(), SearchRule::LdLibraryPath);
+
+        // RPATH comes before LD_LIBRARY_PATH...
+        let order = search_order(&HostFs, &rpath, Some(env_var), true, &[]);
+        assert_eq!(order, [rpath[0].clone(), env.clone()]);
+        // ...but is ignored entirely in favour of a RUNPATH, which comes after LD_LIBRARY_PATH
+        let order = search_order(&HostFs, &rpath, Some(env_var), true, &runpath);
+        assert_eq!(order, [env, runpath[0].clone()]);
+    }
+
+    #[test]
+    fn test_empty_ld_library_path_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env_var = OsString::from(":");
+        env_var.push(dir.path());
+        let cwd = (PathBuf::from("."), SearchRule::LdLibraryPath);
+        let lib_dir = (dir.path().to_owned(), SearchRule::LdLibraryPath);
+        let order = search_order(&HostFs, &[], Some(&env_var), true, &[]);
+        assert_eq!(order, [cwd, lib_dir.clone()]);
+        // Treated as a lint, the empty entry is not searched
+        assert_eq!(search_order(&HostFs, &[], Some(&env_var), false, &[]), [lib_dir]);
+        // An empty LD_LIBRARY_PATH has no entries at all
+        assert_eq!(search_order(&HostFs, &[], Some(OsStr::new("")), true, &[]), []);
+    }
+
+    #[test]
+    fn test_found_by() {
+        let dir = tempfile::tempdir().unwrap();
+        let private = dir.path().join("private");
+        let mid = ElfBuilder::new().needed("libleaf.so.1").write(&private, "libmid.so.1");
+        let leaf = ElfBuilder::new().write(&private, "libleaf.so.1");
+        let custom = ElfBuilder::new().write(dir.path().join("custom"), "libcustom.so.1");
+        let path = ElfBuilder::new().write(dir.path().join("path"), "libpath.so.1");
+        let root = ElfBuilder::new()
+            .rpath(private.to_str().unwrap())
+            .needed("libmid.so.1")
+            .needed("libcustom.so.1")
+            .needed(&path)
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .append_search_dir(dir.path().join("custom"));
+        let graph = ElfFile::with_config(&root, config).dependency_graph().unwrap();
+        let found_by = |path: &PathBuf| graph.node(graph.find_path(path).unwrap()).found_by.clone();
+        // libleaf is found through the RPATH libmid inherits from the root
+        let rpath = SearchRule::Rpath { owner: root.clone() };
+        assert_eq!(found_by(&mid), Some(rpath.clone()));
+        assert_eq!(found_by(&leaf), Some(rpath));
+        assert_eq!(found_by(&custom), Some(SearchRule::SearchDir));
+        assert_eq!(found_by(&path), Some(SearchRule::Path));
+        assert_eq!(graph.node(graph.root()).found_by, None);
+    }
+
+    #[test]
+    fn test_working_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = dir.path().join("work");
+        let libfoo = ElfBuilder::new().write(work.join("lib"), "libfoo.so.1");
+        let libbar = ElfBuilder::new().write(&work, "libbar.so.1");
+        let libbaz = ElfBuilder::new().write(work.join("plugins"), "libbaz.so.1");
+        let app = ElfBuilder::new()
+            .runpath("plugins")
+            .needed("lib/libfoo.so.1")
+            .needed("./libbar.so.1")
+            .needed("libbaz.so.1")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .working_dir(&work);
+        let graph = ElfFile::with_config(&app, config).dependency_graph().unwrap();
+        let libs: Vec<_> = graph.nodes()[1..]
+            .iter()
+            .map(|node| (node.path(), node.found_by.clone()))
+            .collect();
+        let runpath = SearchRule::Runpath { owner: app.clone() };
+        assert_eq!(
+            libs,
+            [
+                (Some(libfoo.as_path()), Some(SearchRule::Path)),
+                (Some(libbar.as_path()), Some(SearchRule::Path)),
+                (Some(libbaz.as_path()), Some(runpath)),
+            ]
+        );
+
+        // Relative to the working directory of this process otherwise
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let graph = ElfFile::with_config(&app, config).dependency_graph().unwrap();
+        assert!(graph.nodes()[1..].iter().all(|node| node.path().is_none()));
+    }
+
+    #[test]
+    fn test_exhaustive_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let arm = ElfBuilder::new().machine(crate::abi::EM_AARCH64);
+        let (a, b, c) = (dir.path().join("a"), dir.path().join("b"), dir.path().join("c"));
+        let wrong_arch = arm.write(&a, "libfoo.so.1");
+        arm.write(&a, "libbar.so.1");
+        let chosen = ElfBuilder::new().write(&b, "libfoo.so.1");
+        let shadowed = ElfBuilder::new().write(&c, "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .rpath(format!("{}:{}", a.display(), b.display()))
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let config = config.append_search_dir(&c).append_search_dir(&b);
+        let elf_file = ElfFile::with_config(&root, config.clone().exhaustive_search(true));
+        let graph = elf_file.dependency_graph().unwrap();
+        let foo = graph.node(graph.find_path(&chosen).unwrap());
+        let candidates: Vec<_> = foo
+            .candidates
+            .iter()
+            .map(|candidate| (&candidate.path, candidate.compatible, candidate.chosen))
+            .collect();
+        assert_eq!(
+            candidates,
+            [(&wrong_arch, false, false), (&chosen, true, true), (&shadowed, true, false)]
+        );
+        let rpath = SearchRule::Rpath { owner: root.clone() };
+        assert_eq!(foo.candidates[1].found_by, rpath);
+        assert_eq!(foo.candidates[2].found_by, SearchRule::SearchDir);
+        // Libraries that can't be loaded still list what was found
+        let bar = &graph.nodes()[2];
+        assert!(bar.resolution.is_err());
+        assert_eq!(bar.candidates.len(), 1);
+        assert!(!bar.candidates[0].compatible);
+
+        let graph = ElfFile::with_config(&root, config).dependency_graph().unwrap();
+        assert!(graph.nodes().iter().all(|node| node.candidates.is_empty()));
+    }
+
+    #[test]
+    fn test_trace_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        let wrong_arch = ElfBuilder::new().elf32().write(&a, "libfoo.so.1");
+        let found = ElfBuilder::new().write(&b, "libfoo.so.1");
+        ElfBuilder::new().write(dir.path().join("c"), "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .runpath(&format!("{}:{}", a.display(), b.display()))
+            .needed("libfoo.so.1")
+            .needed("libmissing.so.1")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .hwcaps(["x86-64-v2"])
+            .append_search_dir(dir.path().join("c"))
+            .trace_search(true);
+        let graph = ElfFile::with_config(&root, config).dependency_graph().unwrap();
+        let steps = |node: &DependencyNode| -> Vec<_> {
+            let steps = node.search_trace.iter();
+            steps.map(|step| (step.path.clone(), step.result)).collect()
+        };
+        let hwcaps = |dir: &Path| dir.join("glibc-hwcaps/x86-64-v2/libfoo.so.1");
+        // The search stops at the library that is found
+        assert_eq!(
+            steps(&graph.nodes()[1]),
+            [
+                (hwcaps(&a), ProbeResult::NotFound),
+                (wrong_arch, ProbeResult::WrongClass),
+                (hwcaps(&b), ProbeResult::NotFound),
+                (found, ProbeResult::Found),
+            ]
+        );
+        let missing = steps(&graph.nodes()[2]);
+        assert_eq!(missing.len(), 6);
+        assert!(missing.iter().all(|(_, result)| *result == ProbeResult::NotFound));
+        assert_eq!(graph.nodes()[2].search_trace[5].found_by, SearchRule::SearchDir);
+        assert!(graph.to_search_trace().contains("  trying file="));
+    }
+
+    #[test]
+    fn test_record_search_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let [env, vendor, custom] = ["env", "vendor", "custom"].map(|name| dir.path().join(name));
+        std::fs::create_dir(&env).unwrap();
+        ElfBuilder::new().write(&vendor, "libbar.so.1");
+        ElfBuilder::new()
+            .runpath("$ORIGIN/../custom")
+            .needed("libbar.so.1")
+            .write(&vendor, "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .rpath("$ORIGIN/vendor")
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new()
+            .environment([("LD_LIBRARY_PATH", &env)])
+            .without_default_dirs()
+            .append_search_dir(&custom)
+            .record_search_paths(true);
+        let graph = ElfFile::with_config(&root, config).dependency_graph().unwrap();
+        let search_path = |node: &DependencyNode| -> Vec<_> {
+            let entries = node.search_path.iter();
+            entries.map(|entry| (entry.path.clone(), entry.found_by.clone())).collect()
+        };
+        let rpath = SearchRule::Rpath { owner: root.clone() };
+        assert_eq!(
+            search_path(&graph.nodes()[0]),
+            [
+                (vendor.clone(), rpath),
+                (env.clone(), SearchRule::LdLibraryPath),
+                (custom.clone(), SearchRule::SearchDir),
+            ]
+        );
+        // With a RUNPATH of its own, the RPATH chain is not searched for the library's
+        let foo = vendor.join("libfoo.so.1");
+        let runpath = SearchRule::Runpath { owner: foo };
+        assert_eq!(
+            search_path(&graph.nodes()[1]),
+            [
+                (env, SearchRule::LdLibraryPath),
+                (vendor.join("../custom"), runpath),
+                (custom, SearchRule::SearchDir),
+            ]
+        );
+        // libbar needs nothing, so nothing is searched for it
+        assert!(graph.nodes()[2].search_path.is_empty());
+        assert!(graph.to_search_paths().contains(" (LD_LIBRARY_PATH)\n"));
+    }
+
+    #[test]
+    fn test_content_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new().soname("libfoo.so.1").write(dir.path(), "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libfoo.so.1")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .write(dir.path(), "app");
+        let sha256 = |path: &Path| crate::digest::sha256_hex(&std::fs::read(path).unwrap());
+        let config = ResolverConfig::new().use_env(false);
+
+        let digests = |config: ResolverConfig| {
+            let file = ElfFile::with_config(&root, config);
+            let graph = file.dependency_graph().unwrap();
+            let nodes: Vec<_> = graph.nodes().iter().map(|node| node.digest.clone()).collect();
+            let dependencies = file.iter_dependencies(TraversalOrder::DepthFirst).unwrap();
+            let dependencies: Vec<_> = dependencies.map(|dependency| dependency.digest).collect();
+            (nodes, dependencies)
+        };
+        let (nodes, dependencies) = digests(config.clone().content_digest(ContentDigest::Sha256));
+        assert_eq!(nodes, [Some(sha256(&root)), Some(sha256(&libfoo)), None]);
+        assert_eq!(dependencies, [Some(sha256(&libfoo)), None]);
+
+        let length = ContentDigest::custom(|data| data.len().to_string());
+        let (nodes, _) = digests(config.clone().content_digest(length));
+        let length = std::fs::metadata(&libfoo).unwrap().len().to_string();
+        assert_eq!(nodes[1], Some(length));
+
+        let (nodes, dependencies) = digests(config);
+        assert!(nodes.iter().chain(&dependencies).all(Option::is_none));
+    }
+
+    #[test]
+    fn test_stat_files() {
+        use crate::FileSystem;
+        use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+        use std::time::{Duration, SystemTime};
+
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new().write(dir.path(), "libfoo.so.1.2");
+        std::fs::set_permissions(&libfoo, std::fs::Permissions::from_mode(0o750)).unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let file = std::fs::File::options().write(true).open(&libfoo).unwrap();
+        file.set_modified(modified).unwrap();
+        symlink("libfoo.so.1.2", dir.path().join("libfoo.so.1")).unwrap();
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libfoo.so.1")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new().use_env(false).stat_files(true);
+
+        // The stat is that of the file the symlink leads to
+        let file = ElfFile::with_config(&root, config.clone());
+        let graph = file.dependency_graph().unwrap();
+        let metadata = std::fs::metadata(&libfoo).unwrap();
+        let expected = FileStat {
+            len: metadata.len(),
+            mode: 0o750,
+            uid: Some(metadata.uid()),
+            gid: Some(metadata.gid()),
+            modified: Some(modified),
+        };
+        assert_eq!(graph.nodes()[1].stat, Some(expected));
+        assert_eq!(graph.nodes()[2].stat, None);
+        let root_len = graph.nodes()[0].stat.map(|stat| stat.len);
+        assert_eq!(root_len, Some(std::fs::metadata(&root).unwrap().len()));
+        let dependencies = file.iter_dependencies(TraversalOrder::DepthFirst).unwrap();
+        let stats: Vec<_> = dependencies.map(|dependency| dependency.stat).collect();
+        assert_eq!(stats, [Some(expected), None]);
+
+        let graph = ElfFile::with_config(&root, config.stat_files(false))
+            .dependency_graph()
+            .unwrap();
+        assert!(graph.nodes().iter().all(|node| node.stat.is_none()));
+
+        // Filesystems that don't know the owner or modification time leave them out
+        let fs = MemoryFs::default()
+            .file("/lib/libfoo.so.1.2", b"data")
+            .symlink("/lib/libfoo.so.1", "libfoo.so.1.2");
+        let stat = fs.stat(Path::new("/lib/libfoo.so.1")).unwrap();
+        let expected = FileStat { len: 4, mode: 0o755, uid: None, gid: None, modified: None };
+        assert_eq!(stat, expected);
+    }
+
+    #[test]
+    fn test_build_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().to_str().unwrap();
+        let liba = ElfBuilder::new().build_id(&[0xaa; 20]).write(dir.path(), "liba.so.1");
+        ElfBuilder::new().write(dir.path(), "libb.so.1");
+        let root = ElfBuilder::new()
+            .build_id(&[0x01, 0x23])
+            .runpath(lib_dir)
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .write(dir.path(), "app.bin");
+
+        let elf_file = ElfFile::new(&root);
+        let graph = elf_file.dependency_graph().unwrap();
+        let build_id = |path| graph.node(graph.find_path(path).unwrap()).build_id.as_deref();
+        assert_eq!(build_id(&root), Some("0123"));
+        assert_eq!(build_id(&liba), Some("aa".repeat(20).as_str()));
+        let dependencies = elf_file.iter_dependencies(TraversalOrder::BreadthFirst).unwrap();
+        let build_ids: Vec<_> = dependencies.map(|dependency| dependency.build_id).collect();
+        assert_eq!(build_ids, [Some("aa".repeat(20)), None]);
+    }
+
+    #[test]
+    fn test_dynamic_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().to_str().unwrap();
+        let liba = ElfBuilder::new()
+            .soname("liba.so.1")
+            .rpath("$ORIGIN/../lib")
+            .needed("libc.so.6")
+            .value(crate::abi::DT_FLAGS, crate::abi::DF_ORIGIN as u64)
+            .write(dir.path(), "liba.so.1");
+        let root = ElfBuilder::new()
+            .runpath(&format!("$ORIGIN:{lib_dir}"))
+            .flags_1(crate::abi::DF_1_NOW)
+            .needed("liba.so.1")
+            .needed("libmissing.so.1")
+            .write(dir.path(), "app");
+
+        let elf_file = ElfFile::with_config(&root, ResolverConfig::new().max_depth(1));
+        let info = elf_file.dynamic_info().unwrap();
+        let expected = DynamicInfo {
+            needed: vec!["liba.so.1".to_owned(), "libmissing.so.1".to_owned()],
+            rpath: None,
+            runpath: Some(format!("$ORIGIN:{lib_dir}")),
+            soname: None,
+            flags: DynamicFlags::empty(),
+            flags_1: DynamicFlags1::NOW,
+        };
+        assert_eq!(info, expected);
+
+        let graph = elf_file.dependency_graph().unwrap();
+        let dynamic = |id: NodeId| graph.node(id).dynamic.clone();
+        assert_eq!(dynamic(graph.root()), Some(expected));
+        let expected = DynamicInfo {
+            needed: vec!["libc.so.6".to_owned()],
+            rpath: Some("$ORIGIN/../lib".to_owned()),
+            runpath: None,
+            soname: Some("liba.so.1".to_owned()),
+            flags: DynamicFlags::ORIGIN,
+            flags_1: DynamicFlags1::empty(),
+        };
+        assert_eq!(dynamic(graph.find_path(&liba).unwrap()), Some(expected.clone()));
+        let missing = graph.nodes().iter().find(|node| node.soname == "libmissing.so.1");
+        assert_eq!(missing.unwrap().dynamic, None);
+        let mut dependencies = elf_file.iter_dependencies(TraversalOrder::BreadthFirst).unwrap();
+        assert_eq!(dependencies.next().unwrap().dynamic, Some(expected));
+    }
+
+    #[test]
+    fn test_debug_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().join("lib");
+        let liba = ElfBuilder::new().build_id(&[0xaa; 20]).write(&lib_dir, "liba.so.1");
+        let libb = ElfBuilder::new().write(&lib_dir, "libb.so.1");
+        let root = ElfBuilder::new()
+            .build_id(&[0x01, 0x23, 0x45])
+            .runpath(lib_dir.to_str().unwrap())
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .needed("libmissing.so.1")
+            .write(dir.path(), "app.bin");
+        let debug_dir = dir.path().join("debug");
+        let debug_file = ElfBuilder::new().write(debug_dir.join(".build-id/01"), "2345.debug");
+
+        // The libraries are read for their build IDs even if the graph would not be recursive
+        let config = ResolverConfig::new().recursive(false);
+        let elf_file = ElfFile::with_config(&root, config);
+        let locator = DebugInfoLocator::new().debug_dir(&debug_dir);
+        let debug_info = elf_file.debug_info(&locator).unwrap();
+        assert_eq!(
+            debug_info,
+            [
+                (root, Some(DebugInfo::File(debug_file))),
+                (liba, None),
+                (libb.clone(), None),
+            ]
+        );
+        let locator = locator.debuginfod_urls("https://debuginfod.example");
+        let url = format!("https://debuginfod.example/buildid/{}/debuginfo", "aa".repeat(20));
+        let debug_info = elf_file.debug_info(&locator).unwrap();
+        assert_eq!(debug_info[1].1, Some(DebugInfo::Debuginfod(vec![url])));
+        assert_eq!(debug_info[2], (libb, None));
+    }
+
+    #[test]
+    fn test_hardening_report() {
+        use crate::abi::{PF_R, PF_W, PT_GNU_STACK};
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().to_str().unwrap();
+        let stack = |builder: ElfBuilder| builder.segment(PT_GNU_STACK, PF_R | PF_W);
+        let liba = stack(ElfBuilder::new()).runpath(lib_dir).needed("libb.so.1");
+        let liba = liba.write(dir.path(), "liba.so.1");
+        // An old library without PT_GNU_STACK, which makes the whole process's stack executable
+        let libb = ElfBuilder::new().write(dir.path(), "libb.so.1");
+        let root = stack(ElfBuilder::new())
+            .interpreter("/lib64/ld-linux-x86-64.so.2")
+            .runpath(lib_dir)
+            .needed("liba.so.1")
+            .needed("libmissing.so.1")
+            .write(dir.path(), "app");
+
+        let report = ElfFile::new(&root).hardening_report().unwrap();
+        let paths: Vec<_> = report.objects.iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, [&root, &liba, &libb]);
+        assert!(report.objects[0].1.nx);
+        assert_eq!(report.objects[0].1.pie, Pie::Enabled);
+        let weakest = report.weakest().unwrap();
+        assert!(!weakest.nx);
+        assert_eq!(weakest.pie, Pie::Enabled);
+    }
+
+    #[test]
+    fn test_insecure_runpaths() {
+        let dir = tempfile::tempdir().unwrap();
+        let liba = ElfBuilder::new().rpath("/var/tmp/build").write(dir.path(), "liba.so.1");
+        // The test files are below /tmp, so only entries relative to $ORIGIN are not flagged
+        let root = ElfBuilder::new().runpath("$ORIGIN:lib").needed("liba.so.1");
+        let root = root.write(dir.path(), "app");
+
+        let config = ResolverConfig::new().environment([("LD_LIBRARY_PATH", ":/opt/lib")]);
+        let findings = ElfFile::with_config(&root, config).insecure_runpaths().unwrap();
+        let findings: Vec<_> = findings
+            .iter()
+            .map(|finding| (finding.object.as_path(), finding.tag, finding.issue))
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                (root.as_path(), RunpathTag::LdLibraryPath, RunpathIssue::Empty),
+                (root.as_path(), RunpathTag::Runpath, RunpathIssue::Relative),
+                (liba.as_path(), RunpathTag::Rpath, RunpathIssue::Temporary),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lints() {
+        let dir = tempfile::tempdir().unwrap();
+        let liba = ElfBuilder::new().needed("libc.so.6").needed("libc.so.6");
+        let liba = liba.write(dir.path(), "liba.so.1");
+        let root = ElfBuilder::new()
+            .rpath("$ORIGIN")
+            .runpath("$ORIGIN:$ORIGIN/lib")
+            .needed("liba.so.1")
+            .build();
+
+        let config = ResolverConfig::new().use_env(false);
+        let elf_file = ElfFile::from_bytes(dir.path().join("app"), root, config);
+        let lints: Vec<_> = elf_file
+            .lints()
+            .unwrap()
+            .into_iter()
+            .map(|lint| (lint.object, lint.kind, lint.subject))
+            .collect();
+        assert_eq!(
+            lints,
+            [
+                (dir.path().join("app"), LintKind::RpathAndRunpath, None),
+                (
+                    dir.path().join("app"),
+                    LintKind::MissingRunpathDir,
+                    Some("$ORIGIN/lib".to_owned())
+                ),
+                (liba, LintKind::DuplicateNeeded, Some("libc.so.6".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filesystem() {
+        let library = ElfBuilder::new().build();
+        let app = ElfBuilder::new().needed("libfoo.so.1").needed("libbar.so.1").build();
+        let fs = MemoryFs::default()
+            .file("/usr/bin/app", &app)
+            .file("/usr/lib/libfoo.so.1.2", &library)
+            .symlink("/usr/lib/libfoo.so.1", "libfoo.so.1.2")
+            .file("/opt/bar/lib/libbar.so.1", &library)
+            .file("/etc/ld.so.conf", b"/opt/bar/lib\n");
+        let config = ResolverConfig::new().use_env(false).filesystem(Arc::new(fs));
+        let elf_file = ElfFile::with_config("/usr/bin/app", config);
+        assert_eq!(elf_file.needed_sonames().unwrap(), ["libfoo.so.1", "libbar.so.1"]);
+        let expected = [
+            ("libfoo.so.1", "/usr/lib/libfoo.so.1"),
+            ("libbar.so.1", "/opt/bar/lib/libbar.so.1"),
+        ]
+        .map(|(soname, path)| (soname.to_owned(), PathBuf::from(path)));
+        assert_eq!(elf_file.get_libs_with_sonames().unwrap(), expected);
+        let graph = elf_file.dependency_graph().unwrap();
+        let foo = graph.find_path("/usr/lib/libfoo.so.1").unwrap();
+        assert_eq!(graph.node(foo).links, [PathBuf::from("/usr/lib/libfoo.so.1.2")]);
+        let stats = elf_file.closure_stats().unwrap();
+        assert_eq!(stats.file_size, app.len() as u64);
+        assert_eq!(stats.total_size, 2 * library.len() as u64);
+
+        // With a sysroot, the sysroot is a directory of the file system
+        let fs = MemoryFs::default()
+            .file("/image/usr/bin/app", &app)
+            .file("/image/usr/lib/libfoo.so.1", &library)
+            .file("/usr/lib/libbar.so.1", &library);
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .sysroot("/image")
+            .filesystem(Arc::new(fs));
+        let resolutions = ElfFile::with_config("/image/usr/bin/app", config)
+            .get_libs_resolutions()
+            .unwrap();
+        let found = resolutions[0].1.as_ref().ok().and_then(Resolved::path);
+        assert_eq!(found, Some(Path::new("/image/usr/lib/libfoo.so.1")));
+        assert!(resolutions[1].1.is_err());
+        // LD_LIBRARY_PATH names directories of the file system, whatever the host has
+        let fs = MemoryFs::default()
+            .file("/usr/bin/app", &app)
+            .file("/opt/env/libfoo.so.1", &library)
+            .file("/opt/env/libbar.so.1", &library);
+        let config = ResolverConfig::new()
+            .environment([("LD_LIBRARY_PATH", "/opt/env")])
+            .filesystem(Arc::new(fs));
+        let libs = ElfFile::with_config("/usr/bin/app", config).get_libs_full_paths().unwrap();
+        assert_eq!(libs, ["/opt/env/libfoo.so.1", "/opt/env/libbar.so.1"].map(PathBuf::from));
+        // Nothing is read from the host
+        let config = ResolverConfig::new().filesystem(Arc::new(MemoryFs::default()));
+        let host_file = std::env::current_exe().unwrap();
+        assert!(matches!(
+            ElfFile::with_config(host_file, config).dependency_graph(),
+            Err(Error::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new().write(dir.path().join("lib"), "libfoo.so.1");
+        let data = ElfBuilder::new()
+            .runpath("$ORIGIN/../lib")
+            .needed("libfoo.so.1")
+            .build();
+        // The file is only in memory, but $ORIGIN is the directory of the path it was given
+        let path = dir.path().join("bin/app");
+        std::fs::create_dir(dir.path().join("bin")).unwrap();
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .content_digest(ContentDigest::Sha256);
+        let elf_file = ElfFile::from_bytes(&path, data.clone(), config.clone());
+        assert_eq!(elf_file.needed_sonames().unwrap(), ["libfoo.so.1"]);
+        let graph = elf_file.dependency_graph().unwrap();
+        assert_eq!(graph.node(graph.root()).path(), Some(path.as_path()));
+        let sha256 = crate::digest::sha256_hex(&data);
+        assert_eq!(graph.node(graph.root()).digest, Some(sha256));
+        let libs = elf_file.get_libs_full_paths().unwrap();
+        assert_eq!(libs, [dir.path().join("bin/../lib/libfoo.so.1")]);
+        let dependencies = elf_file.iter_dependencies(TraversalOrder::DepthFirst).unwrap();
+        assert_eq!(dependencies.count(), 1);
+        assert_eq!(elf_file.closure_stats().unwrap().file_size, data.len() as u64);
+        assert_eq!(elf_file.hardening_report().unwrap().objects.len(), 2);
+        assert!(!path.exists() && libfoo.exists());
+
+        let mut reader = std::io::Cursor::new(data);
+        reader.set_position(10);
+        let elf_file = ElfFile::from_reader(&path, reader, config.clone()).unwrap();
+        assert_eq!(elf_file.get_libs_full_paths().unwrap(), libs);
+        let elf_file = ElfFile::from_bytes(&path, &b"not an ELF file"[..], config);
+        assert!(matches!(elf_file.dependency_graph(), Err(Error::Parse { .. })));
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let liba = ElfBuilder::new().needed("libc.so").write(dir.path(), "liba.so");
+        ElfBuilder::new().write(dir.path(), "libc.so");
+        let big = ElfBuilder::new()
+            .runpath(&"/nonexistent".repeat(100))
+            .write(dir.path(), "libbig.so");
+        let app = ElfBuilder::new()
+            .needed("liba.so")
+            .needed("libbig.so")
+            .write(dir.path(), "app");
+        let size = |path: &Path| std::fs::metadata(path).unwrap().len();
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path());
+        let graph = |config: &ResolverConfig| {
+            ElfFile::with_config(&app, config.clone()).dependency_graph()
+        };
+        let sonames = |graph: &DependencyGraph| -> Vec<String> {
+            graph.nodes().iter().map(|node| node.soname.clone()).collect()
+        };
+        let full = graph(&config).unwrap();
+        assert_eq!(sonames(&full), ["app", "liba.so", "libc.so", "libbig.so"]);
+        assert_eq!(full.exceeded_limit(), None);
+
+        // The analyzed file fails, a library is recorded as failing to load
+        let small = config.clone().max_file_size(size(&app) - 1);
+        let limit = ResourceLimit::FileSize(size(&app) - 1);
+        assert!(matches!(graph(&small), Err(Error::LimitExceeded { limit: l, .. }) if l == limit));
+        let data = std::fs::read(&app).unwrap();
+        let reader = std::io::Cursor::new(data.clone());
+        let from_reader = ElfFile::from_reader(&app, reader, small.clone());
+        assert!(matches!(from_reader, Err(Error::LimitExceeded { .. })));
+        let from_bytes = ElfFile::from_bytes(&app, data, small).dependency_graph();
+        assert!(matches!(from_bytes, Err(Error::LimitExceeded { .. })));
+        assert!(size(&big) > size(&app));
+        let graph_at = graph(&config.clone().max_file_size(size(&app))).unwrap();
+        let node = graph_at.nodes().iter().find(|node| node.soname == "libbig.so");
+        assert!(matches!(
+            &node.unwrap().resolution,
+            Err(ResolveError::Load(Error::LimitExceeded { path, .. })) if *path == big
+        ));
+        assert_eq!(graph_at.exceeded_limit(), None);
+
+        // Collecting stops at the limit, holding what was collected until then
+        let few = config.clone().max_dependencies(2);
+        let graph_few = graph(&few).unwrap();
+        assert_eq!(sonames(&graph_few), ["app", "liba.so", "libc.so"]);
+        assert_eq!(graph_few.exceeded_limit(), Some(ResourceLimit::Dependencies(2)));
+        assert!(!graph_few.is_interrupted());
+        let mut dependencies = ElfFile::with_config(&app, few)
+            .iter_dependencies(TraversalOrder::DepthFirst)
+            .unwrap();
+        assert_eq!(dependencies.by_ref().count(), 2);
+        assert_eq!(dependencies.exceeded_limit(), Some(ResourceLimit::Dependencies(2)));
+
+        // libc is found, but not read
+        let bytes = size(&app) + size(&liba);
+        let little = config.max_bytes_parsed(bytes);
+        let graph_little = graph(&little).unwrap();
+        assert_eq!(sonames(&graph_little), ["app", "liba.so", "libc.so"]);
+        assert_eq!(graph_little.exceeded_limit(), Some(ResourceLimit::BytesParsed(bytes)));
+        assert!(graph_little.nodes()[2].header.is_none());
+        let mut dependencies = ElfFile::with_config(&app, little)
+            .iter_dependencies(TraversalOrder::DepthFirst)
+            .unwrap();
+        assert_eq!(dependencies.next().unwrap().soname, "liba.so");
+        assert!(dependencies.next().is_none());
+        assert_eq!(dependencies.exceeded_limit(), Some(ResourceLimit::BytesParsed(bytes)));
+    }
+
+    #[test]
+    fn test_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let env_dir = dir.path().join("env");
+        let foo = ElfBuilder::new().write(&env_dir, "libfoo.so.1");
+        let preload = ElfBuilder::new().write(&env_dir, "libpre.so");
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+        let environment = [
+            ("LD_LIBRARY_PATH", env_dir.as_os_str()),
+            ("LD_PRELOAD", OsStr::new("libpre.so")),
+        ];
+        let config = ResolverConfig::new()
+            .without_default_dirs()
+            .environment(environment);
+        let libs = ElfFile::with_config(&app, config.clone())
+            .get_libs_full_paths()
+            .unwrap();
+        assert_eq!(libs, [preload, foo]);
+        let graph = ElfFile::with_config(&app, config.clone())
+            .dependency_graph()
+            .unwrap();
+        let foo = graph.nodes().iter().find(|node| node.soname == "libfoo.so.1");
+        assert_eq!(foo.unwrap().found_by, Some(SearchRule::LdLibraryPath));
+
+        // Nothing is taken from the environment without use_env
+        let resolutions = ElfFile::with_config(&app, config.use_env(false))
+            .get_libs_resolutions()
+            .unwrap();
+        assert_eq!(resolutions.len(), 1);
+        assert!(resolutions[0].1.is_err());
+    }
+
+    #[test]
+    fn test_traversal_order() {
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new().needed("libc.so.1").write(dir.path(), "liba.so.1");
+        ElfBuilder::new().write(dir.path(), "libb.so.1");
+        ElfBuilder::new().write(dir.path(), "libc.so.1");
+        let app = ElfBuilder::new()
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .runpath("$ORIGIN")
+            .write(dir.path(), "app");
+        let sonames = |order| {
+            let config = ResolverConfig::new()
+                .without_default_dirs()
+                .use_env(false)
+                .traversal_order(order);
+            let resolutions = ElfFile::with_config(&app, config).get_libs_resolutions().unwrap();
+            resolutions.into_iter().map(|(soname, _)| soname).collect::<Vec<_>>()
+        };
+        assert_eq!(sonames(TraversalOrder::DepthFirst), ["liba.so.1", "libc.so.1", "libb.so.1"]);
+        // The order ld.so loads them in, the same on every run
+        let breadth_first = sonames(TraversalOrder::BreadthFirst);
+        assert_eq!(breadth_first, ["liba.so.1", "libb.so.1", "libc.so.1"]);
+        assert_eq!(sonames(TraversalOrder::BreadthFirst), breadth_first);
+    }
+
+    #[test]
+    fn test_long_dependency_chain() {
+        // Deep enough to overflow the stack of a test thread if every library took a frame
+        const LENGTH: usize = 5000;
+        let dir = tempfile::tempdir().unwrap();
+        let runpath = dir.path().display().to_string();
+        for index in 1..LENGTH {
+            let lib = ElfBuilder::new().runpath(&runpath);
+            let lib = match index + 1 {
+                LENGTH => lib,
+                next => lib.needed(format!("lib{next}.so")),
+            };
+            lib.write(dir.path(), format!("lib{index}.so"));
+        }
+        let root = ElfBuilder::new()
+            .runpath(&runpath)
+            .needed("lib1.so")
+            .write(dir.path(), "app");
+
+        for order in [TraversalOrder::DepthFirst, TraversalOrder::BreadthFirst] {
+            let config = ResolverConfig::new().use_env(false).traversal_order(order);
+            let libs = ElfFile::with_config(&root, config).get_libs_full_paths().unwrap();
+            assert_eq!(libs.len(), LENGTH - 1);
+            assert_eq!(libs[LENGTH - 2], dir.path().join(format!("lib{}.so", LENGTH - 1)));
+        }
+    }
+
+    #[test]
+    fn test_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let aarch64 = ElfBuilder::new().machine(crate::abi::EM_AARCH64);
+        ElfBuilder::new().write(sysroot.join("lib/x86_64-linux-gnu"), "libfoo.so.1");
+        let libfoo = aarch64.write(sysroot.join("lib/aarch64-linux-gnu"), "libfoo.so.1");
+        let libfoo_musl = aarch64.write(sysroot.join("opt/musl"), "libfoo.so.1");
+        std::fs::create_dir(sysroot.join("etc")).unwrap();
+        std::fs::write(sysroot.join("etc/ld-musl-aarch64.path"), "/opt/musl\n").unwrap();
+        // A shared library names no interpreter, so only the target says how it is loaded
+        let root = aarch64.clone().needed("libfoo.so.1").write(sysroot, "libapp.so");
+
+        let config = |triple: &str| {
+            let target = Target::from_triple(triple).unwrap();
+            ResolverConfig::new().use_env(false).sysroot(sysroot).target(target)
+        };
+        let glibc = config("aarch64-unknown-linux-gnu");
+        let elf_file = ElfFile::with_config(&root, glibc.clone());
+        assert_eq!(elf_file.get_libs_full_paths().unwrap(), std::slice::from_ref(&libfoo));
+        let resolver = Resolver::new(glibc);
+        assert_eq!(resolver.resolve_soname("libfoo.so.1").unwrap(), libfoo);
+        let elf_file = ElfFile::with_config(&root, config("aarch64-linux-musl"));
+        assert_eq!(elf_file.get_libs_full_paths().unwrap(), [libfoo_musl]);
+
+        let host = ElfBuilder::new().needed("libfoo.so.1").write(sysroot, "libhost.so");
+        let elf_file = ElfFile::with_config(&host, config("aarch64-unknown-linux-gnu"));
+        assert!(matches!(
+            elf_file.get_libs_full_paths(),
+            Err(Error::WrongTarget { path, triple })
+                if path == host && triple == "aarch64-unknown-linux-gnu"
+        ));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new().write(dir.path().join("lib"), "libfoo.so.1");
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .runpath("$ORIGIN/lib")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new().without_default_dirs().use_env(false);
+        let elf_file = ElfFile::with_config(&app, config);
+        assert_eq!(elf_file.metrics(), ResolutionMetrics::default());
+        elf_file.get_libs_full_paths().unwrap();
+        let metrics = elf_file.metrics();
+        assert_eq!((metrics.files_parsed, metrics.cache_hits), (2, 0));
+        assert!(metrics.bytes_read > 0 && metrics.stat_calls > 0, "{metrics:?}");
+        assert!(metrics.parse_time > std::time::Duration::ZERO);
+
+        // Analyzing the file again only hits the cache
+        elf_file.get_libs_full_paths().unwrap();
+        let again = elf_file.metrics();
+        assert_eq!((again.files_parsed, again.cache_hits), (2, 2));
+        elf_file.resolver.reset_metrics();
+        assert_eq!(elf_file.metrics(), ResolutionMetrics::default());
+    }
+
+    #[test]
+    fn test_dt_soname() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().to_str().unwrap();
+        let liba = ElfBuilder::new().soname("liba.so.1").write(dir.path(), "liba.so.1");
+        let copied = ElfBuilder::new().soname("libz.so.1").write(dir.path(), "libb.so.1");
+        let unnamed = ElfBuilder::new().write(dir.path(), "libc.so.1");
+        let by_path = ElfBuilder::new().soname("libd.so.1").write(dir.path(), "libd.so");
+        let root = ElfBuilder::new()
+            .soname("app")
+            .runpath(lib_dir)
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .needed("libc.so.1")
+            .needed(&by_path)
+            .write(dir.path(), "app.bin");
+
+        let elf_file = ElfFile::new(&root);
+        let graph = elf_file.dependency_graph().unwrap();
+        let node = |path: &PathBuf| graph.node(graph.find_path(path).unwrap());
+        let dt_soname = |path| node(path).dt_soname.as_deref();
+        assert_eq!(dt_soname(&liba), Some("liba.so.1"));
+        assert_eq!(dt_soname(&copied), Some("libz.so.1"));
+        assert_eq!(dt_soname(&unnamed), None);
+        assert_eq!(dt_soname(&root), Some("app"));
+        let misnamed: Vec<_> = graph.nodes().iter().filter(|node| node.is_misnamed()).collect();
+        assert_eq!(misnamed.len(), 1);
+        assert_eq!(misnamed[0].path(), Some(copied.as_path()));
+
+        let dependencies = elf_file.iter_dependencies(TraversalOrder::BreadthFirst).unwrap();
+        let dt_sonames: Vec<_> = dependencies.map(|dependency| dependency.dt_soname).collect();
+        let expected = [Some("liba.so.1"), Some("libz.so.1"), None, Some("libd.so.1")];
+        assert_eq!(dt_sonames, expected.map(|soname| soname.map(str::to_owned)));
+    }
+
+    #[test]
+    fn test_virtual_objects() {
+        let config = ResolverConfig::new().virtual_objects(true);
+        let elf_file = ElfFile::with_config("/usr/bin/ls", config.clone());
+        let graph = elf_file.dependency_graph().unwrap();
+        let vdso = graph.node(graph.dependencies(graph.root())[0]);
+        assert_eq!(vdso.soname, "linux-vdso.so.1");
+        assert_eq!(vdso.kind, DependencyKind::Virtual);
+        assert!(matches!(vdso.resolution, Ok(Resolved::Virtual)));
+        // Methods returning only paths leave it out rather than failing
+        let paths = ElfFile::new("/usr/bin/ls").get_libs_full_paths().unwrap();
+        assert_eq!(elf_file.get_libs_full_paths().unwrap(), paths);
+        let mut dependencies = elf_file.iter_dependencies(TraversalOrder::BreadthFirst).unwrap();
+        let first = dependencies.next().unwrap();
+        assert_eq!(first.soname, "linux-vdso.so.1");
+        assert_eq!(first.kind, DependencyKind::Virtual);
+
+        let dir = tempfile::tempdir().unwrap();
+        let i386 = ElfBuilder::new()
+            .elf32()
+            .machine(crate::abi::EM_386)
+            .interpreter("/lib/ld-linux.so.2")
+            .write(dir.path(), "i386");
+        let resolutions = ElfFile::with_config(&i386, config.clone()).get_libs_resolutions();
+        assert_eq!(resolutions.unwrap()[0].0, "linux-gate.so.1");
+        // Statically linked files get no vDSO listed, like ldd
+        let static_file = ElfBuilder::new().without_dynamic_section().write(dir.path(), "static");
+        let resolutions = ElfFile::with_config(&static_file, config).get_libs_resolutions();
+        assert!(resolutions.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ignore_sonames() {
+        let dir = tempfile::tempdir().unwrap();
+        // What the ignored libfoo needs is never looked for
+        ElfBuilder::new().needed("libmissing.so.1").write(dir.path(), "libfoo.so.1");
+        let libbar = ElfBuilder::new().needed("libc.so.6").write(dir.path(), "libbar.so.1");
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .needed("ld-linux-x86-64.so.2")
+            .interpreter("/nonexistent/ld-linux-x86-64.so.2")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path())
+            .ignore_sonames(["libfoo.so.1", "libc.so.6", "ld-linux*"]);
+        let elf_file = ElfFile::with_config(&app, config);
+        assert_eq!(elf_file.get_libs_full_paths().unwrap(), std::slice::from_ref(&libbar));
+        let graph = elf_file.dependency_graph().unwrap();
+        let ignored: Vec<_> = graph
+            .nodes()
+            .iter()
+            .filter(|node| matches!(node.resolution, Ok(Resolved::Ignored)))
+            .map(|node| node.soname.as_str())
+            .collect();
+        assert_eq!(ignored, ["libfoo.so.1", "libc.so.6", "ld-linux-x86-64.so.2"]);
+        assert!(graph.to_ldd().contains("\tlibc.so.6 => ignored\n"));
+        let dependencies = elf_file.iter_dependencies(TraversalOrder::DepthFirst).unwrap();
+        let resolved: Vec<_> = dependencies
+            .map(|dependency| (dependency.soname, dependency.resolution.unwrap().path().is_some()))
+            .collect();
+        let expected = [
+            ("libfoo.so.1", false),
+            ("libbar.so.1", true),
+            ("libc.so.6", false),
+            ("ld-linux-x86-64.so.2", false),
+        ];
+        assert_eq!(resolved, expected.map(|(soname, ok)| (soname.to_owned(), ok)));
+        // Bundling leaves out the ignored libraries and interpreter instead of failing
+        let out = tempfile::tempdir().unwrap();
+        elf_file.bundle_to(out.path(), BundleMode::Copy).unwrap();
+        let bundled = |path: &Path| out.path().join(path.strip_prefix("/").unwrap());
+        assert!(bundled(&libbar).is_file());
+        assert!(!bundled(&dir.path().join("libfoo.so.1")).exists());
+    }
+
+    #[test]
+    fn test_soname_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new().write(dir.path().join("lib"), "libssl.so.3");
+        let vendored = ElfBuilder::new().needed("libcrypto.so.3");
+        let vendored = vendored.write(dir.path().join("vendor"), "libssl.so.3.0.8");
+        let libcrypto = ElfBuilder::new().write(dir.path().join("lib"), "libcrypto.so.3");
+        let stub = ElfBuilder::new().write(dir.path().join("stubs"), "libcuda.so");
+        let app = ElfBuilder::new()
+            .needed("libssl.so.3")
+            .needed("libcuda.so.1")
+            .needed("libgone.so.1")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path().join("lib"))
+            .soname_overrides([
+                ("libssl.so.3", &vendored),
+                ("libcuda.so.1", &stub),
+                ("libgone.so.1", &dir.path().join("nonexistent.so")),
+            ]);
+        let graph = ElfFile::with_config(&app, config).dependency_graph().unwrap();
+        let libs: Vec<_> = graph.nodes()[1..]
+            .iter()
+            .map(|node| (node.soname.as_str(), node.path(), node.found_by.clone()))
+            .collect();
+        // Overridden libraries still have their dependencies searched for
+        assert_eq!(
+            libs,
+            [
+                ("libssl.so.3", Some(vendored.as_path()), Some(SearchRule::Override)),
+                ("libcrypto.so.3", Some(libcrypto.as_path()), Some(SearchRule::SearchDir)),
+                ("libcuda.so.1", Some(stub.as_path()), Some(SearchRule::Override)),
+                ("libgone.so.1", None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_against_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new().write(dir.path().join("lib"), "libfoo.so.1");
+        let libbar = ElfBuilder::new().write(dir.path().join("lib"), "libbar.so.1");
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path().join("opt"))
+            .append_search_dir(dir.path().join("lib"));
+        let file = ElfFile::with_config(&app, config);
+        let lockfile = file.to_lockfile().unwrap();
+        assert_eq!(lockfile.libraries.len(), 2);
+        assert!(lockfile.libraries.iter().all(|library| library.digest.is_some()));
+        assert_eq!(file.verify_against_lockfile(&lockfile).unwrap(), []);
+
+        // A rebuilt libfoo and a libbar shadowed by a directory searched first both drift
+        ElfBuilder::new().needed("libc.so.6").write(dir.path().join("lib"), "libfoo.so.1");
+        let shadow = ElfBuilder::new().write(dir.path().join("opt"), "libbar.so.1");
+        let drift = file.verify_against_lockfile(&lockfile).unwrap();
+        assert_eq!(drift.len(), 3);
+        assert!(matches!(&drift[0], LockDrift::Modified { path, .. } if *path == libfoo));
+        assert_eq!(
+            drift[1],
+            LockDrift::Moved {
+                soname: "libbar.so.1".to_owned(),
+                locked: Some(libbar),
+                found: Some(shadow),
+            }
+        );
+        assert_eq!(
+            drift[2],
+            LockDrift::Added {
+                soname: "libc.so.6".to_owned(),
+                path: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_possible_dlopen_libraries() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = ElfBuilder::new().write(dir.path().join("plugins"), "libplugin.so.2");
+        ElfBuilder::new()
+            .rodata(b"libfoo.so.1\0libnothere.so\0")
+            .write(dir.path(), "libfoo.so.1");
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .runpath("$ORIGIN:$ORIGIN/plugins")
+            .rodata(b"cannot load %s\0libfoo.so.1\0libplugin.so.2\0")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let elf_file = ElfFile::with_config(&app, config);
+        let libfoo = dir.path().join("libfoo.so.1");
+        let summary = |resolve| -> Vec<_> {
+            let candidates = elf_file.possible_dlopen_libraries(resolve).unwrap();
+            candidates
+                .into_iter()
+                .map(|candidate| {
+                    let resolution = candidate.resolution.map(|resolution| resolution.ok());
+                    let resolution = resolution.map(|resolved| resolved?.into_path());
+                    (candidate.name, candidate.needed_by, resolution)
+                })
+                .collect()
+        };
+        // libfoo, which is loaded already, is left out
+        assert_eq!(
+            summary(false),
+            [
+                ("libplugin.so.2".to_owned(), app.clone(), None),
+                ("libnothere.so".to_owned(), libfoo.clone(), None),
+            ]
+        );
+        assert_eq!(
+            summary(true),
+            [
+                ("libplugin.so.2".to_owned(), app, Some(Some(plugin))),
+                ("libnothere.so".to_owned(), libfoo, Some(None)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plugin_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugins = dir.path().join("plugins");
+        ElfBuilder::new().write(dir.path(), "libdep.so.1");
+        let liba = ElfBuilder::new().needed("libdep.so.1").write(&plugins, "liba.so");
+        let libb = ElfBuilder::new().write(&plugins, "libb.so");
+        std::fs::write(plugins.join("README"), "not a plugin").unwrap();
+        let app = ElfBuilder::new().needed("libdep.so.1").write(dir.path(), "app");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path())
+            .plugin_dirs([&plugins, &dir.path().join("missing")]);
+        let graph = ElfFile::with_config(&app, config).dependency_graph().unwrap();
+        let dependencies: Vec<_> = graph
+            .dependencies(graph.root())
+            .iter()
+            .map(|&id| (graph.node(id).soname.clone(), graph.node(id).kind))
+            .collect();
+        let plugin = |path: &PathBuf| (path.to_string_lossy().into_owned(), DependencyKind::Plugin);
+        assert_eq!(
+            dependencies,
+            [
+                ("libdep.so.1".to_owned(), DependencyKind::Needed),
+                plugin(&liba),
+                plugin(&libb),
+            ]
+        );
+        // The dependencies of plugins are collected too
+        let liba = graph.find_path(&liba).unwrap();
+        assert_eq!(graph.dependencies(liba).len(), 1);
+        assert_eq!(graph.nodes().len(), 4);
+    }
+
+    #[test]
+    fn test_dlopen_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let libdep = ElfBuilder::new().write(dir.path(), "libdep.so.1");
+        let libidn = ElfBuilder::new().needed("libdep.so.1").write(dir.path(), "libidn.so.12");
+        ElfBuilder::new().write(dir.path(), "libzstd.so.1");
+        let app = ElfBuilder::new()
+            .needed("libdep.so.1")
+            .dlopen_note(
+                r#"[{"feature":"idn","soname":["libidn2.so.0","libidn.so.12"]},
+                    {"feature":"zstd","priority":"required","soname":["libzstd.so.1"]}]"#,
+            )
+            .dlopen_note(r#"[{"priority":"suggested","soname":["libgone.so.1"]}]"#)
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path());
+        let graph = ElfFile::with_config(&app, config.clone()).dependency_graph().unwrap();
+        assert_eq!(graph.nodes().len(), 2);
+
+        let config = config.dlopen_notes(true);
+        let graph = ElfFile::with_config(&app, config.clone()).dependency_graph().unwrap();
+        let dependencies: Vec<_> = graph
+            .dependencies(graph.root())
+            .iter()
+            .map(|&id| (graph.node(id).soname.as_str(), graph.node(id).kind))
+            .collect();
+        // The first alternative that is found, and the missing suggested library left out
+        assert_eq!(
+            dependencies,
+            [
+                ("libdep.so.1", DependencyKind::Needed),
+                ("libidn.so.12", DependencyKind::Dlopen(DlopenPriority::Recommended)),
+                ("libzstd.so.1", DependencyKind::Dlopen(DlopenPriority::Required)),
+            ]
+        );
+        let libidn = graph.find_path(&libidn).unwrap();
+        assert_eq!(graph.dependencies(libidn), [graph.find_path(&libdep).unwrap()]);
+        let elf = ElfFile::with_config(&app, config.clone());
+        let sonames: Vec<_> = elf
+            .iter_dependencies(TraversalOrder::BreadthFirst)
+            .unwrap()
+            .map(|dependency| dependency.soname)
+            .collect();
+        assert_eq!(sonames, ["libdep.so.1", "libidn.so.12", "libzstd.so.1"]);
+
+        // A missing required library is reported, under the first alternative
+        let app = ElfBuilder::new()
+            .dlopen_note(r#"[{"priority":"required","soname":["libgone.so.1","libgone.so.0"]}]"#)
+            .write(dir.path(), "app2");
+        let graph = ElfFile::with_config(&app, config).dependency_graph().unwrap();
+        let node = &graph.nodes()[1];
+        assert_eq!(node.soname, "libgone.so.1");
+        assert_eq!(node.kind, DependencyKind::Dlopen(DlopenPriority::Required));
+        assert!(matches!(node.resolution, Err(ResolveError::NotFound { .. })));
+    }
+
+    #[test]
+    fn test_include_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let interpreter = ElfBuilder::new().write(dir.path(), "ld.so");
+        let lib = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .interpreter(interpreter.to_str().unwrap())
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new().include_interpreter(true);
+        let graph = ElfFile::with_config(&root, config.clone()).dependency_graph().unwrap();
+        let dependencies: Vec<_> = graph
+            .dependencies(graph.root())
+            .iter()
+            .map(|&id| (graph.node(id).path(), graph.node(id).kind))
+            .collect();
+        let expected = [
+            (Some(lib.as_path()), DependencyKind::Needed),
+            (Some(interpreter.as_path()), DependencyKind::Interpreter),
+        ];
+        assert_eq!(dependencies, expected);
+        assert_eq!(ElfFile::new(&root).get_libs_full_paths().unwrap(), [lib]);
+
+        // A missing interpreter is reported like a missing library
+        let root = ElfBuilder::new()
+            .interpreter("/lib/ld-dyn-lib-finder-missing.so.1")
+            .write(dir.path(), "app2");
+        let resolutions = ElfFile::with_config(&root, config.clone()).get_libs_resolutions();
+        let resolutions = resolutions.unwrap();
+        assert_eq!(resolutions.len(), 1);
+        assert!(matches!(&resolutions[0].1, Err(ResolveError::NotFound { .. })));
+
+        // The interpreter is not searched twice for symbols
+        let ls = ElfFile::with_config("/usr/bin/ls", config);
+        let malloc = ElfFile::new("/usr/bin/ls").find_symbol("malloc").unwrap();
+        assert_eq!(ls.find_symbol("malloc").unwrap().len(), malloc.len());
+    }
+
+    #[test]
+    fn test_bundle_to() {
+        use std::os::unix::fs::{symlink, MetadataExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        let libfoo = ElfBuilder::new().write(&lib, "libfoo.so.1.2");
+        symlink("libfoo.so.1.2", lib.join("libfoo.so.1")).unwrap();
+        symlink(&lib, dir.path().join("lib64")).unwrap();
+        let interpreter = ElfBuilder::new().write(dir.path(), "ld.so");
+        let root = ElfBuilder::new()
+            .interpreter(interpreter.to_str().unwrap())
+            .runpath(dir.path().join("lib64").to_str().unwrap())
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+
+        let bundle = tempfile::tempdir().unwrap();
+        ElfFile::new(&root).bundle_to(bundle.path(), BundleMode::HardLink).unwrap();
+        let in_bundle = |path: &Path| bundle.path().join(path.strip_prefix("/").unwrap());
+        // The absolute symlink now points inside the bundle
+        let lib64 = in_bundle(&dir.path().join("lib64"));
+        assert!(std::fs::read_link(&lib64).unwrap().is_relative());
+        assert_eq!(std::fs::canonicalize(&lib64).unwrap(), in_bundle(&lib).canonicalize().unwrap());
+        let link = std::fs::read_link(in_bundle(&lib.join("libfoo.so.1"))).unwrap();
+        assert_eq!(link, Path::new("libfoo.so.1.2"));
+        let inode = |path: &Path| std::fs::metadata(path).unwrap().ino();
+        assert_eq!(inode(&in_bundle(&libfoo)), inode(&libfoo));
+        assert!(in_bundle(&interpreter).is_file() && in_bundle(&root).is_file());
+
+        // The bundle is a sysroot in which the same libraries are found
+        let config = ResolverConfig::new().use_env(false).sysroot(bundle.path());
+        let libs = ElfFile::with_config(in_bundle(&root), config).get_libs_full_paths().unwrap();
+        assert_eq!(libs, [in_bundle(&dir.path().join("lib64/libfoo.so.1"))]);
+
+        // Nothing is copied when a library is missing
+        let root = ElfBuilder::new()
+            .needed("libdyn-lib-finder-missing.so.1")
+            .write(dir.path(), "app2");
+        let empty = tempfile::tempdir().unwrap();
+        let error = ElfFile::new(&root).bundle_to(empty.path(), BundleMode::Copy).unwrap_err();
+        assert!(matches!(error, Error::Unresolved { .. }));
+        assert!(std::fs::read_dir(empty.path()).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn test_runpath_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        ElfBuilder::new().write(&lib, "libbar.so.1");
+        let libfoo = ElfBuilder::new()
+            .runpath(&format!("{}:/nonexistent", lib.display()))
+            .needed("libbar.so.1")
+            .write(&lib, "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .rpath(lib.to_str().unwrap())
+            .needed("libfoo.so.1")
+            .write(dir.path().join("bin"), "app");
+        let original = std::fs::read(&root).unwrap();
+
+        let bundle = tempfile::tempdir().unwrap();
+        let file = ElfFile::new(&root);
+        file.bundle_to(bundle.path(), BundleMode::HardLink).unwrap();
+        let plan = file.runpath_plan().unwrap();
+        let relative = |path: &Path| path.strip_prefix("/").unwrap().to_owned();
+        let runpaths: Vec<_> = plan
+            .iter()
+            .map(|rewrite| (rewrite.path.clone(), rewrite.runpath.as_str()))
+            .collect();
+        assert_eq!(
+            runpaths,
+            [(relative(&root), "$ORIGIN/../lib"), (relative(&libfoo), "$ORIGIN")]
+        );
+        for rewrite in &plan {
+            rewrite.apply(bundle.path()).unwrap();
+        }
+        // The hard-linked original is left alone
+        assert_eq!(std::fs::read(&root).unwrap(), original);
+
+        // The bundle finds its own libraries without a sysroot
+        let bundled_root = bundle.path().join(relative(&root));
+        let config = ResolverConfig::new().use_env(false).canonicalize_paths(true);
+        let libs = ElfFile::with_config(&bundled_root, config).get_libs_full_paths().unwrap();
+        let bundled_lib = bundle.path().join(relative(&lib));
+        assert_eq!(libs, [bundled_lib.join("libfoo.so.1"), bundled_lib.join("libbar.so.1")]);
+
+        // A RUNPATH can't be written over a shorter one
+        let short = ElfBuilder::new()
+            .runpath("/x")
+            .needed("libbar.so.1")
+            .write(dir.path().join("bin"), "short");
+        let rewrite = RunpathRewrite {
+            path: relative(&short),
+            runpath: "$ORIGIN/../lib".to_owned(),
+        };
+        let error = rewrite.apply("/").unwrap_err();
+        assert!(matches!(error, Error::RunpathNoSpace { .. }));
+    }
+
+    #[test]
+    fn test_closure_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().to_str().unwrap();
+        let libb = ElfBuilder::new().write(dir.path(), "libb.so.1");
+        let liba = ElfBuilder::new()
+            .runpath(lib_dir)
+            .needed("libb.so.1")
+            .write(dir.path(), "liba.so.1");
+        let root = ElfBuilder::new()
+            .runpath(lib_dir)
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .write(dir.path(), "app");
+
+        let stats = ElfFile::new(&root).closure_stats().unwrap();
+        let size = |path: &PathBuf| std::fs::metadata(path).unwrap().len();
+        assert_eq!(stats.file_size, size(&root));
+        let expected = [(liba.clone(), size(&liba)), (libb.clone(), size(&libb))];
+        assert_eq!(stats.library_sizes, expected);
+        assert_eq!(stats.total_size, size(&liba) + size(&libb));
+        assert_eq!((stats.library_count, stats.unresolved_count), (2, 1));
+        // libb is needed by liba too, but directly by the root as well
+        assert_eq!(stats.max_depth, 1);
+        assert_eq!(ElfFile::new(&liba).closure_stats().unwrap().max_depth, 1);
+        assert_eq!(ElfFile::new(&libb).closure_stats().unwrap().max_depth, 0);
+        assert!(ElfFile::new("/usr/bin/ls").closure_stats().unwrap().max_depth >= 2);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = ElfBuilder::new().write(dir.path(), "libfoo.so.1.2");
+        std::os::unix::fs::symlink("libfoo.so.1.2", dir.path().join("libfoo.so.1")).unwrap();
+        let root = ElfBuilder::new()
+            .runpath(dir.path().to_str().unwrap())
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+
+        // The size is that of the file the symlink leads to
+        let config = ResolverConfig::new().content_digest(ContentDigest::Sha256);
+        let csv = ElfFile::with_config(&root, config).to_csv().unwrap();
+        let contents = std::fs::read(&real).unwrap();
+        let row = format!(
+            "{},libfoo.so.1,{},runpath,{},{}\r\n",
+            root.display(),
+            dir.path().join("libfoo.so.1").display(),
+            contents.len(),
+            crate::digest::sha256_hex(&contents)
+        );
+        assert_eq!(csv, format!("root,soname,path,found_by,size,digest\r\n{row}"));
+    }
+
+    #[test]
+    fn test_runpath_precedes_system_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        // A private copy of libc found through RUNPATH beats the cache and default directories,
+        // while one only reachable through an RPATH alongside a RUNPATH is never considered
+        let rpath_libc = ElfBuilder::new().write(dir.path().join("rpath"), "libc.so.6");
+        let runpath_libc = ElfBuilder::new().write(dir.path().join("runpath"), "libc.so.6");
+        let root = ElfBuilder::new()
+            .rpath(dir.path().join("rpath").to_str().unwrap())
+            .runpath(dir.path().join("runpath").to_str().unwrap())
+            .needed("libc.so.6")
+            .write(dir.path(), "app");
+
+        let libs = ElfFile::new(root).get_libs_full_paths().unwrap();
+        assert!(libs.contains(&runpath_libc));
+        assert!(!libs.contains(&rpath_libc));
+        assert!(!libs.contains(&PathBuf::from("/lib/x86_64-linux-gnu/libc.so.6")));
+    }
+
+    #[test]
+    fn test_rpath_is_inherited() {
+        let dir = tempfile::tempdir().unwrap();
+        let private = dir.path().join("private");
+        ElfBuilder::new().needed("libleaf.so.1").write(&private, "libmid.so.1");
+        let leaf = ElfBuilder::new().write(&private, "libleaf.so.1");
+
+        // libmid has no search path of its own, so it finds libleaf through the root's RPATH...
+        let rpath_root = ElfBuilder::new()
+            .rpath(private.to_str().unwrap())
+            .needed("libmid.so.1")
+            .write(dir.path(), "rpath-app");
+        let libs = ElfFile::new(rpath_root).get_libs_full_paths().unwrap();
+        assert!(libs.contains(&leaf));
+
+        // ...but a RUNPATH only applies to the root's direct dependencies
+        let runpath_root = ElfBuilder::new()
+            .runpath(private.to_str().unwrap())
+            .needed("libmid.so.1")
+            .write(dir.path(), "runpath-app");
+        assert!(matches!(
+            ElfFile::new(runpath_root).get_libs_full_paths(),
+            Err(Error::Unresolved { soname, .. }) if soname == "libleaf.so.1"
+        ));
+    }
+
+    #[test]
+    fn test_canonicalize_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().join("lib");
+        let libb = ElfBuilder::new().write(&lib_dir, "libb.so.1");
+        ElfBuilder::new()
+            .runpath(lib_dir.to_str().unwrap())
+            .needed("libb.so.1")
+            .write(&lib_dir, "liba.so.1");
+        let lib64 = dir.path().join("lib64");
+        std::os::unix::fs::symlink(&lib_dir, &lib64).unwrap();
+        // The root finds libb through the symlink, but liba finds it through the real directory
+        let root = ElfBuilder::new()
+            .runpath(lib64.to_str().unwrap())
+            .needed("liba.so.1")
+            .needed("libb.so.1")
+            .write(dir.path(), "app");
+
+        let libs = ElfFile::new(&root).get_libs_full_paths().unwrap();
+        assert_eq!(libs.len(), 3);
+        let config = ResolverConfig::new().canonicalize_paths(true);
+        let libs = ElfFile::with_config(&root, config.clone()).get_libs_full_paths().unwrap();
+        let real_dir = lib_dir.canonicalize().unwrap();
+        assert_eq!(libs, [real_dir.join("liba.so.1"), libb.canonicalize().unwrap()]);
+        let iterated: Vec<PathBuf> = ElfFile::with_config(&root, config)
+            .iter_dependencies(TraversalOrder::BreadthFirst)
+            .unwrap()
+            .filter_map(|dependency| dependency.resolution.ok()?.into_path())
+            .collect();
+        assert_eq!(iterated, libs);
+    }
+
+    #[test]
+    fn test_symlink_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().join("lib");
+        let real = ElfBuilder::new().write(&lib_dir, "libfoo.so.1.2.3");
+        std::os::unix::fs::symlink("libfoo.so.1.2.3", lib_dir.join("libfoo.so.1")).unwrap();
+        let root = ElfBuilder::new()
+            .runpath(lib_dir.to_str().unwrap())
+            .needed("libfoo.so.1")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .write(dir.path(), "app");
+
+        let graph = ElfFile::new(&root).dependency_graph().unwrap();
+        let libfoo = graph.node(graph.dependencies(graph.root())[0]);
+        assert_eq!(libfoo.path(), Some(lib_dir.join("libfoo.so.1").as_path()));
+        assert_eq!(libfoo.links, [real]);
+        let missing = graph.node(graph.dependencies(graph.root())[1]);
+        assert!(missing.links.is_empty());
+        assert!(graph.node(graph.root()).links.is_empty());
+    }
+
+    #[test]
+    fn test_static_executable() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = ElfBuilder::new().without_dynamic_section().write(dir.path(), "static");
+        let elf_file = ElfFile::new(root);
+        assert!(elf_file.is_statically_linked().unwrap());
+        assert!(elf_file.get_libs_full_paths().unwrap().is_empty());
+        assert!(!ElfFile::new("/usr/bin/ls").is_statically_linked().unwrap());
+    }
+
+    #[test]
+    fn test_foreign_machine_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let foreign = ElfBuilder::new()
+            .machine(crate::abi::EM_AARCH64)
+            .write(dir.path().join("aarch64"), "libfoo.so.1");
+        let native = ElfBuilder::new().write(dir.path().join("x86_64"), "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .runpath(&format!("{0}/aarch64:{0}/x86_64", dir.path().display()))
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+
+        let libs = ElfFile::new(root).get_libs_full_paths().unwrap();
+        assert_eq!(libs, [native]);
+        assert!(!libs.contains(&foreign));
+    }
+
+    #[test]
+    fn test_os_abi_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let freebsd = ElfBuilder::new()
+            .os_abi(crate::abi::ELFOSABI_FREEBSD, 0)
+            .write(dir.path().join("freebsd"), "libfoo.so.1");
+        let linux = ElfBuilder::new().write(dir.path().join("linux"), "libfoo.so.1");
+        // Libraries using GNU extensions load into any Linux executable
+        let gnu = ElfBuilder::new()
+            .os_abi(crate::abi::ELFOSABI_GNU, 1)
+            .write(dir.path().join("linux"), "libbar.so.1");
+        let root = ElfBuilder::new()
+            .runpath(&format!("{0}/freebsd:{0}/linux", dir.path().display()))
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .write(dir.path(), "app");
+
+        let mismatches = |check: OsAbiCheck| {
+            let config = ResolverConfig::new().use_env(false).os_abi_check(check);
+            let graph = ElfFile::with_config(&root, config).dependency_graph().unwrap();
+            graph.nodes()[1..]
+                .iter()
+                .map(|node| (node.path().unwrap().to_owned(), node.os_abi_mismatch))
+                .collect::<Vec<_>>()
+        };
+        let expected = [(linux, false), (gnu.clone(), false)];
+        assert_eq!(mismatches(OsAbiCheck::Reject), expected);
+        let expected = [(freebsd.clone(), true), (gnu.clone(), false)];
+        assert_eq!(mismatches(OsAbiCheck::Warn), expected);
+        assert_eq!(mismatches(OsAbiCheck::Ignore), [(freebsd, false), (gnu, false)]);
+    }
+
+    #[test]
+    fn test_disk_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let libbar = ElfBuilder::new().write(dir.path().join("lib"), "libbar.so.1");
+        ElfBuilder::new().elf32().write(dir.path().join("lib32"), "libfoo.so.1");
+        let libfoo = ElfBuilder::new()
+            .runpath("$ORIGIN")
+            .needed("libbar.so.1")
+            .write(dir.path().join("lib"), "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .runpath(&format!("{0}/lib32:{0}/lib", dir.path().display()))
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+
+        let run = || {
+            let cache = Arc::new(DiskCache::open(dir.path().join("cache")).unwrap());
+            let config = ResolverConfig::new()
+                .use_env(false)
+                .without_default_dirs()
+                .disk_cache(cache.clone());
+            let elf_file = ElfFile::with_config(&root, config);
+            let libs = elf_file.get_libs_full_paths();
+            cache.save().unwrap();
+            (libs, elf_file.metrics())
+        };
+        let (libs, metrics) = run();
+        assert_eq!(libs.unwrap(), [libfoo, libbar.clone()]);
+        assert_eq!(metrics.files_parsed, 3);
+        // The candidate of the wrong class is remembered too, so nothing is read again
+        let (libs, metrics) = run();
+        assert!(libs.is_ok());
+        assert_eq!((metrics.files_parsed, metrics.bytes_read), (0, 0));
+        assert_eq!(metrics.cache_hits, 6);
+
+        // Only the library that changed is parsed again
+        ElfBuilder::new()
+            .needed("libbaz.so.1")
+            .write(dir.path().join("lib"), "libbar.so.1");
+        let (libs, metrics) = run();
+        assert!(matches!(libs, Err(Error::Unresolved { soname, .. }) if soname == "libbaz.so.1"));
+        assert_eq!(metrics.files_parsed, 1);
+    }
+
+    #[test]
+    fn test_changed_files_reparsed() {
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        let root = ElfBuilder::new()
+            .runpath("$ORIGIN")
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let resolver = Arc::new(Resolver::new(config));
+        let elf_file = ElfFile::with_resolver(&root, resolver.clone());
+        assert_eq!(elf_file.get_libs_full_paths().unwrap(), std::slice::from_ref(&libfoo));
+        assert_eq!(resolver.metrics().files_parsed, 2);
+
+        // An upgrade replacing the library is noticed by the same resolver
+        ElfBuilder::new()
+            .needed("libbar.so.1")
+            .write(dir.path(), "libfoo.so.1");
+        assert!(matches!(
+            elf_file.get_libs_full_paths(),
+            Err(Error::Unresolved { soname, .. }) if soname == "libbar.so.1"
+        ));
+        assert_eq!(resolver.metrics().files_parsed, 3);
+
+        // Unchanged files are only parsed again when forgotten
+        assert!(resolver.invalidate(&libfoo));
+        assert!(!resolver.invalidate(dir.path().join("libbar.so.1")));
+        let _ = elf_file.get_libs_full_paths();
+        assert_eq!(resolver.metrics().files_parsed, 4);
+        resolver.clear_cache();
+        assert_eq!(resolver.parsed_files(), 0);
+    }
+
+    #[test]
+    fn test_custom_search_dirs_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let libc = ElfBuilder::new().write(dir.path().join("first"), "libc.so.6");
+        let libfoo = ElfBuilder::new().write(dir.path().join("second"), "libfoo.so.1");
+        ElfBuilder::new().write(dir.path().join("second"), "libc.so.6");
+        let root = ElfBuilder::new()
+            .needed("libc.so.6")
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new()
+            .append_search_dir("/nonexistent")
+            .with_search_dirs([dir.path().join("first")])
+            .append_search_dir(dir.path().join("second"))
+            .without_default_dirs()
+            .use_env(false);
+        let libs = ElfFile::with_config(root, config).get_libs_full_paths().unwrap();
+        assert_eq!(libs, [libc, libfoo]);
+    }
+
+    #[test]
+    fn test_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let vendored = ElfBuilder::new().write(dir.path().join("vendor"), "libvendored.so.1");
+        let root = ElfBuilder::new()
+            .needed("libvendored.so.1")
+            .needed("libc.so.6")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new().append_search_dir(dir.path().join("vendor"));
+        let libs = ElfFile::with_config(&root, config.clone()).get_libs_full_paths().unwrap();
+        assert!(libs.contains(&vendored));
+        assert!(libs.iter().any(|lib| lib.ends_with("libc.so.6")));
+
+        // libc is only found through the system directories
+        let resolutions = ElfFile::with_config(&root, config.without_default_dirs())
+            .get_libs_resolutions()
+            .unwrap();
+        assert!(resolutions.iter().any(|(soname, resolution)| soname == "libc.so.6"
+            && matches!(resolution, Err(ResolveError::NotFound { .. }))));
+
+        // Without recursion, only the direct dependencies of `ls` are reported
+        let config = ResolverConfig::new().recursive(false);
+        let graph = ElfFile::with_config("/usr/bin/ls", config).dependency_graph().unwrap();
+        assert!(graph.dependencies(graph.root()).len() > 1);
+        assert!(graph
+            .node_ids()
+            .skip(1)
+            .all(|id| graph.dependencies(id).is_empty()));
+    }
+
+    #[test]
+    fn test_sysroot() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        // An absolute symlink that would escape to the host if followed naively
+        ElfBuilder::new().write(sysroot.join("usr/lib"), "libfoo.so.1.2");
+        std::fs::create_dir(sysroot.join("etc")).unwrap();
+        std::os::unix::fs::symlink("/usr/lib/libfoo.so.1.2", sysroot.join("usr/lib/libfoo.so.1"))
+            .unwrap();
+        let libbar = ElfBuilder::new().write(sysroot.join("opt/bar/lib"), "libbar.so.1");
+        let libbaz = ElfBuilder::new().write(sysroot.join("opt/rpath"), "libbaz.so.1");
+        std::fs::write(sysroot.join("etc/ld.so.conf"), "/opt/bar/lib\n").unwrap();
+        let root = ElfBuilder::new()
+            .rpath("/opt/rpath")
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .needed("libbaz.so.1")
+            .write(sysroot.join("usr/bin"), "app");
+
+        let config = ResolverConfig::new().use_env(false).sysroot(sysroot);
+        let libs = ElfFile::with_config(root, config).get_libs_with_sonames().unwrap();
+        let expected = [
+            ("libfoo.so.1", sysroot.join("usr/lib/libfoo.so.1")),
+            ("libbar.so.1", libbar),
+            ("libbaz.so.1", libbaz),
+        ]
+        .map(|(soname, path)| (soname.to_owned(), path));
+        assert_eq!(libs, expected);
+    }
+
+    #[test]
+    fn test_sysroot_prefixed_rpath() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let libfoo = ElfBuilder::new().write(sysroot.join("opt/foo"), "libfoo.so.1");
+        let libbar = ElfBuilder::new().write(sysroot.join("opt/lib64"), "libbar.so.1");
+        let root = ElfBuilder::new()
+            .rpath("=/opt/foo:${SYSROOT}/opt/$LIB")
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .write(sysroot.join("usr/bin"), "app");
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .sysroot(sysroot);
+        let libs = ElfFile::with_config(root, config).get_libs_full_paths().unwrap();
+        assert_eq!(libs, [libfoo, libbar]);
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("store");
+        let app = "0c2wrxmg3wbfzflbvvqzq1ifzc0dz8pq-app-1.0";
+        let foo = "1n8ph1arza0c3jfwz3rlbb3dcrv5hjmp-foo-2.1";
+        let foo_lib = "2sa09c1y5mqb8fjjyjm9rf6wj2g3xgab-foo-2.1-lib";
+        ElfBuilder::new().write(store.join(foo_lib).join("lib"), "libfoo.so.2.1");
+        std::fs::create_dir_all(store.join(foo)).unwrap();
+        // Packages link to one another by their paths in the store
+        let target = format!("/nix/store/{foo_lib}/lib");
+        std::os::unix::fs::symlink(target, store.join(foo).join("lib")).unwrap();
+        std::os::unix::fs::symlink("libfoo.so.2.1", store.join(foo_lib).join("lib/libfoo.so.2"))
+            .unwrap();
+        ElfBuilder::new()
+            .runpath(&format!("/nix/store/{foo}/lib"))
+            .needed("libfoo.so.2")
+            .needed("libc.so.6")
+            .write(store.join(app).join("bin"), "app");
+
+        let config = ResolverConfig::new().use_env(false).store_root(&store);
+        let path = Path::new(NIX_STORE_DIR).join(app).join("bin/app");
+        let elf_file = ElfFile::with_config(&path, config.clone());
+        let graph = elf_file.dependency_graph().unwrap();
+        let root = graph.node(graph.root());
+        assert_eq!(root.store_path.as_ref().unwrap().name, "app-1.0");
+        let libfoo = &graph.nodes()[1];
+        let expected = Path::new(NIX_STORE_DIR).join(foo).join("lib/libfoo.so.2");
+        assert_eq!(libfoo.path(), Some(expected.as_path()));
+        assert!(matches!(libfoo.found_by, Some(SearchRule::Runpath { .. })));
+        assert_eq!(libfoo.store_path.as_ref().unwrap().hash, &foo[..32]);
+        // The system directories are not searched
+        assert_eq!(graph.nodes()[2].soname, "libc.so.6");
+        assert!(graph.nodes()[2].resolution.is_err());
+
+        // The store can be analyzed at its host paths too
+        let elf_file = ElfFile::with_config(store.join(app).join("bin/app"), config);
+        let deps = elf_file.iter_dependencies(TraversalOrder::DepthFirst).unwrap();
+        let store_paths: Vec<_> = deps.map(|dep| dep.store_path.map(|path| path.name)).collect();
+        assert_eq!(store_paths, [Some("foo-2.1".to_owned()), None]);
+    }
+
+    #[test]
+    fn test_system_preload() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let libpre = ElfBuilder::new().write(sysroot.join("usr/lib"), "libpre.so.1");
+        let libpath = ElfBuilder::new().write(sysroot.join("opt"), "libpath.so.1");
+        let libfoo = ElfBuilder::new().write(sysroot.join("usr/lib"), "libfoo.so.1");
+        std::fs::create_dir(sysroot.join("etc")).unwrap();
+        std::fs::write(sysroot.join("etc/ld.so.preload"), "libpre.so.1\n/opt/libpath.so.1\n")
+            .unwrap();
+        let root = ElfBuilder::new().needed("libfoo.so.1").write(sysroot.join("usr/bin"), "app");
+
+        let config = ResolverConfig::new().use_env(false).sysroot(sysroot);
+        let elf_file = ElfFile::with_config(&root, config.clone());
+        assert_eq!(elf_file.get_libs_full_paths().unwrap(), std::slice::from_ref(&libfoo));
+        let elf_file = ElfFile::with_config(&root, config.system_preload(true));
+        let graph = elf_file.dependency_graph().unwrap();
+        let nodes: Vec<_> = graph.nodes()[1..]
+            .iter()
+            .map(|node| (node.path().unwrap().to_owned(), node.kind))
+            .collect();
+        let expected = [
+            (libpre, DependencyKind::Preload),
+            (libpath, DependencyKind::Preload),
+            (libfoo, DependencyKind::Needed),
+        ];
+        assert_eq!(nodes, expected);
+    }
+
+    #[test]
+    fn test_ld_preload() {
+        let dir = tempfile::tempdir().unwrap();
+        let libfoo = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        let libpre = ElfBuilder::new().write(dir.path(), "libpre.so.1");
+        let libpath = ElfBuilder::new().write(dir.path().join("preload"), "libpath.so.1");
+        let root = ElfBuilder::new().needed("libfoo.so.1").write(dir.path(), "app");
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path()])
+            .without_default_dirs()
+            .preload(["libpre.so.1".to_owned(), libpath.display().to_string()]);
+
+        let libs = ElfFile::with_config(&root, config.clone().use_env(false))
+            .get_libs_full_paths()
+            .unwrap();
+        assert_eq!(libs, [libpre.clone(), libpath, libfoo.clone()]);
+        // Preloaded paths are ignored for privileged programs
+        let libs = ElfFile::with_config(&root, config.secure_execution(true))
+            .get_libs_full_paths()
+            .unwrap();
+        assert_eq!(libs, [libpre, libfoo]);
+    }
+
+    #[test]
+    fn test_interpreter() {
+        let glibc = Some(PathBuf::from("/lib64/ld-linux-x86-64.so.2"));
+        assert_eq!(ElfFile::new("/usr/bin/ls").interpreter().unwrap(), glibc);
+
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let musl = "/lib/ld-musl-x86_64.so.1";
+        // Only musl searches /usr/local/lib, and only glibc reads ld.so.conf
+        let libfoo = ElfBuilder::new().write(sysroot.join("usr/local/lib"), "libfoo.so.1");
+        let libbar = ElfBuilder::new().write(sysroot.join("opt/lib"), "libbar.so.1");
+        std::fs::create_dir(sysroot.join("etc")).unwrap();
+        std::fs::write(sysroot.join("etc/ld.so.conf"), "/opt/lib\n").unwrap();
+        let root = ElfBuilder::new()
+            .interpreter(musl)
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .write(sysroot.join("bin"), "app");
+
+        let config = ResolverConfig::new().use_env(false).sysroot(sysroot);
+        let elf_file = ElfFile::with_config(&root, config.clone());
+        assert_eq!(elf_file.interpreter().unwrap(), Some(PathBuf::from(musl)));
+        let graph = elf_file.dependency_graph().unwrap();
+        assert_eq!(graph.interpreter(), Some(std::path::Path::new(musl)));
+        let resolutions: Vec<_> = graph.nodes()[1..].iter().map(|node| node.path()).collect();
+        assert_eq!(resolutions, [Some(libfoo.as_path()), None]);
+
+        // The path file replaces the default directories, and is found inside the sysroot even
+        // through an absolute symlink
+        std::fs::create_dir(sysroot.join("usr/etc")).unwrap();
+        std::fs::write(sysroot.join("usr/etc/ld-musl-x86_64.path"), "/opt/lib\n").unwrap();
+        let path_file = sysroot.join("etc/ld-musl-x86_64.path");
+        std::os::unix::fs::symlink("/usr/etc/ld-musl-x86_64.path", path_file).unwrap();
+        let graph = ElfFile::with_config(&root, config).dependency_graph().unwrap();
+        let resolutions: Vec<_> = graph.nodes()[1..].iter().map(|node| node.path()).collect();
+        assert_eq!(resolutions, [None, Some(libbar.as_path())]);
+    }
+
+    #[test]
+    fn test_not_loadable() {
+        let dir = tempfile::tempdir().unwrap();
+        let object = ElfBuilder::new().e_type(crate::abi::ET_REL).write(dir.path(), "foo.o");
+        let elf_file = ElfFile::new(&object);
+        assert_eq!(elf_file.elf_type().unwrap(), ElfType::Relocatable);
+        let error = elf_file.get_libs_full_paths().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::NotLoadable { elf_type: ElfType::Relocatable, .. }
+        ));
+        let message = "is a relocatable object (ET_REL), which has no dynamic dependencies";
+        assert_eq!(error.to_string(), format!("{object:?} {message}"));
+        assert!(elf_file.needed_sonames().is_err());
+
+        let core = ElfBuilder::new().e_type(crate::abi::ET_CORE).write(dir.path(), "core");
+        assert_eq!(ElfFile::new(&core).elf_type().unwrap(), ElfType::Core);
+        assert!(ElfFile::new(&core).is_statically_linked().is_err());
+        let library = ElfBuilder::new().write(dir.path(), "libfoo.so");
+        assert_eq!(ElfFile::new(library).elf_type().unwrap(), ElfType::SharedObject);
+    }
+
+    #[test]
+    fn test_freebsd() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let libfoo = ElfBuilder::new().write(sysroot.join("usr/local/lib"), "libfoo.so.1");
+        let libc = ElfBuilder::new().write(sysroot.join("lib"), "libc.so.7");
+        std::fs::create_dir_all(sysroot.join("var/run")).unwrap();
+        let hints = crate::freebsd::build_hints("/usr/local/lib");
+        std::fs::write(sysroot.join("var/run/ld-elf.so.hints"), hints).unwrap();
+        let app = ElfBuilder::new()
+            .interpreter("/libexec/ld-elf.so.1")
+            .needed("libfoo.so.1")
+            .needed("libc.so.7");
+        let root = app.write(sysroot.join("usr/local/bin"), "app");
+        let library = app.write(sysroot.join("usr/local/lib"), "libapp.so.1");
+
+        let config = ResolverConfig::new().use_env(false).sysroot(sysroot);
+        let libs = ElfFile::with_config(root, config.clone()).get_libs_full_paths().unwrap();
+        assert_eq!(libs, [libfoo.clone(), libc.clone()]);
+        // Files without an interpreter need the platform to be set
+        let config = config.platform(Platform::FreeBsd);
+        let libs = ElfFile::with_config(library, config).get_libs_full_paths().unwrap();
+        assert_eq!(libs, [libfoo, libc]);
+    }
+
+    #[test]
+    fn test_android() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let runtime = sysroot.join("apex/com.android.runtime/lib64");
+        let libc = ElfBuilder::new().write(&runtime, "libc.so");
+        let libvendor = ElfBuilder::new().write(sysroot.join("vendor/lib64"), "libvendor.so");
+        ElfBuilder::new().write(sysroot.join("data/lib"), "librpath.so");
+        let app = ElfBuilder::new()
+            .interpreter("/system/bin/linker64")
+            .rpath("/data/lib")
+            .needed("libc.so")
+            .needed("libvendor.so")
+            .needed("librpath.so");
+        let system_app = app.write(sysroot.join("system/bin"), "app");
+        let vendor_app = app.write(sysroot.join("vendor/bin"), "app");
+        std::fs::create_dir_all(sysroot.join("linkerconfig")).unwrap();
+        let ld_config = "dir.system = /system/bin/\n\
+                         [system]\n\
+                         namespace.default.search.paths = /apex/com.android.runtime/${LIB}\n";
+        std::fs::write(sysroot.join("linkerconfig/ld.config.txt"), ld_config).unwrap();
+
+        // The RPATH is ignored, and /system/bin executables only search the runtime APEX
+        let config = ResolverConfig::new().use_env(false).sysroot(sysroot);
+        let libs = ElfFile::with_config(system_app, config.clone()).get_libs_resolutions();
+        let libs = libs.unwrap();
+        assert_eq!(libs[0].1.as_ref().unwrap().path(), Some(libc.as_path()));
+        assert!(libs[1].1.is_err() && libs[2].1.is_err());
+        // Executables outside the configured directories get bionic's built-in search path
+        let libs = ElfFile::with_config(vendor_app, config.append_search_dir(&runtime));
+        let libs = libs.get_libs_resolutions().unwrap();
+        assert_eq!(libs[1].1.as_ref().unwrap().path(), Some(libvendor.as_path()));
+        assert!(libs[2].1.is_err());
+    }
+
+    #[test]
+    fn test_multiarch_default_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let aarch64 = ElfBuilder::new().machine(crate::abi::EM_AARCH64);
+        let i386 = ElfBuilder::new().elf32().machine(crate::abi::EM_386);
+        let arm64_lib = aarch64.write(sysroot.join("usr/lib/aarch64-linux-gnu"), "libfoo.so.1");
+        let i386_lib = i386.write(sysroot.join("usr/lib32"), "libfoo.so.1");
+        let arm64_root = aarch64.needed("libfoo.so.1").write(sysroot.join("bin"), "arm64-app");
+        let i386_root = i386.needed("libfoo.so.1").write(sysroot.join("bin"), "i386-app");
+        // x32 files are 32-bit too, but only load x32 libraries
+        let x32 = ElfBuilder::new().elf32();
+        let x32_lib = x32.write(sysroot.join("usr/libx32"), "libfoo.so.1");
+        let x32_root = x32.needed("libfoo.so.1").write(sysroot.join("bin"), "x32-app");
+
+        let config = ResolverConfig::new().use_env(false).sysroot(sysroot);
+        let roots = [(arm64_root, arm64_lib), (i386_root, i386_lib), (x32_root, x32_lib)];
+        for (root, lib) in roots {
+            let libs = ElfFile::with_config(root, config.clone()).get_libs_full_paths().unwrap();
+            assert_eq!(libs, [lib]);
+        }
+    }
+
+    #[test]
+    fn test_direct_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let libmid = ElfBuilder::new().needed("libleaf.so.1").write(dir.path(), "libmid.so.1");
+        ElfBuilder::new().write(dir.path(), "libleaf.so.1");
+        let root = ElfBuilder::new()
+            .needed("libmid.so.1")
+            .needed("libdyn-lib-finder-missing.so.1")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new().with_search_dirs([dir.path()]).use_env(false);
+        let elf_file = ElfFile::with_config(&root, config);
+        let direct = elf_file.direct_dependencies().unwrap();
+        assert_eq!(direct.len(), 2);
+        assert_eq!(direct[0].0, "libmid.so.1");
+        assert_eq!(direct[0].1.as_ref().unwrap().path(), Some(libmid.as_path()));
+        assert_eq!(direct[1].0, "libdyn-lib-finder-missing.so.1");
+        assert!(matches!(direct[1].1, Err(ResolveError::NotFound { .. })));
+        // The configured recursion still applies to the other methods
+        assert_eq!(elf_file.get_libs_resolutions().unwrap().len(), 3);
+        assert_eq!(
+            elf_file.needed_sonames().unwrap(),
+            ["libmid.so.1", "libdyn-lib-finder-missing.so.1"]
+        );
+    }
+
     fn test_libc_dependency(elf_file_path: &str) {
         let elf_file = ElfFile::new(elf_file_path);
         let libs = elf_file
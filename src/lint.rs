@@ -0,0 +1,202 @@
+//! Non-fatal findings about how objects declare their dependencies, which the dynamic linker
+//! accepts but which are usually mistakes.
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::os_str::OsStrExt;
+use crate::parse::{self, ParsedObject};
+use crate::{dst, FileKind, ResolverConfig};
+
+/// What a [`Lint`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LintKind {
+    /// The object has a `DT_RPATH`, which unlike a `DT_RUNPATH` is searched before
+    /// `LD_LIBRARY_PATH`, so it can't be overridden, and is deprecated.
+    DeprecatedRpath,
+    /// The object has both a `DT_RPATH` and a `DT_RUNPATH`, so ld.so ignores the `DT_RPATH`.
+    /// Reported instead of [`LintKind::DeprecatedRpath`].
+    RpathAndRunpath,
+    /// The object lists the soname that is the subject of the lint more than once in its
+    /// `DT_NEEDED` entries.
+    DuplicateNeeded,
+    /// The `DT_RPATH` or `DT_RUNPATH` entry that is the subject of the lint names a directory
+    /// that doesn't exist. Empty and relative entries, which depend on the working directory,
+    /// are left to [`ElfFile::insecure_runpaths`](crate::ElfFile::insecure_runpaths).
+    MissingRunpathDir,
+}
+
+impl LintKind {
+    /// Every kind of lint, in the order each object's lints are reported in.
+    pub const ALL: [LintKind; 4] = [
+        LintKind::DeprecatedRpath,
+        LintKind::RpathAndRunpath,
+        LintKind::DuplicateNeeded,
+        LintKind::MissingRunpathDir,
+    ];
+
+    /// The name of the lint, like `deprecated-rpath`, as the command-line tool takes it.
+    pub fn name(self) -> &'static str {
+        match self {
+            LintKind::DeprecatedRpath => "deprecated-rpath",
+            LintKind::RpathAndRunpath => "rpath-and-runpath",
+            LintKind::DuplicateNeeded => "duplicate-needed",
+            LintKind::MissingRunpathDir => "missing-runpath-dir",
+        }
+    }
+}
+
+/// A non-fatal finding about an object, as reported by [`ElfFile::lints`](crate::ElfFile::lints).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lint {
+    /// The object the lint is about.
+    pub object: PathBuf,
+    /// What was found.
+    pub kind: LintKind,
+    /// The soname of a [`LintKind::DuplicateNeeded`] or the entry, as written, of a
+    /// [`LintKind::MissingRunpathDir`], with bytes that are not UTF-8 replaced with `U+FFFD`.
+    pub subject: Option<String>,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let object = &self.object;
+        let subject = self.subject.as_deref().unwrap_or_default();
+        match self.kind {
+            LintKind::DeprecatedRpath => {
+                write!(
+                    f,
+                    "{object:?} uses the deprecated DT_RPATH instead of DT_RUNPATH"
+                )
+            }
+            LintKind::RpathAndRunpath => write!(
+                f,
+                "{object:?} has both DT_RPATH and DT_RUNPATH, so DT_RPATH is ignored"
+            ),
+            LintKind::DuplicateNeeded => write!(f, "{object:?} needs \"{subject}\" more than once"),
+            LintKind::MissingRunpathDir => {
+                write!(f, "{object:?} searches \"{subject}\", which doesn't exist")
+            }
+        }?;
+        write!(f, " [{}]", self.kind.name())
+    }
+}
+
+/// Checks `object`, read from the file at `path`.
+pub(crate) fn lint(path: &Path, object: &ParsedObject, config: &ResolverConfig) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut push = |kind, subject: Option<String>| {
+        lints.push(Lint {
+            object: path.to_owned(),
+            kind,
+            subject,
+        })
+    };
+    match (&object.rpath, &object.runpath) {
+        (Some(_), Some(_)) => push(LintKind::RpathAndRunpath, None),
+        (Some(_), None) => push(LintKind::DeprecatedRpath, None),
+        _ => {}
+    }
+    let mut seen = HashSet::new();
+    let mut reported = HashSet::new();
+    for soname in &object.needed {
+        if !seen.insert(soname) && reported.insert(soname) {
+            push(
+                LintKind::DuplicateNeeded,
+                Some(soname.to_string_lossy().into_owned()),
+            );
+        }
+    }
+
+    let origin = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    let (lib, platform) = dst::lib_and_platform(object.arch, config);
+    let token_values = dst::TokenValues {
+        origin: origin.parent().unwrap_or(Path::new("/")),
+        lib: &lib,
+        platform,
+    };
+    let entries = [&object.rpath, &object.runpath]
+        .into_iter()
+        .flatten()
+        .flat_map(|paths| parse::split(paths, b':'));
+    for entry in entries {
+        let expanded = PathBuf::from(dst::expand(entry, &token_values));
+        if entry.is_empty() || !expanded.is_absolute() {
+            continue;
+        }
+        // $ORIGIN already points into the sysroot, but absolute entries don't
+        let dir = if entry.as_bytes().starts_with(b"/") {
+            config.rebase(&expanded)
+        } else {
+            expanded
+        };
+        let is_dir = config
+            .fs()
+            .metadata(&config.canonical(&dir))
+            .is_ok_and(|metadata| metadata.kind == FileKind::Dir);
+        if !is_dir {
+            push(
+                LintKind::MissingRunpathDir,
+                Some(entry.to_string_lossy().into_owned()),
+            );
+        }
+    }
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, LintKind};
+    use crate::parse::parse_bytes;
+    use crate::test_util::ElfBuilder;
+    use crate::ResolverConfig;
+    use std::fs;
+
+    #[test]
+    fn test_lint() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("lib")).unwrap();
+        let data = ElfBuilder::new()
+            .rpath("$ORIGIN/lib:$ORIGIN/missing")
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .needed("libfoo.so.1")
+            .needed("libfoo.so.1")
+            .build();
+        let path = dir.path().join("app");
+        let object = parse_bytes(&path, &data).unwrap();
+        let lints: Vec<_> = lint(&path, &object, &ResolverConfig::new())
+            .into_iter()
+            .map(|lint| (lint.kind, lint.subject))
+            .collect();
+        assert_eq!(
+            lints,
+            [
+                (LintKind::DeprecatedRpath, None),
+                (LintKind::DuplicateNeeded, Some("libfoo.so.1".to_owned())),
+                (
+                    LintKind::MissingRunpathDir,
+                    Some("$ORIGIN/missing".to_owned())
+                ),
+            ]
+        );
+
+        // Absolute entries are looked up inside the sysroot, and relative ones not at all
+        let data = ElfBuilder::new()
+            .rpath("/lib")
+            .runpath("/opt/lib:lib:")
+            .build();
+        let object = parse_bytes(&path, &data).unwrap();
+        let config = ResolverConfig::new().sysroot(dir.path());
+        let lints = lint(&path, &object, &config);
+        assert_eq!(lints.len(), 2);
+        assert_eq!(lints[0].kind, LintKind::RpathAndRunpath);
+        assert_eq!(lints[1].subject.as_deref(), Some("/opt/lib"));
+        assert_eq!(
+            lints[1].to_string(),
+            format!("{path:?} searches \"/opt/lib\", which doesn't exist [missing-runpath-dir]")
+        );
+    }
+}
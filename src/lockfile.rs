@@ -0,0 +1,373 @@
+//! Lockfiles recording where each library of a closure was found and what it contained, to
+//! detect when the closure a program would load drifts from the one that was qualified.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{DependencyGraph, Error};
+
+/// The first line of every lockfile, naming its format.
+const HEADER: &str = "# dyn-lib-finder lockfile v1";
+
+/// One library of a [`Lockfile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LockedLibrary {
+    /// The soname the library is requested by.
+    pub soname: String,
+    /// Where it was found, or `None` for libraries that were not and virtual objects.
+    pub path: Option<PathBuf>,
+    /// The [content digest](crate::ResolverConfig::content_digest) of the file, if it had one.
+    pub digest: Option<String>,
+}
+
+/// The libraries a closure loads, with where each was found and its digest, as recorded by
+/// [`DependencyGraph::to_lockfile`] and checked by [`DependencyGraph::verify_against_lockfile`].
+///
+/// Lockfiles are text, one tab-separated `soname`, `path` and `digest` line per library
+/// after a header line, with empty fields for a missing path or digest, so that they can be
+/// checked in and reviewed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lockfile {
+    /// The libraries, in load order.
+    pub libraries: Vec<LockedLibrary>,
+}
+
+impl Lockfile {
+    /// Reads the lockfile stored in the file at `path`.
+    /// # Return Value [Err]
+    /// [`Error::Io`] if the file can't be read, and [`Error::InvalidLockfile`] if it is not a
+    /// lockfile.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        parse(&text).map_err(|line| Error::InvalidLockfile {
+            path: path.to_owned(),
+            line,
+        })
+    }
+
+    /// Writes the lockfile to the file at `path`, replacing it in one step, so that readers
+    /// never see a partly written lockfile.
+    /// # Return Value [Err]
+    /// [`Error::Write`] if the file can't be written.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut temp = path.to_owned().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        let write_error = |source| Error::Write {
+            path: path.to_owned(),
+            source,
+        };
+        fs::write(&temp, self.to_string()).map_err(write_error)?;
+        fs::rename(&temp, path).map_err(write_error)
+    }
+}
+
+impl fmt::Display for Lockfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        for library in &self.libraries {
+            let path = library.path.as_deref().unwrap_or(Path::new("")).display();
+            let digest = library.digest.as_deref().unwrap_or_default();
+            writeln!(f, "{}\t{path}\t{digest}", library.soname)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the text of a lockfile, failing with the number of the first line that is not
+/// well-formed.
+fn parse(text: &str) -> Result<Lockfile, usize> {
+    let mut lines = text.lines().enumerate();
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err(1);
+    }
+    let mut libraries = Vec::new();
+    for (index, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let &[soname, path, digest] = fields.as_slice() else {
+            return Err(index + 1);
+        };
+        if soname.is_empty() {
+            return Err(index + 1);
+        }
+        libraries.push(LockedLibrary {
+            soname: soname.to_owned(),
+            path: (!path.is_empty()).then(|| PathBuf::from(path)),
+            digest: (!digest.is_empty()).then(|| digest.to_owned()),
+        });
+    }
+    Ok(Lockfile { libraries })
+}
+
+/// How a closure differs from a [`Lockfile`], as reported by
+/// [`DependencyGraph::verify_against_lockfile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LockDrift {
+    /// The closure loads a library the lockfile doesn't list, from `path` if it was found.
+    Added {
+        soname: String,
+        path: Option<PathBuf>,
+    },
+    /// The lockfile lists a library the closure no longer loads.
+    Removed {
+        soname: String,
+        path: Option<PathBuf>,
+    },
+    /// The library is found at another path than the lockfile's, or only one of them found
+    /// it.
+    Moved {
+        soname: String,
+        locked: Option<PathBuf>,
+        found: Option<PathBuf>,
+    },
+    /// The file at `path` has another digest than the lockfile's, or none because the closure
+    /// was built without [digests](crate::ResolverConfig::content_digest).
+    Modified {
+        soname: String,
+        path: PathBuf,
+        locked: String,
+        found: Option<String>,
+    },
+}
+
+impl fmt::Display for LockDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |path: &Option<PathBuf>| match path {
+            Some(path) => format!("{path:?}"),
+            None => "nothing".to_owned(),
+        };
+        match self {
+            LockDrift::Added { soname, path } => {
+                write!(
+                    f,
+                    "\"{soname}\" is loaded from {}, but not locked",
+                    describe(path)
+                )
+            }
+            LockDrift::Removed { soname, path } => {
+                write!(
+                    f,
+                    "\"{soname}\" is locked to {}, but not loaded",
+                    describe(path)
+                )
+            }
+            LockDrift::Moved {
+                soname,
+                locked,
+                found,
+            } => write!(
+                f,
+                "\"{soname}\" is locked to {}, but loaded from {}",
+                describe(locked),
+                describe(found)
+            ),
+            LockDrift::Modified {
+                soname,
+                path,
+                locked,
+                found,
+            } => write!(
+                f,
+                "\"{soname}\" at {path:?} has digest {}, but {locked} is locked",
+                found.as_deref().unwrap_or("unknown")
+            ),
+        }
+    }
+}
+
+impl DependencyGraph {
+    /// Records the libraries this graph loads in a [`Lockfile`], each soname once with where
+    /// ld.so loads it from first, as in [`DependencyGraph::to_ldd`], and its digest if the graph
+    /// was built with [`ResolverConfig::content_digest`](crate::ResolverConfig::content_digest).
+    /// The analyzed file itself is left out.
+    pub fn to_lockfile(&self) -> Lockfile {
+        let libraries = self
+            .library_nodes()
+            .into_iter()
+            .map(|node| LockedLibrary {
+                soname: node.soname.clone(),
+                path: node.path().map(PathBuf::from),
+                digest: node.digest.clone().filter(|_| node.path().is_some()),
+            })
+            .collect();
+        Lockfile { libraries }
+    }
+
+    /// Compares the libraries this graph loads with those recorded in `lockfile`, returning
+    /// every difference: libraries added, removed, found elsewhere, and, where the lockfile
+    /// has a digest, modified in place. Empty if the closure is the one that was locked.
+    /// Differences are in the load order of this graph, followed by the removed libraries in
+    /// the order of the lockfile.
+    pub fn verify_against_lockfile(&self, lockfile: &Lockfile) -> Vec<LockDrift> {
+        let locked: HashMap<&str, &LockedLibrary> = lockfile
+            .libraries
+            .iter()
+            .map(|library| (library.soname.as_str(), library))
+            .collect();
+        let mut drift = Vec::new();
+        let mut loaded = HashSet::new();
+        for node in self.library_nodes() {
+            loaded.insert(node.soname.as_str());
+            let soname = node.soname.clone();
+            let path = node.path().map(PathBuf::from);
+            let Some(library) = locked.get(node.soname.as_str()) else {
+                drift.push(LockDrift::Added { soname, path });
+                continue;
+            };
+            match (&path, &library.digest) {
+                _ if path != library.path => drift.push(LockDrift::Moved {
+                    soname,
+                    locked: library.path.clone(),
+                    found: path,
+                }),
+                (Some(path), Some(digest)) if node.digest.as_ref() != Some(digest) => {
+                    drift.push(LockDrift::Modified {
+                        soname,
+                        path: path.clone(),
+                        locked: digest.clone(),
+                        found: node.digest.clone(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        let removed = lockfile
+            .libraries
+            .iter()
+            .filter(|library| !loaded.contains(library.soname.as_str()))
+            .map(|library| LockDrift::Removed {
+                soname: library.soname.clone(),
+                path: library.path.clone(),
+            });
+        drift.extend(removed);
+        drift
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, LockDrift, LockedLibrary, Lockfile};
+    use crate::{DependencyGraph, DependencyNode, ResolveError, Resolved};
+    use std::path::PathBuf;
+
+    /// An app needing each library at its path with its digest, or a missing one for `None`.
+    fn graph(libraries: &[(&str, Option<&str>, &str)]) -> DependencyGraph {
+        let mut graph = DependencyGraph::new(DependencyNode::new(
+            "app",
+            Ok(Resolved::Found("/opt/app".into())),
+        ));
+        let root = graph.root();
+        for &(soname, path, digest) in libraries {
+            let resolution = path.map(|path| Resolved::Found(PathBuf::from(path))).ok_or(
+                ResolveError::NotFound {
+                    needed_by: PathBuf::from("/opt/app"),
+                },
+            );
+            let mut node = DependencyNode::new(soname, resolution);
+            node.digest = Some(digest.to_owned());
+            let node = graph.add_node(node);
+            graph.add_edge(root, node);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_lockfile() {
+        let graph = graph(&[
+            ("libfoo.so.1", Some("/usr/lib/libfoo.so.1"), "aa"),
+            ("libmissing.so.1", None, "bb"),
+        ]);
+        let lockfile = graph.to_lockfile();
+        let text = "\
+# dyn-lib-finder lockfile v1
+libfoo.so.1\t/usr/lib/libfoo.so.1\taa
+libmissing.so.1\t\t
+";
+        assert_eq!(lockfile.to_string(), text);
+        assert_eq!(parse(text), Ok(lockfile));
+        assert_eq!(parse("libfoo.so.1\t\t\n"), Err(1));
+        let text = "# dyn-lib-finder lockfile v1\nlibfoo.so.1\t/usr/lib/libfoo.so.1\n";
+        assert_eq!(parse(text), Err(2));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.lock");
+        graph.to_lockfile().write(&path).unwrap();
+        assert_eq!(Lockfile::read(&path).unwrap(), graph.to_lockfile());
+    }
+
+    #[test]
+    fn test_verify_against_lockfile() {
+        let locked = graph(&[
+            ("libfoo.so.1", Some("/usr/lib/libfoo.so.1"), "aa"),
+            ("libbar.so.1", Some("/usr/lib/libbar.so.1"), "bb"),
+            ("libbaz.so.1", Some("/usr/lib/libbaz.so.1"), "cc"),
+            ("libgone.so.1", Some("/usr/lib/libgone.so.1"), "dd"),
+        ])
+        .to_lockfile();
+        assert!(graph(&[])
+            .verify_against_lockfile(&Lockfile::default())
+            .is_empty());
+        let current = graph(&[
+            ("libfoo.so.1", Some("/usr/lib/libfoo.so.1"), "aa"),
+            ("libbar.so.1", Some("/opt/lib/libbar.so.1"), "bb"),
+            ("libbaz.so.1", Some("/usr/lib/libbaz.so.1"), "ee"),
+            ("libnew.so.1", None, "ff"),
+        ]);
+        let drift = current.verify_against_lockfile(&locked);
+        assert_eq!(
+            drift,
+            [
+                LockDrift::Moved {
+                    soname: "libbar.so.1".to_owned(),
+                    locked: Some(PathBuf::from("/usr/lib/libbar.so.1")),
+                    found: Some(PathBuf::from("/opt/lib/libbar.so.1")),
+                },
+                LockDrift::Modified {
+                    soname: "libbaz.so.1".to_owned(),
+                    path: PathBuf::from("/usr/lib/libbaz.so.1"),
+                    locked: "cc".to_owned(),
+                    found: Some("ee".to_owned()),
+                },
+                LockDrift::Added {
+                    soname: "libnew.so.1".to_owned(),
+                    path: None,
+                },
+                LockDrift::Removed {
+                    soname: "libgone.so.1".to_owned(),
+                    path: Some(PathBuf::from("/usr/lib/libgone.so.1")),
+                },
+            ]
+        );
+        assert_eq!(
+            drift[1].to_string(),
+            "\"libbaz.so.1\" at \"/usr/lib/libbaz.so.1\" has digest ee, but cc is locked"
+        );
+
+        // Libraries locked without a digest only have to be found at the same path
+        let mut unhashed = locked.clone();
+        unhashed.libraries[0].digest = None;
+        let current = graph(&[("libfoo.so.1", Some("/usr/lib/libfoo.so.1"), "zz")]);
+        let drift = current.verify_against_lockfile(&unhashed);
+        assert!(drift
+            .iter()
+            .all(|drift| matches!(drift, LockDrift::Removed { .. })));
+        let library = LockedLibrary {
+            soname: "libfoo.so.1".to_owned(),
+            path: Some(PathBuf::from("/usr/lib/libfoo.so.1")),
+            digest: None,
+        };
+        assert_eq!(unhashed.libraries[0], library);
+    }
+}
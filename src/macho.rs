@@ -0,0 +1,579 @@
+//! Dependency resolution for Mach-O files, following the rules of macOS's dyld.
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::resolver::is_set_id;
+use crate::{
+    DependencyGraph, DependencyKind, DependencyNode, Error, NodeId, Resolution, ResolveError,
+    Resolved, ResolverConfig,
+};
+
+const MH_MAGIC: u32 = 0xfeedface;
+const MH_MAGIC_64: u32 = 0xfeedfacf;
+const FAT_MAGIC: u32 = 0xcafebabe;
+const FAT_MAGIC_64: u32 = 0xcafebabf;
+
+pub(crate) const LC_LOAD_DYLIB: u32 = 0xc;
+pub(crate) const LC_LOAD_WEAK_DYLIB: u32 = 0x8000_0018;
+pub(crate) const LC_RPATH: u32 = 0x8000_001c;
+const LC_REEXPORT_DYLIB: u32 = 0x8000_001f;
+const LC_LAZY_LOAD_DYLIB: u32 = 0x20;
+const LC_LOAD_UPWARD_DYLIB: u32 = 0x8000_0023;
+
+pub(crate) const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+pub(crate) const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+
+/// Searched by leaf name after everything else when `DYLD_FALLBACK_LIBRARY_PATH` is not set.
+const DEFAULT_FALLBACK_DIRS: [&str; 2] = ["/usr/local/lib", "/usr/lib"];
+
+/// Represents a Mach-O file on disk, such as a macOS executable or dylib, and provides the same
+/// dependency queries as [`ElfFile`](crate::ElfFile).
+///
+/// Install names starting with `@executable_path/`, `@loader_path/` and `@rpath/` are expanded
+/// like dyld does, with `@rpath` trying the `LC_RPATH` entries of the loading image and then of
+/// each image that led to it. Libraries are searched for in this order:
+/// - Each `DYLD_LIBRARY_PATH` directory, by the leaf name of the install name
+/// - The (expanded) install name itself
+/// - Directories added with [`ResolverConfig::append_search_dir`], by leaf name
+/// - Each `DYLD_FALLBACK_LIBRARY_PATH` directory by leaf name, or `/usr/local/lib` and
+///   `/usr/lib` if it is not set
+///
+/// Universal ("fat") files are supported: the slice matching the analyzed file's CPU type is
+/// used, and libraries without one are skipped. `LC_LOAD_WEAK_DYLIB` libraries that are missing
+/// are left out. The environment is ignored for set-user-ID and set-group-ID files (see
+/// [`ResolverConfig::secure_execution`]), and absolute paths are looked up inside the
+/// [`ResolverConfig::sysroot`]. On macOS 11 and later, system libraries live only in the dyld
+/// shared cache; when analyzing the host, missing libraries under `/usr/lib` and `/System` are
+/// assumed to be there and reported at their install name, without dependencies.
+pub struct MachOFile {
+    path: PathBuf,
+    config: ResolverConfig,
+}
+
+impl MachOFile {
+    /// Creates a [`MachOFile`] instance from [`AsRef<Path>`]
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        MachOFile::with_config(path, ResolverConfig::default())
+    }
+
+    /// Creates a [`MachOFile`] instance that resolves dependencies according to `config`
+    pub fn with_config<P: AsRef<Path>>(path: P, config: ResolverConfig) -> Self {
+        let path = path.as_ref().to_owned();
+        Self { path, config }
+    }
+
+    /// Like [`ElfFile::get_libs_full_paths`](crate::ElfFile::get_libs_full_paths): the paths
+    /// of all dependencies, or [`Error::Unresolved`] if one of them is missing.
+    pub fn get_libs_full_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        self.get_libs_resolutions()?
+            .into_iter()
+            .filter_map(|(soname, resolution)| match resolution {
+                Ok(resolved) => resolved.into_path().map(Ok),
+                Err(error) => Some(Err(error.into_error(soname))),
+            })
+            .collect()
+    }
+
+    /// Like [`ElfFile::get_libs_resolutions`](crate::ElfFile::get_libs_resolutions): every
+    /// install name in the order it was encountered, paired with where it was found.
+    pub fn get_libs_resolutions(&self) -> Result<Vec<(String, Resolution)>, Error> {
+        let nodes = self.dependency_graph()?.into_nodes();
+        Ok(nodes
+            .into_iter()
+            .skip(1)
+            .map(|node| (node.soname, node.resolution))
+            .collect())
+    }
+
+    /// The install names of the libraries this file loads, in load command order.
+    pub fn needed_dylibs(&self) -> Result<Vec<String>, Error> {
+        let object = self.parse()?;
+        Ok(object.dylibs.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// The raw `LC_RPATH` entries of this file, in order.
+    pub fn rpaths(&self) -> Result<Vec<String>, Error> {
+        self.parse().map(|object| object.rpaths)
+    }
+
+    /// Builds the full [`DependencyGraph`] of this file. The soname of each node is the install
+    /// name it was loaded by.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn dependency_graph(&self) -> Result<DependencyGraph, Error> {
+        let object = self.parse()?;
+        let config = &self.config;
+        let file_name = self.path.file_name().unwrap_or(self.path.as_os_str());
+        let resolution = Ok(Resolved::Found(self.path.clone()));
+        let graph = DependencyGraph::new(DependencyNode::new(file_name, resolution));
+        let secure = config
+            .secure_execution
+            .unwrap_or_else(|| is_set_id(config, &self.path));
+        let env_dirs = |name| -> Option<Vec<PathBuf>> {
+            let value = config.env_var(name).filter(|_| !secure)?;
+            Some(env::split_paths(&value).collect())
+        };
+        let mut fallback_dirs = config.search_dirs.clone();
+        if let Some(dirs) = env_dirs("DYLD_FALLBACK_LIBRARY_PATH") {
+            fallback_dirs.extend(dirs);
+        } else if config.default_dirs {
+            fallback_dirs.extend(DEFAULT_FALLBACK_DIRS.map(|dir| config.rebase(Path::new(dir))));
+        }
+        let mut traversal = Traversal {
+            config,
+            executable_dir: parent(&self.path).to_owned(),
+            cputype: object.cputype,
+            library_path: env_dirs("DYLD_LIBRARY_PATH").unwrap_or_default(),
+            fallback_dirs,
+            seen_libs: HashMap::new(),
+            graph,
+        };
+        let root = traversal.graph.root();
+        traversal
+            .seen_libs
+            .insert(self.config.identity(&self.path), root);
+        traversal.collect_libs(&self.path, root, object);
+        Ok(traversal.graph)
+    }
+
+    /// Reads this file, picking the slice for the host CPU from a universal file.
+    fn parse(&self) -> Result<ParsedMachO, Error> {
+        let data = fs::read(&self.path).map_err(|source| Error::Io {
+            path: self.path.clone(),
+            source,
+        })?;
+        parse(&data)
+            .and_then(|slices| select(slices, None))
+            .ok_or_else(|| Error::InvalidMachO {
+                path: self.path.clone(),
+            })
+    }
+}
+
+/// The parts of one Mach-O image needed to find its dependencies.
+#[derive(Debug)]
+struct ParsedMachO {
+    cputype: u32,
+    /// Install names from the `LC_*_DYLIB` load commands.
+    dylibs: Vec<(String, DependencyKind)>,
+    rpaths: Vec<String>,
+}
+
+/// Parses every slice of a thin or universal Mach-O file, or returns `None` if `data` is not
+/// one.
+fn parse(data: &[u8]) -> Option<Vec<ParsedMachO>> {
+    let magic = u32::from_be_bytes(data.get(..4)?.try_into().ok()?);
+    if magic != FAT_MAGIC && magic != FAT_MAGIC_64 {
+        return parse_thin(data).map(|object| vec![object]);
+    }
+    // The fat header and `fat_arch` entries are always big-endian
+    let fat = Reader {
+        data,
+        big_endian: true,
+    };
+    let count = fat.u32(4)? as usize;
+    let entry_size = if magic == FAT_MAGIC_64 { 32 } else { 20 };
+    (0..count)
+        .map(|index| {
+            let entry = index.checked_mul(entry_size)?.checked_add(8)?;
+            let (offset, size) = if magic == FAT_MAGIC_64 {
+                (fat.u64(entry + 8)?, fat.u64(entry + 16)?)
+            } else {
+                (fat.u32(entry + 8)?.into(), fat.u32(entry + 12)?.into())
+            };
+            let start = usize::try_from(offset).ok()?;
+            let end = start.checked_add(usize::try_from(size).ok()?)?;
+            parse_thin(data.get(start..end)?)
+        })
+        .collect()
+}
+
+fn parse_thin(data: &[u8]) -> Option<ParsedMachO> {
+    let magic = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+    let (big_endian, header_size) = match magic {
+        MH_MAGIC => (false, 28),
+        MH_MAGIC_64 => (false, 32),
+        _ if magic == MH_MAGIC.swap_bytes() => (true, 28),
+        _ if magic == MH_MAGIC_64.swap_bytes() => (true, 32),
+        _ => return None,
+    };
+    let header = Reader { data, big_endian };
+    let mut object = ParsedMachO {
+        cputype: header.u32(4)?,
+        dylibs: Vec::new(),
+        rpaths: Vec::new(),
+    };
+    let mut offset = header_size;
+    for _ in 0..header.u32(16)? {
+        let cmd = header.u32(offset)?;
+        let cmdsize = header.u32(offset + 4)? as usize;
+        if cmdsize < 8 {
+            return None;
+        }
+        let command = Reader {
+            data: data.get(offset..offset.checked_add(cmdsize)?)?,
+            big_endian,
+        };
+        let kind = match cmd {
+            LC_LOAD_DYLIB | LC_REEXPORT_DYLIB | LC_LAZY_LOAD_DYLIB | LC_LOAD_UPWARD_DYLIB => {
+                Some(DependencyKind::Needed)
+            }
+            LC_LOAD_WEAK_DYLIB => Some(DependencyKind::Weak),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            object.dylibs.push((command.lc_str()?, kind));
+        } else if cmd == LC_RPATH {
+            object.rpaths.push(command.lc_str()?);
+        }
+        offset += cmdsize;
+    }
+    Some(object)
+}
+
+/// Picks the slice for `cputype`, or for the host CPU (falling back to the first slice) if
+/// `None`.
+fn select(slices: Vec<ParsedMachO>, cputype: Option<u32>) -> Option<ParsedMachO> {
+    let wanted = cputype.or_else(host_cputype);
+    let index = slices
+        .iter()
+        .position(|slice| Some(slice.cputype) == wanted);
+    match (index, cputype) {
+        (Some(index), _) => slices.into_iter().nth(index),
+        (None, None) => slices.into_iter().next(),
+        (None, Some(_)) => None,
+    }
+}
+
+fn host_cputype() -> Option<u32> {
+    if cfg!(target_arch = "x86_64") {
+        Some(CPU_TYPE_X86_64)
+    } else if cfg!(target_arch = "aarch64") {
+        Some(CPU_TYPE_ARM64)
+    } else {
+        None
+    }
+}
+
+/// Bounds-checked reads from a Mach-O structure of either byte order.
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self
+            .data
+            .get(offset..offset.checked_add(4)?)?
+            .try_into()
+            .ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        let bytes = self
+            .data
+            .get(offset..offset.checked_add(8)?)?
+            .try_into()
+            .ok()?;
+        Some(if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    }
+
+    /// The `lc_str` at the start of a load command's fields: a string at the offset it gives,
+    /// NUL-padded to the end of the command.
+    fn lc_str(&self) -> Option<String> {
+        let string = self.data.get(self.u32(8)? as usize..)?;
+        let len = string
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(string.len());
+        String::from_utf8(string[..len].to_vec()).ok()
+    }
+}
+
+/// The directory containing `path`.
+fn parent(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// State shared across the walk of a single [`MachOFile`].
+struct Traversal<'a> {
+    config: &'a ResolverConfig,
+    /// What `@executable_path` expands to.
+    executable_dir: PathBuf,
+    /// Libraries must have a slice for the analyzed file's CPU.
+    cputype: u32,
+    /// `DYLD_LIBRARY_PATH`.
+    library_path: Vec<PathBuf>,
+    fallback_dirs: Vec<PathBuf>,
+    seen_libs: HashMap<PathBuf, NodeId>,
+    graph: DependencyGraph,
+}
+
+/// An image whose libraries are being added to the graph, one at a time.
+struct Frame {
+    path: PathBuf,
+    node: NodeId,
+    /// The libraries left to add.
+    dylibs: std::vec::IntoIter<(String, DependencyKind)>,
+    /// The expanded `LC_RPATH` entries of the image and of those that led to it.
+    rpaths: Vec<PathBuf>,
+    depth: usize,
+}
+
+impl Traversal<'_> {
+    /// Collects the closure of `object`, read from `path`, into `node`, depth-first. Images
+    /// are kept on an explicit stack, so long chains of libraries can't overflow the stack.
+    fn collect_libs(&mut self, path: &Path, node: NodeId, object: ParsedMachO) {
+        let mut stack = Vec::from_iter(self.start(path.to_owned(), node, object, &[], 0));
+        while let Some(frame) = stack.last_mut() {
+            let Some((name, kind)) = frame.dylibs.next() else {
+                stack.pop();
+                continue;
+            };
+            match self.search(&name, parent(&frame.path), &frame.rpaths) {
+                Some((lib_path, lib)) => {
+                    let identity = self.config.identity(&lib_path);
+                    match self.seen_libs.get(&identity) {
+                        Some(&seen) => self.graph.add_edge(frame.node, seen),
+                        None => {
+                            let mut child =
+                                DependencyNode::new(&name, Ok(Resolved::Found(identity.clone())));
+                            child.kind = kind;
+                            child.links = self.config.symlink_chain(&lib_path);
+                            let child = self.graph.add_node(child);
+                            self.graph.add_edge(frame.node, child);
+                            self.seen_libs.insert(identity, child);
+                            if self.config.recursive {
+                                let (rpaths, depth) = (&frame.rpaths, frame.depth + 1);
+                                let next = self.start(lib_path, child, lib, rpaths, depth);
+                                stack.extend(next);
+                            }
+                        }
+                    }
+                }
+                // Like dyld, quietly skip weak libraries that aren't there
+                None if kind == DependencyKind::Weak => {}
+                None => {
+                    let not_found = ResolveError::NotFound {
+                        needed_by: frame.path.clone(),
+                    };
+                    let mut child = DependencyNode::new(&name, Err(not_found));
+                    child.kind = kind;
+                    let child = self.graph.add_node(child);
+                    self.graph.add_edge(frame.node, child);
+                }
+            }
+        }
+    }
+
+    /// The libraries of `object`, read from `path`, to add to `node`, which is `depth` levels
+    /// below the analyzed file, or `None` at the maximum depth or by the traversal filter.
+    /// `inherited_rpaths` are the expanded `LC_RPATH` entries of the images that led to
+    /// `object`.
+    fn start(
+        &mut self,
+        path: PathBuf,
+        node: NodeId,
+        object: ParsedMachO,
+        inherited_rpaths: &[PathBuf],
+        depth: usize,
+    ) -> Option<Frame> {
+        let loader_dir = parent(&path);
+        let mut rpaths: Vec<PathBuf> = object
+            .rpaths
+            .iter()
+            .map(|rpath| self.expand(rpath, loader_dir))
+            .collect();
+        rpaths.extend_from_slice(inherited_rpaths);
+        if self
+            .config
+            .stops_at(&self.graph.node(node).soname, &path, depth)
+        {
+            self.graph.node_mut(node).truncated = !object.dylibs.is_empty();
+            return None;
+        }
+        Some(Frame {
+            path,
+            node,
+            dylibs: object.dylibs.into_iter(),
+            rpaths,
+            depth,
+        })
+    }
+
+    /// Finds the library with install name `name`, loaded by an image in `loader_dir`.
+    fn search(
+        &self,
+        name: &str,
+        loader_dir: &Path,
+        rpaths: &[PathBuf],
+    ) -> Option<(PathBuf, ParsedMachO)> {
+        let leaf = Path::new(name).file_name();
+        let by_leaf = |dirs: &[PathBuf]| -> Vec<PathBuf> {
+            leaf.map_or_else(Vec::new, |leaf| {
+                dirs.iter().map(|dir| dir.join(leaf)).collect()
+            })
+        };
+        let mut candidates = by_leaf(&self.library_path);
+        match name.strip_prefix("@rpath/") {
+            Some(rest) => candidates.extend(rpaths.iter().map(|rpath| rpath.join(rest))),
+            None => candidates.push(self.expand(name, loader_dir)),
+        }
+        candidates.extend(by_leaf(&self.fallback_dirs));
+        let found = candidates.into_iter().find_map(|path| {
+            let data = fs::read(&path).ok()?;
+            let object = select(parse(&data)?, Some(self.cputype))?;
+            Some((path, object))
+        });
+        found.or_else(|| {
+            self.in_shared_cache(name).then(|| {
+                let object = ParsedMachO {
+                    cputype: self.cputype,
+                    dylibs: Vec::new(),
+                    rpaths: Vec::new(),
+                };
+                (PathBuf::from(name), object)
+            })
+        })
+    }
+
+    /// Expands the `@executable_path` and `@loader_path` prefixes of an install name or
+    /// `LC_RPATH` entry, and moves absolute paths into the sysroot.
+    fn expand(&self, path: &str, loader_dir: &Path) -> PathBuf {
+        if let Some(rest) = path.strip_prefix("@executable_path/") {
+            self.executable_dir.join(rest)
+        } else if let Some(rest) = path.strip_prefix("@loader_path/") {
+            loader_dir.join(rest)
+        } else {
+            self.config.rebase(Path::new(path))
+        }
+    }
+
+    /// Whether `name` is a system library that is only in the dyld shared cache of the host.
+    fn in_shared_cache(&self, name: &str) -> bool {
+        cfg!(target_os = "macos")
+            && self.config.sysroot.is_none()
+            && (name.starts_with("/usr/lib/") || name.starts_with("/System/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CPU_TYPE_ARM64, CPU_TYPE_X86_64};
+    use crate::test_util::{fat_macho, MachOBuilder};
+    use crate::{DependencyKind, MachOFile, ResolveError, ResolverConfig};
+
+    #[test]
+    fn test_macho_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        let lib = dir.path().join("lib");
+        let app = MachOBuilder::new()
+            .rpath("@loader_path/../lib")
+            .dylib("@rpath/libfoo.dylib")
+            .dylib("@executable_path/libbar.dylib")
+            .weak_dylib("@rpath/libweak.dylib")
+            .dylib("/opt/dyn-lib-finder/libmissing.dylib")
+            .write(&bin, "app");
+        MachOBuilder::new()
+            .rpath("@loader_path/baz")
+            .dylib("@rpath/libbaz.dylib")
+            .dylib("@executable_path/libbar.dylib")
+            .write(&lib, "libfoo.dylib");
+        let bar = MachOBuilder::new().write(&bin, "libbar.dylib");
+        MachOBuilder::new().write(lib.join("baz"), "libbaz.dylib");
+
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let macho = MachOFile::with_config(&app, config);
+        assert_eq!(macho.rpaths().unwrap(), ["@loader_path/../lib"]);
+        assert_eq!(macho.needed_dylibs().unwrap().len(), 4);
+        let resolutions = macho.get_libs_resolutions().unwrap();
+        let names: Vec<&str> = resolutions.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "@rpath/libfoo.dylib",
+                "@rpath/libbaz.dylib",
+                "@executable_path/libbar.dylib",
+                "/opt/dyn-lib-finder/libmissing.dylib",
+            ]
+        );
+        assert_eq!(
+            resolutions[0].1.as_ref().unwrap().path(),
+            Some(bin.join("../lib/libfoo.dylib").as_path())
+        );
+        assert_eq!(
+            resolutions[1].1.as_ref().unwrap().path(),
+            Some(bin.join("../lib/baz/libbaz.dylib").as_path())
+        );
+        assert_eq!(
+            resolutions[2].1.as_ref().unwrap().path(),
+            Some(bar.as_path())
+        );
+        assert!(matches!(
+            &resolutions[3].1,
+            Err(ResolveError::NotFound { needed_by }) if needed_by == &app
+        ));
+        assert!(macho.get_libs_full_paths().is_err());
+
+        // libbar.dylib is shared by the executable and libfoo.dylib
+        let graph = macho.dependency_graph().unwrap();
+        let bar = graph.find_path(&bar).unwrap();
+        assert_eq!(graph.dependents(bar).count(), 2);
+        assert!(graph
+            .nodes()
+            .iter()
+            .all(|node| node.kind == DependencyKind::Needed));
+    }
+
+    #[test]
+    fn test_macho_fat_and_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = MachOBuilder::new()
+            .cputype(CPU_TYPE_ARM64)
+            .dylib("/opt/dyn-lib-finder/libfoo.dylib")
+            .write(dir.path(), "app");
+        // Only the second fallback directory has a libfoo.dylib with an arm64 slice
+        let wrong_arch = dir.path().join("x86_64");
+        MachOBuilder::new().write(&wrong_arch, "libfoo.dylib");
+        let universal = dir.path().join("universal");
+        let foo = fat_macho(
+            &universal,
+            "libfoo.dylib",
+            &[
+                MachOBuilder::new().cputype(CPU_TYPE_X86_64).build(),
+                MachOBuilder::new()
+                    .cputype(CPU_TYPE_ARM64)
+                    .dylib("libnested.dylib")
+                    .build(),
+            ],
+        );
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .with_search_dirs([wrong_arch, universal]);
+        let resolutions = MachOFile::with_config(&app, config)
+            .get_libs_resolutions()
+            .unwrap();
+        assert_eq!(resolutions.len(), 2);
+        assert_eq!(
+            resolutions[0].1.as_ref().unwrap().path(),
+            Some(foo.as_path())
+        );
+        assert_eq!(resolutions[1].0, "libnested.dylib");
+        assert!(resolutions[1].1.is_err());
+    }
+}
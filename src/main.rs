@@ -0,0 +1,481 @@
+//! `dyn-lib-finder`, a command-line replacement for `ldd` that never runs the analyzed file.
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::{Parser, ValueEnum};
+#[cfg(unix)]
+use elf_dynamic_lib_getter::Daemon;
+use elf_dynamic_lib_getter::{
+    ContentDigest, DependencyNode, DiskCache, ElfFile, LintKind, Lockfile, Resolver,
+    ResolverConfig, SbomFormat, Target,
+};
+
+/// Lists the shared libraries an ELF file needs and where the dynamic linker would find them.
+///
+/// Exits with 0 if every library was found, 1 if some were not, a denied lint was found, the
+/// closure drifted from the --verify-lock lockfile or the --timeout ran out, and 2 if the file
+/// could not be analyzed.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The executable or shared library to analyze
+    elf: PathBuf,
+    /// Analyze every ELF file under ELF, which may be a directory, and list the libraries not
+    /// found with the files that need them
+    #[arg(long, conflicts_with_all = ["tree", "json", "sbom", "hardening"])]
+    audit: bool,
+    /// List every ELF file under ELF, which may be a directory, that needs the library LIB,
+    /// by soname or, if it contains a /, by path
+    #[arg(
+        long,
+        value_name = "LIB",
+        conflicts_with_all = ["audit", "tree", "json", "sbom", "hardening"]
+    )]
+    users_of: Option<String>,
+    /// List every ELF file under ELF, which may be a directory, that would lack a library if
+    /// FILE were deleted, with the libraries it would lack
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["audit", "users_of", "tree", "json", "sbom", "hardening"]
+    )]
+    removing: Option<PathBuf>,
+    /// Analyze every ELF file under ELF, which may be a directory, and print its dependency
+    /// graph or why it could not be analyzed as a line of JSON as soon as it is done
+    #[arg(
+        long,
+        group = "json_output",
+        conflicts_with_all = [
+            "audit", "users_of", "removing", "tree", "sbom", "depfile", "csv", "hardening"
+        ]
+    )]
+    ndjson: bool,
+    /// Listen on a new Unix socket at ELF instead, answering JSON-RPC requests about the
+    /// libraries of files, one per line, with every file parsed once for all of them
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "audit", "users_of", "removing", "ndjson", "tree", "json", "sbom", "depfile", "csv",
+            "hardening", "lock", "verify_lock", "timeout"
+        ]
+    )]
+    #[cfg(unix)]
+    serve: bool,
+    /// Print the dependencies as an indented tree, like lddtree
+    #[arg(long, conflicts_with = "json")]
+    tree: bool,
+    /// Print the dependency graph as JSON
+    #[arg(long, group = "json_output", conflicts_with = "sbom")]
+    json: bool,
+    /// Print a software bill of materials of the file and its libraries
+    #[arg(long, value_name = "FORMAT", conflicts_with = "tree")]
+    sbom: Option<Sbom>,
+    /// Print a Makefile depfile making TARGET depend on the file and each library found, for
+    /// Make or Ninja to rebuild TARGET when any of them changes
+    #[arg(
+        long,
+        value_name = "TARGET",
+        conflicts_with_all = ["tree", "json", "sbom", "hardening"]
+    )]
+    depfile: Option<String>,
+    /// Print one CSV row per library found, with the soname it was loaded as, its path, the
+    /// rule that found it and its size
+    #[arg(long, conflicts_with_all = ["tree", "json", "sbom", "depfile", "hardening"])]
+    csv: bool,
+    /// Print the exploit mitigations of the file and of each library, like checksec
+    #[arg(long, conflicts_with_all = ["tree", "json", "sbom"])]
+    hardening: bool,
+    /// Open relative paths of libraries in DIR, as if the program were started there
+    #[arg(long, value_name = "DIR")]
+    working_dir: Option<PathBuf>,
+    /// Analyze the file as if DIR were mounted at /
+    #[arg(long, value_name = "DIR")]
+    sysroot: Option<PathBuf>,
+    /// Resolve as on NixOS or Guix System, with the store at DIR, like /nix/store, searching no
+    /// system directories
+    #[arg(long, value_name = "DIR")]
+    store: Option<PathBuf>,
+    /// Read the store from DIR instead of its own directory
+    #[arg(long, value_name = "DIR")]
+    store_root: Option<PathBuf>,
+    /// Resolve as on the target TRIPLE, like aarch64-unknown-linux-gnu, instead of the host
+    #[arg(long, value_name = "TRIPLE")]
+    target: Option<Target>,
+    /// Remember the parsed files in FILE, and reuse those unchanged since the last run
+    #[arg(long, value_name = "FILE")]
+    cache: Option<PathBuf>,
+    /// Take libraries with soname SONAME, which may contain * and ? wildcards, as present
+    /// without looking for them or their dependencies
+    #[arg(long, value_name = "SONAME")]
+    ignore: Vec<String>,
+    /// Take the library with soname SONAME to be the file PATH instead of searching for it
+    #[arg(long = "override", value_name = "SONAME=PATH", value_parser = parse_override)]
+    overrides: Vec<(String, PathBuf)>,
+    /// Take the program to load every shared library in DIR, like a plugin directory
+    #[arg(long, value_name = "DIR")]
+    plugin_dir: Vec<PathBuf>,
+    /// Take the libraries each file declares in .note.dlopen notes as loaded by it
+    #[arg(long)]
+    dlopen_notes: bool,
+    /// Warn about deprecated DT_RPATHs, duplicate DT_NEEDED entries and other likely mistakes
+    #[arg(long)]
+    lints: bool,
+    /// Fail if the lint LINT is found, and warn about it
+    #[arg(long, value_name = "LINT")]
+    deny: Vec<LintName>,
+    /// Under each library, list every file by its name in the directories searched for it,
+    /// marking the one used with *
+    #[arg(long)]
+    all_candidates: bool,
+    /// Print every path tried for each library to stderr, like LD_DEBUG=libs
+    #[arg(long)]
+    trace_search: bool,
+    /// Print the directories searched for the libraries of each object to stderr, in order
+    #[arg(long)]
+    search_path: bool,
+    /// Record where each library was found and its SHA-256 digest in the lockfile FILE
+    #[arg(long, value_name = "FILE")]
+    lock: Option<PathBuf>,
+    /// Fail if a library was added, removed, moved or modified since the lockfile FILE was
+    /// recorded with --lock, and print how
+    #[arg(long, value_name = "FILE")]
+    verify_lock: Option<PathBuf>,
+    /// Stop looking for libraries after SECS seconds, and print what was found until then
+    #[arg(long, value_name = "SECS", value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+    /// Show on stderr how many files --audit, --users-of or --removing analyzed so far
+    #[arg(long)]
+    progress: bool,
+    /// Ignore LD_LIBRARY_PATH
+    #[arg(long)]
+    no_env: bool,
+    /// Include the SHA-256 digest of each file in the JSON or NDJSON output
+    #[arg(long, requires = "json_output")]
+    sha256: bool,
+    /// Include the size, modification time, owner, group and permissions of each file in the
+    /// JSON or NDJSON output
+    #[arg(long, requires = "json_output")]
+    stat: bool,
+}
+
+/// The lints `--deny` takes.
+#[derive(Clone, Copy, ValueEnum)]
+enum LintName {
+    DeprecatedRpath,
+    RpathAndRunpath,
+    DuplicateNeeded,
+    MissingRunpathDir,
+}
+
+impl LintName {
+    fn kind(self) -> LintKind {
+        match self {
+            LintName::DeprecatedRpath => LintKind::DeprecatedRpath,
+            LintName::RpathAndRunpath => LintKind::RpathAndRunpath,
+            LintName::DuplicateNeeded => LintKind::DuplicateNeeded,
+            LintName::MissingRunpathDir => LintKind::MissingRunpathDir,
+        }
+    }
+}
+
+/// Parses the `SONAME=PATH` of an `--override`.
+fn parse_override(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((soname, path)) if !soname.is_empty() && !path.is_empty() => {
+            Ok((soname.to_owned(), PathBuf::from(path)))
+        }
+        _ => Err("expected SONAME=PATH".to_owned()),
+    }
+}
+
+/// Parses the seconds of a `--timeout`, which may have a fraction.
+fn parse_timeout(value: &str) -> Result<Duration, String> {
+    let secs: f64 = value.parse().map_err(|_| "expected a number of seconds")?;
+    Duration::try_from_secs_f64(secs).map_err(|_| "expected a number of seconds".to_owned())
+}
+
+/// Warns that the output is incomplete, if the `--timeout` ran out.
+fn warn_interrupted(interrupted: bool) {
+    if interrupted {
+        eprintln!("dyn-lib-finder: timed out, so the output is incomplete");
+    }
+}
+
+/// The SBOM formats `--sbom` takes.
+#[derive(Clone, Copy, ValueEnum)]
+enum Sbom {
+    Spdx,
+    Cyclonedx,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let mut config = ResolverConfig::new()
+        .use_env(!args.no_env)
+        .virtual_objects(true)
+        .ignore_sonames(args.ignore)
+        .soname_overrides(args.overrides)
+        .plugin_dirs(args.plugin_dir)
+        .dlopen_notes(args.dlopen_notes)
+        .exhaustive_search(args.all_candidates)
+        .trace_search(args.trace_search)
+        .record_search_paths(args.search_path)
+        .stat_files(args.stat);
+    if args.sha256 {
+        config = config.content_digest(ContentDigest::Sha256);
+    }
+    if args.progress {
+        config = config.progress(|files, _path, libraries| {
+            eprint!("\rdyn-lib-finder: {files} files analyzed, {libraries} libraries found");
+        });
+    }
+    if let Some(timeout) = args.timeout {
+        config = config.deadline(Instant::now() + timeout);
+    }
+    if let Some(dir) = args.working_dir {
+        config = config.working_dir(dir);
+    }
+    if let Some(sysroot) = args.sysroot {
+        config = config.sysroot(sysroot);
+    }
+    if let Some(store) = args.store {
+        config = config.store(store);
+    }
+    if let Some(root) = args.store_root {
+        config = config.store_root(root);
+    }
+    if let Some(target) = args.target {
+        config = config.target(target);
+    }
+    let cache = match args.cache.map(DiskCache::open).transpose() {
+        Ok(cache) => cache.map(Arc::new),
+        Err(error) => {
+            eprintln!("dyn-lib-finder: {error}");
+            return ExitCode::from(2);
+        }
+    };
+    if let Some(cache) = &cache {
+        config = config.disk_cache(cache.clone());
+    }
+    if args.audit {
+        let report = Arc::new(Resolver::new(config)).audit([&args.elf]);
+        if args.progress {
+            // End the progress line
+            eprintln!();
+        }
+        if let Some(error) = cache.and_then(|cache| cache.save().err()) {
+            eprintln!("dyn-lib-finder: {error}");
+        }
+        let _ = io::stdout().write_all(report.to_string().as_bytes());
+        warn_interrupted(report.interrupted);
+        return exit_code(report.is_clean());
+    }
+    if let Some(library) = &args.users_of {
+        let index = Arc::new(Resolver::new(config.recursive(false))).reverse_index([&args.elf]);
+        if args.progress {
+            // End the progress line
+            eprintln!();
+        }
+        if let Some(error) = cache.and_then(|cache| cache.save().err()) {
+            eprintln!("dyn-lib-finder: {error}");
+        }
+        for (_, error) in &index.failed {
+            eprintln!("dyn-lib-finder: {error}");
+        }
+        warn_interrupted(index.interrupted);
+        let users: Vec<_> = if library.contains('/') {
+            index.users_of_path(library).collect()
+        } else {
+            index.users_of_soname(library).collect()
+        };
+        let mut output = String::new();
+        for user in users {
+            output += &format!("{}\n", user.display());
+        }
+        let _ = io::stdout().write_all(output.as_bytes());
+        return exit_code(!index.interrupted);
+    }
+    if let Some(removed) = &args.removing {
+        let resolver = Arc::new(Resolver::new(config));
+        let index = resolver.reverse_index([&args.elf]);
+        let impact = resolver.impact_of_removing(&index, removed);
+        if args.progress {
+            // End the progress line
+            eprintln!();
+        }
+        if let Some(error) = cache.and_then(|cache| cache.save().err()) {
+            eprintln!("dyn-lib-finder: {error}");
+        }
+        for (_, error) in index.failed.iter().chain(&impact.failed) {
+            eprintln!("dyn-lib-finder: {error}");
+        }
+        let mut output = String::new();
+        for (file, lost) in &impact.broken {
+            let lost: Vec<_> = lost.iter().map(String::as_str).collect();
+            output += &format!("{}: {}\n", file.display(), lost.join(", "));
+        }
+        let _ = io::stdout().write_all(output.as_bytes());
+        let interrupted = index.interrupted || impact.interrupted;
+        warn_interrupted(interrupted);
+        return exit_code(!interrupted);
+    }
+    #[cfg(unix)]
+    if args.serve {
+        // Only returns if it fails
+        let error = match Daemon::bind(&args.elf, config) {
+            Ok(daemon) => daemon.run().err(),
+            Err(error) => Some(error),
+        };
+        if let Some(error) = error {
+            eprintln!("dyn-lib-finder: {}: {error}", args.elf.display());
+        }
+        return ExitCode::from(2);
+    }
+    if args.ndjson {
+        let stdout = io::stdout().lock();
+        let scan = Arc::new(Resolver::new(config)).scan_ndjson([&args.elf], stdout);
+        if args.progress {
+            // End the progress line
+            eprintln!();
+        }
+        if let Some(error) = cache.and_then(|cache| cache.save().err()) {
+            eprintln!("dyn-lib-finder: {error}");
+        }
+        return match scan {
+            Ok(interrupted) => {
+                warn_interrupted(interrupted);
+                exit_code(!interrupted)
+            }
+            // Like the other outputs, quietly stop if the reader went away
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("dyn-lib-finder: {error}");
+                ExitCode::from(2)
+            }
+        };
+    }
+    let file = ElfFile::with_config(&args.elf, config);
+    let graph = match file.dependency_graph() {
+        Ok(graph) => graph,
+        Err(error) => {
+            eprintln!("dyn-lib-finder: {error}");
+            return ExitCode::from(2);
+        }
+    };
+    // A cache that can't be saved only makes the next run slower
+    if let Some(error) = cache.and_then(|cache| cache.save().err()) {
+        eprintln!("dyn-lib-finder: {error}");
+    }
+    if args.trace_search {
+        eprint!("{}", graph.to_search_trace());
+    }
+    if args.search_path {
+        eprint!("{}", graph.to_search_paths());
+    }
+
+    let output = if args.hardening {
+        match file.hardening_report() {
+            Ok(report) => report.to_table(),
+            Err(error) => {
+                eprintln!("dyn-lib-finder: {error}");
+                return ExitCode::from(2);
+            }
+        }
+    } else if let Some(sbom) = args.sbom {
+        let format = match sbom {
+            Sbom::Spdx => SbomFormat::Spdx,
+            Sbom::Cyclonedx => SbomFormat::CycloneDx,
+        };
+        match file.to_sbom(format) {
+            Ok(sbom) => sbom,
+            Err(error) => {
+                eprintln!("dyn-lib-finder: {error}");
+                return ExitCode::from(2);
+            }
+        }
+    } else if args.json {
+        match serde_json::to_string_pretty(&graph) {
+            Ok(json) => json + "\n",
+            Err(error) => {
+                eprintln!("dyn-lib-finder: {error}");
+                return ExitCode::from(2);
+            }
+        }
+    } else if let Some(target) = &args.depfile {
+        graph.to_depfile(target)
+    } else if args.csv {
+        match file.to_csv() {
+            Ok(csv) => csv,
+            Err(error) => {
+                eprintln!("dyn-lib-finder: {error}");
+                return ExitCode::from(2);
+            }
+        }
+    } else if args.tree {
+        graph.to_tree()
+    } else {
+        graph.to_ldd()
+    };
+    // Like other command-line tools, quietly stop if the reader went away, e.g. `| head`
+    let _ = io::stdout().write_all(output.as_bytes());
+    warn_interrupted(graph.is_interrupted());
+
+    let denied: Vec<_> = args.deny.iter().map(|name| name.kind()).collect();
+    let mut is_denied = false;
+    if args.lints || !denied.is_empty() {
+        let lints = match file.lints() {
+            Ok(lints) => lints,
+            Err(error) => {
+                eprintln!("dyn-lib-finder: {error}");
+                return ExitCode::from(2);
+            }
+        };
+        for lint in lints {
+            if denied.contains(&lint.kind) {
+                eprintln!("dyn-lib-finder: error: {lint}");
+                is_denied = true;
+            } else if args.lints {
+                eprintln!("dyn-lib-finder: warning: {lint}");
+            }
+        }
+    }
+
+    if let Some(path) = &args.lock {
+        if let Err(error) = file.to_lockfile().and_then(|lockfile| lockfile.write(path)) {
+            eprintln!("dyn-lib-finder: {error}");
+            return ExitCode::from(2);
+        }
+    }
+    let mut is_drifted = false;
+    if let Some(path) = &args.verify_lock {
+        let drift =
+            Lockfile::read(path).and_then(|lockfile| file.verify_against_lockfile(&lockfile));
+        let drift = match drift {
+            Ok(drift) => drift,
+            Err(error) => {
+                eprintln!("dyn-lib-finder: {error}");
+                return ExitCode::from(2);
+            }
+        };
+        for drift in drift {
+            eprintln!("dyn-lib-finder: error: {drift}");
+            is_drifted = true;
+        }
+    }
+
+    let is_loaded = |node: &DependencyNode| node.resolution.is_ok();
+    let is_complete = !graph.is_interrupted();
+    exit_code(graph.nodes().iter().all(is_loaded) && !is_denied && !is_drifted && is_complete)
+}
+
+/// 0 if `success`, 1 otherwise.
+fn exit_code(success: bool) -> ExitCode {
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
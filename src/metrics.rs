@@ -0,0 +1,196 @@
+//! Counts of the work a resolver does, for finding out where the time of an analysis goes.
+use std::ffi::OsString;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{FileMetadata, FileStat, FileSystem};
+
+/// The work a [`Resolver`](crate::Resolver) has done, as returned by
+/// [`Resolver::metrics`](crate::Resolver::metrics). A resolver shared between analyses counts
+/// the work of all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResolutionMetrics {
+    /// The number of files parsed as ELF files.
+    pub files_parsed: u64,
+    /// The number of times a file was needed again after it had been parsed, or was found in
+    /// the [disk cache](crate::ResolverConfig::disk_cache).
+    pub cache_hits: u64,
+    /// The number of searches for a library that were not made, as the same search had found
+    /// nothing before.
+    pub miss_cache_hits: u64,
+    /// The number of bytes read from files, including the headers read to check the
+    /// architecture of candidate libraries and the system configuration files. With the `mmap`
+    /// feature, files parsed in place from a mapping are not counted.
+    pub bytes_read: u64,
+    /// The number of times the metadata of a path was looked up, as by `stat`, `lstat` or
+    /// `readlink`, mostly to check whether candidate libraries exist.
+    pub stat_calls: u64,
+    /// The time spent reading and parsing files. With the `rayon` feature, the time of every
+    /// thread is added up, so it can exceed the wall time of the analysis.
+    pub parse_time: Duration,
+    /// The time spent searching for libraries, likewise added up over threads.
+    pub search_time: Duration,
+}
+
+/// What a resolution phase is timed as.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Phase {
+    Parse,
+    Search,
+}
+
+/// The counters behind [`ResolutionMetrics`], updated from any thread.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    files_parsed: AtomicU64,
+    cache_hits: AtomicU64,
+    miss_cache_hits: AtomicU64,
+    bytes_read: AtomicU64,
+    stat_calls: AtomicU64,
+    /// Nanoseconds spent in each [`Phase`].
+    parse_nanos: AtomicU64,
+    search_nanos: AtomicU64,
+}
+
+impl Counters {
+    pub fn file_parsed(&self) {
+        self.files_parsed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss_cache_hit(&self) {
+        self.miss_cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn read(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn stat(&self) {
+        self.stat_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Runs `f`, adding the time it takes to `phase`.
+    pub fn time<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let nanos = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        let counter = match phase {
+            Phase::Parse => &self.parse_nanos,
+            Phase::Search => &self.search_nanos,
+        };
+        counter.fetch_add(nanos, Ordering::Relaxed);
+        result
+    }
+
+    /// `reader`, counting the bytes read through it.
+    pub fn reader<R>(&self, reader: R) -> MeteredReader<'_, R> {
+        MeteredReader {
+            inner: reader,
+            counters: self,
+        }
+    }
+
+    /// `fs`, counting the bytes read and the metadata looked up through it.
+    pub fn fs<'a>(&'a self, fs: &'a dyn FileSystem) -> MeteredFs<'a> {
+        MeteredFs { fs, counters: self }
+    }
+
+    pub fn snapshot(&self) -> ResolutionMetrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ResolutionMetrics {
+            files_parsed: load(&self.files_parsed),
+            cache_hits: load(&self.cache_hits),
+            miss_cache_hits: load(&self.miss_cache_hits),
+            bytes_read: load(&self.bytes_read),
+            stat_calls: load(&self.stat_calls),
+            parse_time: Duration::from_nanos(load(&self.parse_nanos)),
+            search_time: Duration::from_nanos(load(&self.search_nanos)),
+        }
+    }
+
+    pub fn reset(&self) {
+        for counter in [
+            &self.files_parsed,
+            &self.cache_hits,
+            &self.miss_cache_hits,
+            &self.bytes_read,
+            &self.stat_calls,
+            &self.parse_nanos,
+            &self.search_nanos,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A reader counting the bytes read through it into [`Counters`].
+pub(crate) struct MeteredReader<'a, R> {
+    inner: R,
+    counters: &'a Counters,
+}
+
+impl<R: Read> Read for MeteredReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.counters.read(read as u64);
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for MeteredReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// A [`FileSystem`] counting the work done through it into [`Counters`].
+#[derive(Debug)]
+pub(crate) struct MeteredFs<'a> {
+    fs: &'a dyn FileSystem,
+    counters: &'a Counters,
+}
+
+impl FileSystem for MeteredFs<'_> {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let data = self.fs.read(path)?;
+        self.counters.read(data.len() as u64);
+        Ok(data)
+    }
+
+    fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let data = self.fs.read_at(path, offset, len)?;
+        self.counters.read(data.len() as u64);
+        Ok(data)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.counters.stat();
+        self.fs.metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.counters.stat();
+        self.fs.read_link(path)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        self.counters.stat();
+        self.fs.stat(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        self.fs.read_dir(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.counters.stat();
+        self.fs.exists(path)
+    }
+}
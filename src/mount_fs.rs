@@ -0,0 +1,267 @@
+//! Directories of filesystems mounted at paths of their own, as the store read from another
+//! root, the app and runtime of a Flatpak sandbox, or the image of an AppImage.
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::filesystem::{slice_at, FileKind, FileMetadata, FileStat, FileSystem};
+use crate::sysroot;
+
+/// The files of an inner filesystem, with some of its directories, or those of other
+/// filesystems, mounted at other paths, over the rest of it or over nothing, and symlinks of
+/// its own. Symlinks are followed by this filesystem, so absolute ones lead into the mounts as
+/// well.
+#[derive(Debug)]
+pub(crate) struct MountFs {
+    inner: Arc<dyn FileSystem>,
+    /// Whether the paths outside the mounts are those of the inner filesystem.
+    outside: bool,
+    /// The mount points, and the filesystems and their directories mounted there.
+    mounts: Vec<(PathBuf, Arc<dyn FileSystem>, PathBuf)>,
+    /// The symlinks of this filesystem, and their targets.
+    links: Vec<(PathBuf, PathBuf)>,
+    /// The files of this filesystem, and their contents.
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// A [`MountFs`] reading paths that have no symlinks in their directories, which are read from
+/// the filesystems mounted as they are.
+#[derive(Debug)]
+struct Direct<'a>(&'a MountFs);
+
+impl MountFs {
+    /// Mounts directories over the files of `inner`.
+    pub(crate) fn over(inner: Arc<dyn FileSystem>) -> Self {
+        MountFs {
+            inner,
+            outside: true,
+            mounts: Vec::new(),
+            links: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Mounts directories of `inner` over an empty tree.
+    pub(crate) fn empty(inner: Arc<dyn FileSystem>) -> Self {
+        MountFs {
+            outside: false,
+            ..MountFs::over(inner)
+        }
+    }
+
+    /// Mounts the directory `dir` of the inner filesystem at `at`.
+    pub(crate) fn mount<P: Into<PathBuf>, D: Into<PathBuf>>(self, at: P, dir: D) -> Self {
+        let inner = self.inner.clone();
+        self.mount_fs(at, inner, dir)
+    }
+
+    /// Mounts the directory `dir` of `fs` at `at`.
+    pub(crate) fn mount_fs<P: Into<PathBuf>, D: Into<PathBuf>>(
+        mut self,
+        at: P,
+        fs: Arc<dyn FileSystem>,
+        dir: D,
+    ) -> Self {
+        self.mounts.push((at.into(), fs, dir.into()));
+        self
+    }
+
+    /// Adds a symlink at `at` to `target`.
+    pub(crate) fn symlink<P: Into<PathBuf>, T: Into<PathBuf>>(mut self, at: P, target: T) -> Self {
+        self.links.push((at.into(), target.into()));
+        self
+    }
+
+    /// Adds a file at `at` with the contents `contents`.
+    pub(crate) fn file<P: Into<PathBuf>>(mut self, at: P, contents: &[u8]) -> Self {
+        self.files.push((at.into(), contents.to_vec()));
+        self
+    }
+
+    /// The contents of the file of this filesystem at `path`, if it has one there.
+    fn own_file(&self, path: &Path) -> Option<&[u8]> {
+        let (_, contents) = self.files.iter().find(|(at, _)| at == path)?;
+        Some(contents)
+    }
+
+    /// The filesystem `path` is read from, and its path there, through the innermost mount it
+    /// is in, or `None` if it is outside them over an empty tree.
+    fn inner_path(&self, path: &Path) -> Option<(&dyn FileSystem, PathBuf)> {
+        let mount = self
+            .mounts
+            .iter()
+            .filter(|(at, _, _)| path.starts_with(at))
+            .max_by_key(|(at, _, _)| at.components().count());
+        match mount {
+            Some((at, fs, dir)) => Some((&**fs, dir.join(path.strip_prefix(at).ok()?))),
+            None => self.outside.then(|| (&*self.inner, path.to_owned())),
+        }
+    }
+
+    /// The names of the mount points, symlinks and files right inside the directory `path`.
+    fn own_entries(&self, path: &Path) -> Vec<OsString> {
+        let own = self.mounts.iter().map(|(at, _, _)| at);
+        let own = own.chain(self.links.iter().map(|(at, _)| at));
+        let own = own.chain(self.files.iter().map(|(at, _)| at));
+        own.filter_map(|at| match at.strip_prefix(path) {
+            Ok(relative) if relative != Path::new("") => {
+                Some(relative.components().next()?.as_os_str().to_owned())
+            }
+            _ => None,
+        })
+        .collect()
+    }
+
+    /// `path` with every symlink along it followed. Relative paths are left to the inner
+    /// filesystem.
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        if path.is_relative() {
+            return Ok(path.to_owned());
+        }
+        sysroot::resolve_in(&Direct(self), Path::new("/"), path)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    /// `path` with the symlinks in its directories followed, but not a final one.
+    fn lookup(&self, path: &Path) -> io::Result<PathBuf> {
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => Ok(self.resolve(parent)?.join(name)),
+            _ => Ok(path.to_owned()),
+        }
+    }
+}
+
+impl Direct<'_> {
+    fn inner_path(&self, path: &Path) -> io::Result<(&dyn FileSystem, PathBuf)> {
+        self.0
+            .inner_path(path)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+impl FileSystem for Direct<'_> {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.0.own_file(path) {
+            Some(contents) => Ok(contents.to_vec()),
+            None => {
+                let (fs, path) = self.inner_path(path)?;
+                fs.read(&path)
+            }
+        }
+    }
+
+    fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        match self.0.own_file(path) {
+            Some(contents) => Ok(slice_at(contents, offset, len).to_vec()),
+            None => {
+                let (fs, path) = self.inner_path(path)?;
+                fs.read_at(&path, offset, len)
+            }
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        if let Some((_, target)) = self.0.links.iter().find(|(at, _)| at == path) {
+            return Ok(FileMetadata {
+                kind: FileKind::Symlink,
+                len: target.as_os_str().len() as u64,
+                mode: 0o777,
+            });
+        }
+        if let Some(contents) = self.0.own_file(path) {
+            return Ok(FileMetadata {
+                kind: FileKind::File,
+                len: contents.len() as u64,
+                mode: 0o644,
+            });
+        }
+        // The directories mount points are in need not exist in the inner filesystem
+        let inner = self
+            .inner_path(path)
+            .and_then(|(fs, path)| fs.metadata(&path));
+        if inner.is_err() && !self.0.own_entries(path).is_empty() {
+            return Ok(FileMetadata {
+                kind: FileKind::Dir,
+                len: 0,
+                mode: 0o755,
+            });
+        }
+        inner
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        match self.0.own_file(path) {
+            Some(contents) => Ok(FileStat {
+                len: contents.len() as u64,
+                mode: 0o644,
+                uid: None,
+                gid: None,
+                modified: None,
+            }),
+            None => {
+                let (fs, path) = self.inner_path(path)?;
+                fs.stat(&path)
+            }
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.0.links.iter().find(|(at, _)| at == path) {
+            Some((_, target)) => Ok(target.clone()),
+            None => {
+                let (fs, path) = self.inner_path(path)?;
+                fs.read_link(&path)
+            }
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        let own = self.0.own_entries(path);
+        let inner = self
+            .inner_path(path)
+            .and_then(|(fs, path)| fs.read_dir(&path));
+        let mut names = match inner {
+            Ok(names) => names,
+            Err(_) if !own.is_empty() => Vec::new(),
+            Err(error) => return Err(error),
+        };
+        for name in own {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+}
+
+impl FileSystem for MountFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        Direct(self).read(&self.resolve(path)?)
+    }
+
+    fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        Direct(self).read_at(&self.resolve(path)?, offset, len)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        Direct(self).metadata(&self.lookup(path)?)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        Direct(self).read_link(&self.lookup(path)?)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        Direct(self).stat(&self.resolve(path)?)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        Direct(self).read_dir(&self.resolve(path)?)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.resolve(path)
+            .is_ok_and(|path| path.is_absolute() || self.inner.exists(&path))
+    }
+}
@@ -0,0 +1,88 @@
+//! The search path of musl's dynamic linker, which is read from `/etc/ld-musl-$(ARCH).path`
+//! rather than from `ld.so.conf` and a cache.
+use std::path::PathBuf;
+
+/// The search path musl falls back to when there is no path file.
+pub(crate) const DEFAULT_SEARCH_DIRS: [&str; 3] = ["/lib", "/usr/local/lib", "/usr/lib"];
+
+/// The architecture names musl uses in the names of its dynamic linker and path file.
+const ARCHES: [&str; 22] = [
+    "aarch64",
+    "aarch64_be",
+    "arm",
+    "armeb",
+    "armebhf",
+    "armhf",
+    "i386",
+    "loongarch64",
+    "m68k",
+    "microblaze",
+    "mips",
+    "mips64",
+    "mips64el",
+    "mipsel",
+    "or1k",
+    "powerpc",
+    "powerpc64",
+    "powerpc64le",
+    "riscv32",
+    "riscv64",
+    "s390x",
+    "x86_64",
+];
+
+/// The musl architecture of a dynamic linker named like `ld-musl-x86_64.so.1`, or `None` if
+/// `file_name` is not that of a musl dynamic linker.
+pub(crate) fn arch_of_interpreter(file_name: &str) -> Option<&'static str> {
+    let arch = file_name.strip_prefix("ld-musl-")?.strip_suffix(".so.1")?;
+    // Variants like soft-float ones have a suffix appended to the architecture
+    ARCHES
+        .iter()
+        .filter(|&&known| arch.starts_with(known))
+        .max_by_key(|known| known.len())
+        .copied()
+        .or(Some(""))
+}
+
+/// The musl architecture this crate was compiled for, if it is a known one.
+pub(crate) fn host_arch() -> &'static str {
+    let arch = match std::env::consts::ARCH {
+        "x86" => "i386",
+        arch => arch,
+    };
+    ARCHES
+        .into_iter()
+        .find(|&known| known == arch)
+        .unwrap_or("")
+}
+
+/// The location of the path file for `arch`.
+pub(crate) fn path_file(arch: &str) -> String {
+    format!("/etc/ld-musl-{arch}.path")
+}
+
+/// The directories listed in a path file, which musl separates by colons or newlines.
+pub(crate) fn parse_path_file(contents: &str) -> Vec<PathBuf> {
+    contents
+        .split([':', '\n'])
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arch_of_interpreter, parse_path_file};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_path_file() {
+        assert_eq!(arch_of_interpreter("ld-musl-x86_64.so.1"), Some("x86_64"));
+        assert_eq!(arch_of_interpreter("ld-musl-armhf.so.1"), Some("armhf"));
+        assert_eq!(arch_of_interpreter("ld-musl-mips-sf.so.1"), Some("mips"));
+        assert_eq!(arch_of_interpreter("ld-linux-x86-64.so.2"), None);
+
+        let dirs = parse_path_file("/lib:/usr/lib\n/opt/lib\n\n");
+        assert_eq!(dirs, ["/lib", "/usr/lib", "/opt/lib"].map(PathBuf::from));
+    }
+}
@@ -0,0 +1,175 @@
+//! Newline-delimited JSON streams of scan results, one object per file as soon as it is
+//! analyzed, for piping into `jq` or ingestion pipelines.
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{DependencyGraph, Error, Resolver};
+
+/// One line of [`Resolver::scan_ndjson`].
+#[derive(serde::Serialize)]
+struct Record<'a> {
+    #[serde(serialize_with = "crate::serde_path::serialize")]
+    path: &'a Path,
+    #[serde(flatten)]
+    outcome: Outcome<'a>,
+}
+
+/// What became of the file of a [`Record`], under the key `graph` or `error`.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome<'a> {
+    Graph(&'a DependencyGraph),
+    Error(&'a Error),
+}
+
+impl Resolver {
+    /// Finds every ELF file under the directories `roots` like [`Resolver::audit`], and writes
+    /// each to `out` as soon as it is analyzed, as a JSON object on a line of its own with its
+    /// `path` and either its dependency `graph` or the `error` it failed with. Directories
+    /// that could not be listed are written with their error too. Nothing is kept once
+    /// written, so scans of any size take as much memory as their largest graph.
+    ///
+    /// `out` is flushed after each line. Returns whether the scan was stopped by the
+    /// [cancel flag](crate::ResolverConfig::cancel_flag) or the
+    /// [deadline](crate::ResolverConfig::deadline), or the first error writing to `out`, which
+    /// stops the scan.
+    pub fn scan_ndjson<I, P, W>(self: &Arc<Self>, roots: I, mut out: W) -> io::Result<bool>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+        W: Write,
+    {
+        let mut result = Ok(());
+        let interrupted = self.scan(roots, &mut |path, graph| {
+            let outcome = match &graph {
+                Ok(graph) => Outcome::Graph(graph),
+                Err(error) => Outcome::Error(error),
+            };
+            result = write_record(&mut out, &Record { path, outcome });
+            match result {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(_) => ControlFlow::Break(()),
+            }
+        });
+        result.map(|()| interrupted)
+    }
+}
+
+/// Writes `record` to `out` as a line of JSON, and flushes it.
+fn write_record(out: &mut impl Write, record: &Record) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, record)?;
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ElfBuilder;
+    use crate::{DependencyGraph, Resolver, ResolverConfig};
+    use std::fs;
+    use std::io::{self, Write};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_scan_ndjson() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+        let broken = dir.path().join("broken");
+        fs::write(&broken, b"\x7fELF").unwrap();
+        fs::write(dir.path().join("notes.txt"), "not an ELF file").unwrap();
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(dir.path());
+        let resolver = Arc::new(Resolver::new(config));
+
+        let mut out = Vec::new();
+        assert!(!resolver.scan_ndjson([dir.path()], &mut out).unwrap());
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let paths: Vec<_> = lines
+            .iter()
+            .map(|line| line["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                app.to_str().unwrap(),
+                broken.to_str().unwrap(),
+                lib.to_str().unwrap()
+            ]
+        );
+        let graph: DependencyGraph = serde_json::from_value(lines[0]["graph"].clone()).unwrap();
+        assert_eq!(graph.nodes()[1].path(), Some(lib.as_path()));
+        assert!(lines[1].get("graph").is_none());
+        assert!(lines[1]["error"].get("Parse").is_some());
+    }
+
+    #[test]
+    fn test_scan_ndjson_non_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().join(OsStr::from_bytes(b"dir\xff"));
+        let lib = ElfBuilder::new().write(&lib_dir, "libfoo.so.1");
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(&lib_dir);
+        let resolver = Arc::new(Resolver::new(config));
+
+        // A path that is not UTF-8 is written as its bytes, without ending the stream
+        let mut out = Vec::new();
+        assert!(!resolver.scan_ndjson([dir.path()], &mut out).unwrap());
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["path"], app.to_str().unwrap());
+        let graph: DependencyGraph = serde_json::from_value(lines[0]["graph"].clone()).unwrap();
+        assert_eq!(graph.nodes()[1].path(), Some(lib.as_path()));
+        let path: Vec<u8> = serde_json::from_value(lines[1]["path"].clone()).unwrap();
+        assert_eq!(path, lib.as_os_str().as_bytes());
+        assert!(lines[1].get("graph").is_some());
+    }
+
+    /// A writer that fails on every write.
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_scan_ndjson_write_error() {
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new().write(dir.path(), "app1");
+        ElfBuilder::new().write(dir.path(), "app2");
+        let resolver = Arc::new(Resolver::new(ResolverConfig::new().use_env(false)));
+
+        let error = resolver.scan_ndjson([dir.path()], Closed).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        // The scan stopped at the first file
+        assert_eq!(resolver.parsed_files(), 1);
+    }
+}
@@ -0,0 +1,484 @@
+//! Access to the headers and contents of ELF files that is the same whichever backend parses
+//! them. A [`Backend`] decodes the structures of the format, while [`Object`] reads the parts
+//! of a file that are asked for, and only those.
+use std::borrow::Cow;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::abi::{
+    DT_NULL, PN_XNUM, PT_DYNAMIC, PT_NOTE, SHN_UNDEF, SHN_XINDEX, SHT_DYNAMIC, SHT_NOBITS, SHT_NOTE,
+};
+use crate::parse::c_str;
+use crate::ParseError;
+
+#[cfg(all(feature = "elf", not(feature = "goblin")))]
+pub(crate) type DefaultBackend = crate::elf_backend::ElfBackend;
+#[cfg(feature = "goblin")]
+pub(crate) type DefaultBackend = crate::goblin_backend::GoblinBackend;
+
+/// The largest file header, that of 64-bit files.
+pub(crate) const MAX_HEADER_SIZE: u64 = 64;
+
+/// A decoder of the structures of ELF files, which the `elf` feature implements with the `elf`
+/// crate and the `goblin` feature with `goblin`. Each function is given the bytes of a single
+/// structure or table, never the whole file.
+pub(crate) trait Backend {
+    /// The file header at the start of `data`, which holds the first [`MAX_HEADER_SIZE`] bytes
+    /// of the file, or all of it if it is shorter.
+    fn file_header(data: &[u8]) -> Result<FileHeader, ParseError>;
+
+    /// The section headers in the section header table `data`.
+    fn section_headers(header: &FileHeader, data: &[u8]) -> Result<Vec<SectionHeader>, ParseError>;
+
+    /// The program headers in the program header table `data`.
+    fn program_headers(header: &FileHeader, data: &[u8]) -> Result<Vec<ProgramHeader>, ParseError>;
+
+    /// The tags and values of the entries of the dynamic array `data`.
+    fn dynamic(header: &FileHeader, data: &[u8]) -> Vec<(i64, u64)>;
+
+    /// The symbols in the symbol table `data`.
+    fn symbols(header: &FileHeader, data: &[u8]) -> Vec<Symbol>;
+
+    /// The notes in `data`, whose entries are aligned to `align`, up to the first malformed one.
+    fn notes<'a>(header: &FileHeader, align: u64, data: &'a [u8]) -> Vec<Note<'a>>;
+
+    /// The first `count` entries of the `.gnu.version_r` section `data`.
+    fn version_needs(header: &FileHeader, data: &[u8], count: u64) -> Vec<VersionNeed>;
+
+    /// The first `count` entries of the `.gnu.version_d` section `data`.
+    fn version_defs(header: &FileHeader, data: &[u8], count: u64) -> Vec<VersionDef>;
+}
+
+/// The fields of the file header of an ELF file that are needed here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileHeader {
+    pub is_64_bit: bool,
+    pub big_endian: bool,
+    pub os_abi: u8,
+    pub abi_version: u8,
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_phoff: u64,
+    pub e_shoff: u64,
+    pub e_phentsize: u16,
+    pub e_phnum: u16,
+    pub e_shentsize: u16,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+impl FileHeader {
+    /// Reads the unsigned integer of `size` bytes at `*pos` in `data`, in the byte order of the
+    /// file, and moves past it.
+    pub fn read_uint(&self, data: &[u8], pos: &mut usize, size: usize) -> Option<u64> {
+        let bytes = data.get(*pos..pos.checked_add(size)?)?;
+        *pos += size;
+        let fold = |value: u64, &byte: &u8| value << 8 | u64::from(byte);
+        Some(if self.big_endian {
+            bytes.iter().fold(0, fold)
+        } else {
+            bytes.iter().rev().fold(0, fold)
+        })
+    }
+
+    /// Reads the 32-bit word at `*pos` in `data`, and moves past it.
+    pub fn read_u32(&self, data: &[u8], pos: &mut usize) -> Option<u32> {
+        self.read_uint(data, pos, 4).map(|value| value as u32)
+    }
+
+    /// Reads the address-sized word at `*pos` in `data`, and moves past it.
+    pub fn read_word(&self, data: &[u8], pos: &mut usize) -> Option<u64> {
+        self.read_uint(data, pos, if self.is_64_bit { 8 } else { 4 })
+    }
+}
+
+/// A section header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct SectionHeader {
+    pub sh_name: u32,
+    pub sh_type: u32,
+    pub sh_flags: u64,
+    pub sh_addr: u64,
+    pub sh_offset: u64,
+    pub sh_size: u64,
+    pub sh_link: u32,
+    pub sh_info: u32,
+    pub sh_addralign: u64,
+    pub sh_entsize: u64,
+}
+
+/// A program header, describing a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct ProgramHeader {
+    pub p_type: u32,
+    pub p_flags: u32,
+    pub p_offset: u64,
+    pub p_vaddr: u64,
+    pub p_filesz: u64,
+    pub p_memsz: u64,
+    pub p_align: u64,
+}
+
+/// The symbols of a symbol table and the string table their names are in.
+pub(crate) type SymbolTable = (Vec<Symbol>, Vec<u8>);
+
+/// An entry of a symbol table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Symbol {
+    pub st_name: u32,
+    pub st_info: u8,
+    /// Only the visibility bits, the rest being reserved.
+    pub st_other: u8,
+    pub st_shndx: u16,
+    pub st_value: u64,
+    pub st_size: u64,
+}
+
+impl Symbol {
+    /// The binding of the symbol, an `STB_*` value.
+    pub fn bind(&self) -> u8 {
+        self.st_info >> 4
+    }
+
+    /// The visibility of the symbol, an `STV_*` value.
+    pub fn visibility(&self) -> u8 {
+        self.st_other & 0x3
+    }
+
+    /// Whether the symbol is defined in another object.
+    pub fn is_undefined(&self) -> bool {
+        self.st_shndx == SHN_UNDEF
+    }
+}
+
+/// A note, with its name without the terminating NUL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Note<'a> {
+    pub n_type: u64,
+    pub name: &'a [u8],
+    pub desc: &'a [u8],
+}
+
+/// The versions required from one library, an entry of `.gnu.version_r`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VersionNeed {
+    /// The offset of the soname of the library in the string table.
+    pub file: u32,
+    /// The offset of the name of each version in the string table, and its flags.
+    pub versions: Vec<(u32, u16)>,
+}
+
+/// A version an object defines, an entry of `.gnu.version_d`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VersionDef {
+    /// The index `.gnu.version` refers to the version by.
+    pub index: u16,
+    pub flags: u16,
+    /// The offsets of the names in the string table: that of the version, then those of its
+    /// parents.
+    pub names: Vec<u32>,
+}
+
+/// Where the bytes of an ELF file come from: a reader, through [`Stream`], or memory.
+pub(crate) trait Source {
+    /// The size of the file.
+    fn len(&mut self) -> Result<u64, ParseError>;
+
+    /// The `size` bytes at `offset`, failing for ranges past the end of the file.
+    fn bytes(&mut self, offset: u64, size: u64) -> Result<Cow<'_, [u8]>, ParseError>;
+}
+
+/// A file read through `R`, reading only the ranges that are asked for.
+pub(crate) struct Stream<R>(pub R);
+
+impl<R: Read + Seek> Source for Stream<R> {
+    fn len(&mut self) -> Result<u64, ParseError> {
+        Ok(self.0.seek(SeekFrom::End(0))?)
+    }
+
+    fn bytes(&mut self, offset: u64, size: u64) -> Result<Cow<'_, [u8]>, ParseError> {
+        let end = offset.checked_add(size);
+        if end.is_none_or(|end| end > self.len().unwrap_or(0)) {
+            return Err(ParseError::bad_range(offset, size));
+        }
+        self.0.seek(SeekFrom::Start(offset))?;
+        let size = usize::try_from(size).map_err(|_| ParseError::bad_range(offset, size))?;
+        let mut data = vec![0; size];
+        self.0.read_exact(&mut data)?;
+        Ok(Cow::Owned(data))
+    }
+}
+
+impl Source for &[u8] {
+    fn len(&mut self) -> Result<u64, ParseError> {
+        Ok(<[u8]>::len(self) as u64)
+    }
+
+    fn bytes(&mut self, offset: u64, size: u64) -> Result<Cow<'_, [u8]>, ParseError> {
+        let range = usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(size).ok())
+            .and_then(|(start, size)| Some(start..start.checked_add(size)?));
+        let bytes = range.and_then(|range| self.get(range));
+        bytes
+            .map(Cow::Borrowed)
+            .ok_or_else(|| ParseError::bad_range(offset, size))
+    }
+}
+
+/// An ELF file whose headers have been read, and whose other parts are read from `S` when they
+/// are asked for.
+pub(crate) struct Object<S> {
+    pub header: FileHeader,
+    pub sections: Vec<SectionHeader>,
+    pub segments: Vec<ProgramHeader>,
+    /// The section name table, once it has been read, or `None` inside if there is none.
+    section_names: Option<Option<Vec<u8>>>,
+    source: S,
+}
+
+impl<S: Source> Object<S> {
+    /// Reads the file header, section headers and program headers of the file `source` reads.
+    pub fn open(mut source: S) -> Result<Self, ParseError> {
+        let header = file_header(&mut source)?;
+        // Files with too many sections or segments for the header keep the number in the first
+        // section header
+        let first_section = |source: &mut S| -> Result<SectionHeader, ParseError> {
+            let tables = section_header_table(&header, source, 1)?;
+            let first = tables.first().copied();
+            first.ok_or_else(|| ParseError::malformed("the first section header is missing"))
+        };
+
+        let mut sections = Vec::new();
+        if header.e_shoff != 0 {
+            let count = match header.e_shnum {
+                0 => first_section(&mut source)?.sh_size,
+                count => count.into(),
+            };
+            sections = section_header_table(&header, &mut source, count)?;
+        }
+        let mut segments = Vec::new();
+        if header.e_phoff != 0 {
+            let count = match header.e_phnum {
+                PN_XNUM => first_section(&mut source)?.sh_info.into(),
+                count => u64::from(count),
+            };
+            let size = table_size(header.e_phentsize, if header.is_64_bit { 56 } else { 32 })?;
+            let size = size
+                .checked_mul(count)
+                .ok_or_else(|| ParseError::bad_range(header.e_phoff, u64::MAX))?;
+            let data = source.bytes(header.e_phoff, size)?;
+            segments = DefaultBackend::program_headers(&header, &data)?;
+        }
+        Ok(Object {
+            header,
+            sections,
+            segments,
+            section_names: None,
+            source,
+        })
+    }
+
+    /// The `size` bytes at `offset`.
+    pub fn bytes(&mut self, (offset, size): (u64, u64)) -> Result<Cow<'_, [u8]>, ParseError> {
+        self.source.bytes(offset, size)
+    }
+
+    /// The contents of the section `shdr`, empty for sections that take no space in the file.
+    pub fn section_data(&mut self, shdr: &SectionHeader) -> Result<Cow<'_, [u8]>, ParseError> {
+        if shdr.sh_type == SHT_NOBITS {
+            return Ok(Cow::Borrowed(&[]));
+        }
+        self.source.bytes(shdr.sh_offset, shdr.sh_size)
+    }
+
+    /// The contents of the segment `phdr` in the file.
+    pub fn segment_data(&mut self, phdr: &ProgramHeader) -> Result<Cow<'_, [u8]>, ParseError> {
+        self.source.bytes(phdr.p_offset, phdr.p_filesz)
+    }
+
+    /// The sections whose names `is_wanted` accepts, in order. A file without a section name
+    /// table has none.
+    pub fn sections_named(
+        &mut self,
+        is_wanted: impl Fn(&[u8]) -> bool,
+    ) -> Result<Vec<SectionHeader>, ParseError> {
+        if self.section_names.is_none() {
+            self.section_names = Some(self.read_section_names()?);
+        }
+        let Some(Some(names)) = &self.section_names else {
+            return Ok(Vec::new());
+        };
+        let name = |shdr: &SectionHeader| {
+            let name = usize::try_from(shdr.sh_name)
+                .ok()
+                .and_then(|start| names.get(start..));
+            name.and_then(c_str)
+        };
+        let sections = self.sections.iter();
+        Ok(sections
+            .filter(|shdr| name(shdr).is_some_and(&is_wanted))
+            .copied()
+            .collect())
+    }
+
+    /// The first section named `name`.
+    pub fn section_by_name(&mut self, name: &str) -> Result<Option<SectionHeader>, ParseError> {
+        let sections = self.sections_named(|section| section == name.as_bytes())?;
+        Ok(sections.first().copied())
+    }
+
+    /// Reads the section name table, which `e_shstrndx` names.
+    fn read_section_names(&mut self) -> Result<Option<Vec<u8>>, ParseError> {
+        let index = match self.header.e_shstrndx {
+            SHN_UNDEF => return Ok(None),
+            SHN_XINDEX => self.sections.first().map_or(0, |shdr| shdr.sh_link),
+            index => index.into(),
+        };
+        if self.sections.is_empty() {
+            return Ok(None);
+        }
+        let shdr = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.sections.get(index))
+            .copied()
+            .ok_or_else(|| ParseError::malformed(format_args!("no section {index} for names")))?;
+        Ok(Some(self.section_data(&shdr)?.into_owned()))
+    }
+
+    /// The file offset and size of the dynamic array, found through the section headers or,
+    /// for files whose section headers have been stripped, through the `PT_DYNAMIC` program
+    /// header.
+    pub fn dynamic_range(&self) -> Option<(u64, u64)> {
+        let section = self
+            .sections
+            .iter()
+            .find(|shdr| shdr.sh_type == SHT_DYNAMIC)
+            .map(|shdr| (shdr.sh_offset, shdr.sh_size));
+        section.or_else(|| {
+            self.segments
+                .iter()
+                .find(|phdr| phdr.p_type == PT_DYNAMIC)
+                .map(|phdr| (phdr.p_offset, phdr.p_filesz))
+        })
+    }
+
+    /// The entries of the dynamic array at `range`, up to the `DT_NULL` that ends it.
+    pub fn dynamic_at(&mut self, range: (u64, u64)) -> Result<Vec<(i64, u64)>, ParseError> {
+        let header = self.header;
+        let data = self.bytes(range)?;
+        let mut dynamic = DefaultBackend::dynamic(&header, &data);
+        if let Some(end) = dynamic.iter().position(|&(tag, _)| tag == DT_NULL) {
+            dynamic.truncate(end);
+        }
+        Ok(dynamic)
+    }
+
+    /// The entries of the dynamic array, or `None` if the file has none.
+    pub fn dynamic(&mut self) -> Result<Option<Vec<(i64, u64)>>, ParseError> {
+        self.dynamic_range()
+            .map(|range| self.dynamic_at(range))
+            .transpose()
+    }
+
+    /// The symbols of the first symbol table of type `sh_type`, `SHT_DYNSYM` or `SHT_SYMTAB`,
+    /// and the string table their names are in, or `None` if there is no such table.
+    pub fn symbols(&mut self, sh_type: u32) -> Result<Option<SymbolTable>, ParseError> {
+        let Some(shdr) = self.sections.iter().find(|shdr| shdr.sh_type == sh_type) else {
+            return Ok(None);
+        };
+        let shdr = *shdr;
+        let strings = self.linked_strings(&shdr)?;
+        let header = self.header;
+        let data = self.section_data(&shdr)?;
+        Ok(Some((DefaultBackend::symbols(&header, &data), strings)))
+    }
+
+    /// The contents of the string table the section `shdr` links to.
+    pub fn linked_strings(&mut self, shdr: &SectionHeader) -> Result<Vec<u8>, ParseError> {
+        let strings = usize::try_from(shdr.sh_link)
+            .ok()
+            .and_then(|index| self.sections.get(index))
+            .copied()
+            .ok_or_else(|| ParseError::malformed(format_args!("no section {}", shdr.sh_link)))?;
+        Ok(self.section_data(&strings)?.into_owned())
+    }
+
+    /// The entries of the `.gnu.version_r` section `shdr`.
+    pub fn version_needs(&mut self, shdr: &SectionHeader) -> Result<Vec<VersionNeed>, ParseError> {
+        let header = self.header;
+        let data = self.section_data(shdr)?;
+        Ok(DefaultBackend::version_needs(
+            &header,
+            &data,
+            shdr.sh_info.into(),
+        ))
+    }
+
+    /// The entries of the `.gnu.version_d` section `shdr`.
+    pub fn version_defs(&mut self, shdr: &SectionHeader) -> Result<Vec<VersionDef>, ParseError> {
+        let header = self.header;
+        let data = self.section_data(shdr)?;
+        Ok(DefaultBackend::version_defs(
+            &header,
+            &data,
+            shdr.sh_info.into(),
+        ))
+    }
+
+    /// The notes in the section `section` or, if there is no such section, in the `PT_NOTE`
+    /// segments, that `f` maps to a value. Notes that can't be read are left out.
+    pub fn notes<T>(&mut self, section: &str, mut f: impl FnMut(Note<'_>) -> Option<T>) -> Vec<T> {
+        let header = self.header;
+        let shdr = self.section_by_name(section).ok().flatten();
+        if let Some(shdr) = shdr.filter(|shdr| shdr.sh_type == SHT_NOTE) {
+            let Ok(data) = self.section_data(&shdr) else {
+                return Vec::new();
+            };
+            let notes = DefaultBackend::notes(&header, shdr.sh_addralign, &data);
+            return notes.into_iter().filter_map(f).collect();
+        }
+        let segments: Vec<ProgramHeader> = self
+            .segments
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_NOTE)
+            .copied()
+            .collect();
+        let mut values = Vec::new();
+        for phdr in &segments {
+            if let Ok(data) = self.segment_data(phdr) {
+                let notes = DefaultBackend::notes(&header, phdr.p_align, &data);
+                values.extend(notes.into_iter().filter_map(&mut f));
+            }
+        }
+        values
+    }
+}
+
+/// Reads the file header of the file `source` reads.
+pub(crate) fn file_header(source: &mut impl Source) -> Result<FileHeader, ParseError> {
+    let size = source.len()?.min(MAX_HEADER_SIZE);
+    DefaultBackend::file_header(&source.bytes(0, size)?)
+}
+
+/// Reads the first `count` entries of the section header table.
+fn section_header_table(
+    header: &FileHeader,
+    source: &mut impl Source,
+    count: u64,
+) -> Result<Vec<SectionHeader>, ParseError> {
+    let size = table_size(header.e_shentsize, if header.is_64_bit { 64 } else { 40 })?;
+    let size = size
+        .checked_mul(count)
+        .ok_or_else(|| ParseError::bad_range(header.e_shoff, u64::MAX))?;
+    let data = source.bytes(header.e_shoff, size)?;
+    DefaultBackend::section_headers(header, &data)
+}
+
+/// The size of the entries of a header table, which must be `expected` for its class.
+fn table_size(entry_size: u16, expected: u64) -> Result<u64, ParseError> {
+    if u64::from(entry_size) != expected {
+        let message = format_args!("header table entries of {entry_size} bytes, not {expected}");
+        return Err(ParseError::malformed(message));
+    }
+    Ok(expected)
+}
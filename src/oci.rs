@@ -0,0 +1,320 @@
+//! Container images saved by `docker save` or as OCI image layout archives, whose layers are
+//! stacked in memory so binaries can be analyzed without extracting the image.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::archive_fs::{read_at, ArchiveFs, Format};
+use crate::filesystem::FileSystem;
+use crate::tar::{self, EntryKind};
+use crate::{DependencyGraph, ElfFile, Error, ResolverConfig};
+/// The annotation OCI image indexes name each image of the layout with.
+const REF_NAME_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+
+/// A container image in a tar archive, as written by `docker save` or holding an OCI image
+/// layout, with its layers applied in order. Only the headers of the layers are read when the
+/// image is opened; files are read from the archive as needed, except for the files of
+/// compressed layers, which are decompressed into memory.
+///
+/// ```no_run
+/// use elf_dynamic_lib_getter::{OciImage, ResolverConfig};
+///
+/// let image = OciImage::open("nginx.tar")?;
+/// let graph = image.dependency_graph("/usr/sbin/nginx", ResolverConfig::new())?;
+/// print!("{}", graph.to_ldd());
+/// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct OciImage {
+    tags: Vec<String>,
+    fs: Arc<ArchiveFs>,
+}
+
+impl OciImage {
+    /// Opens the image archive at `path`. If it holds several images, the first is used, and
+    /// of a multi-platform image the first platform.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if the archive can't be read, or [`Error::InvalidImage`] if it isn't an
+    /// image archive, or has a layer compressed other than with gzip, xz or zstd. A layer
+    /// compressed with a format whose feature is disabled gives an [`Error::Io`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        OciImage::open_with_config(path, &ResolverConfig::new())
+    }
+
+    /// Opens the image archive at `path` as [`OciImage::open`] does, decompressing each layer
+    /// only up to the [maximum decompressed size](ResolverConfig::max_decompressed_size) of
+    /// `config`.
+    ///
+    /// # Return Value [Err]
+    /// As for [`OciImage::open`], or [`Error::LimitExceeded`] if a layer decompresses to more.
+    pub fn open_with_config<P: AsRef<Path>>(
+        path: P,
+        config: &ResolverConfig,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let limit = config.decompressed_size_limit();
+        let io_error = |source: io::Error| {
+            config.decompression_error(path, source, |source| match source.kind() {
+                io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => Error::InvalidImage {
+                    path: path.to_owned(),
+                },
+                _ => Error::Io {
+                    path: path.to_owned(),
+                    source,
+                },
+            })
+        };
+        let invalid = || Error::InvalidImage {
+            path: path.to_owned(),
+        };
+        let mut archive = File::open(path).map_err(io_error)?;
+        let end = archive.metadata().map_err(io_error)?.len();
+        let blobs: HashMap<PathBuf, (u64, u64)> = tar::entries(&mut archive, 0, end, limit)
+            .map_err(io_error)?
+            .into_iter()
+            .filter(|entry| entry.kind == EntryKind::File)
+            .map(|entry| (entry.path, (entry.offset, entry.size)))
+            .collect();
+        let mut read_json = |name: &str| -> Result<Option<Value>, Error> {
+            let Some(&(offset, size)) = blobs.get(Path::new(name)) else {
+                return Ok(None);
+            };
+            let data = read_at(&mut archive, offset, size).map_err(io_error)?;
+            serde_json::from_slice(&data)
+                .map(Some)
+                .map_err(|_| invalid())
+        };
+
+        let strings = |value: &Value| -> Vec<String> {
+            let strings = value.as_array().into_iter().flatten();
+            strings
+                .filter_map(|string| Some(string.as_str()?.to_owned()))
+                .collect()
+        };
+        let (layers, tags) = if let Some(manifest) = read_json("manifest.json")? {
+            // docker save lists each image with its tags and layer files
+            let image = &manifest[0];
+            (strings(&image["Layers"]), strings(&image["RepoTags"]))
+        } else if let Some(index) = read_json("index.json")? {
+            let tags = index["manifests"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|manifest| {
+                    Some(
+                        manifest["annotations"][REF_NAME_ANNOTATION]
+                            .as_str()?
+                            .to_owned(),
+                    )
+                })
+                .collect();
+            // Multi-platform images are indexes of per-platform manifests
+            let mut manifest = index;
+            while let Some(descriptor) = manifest["manifests"].get(0) {
+                let name = blob_name(&descriptor["digest"]).ok_or_else(invalid)?;
+                manifest = read_json(&name)?.ok_or_else(invalid)?;
+            }
+            let layers = manifest["layers"].as_array().ok_or_else(invalid)?;
+            let layers = layers.iter().map(|layer| blob_name(&layer["digest"]));
+            (layers.collect::<Option<_>>().ok_or_else(invalid)?, tags)
+        } else {
+            return Err(invalid());
+        };
+
+        let mut fs = ArchiveFs::new(true, limit);
+        for layer in &layers {
+            let &(offset, size) = blobs.get(Path::new(layer)).ok_or_else(invalid)?;
+            fs.add_layer(path, offset, size, Format::Tar)
+                .map_err(io_error)?;
+        }
+        Ok(OciImage {
+            tags,
+            fs: Arc::new(fs),
+        })
+    }
+
+    /// The names the image was saved under, like `nginx:1.25`, from the `RepoTags` of
+    /// `docker save` or the `org.opencontainers.image.ref.name` annotations of an OCI layout.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Whether the image has a file or directory at `path`, following symlinks.
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.fs.exists(&Path::new("/").join(path))
+    }
+
+    /// The contents of the file at `path` in the image, following symlinks.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if the image has no such file, or the archive can't be read.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, Error> {
+        let path = Path::new("/").join(path);
+        self.fs
+            .read(&path)
+            .map_err(|source| Error::Io { path, source })
+    }
+
+    /// Builds the [`DependencyGraph`] of the executable or library at `path` in the image, as
+    /// [`ElfFile::dependency_graph`] does, according to `config`. Every file is looked up in
+    /// the image: its `ld.so.cache`, `ld.so.conf` and default directories, and the custom
+    /// search directories of `config`. The environment of this process says nothing about the
+    /// image, so `LD_LIBRARY_PATH` and `LD_PRELOAD` are not used, and any
+    /// [sysroot](ResolverConfig::sysroot) is replaced by the image.
+    ///
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn dependency_graph<P: AsRef<Path>>(
+        &self,
+        path: P,
+        config: ResolverConfig,
+    ) -> Result<DependencyGraph, Error> {
+        let config = config
+            .use_env(false)
+            .sysroot("/")
+            .filesystem(self.fs.clone());
+        ElfFile::with_config(Path::new("/").join(path), config).dependency_graph()
+    }
+}
+
+/// The name in the archive of the OCI blob with the digest `digest`, like `sha256:<hex>`.
+fn blob_name(digest: &Value) -> Option<String> {
+    let (algorithm, hex) = digest.as_str()?.split_once(':')?;
+    Some(format!("blobs/{algorithm}/{hex}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OciImage;
+    use crate::test_util::{gzip, tar, ArchiveEntry, ElfBuilder};
+    use crate::{Error, ResolverConfig, SearchRule};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// The layers of an image: the second hides a library of the first, replaces its
+    /// `/opt` and reconfigures `ld.so.conf`.
+    fn layers() -> [Vec<u8>; 2] {
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .needed("libgone.so.1")
+            .build();
+        let library = ElfBuilder::new().build();
+        let lower = tar(&[
+            ArchiveEntry::Dir("usr/"),
+            ArchiveEntry::File("usr/bin/app", &app),
+            ArchiveEntry::File("usr/lib/libfoo.so.1.0", &library),
+            ArchiveEntry::Symlink("usr/lib/libfoo.so.1", "libfoo.so.1.0"),
+            ArchiveEntry::File("usr/lib/libgone.so.1", &library),
+            ArchiveEntry::File("opt/old/libbar.so.1", &library),
+            ArchiveEntry::File("etc/ld.so.conf", b"include /etc/ld.so.conf.d/*.conf\n"),
+            ArchiveEntry::File("etc/ld.so.conf.d/opt.conf", b"/opt/old\n"),
+        ]);
+        let upper = tar(&[
+            ArchiveEntry::File("usr/lib/.wh.libgone.so.1", b""),
+            ArchiveEntry::File("opt/.wh..wh..opq", b""),
+            ArchiveEntry::File("opt/new/libbar.so.1", &library),
+            ArchiveEntry::HardLink("opt/new/libbaz.so.1", "opt/new/libbar.so.1"),
+            ArchiveEntry::File("etc/ld.so.conf.d/opt.conf", b"/opt/new\n"),
+            ArchiveEntry::Symlink("lib", "usr/lib"),
+        ]);
+        [lower, gzip(&upper)]
+    }
+
+    fn check(image: &OciImage) {
+        assert!(image.exists("/usr/bin/app"));
+        assert!(image.exists("lib/libfoo.so.1.0"));
+        assert!(!image.exists("/usr/lib/libgone.so.1"));
+        assert!(!image.exists("/opt/old"));
+        assert_eq!(
+            // `..` leaves the target of the symlink, as it does for the kernel
+            image.read("/lib/../../etc/ld.so.conf.d/opt.conf").unwrap(),
+            b"/opt/new\n"
+        );
+        assert!(matches!(
+            image.read("/usr/lib/libgone.so.1"),
+            Err(Error::Io { .. })
+        ));
+        let bar = image.read("/opt/new/libbar.so.1").unwrap();
+        assert_eq!(image.read("/opt/new/libbaz.so.1").unwrap(), bar);
+
+        let graph = image
+            .dependency_graph("usr/bin/app", ResolverConfig::new())
+            .unwrap();
+        let node = |soname: &str| {
+            let id = graph
+                .node_ids()
+                .find(|&id| graph.node(id).soname == soname)
+                .unwrap();
+            graph.node(id)
+        };
+        let foo = node("libfoo.so.1");
+        assert_eq!(foo.path(), Some(Path::new("/usr/lib/libfoo.so.1")));
+        assert_eq!(foo.links, [PathBuf::from("/usr/lib/libfoo.so.1.0")]);
+        let bar = node("libbar.so.1");
+        assert_eq!(bar.path(), Some(Path::new("/opt/new/libbar.so.1")));
+        assert_eq!(bar.found_by, Some(SearchRule::ConfiguredDir));
+        assert_eq!(node("libgone.so.1").path(), None);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gzip"), ignore = "needs the `gzip` feature")]
+    fn test_docker_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let [lower, upper] = layers();
+        let manifest = br#"[{"Config":"config.json","RepoTags":["app:1.0"],
+            "Layers":["lower/layer.tar","upper/layer.tar"]}]"#;
+        let archive = tar(&[
+            ArchiveEntry::File("manifest.json", manifest),
+            ArchiveEntry::File("config.json", b"{}"),
+            ArchiveEntry::File("lower/layer.tar", &lower),
+            ArchiveEntry::File("upper/layer.tar", &upper),
+        ]);
+        let path = dir.path().join("app.tar");
+        fs::write(&path, archive).unwrap();
+        let image = OciImage::open(&path).unwrap();
+        assert_eq!(image.tags(), ["app:1.0"]);
+        check(&image);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gzip"), ignore = "needs the `gzip` feature")]
+    fn test_oci_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let [lower, upper] = layers();
+        let manifest = br#"{"schemaVersion":2,"layers":[
+            {"digest":"sha256:aaaa","mediaType":"application/vnd.oci.image.layer.v1.tar"},
+            {"digest":"sha256:bbbb","mediaType":"application/vnd.oci.image.layer.v1.tar+gzip"}
+        ]}"#;
+        let index = br#"{"schemaVersion":2,"manifests":[{"digest":"sha256:cccc",
+            "annotations":{"org.opencontainers.image.ref.name":"app:1.0"}}]}"#;
+        let archive = tar(&[
+            ArchiveEntry::File("oci-layout", br#"{"imageLayoutVersion":"1.0.0"}"#),
+            ArchiveEntry::File("index.json", index),
+            ArchiveEntry::File("blobs/sha256/cccc", manifest),
+            ArchiveEntry::File("blobs/sha256/aaaa", &lower),
+            ArchiveEntry::File("blobs/sha256/bbbb", &upper),
+        ]);
+        let path = dir.path().join("app.tar");
+        fs::write(&path, archive).unwrap();
+        let image = OciImage::open(&path).unwrap();
+        assert_eq!(image.tags(), ["app:1.0"]);
+        check(&image);
+
+        fs::write(&path, tar(&[ArchiveEntry::File("index.json", b"{}")])).unwrap();
+        assert!(matches!(
+            OciImage::open(&path),
+            Err(Error::InvalidImage { .. })
+        ));
+        fs::write(&path, b"not an archive").unwrap();
+        assert!(matches!(
+            OciImage::open(&path),
+            Err(Error::InvalidImage { .. })
+        ));
+    }
+}
@@ -0,0 +1,51 @@
+//! The byte views of OS strings that library names and paths are handled as, as on Unix, for
+//! every target the resolver is built for, including WebAssembly.
+//!
+//! Only Unix targets run the tests, so CI also checks that the crate builds for WebAssembly:
+//!
+//! ```sh
+//! rustup target add wasm32-unknown-unknown
+//! cargo check --target wasm32-unknown-unknown --no-default-features
+//! ```
+//!
+//! Modules that only work on a Unix host, like core dumps of its processes, are compiled for
+//! `cfg(unix)` only.
+#[cfg(unix)]
+pub(crate) use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+#[cfg(target_family = "wasm")]
+pub(crate) use self::wasm::{OsStrExt, OsStringExt};
+
+#[cfg(target_family = "wasm")]
+mod wasm {
+    use std::ffi::{OsStr, OsString};
+
+    /// Like `std::os::unix::ffi::OsStrExt`.
+    pub(crate) trait OsStrExt {
+        fn from_bytes(bytes: &[u8]) -> &Self;
+        fn as_bytes(&self) -> &[u8];
+    }
+
+    /// Like `std::os::unix::ffi::OsStringExt`.
+    pub(crate) trait OsStringExt {
+        fn from_vec(bytes: Vec<u8>) -> Self;
+    }
+
+    impl OsStrExt for OsStr {
+        fn from_bytes(bytes: &[u8]) -> &Self {
+            // SAFETY: like on Unix, OS strings on WebAssembly are any bytes, encoded as is
+            unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }
+        }
+
+        fn as_bytes(&self) -> &[u8] {
+            self.as_encoded_bytes()
+        }
+    }
+
+    impl OsStringExt for OsString {
+        fn from_vec(bytes: Vec<u8>) -> Self {
+            // SAFETY: as for `OsStr::from_bytes`
+            unsafe { OsString::from_encoded_bytes_unchecked(bytes) }
+        }
+    }
+}
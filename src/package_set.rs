@@ -0,0 +1,329 @@
+//! Sets of `.deb` and `.rpm` packages, whose files are laid out in memory as if they were
+//! installed, so the libraries packaged binaries need can be looked for without installing
+//! anything.
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::archive_fs::{read_at, ArchiveFs, Format};
+use crate::filesystem::FileSystem;
+use crate::{DependencyGraph, ElfFile, Error, ResolverConfig};
+
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+const AR_HEADER_SIZE: u64 = 60;
+const RPM_LEAD_MAGIC: &[u8] = &[0xed, 0xab, 0xee, 0xdb];
+const RPM_LEAD_SIZE: u64 = 96;
+const RPM_HEADER_MAGIC: &[u8] = &[0x8e, 0xad, 0xe8, 0x01];
+
+/// The files of a set of `.deb` and `.rpm` packages, as if they were installed on an empty
+/// system. Only the headers of each package are read when the set is opened; files are read
+/// from the packages as needed, except for those whose contents are compressed, with gzip, xz
+/// or zstd as `.deb` and `.rpm` packages usually are, which are decompressed into memory.
+///
+/// ```no_run
+/// use elf_dynamic_lib_getter::{PackageSet, ResolverConfig};
+///
+/// let packages = PackageSet::open(["curl_8.5.0_amd64.deb", "libcurl4_8.5.0_amd64.deb"])?;
+/// let graph = packages.dependency_graph("/usr/bin/curl", ResolverConfig::new())?;
+/// for node in graph.nodes().iter().filter(|node| node.resolution.is_err()) {
+///     println!("not in the packages: {}", node.soname);
+/// }
+/// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PackageSet {
+    packages: Vec<PathBuf>,
+    fs: Arc<ArchiveFs>,
+}
+
+impl PackageSet {
+    /// Opens the packages at `paths`, telling `.deb` from `.rpm` files by their contents. A
+    /// file of a later package replaces a file of an earlier one at the same path.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if a package can't be read, or [`Error::InvalidPackage`] if it isn't a
+    /// `.deb` or `.rpm` package, or has its files compressed other than with gzip, xz or zstd.
+    /// Files compressed with a format whose feature is disabled give an [`Error::Io`].
+    pub fn open<I, P>(paths: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        PackageSet::open_with_config(paths, &ResolverConfig::new())
+    }
+
+    /// Opens the packages at `paths` as [`PackageSet::open`] does, decompressing the files of
+    /// each only up to the [maximum decompressed size](ResolverConfig::max_decompressed_size)
+    /// of `config`.
+    ///
+    /// # Return Value [Err]
+    /// As for [`PackageSet::open`], or [`Error::LimitExceeded`] if the files of a package
+    /// decompress to more.
+    pub fn open_with_config<I, P>(paths: I, config: &ResolverConfig) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut packages = Vec::new();
+        let mut fs = ArchiveFs::new(false, config.decompressed_size_limit());
+        for path in paths {
+            let path = path.as_ref();
+            let io_error = |source: io::Error| {
+                config.decompression_error(path, source, |source| match source.kind() {
+                    io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                        Error::InvalidPackage {
+                            path: path.to_owned(),
+                        }
+                    }
+                    _ => Error::Io {
+                        path: path.to_owned(),
+                        source,
+                    },
+                })
+            };
+            let (offset, size, format) = payload(path).map_err(io_error)?;
+            fs.add_layer(path, offset, size, format).map_err(io_error)?;
+            packages.push(path.to_owned());
+        }
+        Ok(PackageSet {
+            packages,
+            fs: Arc::new(fs),
+        })
+    }
+
+    /// The paths of the packages, in the order they were given.
+    pub fn packages(&self) -> &[PathBuf] {
+        &self.packages
+    }
+
+    /// The package the file at `path` comes from, following symlinks. `None` if no package
+    /// has it, or it is a directory no package lists of its own.
+    pub fn owner<P: AsRef<Path>>(&self, path: P) -> Option<&Path> {
+        let layer = self.fs.layer(&Path::new("/").join(path))?;
+        Some(&self.packages[layer])
+    }
+
+    /// Whether a package has a file or directory at `path`, following symlinks.
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.fs.exists(&Path::new("/").join(path))
+    }
+
+    /// The contents of the file at `path` in the packages, following symlinks.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if no package has such a file, or a package can't be read.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, Error> {
+        let path = Path::new("/").join(path);
+        self.fs
+            .read(&path)
+            .map_err(|source| Error::Io { path, source })
+    }
+
+    /// Builds the [`DependencyGraph`] of the executable or library at `path` in the packages,
+    /// as [`ElfFile::dependency_graph`] does, according to `config`. Every file is looked up
+    /// in the packages: their `ld.so.conf` and default directories, and the custom search
+    /// directories of `config`, so a library the graph leaves unresolved is one none of the
+    /// packages provides. `LD_LIBRARY_PATH` and `LD_PRELOAD` are not used, and any
+    /// [sysroot](ResolverConfig::sysroot) is replaced by the packages.
+    ///
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn dependency_graph<P: AsRef<Path>>(
+        &self,
+        path: P,
+        config: ResolverConfig,
+    ) -> Result<DependencyGraph, Error> {
+        let config = config
+            .use_env(false)
+            .sysroot("/")
+            .filesystem(self.fs.clone());
+        ElfFile::with_config(Path::new("/").join(path), config).dependency_graph()
+    }
+}
+
+/// Where the archive of the files of the package at `path` is, as its offset, size and
+/// format: the `data.tar` member of a `.deb`, or the cpio payload after the headers of an
+/// `.rpm`.
+fn payload(path: &Path) -> io::Result<(u64, u64, Format)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a .deb or .rpm package");
+    let mut file = File::open(path)?;
+    let end = file.metadata()?.len();
+    let mut magic = [0; 8];
+    file.read_exact(&mut magic)?;
+    if magic == AR_MAGIC {
+        let mut pos = AR_MAGIC.len() as u64;
+        while pos + AR_HEADER_SIZE <= end {
+            let header = read_at(&mut file, pos, AR_HEADER_SIZE)?;
+            let field = |range: std::ops::Range<usize>| {
+                std::str::from_utf8(&header[range])
+                    .map(str::trim_end)
+                    .map_err(|_| invalid())
+            };
+            // GNU ar ends names with a slash
+            let name = field(0..16)?;
+            let name = name.strip_suffix('/').unwrap_or(name);
+            let size: u64 = field(48..58)?.parse().map_err(|_| invalid())?;
+            let offset = pos + AR_HEADER_SIZE;
+            if offset + size > end {
+                return Err(invalid());
+            }
+            if name.starts_with("data.tar") {
+                return Ok((offset, size, Format::Tar));
+            }
+            // Members are aligned to even offsets
+            pos = offset + size.next_multiple_of(2);
+        }
+        return Err(invalid());
+    }
+    if magic[..4] != *RPM_LEAD_MAGIC {
+        return Err(invalid());
+    }
+    // The signature header is padded to a multiple of eight bytes, the main header is not
+    let signature = RPM_LEAD_SIZE;
+    let header = signature + header_size(&mut file, signature)?.next_multiple_of(8);
+    let payload = header + header_size(&mut file, header)?;
+    if payload > end {
+        return Err(invalid());
+    }
+    Ok((payload, end - payload, Format::Cpio))
+}
+
+/// The size of the rpm header structure at `offset` in `file`: its intro, index entries and
+/// data.
+fn header_size(file: &mut File, offset: u64) -> io::Result<u64> {
+    let intro = read_at(file, offset, 16)?;
+    if intro[..4] != *RPM_HEADER_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "malformed rpm header",
+        ));
+    }
+    let count = u32::from_be_bytes(intro[8..12].try_into().unwrap());
+    let data_size = u32::from_be_bytes(intro[12..16].try_into().unwrap());
+    Ok(16 + 16 * u64::from(count) + u64::from(data_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PackageSet;
+    use crate::test_util::{cpio, deb, gzip, rpm, tar, xz, zstd, ArchiveEntry, ElfBuilder};
+    use crate::{Error, ResolverConfig, SearchRule};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    #[cfg_attr(
+        not(all(feature = "gzip", feature = "xz", feature = "zstd")),
+        ignore = "needs the `gzip`, `xz` and `zstd` features"
+    )]
+    fn test_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .needed("libmissing.so.1")
+            .build();
+        let library = ElfBuilder::new().build();
+        let app_deb = dir.path().join("app_1.0_amd64.deb");
+        let data = tar(&[
+            ArchiveEntry::Dir("./usr/bin/"),
+            ArchiveEntry::File("./usr/bin/app", &app),
+        ]);
+        fs::write(&app_deb, deb("data.tar", &data)).unwrap();
+        let foo_deb = dir.path().join("libfoo1_1.0_amd64.deb");
+        let data = tar(&[
+            ArchiveEntry::File("./opt/foo/lib/libfoo.so.1", &library),
+            ArchiveEntry::File("./etc/ld.so.conf", b"/opt/foo/lib\n"),
+        ]);
+        fs::write(&foo_deb, deb("data.tar.gz", &gzip(&data))).unwrap();
+        let bar_rpm = dir.path().join("libbar-1.0-1.x86_64.rpm");
+        let payload = cpio(&[
+            ArchiveEntry::File("usr/lib64/libbar.so.1.0", &library),
+            ArchiveEntry::Symlink("usr/lib64/libbar.so.1", "libbar.so.1.0"),
+        ]);
+        fs::write(&bar_rpm, rpm(&gzip(&payload))).unwrap();
+
+        let packages = PackageSet::open([&app_deb, &foo_deb, &bar_rpm]).unwrap();
+        assert_eq!(
+            packages.packages(),
+            [app_deb.clone(), foo_deb.clone(), bar_rpm.clone()]
+        );
+        assert!(packages.exists("/usr/bin"));
+        assert!(!packages.exists("/usr/lib/libmissing.so.1"));
+        assert_eq!(packages.read("etc/ld.so.conf").unwrap(), b"/opt/foo/lib\n");
+        assert_eq!(packages.owner("/usr/bin/app"), Some(app_deb.as_path()));
+        assert_eq!(
+            packages.owner("/usr/lib64/libbar.so.1"),
+            Some(bar_rpm.as_path())
+        );
+        assert_eq!(packages.owner("/usr/bin"), Some(app_deb.as_path()));
+        assert_eq!(packages.owner("/usr/lib64"), None);
+
+        let graph = packages
+            .dependency_graph("/usr/bin/app", ResolverConfig::new())
+            .unwrap();
+        let node = |soname: &str| {
+            let id = graph
+                .node_ids()
+                .find(|&id| graph.node(id).soname == soname)
+                .unwrap();
+            graph.node(id)
+        };
+        let foo = node("libfoo.so.1");
+        assert_eq!(foo.path(), Some(Path::new("/opt/foo/lib/libfoo.so.1")));
+        assert_eq!(foo.found_by, Some(SearchRule::ConfiguredDir));
+        let bar = node("libbar.so.1");
+        assert_eq!(bar.path(), Some(Path::new("/usr/lib64/libbar.so.1")));
+        assert_eq!(bar.found_by, Some(SearchRule::DefaultDir));
+        assert_eq!(node("libmissing.so.1").path(), None);
+
+        // Contents compressed with xz, as dpkg does by default, or zstd, as Ubuntu and current
+        // rpm do
+        let foo_deb = dir.path().join("libfoo1_1.0_amd64.xz.deb");
+        let data = tar(&[ArchiveEntry::File("./usr/lib/libfoo.so.1", &library)]);
+        fs::write(&foo_deb, deb("data.tar.xz", &xz(&data))).unwrap();
+        let bar_deb = dir.path().join("libbar1_1.0_amd64.zst.deb");
+        let data = tar(&[ArchiveEntry::File("./usr/lib/libbar.so.1", &library)]);
+        fs::write(&bar_deb, deb("data.tar.zst", &zstd(&data))).unwrap();
+        let missing_rpm = dir.path().join("libmissing-1.0-1.x86_64.rpm");
+        let payload = cpio(&[ArchiveEntry::File("usr/lib64/libmissing.so.1", &library)]);
+        fs::write(&missing_rpm, rpm(&zstd(&payload))).unwrap();
+        let other_rpm = dir.path().join("other-1.0-1.x86_64.rpm");
+        fs::write(&other_rpm, rpm(&xz(&cpio(&[])))).unwrap();
+        let packages = [&app_deb, &foo_deb, &bar_deb, &missing_rpm, &other_rpm];
+        let graph = PackageSet::open(packages)
+            .unwrap()
+            .dependency_graph("/usr/bin/app", ResolverConfig::new())
+            .unwrap();
+        let paths: Vec<_> = graph
+            .library_nodes()
+            .iter()
+            .map(|node| node.path())
+            .collect();
+        let expected = [
+            "/usr/lib/libfoo.so.1",
+            "/usr/lib/libbar.so.1",
+            "/usr/lib64/libmissing.so.1",
+        ];
+        assert_eq!(paths, expected.map(|path| Some(Path::new(path))));
+
+        // A truncated stream is not a valid package
+        let truncated = dir.path().join("truncated.deb");
+        fs::write(&truncated, deb("data.tar.xz", b"\xfd7zXZ\0")).unwrap();
+        let open = |path: &Path| PackageSet::open([path]);
+        assert!(matches!(
+            open(&truncated),
+            Err(Error::InvalidPackage { .. })
+        ));
+        assert!(matches!(
+            open(&dir.path().join("app")),
+            Err(Error::Io { .. })
+        ));
+        fs::write(dir.path().join("app"), &app).unwrap();
+        assert!(matches!(
+            open(&dir.path().join("app")),
+            Err(Error::InvalidPackage { .. })
+        ));
+    }
+}
@@ -0,0 +1,356 @@
+//! Lookups of the OS package that installed a file, in the databases of dpkg and rpm.
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::os_str::OsStrExt;
+use crate::sqlite::{self, Database, Value};
+use crate::{sysroot, Error};
+
+/// The directory holding a `<package>.list` file of installed paths per dpkg package.
+const DPKG_INFO_DIR: &str = "/var/lib/dpkg/info";
+/// Where rpm 4.16 and later keep their SQLite database, most common first.
+const RPM_DB_PATHS: &[&str] = &[
+    "/var/lib/rpm/rpmdb.sqlite",
+    "/usr/lib/sysimage/rpm/rpmdb.sqlite",
+];
+
+const RPMTAG_NAME: u32 = 1000;
+const RPMTAG_ARCH: u32 = 1022;
+const RPMTAG_DIRINDEXES: u32 = 1116;
+const RPMTAG_BASENAMES: u32 = 1117;
+const RPMTAG_DIRNAMES: u32 = 1118;
+const RPM_INT32_TYPE: u32 = 4;
+const RPM_STRING_TYPE: u32 = 6;
+const RPM_STRING_ARRAY_TYPE: u32 = 8;
+
+/// The files the OS packages installed on a system own, read from dpkg's `.list` files and
+/// rpm's SQLite database, for finding which package a library came from.
+///
+/// The Berkeley DB and NDB formats of older rpm versions and SUSE are not read, nor are dpkg
+/// diversions.
+#[derive(Debug, Clone, Default)]
+pub struct PackageDb {
+    sysroot: Option<PathBuf>,
+    /// The index in `packages` of the package owning each path.
+    owners: HashMap<PathBuf, usize>,
+    packages: Vec<String>,
+}
+
+impl PackageDb {
+    /// Reads the package databases of this system. Package managers that aren't installed
+    /// add no files.
+    pub fn load() -> Result<Self, Error> {
+        Self::load_from(None)
+    }
+
+    /// Reads the package databases of the root filesystem at `sysroot`, for looking up
+    /// libraries found with [`ResolverConfig::sysroot`](crate::ResolverConfig::sysroot).
+    pub fn load_in_sysroot<P: AsRef<Path>>(sysroot: P) -> Result<Self, Error> {
+        Self::load_from(Some(sysroot.as_ref()))
+    }
+
+    fn load_from(sysroot: Option<&Path>) -> Result<Self, Error> {
+        let mut db = PackageDb {
+            sysroot: sysroot.map(Path::to_path_buf),
+            ..PackageDb::default()
+        };
+        // Symlinks like /var/lib/rpm -> ../../usr/lib/sysimage/rpm stay inside the sysroot
+        let locate = |path: &str| match sysroot {
+            Some(root) => sysroot::resolve(root, &sysroot::rebase(root, Path::new(path))),
+            None => Some(PathBuf::from(path)),
+        };
+        if let Some(dir) = locate(DPKG_INFO_DIR) {
+            db.read_dpkg(&dir)?;
+        }
+        let rpm_db = RPM_DB_PATHS
+            .iter()
+            .filter_map(|path| locate(path))
+            .find(|path| path.is_file());
+        if let Some(path) = rpm_db {
+            db.read_rpm(&path)?;
+        }
+        Ok(db)
+    }
+
+    /// Adds the paths listed in each `.list` file in `dir`, which need not exist.
+    fn read_dpkg(&mut self, dir: &Path) -> Result<(), Error> {
+        let io_error = |path: &Path| {
+            let path = path.to_owned();
+            move |source| Error::Io { path, source }
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(source) => return Err(io_error(dir)(source)),
+        };
+        let mut lists: Vec<PathBuf> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "list")
+            })
+            .collect();
+        // Directories are listed by every package installing into them, so which of them owns
+        // a directory must not depend on the order of the directory entries
+        lists.sort();
+        for list in lists {
+            let contents = fs::read(&list).map_err(io_error(&list))?;
+            // The file name is the package name, with its architecture for multi-arch ones
+            let package = list.file_stem().unwrap_or_default().to_string_lossy();
+            let paths = contents
+                .split(|&byte| byte == b'\n')
+                .filter(|line| line.starts_with(b"/") && *line != b"/.");
+            self.add(
+                &package,
+                paths.map(|line| PathBuf::from(OsStr::from_bytes(line))),
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds the files of each package header in the rpm database at `path`.
+    fn read_rpm(&mut self, path: &Path) -> Result<(), Error> {
+        let data = fs::read(path).map_err(|source| Error::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let invalid = || Error::InvalidPackageDb {
+            path: path.to_owned(),
+        };
+        let database = Database::new(&data).ok_or_else(invalid)?;
+        for (_, payload) in database.table_rows("Packages").ok_or_else(invalid)? {
+            // The columns are hnum, an alias of the rowid, and the header blob
+            let record = sqlite::record(&payload).ok_or_else(invalid)?;
+            let Some(&Value::Blob(blob)) = record.get(1) else {
+                return Err(invalid());
+            };
+            let (package, files) = rpm_header_files(blob).ok_or_else(invalid)?;
+            self.add(&package, files.into_iter());
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, package: &str, paths: impl Iterator<Item = PathBuf>) {
+        let index = self.packages.len();
+        self.packages.push(package.to_owned());
+        for path in paths {
+            self.owners.entry(path).or_insert(index);
+        }
+    }
+
+    /// The package owning the file at `path`, which is inside the sysroot if there is one.
+    /// As packages may list a file under a path whose directories are symlinks, like `/lib`
+    /// pointing to `usr/lib`, the path is also looked up with the symlinks of its directory,
+    /// then those of the file, followed.
+    pub fn owner<P: AsRef<Path>>(&self, path: P) -> Option<&str> {
+        let path = path.as_ref();
+        let root = self
+            .sysroot
+            .as_deref()
+            .filter(|root| path.starts_with(root));
+        let resolve = |path: &Path| match root {
+            Some(root) => sysroot::resolve(root, path),
+            None => fs::canonicalize(path).ok(),
+        };
+        let real_dir = path
+            .parent()
+            .and_then(resolve)
+            .zip(path.file_name())
+            .map(|(dir, name)| dir.join(name));
+        let candidates = [Some(path.to_owned()), real_dir, resolve(path)];
+        candidates.into_iter().flatten().find_map(|candidate| {
+            // Packages list the paths the files have with the sysroot mounted at /
+            let installed = match root {
+                Some(root) => Path::new("/").join(candidate.strip_prefix(root).ok()?),
+                None => candidate,
+            };
+            let &index = self.owners.get(&installed)?;
+            Some(self.packages[index].as_str())
+        })
+    }
+
+    /// Whether no package owns any file, as when no known package manager is installed.
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+}
+
+/// The name of the package an rpm header describes, with its architecture after a dot, and
+/// the paths of its files.
+fn rpm_header_files(header: &[u8]) -> Option<(String, Vec<PathBuf>)> {
+    let read_u32 = |offset: usize| {
+        let bytes = header.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_be_bytes(bytes.try_into().ok()?))
+    };
+    let count = usize::try_from(read_u32(0)?).ok()?;
+    let store_start = count.checked_mul(16)?.checked_add(8)?;
+    let store = header.get(store_start..store_start.checked_add(read_u32(4)? as usize)?)?;
+    let mut tags = HashMap::new();
+    for index in 0..count {
+        let entry = 8 + index * 16;
+        let tag = read_u32(entry)?;
+        let value = (
+            read_u32(entry + 4)?,
+            read_u32(entry + 8)? as usize,
+            read_u32(entry + 12)?,
+        );
+        tags.insert(tag, value);
+    }
+    let strings = |tag| -> Option<Vec<&[u8]>> {
+        let &(kind, offset, count) = tags.get(&tag)?;
+        if kind != RPM_STRING_ARRAY_TYPE && kind != RPM_STRING_TYPE {
+            return None;
+        }
+        let mut strings = store.get(offset..)?.split(|&byte| byte == 0);
+        (0..count).map(|_| strings.next()).collect()
+    };
+    let mut package = String::from_utf8_lossy(strings(RPMTAG_NAME)?.first()?).into_owned();
+    if let Some(arch) = strings(RPMTAG_ARCH).as_ref().and_then(|arch| arch.first()) {
+        package.push('.');
+        package.push_str(&String::from_utf8_lossy(arch));
+    }
+    // Packages without files, like gpg-pubkey, have none of these
+    let (Some(basenames), Some(dirnames), Some(&(RPM_INT32_TYPE, offset, count))) = (
+        strings(RPMTAG_BASENAMES),
+        strings(RPMTAG_DIRNAMES),
+        tags.get(&RPMTAG_DIRINDEXES),
+    ) else {
+        return Some((package, Vec::new()));
+    };
+    let mut files = Vec::with_capacity(basenames.len());
+    for (index, basename) in basenames.iter().enumerate().take(count as usize) {
+        let start = offset.checked_add(index * 4)?;
+        let dir_index = u32::from_be_bytes(store.get(start..start + 4)?.try_into().ok()?);
+        let dirname = dirnames.get(dir_index as usize)?;
+        let mut path = PathBuf::from(OsStr::from_bytes(dirname));
+        path.push(OsStr::from_bytes(basename));
+        files.push(path);
+    }
+    Some((package, files))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PackageDb;
+    use crate::test_util::{rpm_header, sqlite_database, ElfBuilder};
+    use crate::{ElfFile, Error, ResolverConfig};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+
+    #[test]
+    fn test_dpkg() {
+        let root = tempfile::tempdir().unwrap();
+        let info = root.path().join("var/lib/dpkg/info");
+        fs::create_dir_all(&info).unwrap();
+        fs::write(info.join("libc6:amd64.list"), "/.\n/lib\n/lib/libc.so.6\n").unwrap();
+        fs::write(info.join("libfoo1.list"), "/.\n/usr/lib/libfoo.so.1.2\n").unwrap();
+        fs::write(info.join("libfoo1.md5sums"), "/usr/lib/libbar.so.1\n").unwrap();
+        let lib = root.path().join("usr/lib");
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(lib.join("libc.so.6"), "").unwrap();
+        fs::write(lib.join("libfoo.so.1.2"), "").unwrap();
+        fs::write(lib.join("libbar.so.1"), "").unwrap();
+        symlink("libfoo.so.1.2", lib.join("libfoo.so.1")).unwrap();
+        // Merged /usr: the libc package still lists its files under /lib
+        symlink("/usr/lib", root.path().join("lib")).unwrap();
+
+        let db = PackageDb::load_in_sysroot(root.path()).unwrap();
+        assert_eq!(
+            db.owner(root.path().join("lib/libc.so.6")),
+            Some("libc6:amd64")
+        );
+        assert_eq!(db.owner(lib.join("libc.so.6")), None);
+        assert_eq!(db.owner(lib.join("libfoo.so.1")), Some("libfoo1"));
+        assert_eq!(db.owner(lib.join("libbar.so.1")), None);
+        assert_eq!(db.owner(root.path().join("lib")), Some("libc6:amd64"));
+        assert_eq!(db.owner(root.path()), None);
+    }
+
+    #[test]
+    fn test_rpm() {
+        let root = tempfile::tempdir().unwrap();
+        let headers = [
+            rpm_header(
+                "glibc",
+                "x86_64",
+                &["/usr/lib64/libc.so.6", "/usr/lib64/libm.so.6"],
+            ),
+            rpm_header("gpg-pubkey", "(none)", &[]),
+            rpm_header(
+                "libfoo",
+                "x86_64",
+                &["/usr/lib64/libfoo.so.1", "/etc/foo.conf"],
+            ),
+        ];
+        let blobs: Vec<&[u8]> = headers.iter().map(Vec::as_slice).collect();
+        let rpm = root.path().join("usr/lib/sysimage/rpm");
+        fs::create_dir_all(&rpm).unwrap();
+        fs::write(
+            rpm.join("rpmdb.sqlite"),
+            sqlite_database("Packages", &blobs),
+        )
+        .unwrap();
+        fs::create_dir_all(root.path().join("var/lib")).unwrap();
+        symlink(
+            "../../usr/lib/sysimage/rpm",
+            root.path().join("var/lib/rpm"),
+        )
+        .unwrap();
+
+        let db = PackageDb::load_in_sysroot(root.path()).unwrap();
+        let owner = |path: &str| db.owner(root.path().join(path));
+        assert_eq!(owner("usr/lib64/libm.so.6"), Some("glibc.x86_64"));
+        assert_eq!(owner("usr/lib64/libfoo.so.1"), Some("libfoo.x86_64"));
+        assert_eq!(owner("etc/foo.conf"), Some("libfoo.x86_64"));
+        assert_eq!(owner("usr/lib64/libbar.so.1"), None);
+
+        fs::write(rpm.join("rpmdb.sqlite"), "not a database").unwrap();
+        let error = PackageDb::load_in_sysroot(root.path()).unwrap_err();
+        assert!(matches!(error, Error::InvalidPackageDb { .. }));
+    }
+
+    #[test]
+    fn test_required_packages() {
+        let root = tempfile::tempdir().unwrap();
+        let lib = root.path().join("usr/lib");
+        ElfBuilder::new().write(&lib, "libc.so.6");
+        ElfBuilder::new()
+            .needed("libc.so.6")
+            .write(&lib, "libfoo.so.1");
+        ElfBuilder::new().write(&lib, "liblocal.so.1");
+        ElfBuilder::new().write(&lib, "ld-linux-x86-64.so.2");
+        let app = ElfBuilder::new()
+            .interpreter("/usr/lib/ld-linux-x86-64.so.2")
+            .needed("libfoo.so.1")
+            .needed("liblocal.so.1")
+            .needed("libc.so.6")
+            .write(root.path(), "app");
+        let info = root.path().join("var/lib/dpkg/info");
+        fs::create_dir_all(&info).unwrap();
+        let libc6 = "/usr/lib/libc.so.6\n/usr/lib/ld-linux-x86-64.so.2\n";
+        fs::write(info.join("libc6.list"), libc6).unwrap();
+        fs::write(info.join("libfoo1.list"), "/usr/lib/libfoo.so.1\n").unwrap();
+
+        let db = PackageDb::load_in_sysroot(root.path()).unwrap();
+        let config = ResolverConfig::new().use_env(false).sysroot(root.path());
+        let file = ElfFile::with_config(&app, config);
+        let owners = file.package_owners(&db).unwrap();
+        let owners: Vec<_> = owners
+            .iter()
+            .map(|(path, owner)| (path.strip_prefix(&lib).unwrap(), owner.as_deref()))
+            .collect();
+        assert_eq!(
+            owners,
+            [
+                (Path::new("libfoo.so.1"), Some("libfoo1")),
+                (Path::new("libc.so.6"), Some("libc6")),
+                (Path::new("liblocal.so.1"), None),
+            ]
+        );
+        assert_eq!(file.required_packages(&db).unwrap(), ["libfoo1", "libc6"]);
+    }
+}
@@ -0,0 +1,682 @@
+//! Extraction of the dynamic-linking information of a single ELF file.
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use crate::abi::{
+    DT_FLAGS, DT_FLAGS_1, DT_NEEDED, DT_RPATH, DT_RUNPATH, DT_SONAME, DT_STRSZ, DT_STRTAB,
+    ELFOSABI_GNU, ELFOSABI_NONE, EM_386, EM_AARCH64, EM_ARM, EM_PPC, EM_PPC64, EM_RISCV, EM_S390,
+    EM_X86_64, ET_CORE, ET_DYN, ET_EXEC, ET_REL, NT_GNU_BUILD_ID, PT_INTERP, PT_LOAD,
+};
+use crate::dlopen_note::{self, DlopenFeature};
+use crate::metrics::Counters;
+use crate::object::{self, FileHeader, Object, ProgramHeader, Source, Stream};
+use crate::os_str::{OsStrExt, OsStringExt};
+use crate::{DependencyKind, DynamicFlags, DynamicFlags1, Error, ParseError};
+
+/// Names the libraries a filter object takes (some of) its symbols from.
+pub(crate) const DT_FILTER: i64 = 0x7fffffff;
+/// Like `DT_FILTER`, but the libraries are optional.
+pub(crate) const DT_AUXILIARY: i64 = 0x7ffffffd;
+
+/// The properties a library must share with the executable for the dynamic linker to load it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Arch {
+    pub is_64_bit: bool,
+    pub big_endian: bool,
+    pub machine: u16,
+}
+
+impl Arch {
+    pub fn of(header: &FileHeader) -> Self {
+        Self {
+            is_64_bit: header.is_64_bit,
+            big_endian: header.big_endian,
+            machine: header.e_machine,
+        }
+    }
+
+    /// The Debian-style multiarch triplets of libraries for this architecture, most common first.
+    pub fn multiarch_triplets(self) -> &'static [&'static str] {
+        match (self.machine, self.is_64_bit, self.big_endian) {
+            (EM_X86_64, true, _) => &["x86_64-linux-gnu"],
+            (EM_X86_64, false, _) => &["x86_64-linux-gnux32"],
+            (EM_386, ..) => &["i386-linux-gnu"],
+            (EM_AARCH64, _, false) => &["aarch64-linux-gnu"],
+            (EM_AARCH64, _, true) => &["aarch64_be-linux-gnu"],
+            // Hard- and soft-float ABIs can't be told apart by the header alone
+            (EM_ARM, ..) => &["arm-linux-gnueabihf", "arm-linux-gnueabi"],
+            (EM_RISCV, true, _) => &["riscv64-linux-gnu"],
+            (EM_PPC, ..) => &["powerpc-linux-gnu"],
+            (EM_PPC64, _, false) => &["powerpc64le-linux-gnu"],
+            (EM_PPC64, _, true) => &["powerpc64-linux-gnu"],
+            (EM_S390, true, _) => &["s390x-linux-gnu"],
+            _ => &[],
+        }
+    }
+
+    /// Whether this is the x32 ABI: 32-bit files for x86-64 processors, which can't load i386
+    /// libraries and have their own in `/libx32` and `/usr/libx32`.
+    pub fn is_x32(self) -> bool {
+        self.machine == EM_X86_64 && !self.is_64_bit
+    }
+
+    /// The soname of the vDSO the Linux kernel maps into processes of this architecture.
+    pub fn vdso_soname(self) -> &'static str {
+        match (self.machine, self.is_64_bit) {
+            (EM_386, _) => "linux-gate.so.1",
+            (EM_PPC | EM_S390, false) => "linux-vdso32.so.1",
+            (EM_PPC64 | EM_S390, true) => "linux-vdso64.so.1",
+            _ => "linux-vdso.so.1",
+        }
+    }
+
+    /// The architecture this crate was compiled for, if it is one of the known ones.
+    pub fn host() -> Option<Self> {
+        let machine = match env::consts::ARCH {
+            "x86_64" => EM_X86_64,
+            "x86" => EM_386,
+            "aarch64" => EM_AARCH64,
+            "arm" => EM_ARM,
+            "riscv64" => EM_RISCV,
+            "powerpc64" => EM_PPC64,
+            "s390x" => EM_S390,
+            _ => return None,
+        };
+        Some(Self {
+            is_64_bit: cfg!(target_pointer_width = "64"),
+            big_endian: cfg!(target_endian = "big"),
+            machine,
+        })
+    }
+}
+
+/// The operating system ABI an ELF file is built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct OsAbi {
+    /// The `EI_OSABI` byte of the ELF header.
+    pub os_abi: u8,
+    /// The `EI_ABIVERSION` byte, whose meaning depends on the `EI_OSABI`.
+    pub version: u8,
+}
+
+impl OsAbi {
+    pub fn of(header: &FileHeader) -> Self {
+        Self {
+            os_abi: header.os_abi,
+            version: header.abi_version,
+        }
+    }
+
+    /// Whether a library of this ABI can be loaded into an executable of the ABI `executable`.
+    /// Libraries for no particular operating system (`ELFOSABI_NONE`) can be loaded by any
+    /// executable, and those using GNU extensions (`ELFOSABI_GNU`) by every Linux one, but other
+    /// libraries only by executables for their own operating system, of at least their version.
+    /// Like glibc, `ELFOSABI_NONE` libraries must have version 0.
+    pub fn loads_into(self, executable: OsAbi) -> bool {
+        let is_linux = |os_abi| os_abi == ELFOSABI_NONE || os_abi == ELFOSABI_GNU;
+        match self.os_abi {
+            ELFOSABI_NONE => self.version == 0,
+            ELFOSABI_GNU => is_linux(executable.os_abi),
+            os_abi => os_abi == executable.os_abi && self.version <= executable.version,
+        }
+    }
+}
+
+/// What an ELF file is, by its `e_type`, as reported by
+/// [`ElfFile::elf_type`](crate::ElfFile::elf_type). Only executables and shared objects have
+/// dependencies to resolve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ElfType {
+    /// `ET_REL`: a relocatable object, like a `.o` file or a Linux kernel module, which is
+    /// linked rather than loaded.
+    Relocatable,
+    /// `ET_EXEC`: an executable loaded at a fixed address.
+    Executable,
+    /// `ET_DYN`: a shared library or a position-independent executable.
+    SharedObject,
+    /// `ET_CORE`: a core dump.
+    Core,
+    /// Any other `e_type`, like the OS- and processor-specific ones.
+    Other(u16),
+}
+
+impl ElfType {
+    /// The type with the `e_type` value `e_type`.
+    pub fn from_e_type(e_type: u16) -> Self {
+        match e_type {
+            ET_REL => ElfType::Relocatable,
+            ET_EXEC => ElfType::Executable,
+            ET_DYN => ElfType::SharedObject,
+            ET_CORE => ElfType::Core,
+            e_type => ElfType::Other(e_type),
+        }
+    }
+
+    /// The `e_type` value of this type.
+    pub fn e_type(self) -> u16 {
+        match self {
+            ElfType::Relocatable => ET_REL,
+            ElfType::Executable => ET_EXEC,
+            ElfType::SharedObject => ET_DYN,
+            ElfType::Core => ET_CORE,
+            ElfType::Other(e_type) => e_type,
+        }
+    }
+
+    /// Whether the dynamic linker can load files of this type, which is what gives them
+    /// dependencies.
+    pub fn is_loadable(self) -> bool {
+        matches!(self, ElfType::Executable | ElfType::SharedObject)
+    }
+}
+
+impl fmt::Display for ElfType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElfType::Relocatable => f.write_str("relocatable object (ET_REL)"),
+            ElfType::Executable => f.write_str("executable (ET_EXEC)"),
+            ElfType::SharedObject => f.write_str("shared object (ET_DYN)"),
+            ElfType::Core => f.write_str("core dump (ET_CORE)"),
+            ElfType::Other(e_type) => write!(f, "file of type {e_type:#x}"),
+        }
+    }
+}
+
+/// The parts of an ELF file that matter for finding its dependencies.
+#[derive(Debug)]
+pub(crate) struct ParsedObject {
+    pub arch: Arch,
+    pub os_abi: OsAbi,
+    pub elf_type: ElfType,
+    /// Whether the file has no dynamic array at all, as for statically linked executables.
+    pub is_static: bool,
+    /// The program interpreter (dynamic linker) named by `PT_INTERP`, if the file is dynamic.
+    pub interpreter: Option<OsString>,
+    /// The `DT_SONAME` entry: the name the file advertises itself under.
+    pub soname: Option<OsString>,
+    /// `DT_NEEDED` entries, in order.
+    pub needed: Vec<OsString>,
+    /// The raw (unexpanded) `DT_RPATH` string.
+    pub rpath: Option<OsString>,
+    /// The raw (unexpanded) `DT_RUNPATH` string.
+    pub runpath: Option<OsString>,
+    /// `DT_FILTER` entries, in order.
+    pub filters: Vec<OsString>,
+    /// `DT_AUXILIARY` entries, in order.
+    pub auxiliary: Vec<OsString>,
+    /// The `DT_FLAGS` entry, empty if there is none.
+    pub flags: DynamicFlags,
+    /// The `DT_FLAGS_1` entry, empty if there is none.
+    pub flags_1: DynamicFlags1,
+    /// The GNU build ID from the `NT_GNU_BUILD_ID` note, in lowercase hexadecimal.
+    pub build_id: Option<String>,
+    /// The features declared in `.note.dlopen` notes, in order.
+    pub dlopen: Vec<DlopenFeature>,
+}
+
+impl ParsedObject {
+    /// An object with no dynamic-linking information yet.
+    pub fn new(arch: Arch, os_abi: OsAbi, elf_type: ElfType, build_id: Option<String>) -> Self {
+        Self {
+            arch,
+            os_abi,
+            elf_type,
+            is_static: false,
+            interpreter: None,
+            soname: None,
+            needed: Vec::new(),
+            rpath: None,
+            runpath: None,
+            filters: Vec::new(),
+            auxiliary: Vec::new(),
+            flags: DynamicFlags::empty(),
+            flags_1: DynamicFlags1::empty(),
+            build_id,
+            dlopen: Vec::new(),
+        }
+    }
+
+    /// Whether `DT_FLAGS_1` has `DF_1_NODEFLIB` set, as for files linked with
+    /// `-z nodefaultlib`, so the system directories are not searched for its dependencies.
+    pub fn nodeflib(&self) -> bool {
+        self.flags_1.contains(DynamicFlags1::NODEFLIB)
+    }
+
+    /// The libraries the file depends on: its `DT_NEEDED` entries, then the libraries named by
+    /// its `DT_FILTER` and `DT_AUXILIARY` entries, which may each list several separated by
+    /// colons.
+    pub fn dependencies(&self) -> Vec<(OsString, DependencyKind)> {
+        let filtees = |entries: &[OsString], kind| {
+            entries
+                .iter()
+                .flat_map(|entry| split(entry, b':'))
+                .filter(|filtee| !filtee.is_empty())
+                .map(move |filtee| (filtee.to_owned(), kind))
+                .collect::<Vec<_>>()
+        };
+        let needed = self
+            .needed
+            .iter()
+            .map(|soname| (soname.clone(), DependencyKind::Needed));
+        needed
+            .chain(filtees(&self.filters, DependencyKind::Filter))
+            .chain(filtees(&self.auxiliary, DependencyKind::Auxiliary))
+            .collect()
+    }
+
+    /// Sets the fields that come from the dynamic array `dynamic`, whose strings are in
+    /// `dynstr`.
+    pub fn read_dynamic(
+        &mut self,
+        path: &Path,
+        dynamic: Vec<(i64, u64)>,
+        dynstr: &[u8],
+    ) -> Result<(), Error> {
+        for (tag, value) in dynamic {
+            // The flags are 32 bits wide even in 64-bit files
+            if tag == DT_FLAGS {
+                self.flags = DynamicFlags::from_bits_retain(value as u32);
+                continue;
+            }
+            if tag == DT_FLAGS_1 {
+                self.flags_1 = DynamicFlags1::from_bits_retain(value as u32);
+                continue;
+            }
+            if !matches!(
+                tag,
+                DT_NEEDED | DT_SONAME | DT_RPATH | DT_RUNPATH | DT_FILTER | DT_AUXILIARY
+            ) {
+                continue;
+            }
+            let offset = usize::try_from(value)
+                .ok()
+                .filter(|&offset| offset < dynstr.len())
+                .ok_or_else(|| Error::CorruptDynamicEntry {
+                    path: path.to_owned(),
+                    tag,
+                    offset: value,
+                })?;
+            let string = c_str(&dynstr[offset..]).ok_or_else(|| Error::InvalidString {
+                path: path.to_owned(),
+                offset,
+            })?;
+            let string = os_string(string);
+            // Like ld.so, the last of repeated DT_RPATH/DT_RUNPATH entries wins
+            match tag {
+                // This is a needed shared library!
+                DT_NEEDED => self.needed.push(string),
+                DT_SONAME => self.soname = Some(string),
+                DT_RPATH => self.rpath = Some(string),
+                DT_RUNPATH => self.runpath = Some(string),
+                DT_FILTER => self.filters.push(string),
+                _ => self.auxiliary.push(string),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the ELF file `source` reads, reading only its headers, the interpreter, the dynamic
+/// array and its string table, and the notes that are needed; `path` is only used for error
+/// reporting.
+fn parse_object(path: &Path, source: impl Source) -> Result<ParsedObject, Error> {
+    let parse_error = |source| parse_error(path, source);
+    let mut elf = Object::open(source).map_err(parse_error)?;
+    let build_id = build_id(&mut elf);
+    let elf_type = ElfType::from_e_type(elf.header.e_type);
+    let (arch, os_abi) = (Arch::of(&elf.header), OsAbi::of(&elf.header));
+    let mut object = ParsedObject::new(arch, os_abi, elf_type, build_id);
+    object.dlopen = dlopen_features(&mut elf);
+
+    // Without a dynamic array the file is statically linked and needs no libraries
+    let Some(dynamic_range) = elf.dynamic_range() else {
+        object.is_static = true;
+        return Ok(object);
+    };
+    let dynstr = elf.section_by_name(".dynstr").map_err(parse_error)?;
+    let interp = elf.segments.iter().find(|phdr| phdr.p_type == PT_INTERP);
+    if let Some(phdr) = interp.copied() {
+        let interp_bytes = elf.segment_data(&phdr).map_err(parse_error)?;
+        object.interpreter = c_str(&interp_bytes).map(os_string);
+    }
+
+    let dynamic = elf.dynamic_at(dynamic_range).map_err(parse_error)?;
+    let dynstr_range = dynstr
+        .map(|shdr| (shdr.sh_offset, shdr.sh_size))
+        .or_else(|| dynstr_range_from_dynamic(&elf.segments, &dynamic))
+        .ok_or_else(|| Error::MissingDynamic {
+            path: path.to_owned(),
+        })?;
+    let dynstr_bytes = elf.bytes(dynstr_range).map_err(parse_error)?;
+    object.read_dynamic(path, dynamic, &dynstr_bytes)?;
+    Ok(object)
+}
+
+pub(crate) fn parse_error(path: &Path, source: ParseError) -> Error {
+    Error::Parse {
+        path: path.to_owned(),
+        source,
+    }
+}
+
+/// Reads and parses the ELF file at `path`, counting the bytes read into `counters`. Only the
+/// headers, the dynamic array and its string table are read, not the whole file.
+#[cfg(not(feature = "mmap"))]
+pub(crate) fn parse_file(path: &Path, counters: &Counters) -> Result<ParsedObject, Error> {
+    let file = File::open(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    parse_reader(path, counters.reader(file))
+}
+
+/// Maps the ELF file at `path` into memory and parses it in place, falling back to reading
+/// the parts that are needed for files that can't be mapped. Mapped files are not counted
+/// into `counters`, as only the pages that are touched are read.
+///
+/// A file that another process truncates while it is mapped raises `SIGBUS` when the pages
+/// past its new end are touched, which kills the process unless it handles the signal.
+/// Package managers replace libraries by renaming new files over them, which leaves the
+/// mapped file intact, but tools that rewrite files in place do truncate them.
+#[cfg(feature = "mmap")]
+pub(crate) fn parse_file(path: &Path, counters: &Counters) -> Result<ParsedObject, Error> {
+    let file = File::open(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    // SAFETY: the mapping is only read, and is unmapped before this returns. `Mmap` hands it
+    // out as a `&[u8]`, which the file changing underneath breaks: the parsed values are then
+    // meaningless, and truncation raises `SIGBUS` as documented above.
+    match unsafe { memmap2::Mmap::map(&file) } {
+        Ok(mmap) => parse_bytes(path, &mmap),
+        Err(_) => parse_reader(path, counters.reader(file)),
+    }
+}
+
+/// Parses the contents of an ELF file; `path` is only used for error reporting.
+pub(crate) fn parse_bytes(path: &Path, data: &[u8]) -> Result<ParsedObject, Error> {
+    parse_object(path, data)
+}
+
+/// Parses an ELF file, reading only the parts that are needed; `path` is only used for error
+/// reporting.
+fn parse_reader<S: Read + Seek>(path: &Path, reader: S) -> Result<ParsedObject, Error> {
+    parse_object(path, Stream(reader))
+}
+
+/// The GNU build ID of the file, found in the `.note.gnu.build-id` section or, for files whose
+/// section headers have been stripped, in a `PT_NOTE` segment. Malformed notes are ignored, as
+/// the build ID is not needed to load the file.
+fn build_id(elf: &mut Object<impl Source>) -> Option<String> {
+    let ids = elf.notes(".note.gnu.build-id", |note| {
+        (note.n_type == NT_GNU_BUILD_ID && note.name == b"GNU").then(|| note.desc.to_vec())
+    });
+    Some(hex(ids.first()?))
+}
+
+/// The libraries the file declares it loads with `dlopen` in its `.note.dlopen` section or,
+/// for files whose section headers have been stripped, in a `PT_NOTE` segment. Malformed notes
+/// are ignored, as for the build ID.
+fn dlopen_features(elf: &mut Object<impl Source>) -> Vec<DlopenFeature> {
+    let features = elf.notes(dlopen_note::SECTION, |note| {
+        let is_dlopen = note.n_type == dlopen_note::NT_FDO_DLOPEN_METADATA
+            && note.name == dlopen_note::NOTE_OWNER.as_bytes();
+        is_dlopen.then(|| dlopen_note::parse(note.desc))
+    });
+    features.into_iter().flatten().collect()
+}
+
+/// `bytes` in lowercase hexadecimal, as build IDs are written.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// The file offset and size of the string table the dynamic array indexes, found through its
+/// `DT_STRTAB`/`DT_STRSZ` entries mapped through the `PT_LOAD` segments. Used when there is no
+/// `.dynstr` section.
+pub(crate) fn dynstr_range_from_dynamic(
+    segments: &[ProgramHeader],
+    dynamic: &[(i64, u64)],
+) -> Option<(u64, u64)> {
+    let loads = segments
+        .iter()
+        .filter(|phdr| phdr.p_type == PT_LOAD)
+        .map(|phdr| (phdr.p_vaddr, phdr.p_offset, phdr.p_filesz));
+    dynstr_range_in(loads, dynamic)
+}
+
+/// Like [`dynstr_range_from_dynamic`], with the `PT_LOAD` segments given by their virtual
+/// address, file offset and size in the file.
+pub(crate) fn dynstr_range_in(
+    loads: impl IntoIterator<Item = (u64, u64, u64)>,
+    dynamic: &[(i64, u64)],
+) -> Option<(u64, u64)> {
+    let find = |tag| {
+        dynamic
+            .iter()
+            .find(|&&(entry_tag, _)| entry_tag == tag)
+            .map(|&(_, value)| value)
+    };
+    let (address, size) = (find(DT_STRTAB)?, find(DT_STRSZ)?);
+    let (vaddr, offset, _) = loads
+        .into_iter()
+        .find(|&(vaddr, _, filesz)| vaddr <= address && address - vaddr < filesz)?;
+    Some(((address - vaddr).checked_add(offset)?, size))
+}
+
+pub(crate) fn u8_slice_to_str(c_str: &[u8]) -> Option<&str> {
+    self::c_str(c_str).and_then(|slice| std::str::from_utf8(slice).ok())
+}
+
+/// The bytes of `data` before its first NUL, or `None` if it has none. Names and paths in ELF
+/// files are arbitrary bytes, like on Unix, so they need not be UTF-8.
+pub(crate) fn c_str(data: &[u8]) -> Option<&[u8]> {
+    let end = data.iter().position(|&byte| byte == b'\0')?;
+    Some(&data[..end])
+}
+
+pub(crate) fn os_string(bytes: &[u8]) -> OsString {
+    OsString::from_vec(bytes.to_vec())
+}
+
+/// The parts of `list` between each `separator` byte, including empty ones.
+pub(crate) fn split(list: &OsStr, separator: u8) -> impl Iterator<Item = &OsStr> {
+    list.as_bytes()
+        .split(move |&byte| byte == separator)
+        .map(OsStr::from_bytes)
+}
+
+/// Whether the file at `path` starts with the ELF magic bytes, whatever its name.
+pub(crate) fn has_elf_magic(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| magic == *b"\x7fELF")
+}
+
+/// The architecture and OS ABI of the ELF file at `lib_path`, or `None` if it is not an ELF
+/// file. Only the headers are read, and counted into `counters`.
+pub(crate) fn read_header(lib_path: &Path, counters: &Counters) -> Option<(Arch, OsAbi)> {
+    let lib_file = File::open(lib_path).ok()?;
+    read_header_of(counters.reader(lib_file))
+}
+
+/// The architecture and OS ABI of the ELF file `reader` reads, or `None` if it is not an ELF
+/// file, reading only its headers.
+pub(crate) fn read_header_of<R: Read + Seek>(reader: R) -> Option<(Arch, OsAbi)> {
+    let header = object::file_header(&mut Stream(reader)).ok()?;
+    Some((Arch::of(&header), OsAbi::of(&header)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_bytes, parse_file, parse_reader};
+    use crate::metrics::Counters;
+    use crate::test_util::ElfBuilder;
+    use crate::Error;
+    use std::ffi::OsStr;
+    use std::io::{self, Cursor, Read, Seek, SeekFrom};
+    use std::path::Path;
+
+    /// Counts the bytes read through it.
+    struct CountingReader<R> {
+        inner: R,
+        read: usize,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.read += read;
+            Ok(read)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_without_section_headers() {
+        let data = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .runpath("/opt/foo")
+            .without_section_headers()
+            .build();
+        let object = parse_bytes(Path::new("sstripped"), &data).unwrap();
+        assert_eq!(object.needed, ["libfoo.so.1"]);
+        assert_eq!(object.runpath.as_deref(), Some(OsStr::new("/opt/foo")));
+    }
+
+    #[test]
+    fn test_static() {
+        let data = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .without_dynamic_section()
+            .build();
+        let object = parse_bytes(Path::new("static"), &data).unwrap();
+        assert!(object.is_static);
+        assert!(object.needed.is_empty());
+    }
+
+    #[test]
+    fn test_build_id() {
+        let id = [0xde, 0xad, 0xbe, 0xef, 0x01];
+        let build_id = |builder: ElfBuilder| {
+            let data = builder.build();
+            parse_bytes(Path::new("noted"), &data).unwrap().build_id
+        };
+        let expected = Some("deadbeef01".to_owned());
+        assert_eq!(build_id(ElfBuilder::new().build_id(&id)), expected);
+        assert_eq!(build_id(ElfBuilder::new().elf32().build_id(&id)), expected);
+        // Found through PT_NOTE without section headers, and in static executables
+        let stripped = ElfBuilder::new().build_id(&id).without_section_headers();
+        assert_eq!(build_id(stripped), expected);
+        let static_executable = ElfBuilder::new().build_id(&id).without_dynamic_section();
+        assert_eq!(build_id(static_executable), expected);
+        assert_eq!(build_id(ElfBuilder::new()), None);
+    }
+
+    #[test]
+    fn test_reads_only_needed_parts() {
+        // The headers and dynamic parts come first, followed by a large unrelated section
+        let mut data = ElfBuilder::new().needed("libfoo.so.1").build();
+        data.resize(data.len() + (1 << 20), 0xAA);
+        let mut reader = CountingReader {
+            inner: Cursor::new(data),
+            read: 0,
+        };
+        let object = parse_reader(Path::new("large"), &mut reader).unwrap();
+        assert_eq!(object.needed, ["libfoo.so.1"]);
+        assert!(reader.read < 4096, "read {} bytes", reader.read);
+    }
+
+    #[test]
+    fn test_corrupt_dynamic_entry() {
+        let data = ElfBuilder::new()
+            .value(crate::abi::DT_NEEDED, 0x10000)
+            .build();
+        let error = parse_bytes(Path::new("corrupt"), &data).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::CorruptDynamicEntry {
+                tag: crate::abi::DT_NEEDED,
+                offset: 0x10000,
+                ..
+            }
+        ));
+        assert!(error.to_string().contains("tag 0x1"));
+    }
+
+    #[test]
+    fn test_parses_in_place() {
+        // Parsing in place finds the same as reading, for valid and invalid files alike
+        let builder = ElfBuilder::new()
+            .interpreter("/lib64/ld-linux-x86-64.so.2")
+            .needed("libfoo.so.1")
+            .runpath("/opt/foo")
+            .build_id(&[0xab, 0xcd]);
+        let path = Path::new("in-place");
+        let variants = [
+            builder.build(),
+            builder.clone().elf32().build(),
+            builder.without_section_headers().build(),
+        ];
+        for data in variants {
+            let mut inputs: Vec<Vec<u8>> =
+                (0..=data.len()).map(|len| data[..len].to_vec()).collect();
+            for index in 0..data.len() {
+                let mut corrupted = data.clone();
+                corrupted[index] ^= 0xff;
+                inputs.push(corrupted);
+            }
+            for input in inputs {
+                let read = parse_reader(path, Cursor::new(&input));
+                let in_place = parse_bytes(path, &input);
+                assert_eq!(format!("{:?}", read.ok()), format!("{:?}", in_place.ok()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .write(dir.path(), "libbar.so");
+        let counters = Counters::default();
+        let object = parse_file(&path, &counters).unwrap();
+        assert_eq!(object.needed, ["libfoo.so.1"]);
+        // Mapped files are parsed in place, so nothing is read
+        let read = counters.snapshot().bytes_read;
+        assert_eq!(read == 0, cfg!(feature = "mmap"), "read {read} bytes");
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let builder = ElfBuilder::new()
+            .interpreter("/lib64/ld-linux-x86-64.so.2")
+            .needed("libfoo.so.1")
+            .rpath("$ORIGIN/../lib");
+        for data in [builder.build(), builder.without_section_headers().build()] {
+            // Every truncation and every corrupted byte must fail or succeed, but not panic
+            for len in 0..data.len() {
+                let _ = parse_bytes(Path::new("truncated"), &data[..len]);
+            }
+            for index in 0..data.len() {
+                for value in [0x00, 0x7f, 0x80, 0xff] {
+                    let mut corrupted = data.clone();
+                    corrupted[index] = value;
+                    let _ = parse_bytes(Path::new("corrupted"), &corrupted);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Rewriting the dynamic array of ELF files in place.
+use std::fs;
+use std::path::Path;
+
+use crate::abi::{DT_NEEDED, DT_RPATH, DT_RUNPATH, DT_SONAME, SHT_DYNSYM};
+use crate::object::Object;
+use crate::parse::{c_str, dynstr_range_from_dynamic, parse_error, DT_AUXILIARY, DT_FILTER};
+use crate::Error;
+
+/// Sets the `DT_RUNPATH` of the ELF file at `path` to `runpath`, turning its `DT_RPATH` into a
+/// `DT_RUNPATH` if it has none. The new string is written over the old one, so it can't be
+/// longer, and nothing else in the file moves. The file is replaced rather than written to, so
+/// other hard links to it keep the original.
+pub(crate) fn set_runpath(path: &Path, runpath: &str) -> Result<(), Error> {
+    let read_error = |source| Error::Io {
+        path: path.to_owned(),
+        source,
+    };
+    let parse_error = |source| parse_error(path, source);
+    let no_space = || Error::RunpathNoSpace {
+        path: path.to_owned(),
+        runpath: runpath.to_owned(),
+    };
+    let mut data = fs::read(path).map_err(read_error)?;
+
+    let mut elf = Object::open(&data[..]).map_err(parse_error)?;
+    let header = elf.header;
+    let (dynamic_offset, dynamic_size) = elf.dynamic_range().ok_or_else(no_space)?;
+    let dynstr_range = elf
+        .section_by_name(".dynstr")
+        .map_err(parse_error)?
+        .map(|shdr| (shdr.sh_offset, shdr.sh_size));
+    // Symbol names index the same string table as the dynamic array
+    let mut names: Vec<u64> = match elf.symbols(SHT_DYNSYM).map_err(parse_error)? {
+        Some((table, _)) => table.iter().map(|symbol| symbol.st_name.into()).collect(),
+        None => Vec::new(),
+    };
+    let dynamic = elf
+        .dynamic_at((dynamic_offset, dynamic_size))
+        .map_err(|_| no_space())?;
+    let segments = elf.segments;
+
+    let range = |offset: u64, size: u64| {
+        let start = usize::try_from(offset).ok()?;
+        let end = start.checked_add(usize::try_from(size).ok()?)?;
+        (end <= data.len()).then_some(start..end)
+    };
+    let dynamic_range = range(dynamic_offset, dynamic_size).ok_or_else(no_space)?;
+    let (dynstr_offset, dynstr_size) = dynstr_range
+        .or_else(|| dynstr_range_from_dynamic(&segments, &dynamic))
+        .ok_or_else(no_space)?;
+    let dynstr = range(dynstr_offset, dynstr_size).ok_or_else(no_space)?;
+
+    // Like ld.so, the last DT_RUNPATH counts, or the last DT_RPATH without one
+    let last = |tag| dynamic.iter().rposition(|&(entry_tag, _)| entry_tag == tag);
+    let index = last(DT_RUNPATH)
+        .or_else(|| last(DT_RPATH))
+        .ok_or_else(no_space)?;
+    let start = usize::try_from(dynamic[index].1)
+        .ok()
+        .filter(|&start| start < dynstr.len())
+        .ok_or_else(no_space)?;
+    let length = c_str(&data[dynstr.start + start..dynstr.end])
+        .ok_or_else(no_space)?
+        .len();
+    // The bytes can't be reused if the string is the tail of another one, or if another
+    // string starts inside it, as linkers merging strings with common suffixes produce
+    names.extend(dynamic.iter().filter_map(|&(tag, value)| {
+        let string_tag = matches!(tag, DT_NEEDED | DT_SONAME | DT_FILTER | DT_AUXILIARY);
+        string_tag.then_some(value)
+    }));
+    let shared = (start > 0 && data[dynstr.start + start - 1] != 0)
+        || names
+            .iter()
+            .any(|&name| name >= start as u64 && name <= (start + length) as u64);
+    if shared || runpath.len() > length || runpath.as_bytes().contains(&0) {
+        return Err(no_space());
+    }
+
+    let string = dynstr.start + start;
+    data[string..string + length].fill(0);
+    data[string..string + runpath.len()].copy_from_slice(runpath.as_bytes());
+    let (entry_size, tag_size) = if header.is_64_bit { (16, 8) } else { (8, 4) };
+    let tag = dynamic_range.start + index * entry_size;
+    let tag_bytes = if header.big_endian {
+        (DT_RUNPATH as u64).to_be_bytes()[8 - tag_size..].to_vec()
+    } else {
+        (DT_RUNPATH as u64).to_le_bytes()[..tag_size].to_vec()
+    };
+    data[tag..tag + tag_size].copy_from_slice(&tag_bytes);
+
+    let write_error = |source| Error::Write {
+        path: path.to_owned(),
+        source,
+    };
+    let permissions = fs::metadata(path).map_err(read_error)?.permissions();
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".runpath");
+    fs::write(&temporary, &data).map_err(write_error)?;
+    fs::set_permissions(&temporary, permissions)
+        .and_then(|()| fs::rename(&temporary, path))
+        .map_err(|source| {
+            let _ = fs::remove_file(&temporary);
+            write_error(source)
+        })
+}
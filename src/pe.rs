@@ -0,0 +1,459 @@
+//! Dependency resolution for Windows PE files, following the Windows DLL search order.
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    DependencyGraph, DependencyKind, DependencyNode, Error, NodeId, Resolution, ResolveError,
+    Resolved, ResolverConfig,
+};
+
+const IMAGE_FILE_MACHINE_I386: u16 = 0x14c;
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const IMPORT_DIRECTORY: usize = 1;
+const DELAY_IMPORT_DIRECTORY: usize = 13;
+
+/// Where Windows lives when no sysroot is configured and `SystemRoot` is not used.
+const DEFAULT_WINDOWS_DIR: &str = "C:\\Windows";
+
+/// Represents a Windows PE file on disk, such as an `.exe` or `.dll`, and provides the same
+/// dependency queries as [`ElfFile`](crate::ElfFile).
+///
+/// Imported DLLs, including delay-loaded ones, are searched for in the standard order for
+/// desktop applications, matching file names case-insensitively:
+/// - The directory of the analyzed file
+/// - The system directory, `Windows\System32` (`Windows\SysWOW64` for 32-bit x86 files, if it
+///   exists)
+/// - The 16-bit system directory, `Windows\System`
+/// - The Windows directory
+/// - All directories in the `PATH` environment variable
+/// - Directories added with [`ResolverConfig::append_search_dir`]
+///
+/// The Windows directory is `Windows` inside the [`ResolverConfig::sysroot`], which should be
+/// the root of the Windows drive, or else `%SystemRoot%`. `KnownDLLs` and the current directory
+/// are not considered, and DLLs of another machine type are skipped. API sets such as
+/// `api-ms-win-core-file-l1-1-0.dll` are virtual, and so are left out.
+pub struct PeFile {
+    path: PathBuf,
+    config: ResolverConfig,
+}
+
+impl PeFile {
+    /// Creates a [`PeFile`] instance from [`AsRef<Path>`]
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        PeFile::with_config(path, ResolverConfig::default())
+    }
+
+    /// Creates a [`PeFile`] instance that resolves dependencies according to `config`
+    pub fn with_config<P: AsRef<Path>>(path: P, config: ResolverConfig) -> Self {
+        let path = path.as_ref().to_owned();
+        Self { path, config }
+    }
+
+    /// Like [`ElfFile::get_libs_full_paths`](crate::ElfFile::get_libs_full_paths): the paths
+    /// of all dependencies, or [`Error::Unresolved`] if one of them is missing.
+    pub fn get_libs_full_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        self.get_libs_resolutions()?
+            .into_iter()
+            .filter_map(|(soname, resolution)| match resolution {
+                Ok(resolved) => resolved.into_path().map(Ok),
+                Err(error) => Some(Err(error.into_error(soname))),
+            })
+            .collect()
+    }
+
+    /// Like [`ElfFile::get_libs_resolutions`](crate::ElfFile::get_libs_resolutions): every
+    /// imported DLL name in the order it was encountered, paired with where it was found.
+    pub fn get_libs_resolutions(&self) -> Result<Vec<(String, Resolution)>, Error> {
+        let nodes = self.dependency_graph()?.into_nodes();
+        Ok(nodes
+            .into_iter()
+            .skip(1)
+            .map(|node| (node.soname, node.resolution))
+            .collect())
+    }
+
+    /// The DLL names this file imports, followed by the ones it delay-loads, in table order.
+    pub fn imported_dlls(&self) -> Result<Vec<String>, Error> {
+        let object = self.parse()?;
+        Ok(object.imports.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Builds the full [`DependencyGraph`] of this file. The soname of each node is the DLL name
+    /// it was imported by.
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn dependency_graph(&self) -> Result<DependencyGraph, Error> {
+        let object = self.parse()?;
+        let config = &self.config;
+        let file_name = self.path.file_name().unwrap_or(self.path.as_os_str());
+        let resolution = Ok(Resolved::Found(self.path.clone()));
+        let graph = DependencyGraph::new(DependencyNode::new(file_name, resolution));
+
+        let mut search_dirs = vec![parent(&self.path).to_owned()];
+        if config.default_dirs {
+            let windows_dir = match &config.sysroot {
+                Some(root) => root.join("Windows"),
+                None => config
+                    .env_var("SystemRoot")
+                    .map_or_else(|| PathBuf::from(DEFAULT_WINDOWS_DIR), PathBuf::from),
+            };
+            let wow64 = windows_dir.join("SysWOW64");
+            if object.machine == IMAGE_FILE_MACHINE_I386 && wow64.is_dir() {
+                search_dirs.push(wow64);
+            } else {
+                search_dirs.push(windows_dir.join("System32"));
+            }
+            search_dirs.push(windows_dir.join("System"));
+            search_dirs.push(windows_dir);
+        }
+        if let Some(path) = config.env_var("PATH") {
+            search_dirs.extend(env::split_paths(&path));
+        }
+        search_dirs.extend_from_slice(&config.search_dirs);
+
+        let mut traversal = Traversal {
+            config,
+            machine: object.machine,
+            search_dirs,
+            dir_entries: HashMap::new(),
+            seen_libs: HashMap::new(),
+            graph,
+        };
+        let root = traversal.graph.root();
+        traversal
+            .seen_libs
+            .insert(self.config.identity(&self.path), root);
+        traversal.collect_libs(&self.path, root, object);
+        Ok(traversal.graph)
+    }
+
+    fn parse(&self) -> Result<ParsedPe, Error> {
+        let data = fs::read(&self.path).map_err(|source| Error::Io {
+            path: self.path.clone(),
+            source,
+        })?;
+        parse(&data).ok_or_else(|| Error::InvalidPe {
+            path: self.path.clone(),
+        })
+    }
+}
+
+/// The parts of a PE file needed to find its dependencies.
+#[derive(Debug)]
+struct ParsedPe {
+    machine: u16,
+    /// DLL names from the import directory, then from the delay-load import directory.
+    imports: Vec<(String, DependencyKind)>,
+}
+
+/// Parses the headers and import tables of a PE file, or returns `None` if `data` is not one.
+fn parse(data: &[u8]) -> Option<ParsedPe> {
+    let reader = Reader(data);
+    if data.get(..2)? != b"MZ" {
+        return None;
+    }
+    let pe_header = reader.u32(0x3c)? as usize;
+    if data.get(pe_header..pe_header.checked_add(4)?)? != b"PE\0\0" {
+        return None;
+    }
+    let coff = pe_header + 4;
+    let machine = reader.u16(coff)?;
+    let section_count = reader.u16(coff + 2)? as usize;
+    let optional = coff + 20;
+    let sections = optional + reader.u16(coff + 16)? as usize;
+    let (image_base, directories) = match reader.u16(optional)? {
+        PE32_MAGIC => (reader.u32(optional + 28)?.into(), optional + 96),
+        PE32_PLUS_MAGIC => (reader.u64(optional + 24)?, optional + 112),
+        _ => return None,
+    };
+    let directory_count = reader.u32(directories - 4)? as usize;
+
+    // Maps a relative virtual address to a file offset through the section table
+    let offset_of = |rva: u32| -> Option<usize> {
+        (0..section_count).find_map(|index| {
+            let section = sections.checked_add(index.checked_mul(40)?)?;
+            let size = reader.u32(section + 8)?.max(reader.u32(section + 16)?);
+            let address = reader.u32(section + 12)?;
+            let offset = rva.checked_sub(address).filter(|&offset| offset < size)?;
+            let raw = reader.u32(section + 20)?;
+            Some(raw.checked_add(offset)? as usize)
+        })
+    };
+    let directory = |index: usize| -> Option<usize> {
+        let rva = (index < directory_count).then(|| reader.u32(directories + index * 8))??;
+        if rva == 0 {
+            return None;
+        }
+        offset_of(rva)
+    };
+
+    let mut imports = Vec::new();
+    if let Some(table) = directory(IMPORT_DIRECTORY) {
+        // IMAGE_IMPORT_DESCRIPTORs, ending with an all-zero one
+        for entry in (table..).step_by(20) {
+            let name = reader.u32(entry + 12)?;
+            if name == 0 {
+                break;
+            }
+            imports.push((reader.str(offset_of(name)?)?, DependencyKind::Needed));
+        }
+    }
+    if let Some(table) = directory(DELAY_IMPORT_DIRECTORY) {
+        for entry in (table..).step_by(32) {
+            let name = reader.u32(entry + 4)?;
+            if name == 0 {
+                break;
+            }
+            // Old-style descriptors, without the RVA attribute, hold virtual addresses
+            let name = if reader.u32(entry)? & 1 == 0 {
+                u32::try_from(u64::from(name).checked_sub(image_base)?).ok()?
+            } else {
+                name
+            };
+            imports.push((reader.str(offset_of(name)?)?, DependencyKind::DelayLoad));
+        }
+    }
+    Some(ParsedPe { machine, imports })
+}
+
+/// Bounds-checked little-endian reads from a PE file.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.0.get(offset..offset.checked_add(2)?)?;
+        Some(u16::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.0.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        let bytes = self.0.get(offset..offset.checked_add(8)?)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    /// The NUL-terminated string at `offset`.
+    fn str(&self, offset: usize) -> Option<String> {
+        let string = self.0.get(offset..)?;
+        let len = string.iter().position(|&byte| byte == 0)?;
+        String::from_utf8(string[..len].to_vec()).ok()
+    }
+}
+
+/// The directory containing `path`.
+fn parent(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// Whether `name` is an API set contract, which Windows maps to a real DLL internally.
+fn is_api_set(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("api-ms-") || name.starts_with("ext-ms-")
+}
+
+/// State shared across the walk of a single [`PeFile`].
+struct Traversal<'a> {
+    config: &'a ResolverConfig,
+    /// DLLs must have the analyzed file's machine type.
+    machine: u16,
+    /// Directories searched after the directory of the analyzed file.
+    search_dirs: Vec<PathBuf>,
+    /// The file names in each searched directory, for case-insensitive lookups.
+    dir_entries: HashMap<PathBuf, Vec<OsString>>,
+    seen_libs: HashMap<PathBuf, NodeId>,
+    graph: DependencyGraph,
+}
+
+/// A file whose DLLs are being added to the graph, one at a time.
+struct Frame {
+    path: PathBuf,
+    node: NodeId,
+    /// The DLLs left to add.
+    imports: std::vec::IntoIter<(String, DependencyKind)>,
+    depth: usize,
+}
+
+impl Traversal<'_> {
+    /// Collects the closure of `object`, read from `path`, into `node`, depth-first. Files are
+    /// kept on an explicit stack, so long chains of DLLs can't overflow the stack.
+    fn collect_libs(&mut self, path: &Path, node: NodeId, object: ParsedPe) {
+        let mut stack = Vec::from_iter(self.start(path.to_owned(), node, object, 0));
+        while let Some(frame) = stack.last_mut() {
+            let Some((name, kind)) = frame.imports.next() else {
+                stack.pop();
+                continue;
+            };
+            match self.search(&name) {
+                Some((lib_path, lib)) => {
+                    let identity = self.config.identity(&lib_path);
+                    match self.seen_libs.get(&identity) {
+                        Some(&seen) => self.graph.add_edge(frame.node, seen),
+                        None => {
+                            let mut child =
+                                DependencyNode::new(&name, Ok(Resolved::Found(identity.clone())));
+                            child.kind = kind;
+                            child.links = self.config.symlink_chain(&lib_path);
+                            let child = self.graph.add_node(child);
+                            self.graph.add_edge(frame.node, child);
+                            self.seen_libs.insert(identity, child);
+                            if self.config.recursive {
+                                let next = self.start(lib_path, child, lib, frame.depth + 1);
+                                stack.extend(next);
+                            }
+                        }
+                    }
+                }
+                None => {
+                    let not_found = ResolveError::NotFound {
+                        needed_by: frame.path.clone(),
+                    };
+                    let mut child = DependencyNode::new(&name, Err(not_found));
+                    child.kind = kind;
+                    let child = self.graph.add_node(child);
+                    self.graph.add_edge(frame.node, child);
+                }
+            }
+        }
+    }
+
+    /// The DLLs of `object`, read from `path`, to add to `node`, which is `depth` levels below
+    /// the analyzed file, or `None` at the maximum depth or by the traversal filter. API sets
+    /// are left out.
+    fn start(
+        &mut self,
+        path: PathBuf,
+        node: NodeId,
+        object: ParsedPe,
+        depth: usize,
+    ) -> Option<Frame> {
+        let imports: Vec<_> = object
+            .imports
+            .into_iter()
+            .filter(|(name, _)| !is_api_set(name))
+            .collect();
+        if self
+            .config
+            .stops_at(&self.graph.node(node).soname, &path, depth)
+        {
+            self.graph.node_mut(node).truncated = !imports.is_empty();
+            return None;
+        }
+        Some(Frame {
+            path,
+            node,
+            imports: imports.into_iter(),
+            depth,
+        })
+    }
+
+    /// Finds the first DLL called `name`, ignoring case, with the right machine type.
+    fn search(&mut self, name: &str) -> Option<(PathBuf, ParsedPe)> {
+        for dir in self.search_dirs.clone() {
+            let entries = self.dir_entries.entry(dir.clone()).or_insert_with(|| {
+                let entries = fs::read_dir(&dir).into_iter().flatten().flatten();
+                entries.map(|entry| entry.file_name()).collect()
+            });
+            let matching = entries.iter().filter(|entry| {
+                entry
+                    .to_str()
+                    .is_some_and(|entry| entry.eq_ignore_ascii_case(name))
+            });
+            for file_name in matching {
+                let path = dir.join(file_name);
+                let Some(object) = fs::read(&path).ok().and_then(|data| parse(&data)) else {
+                    continue;
+                };
+                if object.machine == self.machine {
+                    return Some((path, object));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::PeBuilder;
+    use crate::{DependencyKind, PeFile, ResolveError, ResolverConfig};
+
+    #[test]
+    fn test_pe_imports() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = dir.path().join("Program Files/App");
+        let system32 = dir.path().join("Windows/System32");
+        let app = PeBuilder::new()
+            .import("LIBFOO.DLL")
+            .import("KERNEL32.dll")
+            .import("api-ms-win-crt-runtime-l1-1-0.dll")
+            .import("missing.dll")
+            .delay_import("delayed.dll")
+            .write(&app_dir, "app.exe");
+        let foo = PeBuilder::new()
+            .import("kernel32.dll")
+            .write(&app_dir, "libfoo.dll");
+        let kernel32 = PeBuilder::new().write(&system32, "kernel32.dll");
+        let delayed = PeBuilder::new().write(dir.path().join("Windows"), "Delayed.dll");
+
+        let config = ResolverConfig::new().use_env(false).sysroot(dir.path());
+        let pe = PeFile::with_config(&app, config);
+        assert_eq!(pe.imported_dlls().unwrap().len(), 5);
+        let resolutions = pe.get_libs_resolutions().unwrap();
+        let names: Vec<&str> = resolutions.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["LIBFOO.DLL", "kernel32.dll", "missing.dll", "delayed.dll"]
+        );
+        assert_eq!(
+            resolutions[0].1.as_ref().unwrap().path(),
+            Some(foo.as_path())
+        );
+        assert_eq!(
+            resolutions[1].1.as_ref().unwrap().path(),
+            Some(kernel32.as_path())
+        );
+        assert!(matches!(
+            &resolutions[2].1,
+            Err(ResolveError::NotFound { needed_by }) if needed_by == &app
+        ));
+        assert_eq!(
+            resolutions[3].1.as_ref().unwrap().path(),
+            Some(delayed.as_path())
+        );
+
+        let graph = pe.dependency_graph().unwrap();
+        let kernel32 = graph.find_path(&kernel32).unwrap();
+        assert_eq!(graph.dependents(kernel32).count(), 2);
+        let delayed = graph.find_path(&delayed).unwrap();
+        assert_eq!(graph.node(delayed).kind, DependencyKind::DelayLoad);
+    }
+
+    #[test]
+    fn test_pe_wow64() {
+        let dir = tempfile::tempdir().unwrap();
+        let windows = dir.path().join("Windows");
+        let app = PeBuilder::new()
+            .pe32()
+            .import("user32.dll")
+            .write(dir.path(), "app.exe");
+        // The 64-bit DLL in the application directory is skipped
+        PeBuilder::new().write(dir.path(), "user32.dll");
+        PeBuilder::new().write(windows.join("System32"), "user32.dll");
+        let user32 = PeBuilder::new()
+            .pe32()
+            .write(windows.join("SysWOW64"), "user32.dll");
+
+        let config = ResolverConfig::new().use_env(false).sysroot(dir.path());
+        let paths = PeFile::with_config(&app, config)
+            .get_libs_full_paths()
+            .unwrap();
+        assert_eq!(paths, [user32]);
+    }
+}
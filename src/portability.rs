@@ -0,0 +1,166 @@
+//! Comparisons of how the same binary resolves on two systems, like two distribution releases
+//! unpacked as sysroots, before deploying it to either.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::{DependencyGraph, ResolveError, ResolverConfig};
+
+/// A library both systems find, but at different places or with different contents, as
+/// reported in [`PortabilityReport::differing`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DifferingLibrary {
+    /// The soname the library is requested by.
+    pub soname: String,
+    /// Where the first system found it, inside its sysroot if it has one.
+    pub first: PathBuf,
+    /// Where the second system found it, inside its sysroot if it has one.
+    pub second: PathBuf,
+}
+
+/// The differences between the closures of a binary on two systems, as computed by
+/// [`ElfFile::compare_systems`](crate::ElfFile::compare_systems). Libraries are told apart by
+/// the soname they are requested by, and each list is in the load order of the closure its
+/// libraries come from.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PortabilityReport {
+    /// The libraries the first system lacks, or has but could not read.
+    pub missing_on_first: Vec<String>,
+    /// The libraries the second system lacks, or has but could not read.
+    pub missing_on_second: Vec<String>,
+    /// The libraries both systems find at different paths inside their sysroots, or at the
+    /// same path but with different digests, when both configure
+    /// [`ResolverConfig::content_digest`].
+    pub differing: Vec<DifferingLibrary>,
+}
+
+impl PortabilityReport {
+    /// Whether the binary finds all of its libraries on both systems, at the same places.
+    pub fn is_empty(&self) -> bool {
+        self.missing_on_first.is_empty()
+            && self.missing_on_second.is_empty()
+            && self.differing.is_empty()
+    }
+}
+
+/// Compares `first`, the graph of a binary resolved with `first_config`, with `second`, the
+/// graph of the same binary resolved with `second_config`.
+pub(crate) fn compare(
+    (first, first_config): (&DependencyGraph, &ResolverConfig),
+    (second, second_config): (&DependencyGraph, &ResolverConfig),
+) -> PortabilityReport {
+    let missing = |graph: &DependencyGraph| -> Vec<String> {
+        let nodes = graph.library_nodes().into_iter();
+        let nodes = nodes.filter(|node| {
+            matches!(
+                node.resolution,
+                Err(ResolveError::NotFound { .. } | ResolveError::Load(_))
+            )
+        });
+        nodes.map(|node| node.soname.clone()).collect()
+    };
+    let mut report = PortabilityReport {
+        missing_on_first: missing(first),
+        missing_on_second: missing(second),
+        differing: Vec::new(),
+    };
+    let found = second.library_nodes().into_iter();
+    let found: HashMap<&str, _> = found
+        .filter_map(|node| Some((node.soname.as_str(), (node.path()?, &node.digest))))
+        .collect();
+    for node in first.library_nodes() {
+        let (Some(path), Some(&(other, digest))) = (node.path(), found.get(node.soname.as_str()))
+        else {
+            continue;
+        };
+        let first_path = in_sysroot(first_config, path);
+        let second_path = in_sysroot(second_config, other);
+        let contents_differ = matches!((&node.digest, digest), (Some(a), Some(b)) if a != b);
+        if first_path != second_path || contents_differ {
+            report.differing.push(DifferingLibrary {
+                soname: node.soname.clone(),
+                first: first_path,
+                second: second_path,
+            });
+        }
+    }
+    report
+}
+
+/// The path `path` found with `config` has inside its sysroot, or `path` itself without one or
+/// if it lies outside of it, like the directories of `LD_LIBRARY_PATH`.
+fn in_sysroot(config: &ResolverConfig, path: &Path) -> PathBuf {
+    let relative = config
+        .sysroot
+        .as_deref()
+        .and_then(|root| path.strip_prefix(root).ok());
+    match relative {
+        Some(relative) => Path::new("/").join(relative),
+        None => path.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DifferingLibrary;
+    use crate::test_util::ElfBuilder;
+    use crate::{ContentDigest, ElfFile, ResolverConfig};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_compare_systems() {
+        let dir = tempfile::tempdir().unwrap();
+        let (old, new) = (dir.path().join("old"), dir.path().join("new"));
+        let old_lib = old.join("usr/lib");
+        let new_lib = new.join("usr/lib");
+        // libssl moved, libz changed, libold and libnew are only on one system each
+        ElfBuilder::new().write(&old_lib, "libssl.so.3");
+        ElfBuilder::new().write(new.join("opt/ssl/lib"), "libssl.so.3");
+        ElfBuilder::new().write(&old_lib, "libz.so.1");
+        ElfBuilder::new()
+            .soname("libz.so.1")
+            .write(&new_lib, "libz.so.1");
+        ElfBuilder::new().write(&old_lib, "libold.so.1");
+        ElfBuilder::new().write(&new_lib, "libnew.so.1");
+        ElfBuilder::new().write(&old_lib, "libsame.so.1");
+        ElfBuilder::new().write(&new_lib, "libsame.so.1");
+        let app = ElfBuilder::new()
+            .runpath("/usr/lib:/opt/ssl/lib")
+            .needed("libssl.so.3")
+            .needed("libz.so.1")
+            .needed("libold.so.1")
+            .needed("libnew.so.1")
+            .needed("libsame.so.1")
+            .write(dir.path(), "app");
+        let config = |root: &Path| {
+            ResolverConfig::new()
+                .use_env(false)
+                .without_default_dirs()
+                .sysroot(root)
+        };
+
+        let file = ElfFile::new(&app);
+        let report = file.compare_systems(config(&old), config(&new)).unwrap();
+        assert_eq!(report.missing_on_first, ["libnew.so.1"]);
+        assert_eq!(report.missing_on_second, ["libold.so.1"]);
+        assert_eq!(
+            report.differing,
+            [DifferingLibrary {
+                soname: "libssl.so.3".to_owned(),
+                first: PathBuf::from("/usr/lib/libssl.so.3"),
+                second: PathBuf::from("/opt/ssl/lib/libssl.so.3"),
+            }]
+        );
+        assert!(!report.is_empty());
+
+        // With digests, libz differs too, though it is at the same place
+        let digest = |root| config(root).content_digest(ContentDigest::Sha256);
+        let report = file.compare_systems(digest(&old), digest(&new)).unwrap();
+        let differing: Vec<_> = report.differing.iter().map(|l| &l.soname).collect();
+        assert_eq!(differing, ["libssl.so.3", "libz.so.1"]);
+        let same = file.compare_systems(config(&old), config(&old)).unwrap();
+        assert!(same.differing.is_empty());
+        assert_eq!(same.missing_on_first, same.missing_on_second);
+    }
+}
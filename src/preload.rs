@@ -0,0 +1,17 @@
+//! Libraries the dynamic linker loads into every process before the executable's dependencies.
+use std::ffi::{OsStr, OsString};
+
+use crate::os_str::OsStrExt;
+
+/// The default location of the system-wide preload list.
+pub const LD_SO_PRELOAD_PATH: &str = "/etc/ld.so.preload";
+
+/// The entries of a preload list like `LD_PRELOAD` or the contents of `/etc/ld.so.preload`,
+/// which ld.so separates by whitespace or colons.
+pub(crate) fn split(list: &OsStr) -> Vec<OsString> {
+    list.as_bytes()
+        .split(|&byte| byte.is_ascii_whitespace() || byte == b':')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| OsStr::from_bytes(entry).to_owned())
+        .collect()
+}
@@ -0,0 +1,327 @@
+//! The shared objects a running process has mapped, read from `/proc/<pid>/maps`, to compare
+//! with the libraries predicted from its executable.
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::os_str::OsStrExt;
+use crate::parse;
+use crate::{DependencyGraph, Error, NodeId};
+
+/// What the kernel appends to the path of a mapped file that has been unlinked or replaced.
+pub(crate) const DELETED_SUFFIX: &[u8] = b" (deleted)";
+
+/// A shared object mapped into a process, as listed by [`ProcessLibs::libraries`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MappedLibrary {
+    /// The path the object was mapped from, without the ` (deleted)` suffix.
+    pub path: PathBuf,
+    /// Whether the file was deleted or replaced after being mapped, as when a package upgrade
+    /// replaces a library a long-running process still uses.
+    pub deleted: bool,
+    /// The lowest address the object is mapped at.
+    pub address: u64,
+}
+
+/// How the libraries a process mapped differ from those predicted for its executable, as
+/// computed by [`ProcessLibs::compare`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuntimeComparison {
+    /// The mapped libraries that are not in the predicted closure, like those loaded with
+    /// `dlopen`, plugins, and ones preloaded with `LD_PRELOAD`.
+    pub extra: Vec<MappedLibrary>,
+    /// The libraries of the predicted closure that are not mapped, in load order, as when the
+    /// process runs with a different `LD_LIBRARY_PATH`, or the executable was rebuilt since it
+    /// started.
+    pub unloaded: Vec<NodeId>,
+}
+
+impl RuntimeComparison {
+    /// Whether the process mapped exactly the predicted libraries.
+    pub fn is_empty(&self) -> bool {
+        self.extra.is_empty() && self.unloaded.is_empty()
+    }
+}
+
+/// The shared objects mapped into a live process, in the order of their addresses. Unlike the
+/// libraries predicted by [`ElfFile::get_libs_full_paths`](crate::ElfFile::get_libs_full_paths),
+/// these include libraries loaded with `dlopen` and preloaded ones, and are the files actually
+/// loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessLibs {
+    pid: u32,
+    executable: Option<PathBuf>,
+    libraries: Vec<MappedLibrary>,
+}
+
+impl ProcessLibs {
+    /// Reads the mappings of the process `pid`. The executable of the process is left out, and
+    /// so are mapped files that are not ELF files, like locale archives. Files that are deleted
+    /// can't be told apart by their contents, so they count as shared objects if they are named
+    /// like one.
+    ///
+    /// # Return Value [Err]
+    /// Returns [`Error::Io`] if `/proc/<pid>/maps` could not be read, as when the process does
+    /// not exist or belongs to another user.
+    pub fn from_pid(pid: u32) -> Result<Self, Error> {
+        let proc_dir = PathBuf::from(format!("/proc/{pid}"));
+        let maps_path = proc_dir.join("maps");
+        let maps = fs::read(&maps_path).map_err(|source| Error::Io {
+            path: maps_path,
+            source,
+        })?;
+        // Only readable with ptrace access, but then it also names deleted executables
+        let executable = fs::read_link(proc_dir.join("exe")).ok().map(|path| {
+            let bytes = path.as_os_str().as_bytes();
+            let path = bytes.strip_suffix(DELETED_SUFFIX).unwrap_or(bytes);
+            PathBuf::from(OsStr::from_bytes(path))
+        });
+        let libraries = parse_maps(&maps, |path, deleted| {
+            Some(path) != executable.as_deref() && is_shared_object(path, deleted)
+        });
+        Ok(ProcessLibs {
+            pid,
+            executable,
+            libraries,
+        })
+    }
+
+    /// The process ID the mappings were read from.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// The executable of the process, or `None` if it could not be read.
+    pub fn executable(&self) -> Option<&Path> {
+        self.executable.as_deref()
+    }
+
+    /// Each shared object mapped into the process once, by its lowest address.
+    pub fn libraries(&self) -> &[MappedLibrary] {
+        &self.libraries
+    }
+
+    /// The shared objects whose files were deleted or replaced since they were mapped, which
+    /// a process keeps using until it restarts.
+    pub fn deleted(&self) -> impl Iterator<Item = &MappedLibrary> {
+        self.libraries.iter().filter(|library| library.deleted)
+    }
+
+    /// Compares the mapped libraries with the closure `predicted` for the executable of the
+    /// process, as from [`ElfFile::dependency_graph`](crate::ElfFile::dependency_graph).
+    /// Libraries are matched by the files they resolve to, following symlinks, since the
+    /// kernel names mappings by their real paths, and a library replaced since it was mapped
+    /// still counts as loaded. The program interpreter is mapped by the kernel, so it is not an
+    /// extra even if `predicted` leaves it out.
+    pub fn compare(&self, predicted: &DependencyGraph) -> RuntimeComparison {
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        let libraries: Vec<(NodeId, PathBuf)> = predicted
+            .load_order()
+            .into_iter()
+            .filter(|&id| id != predicted.root())
+            .filter_map(|id| Some((id, canonical(predicted.node(id).path()?))))
+            .collect();
+        let interpreter = predicted.interpreter().map(canonical);
+        let is_mapped = |path: &Path| self.libraries.iter().any(|library| library.path == path);
+        let is_predicted = |library: &MappedLibrary| {
+            interpreter.as_ref() == Some(&library.path)
+                || libraries.iter().any(|(_, path)| *path == library.path)
+        };
+        RuntimeComparison {
+            extra: self
+                .libraries
+                .iter()
+                .filter(|library| !is_predicted(library))
+                .cloned()
+                .collect(),
+            unloaded: libraries
+                .iter()
+                .filter(|(_, path)| !is_mapped(path))
+                .map(|&(id, _)| id)
+                .collect(),
+        }
+    }
+}
+
+/// The file-backed mappings in the contents of a `maps` file that `is_library` accepts, given
+/// their path and whether they are deleted, once per file.
+fn parse_maps(maps: &[u8], is_library: impl Fn(&Path, bool) -> bool) -> Vec<MappedLibrary> {
+    let mut libraries: Vec<MappedLibrary> = Vec::new();
+    for line in maps.split(|&byte| byte == b'\n') {
+        // The address range, permissions, offset, device and inode, then the padded path
+        let mut rest = line;
+        let mut fields = Vec::with_capacity(5);
+        for _ in 0..5 {
+            rest = rest.trim_ascii_start();
+            let end = rest
+                .iter()
+                .position(|&byte| byte == b' ')
+                .unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        let name = rest.trim_ascii_start();
+        // Anonymous mappings have no path, and [heap], [stack] and [vdso] aren't files
+        if !name.starts_with(b"/") {
+            continue;
+        }
+        let (name, deleted) = match name.strip_suffix(DELETED_SUFFIX) {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        let path = Path::new(OsStr::from_bytes(name));
+        let start = fields[0]
+            .split(|&byte| byte == b'-')
+            .next()
+            .unwrap_or_default();
+        let Some(address) = std::str::from_utf8(start)
+            .ok()
+            .and_then(|start| u64::from_str_radix(start, 16).ok())
+        else {
+            continue;
+        };
+        let seen = libraries
+            .iter_mut()
+            .find(|library| library.path == path && library.deleted == deleted);
+        match seen {
+            Some(library) => library.address = library.address.min(address),
+            None if is_library(path, deleted) => libraries.push(MappedLibrary {
+                path: path.to_owned(),
+                deleted,
+                address,
+            }),
+            None => {}
+        }
+    }
+    libraries
+}
+
+/// Whether the file at `path` is an ELF file or, if it was deleted, is named like a shared
+/// object.
+fn is_shared_object(path: &Path, deleted: bool) -> bool {
+    if deleted {
+        let name = path.file_name().unwrap_or_default().as_bytes();
+        return name.ends_with(b".so") || name.windows(4).any(|window| window == b".so.");
+    }
+    parse::has_elf_magic(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_maps, MappedLibrary, ProcessLibs};
+    use crate::{DependencyGraph, DependencyNode, ElfFile, ResolveError, Resolved};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_parse_maps() {
+        let maps = b"\
+55d0c0a00000-55d0c0a02000 r--p 00000000 08:01 1001  /usr/bin/app
+55d0c1a00000-55d0c1a21000 rw-p 00000000 00:00 0  [heap]
+7f0000002000-7f0000010000 r-xp 00002000 08:01 2002  /usr/lib/libc.so.6
+7f0000000000-7f0000002000 r--p 00000000 08:01 2002  /usr/lib/libc.so.6
+7f0000020000-7f0000021000 r--p 00000000 08:01 3003  /usr/lib/locale/C.utf8/LC_CTYPE
+7f0000030000-7f0000031000 r--p 00000000 08:01 4004  /opt/my plugins/libfoo.so (deleted)
+7f0000040000-7f0000041000 rw-p 00000000 00:00 0
+7ffd00000000-7ffd00002000 r-xp 00000000 00:00 0  [vdso]
+";
+        let libraries = parse_maps(maps, |path, _| {
+            path != Path::new("/usr/bin/app") && !path.starts_with("/usr/lib/locale")
+        });
+        let summary: Vec<_> = libraries
+            .iter()
+            .map(|library| (library.path.clone(), library.deleted, library.address))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (PathBuf::from("/usr/lib/libc.so.6"), false, 0x7f00_0000_0000),
+                (
+                    PathBuf::from("/opt/my plugins/libfoo.so"),
+                    true,
+                    0x7f00_0003_0000
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_pid() {
+        let process = ProcessLibs::from_pid(std::process::id()).unwrap();
+        assert_eq!(process.pid(), std::process::id());
+        let executable = std::env::current_exe().unwrap();
+        assert_eq!(process.executable(), Some(executable.as_path()));
+        let libraries = process.libraries();
+        assert!(libraries.iter().all(|library| library.path != executable));
+        assert!(libraries.iter().any(|library| library
+            .path
+            .file_name()
+            .is_some_and(|name| name == "libc.so.6")));
+        assert_eq!(process.deleted().count(), 0);
+        assert!(ProcessLibs::from_pid(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        for name in ["libc.so.6", "libfoo.so.1", "ld-linux.so.2"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        // The closure finds libc through a symlink, but the kernel names the real file
+        symlink(dir.join("libc.so.6"), dir.join("libc.so")).unwrap();
+        let mut predicted = DependencyGraph::new(DependencyNode::new(
+            "app",
+            Ok(Resolved::Found("/opt/app".into())),
+        ));
+        predicted.set_interpreter(Some(dir.join("ld-linux.so.2")));
+        let root = predicted.root();
+        for (soname, path) in [
+            ("libc.so.6", dir.join("libc.so")),
+            ("libfoo.so.1", dir.join("libfoo.so.1")),
+            ("libbar.so.1", dir.join("libbar.so.1")),
+        ] {
+            let node = predicted.add_node(DependencyNode::new(soname, Ok(Resolved::Found(path))));
+            predicted.add_edge(root, node);
+        }
+        let missing = ResolveError::NotFound {
+            needed_by: PathBuf::from("/opt/app"),
+        };
+        predicted.add_node(DependencyNode::new("libmissing.so.1", Err(missing)));
+
+        let mapped = |path: PathBuf, deleted| MappedLibrary {
+            path,
+            deleted,
+            address: 0,
+        };
+        let plugin = mapped(dir.join("plugin.so"), false);
+        let process = ProcessLibs {
+            pid: 1,
+            executable: Some(PathBuf::from("/opt/app")),
+            libraries: vec![
+                mapped(dir.join("libc.so.6"), false),
+                mapped(dir.join("libfoo.so.1"), true),
+                mapped(dir.join("ld-linux.so.2"), false),
+                plugin.clone(),
+            ],
+        };
+        let comparison = process.compare(&predicted);
+        assert_eq!(comparison.extra, [plugin]);
+        let unloaded = predicted.find_path(dir.join("libbar.so.1")).unwrap();
+        assert_eq!(comparison.unloaded, [unloaded]);
+        assert!(!comparison.is_empty());
+    }
+
+    #[test]
+    fn test_compare_running_process() {
+        let process = ProcessLibs::from_pid(std::process::id()).unwrap();
+        let predicted = ElfFile::new(std::env::current_exe().unwrap())
+            .dependency_graph()
+            .unwrap();
+        assert_eq!(process.compare(&predicted).unloaded, []);
+    }
+}
@@ -0,0 +1,339 @@
+//! Python bindings, for the `python` feature: the extension module `_native` of the
+//! `elf_dynamic_lib_getter` package under `python/`, which maturin builds the crate into.
+//!
+//! Paths are handed to Python as `str`, decoded like the `os` module does, so that ones that
+//! are not UTF-8 round-trip.
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+
+use crate::{DependencyKind, ElfFile, Error, Resolution, ResolverConfig, SearchRule};
+
+create_exception!(
+    _native,
+    AnalysisError,
+    PyException,
+    "The analyzed file could not be read or is not an ELF file."
+);
+
+fn analysis_error(error: Error) -> PyErr {
+    AnalysisError::new_err(error.to_string())
+}
+
+fn os_string(path: &Path) -> OsString {
+    path.as_os_str().to_owned()
+}
+
+/// What Python's `repr` gives for `value`.
+fn repr<'py, T: IntoPyObject<'py>>(py: Python<'py>, value: T) -> PyResult<String> {
+    let value = value.into_bound_py_any(py)?;
+    Ok(value.repr()?.to_string())
+}
+
+/// The path a library resolved to, and why it was not found, as a [`Dependency`] holds them.
+fn split_resolution(resolution: &Resolution) -> (Option<OsString>, Option<String>) {
+    match resolution {
+        Ok(resolved) => (resolved.path().map(os_string), None),
+        Err(error) => (None, Some(error.to_string())),
+    }
+}
+
+/// Searches for libraries as if `sysroot` were mounted at /, or in the host's directories,
+/// and using LD_LIBRARY_PATH if `use_env`. A resolver can be shared by analyses, on several
+/// threads, and remembers the files it parsed.
+#[pyclass(name = "Resolver", module = "elf_dynamic_lib_getter", frozen)]
+struct PyResolver(Arc<crate::Resolver>);
+
+#[pymethods]
+impl PyResolver {
+    #[new]
+    #[pyo3(signature = (sysroot=None, use_env=true))]
+    fn new(sysroot: Option<PathBuf>, use_env: bool) -> Self {
+        let mut config = ResolverConfig::new().use_env(use_env);
+        if let Some(sysroot) = sysroot {
+            config = config.sysroot(sysroot);
+        }
+        Self(Arc::new(crate::Resolver::new(config)))
+    }
+
+    /// Every library the ELF file at `path` needs, recursively, in the order they were
+    /// encountered, each once. Raises AnalysisError if the file itself can't be analyzed.
+    fn dependencies(&self, py: Python<'_>, path: PathBuf) -> PyResult<Vec<Dependency>> {
+        let resolutions = PyElfFile::analyze(py, &self.0, &path, ElfFile::get_libs_resolutions)?;
+        let dependencies = resolutions.into_iter().map(|(soname, resolution)| {
+            let (path, error) = split_resolution(&resolution);
+            Dependency {
+                soname,
+                path,
+                error,
+            }
+        });
+        Ok(dependencies.collect())
+    }
+}
+
+/// An ELF file to analyze, with `resolver` or one of its own.
+#[pyclass(name = "ElfFile", module = "elf_dynamic_lib_getter", frozen)]
+struct PyElfFile {
+    path: PathBuf,
+    resolver: Py<PyResolver>,
+}
+
+impl PyElfFile {
+    /// Runs `analysis` on the file at `path` with `resolver`, letting other Python threads run
+    /// meanwhile.
+    fn analyze<T: Send>(
+        py: Python<'_>,
+        resolver: &Arc<crate::Resolver>,
+        path: &Path,
+        analysis: impl FnOnce(&ElfFile) -> Result<T, Error> + Send,
+    ) -> PyResult<T> {
+        let elf_file = ElfFile::with_resolver(path, resolver.clone());
+        py.detach(|| analysis(&elf_file)).map_err(analysis_error)
+    }
+}
+
+#[pymethods]
+impl PyElfFile {
+    #[new]
+    #[pyo3(signature = (path, resolver=None))]
+    fn new(py: Python<'_>, path: PathBuf, resolver: Option<Py<PyResolver>>) -> PyResult<Self> {
+        let resolver = match resolver {
+            Some(resolver) => resolver,
+            None => Py::new(py, PyResolver::new(None, true))?,
+        };
+        Ok(Self { path, resolver })
+    }
+
+    #[getter]
+    fn path(&self) -> OsString {
+        os_string(&self.path)
+    }
+
+    #[getter]
+    fn resolver(&self, py: Python<'_>) -> Py<PyResolver> {
+        self.resolver.clone_ref(py)
+    }
+
+    /// Like Resolver.dependencies, for this file.
+    fn dependencies(&self, py: Python<'_>) -> PyResult<Vec<Dependency>> {
+        self.resolver.get().dependencies(py, self.path.clone())
+    }
+
+    /// The path of every library needed, recursively, leaving out the vDSO and ignored
+    /// libraries, which are not files. Raises AnalysisError if one is not found, naming it.
+    fn full_paths(&self, py: Python<'_>) -> PyResult<Vec<OsString>> {
+        let resolver = &self.resolver.get().0;
+        let paths = Self::analyze(py, resolver, &self.path, ElfFile::get_libs_full_paths)?;
+        Ok(paths.iter().map(|path| os_string(path)).collect())
+    }
+
+    /// The full dependency graph of this file, with a node per distinct library. Raises
+    /// AnalysisError if the file itself can't be analyzed.
+    fn dependency_graph(&self, py: Python<'_>) -> PyResult<DependencyGraph> {
+        let resolver = &self.resolver.get().0;
+        let graph = Self::analyze(py, resolver, &self.path, ElfFile::dependency_graph)?;
+        let interpreter = graph.interpreter().map(os_string);
+        let (nodes, dependencies) = graph.into_adjacency_list();
+        let nodes = nodes
+            .iter()
+            .map(|node| Py::new(py, DependencyNode::from(node)));
+        let dependencies = dependencies
+            .iter()
+            .map(|ids| ids.iter().map(|id| id.index()));
+        Ok(DependencyGraph {
+            nodes: nodes.collect::<PyResult<_>>()?,
+            dependencies: dependencies.map(Iterator::collect).collect(),
+            interpreter,
+        })
+    }
+}
+
+/// A library of the closure of an analyzed file.
+#[pyclass(module = "elf_dynamic_lib_getter", frozen, eq, get_all)]
+#[derive(PartialEq)]
+struct Dependency {
+    /// The name the library was requested by.
+    soname: String,
+    /// Where it was found, or None if it was not, or is the vDSO or an ignored library.
+    path: Option<OsString>,
+    /// Why it was not found, or None if it was, or is the vDSO or an ignored library.
+    error: Option<String>,
+}
+
+#[pymethods]
+impl Dependency {
+    #[new]
+    #[pyo3(signature = (soname, path=None, error=None))]
+    fn new(soname: String, path: Option<OsString>, error: Option<String>) -> Self {
+        Self {
+            soname,
+            path,
+            error,
+        }
+    }
+
+    /// Whether the library was found on disk.
+    #[getter]
+    fn found(&self) -> bool {
+        self.path.is_some()
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!(
+            "Dependency(soname={}, path={}, error={})",
+            repr(py, &self.soname)?,
+            repr(py, &self.path)?,
+            repr(py, &self.error)?
+        ))
+    }
+}
+
+/// The shared-library dependencies of an ELF file: a node per distinct library, the first
+/// being the analyzed file, and the libraries each one directly needs, by index.
+#[pyclass(module = "elf_dynamic_lib_getter", frozen)]
+struct DependencyGraph {
+    nodes: Vec<Py<DependencyNode>>,
+    dependencies: Vec<Vec<usize>>,
+    interpreter: Option<OsString>,
+}
+
+#[pymethods]
+impl DependencyGraph {
+    /// Every node, the analyzed file first, in the order they were encountered.
+    #[getter]
+    fn nodes(&self, py: Python<'_>) -> Vec<Py<DependencyNode>> {
+        self.nodes.iter().map(|node| node.clone_ref(py)).collect()
+    }
+
+    /// The index of the analyzed file itself.
+    #[getter]
+    fn root(&self) -> usize {
+        0
+    }
+
+    /// The interpreter the analyzed file requests, if it has one.
+    #[getter]
+    fn interpreter(&self) -> Option<OsString> {
+        self.interpreter.clone()
+    }
+
+    /// The indices of the libraries the node at `index` directly needs, in DT_NEEDED order.
+    fn dependencies(&self, index: usize) -> PyResult<Vec<usize>> {
+        self.dependencies
+            .get(index)
+            .cloned()
+            .ok_or_else(|| pyo3::exceptions::PyIndexError::new_err(index))
+    }
+
+    /// Every (needed by, needed) pair of indices.
+    fn edges(&self) -> Vec<(usize, usize)> {
+        let edges = self.dependencies.iter().enumerate();
+        edges
+            .flat_map(|(from, to)| to.iter().map(move |&to| (from, to)))
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+/// A single object in a DependencyGraph.
+#[pyclass(module = "elf_dynamic_lib_getter", frozen, get_all)]
+struct DependencyNode {
+    /// The name this library was requested by, or the file name of the analyzed file.
+    soname: String,
+    /// How the object that first led to this library refers to it, like "Needed".
+    kind: &'static str,
+    /// Where the library was found, or None if it was not, or is not a file.
+    path: Option<OsString>,
+    /// Why the library was not found, or None if it was.
+    error: Option<String>,
+    /// Which rule of the search order found the library, like "Runpath", or None.
+    found_by: Option<&'static str>,
+    /// The library's own DT_SONAME, if it was read and has one.
+    dt_soname: Option<String>,
+    /// The GNU build ID of the file, in lowercase hexadecimal, if it has one.
+    build_id: Option<String>,
+    /// The symlinks followed from `path` to the library file itself.
+    links: Vec<OsString>,
+    /// Whether the library's own dependencies were left out.
+    truncated: bool,
+}
+
+impl From<&crate::DependencyNode> for DependencyNode {
+    fn from(node: &crate::DependencyNode) -> Self {
+        let (path, error) = split_resolution(&node.resolution);
+        Self {
+            soname: node.soname.clone(),
+            kind: kind_name(node.kind),
+            path,
+            error,
+            found_by: node.found_by.as_ref().map(rule_name),
+            dt_soname: node.dt_soname.clone(),
+            build_id: node.build_id.clone(),
+            links: node.links.iter().map(|link| os_string(link)).collect(),
+            truncated: node.truncated,
+        }
+    }
+}
+
+#[pymethods]
+impl DependencyNode {
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!(
+            "DependencyNode(soname={}, kind={}, path={})",
+            repr(py, &self.soname)?,
+            repr(py, self.kind)?,
+            repr(py, &self.path)?
+        ))
+    }
+}
+
+/// The name of the variant of `kind`, as it is serialized.
+fn kind_name(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Needed => "Needed",
+        DependencyKind::Filter => "Filter",
+        DependencyKind::Auxiliary => "Auxiliary",
+        DependencyKind::Preload => "Preload",
+        DependencyKind::Plugin => "Plugin",
+        DependencyKind::Weak => "Weak",
+        DependencyKind::DelayLoad => "DelayLoad",
+        DependencyKind::Interpreter => "Interpreter",
+        DependencyKind::Virtual => "Virtual",
+        DependencyKind::Dlopen(_) => "Dlopen",
+    }
+}
+
+/// The name of the variant of `rule`, as it is serialized.
+fn rule_name(rule: &SearchRule) -> &'static str {
+    match rule {
+        SearchRule::Path => "Path",
+        SearchRule::Rpath { .. } => "Rpath",
+        SearchRule::LdLibraryPath => "LdLibraryPath",
+        SearchRule::Runpath { .. } => "Runpath",
+        SearchRule::SearchDir => "SearchDir",
+        SearchRule::Cache => "Cache",
+        SearchRule::ConfiguredDir => "ConfiguredDir",
+        SearchRule::DefaultDir => "DefaultDir",
+        SearchRule::Override => "Override",
+    }
+}
+
+#[pymodule]
+fn _native(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("AnalysisError", module.py().get_type::<AnalysisError>())?;
+    module.add_class::<PyResolver>()?;
+    module.add_class::<PyElfFile>()?;
+    module.add_class::<Dependency>()?;
+    module.add_class::<DependencyGraph>()?;
+    module.add_class::<DependencyNode>()?;
+    Ok(())
+}
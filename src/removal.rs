@@ -0,0 +1,228 @@
+//! What breaks when a library is removed, e.g. before pruning an image down to what its
+//! programs use.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{
+    ElfFile, Error, FileMetadata, FileStat, FileSystem, ResolveError, Resolver, ResolverConfig,
+    ReverseIndex,
+};
+
+/// The results of [`Resolver::impact_of_removing`].
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemovalImpact {
+    /// Each scanned file that needs the removed library, directly or through other libraries,
+    /// and would then lack one, with the sonames that would no longer be found.
+    pub broken: BTreeMap<PathBuf, BTreeSet<String>>,
+    /// The scanned files that need the removed library, but would find another in its place.
+    pub unaffected: BTreeSet<PathBuf>,
+    /// The files that could not be analyzed without the removed library, with why.
+    pub failed: Vec<(PathBuf, Error)>,
+    /// Whether the analysis was stopped by the [cancel flag](crate::ResolverConfig::cancel_flag)
+    /// or the [deadline](crate::ResolverConfig::deadline) before every affected file was
+    /// analyzed again.
+    pub interrupted: bool,
+}
+
+impl Resolver {
+    /// Finds which of the files in `index`, as built by [`Resolver::reverse_index`] with this
+    /// resolver, would have libraries that are not found if the file at `path` were deleted.
+    /// Every file that needs the library at `path`, or needs a scanned library that does, and
+    /// so on, is analyzed again as if it were gone, so a file that would find another copy of
+    /// the library further down its search path is not broken. `path` may name the library
+    /// through a symlinked directory; if it is a symlink, only the symlink is removed.
+    pub fn impact_of_removing<P: AsRef<Path>>(
+        self: &Arc<Self>,
+        index: &ReverseIndex,
+        path: P,
+    ) -> RemovalImpact {
+        let config = self.config();
+        let path = path.as_ref();
+        // The files that need the removed library, and the files that need any of those. Files
+        // are told apart by their canonical paths, so this may find more than a removed symlink
+        // affects, but each is checked below.
+        let mut users: HashMap<PathBuf, Vec<&PathBuf>> = HashMap::new();
+        for (library, files) in &index.paths {
+            users
+                .entry(config.canonical(library))
+                .or_default()
+                .extend(files);
+        }
+        let mut affected = BTreeSet::new();
+        let mut libraries = vec![config.canonical(path)];
+        while let Some(library) = libraries.pop() {
+            for &user in users.get(&library).into_iter().flatten() {
+                if affected.insert(user.clone()) {
+                    libraries.push(config.canonical(user));
+                }
+            }
+        }
+        let removed = Removed::new(config.clone(), path);
+        affected.retain(|file| !removed.is(file));
+
+        let config = config.clone().filesystem(Arc::new(removed));
+        let without = Arc::new(Resolver::new(config));
+        let mut impact = RemovalImpact::default();
+        for file in affected {
+            let before = missing(self, &file).unwrap_or_default();
+            let after = missing(&without, &file);
+            // Either graph may be partial
+            if self.config().is_interrupted() {
+                impact.interrupted = true;
+                break;
+            }
+            match after {
+                Ok(after) => {
+                    let lost: BTreeSet<String> = after.difference(&before).cloned().collect();
+                    if lost.is_empty() {
+                        impact.unaffected.insert(file);
+                    } else {
+                        impact.broken.insert(file, lost);
+                    }
+                }
+                Err(error) => impact.failed.push((file, error)),
+            }
+        }
+        impact
+    }
+}
+
+/// The sonames `resolver` does not find in the closure of the file at `path`.
+fn missing(resolver: &Arc<Resolver>, path: &Path) -> Result<BTreeSet<String>, Error> {
+    let graph = ElfFile::with_resolver(path, resolver.clone()).dependency_graph()?;
+    let missing = graph
+        .nodes()
+        .iter()
+        .filter(|node| matches!(node.resolution, Err(ResolveError::NotFound { .. })))
+        .map(|node| node.soname.clone())
+        .collect();
+    Ok(missing)
+}
+
+/// The files of `config` without the removed one, as a [`FileSystem`].
+#[derive(Debug)]
+struct Removed {
+    config: ResolverConfig,
+    /// The canonical path of the directory the removed file is in.
+    dir: PathBuf,
+    name: OsString,
+}
+
+impl Removed {
+    fn new(config: ResolverConfig, path: &Path) -> Self {
+        let name = path.file_name().unwrap_or_default().to_owned();
+        let dir = config.canonical(path.parent().unwrap_or(Path::new("/")));
+        Self { config, dir, name }
+    }
+
+    /// Whether `path` names the removed file.
+    fn is(&self, path: &Path) -> bool {
+        // Only canonicalize the paths that could name it
+        path.file_name() == Some(&self.name)
+            && self
+                .config
+                .canonical(path.parent().unwrap_or(Path::new("/")))
+                == self.dir
+    }
+
+    fn check(&self, path: &Path) -> io::Result<()> {
+        if self.is(path) {
+            Err(io::ErrorKind::NotFound.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl FileSystem for Removed {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.check(path)?;
+        self.config.fs().read(path)
+    }
+
+    fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.check(path)?;
+        self.config.fs().read_at(path, offset, len)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.check(path)?;
+        self.config.fs().metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.check(path)?;
+        self.config.fs().read_link(path)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        self.check(path)?;
+        self.config.fs().stat(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        let mut names = self.config.fs().read_dir(path)?;
+        if self.config.canonical(path) == self.dir {
+            names.retain(|name| *name != self.name);
+        }
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ElfBuilder;
+    use crate::{Resolver, ResolverConfig};
+    use std::collections::BTreeSet;
+    use std::os::unix::fs::symlink;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_impact_of_removing() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        let fallback = dir.path().join("fallback");
+        let libfoo = ElfBuilder::new().write(&lib, "libfoo.so.2");
+        let libbar = ElfBuilder::new()
+            .needed("libfoo.so.2")
+            .write(&lib, "libbar.so.1");
+        let libz = ElfBuilder::new().write(&lib, "libz.so.1");
+        ElfBuilder::new().write(&fallback, "libz.so.1");
+        let bin = dir.path().join("bin");
+        let app = ElfBuilder::new().needed("libfoo.so.2").write(&bin, "app");
+        let tool = ElfBuilder::new()
+            .needed("libbar.so.1")
+            .needed("libz.so.1")
+            .write(&bin, "tool");
+        let zapp = ElfBuilder::new().needed("libz.so.1").write(&bin, "zapp");
+        ElfBuilder::new().write(&bin, "other");
+        symlink(&lib, dir.path().join("alias")).unwrap();
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(&lib)
+            .append_search_dir(&fallback);
+        let resolver = Arc::new(Resolver::new(config));
+        let index = resolver.reverse_index([&bin, &lib]);
+
+        let impact = resolver.impact_of_removing(&index, &libfoo);
+        let foo = BTreeSet::from(["libfoo.so.2".to_owned()]);
+        let broken: Vec<_> = impact.broken.iter().collect();
+        assert_eq!(broken, [(&app, &foo), (&tool, &foo), (&libbar, &foo)]);
+        assert!(impact.unaffected.is_empty());
+        assert!(impact.failed.is_empty());
+        // The same file, named through a symlinked directory
+        let impact = resolver.impact_of_removing(&index, dir.path().join("alias/libfoo.so.2"));
+        assert_eq!(impact.broken.len(), 3);
+
+        // Both users of libz find the other copy
+        let impact = resolver.impact_of_removing(&index, &libz);
+        assert!(impact.broken.is_empty());
+        assert!(impact.unaffected.iter().eq([&tool, &zapp]));
+    }
+}
@@ -0,0 +1,1754 @@
+//! Searching the configured directories for a library by its soname.
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::abi::{ELFOSABI_FREEBSD, ELFOSABI_NONE};
+use crate::android::LdConfig;
+use crate::disk_cache::Stamp;
+use crate::incremental::Tracking;
+use crate::metrics::{Counters, MeteredFs, Phase};
+use crate::object::MAX_HEADER_SIZE;
+use crate::os_str::OsStrExt;
+use crate::parse::{self, Arch, OsAbi, ParsedObject};
+use crate::search_trace::{ProbeResult, Rejection, RejectionReason, SearchPathEntry, SearchStep};
+use crate::{
+    android, default_search_dirs, dlopen, dst, freebsd, hwcaps, musl, preload, search_order,
+    sysroot, Candidate, DependencyKind, DynamicInfo, ElfClass, ElfHeader, Endianness, Error,
+    FileStat, FileSystem, LdSoCache, LdSoConf, Machine, OsAbiCheck, OsAbiKind, Platform,
+    ResolutionMetrics, ResolverConfig, SearchRule, Target, LD_SO_CACHE_PATH, LD_SO_CONF_PATH,
+    LD_SO_PRELOAD_PATH,
+};
+
+/// The path a library was found at and the rule that found it, or every path that was tried.
+pub(crate) type SearchOutcome = Result<(PathBuf, SearchRule), Vec<PathBuf>>;
+
+/// What [`Resolver::search_recorded`] notes down about a search besides its outcome.
+#[derive(Default)]
+pub(crate) struct SearchRecord {
+    /// Whether to go on after the library is found, adding every file by its name to
+    /// `candidates`, as for [`ResolverConfig::exhaustive_search`].
+    pub exhaustive: bool,
+    /// Whether to add every path probed until the library is found to `steps`, as for
+    /// [`ResolverConfig::trace_search`].
+    pub trace: bool,
+    pub candidates: Vec<Candidate>,
+    pub steps: Vec<SearchStep>,
+    /// If the library was not found, why each path was passed over.
+    pub rejections: Vec<Rejection>,
+}
+
+/// Finds libraries the way the dynamic linker would, according to a [`ResolverConfig`]. The
+/// process environment is read when the resolver is created, and the `ld.so.cache`,
+/// `ld.so.conf` and `ld.so.preload` when they are first needed, and again once they change.
+///
+/// Every file a resolver reads is parsed only once, so analyzing many binaries with one shared
+/// resolver (see [`ElfFile::with_resolver`](crate::ElfFile::with_resolver)) reads common
+/// libraries like `libc.so.6` only once. Resolvers are `Send` and `Sync`: one behind an
+/// [`Arc`] can serve every thread of a long-running service, which analyze files concurrently
+/// while sharing its caches. Nothing a resolver does changes the process environment or
+/// working directory, and later changes to the environment do not change its results.
+///
+/// ```
+/// use elf_dynamic_lib_getter::{Resolver, ResolverConfig};
+///
+/// let resolver = Resolver::new(ResolverConfig::new());
+/// match resolver.resolve_soname("libc.so.6") {
+///     Ok(path) => println!("libc.so.6 => {}", path.display()),
+///     Err(error) => println!("{error}"),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Resolver {
+    config: ResolverConfig,
+    ld_library_path: Option<OsString>,
+    ld_preload: Vec<OsString>,
+    /// The `glibc-hwcaps` subdirectories the host CPU supports.
+    host_hwcaps: Vec<String>,
+    /// The subdirectories for the [legacy hardware capabilities](ResolverConfig::legacy_hwcaps).
+    legacy_hwcaps: Vec<PathBuf>,
+    /// The system configuration, read when first needed and again once the files it was read
+    /// from change.
+    system: Mutex<Option<Arc<SystemConfig>>>,
+    system_dirs: Mutex<SystemDirs>,
+    /// The host files `system` and `system_dirs` were read from, with the stamps they had then.
+    system_stamps: Mutex<Vec<(PathBuf, Option<Stamp>)>>,
+    /// Parsed objects by the path they were read from, with the stamp the file had then if it
+    /// is on the host.
+    parsed: Mutex<HashMap<PathBuf, ParsedEntry>>,
+    /// Every candidate path searching has checked, if they are recorded.
+    probes: Option<Mutex<HashSet<PathBuf>>>,
+    /// The searches that found nothing, with the paths they tried.
+    misses: Mutex<HashMap<MissKey, Vec<PathBuf>>>,
+    /// The system libraries [`ElfFile::all_deps_resolvable`](crate::ElfFile::all_deps_resolvable)
+    /// found every library of, by the executable loading them.
+    verified: Mutex<HashSet<VerifiedKey>>,
+    /// The closures kept track of for [`Resolver::recompute_affected`].
+    tracking: Mutex<Tracking>,
+    metrics: Counters,
+}
+
+/// What the outcome of a search depends on: the soname, the directories searched before the
+/// custom and system ones, the architecture, OS ABI and C library of the executable, and
+/// `nodeflib`.
+type MissKey = (OsString, Vec<PathBuf>, Option<(Arch, OsAbi, Libc)>, bool);
+
+/// A library, and the architecture, OS ABI, C library and secure-execution mode of the
+/// executable loading it.
+type VerifiedKey = (PathBuf, Arch, OsAbi, Libc, bool);
+
+/// A parsed object, with the stamp of its file when it was read from the host.
+type ParsedEntry = (Option<Stamp>, Arc<ParsedObject>);
+
+/// The system directories, by dynamic linker and architecture.
+type SystemDirs = HashMap<(Libc, Option<Arch>), Arc<[(PathBuf, SearchRule)]>>;
+
+/// The system-wide dynamic linker configuration.
+#[derive(Debug, Default)]
+struct SystemConfig {
+    cache: Option<LdSoCache>,
+    conf: LdSoConf,
+    /// The entries of `ld.so.preload`.
+    preload: Vec<OsString>,
+    /// Android's linker namespace configuration.
+    ld_config: LdConfig,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new(ResolverConfig::default())
+    }
+}
+
+impl Resolver {
+    /// Creates a resolver that searches for libraries according to `config`.
+    pub fn new(mut config: ResolverConfig) -> Self {
+        // Every analysis sees the same variables, whatever the process sets meanwhile
+        if config.use_env && config.environment.is_none() {
+            config.environment = Some(env::vars_os().collect());
+        }
+        let env_var = |name| config.env_var(name);
+        let ld_library_path = env_var("LD_LIBRARY_PATH");
+        let ld_preload = match &config.preload {
+            Some(libs) => libs.iter().map(OsString::from).collect(),
+            None => preload::split(&env_var("LD_PRELOAD").unwrap_or_default()),
+        };
+        let legacy_hwcaps = hwcaps::legacy_subdirs(&config.legacy_hwcaps);
+        Self {
+            config,
+            ld_library_path,
+            ld_preload,
+            host_hwcaps: hwcaps::host_subdirs(),
+            legacy_hwcaps,
+            system: Mutex::new(None),
+            system_dirs: Mutex::new(HashMap::new()),
+            system_stamps: Mutex::new(Vec::new()),
+            parsed: Mutex::new(HashMap::new()),
+            probes: None,
+            misses: Mutex::new(HashMap::new()),
+            verified: Mutex::new(HashSet::new()),
+            tracking: Mutex::new(Tracking::default()),
+            metrics: Counters::default(),
+        }
+    }
+
+    /// A resolver like [`Resolver::new`]'s, that records every candidate path it checks while
+    /// searching for libraries, for [`Resolver::take_probes`].
+    #[cfg(all(unix, feature = "watch"))]
+    pub(crate) fn recording_probes(config: ResolverConfig) -> Self {
+        Self {
+            probes: Some(Mutex::new(HashSet::new())),
+            ..Self::new(config)
+        }
+    }
+
+    /// The candidate paths checked since the last call, if they are recorded: the paths a
+    /// library appearing at would change what is found.
+    #[cfg(all(unix, feature = "watch"))]
+    pub(crate) fn take_probes(&self) -> HashSet<PathBuf> {
+        let Some(probes) = &self.probes else {
+            return HashSet::new();
+        };
+        std::mem::take(&mut probes.lock().unwrap_or_else(|error| error.into_inner()))
+    }
+
+    pub(crate) fn tracking(&self) -> MutexGuard<'_, Tracking> {
+        self.tracking
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Reads the system configuration files the configuration asks for, unless they were read
+    /// before.
+    fn system(&self) -> Arc<SystemConfig> {
+        // Held while reading, so the stamps are never cleared between reading and remembering
+        let mut system = self
+            .system
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let system = system.get_or_insert_with(|| {
+            let config = &self.config;
+            let fs = self.fs();
+            let mut system = SystemConfig::default();
+            if config.default_dirs {
+                // Like ld.so, carry on without the cache if it is missing or unreadable
+                let cache = self.read_system_file(LD_SO_CACHE_PATH);
+                system.cache = cache.and_then(|cache| LdSoCache::parse(&cache));
+                let sysroot = config.sysroot.as_deref().unwrap_or(Path::new("/"));
+                self.stamp_system_file(config.rebase(Path::new(LD_SO_CONF_PATH)));
+                system.conf =
+                    LdSoConf::load_in(&fs, sysroot, Path::new(LD_SO_CONF_PATH)).unwrap_or_default();
+                for source in system.conf.sources() {
+                    self.stamp_system_file(source.clone());
+                }
+                let ld_config = android::LD_CONFIG_PATHS
+                    .iter()
+                    .find_map(|path| String::from_utf8(self.read_system_file(path)?).ok());
+                system.ld_config = LdConfig::parse(&ld_config.unwrap_or_default());
+            }
+            if config.system_preload {
+                let contents = self.read_system_file(LD_SO_PRELOAD_PATH);
+                system.preload = preload::split(OsStr::from_bytes(&contents.unwrap_or_default()));
+            }
+            Arc::new(system)
+        });
+        system.clone()
+    }
+
+    /// Reads the system file at `path`, as the target sees it, noting the stamp it has first
+    /// if it is on the host.
+    fn read_system_file(&self, path: &str) -> Option<Vec<u8>> {
+        let path = Path::new(path);
+        self.stamp_system_file(self.config.rebase(path));
+        self.fs().read(&self.config.system_file(path)).ok()
+    }
+
+    /// Notes the stamp the system file or directory at the host path `path` has, unless it is
+    /// in a [filesystem](ResolverConfig::filesystem).
+    fn stamp_system_file(&self, path: PathBuf) {
+        if self.config.filesystem.is_some() {
+            return;
+        }
+        self.metrics.stat();
+        let stamp = Stamp::of(&path);
+        self.system_stamps
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .push((path, stamp));
+    }
+
+    /// Forgets the system configuration if a file it was read from has changed since, as when
+    /// `ldconfig` rebuilds the cache, so it is read again the next time it is needed.
+    fn refresh_system(&self) {
+        let stamps = self
+            .system_stamps
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clone();
+        let changed = stamps.iter().any(|(path, stamp)| {
+            self.metrics.stat();
+            Stamp::of(path) != *stamp
+        });
+        if changed {
+            debug!("system configuration changed since it was read");
+            self.reset_system();
+        }
+    }
+
+    /// Whether `path` is one of the system files the configuration and system directories are
+    /// read from, such as `ld.so.cache`, or a file or directory `ld.so.conf` includes.
+    fn is_system_file(&self, path: &Path) -> bool {
+        let config = &self.config;
+        let fixed = [LD_SO_CACHE_PATH, LD_SO_CONF_PATH, LD_SO_PRELOAD_PATH]
+            .into_iter()
+            .chain(android::LD_CONFIG_PATHS)
+            .map(Path::new)
+            .any(|file| path == config.rebase(file) || path == config.system_file(file));
+        let included = self
+            .system
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .as_ref()
+            .is_some_and(|system| system.conf.sources().iter().any(|source| source == path));
+        let stamped = self
+            .system_stamps
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .iter()
+            .any(|(stamped, _)| stamped == path);
+        fixed || included || stamped
+    }
+
+    /// Forgets the system configuration and directories, and the searches and libraries found
+    /// under them.
+    fn reset_system(&self) {
+        {
+            // Locked in the order system_dirs takes them
+            let mut system_dirs = self
+                .system_dirs
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            let mut system = self
+                .system
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            system_dirs.clear();
+            *system = None;
+            self.system_stamps
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+                .clear();
+        }
+        self.misses
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clear();
+        self.verified
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clear();
+    }
+
+    /// The number of distinct files this resolver has parsed so far.
+    pub fn parsed_files(&self) -> usize {
+        self.parsed
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .len()
+    }
+
+    /// Forgets the file at `path`, so it is parsed again the next time it is
+    /// needed, and removes it from the [disk cache](ResolverConfig::disk_cache). Searches that
+    /// found no library but tried `path` are forgotten too, so a library created there is
+    /// found. If `path` is a system file like `ld.so.cache`, `ld.so.conf` or `ld.so.preload`,
+    /// the system configuration is read again, and every search that found no library is
+    /// forgotten. Files on the
+    /// host are parsed again by themselves once their device, inode, size or modification or
+    /// change time differs, but files of a [filesystem](ResolverConfig::filesystem) are not,
+    /// and a file may be rewritten in place within the resolution of its timestamps. The
+    /// [tracked](Resolver::track) closures the file may change are resolved again by
+    /// [`Resolver::recompute_affected`]. Returns whether the file had been parsed.
+    pub fn invalidate<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        self.tracking().invalidate(path);
+        if let Some(cache) = &self.config.disk_cache {
+            cache.invalidate(path);
+        }
+        if self.is_system_file(path) {
+            debug!(?path, "system configuration invalidated");
+            self.reset_system();
+        }
+        // Any library may need the file, so none is known to have all of its libraries
+        self.verified
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clear();
+        self.misses
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .retain(|_, tried| !tried.iter().any(|tried| tried == path));
+        self.parsed
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .remove(path)
+            .is_some()
+    }
+
+    /// Forgets every file parsed so far, like [`Resolver::invalidate`] for each of them, every
+    /// search that found no library, and the system configuration, which is read again the
+    /// next time it is needed. The
+    /// [disk cache](ResolverConfig::disk_cache) is left alone;
+    /// [`DiskCache::clear`](crate::DiskCache::clear) empties it.
+    pub fn clear_cache(&self) {
+        self.parsed
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clear();
+        self.reset_system();
+    }
+
+    /// Whether the library at `path`, loaded by `executable`, was found to have every library
+    /// it needs.
+    pub(crate) fn is_verified(&self, path: &Path, executable: Executable) -> bool {
+        self.verified
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .contains(&executable.verified_key(path))
+    }
+
+    /// Notes that the libraries at `paths`, loaded by `executable`, have every library they
+    /// need.
+    pub(crate) fn mark_verified(&self, paths: Vec<PathBuf>, executable: Executable) {
+        let mut verified = self
+            .verified
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        verified.extend(paths.iter().map(|path| executable.verified_key(path)));
+    }
+
+    /// Parses the file at `path`, or returns the result of parsing it before if the file has
+    /// not changed since.
+    pub(crate) fn parse(&self, path: &Path) -> Result<Arc<ParsedObject>, Error> {
+        let lock = || {
+            self.parsed
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+        };
+        // Files on the host may be replaced underneath a long-lived resolver, as by package
+        // upgrades, so they are parsed again when their identity changes. The stamp is taken
+        // before parsing, so a file changed meanwhile is parsed again next time.
+        let stamp = match &self.config.filesystem {
+            Some(_) => None,
+            None => {
+                self.metrics.stat();
+                Stamp::of(path)
+            }
+        };
+        if let Some((parsed_stamp, object)) = lock().get(path) {
+            if *parsed_stamp == stamp {
+                trace!(?path, "reusing parsed ELF file");
+                self.metrics.cache_hit();
+                return Ok(object.clone());
+            }
+            debug!(?path, "ELF file changed since it was parsed");
+        }
+        // Keeps the object another thread parsed meanwhile, so every thread gets the same one
+        let remember = |object: Arc<ParsedObject>| {
+            let mut parsed = lock();
+            match parsed.get(path) {
+                Some((parsed_stamp, parsed)) if *parsed_stamp == stamp => parsed.clone(),
+                _ => {
+                    parsed.insert(path.to_owned(), (stamp, object.clone()));
+                    object
+                }
+            }
+        };
+        // Files on the host may be remembered from an earlier run
+        let disk_cache = self.config.disk_cache.as_deref().zip(stamp);
+        let remembered = disk_cache.and_then(|(cache, stamp)| cache.object(path, stamp));
+        if let Some(object) = remembered {
+            trace!(?path, "reusing ELF file parsed in an earlier run");
+            self.metrics.cache_hit();
+            return Ok(remember(object));
+        }
+        if let Some(len) = self.file_size(path) {
+            self.config.check_file_size(path, len)?;
+        }
+        // Parse without holding the lock, so other threads can go on meanwhile
+        let object = self
+            .metrics
+            .time(Phase::Parse, || match &self.config.filesystem {
+                Some(_) => {
+                    let data = self.fs().read(path).map_err(|source| Error::Io {
+                        path: path.to_owned(),
+                        source,
+                    })?;
+                    parse::parse_bytes(path, &data)
+                }
+                None => parse::parse_file(path, &self.metrics),
+            })?;
+        self.metrics.file_parsed();
+        debug!(
+            ?path,
+            needed = ?object.needed,
+            rpath = ?object.rpath,
+            runpath = ?object.runpath,
+            "parsed ELF file"
+        );
+        let object = Arc::new(object);
+        if let Some((cache, stamp)) = disk_cache {
+            cache.insert_object(path, stamp, object.clone());
+        }
+        Ok(remember(object))
+    }
+
+    /// The architecture and OS ABI of the host file at `path`, or `None` if it is not an ELF
+    /// file, from the [disk cache](ResolverConfig::disk_cache) if it has not changed since it
+    /// was remembered there.
+    fn read_header(&self, path: &Path) -> Option<(Arch, OsAbi)> {
+        let Some(cache) = &self.config.disk_cache else {
+            return parse::read_header(path, &self.metrics);
+        };
+        self.metrics.stat();
+        let stamp = Stamp::of(path)?;
+        if let Some(header) = cache.header(path, stamp) {
+            self.metrics.cache_hit();
+            return Some(header);
+        }
+        let header = parse::read_header(path, &self.metrics)?;
+        cache.insert_header(path, stamp, header);
+        Some(header)
+    }
+
+    /// The work this resolver has done since it was created or
+    /// [`Resolver::reset_metrics`] was last called.
+    pub fn metrics(&self) -> ResolutionMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Sets every figure of [`Resolver::metrics`] back to zero, e.g. to measure each analysis
+    /// done with a shared resolver on its own.
+    pub fn reset_metrics(&self) {
+        self.metrics.reset();
+    }
+
+    /// The file system the configuration reads from, counting the work done through it.
+    fn fs(&self) -> MeteredFs<'_> {
+        self.metrics.fs(self.config.fs())
+    }
+
+    /// The configuration this resolver was created with.
+    pub fn config(&self) -> &ResolverConfig {
+        &self.config
+    }
+
+    /// Finds the file the dynamic linker would load for `soname` if no object asked for it,
+    /// i.e. without any `RPATH`/`RUNPATH`. Only libraries for the architecture this crate was
+    /// compiled for, or that of the [target](ResolverConfig::target), are accepted.
+    /// # Return Value [Err]
+    /// [`Error::SonameNotFound`], listing every path that was tried.
+    pub fn resolve_soname<S: AsRef<OsStr>>(&self, soname: S) -> Result<PathBuf, Error> {
+        let soname = soname.as_ref();
+        self.refresh_system();
+        let executable = self.default_executable();
+        self.search(
+            soname,
+            &self.search_dirs(&[], &[], executable),
+            executable,
+            false,
+        )
+        .map(|(path, _)| path)
+        .map_err(|searched| Error::SonameNotFound {
+            soname: soname.to_string_lossy().into_owned(),
+            searched,
+        })
+    }
+
+    /// Reads the object at `path` and works out where to search for its dependencies.
+    /// `inherited_rpath` holds the `RPATH`s of the objects that (transitively) loaded `path`,
+    /// nearest first, which ld.so also searches when `path` has no `RUNPATH`. `executable`
+    /// describes the executable that loads `path`, or is `None` if `path` is the executable.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(?path))
+    )]
+    pub(crate) fn load_object(
+        &self,
+        path: &Path,
+        inherited_rpath: &[(PathBuf, SearchRule)],
+        executable: Option<Executable>,
+    ) -> Result<LoadedObject, Error> {
+        let real_path = self.real_path(path);
+        let object = self.parse(&real_path)?;
+        self.load_parsed(path, real_path, object, inherited_rpath, executable)
+    }
+
+    /// Like [`Resolver::load_object`], for the `object` already parsed from `real_path`, the
+    /// host path of `path`. Fails with [`Error::NotLoadable`] if the dynamic linker would not
+    /// load it, and with [`Error::WrongTarget`] if `path` is the executable and is not built
+    /// for the target.
+    pub(crate) fn load_parsed(
+        &self,
+        path: &Path,
+        real_path: PathBuf,
+        object: Arc<ParsedObject>,
+        inherited_rpath: &[(PathBuf, SearchRule)],
+        executable: Option<Executable>,
+    ) -> Result<LoadedObject, Error> {
+        let is_root = executable.is_none();
+        if is_root {
+            self.refresh_system();
+        }
+        if !object.elf_type.is_loadable() {
+            return Err(Error::NotLoadable {
+                path: path.to_owned(),
+                elf_type: object.elf_type,
+            });
+        }
+        let target = self.config.target.as_ref();
+        if let Some(target) = target.filter(|target| is_root && target.arch() != object.arch) {
+            return Err(Error::WrongTarget {
+                path: path.to_owned(),
+                triple: target.triple().to_owned(),
+            });
+        }
+        let executable = executable.unwrap_or_else(|| {
+            let mut libc = Libc::of(self.config.platform, target, &object);
+            if let Libc::Bionic(section) = &mut libc {
+                // The linker namespaces are picked by where the executable is on the device
+                let device_path = match &self.config.sysroot {
+                    Some(root) => match real_path.strip_prefix(root) {
+                        Ok(relative) => Path::new("/").join(relative),
+                        Err(_) => real_path.clone(),
+                    },
+                    None => real_path.clone(),
+                };
+                *section = self.system().ld_config.section_for(&device_path);
+            }
+            Executable {
+                arch: object.arch,
+                os_abi: object.os_abi,
+                secure: self.config.secure_execution.unwrap_or_else(|| {
+                    let metadata = self.fs().metadata(&real_path);
+                    metadata.is_ok_and(|metadata| metadata.mode & 0o6000 != 0)
+                }),
+                libc,
+            }
+        });
+
+        // Values for the tokens that may appear in RPATH/RUNPATH. Like ld.so, $ORIGIN is the
+        // symlink-resolved directory of the executable but the as-found directory of libraries
+        let origin = if !is_root {
+            std::path::absolute(path).unwrap_or_else(|_| path.to_owned())
+        } else if self.config.sysroot.is_some() {
+            real_path
+        } else {
+            self.config.canonical(path)
+        };
+        let arch = executable.arch;
+        let (lib_token, platform_token) = dst::lib_and_platform(arch, &self.config);
+        let token_values = dst::TokenValues {
+            origin: origin.parent().unwrap_or(Path::new("/")),
+            lib: &lib_token,
+            platform: platform_token,
+        };
+        let expand = |paths: &Option<OsString>, rule: SearchRule| -> Vec<(PathBuf, SearchRule)> {
+            paths
+                .iter()
+                .flat_map(|paths| parse::split(paths, b':'))
+                .filter_map(|path| {
+                    let (path, in_sysroot) = match dst::strip_sysroot_prefix(path) {
+                        Some(rest) => (rest, true),
+                        None => (path, false),
+                    };
+                    let expanded = PathBuf::from(dst::expand(path, &token_values));
+                    let path = path.as_bytes();
+                    // $ORIGIN already points into the sysroot, but absolute entries don't
+                    let expanded = if in_sysroot {
+                        self.config.rebase(&Path::new("/").join(expanded))
+                    } else if path.starts_with(b"/") {
+                        self.config.rebase(&expanded)
+                    } else {
+                        expanded
+                    };
+                    // In secure-execution mode, ld.so only substitutes tokens when that leads
+                    // to a trusted system directory
+                    let trusted = || {
+                        default_search_dirs(Some(arch))
+                            .iter()
+                            .any(|dir| self.config.rebase(dir) == expanded)
+                    };
+                    let keep = !executable.secure || !path.contains(&b'$') || trusted();
+                    if !keep {
+                        debug!(
+                            ?expanded,
+                            "ignoring untrusted search path in secure-execution mode"
+                        );
+                    }
+                    keep.then(|| (expanded, rule.clone()))
+                })
+                .collect()
+        };
+        let owner = path.to_owned();
+        let mut rpath = expand(&object.rpath, SearchRule::Rpath { owner });
+        let owner = path.to_owned();
+        let mut runpath = expand(&object.runpath, SearchRule::Runpath { owner });
+
+        // An object's own RPATH is searched before those it inherits, and the whole chain is
+        // passed on to its dependencies unless a RUNPATH disables it for this object. musl
+        // treats a RUNPATH like an RPATH that replaces the object's own
+        if matches!(executable.libc, Libc::Musl(_)) && !runpath.is_empty() {
+            rpath = std::mem::take(&mut runpath);
+        }
+        // bionic ignores RPATHs altogether
+        if matches!(executable.libc, Libc::Bionic(_)) {
+            rpath.clear();
+        }
+        if runpath.is_empty() {
+            rpath.extend_from_slice(inherited_rpath);
+        } else {
+            rpath = inherited_rpath.to_vec();
+        }
+        let search_dirs = self.search_dirs(&rpath, &runpath, Some(executable));
+        debug!(
+            ?path,
+            secure = executable.secure,
+            libc = ?executable.libc,
+            ?search_dirs,
+            "loaded object"
+        );
+
+        // ld.so loads the preloads right after the executable, searching for them like for its
+        // own dependencies. In secure-execution mode, it ignores LD_PRELOAD paths
+        let mut dependencies = object.dependencies();
+        if is_root {
+            let ld_preload = self.ld_preload.iter();
+            let ld_preload = ld_preload.filter(|lib| !executable.secure || !has_slash(lib));
+            let system = self.system();
+            let preloads = ld_preload.chain(&system.preload);
+            let preloads = preloads.map(|lib| (lib.clone(), DependencyKind::Preload));
+            dependencies.splice(0..0, preloads);
+            dependencies.extend(self.plugins());
+        }
+        if self.config.dlopen_notes {
+            for feature in &object.dlopen {
+                let Some(first) = feature.sonames.first() else {
+                    continue;
+                };
+                let found = feature.sonames.iter().find(|soname| {
+                    let soname = OsStr::new(soname.as_str());
+                    let found =
+                        self.search(soname, &search_dirs, Some(executable), object.nodeflib());
+                    found.is_ok()
+                });
+                let soname = OsString::from(found.unwrap_or(first));
+                dependencies.push((soname, DependencyKind::Dlopen(feature.priority)));
+            }
+        }
+        // The interpreter comes last, as in ldd's output
+        if is_root && self.config.include_interpreter {
+            if let Some(interpreter) = &object.interpreter {
+                dependencies.push((interpreter.clone(), DependencyKind::Interpreter));
+            }
+        }
+        let is_linux = !matches!(executable.libc, Libc::FreeBsd);
+        if is_root && self.config.virtual_objects && is_linux && !object.is_static {
+            let vdso = OsString::from(object.arch.vdso_soname());
+            dependencies.insert(0, (vdso, DependencyKind::Virtual));
+        }
+        let os_abi_mismatch = !is_root
+            && self.config.os_abi_check == OsAbiCheck::Warn
+            && !object.os_abi.loads_into(executable.os_abi);
+        if os_abi_mismatch {
+            warn!(
+                ?path,
+                "library built for another OS ABI than the executable"
+            );
+        }
+        Ok(LoadedObject {
+            executable,
+            dependencies,
+            interpreter: object.interpreter.clone(),
+            soname: object.soname.clone(),
+            build_id: object.build_id.clone(),
+            header: ElfHeader::of(&object),
+            dynamic: DynamicInfo::of(&object),
+            rpath,
+            search_dirs,
+            nodeflib: object.nodeflib(),
+            os_abi_mismatch,
+        })
+    }
+
+    /// The shared libraries in the plugin directories, by path, in order of directory and then
+    /// name. Directories that cannot be read are left out.
+    fn plugins(&self) -> Vec<(OsString, DependencyKind)> {
+        let mut plugins = Vec::new();
+        for dir in &self.config.plugin_dirs {
+            let names = self
+                .locate(&self.config.rebase(dir))
+                .and_then(|real_dir| self.fs().read_dir(&real_dir).ok());
+            let Some(mut names) = names else {
+                debug!(?dir, "cannot read plugin directory");
+                continue;
+            };
+            names.sort();
+            let names = names
+                .into_iter()
+                .filter(|name| dlopen::looks_like_library(name.as_bytes()));
+            plugins.extend(names.map(|name| (dir.join(name).into(), DependencyKind::Plugin)));
+        }
+        plugins
+    }
+
+    /// The executable to search for libraries for when no object needs them: a regular one
+    /// for the target, or for the host if there is none.
+    pub(crate) fn default_executable(&self) -> Option<Executable> {
+        match &self.config.target {
+            Some(target) => Some(Executable {
+                arch: target.arch(),
+                os_abi: target.libc().os_abi(),
+                secure: false,
+                libc: target.libc(),
+            }),
+            None => Executable::host(),
+        }
+    }
+
+    /// The C library of executables whose own is not known.
+    fn default_libc(&self) -> Libc {
+        self.config
+            .target
+            .as_ref()
+            .map_or(Libc::host(), Target::libc)
+    }
+
+    /// The directories to search for the dependencies of an object loaded by `executable` (or
+    /// by an executable for the host, if `None`) with the given expanded `RPATH` chain and
+    /// `RUNPATH`, before the custom and system directories. `LD_LIBRARY_PATH` is left out in
+    /// secure-execution mode.
+    pub(crate) fn search_dirs(
+        &self,
+        rpath: &[(PathBuf, SearchRule)],
+        runpath: &[(PathBuf, SearchRule)],
+        executable: Option<Executable>,
+    ) -> Vec<(PathBuf, SearchRule)> {
+        let secure = executable.is_some_and(|executable| executable.secure);
+        let ld_library_path = self.ld_library_path.as_deref().filter(|_| !secure);
+        let libc = executable.map_or(self.default_libc(), |executable| executable.libc);
+        let empty_as_cwd = libc == Libc::Glibc && self.config.ld_library_path_empty_as_cwd;
+        match libc {
+            Libc::Glibc | Libc::FreeBsd | Libc::Bionic(_) => {
+                search_order(&self.fs(), rpath, ld_library_path, empty_as_cwd, runpath)
+            }
+            // musl searches LD_LIBRARY_PATH before anything else
+            Libc::Musl(_) => {
+                let mut search_dirs = search_order(&self.fs(), &[], ld_library_path, false, &[]);
+                search_dirs.extend_from_slice(rpath);
+                search_dirs
+            }
+        }
+    }
+
+    /// The places a search like [`Resolver::search`] goes through for the dependencies of an
+    /// object loaded by `executable`, in order: `search_dirs`, the custom search directories,
+    /// then unless `nodeflib` glibc's `ld.so.cache`, if there is one, and the system
+    /// directories.
+    pub(crate) fn search_path(
+        &self,
+        search_dirs: &[(PathBuf, SearchRule)],
+        executable: Executable,
+        nodeflib: bool,
+    ) -> Vec<SearchPathEntry> {
+        let config = &self.config;
+        let entry = |(path, found_by): (PathBuf, SearchRule)| SearchPathEntry {
+            path: config.in_working_dir(&path),
+            found_by,
+        };
+        let custom = config
+            .search_dirs
+            .iter()
+            .map(|dir| (dir.clone(), SearchRule::SearchDir));
+        let mut entries: Vec<_> = search_dirs
+            .iter()
+            .cloned()
+            .chain(custom)
+            .map(entry)
+            .collect();
+        if nodeflib {
+            return entries;
+        }
+        let libc = executable.libc;
+        if libc == Libc::Glibc && self.system().cache.is_some() {
+            let cache = config.system_file(Path::new(LD_SO_CACHE_PATH));
+            entries.push(entry((cache, SearchRule::Cache)));
+        }
+        if config.default_dirs {
+            let system_dirs = self.system_dirs(libc, Some(executable.arch));
+            entries.extend(system_dirs.iter().cloned().map(entry));
+        }
+        entries
+    }
+
+    /// Searches `search_dirs`, then the custom and system directories, for a library named
+    /// `soname` that `executable` can load, or for one of any architecture if `executable` is
+    /// `None`. With `nodeflib`, the system directories are left out. Returns the path as found
+    /// and the rule that found it, or every path that was tried. Searches that find nothing
+    /// are remembered until [`Resolver::clear_cache`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(?soname))
+    )]
+    pub(crate) fn search(
+        &self,
+        soname: &OsStr,
+        search_dirs: &[(PathBuf, SearchRule)],
+        executable: Option<Executable>,
+        nodeflib: bool,
+    ) -> SearchOutcome {
+        self.metrics.time(Phase::Search, || {
+            // Batch scans look for the same missing libraries in the same places over and over
+            let key = (
+                soname.to_owned(),
+                search_dirs.iter().map(|(dir, _)| dir.clone()).collect(),
+                executable.map(|executable| (executable.arch, executable.os_abi, executable.libc)),
+                nodeflib,
+            );
+            let misses = || {
+                self.misses
+                    .lock()
+                    .unwrap_or_else(|error| error.into_inner())
+            };
+            if let Some(tried) = misses().get(&key) {
+                self.metrics.miss_cache_hit();
+                return Err(tried.clone());
+            }
+            let outcome = self.search_untimed(soname, search_dirs, executable, nodeflib, None);
+            if let Err(tried) = &outcome {
+                misses().insert(key, tried.clone());
+            }
+            outcome
+        })
+    }
+
+    /// Like [`Resolver::search`], also noting down in `record` what it asks for.
+    pub(crate) fn search_recorded(
+        &self,
+        soname: &OsStr,
+        search_dirs: &[(PathBuf, SearchRule)],
+        executable: Option<Executable>,
+        nodeflib: bool,
+        record: &mut SearchRecord,
+    ) -> SearchOutcome {
+        self.metrics.time(Phase::Search, || {
+            self.search_untimed(soname, search_dirs, executable, nodeflib, Some(record))
+        })
+    }
+
+    /// Searches like [`Resolver::search`], noting down what `record` asks for if given.
+    fn search_untimed(
+        &self,
+        soname: &OsStr,
+        search_dirs: &[(PathBuf, SearchRule)],
+        executable: Option<Executable>,
+        nodeflib: bool,
+        mut record: Option<&mut SearchRecord>,
+    ) -> SearchOutcome {
+        let config = &self.config;
+        let arch = executable.map(|executable| executable.arch);
+        let libc = executable.map_or(self.default_libc(), |executable| executable.libc);
+        let os_abi = executable
+            .map(|executable| executable.os_abi)
+            .filter(|_| config.os_abi_check == OsAbiCheck::Reject);
+        let locate = |candidate: &Path| {
+            if let Some(probes) = &self.probes {
+                let mut probes = probes.lock().unwrap_or_else(|error| error.into_inner());
+                probes.insert(candidate.to_owned());
+            }
+            self.locate(candidate)
+        };
+        let probe = |candidate: &Path| match locate(candidate) {
+            Some(real_path) => ProbeResult::of(self.header_at(&real_path), arch, os_abi),
+            None => ProbeResult::NotFound,
+        };
+        let candidates = self.probed_paths(soname, search_dirs, libc, arch, nodeflib);
+
+        let mut searched = Vec::new();
+        let mut found = None;
+        for (candidate, rule) in candidates {
+            trace!(?candidate, ?rule, "probing candidate");
+            let Some(record) = record.as_deref_mut() else {
+                if probe(&candidate) == ProbeResult::Found {
+                    debug!(path = ?candidate, ?rule, "found library");
+                    return Ok((candidate, rule));
+                }
+                searched.push(candidate);
+                continue;
+            };
+            // Each file is listed once, under the first rule that reached it
+            let listed = record
+                .candidates
+                .iter()
+                .any(|listed| listed.path == candidate);
+            if listed && found.is_some() {
+                continue;
+            }
+            let result = probe(&candidate);
+            // Like ld.so's, the trace ends with the library that is loaded
+            if record.trace && found.is_none() {
+                record.steps.push(SearchStep {
+                    path: candidate.clone(),
+                    found_by: rule.clone(),
+                    result,
+                });
+            }
+            let compatible = result == ProbeResult::Found;
+            let chosen = compatible && found.is_none();
+            if record.exhaustive && !listed && result != ProbeResult::NotFound {
+                record.candidates.push(Candidate {
+                    path: candidate.clone(),
+                    found_by: rule.clone(),
+                    compatible,
+                    chosen,
+                });
+            }
+            if chosen {
+                debug!(path = ?candidate, ?rule, "found library");
+                found = Some((candidate, rule));
+                if !record.exhaustive {
+                    break;
+                }
+            } else {
+                searched.push(candidate);
+            }
+        }
+        if let Some(found) = found {
+            return Ok(found);
+        }
+        debug!(tried = searched.len(), "library not found");
+        Err(searched)
+    }
+
+    /// Why each path a search like [`Resolver::search`] for `soname` probes was passed over,
+    /// for a library that was not found: the directories in `search_dirs` and the custom search
+    /// directories that don't exist, then the files by its name that exist but can't be
+    /// loaded, in search order.
+    pub(crate) fn rejections(
+        &self,
+        soname: &OsStr,
+        search_dirs: &[(PathBuf, SearchRule)],
+        executable: Option<Executable>,
+        nodeflib: bool,
+    ) -> Vec<Rejection> {
+        let config = &self.config;
+        let arch = executable.map(|executable| executable.arch);
+        let libc = executable.map_or(self.default_libc(), |executable| executable.libc);
+        let os_abi = executable
+            .map(|executable| executable.os_abi)
+            .filter(|_| config.os_abi_check == OsAbiCheck::Reject);
+        let mut rejections = Vec::new();
+        // A missing system directory is no mistake, but a missing RUNPATH entry likely is
+        let custom = config
+            .search_dirs
+            .iter()
+            .map(|dir| (dir.clone(), SearchRule::SearchDir));
+        let dirs = search_dirs.iter().cloned().chain(custom);
+        for (dir, rule) in dirs.filter(|_| !has_slash(soname)) {
+            let dir = config.in_working_dir(&dir);
+            let listed = rejections
+                .iter()
+                .any(|rejection: &Rejection| rejection.path == dir);
+            if !listed && self.locate(&dir).is_none() {
+                let reason = RejectionReason::MissingDirectory;
+                rejections.push(Rejection {
+                    path: dir,
+                    found_by: rule,
+                    reason,
+                });
+            }
+        }
+        for (path, rule) in self.probed_paths(soname, search_dirs, libc, arch, nodeflib) {
+            // Each file is listed once, under the first rule that reached it
+            let listed = rejections.iter().any(|rejection| rejection.path == path);
+            let Some(real_path) = self.locate(&path).filter(|_| !listed) else {
+                continue;
+            };
+            let header = self.header_at(&real_path);
+            let reason = match (header, ProbeResult::of(header, arch, os_abi)) {
+                (_, ProbeResult::Found | ProbeResult::NotFound) => continue,
+                (Some((lib_arch, lib_os_abi)), result) => RejectionReason::Incompatible {
+                    result,
+                    class: ElfClass::of(lib_arch),
+                    endianness: Endianness::of(lib_arch),
+                    machine: Machine::from_e_machine(lib_arch.machine),
+                    os_abi: OsAbiKind::from_os_abi(lib_os_abi.os_abi),
+                },
+                (None, _) => {
+                    // Files that can't be read look like files that aren't ELF files
+                    let error = match &config.filesystem {
+                        Some(_) => self.fs().read_at(&real_path, 0, 0).err(),
+                        None => File::open(&real_path).err(),
+                    };
+                    match error {
+                        Some(error) => RejectionReason::Unreadable {
+                            error: error.to_string(),
+                        },
+                        None => RejectionReason::NotElf,
+                    }
+                }
+            };
+            rejections.push(Rejection {
+                path,
+                found_by: rule,
+                reason,
+            });
+        }
+        rejections
+    }
+
+    /// The architecture and OS ABI from the header of the file at `real_path`, `None` if it is
+    /// not an ELF file or can't be read.
+    fn header_at(&self, real_path: &Path) -> Option<(Arch, OsAbi)> {
+        match &self.config.filesystem {
+            Some(_) => self
+                .fs()
+                .read_at(real_path, 0, MAX_HEADER_SIZE as usize)
+                .ok()
+                .and_then(|data| parse::read_header_of(Cursor::new(data))),
+            None => self.read_header(real_path),
+        }
+    }
+
+    /// The paths to probe for a library named `soname`, like [`Resolver::candidates`], but
+    /// also for names containing a slash, which are paths and are not searched for, and for
+    /// overridden ones.
+    fn probed_paths<'a>(
+        &'a self,
+        soname: &'a OsStr,
+        search_dirs: &'a [(PathBuf, SearchRule)],
+        libc: Libc,
+        arch: Option<Arch>,
+        nodeflib: bool,
+    ) -> Box<dyn Iterator<Item = (PathBuf, SearchRule)> + 'a> {
+        let config = &self.config;
+        if let Some(path) = config.soname_overrides.get(soname) {
+            Box::new(std::iter::once((path.clone(), SearchRule::Override)))
+        } else if has_slash(soname) {
+            let path = config.in_working_dir(&config.rebase(Path::new(soname)));
+            Box::new(std::iter::once((path, SearchRule::Path)))
+        } else {
+            let candidates = self.candidates(soname, search_dirs, libc, arch, nodeflib);
+            Box::new(candidates.map(|(path, rule)| (config.in_working_dir(&path), rule)))
+        }
+    }
+
+    /// The paths to probe for a library named `soname` needed by an object of `arch` using
+    /// `libc`, with the rule that leads to each, in search order. `search_dirs` come first, and
+    /// with `nodeflib` the system directories are left out.
+    fn candidates<'a>(
+        &'a self,
+        soname: &'a OsStr,
+        search_dirs: &'a [(PathBuf, SearchRule)],
+        libc: Libc,
+        arch: Option<Arch>,
+        nodeflib: bool,
+    ) -> impl Iterator<Item = (PathBuf, SearchRule)> + 'a {
+        let config = &self.config;
+
+        let hwcaps: &[String] = match &config.hwcaps {
+            _ if libc != Libc::Glibc => &[],
+            Some(hwcaps) => hwcaps,
+            None if config.target.is_none() && arch == Arch::host() => &self.host_hwcaps,
+            None => &[],
+        };
+        let legacy: &[PathBuf] = match libc {
+            Libc::Glibc => &self.legacy_hwcaps,
+            _ => &[],
+        };
+        let probe = move |(dir, rule): &(PathBuf, SearchRule)| {
+            let candidates = hwcaps::candidates(dir, hwcaps, legacy, soname);
+            let rule = rule.clone();
+            candidates
+                .into_iter()
+                .map(move |candidate| (candidate, rule.clone()))
+        };
+        // Only glibc has a cache of individual libraries
+        let system_dirs = self.system_dirs(libc, arch);
+        let cached = iter::once(self.system())
+            .filter(move |_| libc == Libc::Glibc && !nodeflib)
+            .flat_map(move |system| {
+                let cache = system.cache.as_ref().zip(soname.to_str());
+                let paths = cache.map(|(cache, soname)| cache.lookup(soname));
+                let paths = paths.into_iter().flatten();
+                paths
+                    .map(|path| (config.rebase(path), SearchRule::Cache))
+                    .collect::<Vec<_>>()
+            });
+        let system = (0..system_dirs.len())
+            .filter(move |_| config.default_dirs && !nodeflib)
+            .flat_map(move |index| probe(&system_dirs[index]));
+        let custom = config
+            .search_dirs
+            .iter()
+            .map(|dir| (dir.clone(), SearchRule::SearchDir));
+        search_dirs
+            .iter()
+            .flat_map(probe)
+            .chain(custom.flat_map(move |dir| probe(&dir)))
+            .chain(cached)
+            .chain(system)
+    }
+
+    /// The system directories `libc`'s dynamic linker searches for libraries of `arch`, after
+    /// any cache, as host paths, with whether they are configured or built in.
+    fn system_dirs(&self, libc: Libc, arch: Option<Arch>) -> Arc<[(PathBuf, SearchRule)]> {
+        let mut system_dirs = self
+            .system_dirs
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let dirs = system_dirs.entry((libc, arch)).or_insert_with(|| {
+            let config = &self.config;
+            let read = |path: &str| self.read_system_file(path);
+            let (configured, defaults) = match libc {
+                Libc::Glibc => (
+                    self.system().conf.dirs().to_vec(),
+                    default_search_dirs(arch),
+                ),
+                Libc::Musl(musl_arch) => match read(&musl::path_file(musl_arch)) {
+                    Some(contents) => (
+                        musl::parse_path_file(&String::from_utf8_lossy(&contents)),
+                        Vec::new(),
+                    ),
+                    None => (
+                        Vec::new(),
+                        musl::DEFAULT_SEARCH_DIRS.map(PathBuf::from).to_vec(),
+                    ),
+                },
+                Libc::FreeBsd => {
+                    // 32-bit executables on 64-bit systems have hints of their own
+                    let hints_32 = arch
+                        .filter(|arch| !arch.is_64_bit)
+                        .and_then(|_| read(freebsd::HINTS_PATH_32));
+                    let (hints, defaults) = match hints_32 {
+                        Some(hints) => (Some(hints), &freebsd::DEFAULT_SEARCH_DIRS_32[..]),
+                        None => (read(freebsd::HINTS_PATH), &freebsd::DEFAULT_SEARCH_DIRS[..]),
+                    };
+                    let dirs = hints
+                        .and_then(|hints| freebsd::parse_hints(&hints))
+                        .unwrap_or_default();
+                    (dirs, defaults.iter().map(PathBuf::from).collect())
+                }
+                Libc::Bionic(section) => {
+                    let is_64_bit =
+                        arch.map_or(cfg!(target_pointer_width = "64"), |arch| arch.is_64_bit);
+                    let dirs = self.system().ld_config.search_dirs(section, is_64_bit);
+                    // Without a section, bionic falls back to its built-in search path
+                    if section.is_some() {
+                        (dirs, Vec::new())
+                    } else {
+                        (Vec::new(), dirs)
+                    }
+                }
+            };
+            let configured = configured
+                .iter()
+                .map(|dir| (config.rebase(dir), SearchRule::ConfiguredDir));
+            let defaults = defaults
+                .iter()
+                .map(|dir| (config.rebase(dir), SearchRule::DefaultDir));
+            configured.chain(defaults).collect()
+        });
+        dirs.clone()
+    }
+
+    /// The [content digest](ResolverConfig::content_digest) of the file at `path`, or `None`
+    /// if none is configured or the file can't be read.
+    pub(crate) fn digest(&self, path: &Path) -> Option<String> {
+        let digest = self.config.content_digest.as_ref()?;
+        let path = self.locate(path)?;
+        if let Some(len) = self.file_size(&path) {
+            self.config.check_file_size(&path, len).ok()?;
+        }
+        let data = self.fs().read(&path).ok()?;
+        Some(digest.digest(&data))
+    }
+
+    /// The [`FileStat`] of the file at `path`, or `None` if
+    /// [`ResolverConfig::stat_files`] is not set or the file can't be read.
+    pub(crate) fn stat(&self, path: &Path) -> Option<FileStat> {
+        if !self.config.stat_files {
+            return None;
+        }
+        self.fs().stat(&self.locate(path)?).ok()
+    }
+
+    /// The size of the file at `path`, with symlinks followed, or `None` if it can't be read.
+    pub(crate) fn file_size(&self, path: &Path) -> Option<u64> {
+        Some(self.fs().stat(&self.locate(path)?).ok()?.len)
+    }
+
+    /// The host path of the file at `path`, if it exists. Paths inside the sysroot have their
+    /// symlinks followed relative to the sysroot.
+    pub(crate) fn locate(&self, path: &Path) -> Option<PathBuf> {
+        match &self.config.sysroot {
+            Some(root) if path.starts_with(root) => sysroot::resolve_in(&self.fs(), root, path),
+            _ => self.fs().exists(path).then(|| path.to_owned()),
+        }
+    }
+
+    /// The path to read the file at `path` from: its [host path](Resolver::locate), or `path`
+    /// itself if it doesn't exist, for reading it to fail there.
+    pub(crate) fn real_path(&self, path: &Path) -> PathBuf {
+        self.locate(path).unwrap_or_else(|| path.to_owned())
+    }
+
+    /// Opens the file at `path` from its [real path](Resolver::real_path), in the
+    /// [filesystem](ResolverConfig::filesystem) if there is one.
+    pub(crate) fn open_in_sysroot(&self, path: &Path) -> Result<OpenedFile, Error> {
+        let real_path = self.real_path(path);
+        let io_error = |source| Error::Io {
+            path: real_path.clone(),
+            source,
+        };
+        match &self.config.filesystem {
+            Some(fs) => Ok(OpenedFile::Read(Cursor::new(
+                fs.read(&real_path).map_err(io_error)?,
+            ))),
+            None => Ok(OpenedFile::Host(File::open(&real_path).map_err(io_error)?)),
+        }
+    }
+}
+
+/// A file opened by [`Resolver::open_in_sysroot`]: on the host, or read whole from a
+/// filesystem.
+pub(crate) enum OpenedFile {
+    Host(File),
+    Read(Cursor<Vec<u8>>),
+}
+
+impl Read for OpenedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            OpenedFile::Host(file) => file.read(buf),
+            OpenedFile::Read(data) => data.read(buf),
+        }
+    }
+}
+
+impl Seek for OpenedFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            OpenedFile::Host(file) => file.seek(pos),
+            OpenedFile::Read(data) => data.seek(pos),
+        }
+    }
+}
+
+/// Whether `name` contains a slash, which makes it a path rather than a soname to search for.
+pub(crate) fn has_slash(name: &OsStr) -> bool {
+    name.as_bytes().contains(&b'/')
+}
+
+/// Whether the file at `path` in the files of `config` is set-user-ID or set-group-ID, which
+/// makes ld.so run it in secure-execution mode.
+pub(crate) fn is_set_id(config: &ResolverConfig, path: &Path) -> bool {
+    let metadata = config.fs().metadata(&config.canonical(path));
+    metadata.is_ok_and(|metadata| metadata.mode & 0o6000 != 0)
+}
+
+/// The C library whose dynamic linker loads an executable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Libc {
+    Glibc,
+    /// musl, for the named musl architecture, which may be unknown (empty).
+    Musl(&'static str),
+    FreeBsd,
+    /// Android's bionic, using the namespaces of the `ld.config.txt` section with this index,
+    /// or bionic's built-in search path if `None`.
+    Bionic(Option<usize>),
+}
+
+impl Libc {
+    /// The C library of the executable `object`, unless `platform` overrides it. Files with no
+    /// interpreter are loaded by that of `target`, or of the host if there is none.
+    fn of(platform: Option<Platform>, target: Option<&Target>, object: &ParsedObject) -> Self {
+        let file_name = object
+            .interpreter
+            .as_deref()
+            .and_then(|interpreter| Path::new(interpreter).file_name())
+            .map(|name| name.to_string_lossy());
+        let musl_arch = file_name.as_deref().and_then(musl::arch_of_interpreter);
+        let is_bionic = file_name.as_deref().is_some_and(android::is_interpreter);
+        let is_freebsd = object.os_abi.os_abi == ELFOSABI_FREEBSD
+            || file_name
+                .as_deref()
+                .is_some_and(|name| name.starts_with("ld-elf"));
+        let detected = if musl_arch.is_some() {
+            Some(Platform::Musl)
+        } else if is_freebsd {
+            Some(Platform::FreeBsd)
+        } else if is_bionic {
+            Some(Platform::Android)
+        } else {
+            object.interpreter.as_ref().map(|_| Platform::Glibc)
+        };
+        let target_musl_arch = || target.map_or_else(musl::host_arch, Target::musl_arch);
+        match platform.or(detected) {
+            Some(Platform::Glibc) => Libc::Glibc,
+            Some(Platform::Musl) => Libc::Musl(musl_arch.unwrap_or_else(target_musl_arch)),
+            Some(Platform::FreeBsd) => Libc::FreeBsd,
+            Some(Platform::Android) => Libc::Bionic(None),
+            None => target.map_or(Libc::host(), Target::libc),
+        }
+    }
+
+    /// The OS ABI of the executables this C library's dynamic linker loads, if they have no
+    /// file to read it from.
+    fn os_abi(self) -> OsAbi {
+        let os_abi = match self {
+            Libc::FreeBsd => ELFOSABI_FREEBSD,
+            Libc::Glibc | Libc::Musl(_) | Libc::Bionic(_) => ELFOSABI_NONE,
+        };
+        OsAbi { os_abi, version: 0 }
+    }
+
+    /// The C library this crate was compiled for.
+    fn host() -> Self {
+        if cfg!(target_os = "freebsd") {
+            Libc::FreeBsd
+        } else if cfg!(target_os = "android") {
+            Libc::Bionic(None)
+        } else if cfg!(target_env = "musl") {
+            Libc::Musl(musl::host_arch())
+        } else {
+            Libc::Glibc
+        }
+    }
+}
+
+/// What the dependencies of an executable need to know about it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Executable {
+    /// The architecture every library must be built for.
+    pub arch: Arch,
+    /// The OS ABI every library must [load into](OsAbi::loads_into).
+    pub os_abi: OsAbi,
+    /// Whether ld.so runs the executable in secure-execution mode.
+    pub secure: bool,
+    /// The C library whose dynamic linker loads the executable.
+    pub libc: Libc,
+}
+
+impl Executable {
+    /// A regular executable for the host, if its architecture is a known one.
+    fn host() -> Option<Self> {
+        Arch::host().map(|arch| Self {
+            arch,
+            os_abi: Libc::host().os_abi(),
+            secure: false,
+            libc: Libc::host(),
+        })
+    }
+
+    /// What [`Resolver::is_verified`] knows the library at `path` loaded by this executable by.
+    fn verified_key(self, path: &Path) -> VerifiedKey {
+        (
+            path.to_owned(),
+            self.arch,
+            self.os_abi,
+            self.libc,
+            self.secure,
+        )
+    }
+}
+
+/// An object read by [`Resolver::load_object`].
+pub(crate) struct LoadedObject {
+    /// The executable loading the object, which may be the object itself.
+    pub executable: Executable,
+    /// `DT_NEEDED` entries, in order, followed by the filtees.
+    pub dependencies: Vec<(OsString, DependencyKind)>,
+    /// The program interpreter named by the object's `PT_INTERP`.
+    pub interpreter: Option<OsString>,
+    /// The object's own `DT_SONAME`.
+    pub soname: Option<OsString>,
+    /// The object's GNU build ID, in lowercase hexadecimal.
+    pub build_id: Option<String>,
+    /// What the object's ELF header says.
+    pub header: ElfHeader,
+    /// The raw entries of the object's dynamic section.
+    pub dynamic: DynamicInfo,
+    /// The `RPATH` chain to pass on to the object's dependencies.
+    pub rpath: Vec<(PathBuf, SearchRule)>,
+    /// Directories to search for the dependencies before the custom and system directories.
+    pub search_dirs: Vec<(PathBuf, SearchRule)>,
+    /// Whether to leave out the system directories when searching for the dependencies.
+    pub nodeflib: bool,
+    /// Whether the object does not [load into](OsAbi::loads_into) the executable, which is
+    /// only checked with [`OsAbiCheck::Warn`].
+    pub os_abi_mismatch: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Resolver;
+    use crate::test_util::ElfBuilder;
+    use crate::{ElfFile, Error, HostFs, ResolverConfig};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_resolve_soname() {
+        let resolver = Resolver::default();
+        let libc = resolver.resolve_soname("libc.so.6").unwrap();
+        assert_eq!(libc, PathBuf::from("/lib/x86_64-linux-gnu/libc.so.6"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let foreign = ElfBuilder::new()
+            .machine(crate::abi::EM_AARCH64)
+            .write(dir.path().join("foreign"), "libfoo.so.1");
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path().join("foreign")])
+            .without_default_dirs()
+            .use_env(false)
+            .hwcaps(Vec::<String>::new());
+        let resolver = Resolver::new(config);
+        assert!(matches!(
+            resolver.resolve_soname("libfoo.so.1"),
+            Err(Error::SonameNotFound { soname, searched })
+                if soname == "libfoo.so.1" && searched == [foreign]
+        ));
+    }
+
+    #[test]
+    fn test_negative_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path()])
+            .without_default_dirs()
+            .use_env(false)
+            .hwcaps(Vec::<String>::new());
+        let resolver = Resolver::new(config);
+        assert!(resolver.resolve_soname("libfoo.so.1").is_err());
+        let stat_calls = resolver.metrics().stat_calls;
+        // The same search again is not made, even once the library is there
+        let libfoo = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        assert!(resolver.resolve_soname("libfoo.so.1").is_err());
+        let metrics = resolver.metrics();
+        assert_eq!(
+            (metrics.miss_cache_hits, metrics.stat_calls),
+            (1, stat_calls)
+        );
+        assert!(resolver.resolve_soname("libbar.so.1").is_err());
+        assert_eq!(resolver.metrics().miss_cache_hits, 1);
+
+        resolver.invalidate(&libfoo);
+        assert_eq!(resolver.resolve_soname("libfoo.so.1").unwrap(), libfoo);
+        assert!(resolver.resolve_soname("libbar.so.1").is_err());
+        assert_eq!(resolver.metrics().miss_cache_hits, 2);
+        resolver.clear_cache();
+        assert!(resolver.resolve_soname("libbar.so.1").is_err());
+        assert_eq!(resolver.metrics().miss_cache_hits, 2);
+    }
+
+    #[test]
+    fn test_system_config_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let libfoo = ElfBuilder::new().write(sysroot.join("opt/foo"), "libfoo.so.1");
+        let libbar = ElfBuilder::new().write(sysroot.join("opt/bar"), "libbar.so.1");
+        std::fs::create_dir_all(sysroot.join("etc/ld.so.conf.d")).unwrap();
+        let conf = sysroot.join("etc/ld.so.conf");
+        std::fs::write(&conf, "include ld.so.conf.d/*.conf\n").unwrap();
+        let config = ResolverConfig::new()
+            .sysroot(sysroot)
+            .use_env(false)
+            .hwcaps(Vec::<String>::new());
+        let resolver = Resolver::new(config);
+        assert!(resolver.resolve_soname("libfoo.so.1").is_err());
+        assert!(resolver.resolve_soname("libbar.so.1").is_err());
+
+        // Both a new file in an included directory and a changed ld.so.conf are picked up
+        std::fs::write(sysroot.join("etc/ld.so.conf.d/foo.conf"), "/opt/foo\n").unwrap();
+        assert_eq!(resolver.resolve_soname("libfoo.so.1").unwrap(), libfoo);
+        let mut contents = std::fs::read_to_string(&conf).unwrap();
+        contents.push_str("/opt/bar\n");
+        std::fs::write(&conf, contents).unwrap();
+        assert_eq!(resolver.resolve_soname("libbar.so.1").unwrap(), libbar);
+    }
+
+    #[test]
+    fn test_invalidate_system_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysroot = dir.path();
+        let libfoo = ElfBuilder::new().write(sysroot.join("opt/foo"), "libfoo.so.1");
+        let libbar = ElfBuilder::new().write(sysroot.join("opt/bar"), "libbar.so.1");
+        std::fs::create_dir(sysroot.join("etc")).unwrap();
+        let conf = sysroot.join("etc/ld.so.conf");
+        std::fs::write(&conf, "").unwrap();
+        // Files of a filesystem are not stamped, so only invalidating them brings changes in
+        let config = ResolverConfig::new()
+            .sysroot(sysroot)
+            .filesystem(Arc::new(HostFs))
+            .use_env(false)
+            .hwcaps(Vec::<String>::new());
+        let resolver = Resolver::new(config);
+        assert!(resolver.resolve_soname("libfoo.so.1").is_err());
+        assert!(resolver.resolve_soname("libbar.so.1").is_err());
+
+        std::fs::write(&conf, "/opt/foo\n").unwrap();
+        assert!(resolver.resolve_soname("libfoo.so.1").is_err());
+        resolver.invalidate(&conf);
+        assert_eq!(resolver.resolve_soname("libfoo.so.1").unwrap(), libfoo);
+
+        std::fs::write(&conf, "/opt/foo\n/opt/bar\n").unwrap();
+        assert!(resolver.resolve_soname("libbar.so.1").is_err());
+        resolver.clear_cache();
+        assert_eq!(resolver.resolve_soname("libbar.so.1").unwrap(), libbar);
+    }
+
+    #[test]
+    fn test_hwcaps() {
+        let dir = tempfile::tempdir().unwrap();
+        let hwcaps_dir = dir.path().join("glibc-hwcaps");
+        let plain = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        let v2 = ElfBuilder::new().write(hwcaps_dir.join("x86-64-v2"), "libfoo.so.1");
+        ElfBuilder::new().write(hwcaps_dir.join("x86-64-v4"), "libfoo.so.1");
+        let resolve = |hwcaps: &[&str]| {
+            let config = ResolverConfig::new()
+                .with_search_dirs([dir.path()])
+                .without_default_dirs()
+                .use_env(false)
+                .hwcaps(hwcaps.iter().copied());
+            Resolver::new(config).resolve_soname("libfoo.so.1").unwrap()
+        };
+        assert_eq!(resolve(&["x86-64-v3", "x86-64-v2"]), v2);
+        assert_eq!(resolve(&[]), plain);
+    }
+
+    #[test]
+    fn test_legacy_hwcaps() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = ElfBuilder::new().write(dir.path(), "libfoo.so.1");
+        let tls = ElfBuilder::new().write(dir.path().join("tls"), "libfoo.so.1");
+        let haswell = ElfBuilder::new().write(dir.path().join("haswell/x86_64"), "libfoo.so.1");
+        let hwcaps =
+            ElfBuilder::new().write(dir.path().join("glibc-hwcaps/x86-64-v3"), "libfoo.so.1");
+        let resolve = |hwcaps: &[&str], legacy: &[&str]| {
+            let config = ResolverConfig::new()
+                .with_search_dirs([dir.path()])
+                .without_default_dirs()
+                .use_env(false)
+                .hwcaps(hwcaps.iter().copied())
+                .legacy_hwcaps(legacy.iter().copied());
+            Resolver::new(config).resolve_soname("libfoo.so.1").unwrap()
+        };
+        // tls/haswell/x86_64, tls/haswell and tls/x86_64 have no library
+        assert_eq!(resolve(&[], &["tls", "haswell", "x86_64"]), tls);
+        assert_eq!(resolve(&[], &["haswell", "x86_64"]), haswell);
+        assert_eq!(resolve(&[], &["x86_64"]), plain);
+        assert_eq!(resolve(&[], &[]), plain);
+        // The glibc-hwcaps subdirectories come first
+        assert_eq!(resolve(&["x86-64-v3"], &["tls"]), hwcaps);
+    }
+
+    #[test]
+    fn test_shared_parse_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new().write(dir.path(), "libcommon.so.1");
+        let apps = ["app1", "app2"].map(|name| {
+            ElfBuilder::new()
+                .needed("libcommon.so.1")
+                .write(dir.path(), name)
+        });
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path()])
+            .without_default_dirs()
+            .use_env(false);
+        let resolver = Arc::new(Resolver::new(config));
+        for app in &apps {
+            let elf_file = ElfFile::with_resolver(app, resolver.clone());
+            assert_eq!(elf_file.get_libs_full_paths().unwrap().len(), 1);
+        }
+        // Both apps and the library they share, which was only parsed once
+        assert_eq!(resolver.parsed_files(), 3);
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Resolver>();
+        assert_send_sync::<ResolverConfig>();
+        assert_send_sync::<ElfFile>();
+    }
+
+    #[test]
+    fn test_concurrent_analyses() {
+        let dir = tempfile::tempdir().unwrap();
+        ElfBuilder::new().write(dir.path(), "libcommon.so.1");
+        ElfBuilder::new()
+            .needed("libcommon.so.1")
+            .write(dir.path(), "libmid.so.1");
+        let apps: Vec<_> = (0..8)
+            .map(|i| {
+                ElfBuilder::new()
+                    .needed("libmid.so.1")
+                    .needed("libgone.so.1")
+                    .write(dir.path(), format!("app{i}"))
+            })
+            .collect();
+        let config = ResolverConfig::new()
+            .with_search_dirs([dir.path()])
+            .without_default_dirs()
+            .use_env(false);
+        let resolver = Arc::new(Resolver::new(config));
+        std::thread::scope(|scope| {
+            for app in &apps {
+                let resolver = resolver.clone();
+                scope.spawn(move || {
+                    for _ in 0..4 {
+                        let graph = ElfFile::with_resolver(app, resolver.clone())
+                            .dependency_graph()
+                            .unwrap();
+                        assert_eq!(graph.nodes().len(), 4);
+                        assert_eq!(
+                            graph.nodes().iter().filter(|n| n.path().is_none()).count(),
+                            1
+                        );
+                    }
+                });
+            }
+        });
+        // Each app and the two libraries, whichever threads got to them first
+        assert_eq!(resolver.parsed_files(), 10);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing() {
+        use std::fmt::Debug;
+        use std::sync::Mutex;
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Records the fields of every event, formatted.
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        struct Fields(String);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.push_str(&format!("{}={value:?} ", field.name()));
+            }
+        }
+
+        impl Subscriber for &'static Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields(String::new());
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0);
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let lib = ElfBuilder::new().write(dir.path().join("lib"), "libfoo.so.1");
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .runpath("$ORIGIN/lib")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new().without_default_dirs().use_env(false);
+        let recorder: &'static Recorder = Box::leak(Box::default());
+        tracing::subscriber::with_default(recorder, || {
+            ElfFile::with_config(&app, config)
+                .get_libs_full_paths()
+                .unwrap();
+        });
+        let events = recorder.0.lock().unwrap();
+        let has = |message: &str, path: &std::path::Path| {
+            let (message, path) = (format!("message={message}"), format!("{path:?}"));
+            events
+                .iter()
+                .any(|event| event.starts_with(&message) && event.contains(&path))
+        };
+        assert!(has("parsed ELF file", &app), "{events:?}");
+        assert!(has("probing candidate", &lib), "{events:?}");
+        assert!(has("found library", &lib), "{events:?}");
+    }
+}
@@ -0,0 +1,144 @@
+//! Indexes of which files in a directory tree need a library, to answer "who uses
+//! `libcrypto.so.1.1` here?" before removing or upgrading it.
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{DependencyKind, Error, Resolver};
+
+/// The results of [`Resolver::reverse_index`]: for each library, the scanned files that need
+/// it directly, through a `DT_NEEDED`, `DT_FILTER` or `DT_AUXILIARY` entry of their own.
+/// Libraries only needed through other libraries are listed under those, if they were scanned.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReverseIndex {
+    /// The number of ELF files indexed.
+    pub scanned: usize,
+    /// Each soname needed, found or not, with the files that need it.
+    pub sonames: BTreeMap<String, BTreeSet<PathBuf>>,
+    /// Each path a needed library was found at, and each symlink target on the way to the
+    /// library file, with the files that need it.
+    pub paths: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+    /// The directories that could not be listed and the ELF files that could not be analyzed,
+    /// with why, in the order they were come across.
+    pub failed: Vec<(PathBuf, Error)>,
+    /// Whether indexing was stopped by the [cancel flag](crate::ResolverConfig::cancel_flag)
+    /// or the [deadline](crate::ResolverConfig::deadline) before every file was scanned.
+    pub interrupted: bool,
+}
+
+impl ReverseIndex {
+    /// The scanned files that need the library `soname`, in path order.
+    pub fn users_of_soname(&self, soname: &str) -> impl Iterator<Item = &Path> + '_ {
+        self.sonames
+            .get(soname)
+            .into_iter()
+            .flatten()
+            .map(PathBuf::as_path)
+    }
+
+    /// The scanned files that need the library at `path`, whether they found it there or
+    /// through a symlink to it, in path order.
+    pub fn users_of_path<P: AsRef<Path>>(&self, path: P) -> impl Iterator<Item = &Path> + '_ {
+        self.paths
+            .get(path.as_ref())
+            .into_iter()
+            .flatten()
+            .map(PathBuf::as_path)
+    }
+}
+
+impl Resolver {
+    /// Finds every ELF file under the directories `roots` like [`Resolver::audit`], and indexes
+    /// which libraries each needs by soname and by the path this resolver finds them at. As
+    /// only the libraries a file needs itself are indexed, a resolver that is not
+    /// [recursive](crate::ResolverConfig::recursive) builds the same index faster.
+    pub fn reverse_index<I, P>(self: &Arc<Self>, roots: I) -> ReverseIndex
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut index = ReverseIndex::default();
+        index.interrupted = self.scan(roots, &mut |path, graph| {
+            let graph = match graph {
+                Ok(graph) => graph,
+                Err(error) => {
+                    index.failed.push((path.to_owned(), error));
+                    return ControlFlow::Continue(());
+                }
+            };
+            index.scanned += 1;
+            // Preloads, plugins and the like are not needed by the file itself
+            let needed = graph
+                .dependencies(graph.root())
+                .iter()
+                .map(|&id| graph.node(id))
+                .filter(|node| {
+                    matches!(
+                        node.kind,
+                        DependencyKind::Needed | DependencyKind::Filter | DependencyKind::Auxiliary
+                    )
+                });
+            for node in needed {
+                let users = index.sonames.entry(node.soname.clone()).or_default();
+                users.insert(path.to_owned());
+                let links = node.links.iter().map(PathBuf::as_path);
+                for library in node.path().into_iter().chain(links) {
+                    let users = index.paths.entry(library.to_owned()).or_default();
+                    users.insert(path.to_owned());
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ElfBuilder;
+    use crate::{Resolver, ResolverConfig};
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_reverse_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        let libcrypto = ElfBuilder::new().write(&lib, "libcrypto.so.1.1.1");
+        symlink("libcrypto.so.1.1.1", lib.join("libcrypto.so.1.1")).unwrap();
+        let libssl = ElfBuilder::new()
+            .needed("libcrypto.so.1.1")
+            .write(&lib, "libssl.so.1.1");
+        let bin = dir.path().join("bin");
+        let curl = ElfBuilder::new()
+            .needed("libssl.so.1.1")
+            .needed("libcrypto.so.1.1")
+            .write(&bin, "curl");
+        let wget = ElfBuilder::new()
+            .needed("libssl.so.1.1")
+            .needed("libgone.so.1")
+            .write(&bin, "wget");
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .append_search_dir(&lib)
+            .recursive(false);
+        let resolver = Arc::new(Resolver::new(config));
+        let index = resolver.reverse_index([&bin, &lib]);
+        // The symlink itself is not an ELF file to index
+        assert_eq!(index.scanned, 4);
+        let users: Vec<&Path> = index.users_of_soname("libcrypto.so.1.1").collect();
+        assert_eq!(users, [&curl, &libssl]);
+        assert!(index.users_of_path(&libcrypto).eq(users));
+        let users: Vec<&Path> = index.users_of_path(lib.join("libcrypto.so.1.1")).collect();
+        assert_eq!(users, [&curl, &libssl]);
+        assert!(index.users_of_path(&libssl).eq([&curl, &wget]));
+        assert!(index.users_of_soname("libgone.so.1").eq([&wget]));
+        assert_eq!(index.users_of_soname("libc.so.6").count(), 0);
+        assert!(index.paths.keys().all(|path| path.starts_with(&lib)));
+    }
+}
@@ -0,0 +1,214 @@
+//! Root filesystems archived whole in a tar file, as firmware images are often shipped, read in
+//! memory so binaries can be resolved against them without extracting anything.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::archive_fs::{ArchiveFs, Format};
+use crate::filesystem::FileSystem;
+use crate::{DependencyGraph, ElfFile, Error, ResolverConfig};
+
+/// A root filesystem in a tar archive, plain or compressed with gzip, xz or zstd, its members
+/// taken as the files of a system mounted at `/`. Only the headers are read when the archive is
+/// opened; files are read from it as needed, or, if it is compressed, from its contents
+/// decompressed into memory. Symlinks are followed inside the archive, absolute ones from its root.
+///
+/// ```no_run
+/// use elf_dynamic_lib_getter::{ResolverConfig, RootfsArchive};
+///
+/// let rootfs = RootfsArchive::open("firmware-rootfs.tar.gz")?;
+/// let graph = rootfs.dependency_graph("/usr/sbin/httpd", ResolverConfig::new())?;
+/// print!("{}", graph.to_ldd());
+/// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct RootfsArchive {
+    path: PathBuf,
+    fs: Arc<ArchiveFs>,
+}
+
+impl RootfsArchive {
+    /// Opens the archive at `path`, telling a compressed archive, and how, from a plain one by
+    /// its contents.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if the archive can't be read, or [`Error::InvalidRootfs`] if it isn't a
+    /// tar archive, or is compressed other than with gzip, xz or zstd. Archives compressed
+    /// with a format whose feature, `gzip`, `xz` or `zstd`, is disabled give an [`Error::Io`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        RootfsArchive::open_with_config(path, &ResolverConfig::new())
+    }
+
+    /// Opens the archive at `path` as [`RootfsArchive::open`] does, decompressing it only up
+    /// to the [maximum decompressed size](ResolverConfig::max_decompressed_size) of `config`.
+    ///
+    /// # Return Value [Err]
+    /// As for [`RootfsArchive::open`], or [`Error::LimitExceeded`] if the archive decompresses
+    /// to more.
+    pub fn open_with_config<P: AsRef<Path>>(
+        path: P,
+        config: &ResolverConfig,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let io_error = |source: io::Error| {
+            config.decompression_error(path, source, |source| match source.kind() {
+                io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => Error::InvalidRootfs {
+                    path: path.to_owned(),
+                },
+                _ => Error::Io {
+                    path: path.to_owned(),
+                    source,
+                },
+            })
+        };
+        let size = std::fs::metadata(path).map_err(io_error)?.len();
+        let mut fs = ArchiveFs::new(false, config.decompressed_size_limit());
+        fs.add_layer(path, 0, size, Format::Tar).map_err(io_error)?;
+        Ok(RootfsArchive {
+            path: path.to_owned(),
+            fs: Arc::new(fs),
+        })
+    }
+
+    /// The path of the archive.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the archive has a file or directory at `path`, following symlinks.
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.fs.exists(&Path::new("/").join(path))
+    }
+
+    /// The contents of the file at `path` in the archive, following symlinks.
+    ///
+    /// # Return Value [Err]
+    /// [`Error::Io`] if the archive has no such file, or can't be read.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, Error> {
+        let path = Path::new("/").join(path);
+        self.fs
+            .read(&path)
+            .map_err(|source| Error::Io { path, source })
+    }
+
+    /// Builds the [`DependencyGraph`] of the executable or library at `path` in the archive,
+    /// as [`ElfFile::dependency_graph`] does, according to `config`. Every file is looked up
+    /// in the archive: its `ld.so.cache`, `ld.so.conf` and default directories, and the custom
+    /// search directories of `config`. `LD_LIBRARY_PATH` and `LD_PRELOAD` are not used, and any
+    /// [sysroot](ResolverConfig::sysroot) is replaced by the archive.
+    ///
+    /// # Return Value [Err]
+    /// Only returned when the file itself cannot be analyzed.
+    pub fn dependency_graph<P: AsRef<Path>>(
+        &self,
+        path: P,
+        config: ResolverConfig,
+    ) -> Result<DependencyGraph, Error> {
+        let config = config
+            .use_env(false)
+            .sysroot("/")
+            .filesystem(self.fs.clone());
+        ElfFile::with_config(Path::new("/").join(path), config).dependency_graph()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RootfsArchive;
+    use crate::test_util::{gzip, gzip_zeros, tar, xz, zstd, ArchiveEntry, ElfBuilder};
+    use crate::{Error, ResolverConfig, ResourceLimit, SearchRule};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    #[cfg_attr(
+        not(all(feature = "gzip", feature = "xz", feature = "zstd")),
+        ignore = "needs the `gzip`, `xz` and `zstd` features"
+    )]
+    fn test_rootfs() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = ElfBuilder::new()
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .needed("libmissing.so.1")
+            .build();
+        let library = ElfBuilder::new().build();
+        let data = tar(&[
+            ArchiveEntry::Dir("./usr/bin/"),
+            ArchiveEntry::File("./usr/bin/app", &app),
+            ArchiveEntry::Symlink("./bin", "usr/bin"),
+            ArchiveEntry::File("./opt/foo/lib/libfoo.so.1.2", &library),
+            ArchiveEntry::Symlink("./opt/foo/lib/libfoo.so.1", "/opt/foo/lib/libfoo.so.1.2"),
+            ArchiveEntry::Symlink("./etc/ld.so.conf", "/opt/foo/ld.so.conf"),
+            ArchiveEntry::File("./opt/foo/ld.so.conf", b"/opt/foo/lib\n"),
+            ArchiveEntry::File("./usr/lib/libbar.so.1", &library),
+        ]);
+        let plain = dir.path().join("rootfs.tar");
+        fs::write(&plain, &data).unwrap();
+        let gzip_path = dir.path().join("rootfs.tar.gz");
+        fs::write(&gzip_path, gzip(&data)).unwrap();
+        let xz_path = dir.path().join("rootfs.tar.xz");
+        fs::write(&xz_path, xz(&data)).unwrap();
+        let zstd_path = dir.path().join("rootfs.tar.zst");
+        fs::write(&zstd_path, zstd(&data)).unwrap();
+
+        for path in [&plain, &gzip_path, &xz_path, &zstd_path] {
+            let rootfs = RootfsArchive::open(path).unwrap();
+            assert_eq!(rootfs.path(), path.as_path());
+            assert!(rootfs.exists("/bin/app"));
+            assert!(!rootfs.exists("/usr/lib/libmissing.so.1"));
+            assert_eq!(rootfs.read("etc/ld.so.conf").unwrap(), b"/opt/foo/lib\n");
+            assert!(matches!(rootfs.read("/usr"), Err(Error::Io { .. })));
+
+            let graph = rootfs
+                .dependency_graph("/bin/app", ResolverConfig::new())
+                .unwrap();
+            let node = |soname: &str| {
+                let id = graph
+                    .node_ids()
+                    .find(|&id| graph.node(id).soname == soname)
+                    .unwrap();
+                graph.node(id)
+            };
+            let foo = node("libfoo.so.1");
+            assert_eq!(foo.path(), Some(Path::new("/opt/foo/lib/libfoo.so.1")));
+            assert_eq!(foo.found_by, Some(SearchRule::ConfiguredDir));
+            let bar = node("libbar.so.1");
+            assert_eq!(bar.path(), Some(Path::new("/usr/lib/libbar.so.1")));
+            assert_eq!(bar.found_by, Some(SearchRule::DefaultDir));
+            assert_eq!(node("libmissing.so.1").path(), None);
+        }
+
+        // A truncated stream is not a valid archive
+        let truncated = dir.path().join("truncated.tar.zst");
+        fs::write(&truncated, b"\x28\xb5\x2f\xfd\0\0\0\0").unwrap();
+        let open = |path: &Path| RootfsArchive::open(path);
+        assert!(matches!(open(&truncated), Err(Error::InvalidRootfs { .. })));
+        assert!(matches!(
+            open(&dir.path().join("missing.tar")),
+            Err(Error::Io { .. })
+        ));
+        fs::write(dir.path().join("app"), &app).unwrap();
+        assert!(matches!(
+            open(&dir.path().join("app")),
+            Err(Error::InvalidRootfs { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gzip"), ignore = "needs the `gzip` feature")]
+    fn test_decompressed_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        // Zeros are an empty tar archive, of 4 MiB from 26 KiB of gzip
+        let path = dir.path().join("bomb.tar.gz");
+        fs::write(&path, gzip_zeros(4 << 20)).unwrap();
+        assert!(!RootfsArchive::open(&path).unwrap().exists("/bin"));
+
+        let config = ResolverConfig::new().max_decompressed_size(64 << 10);
+        let limit = ResourceLimit::DecompressedSize(64 << 10);
+        assert!(matches!(
+            RootfsArchive::open_with_config(&path, &config),
+            Err(Error::LimitExceeded { path: p, limit: l }) if p == path && l == limit
+        ));
+    }
+}
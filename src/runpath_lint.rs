@@ -0,0 +1,215 @@
+//! Checks of the `RPATH` and `RUNPATH` entries of objects for directories other users could
+//! plant libraries in.
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::os_str::OsStrExt;
+use crate::parse::{self, ParsedObject};
+use crate::{dst, FileKind, ResolverConfig};
+
+/// Directories every user can create files in, and which are often cleaned up.
+const TEMPORARY_DIRS: &[&str] = &["/tmp", "/var/tmp", "/dev/shm"];
+
+/// The dynamic array entry a run path comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RunpathTag {
+    Rpath,
+    Runpath,
+    /// Not an entry, but the `LD_LIBRARY_PATH` the analyzed file is run with, whose findings
+    /// name the analyzed file as their object.
+    LdLibraryPath,
+}
+
+/// What makes a run path entry dangerous.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RunpathIssue {
+    /// The entry is relative, and not to `$ORIGIN`, so it is relative to the working directory
+    /// of whoever runs the program.
+    Relative,
+    /// The entry is empty, as in `/opt/lib:` or `a::b`, which ld.so takes as the working
+    /// directory (unless, for `LD_LIBRARY_PATH`,
+    /// [`ResolverConfig::ld_library_path_empty_as_cwd`] is `false`).
+    Empty,
+    /// Every user can write to the directory or, if it doesn't exist, to the directory it
+    /// would be created in.
+    WorldWritable,
+    /// The entry is below a temporary directory like `/tmp`, usually left over from a build.
+    Temporary,
+}
+
+/// A dangerous `RPATH` or `RUNPATH` entry of an object, as reported by
+/// [`ElfFile::insecure_runpaths`](crate::ElfFile::insecure_runpaths).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunpathFinding {
+    /// The object whose entry this is.
+    pub object: PathBuf,
+    pub tag: RunpathTag,
+    /// The entry as written, before `$ORIGIN` and the other tokens are expanded, with bytes
+    /// that are not UTF-8 replaced with `U+FFFD`.
+    pub entry: String,
+    pub issue: RunpathIssue,
+}
+
+/// Checks the run paths of `object`, read from the file at `path`.
+pub(crate) fn lint(
+    path: &Path,
+    object: &ParsedObject,
+    config: &ResolverConfig,
+) -> Vec<RunpathFinding> {
+    let mut findings = Vec::new();
+    let tags = [
+        (RunpathTag::Rpath, &object.rpath),
+        (RunpathTag::Runpath, &object.runpath),
+    ];
+    for (tag, paths) in tags {
+        if let Some(paths) = paths {
+            lint_entries(path, object, config, tag, paths, &mut findings);
+        }
+    }
+    findings
+}
+
+/// Checks the `LD_LIBRARY_PATH` the executable `object`, read from the file at `path`, would
+/// be run with. An `LD_LIBRARY_PATH` that is empty altogether is ignored by ld.so, so it is
+/// not reported.
+pub(crate) fn lint_ld_library_path(
+    path: &Path,
+    object: &ParsedObject,
+    config: &ResolverConfig,
+) -> Vec<RunpathFinding> {
+    let mut findings = Vec::new();
+    if let Some(paths) = config
+        .env_var("LD_LIBRARY_PATH")
+        .filter(|paths| !paths.is_empty())
+    {
+        let tag = RunpathTag::LdLibraryPath;
+        lint_entries(path, object, config, tag, &paths, &mut findings);
+    }
+    findings
+}
+
+/// Adds the findings about the entries of the colon-separated list `paths` to `findings`.
+fn lint_entries(
+    path: &Path,
+    object: &ParsedObject,
+    config: &ResolverConfig,
+    tag: RunpathTag,
+    paths: &OsStr,
+    findings: &mut Vec<RunpathFinding>,
+) {
+    let origin = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    let (lib, platform) = dst::lib_and_platform(object.arch, config);
+    let token_values = dst::TokenValues {
+        origin: origin.parent().unwrap_or(Path::new("/")),
+        lib: &lib,
+        platform,
+    };
+    for entry in parse::split(paths, b':') {
+        let mut finding = |issue| {
+            findings.push(RunpathFinding {
+                object: path.to_owned(),
+                tag,
+                entry: entry.to_string_lossy().into_owned(),
+                issue,
+            })
+        };
+        if entry.is_empty() {
+            finding(RunpathIssue::Empty);
+            continue;
+        }
+        let expanded = PathBuf::from(dst::expand(entry, &token_values));
+        if !expanded.is_absolute() {
+            finding(RunpathIssue::Relative);
+            continue;
+        }
+        // $ORIGIN already points into the sysroot, but absolute entries don't. LD_LIBRARY_PATH
+        // is searched on the host even with a sysroot
+        let is_absolute = entry.as_bytes().starts_with(b"/");
+        if is_absolute && TEMPORARY_DIRS.iter().any(|dir| expanded.starts_with(dir)) {
+            finding(RunpathIssue::Temporary);
+            continue;
+        }
+        let dir = if is_absolute && tag != RunpathTag::LdLibraryPath {
+            config.rebase(&expanded)
+        } else {
+            expanded
+        };
+        if is_world_writable(config, &dir) {
+            finding(RunpathIssue::WorldWritable);
+        }
+    }
+}
+
+/// Whether every user can write to `dir` or, if it doesn't exist, to the nearest directory
+/// above it that does, where they could create it.
+fn is_world_writable(config: &ResolverConfig, dir: &Path) -> bool {
+    // Nothing above the sysroot is created by whoever runs the program
+    let inside = |ancestor: &&Path| {
+        config
+            .sysroot
+            .as_ref()
+            .is_none_or(|root| ancestor.starts_with(root))
+    };
+    dir.ancestors()
+        .take_while(inside)
+        .filter(|ancestor| ancestor.as_os_str() != OsStr::new(""))
+        .find_map(|ancestor| config.fs().metadata(&config.canonical(ancestor)).ok())
+        .is_some_and(|metadata| metadata.kind == FileKind::Dir && metadata.mode & 0o002 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, RunpathIssue, RunpathTag};
+    use crate::parse::parse_bytes;
+    use crate::test_util::ElfBuilder;
+    use crate::ResolverConfig;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    #[test]
+    fn test_lint() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::create_dir(dir.path().join("bin")).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o777)).unwrap();
+        let rpath = "lib::/tmp/build/lib:$ORIGIN/../lib:$ORIGIN/../shared/missing";
+        let data = ElfBuilder::new().rpath(rpath).runpath("/usr/lib:").build();
+        let path = dir.path().join("bin/app");
+        let object = parse_bytes(&path, &data).unwrap();
+
+        let findings: Vec<_> = lint(&path, &object, &ResolverConfig::new())
+            .into_iter()
+            .map(|finding| (finding.tag, finding.entry, finding.issue))
+            .collect();
+        let rpath = |entry: &str, issue| (RunpathTag::Rpath, entry.to_owned(), issue);
+        assert_eq!(
+            findings,
+            [
+                rpath("lib", RunpathIssue::Relative),
+                rpath("", RunpathIssue::Empty),
+                rpath("/tmp/build/lib", RunpathIssue::Temporary),
+                rpath("$ORIGIN/../shared/missing", RunpathIssue::WorldWritable),
+                (RunpathTag::Runpath, String::new(), RunpathIssue::Empty),
+            ]
+        );
+
+        // Inside a sysroot, absolute entries are looked up below it
+        let sysroot = dir.path().join("root");
+        fs::create_dir_all(sysroot.join("opt/plugins")).unwrap();
+        let plugins = sysroot.join("opt/plugins");
+        fs::set_permissions(&plugins, fs::Permissions::from_mode(0o777)).unwrap();
+        let data = ElfBuilder::new().runpath("/opt/plugins").build();
+        let path = sysroot.join("usr/bin/app");
+        let object = parse_bytes(&path, &data).unwrap();
+        let findings = lint(&path, &object, &ResolverConfig::new().sysroot(&sysroot));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].issue, RunpathIssue::WorldWritable);
+        assert_eq!(findings[0].object, path);
+        assert!(lint(Path::new("/usr/bin/app"), &object, &ResolverConfig::new()).is_empty());
+    }
+}
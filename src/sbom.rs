@@ -0,0 +1,300 @@
+//! Software bills of materials listing the libraries of a dependency closure, in the SPDX and
+//! CycloneDX JSON formats.
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use crate::digest::sha256_hex;
+use crate::{DependencyGraph, NodeId};
+
+/// The name and version this crate signs the documents it creates with.
+const TOOL_NAME: &str = env!("CARGO_PKG_NAME");
+const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The document format of [`ElfFile::to_sbom`](crate::ElfFile::to_sbom).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// An SPDX 2.3 JSON document: one package per file, with `DEPENDS_ON` relationships.
+    Spdx,
+    /// A CycloneDX 1.5 JSON BOM: one component per library, with its dependencies.
+    CycloneDx,
+}
+
+/// Renders the files of `graph` as an SBOM, as created at `created`. The digest of each node
+/// must be its SHA-256 digest, if it has one. Unresolved and virtual objects, which are not
+/// files, are left out.
+pub(crate) fn to_sbom(graph: &DependencyGraph, format: SbomFormat, created: SystemTime) -> String {
+    let files: Vec<(NodeId, &Path, Option<&str>)> = graph
+        .load_order()
+        .into_iter()
+        .filter_map(|id| {
+            let node = graph.node(id);
+            Some((id, node.path()?, node.digest.as_deref()))
+        })
+        .collect();
+    // Each library is named after the soname it was loaded as, and the analyzed file after its
+    // file name
+    let name = |id: NodeId| graph.node(id).soname.as_str();
+    let is_file = |id: &NodeId| graph.node(*id).path().is_some();
+    let document = match format {
+        SbomFormat::Spdx => {
+            let spdx_id = |id: NodeId| format!("SPDXRef-Package-{}", id.index());
+            let packages: Vec<Value> = files
+                .iter()
+                .map(|(id, path, hash)| {
+                    let purpose = if *id == graph.root() {
+                        "APPLICATION"
+                    } else {
+                        "LIBRARY"
+                    };
+                    let mut package = json!({
+                        "name": name(*id),
+                        "SPDXID": spdx_id(*id),
+                        "packageFileName": path.to_string_lossy(),
+                        "downloadLocation": "NOASSERTION",
+                        "filesAnalyzed": false,
+                        "primaryPackagePurpose": purpose,
+                    });
+                    if let Some(hash) = hash {
+                        package["checksums"] = json!([{
+                            "algorithm": "SHA256",
+                            "checksumValue": hash,
+                        }]);
+                    }
+                    package
+                })
+                .collect();
+            let mut relationships = vec![json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": spdx_id(graph.root()),
+            })];
+            relationships.extend(
+                graph
+                    .edges()
+                    .filter(|(from, to)| is_file(from) && is_file(to))
+                    .map(|(from, to)| {
+                        json!({
+                            "spdxElementId": spdx_id(from),
+                            "relationshipType": "DEPENDS_ON",
+                            "relatedSpdxElement": spdx_id(to),
+                        })
+                    }),
+            );
+            // The namespace must be unique to this document, so it covers the time and contents
+            let mut identity = timestamp(created);
+            for (_, path, hash) in &files {
+                identity.push_str(&path.to_string_lossy());
+                identity.push_str(hash.unwrap_or_default());
+            }
+            json!({
+                "spdxVersion": "SPDX-2.3",
+                "dataLicense": "CC0-1.0",
+                "SPDXID": "SPDXRef-DOCUMENT",
+                "name": name(graph.root()),
+                "documentNamespace": format!(
+                    "https://spdx.org/spdxdocs/{}-{}",
+                    name(graph.root()).replace(|c: char| !c.is_ascii_alphanumeric(), "-"),
+                    sha256_hex(identity.as_bytes()),
+                ),
+                "creationInfo": {
+                    "created": timestamp(created),
+                    "creators": [format!("Tool: {}-{}", TOOL_NAME, TOOL_VERSION)],
+                },
+                "packages": packages,
+                "relationships": relationships,
+            })
+        }
+        SbomFormat::CycloneDx => {
+            let bom_ref = |id: NodeId| format!("object-{}", id.index());
+            let mut components = files.iter().map(|(id, path, hash)| {
+                let kind = if *id == graph.root() {
+                    "application"
+                } else {
+                    "library"
+                };
+                let mut component = json!({
+                    "type": kind,
+                    "bom-ref": bom_ref(*id),
+                    "name": name(*id),
+                    "evidence": {"occurrences": [{"location": path.to_string_lossy()}]},
+                });
+                if let Some(hash) = hash {
+                    component["hashes"] = json!([{"alg": "SHA-256", "content": hash}]);
+                }
+                component
+            });
+            let root = components.next();
+            let components: Vec<Value> = components.collect();
+            let dependencies: Vec<Value> = files
+                .iter()
+                .map(|(id, ..)| {
+                    let depends_on: Vec<String> = graph
+                        .dependencies(*id)
+                        .iter()
+                        .filter(|&dependency| is_file(dependency))
+                        .map(|&dependency| bom_ref(dependency))
+                        .collect();
+                    json!({"ref": bom_ref(*id), "dependsOn": depends_on})
+                })
+                .collect();
+            let mut metadata = json!({
+                "timestamp": timestamp(created),
+                "tools": {"components": [{
+                    "type": "application",
+                    "name": TOOL_NAME,
+                    "version": TOOL_VERSION,
+                }]},
+            });
+            if let Some(root) = root {
+                metadata["component"] = root;
+            }
+            json!({
+                "bomFormat": "CycloneDX",
+                "specVersion": "1.5",
+                "version": 1,
+                "metadata": metadata,
+                "components": components,
+                "dependencies": dependencies,
+            })
+        }
+    };
+    let mut sbom = serde_json::to_string_pretty(&document).unwrap_or_default();
+    sbom.push('\n');
+    sbom
+}
+
+/// Formats `time` in UTC as `YYYY-MM-DDThh:mm:ssZ`, as both formats expect.
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // Converts days since 1970-01-01 to a proleptic Gregorian date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{timestamp, to_sbom, SbomFormat};
+    use crate::{DependencyGraph, DependencyKind, DependencyNode, ResolveError, Resolved};
+    use serde_json::Value;
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// An app needing libfoo, which also needs libbar, a missing library and the vDSO.
+    fn test_graph() -> DependencyGraph {
+        let path = |path: &str| Ok(Resolved::Found(PathBuf::from(path)));
+        let digest = Some("00".repeat(32));
+        let mut app = DependencyNode::new("app", path("/opt/app"));
+        app.digest = digest.clone();
+        let mut graph = DependencyGraph::new(app);
+        let root = graph.root();
+        let mut foo = DependencyNode::new("libfoo.so.1", path("/lib/libfoo.so.1"));
+        foo.digest = digest;
+        let foo = graph.add_node(foo);
+        // libbar could not be read, so it has no digest
+        let bar = graph.add_node(DependencyNode::new("libbar.so.1", path("/lib/libbar.so.1")));
+        let not_found = ResolveError::NotFound {
+            needed_by: PathBuf::from("/opt/app"),
+        };
+        let missing = graph.add_node(DependencyNode::new("libmissing.so.1", Err(not_found)));
+        let mut vdso = DependencyNode::new("linux-vdso.so.1", Ok(Resolved::Virtual));
+        vdso.kind = DependencyKind::Virtual;
+        let vdso = graph.add_node(vdso);
+        graph.add_edge(root, vdso);
+        graph.add_edge(root, foo);
+        graph.add_edge(foo, bar);
+        graph.add_edge(root, missing);
+        graph
+    }
+
+    fn render(format: SbomFormat) -> Value {
+        let created = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        serde_json::from_str(&to_sbom(&test_graph(), format, created)).unwrap()
+    }
+
+    #[test]
+    fn test_spdx() {
+        let spdx = render(SbomFormat::Spdx);
+        assert_eq!(spdx["spdxVersion"], "SPDX-2.3");
+        assert_eq!(spdx["creationInfo"]["created"], "2023-11-14T22:13:20Z");
+        let packages = spdx["packages"].as_array().unwrap();
+        let names: Vec<_> = packages.iter().map(|package| &package["name"]).collect();
+        assert_eq!(names, ["app", "libfoo.so.1", "libbar.so.1"]);
+        assert_eq!(packages[1]["packageFileName"], "/lib/libfoo.so.1");
+        assert_eq!(packages[1]["checksums"][0]["algorithm"], "SHA256");
+        assert!(packages[2].get("checksums").is_none());
+        let relationships: Vec<_> = spdx["relationships"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|relationship| {
+                let field = |name: &str| relationship[name].as_str().unwrap().to_owned();
+                (
+                    field("spdxElementId"),
+                    field("relationshipType"),
+                    field("relatedSpdxElement"),
+                )
+            })
+            .collect();
+        let relationship =
+            |from: &str, kind: &str, to: &str| (from.to_owned(), kind.to_owned(), to.to_owned());
+        assert_eq!(
+            relationships,
+            [
+                relationship("SPDXRef-DOCUMENT", "DESCRIBES", "SPDXRef-Package-0"),
+                relationship("SPDXRef-Package-0", "DEPENDS_ON", "SPDXRef-Package-1"),
+                relationship("SPDXRef-Package-1", "DEPENDS_ON", "SPDXRef-Package-2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cyclonedx() {
+        let bom = render(SbomFormat::CycloneDx);
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["metadata"]["component"]["name"], "app");
+        let components = bom["components"].as_array().unwrap();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0]["name"], "libfoo.so.1");
+        assert_eq!(components[0]["type"], "library");
+        let location = &components[0]["evidence"]["occurrences"][0]["location"];
+        assert_eq!(location, "/lib/libfoo.so.1");
+        assert_eq!(components[0]["hashes"][0]["alg"], "SHA-256");
+        assert_eq!(bom["dependencies"][0]["ref"], "object-0");
+        assert_eq!(
+            bom["dependencies"][0]["dependsOn"],
+            serde_json::json!(["object-1"])
+        );
+        assert_eq!(bom["dependencies"][2]["dependsOn"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400 + 3661);
+        assert_eq!(timestamp(leap_day), "2000-02-29T01:01:01Z");
+    }
+}
@@ -0,0 +1,287 @@
+//! Step-by-step records of library searches, to compare with what `LD_DEBUG=libs` shows the
+//! dynamic linker doing.
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::parse::{Arch, OsAbi};
+use crate::{ElfClass, Endianness, Machine, OsAbiKind, SearchRule};
+
+/// What was at a path probed while searching for a library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProbeResult {
+    /// There is no file at the path.
+    NotFound,
+    /// The file is not an ELF file.
+    NotElf,
+    /// The file is 32-bit and the executable 64-bit, or the other way round.
+    WrongClass,
+    /// The file has the other byte order than the executable.
+    WrongByteOrder,
+    /// The file is built for another machine than the executable, like `aarch64` for `x86_64`.
+    WrongMachine,
+    /// The file is built for an operating system ABI the executable's doesn't load, which is
+    /// only checked with [`OsAbiCheck::Reject`](crate::OsAbiCheck::Reject).
+    WrongOsAbi,
+    /// The file can be loaded, so the search stops there.
+    Found,
+}
+
+impl ProbeResult {
+    /// What a search for a library to load into an executable of `arch` and `os_abi` makes of
+    /// a file with the architecture and OS ABI `header`, `None` if it is not an ELF file. Any
+    /// architecture or OS ABI matches if `None`.
+    pub(crate) fn of(
+        header: Option<(Arch, OsAbi)>,
+        arch: Option<Arch>,
+        os_abi: Option<OsAbi>,
+    ) -> Self {
+        let Some((lib_arch, lib_os_abi)) = header else {
+            return ProbeResult::NotElf;
+        };
+        match arch {
+            Some(arch) if lib_arch.is_64_bit != arch.is_64_bit => ProbeResult::WrongClass,
+            Some(arch) if lib_arch.big_endian != arch.big_endian => ProbeResult::WrongByteOrder,
+            Some(arch) if lib_arch.machine != arch.machine => ProbeResult::WrongMachine,
+            _ if os_abi.is_some_and(|os_abi| !lib_os_abi.loads_into(os_abi)) => {
+                ProbeResult::WrongOsAbi
+            }
+            _ => ProbeResult::Found,
+        }
+    }
+}
+
+impl fmt::Display for ProbeResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProbeResult::NotFound => "not found",
+            ProbeResult::NotElf => "not an ELF file",
+            ProbeResult::WrongClass => "wrong class",
+            ProbeResult::WrongByteOrder => "wrong byte order",
+            ProbeResult::WrongMachine => "wrong machine",
+            ProbeResult::WrongOsAbi => "wrong OS ABI",
+            ProbeResult::Found => "found",
+        })
+    }
+}
+
+/// One path probed while searching for a library, recorded with
+/// [`ResolverConfig::trace_search`](crate::ResolverConfig::trace_search).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchStep {
+    /// The path probed.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_path"))]
+    pub path: PathBuf,
+    /// Which rule led to the path.
+    pub found_by: SearchRule,
+    pub result: ProbeResult,
+}
+
+/// A place searched for the dependencies of an object, recorded with
+/// [`ResolverConfig::record_search_paths`](crate::ResolverConfig::record_search_paths).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchPathEntry {
+    /// The directory, or for [`SearchRule::Cache`] the `ld.so.cache` file.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_path"))]
+    pub path: PathBuf,
+    /// Which rule the directory is searched by.
+    pub found_by: SearchRule,
+}
+
+/// A path passed over while searching for a library that was not found, as listed in
+/// [`DependencyNode::rejections`](crate::DependencyNode::rejections). Displayed like
+/// `exists at /usr/lib32/libfoo.so.1 but is 32-bit`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rejection {
+    /// The file, or for [`RejectionReason::MissingDirectory`] the directory.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_path"))]
+    pub path: PathBuf,
+    /// Which rule led to the path.
+    pub found_by: SearchRule,
+    pub reason: RejectionReason,
+}
+
+/// Why a [`Rejection`] was passed over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RejectionReason {
+    /// The directory, from an `RPATH`, `RUNPATH` or `LD_LIBRARY_PATH` entry or a custom search
+    /// directory, doesn't exist. Missing system directories are not reported.
+    MissingDirectory,
+    /// The file exists but could not be read, with the error, like `Permission denied (os
+    /// error 13)`.
+    Unreadable { error: String },
+    /// The file exists but is not an ELF file.
+    NotElf,
+    /// The file is an ELF file the executable can't load, as `result` says, with what its
+    /// header says it is.
+    Incompatible {
+        result: ProbeResult,
+        class: ElfClass,
+        endianness: Endianness,
+        machine: Machine,
+        os_abi: OsAbiKind,
+    },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display();
+        let (result, class, endianness, machine, os_abi) = match &self.reason {
+            RejectionReason::MissingDirectory => {
+                let from = match &self.found_by {
+                    SearchRule::Rpath { owner } => format!("the RPATH of {}", owner.display()),
+                    SearchRule::Runpath { owner } => format!("the RUNPATH of {}", owner.display()),
+                    SearchRule::LdLibraryPath => "LD_LIBRARY_PATH".to_owned(),
+                    _ => "the custom search path".to_owned(),
+                };
+                return write!(f, "directory {path} in {from} doesn't exist");
+            }
+            RejectionReason::Unreadable { error } => {
+                return write!(f, "exists at {path} but is unreadable ({error})");
+            }
+            RejectionReason::NotElf => return write!(f, "exists at {path} but is not an ELF file"),
+            RejectionReason::Incompatible {
+                result,
+                class,
+                endianness,
+                machine,
+                os_abi,
+            } => (result, class, endianness, machine, os_abi),
+        };
+        write!(f, "exists at {path} but ")?;
+        match result {
+            ProbeResult::WrongClass => match class {
+                ElfClass::Elf32 => write!(f, "is 32-bit"),
+                ElfClass::Elf64 => write!(f, "is 64-bit"),
+            },
+            ProbeResult::WrongByteOrder => match endianness {
+                Endianness::Little => write!(f, "is little-endian"),
+                Endianness::Big => write!(f, "is big-endian"),
+            },
+            ProbeResult::WrongMachine => write!(f, "is for {machine}"),
+            ProbeResult::WrongOsAbi => write!(f, "is for {os_abi}"),
+            result => write!(f, "was passed over ({result})"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProbeResult, Rejection, RejectionReason};
+    use crate::abi::{ELFOSABI_FREEBSD, ELFOSABI_NONE, EM_AARCH64, EM_X86_64};
+    use crate::parse::{Arch, OsAbi};
+    use crate::test_util::ElfBuilder;
+    use crate::{
+        ElfClass, ElfFile, Endianness, Machine, OsAbiKind, ResolverConfig, SearchRule,
+        TraversalOrder,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_probe_result() {
+        let x86_64 = Arch {
+            is_64_bit: true,
+            big_endian: false,
+            machine: EM_X86_64,
+        };
+        let x32 = Arch {
+            is_64_bit: false,
+            ..x86_64
+        };
+        let big_endian = Arch {
+            big_endian: true,
+            ..x86_64
+        };
+        let aarch64 = Arch {
+            machine: EM_AARCH64,
+            ..x86_64
+        };
+        let linux = OsAbi {
+            os_abi: ELFOSABI_NONE,
+            version: 0,
+        };
+        let freebsd = OsAbi {
+            os_abi: ELFOSABI_FREEBSD,
+            version: 0,
+        };
+        let of = |header| ProbeResult::of(Some(header), Some(x86_64), Some(linux));
+        assert_eq!(of((x86_64, linux)), ProbeResult::Found);
+        assert_eq!(of((x32, linux)), ProbeResult::WrongClass);
+        assert_eq!(of((big_endian, linux)), ProbeResult::WrongByteOrder);
+        assert_eq!(of((aarch64, linux)), ProbeResult::WrongMachine);
+        assert_eq!(of((x86_64, freebsd)), ProbeResult::WrongOsAbi);
+        let not_elf = ProbeResult::of(None, Some(x86_64), None);
+        assert_eq!(not_elf, ProbeResult::NotElf);
+        let any = ProbeResult::of(Some((aarch64, freebsd)), None, None);
+        assert_eq!(any, ProbeResult::Found);
+    }
+
+    #[test]
+    fn test_rejections() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib32 = dir.path().join("lib32");
+        let libfoo = ElfBuilder::new().elf32().write(&lib32, "libfoo.so.1");
+        let script = lib32.join("libbar.so.1");
+        fs::write(&script, "INPUT(libbar.so.1.0)\n").unwrap();
+        let missing = dir.path().join("missing");
+        let runpath = format!("{}:{}", missing.display(), lib32.display());
+        let app = ElfBuilder::new()
+            .runpath(&runpath)
+            .needed("libfoo.so.1")
+            .needed("libbar.so.1")
+            .write(dir.path(), "app");
+
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let graph = ElfFile::with_config(&app, config.clone())
+            .dependency_graph()
+            .unwrap();
+        let runpath = SearchRule::Runpath { owner: app.clone() };
+        let rejections = &graph.nodes()[1].rejections;
+        assert_eq!(
+            *rejections,
+            [
+                Rejection {
+                    path: missing.clone(),
+                    found_by: runpath.clone(),
+                    reason: RejectionReason::MissingDirectory,
+                },
+                Rejection {
+                    path: libfoo.clone(),
+                    found_by: runpath.clone(),
+                    reason: RejectionReason::Incompatible {
+                        result: ProbeResult::WrongClass,
+                        class: ElfClass::Elf32,
+                        endianness: Endianness::Little,
+                        machine: Machine::X86_64,
+                        os_abi: OsAbiKind::SystemV,
+                    },
+                },
+            ]
+        );
+        let messages: Vec<_> = rejections.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                format!(
+                    "directory {} in the RUNPATH of {} doesn't exist",
+                    missing.display(),
+                    app.display()
+                ),
+                format!("exists at {} but is 32-bit", libfoo.display()),
+            ]
+        );
+        let rejection = graph.nodes()[2].rejections[1].to_string();
+        let not_elf = format!("exists at {} but is not an ELF file", script.display());
+        assert_eq!(rejection, not_elf);
+
+        // The same when iterating
+        let elf = ElfFile::with_config(&app, config);
+        let dependencies = elf.iter_dependencies(TraversalOrder::BreadthFirst).unwrap();
+        let counts: Vec<_> = dependencies.map(|dep| dep.rejections.len()).collect();
+        assert_eq!(counts, [2, 2]);
+    }
+}
@@ -0,0 +1,121 @@
+//! Serialization of paths, for the `serde` feature, that keeps every path intact: paths that
+//! are UTF-8 as strings, and others as their bytes, which `serde`'s own implementation
+//! refuses. Used with `#[serde(with = "crate::serde_path")]`, or its `option` and `vec`
+//! modules for optional paths and lists of them.
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::os_str::{OsStrExt, OsStringExt};
+
+/// Serializes `path` as a string if it is UTF-8, and as its bytes otherwise.
+pub(crate) fn serialize<P, S>(path: &P, serializer: S) -> Result<S::Ok, S::Error>
+where
+    P: AsRef<Path> + ?Sized,
+    S: Serializer,
+{
+    let path = path.as_ref();
+    match path.to_str() {
+        Some(path) => serializer.serialize_str(path),
+        None => serializer.serialize_bytes(path.as_os_str().as_bytes()),
+    }
+}
+
+/// Deserializes a path serialized by [`serialize`], from a string or from bytes.
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    deserializer.deserialize_any(PathVisitor)
+}
+
+struct PathVisitor;
+
+impl<'de> Visitor<'de> for PathVisitor {
+    type Value = PathBuf;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a path, as a string or as bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, path: &str) -> Result<PathBuf, E> {
+        Ok(PathBuf::from(path))
+    }
+
+    fn visit_bytes<E: de::Error>(self, path: &[u8]) -> Result<PathBuf, E> {
+        Ok(PathBuf::from(OsString::from_vec(path.to_vec())))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<PathBuf, A::Error> {
+        // Formats without bytes of their own, like JSON, write them as a list of numbers
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(PathBuf::from(OsString::from_vec(bytes)))
+    }
+}
+
+/// A path serialized with [`serialize`], as an element of an option or a list.
+struct Borrowed<'a>(&'a Path);
+
+impl Serialize for Borrowed<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(self.0, serializer)
+    }
+}
+
+/// A path deserialized with [`deserialize`], as an element of an option or a list.
+struct Owned(PathBuf);
+
+impl<'de> Deserialize<'de> for Owned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(Owned)
+    }
+}
+
+/// Like the parent module, for `Option<PathBuf>`.
+pub(crate) mod option {
+    use std::path::PathBuf;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Borrowed, Owned};
+
+    pub(crate) fn serialize<S: Serializer>(
+        path: &Option<PathBuf>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        path.as_deref().map(Borrowed).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<PathBuf>, D::Error> {
+        let path = Option::<Owned>::deserialize(deserializer)?;
+        Ok(path.map(|Owned(path)| path))
+    }
+}
+
+/// Like the parent module, for `Vec<PathBuf>`.
+pub(crate) mod vec {
+    use std::path::PathBuf;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{Borrowed, Owned};
+
+    pub(crate) fn serialize<S: Serializer>(
+        paths: &[PathBuf],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| Borrowed(path)))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<PathBuf>, D::Error> {
+        let paths = Vec::<Owned>::deserialize(deserializer)?;
+        Ok(paths.into_iter().map(|Owned(path)| path).collect())
+    }
+}
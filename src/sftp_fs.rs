@@ -0,0 +1,901 @@
+//! Files read over SFTP from a remote host, so libraries are found as they would be there
+//! without copying its root file system, with the `ssh` client carrying the protocol.
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::filesystem::{slice_at, FileKind, FileMetadata, FileStat, FileSystem};
+use crate::os_str::{OsStrExt, OsStringExt};
+
+/// The version of the protocol spoken, the one OpenSSH's `sftp-server` speaks.
+const VERSION: u32 = 3;
+
+const FXP_INIT: u8 = 1;
+const FXP_VERSION: u8 = 2;
+const FXP_OPEN: u8 = 3;
+const FXP_CLOSE: u8 = 4;
+const FXP_READ: u8 = 5;
+const FXP_LSTAT: u8 = 7;
+const FXP_OPENDIR: u8 = 11;
+const FXP_READDIR: u8 = 12;
+const FXP_STAT: u8 = 17;
+const FXP_READLINK: u8 = 19;
+const FXP_STATUS: u8 = 101;
+const FXP_HANDLE: u8 = 102;
+const FXP_DATA: u8 = 103;
+const FXP_NAME: u8 = 104;
+const FXP_ATTRS: u8 = 105;
+
+const FX_OK: u32 = 0;
+const FX_EOF: u32 = 1;
+const FX_NO_SUCH_FILE: u32 = 2;
+const FX_PERMISSION_DENIED: u32 = 3;
+const FX_OP_UNSUPPORTED: u32 = 8;
+
+const ATTR_SIZE: u32 = 0x1;
+const ATTR_UIDGID: u32 = 0x2;
+const ATTR_PERMISSIONS: u32 = 0x4;
+const ATTR_ACMODTIME: u32 = 0x8;
+const ATTR_EXTENDED: u32 = 0x8000_0000;
+
+const FXF_READ: u32 = 0x1;
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// The most bytes asked for by one read, which servers answer in full.
+const CHUNK: u32 = 32 * 1024;
+/// The most reads of a file sent before their replies arrive, so that reading a library
+/// doesn't take a round trip for every chunk of it.
+const READS_IN_FLIGHT: usize = 16;
+/// The largest packet accepted, as large as OpenSSH sends, so a corrupt length isn't taken for
+/// a huge allocation.
+const MAX_PACKET: u32 = 256 * 1024;
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid SFTP packet")
+}
+
+/// The root file system of a remote host, read over SFTP, set with
+/// [`ResolverConfig::filesystem`](crate::ResolverConfig::filesystem). Paths are those of the
+/// host, whose server follows symlinks, so the closure of a binary is the one it has there.
+/// Every file resolution reads is fetched when it is read, its `ld.so.cache` and
+/// `ld.so.conf` included, and nothing else is: of the files come across while searching, only
+/// the headers are, unless they are loaded. What is fetched is kept, so no part of a file is
+/// fetched twice, and changes on the host after it was are not seen.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use elf_dynamic_lib_getter::{ElfFile, ResolverConfig, SftpFs};
+///
+/// let fs = SftpFs::connect_via("bastion.example.com", "deploy@build-7.internal").unwrap();
+/// let config = ResolverConfig::new().use_env(false).filesystem(Arc::new(fs));
+/// let libs = ElfFile::with_config("/usr/bin/app", config).get_libs_full_paths();
+/// ```
+#[derive(Debug)]
+pub struct SftpFs {
+    session: Mutex<Session>,
+    /// The start of each file read so far, by the path it was read at.
+    fetched: Mutex<HashMap<PathBuf, Fetched>>,
+}
+
+/// The start of a file, as far as it was fetched.
+#[derive(Debug, Default)]
+struct Fetched {
+    data: Vec<u8>,
+    /// Whether `data` is the whole file.
+    complete: bool,
+}
+
+/// A connection to an SFTP server, which requests go through one at a time.
+struct Session {
+    reader: Box<dyn Read + Send>,
+    /// Taken when the session is dropped, which ends it.
+    writer: Option<Box<dyn Write + Send>>,
+    /// The process carrying the connection, if the session started it.
+    child: Option<Child>,
+    next_id: u32,
+}
+
+/// A packet to send, with room for its length at the front.
+struct Packet(Vec<u8>);
+
+/// A packet received, read from its start.
+struct Reply {
+    kind: u8,
+    /// The id of the request it answers, or the version of the server.
+    id: u32,
+    data: Vec<u8>,
+    pos: usize,
+}
+
+/// The attributes of a file, as far as the server sent them.
+#[derive(Debug, Default)]
+struct Attrs {
+    size: Option<u64>,
+    owner: Option<(u32, u32)>,
+    permissions: Option<u32>,
+    modified: Option<u32>,
+}
+
+impl SftpFs {
+    /// The root file system of `destination`, `[user@]host` or an `ssh://` URL, connecting
+    /// with `ssh` and its `sftp` subsystem. The port, keys and other options come from the
+    /// SSH configuration and agent as they do for `ssh` itself, which is run in batch mode so
+    /// that it fails rather than asks for a password.
+    pub fn connect(destination: &str) -> io::Result<Self> {
+        SftpFs::spawn(ssh_command(None, destination))
+    }
+
+    /// Like [`SftpFs::connect`], for a `destination` only reachable through the jump host
+    /// `jump`, as `ssh -J` takes it.
+    pub fn connect_via(jump: &str, destination: &str) -> io::Result<Self> {
+        SftpFs::spawn(ssh_command(Some(jump), destination))
+    }
+
+    /// Speaks SFTP with the standard input and output of `command`, like `ssh` with options of
+    /// its own, or `sftp-server` in a container. The command is left to exit when the file
+    /// system is dropped.
+    pub fn spawn(mut command: Command) -> io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let writer = child.stdin.take().expect("stdin is piped");
+        let reader = child.stdout.take().expect("stdout is piped");
+        let session = Session::start(Box::new(reader), Box::new(writer), Some(child))?;
+        Ok(SftpFs::with_session(session))
+    }
+
+    /// Speaks SFTP with a server through `reader` and `writer`, like the channel of an SSH
+    /// library's session with the `sftp` subsystem started on it.
+    pub fn over<R, W>(reader: R, writer: W) -> io::Result<Self>
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let session = Session::start(Box::new(reader), Box::new(writer), None)?;
+        Ok(SftpFs::with_session(session))
+    }
+
+    fn with_session(session: Session) -> Self {
+        SftpFs {
+            session: Mutex::new(session),
+            fetched: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn session(&self) -> std::sync::MutexGuard<'_, Session> {
+        self.session.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Up to `len` bytes of the file at `path` from `offset` on, or the rest of it without
+    /// `len`, fetching only what was not fetched before.
+    fn fetch(&self, path: &Path, offset: u64, len: Option<usize>) -> io::Result<Vec<u8>> {
+        let mut files = self.fetched.lock().unwrap_or_else(|e| e.into_inner());
+        let stop = len.map(|len| offset.saturating_add(len as u64));
+        let fetched = files.get(path);
+        let have = fetched.map_or(0, |fetched| fetched.data.len() as u64);
+        // A file is opened even for no bytes, to fail if it can't be
+        let needed = match fetched {
+            Some(fetched) => !fetched.complete && stop.is_none_or(|stop| stop > have),
+            None => true,
+        };
+        if needed {
+            // Only the start of a file is kept, so what lies before `offset` is fetched too
+            let rest = self.session().read(path, have, stop)?;
+            let fetched = files.entry(path.to_owned()).or_default();
+            fetched.complete = stop.is_none_or(|stop| have + (rest.len() as u64) < stop);
+            fetched.data.extend(rest);
+        }
+        let data = &files[path].data;
+        Ok(slice_at(data, offset, len.unwrap_or(usize::MAX)).to_vec())
+    }
+}
+
+/// The `ssh` command starting the `sftp` subsystem on `destination`, through `jump` if given.
+fn ssh_command(jump: Option<&str>, destination: &str) -> Command {
+    let mut command = Command::new("ssh");
+    command.args(["-o", "BatchMode=yes"]);
+    if let Some(jump) = jump {
+        command.args(["-J", jump]);
+    }
+    command.args(["-s", "--", destination, "sftp"]);
+    command
+}
+
+impl FileSystem for SftpFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.fetch(path, 0, None)
+    }
+
+    fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.fetch(path, offset, Some(len))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let attrs = self.session().attrs(FXP_LSTAT, path)?;
+        let permissions = attrs.permissions.unwrap_or_default();
+        let kind = match permissions & S_IFMT {
+            S_IFDIR => FileKind::Dir,
+            S_IFLNK => FileKind::Symlink,
+            _ => FileKind::File,
+        };
+        Ok(FileMetadata {
+            kind,
+            len: attrs.size.unwrap_or_default(),
+            mode: permissions & 0o7777,
+        })
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let mut session = self.session();
+        let mut reply = session.request(FXP_READLINK, |p| p.path(path))?;
+        let target = match reply.expect(FXP_NAME)?.names()?.pop() {
+            Some((name, _)) => name,
+            None => return Err(invalid()),
+        };
+        Ok(PathBuf::from(target))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        self.session().read_dir(path)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        let attrs = self.session().attrs(FXP_STAT, path)?;
+        Ok(FileStat {
+            len: attrs.size.unwrap_or_default(),
+            mode: attrs.permissions.unwrap_or_default() & 0o7777,
+            uid: attrs.owner.map(|(uid, _)| uid),
+            gid: attrs.owner.map(|(_, gid)| gid),
+            modified: attrs
+                .modified
+                .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs.into())),
+        })
+    }
+
+    /// Asks the server, which follows the symlinks itself.
+    fn exists(&self, path: &Path) -> bool {
+        self.session().attrs(FXP_STAT, path).is_ok()
+    }
+}
+
+impl Session {
+    /// Starts a session with the server at the other end of `reader` and `writer`.
+    fn start(
+        reader: Box<dyn Read + Send>,
+        writer: Box<dyn Write + Send>,
+        child: Option<Child>,
+    ) -> io::Result<Self> {
+        let mut session = Session {
+            reader,
+            writer: Some(writer),
+            child,
+            next_id: 0,
+        };
+        session.send(Packet::new(FXP_INIT).u32(VERSION))?;
+        let reply = session.receive()?;
+        if reply.kind != FXP_VERSION || reply.id < VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "the SFTP server speaks version {} of the protocol",
+                    reply.id
+                ),
+            ));
+        }
+        Ok(session)
+    }
+
+    fn send(&mut self, packet: Packet) -> io::Result<()> {
+        let mut data = packet.0;
+        let len = (data.len() - 4) as u32;
+        data[..4].copy_from_slice(&len.to_be_bytes());
+        let writer = self.writer.as_mut().expect("the session is open");
+        writer.write_all(&data)?;
+        writer.flush()
+    }
+
+    /// Sends a request of `kind`, whose fields `fields` adds after its id, returning the id.
+    fn send_request(&mut self, kind: u8, fields: impl FnOnce(Packet) -> Packet) -> io::Result<u32> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.send(fields(Packet::new(kind).u32(id)))?;
+        Ok(id)
+    }
+
+    fn receive(&mut self) -> io::Result<Reply> {
+        let mut len = [0; 4];
+        self.reader
+            .read_exact(&mut len)
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::UnexpectedEof => io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    "the SFTP server closed the connection",
+                ),
+                _ => error,
+            })?;
+        let len = u32::from_be_bytes(len);
+        if !(5..=MAX_PACKET).contains(&len) {
+            return Err(invalid());
+        }
+        let mut data = vec![0; len as usize];
+        self.reader.read_exact(&mut data)?;
+        let mut reply = Reply {
+            kind: data[0],
+            id: 0,
+            data,
+            pos: 1,
+        };
+        reply.id = reply.u32()?;
+        Ok(reply)
+    }
+
+    /// Like [`Session::send_request`], waiting for the reply.
+    fn request(&mut self, kind: u8, fields: impl FnOnce(Packet) -> Packet) -> io::Result<Reply> {
+        let id = self.send_request(kind, fields)?;
+        let reply = self.receive()?;
+        if reply.id != id {
+            return Err(invalid());
+        }
+        Ok(reply)
+    }
+
+    /// The attributes of `path`, with `kind` either `FXP_STAT` or `FXP_LSTAT`.
+    fn attrs(&mut self, kind: u8, path: &Path) -> io::Result<Attrs> {
+        self.request(kind, |p| p.path(path))?
+            .expect(FXP_ATTRS)?
+            .attrs()
+    }
+
+    /// Opens the file or directory at `path` with a request of `kind`, whose fields after
+    /// the path `fields` adds, returning its handle.
+    fn open(
+        &mut self,
+        kind: u8,
+        path: &Path,
+        fields: impl FnOnce(Packet) -> Packet,
+    ) -> io::Result<Vec<u8>> {
+        let mut reply = self.request(kind, |p| fields(p.path(path)))?;
+        Ok(reply.expect(FXP_HANDLE)?.bytes()?.to_vec())
+    }
+
+    fn close(&mut self, handle: &[u8]) -> io::Result<()> {
+        let mut reply = self.request(FXP_CLOSE, |p| p.bytes(handle))?;
+        if reply.kind != FXP_STATUS {
+            return Err(invalid());
+        }
+        reply.status()
+    }
+
+    /// The bytes of the file at `path` from `start` to `stop`, or to its end.
+    fn read(&mut self, path: &Path, start: u64, stop: Option<u64>) -> io::Result<Vec<u8>> {
+        // No attributes are set
+        let handle = self.open(FXP_OPEN, path, |p| p.u32(FXF_READ).u32(0))?;
+        let data = self.read_handle(&handle, start, stop);
+        let closed = self.close(&handle);
+        let data = data?;
+        closed?;
+        Ok(data)
+    }
+
+    /// Reads the open file `handle` from `start` to `stop`, or to its end, with several chunks
+    /// asked for at once.
+    fn read_handle(&mut self, handle: &[u8], start: u64, stop: Option<u64>) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut in_flight = HashMap::new();
+        // The rests of chunks the server sent less of than was asked for
+        let mut rests = Vec::new();
+        let (mut next, mut end) = (start, None);
+        let mut error = None;
+        loop {
+            while in_flight.len() < READS_IN_FLIGHT && error.is_none() {
+                let (offset, len) = match rests.pop() {
+                    Some(rest) => rest,
+                    None if end.is_none() && stop.is_none_or(|stop| next < stop) => {
+                        let left = stop.map_or(CHUNK.into(), |stop| stop - next);
+                        (next, left.min(CHUNK.into()) as u32)
+                    }
+                    None => break,
+                };
+                next = next.max(offset + u64::from(len));
+                let id = self.send_request(FXP_READ, |p| p.bytes(handle).u64(offset).u32(len))?;
+                in_flight.insert(id, (offset, len));
+            }
+            if in_flight.is_empty() {
+                break;
+            }
+            // Every reply must be read before the next request, even after an error
+            let mut reply = self.receive()?;
+            let (offset, len) = in_flight.remove(&reply.id).ok_or_else(invalid)?;
+            match reply.kind {
+                FXP_DATA => {
+                    let chunk = reply.bytes()?;
+                    if chunk.is_empty() || chunk.len() > len as usize {
+                        return Err(invalid());
+                    }
+                    let from = usize::try_from(offset - start).map_err(|_| invalid())?;
+                    let to = from + chunk.len();
+                    if data.len() < to {
+                        data.resize(to, 0);
+                    }
+                    data[from..to].copy_from_slice(chunk);
+                    if chunk.len() < len as usize {
+                        let read = chunk.len() as u32;
+                        rests.push((offset + u64::from(read), len - read));
+                    }
+                }
+                FXP_STATUS => match reply.u32()? {
+                    FX_EOF => end = Some(end.map_or(offset, |end: u64| end.min(offset))),
+                    code => error = error.or(Some(reply.status_error(code))),
+                },
+                _ => return Err(invalid()),
+            }
+        }
+        if let Some(error) = error {
+            return Err(error);
+        }
+        data.truncate(end.map_or(data.len(), |end| (end - start) as usize));
+        Ok(data)
+    }
+
+    fn read_dir(&mut self, path: &Path) -> io::Result<Vec<OsString>> {
+        let handle = self.open(FXP_OPENDIR, path, |p| p)?;
+        let entries = self.list_handle(&handle);
+        let closed = self.close(&handle);
+        let mut entries = entries?;
+        closed?;
+        entries.retain(|name| name != "." && name != "..");
+        Ok(entries)
+    }
+
+    /// The names of the entries in the open directory `handle`.
+    fn list_handle(&mut self, handle: &[u8]) -> io::Result<Vec<OsString>> {
+        let mut entries = Vec::new();
+        loop {
+            let mut reply = self.request(FXP_READDIR, |p| p.bytes(handle))?;
+            if reply.kind == FXP_STATUS {
+                match reply.u32()? {
+                    FX_EOF => return Ok(entries),
+                    code => return Err(reply.status_error(code)),
+                }
+            }
+            let names = reply.expect(FXP_NAME)?.names()?;
+            entries.extend(names.into_iter().map(|(name, _)| name));
+        }
+    }
+}
+
+impl Drop for Session {
+    /// Ends the session by closing its end of the connection, which a server started by it
+    /// exits on.
+    fn drop(&mut self) {
+        drop(self.writer.take());
+        if let Some(child) = &mut self.child {
+            let _ = child.wait();
+        }
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("child", &self.child)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Packet {
+    fn new(kind: u8) -> Self {
+        Packet(vec![0, 0, 0, 0, kind])
+    }
+
+    fn u32(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn u64(mut self, value: u64) -> Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn bytes(self, bytes: &[u8]) -> Self {
+        let mut packet = self.u32(bytes.len() as u32);
+        packet.0.extend_from_slice(bytes);
+        packet
+    }
+
+    fn path(self, path: &Path) -> Self {
+        self.bytes(path.as_os_str().as_bytes())
+    }
+}
+
+impl Reply {
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.data.get(self.pos..self.pos + 4).ok_or_else(invalid)?;
+        self.pos += 4;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let high = self.u32()?;
+        Ok(u64::from(high) << 32 | u64::from(self.u32()?))
+    }
+
+    fn bytes(&mut self) -> io::Result<&[u8]> {
+        let len = self.u32()? as usize;
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(invalid)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// This reply if it is of `kind`, or the error its status says.
+    fn expect(&mut self, kind: u8) -> io::Result<&mut Self> {
+        if self.kind == kind {
+            return Ok(self);
+        }
+        if self.kind == FXP_STATUS {
+            self.status()?;
+        }
+        Err(invalid())
+    }
+
+    /// The outcome a status reply says.
+    fn status(&mut self) -> io::Result<()> {
+        match self.u32()? {
+            FX_OK => Ok(()),
+            code => Err(self.status_error(code)),
+        }
+    }
+
+    /// The error of the status reply with `code`, whose message follows.
+    fn status_error(&mut self, code: u32) -> io::Error {
+        let message = match self.bytes() {
+            Ok(message) => String::from_utf8_lossy(message).into_owned(),
+            Err(error) => return error,
+        };
+        let kind = match code {
+            FX_NO_SUCH_FILE => io::ErrorKind::NotFound,
+            FX_PERMISSION_DENIED => io::ErrorKind::PermissionDenied,
+            FX_OP_UNSUPPORTED => io::ErrorKind::Unsupported,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, message)
+    }
+
+    /// The names and attributes an `FXP_NAME` reply lists.
+    fn names(&mut self) -> io::Result<Vec<(OsString, Attrs)>> {
+        let count = self.u32()?;
+        let mut names = Vec::new();
+        for _ in 0..count {
+            let name = OsString::from_vec(self.bytes()?.to_vec());
+            // The long name is meant for humans, like a line of `ls -l`
+            self.bytes()?;
+            names.push((name, self.attrs()?));
+        }
+        Ok(names)
+    }
+
+    fn attrs(&mut self) -> io::Result<Attrs> {
+        let flags = self.u32()?;
+        let mut attrs = Attrs::default();
+        if flags & ATTR_SIZE != 0 {
+            attrs.size = Some(self.u64()?);
+        }
+        if flags & ATTR_UIDGID != 0 {
+            attrs.owner = Some((self.u32()?, self.u32()?));
+        }
+        if flags & ATTR_PERMISSIONS != 0 {
+            attrs.permissions = Some(self.u32()?);
+        }
+        if flags & ATTR_ACMODTIME != 0 {
+            let _accessed = self.u32()?;
+            attrs.modified = Some(self.u32()?);
+        }
+        if flags & ATTR_EXTENDED != 0 {
+            for _ in 0..self.u32()? {
+                self.bytes()?;
+                self.bytes()?;
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ssh_command, Packet, Reply, SftpFs, FXP_ATTRS, FXP_CLOSE, FXP_DATA, FXP_HANDLE, FXP_INIT,
+        FXP_LSTAT, FXP_NAME, FXP_OPEN, FXP_OPENDIR, FXP_READ, FXP_READDIR, FXP_READLINK, FXP_STAT,
+        FXP_STATUS, FXP_VERSION, FX_EOF, FX_NO_SUCH_FILE, FX_OK,
+    };
+    use crate::os_str::OsStrExt;
+    use crate::test_util::ElfBuilder;
+    use crate::{ElfFile, FileKind, FileSystem, ResolverConfig};
+    use std::collections::HashMap;
+    use std::ffi::{OsStr, OsString};
+    use std::io::{self, Read, Write};
+    use std::os::unix::fs::{symlink, MetadataExt};
+    use std::os::unix::net::UnixStream;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    /// The bytes the test server sent of each file, by its path there.
+    type Served = Arc<Mutex<HashMap<PathBuf, usize>>>;
+
+    /// What a handle of the test server is open on.
+    enum Open {
+        /// A file, with its path on the server.
+        File(PathBuf, Vec<u8>),
+        /// The entries of a directory, until they were listed.
+        Dir(Option<Vec<OsString>>),
+    }
+
+    /// Serves the files under `root` as `/` on `stream`, like `sftp-server` chrooted there but
+    /// reading no more than 1000 bytes at once, until the client hangs up.
+    fn serve(root: &Path, served: &Served, mut stream: UnixStream) {
+        let mut handles = HashMap::new();
+        loop {
+            let mut len = [0; 4];
+            if stream.read_exact(&mut len).is_err() {
+                return;
+            }
+            let mut data = vec![0; u32::from_be_bytes(len) as usize];
+            stream.read_exact(&mut data).unwrap();
+            let mut request = Reply {
+                kind: data[0],
+                id: 0,
+                data,
+                pos: 1,
+            };
+            request.id = request.u32().unwrap();
+            let Packet(mut reply) = answer(root, served, &mut handles, &mut request);
+            let len = (reply.len() - 4) as u32;
+            reply[..4].copy_from_slice(&len.to_be_bytes());
+            stream.write_all(&reply).unwrap();
+        }
+    }
+
+    fn answer(
+        root: &Path,
+        served: &Served,
+        handles: &mut HashMap<Vec<u8>, Open>,
+        request: &mut Reply,
+    ) -> Packet {
+        let id = request.id;
+        let status = |code| {
+            Packet::new(FXP_STATUS)
+                .u32(id)
+                .u32(code)
+                .bytes(b"")
+                .bytes(b"")
+        };
+        let path = |request: &mut Reply| {
+            let path = Path::new(OsStr::from_bytes(request.bytes().unwrap())).to_owned();
+            root.join(path.strip_prefix("/").unwrap())
+        };
+        let attrs = |packet: Packet, metadata: std::fs::Metadata| {
+            packet
+                .u32(0xf)
+                .u64(metadata.len())
+                .u32(metadata.uid())
+                .u32(metadata.gid())
+                .u32(metadata.mode())
+                .u32(metadata.atime() as u32)
+                .u32(metadata.mtime() as u32)
+        };
+        let handle = (handles.len() as u32).to_be_bytes().to_vec();
+        let opened = match request.kind {
+            FXP_INIT => return Packet::new(FXP_VERSION).u32(3),
+            FXP_OPEN => {
+                let path = path(request);
+                let served_path = Path::new("/").join(path.strip_prefix(root).unwrap());
+                std::fs::read(path).map(|data| Open::File(served_path, data))
+            }
+            FXP_OPENDIR => std::fs::read_dir(path(request)).map(|entries| {
+                let entries = entries.map(|entry| entry.unwrap().file_name());
+                let dots = [OsString::from("."), OsString::from("..")];
+                Open::Dir(Some(entries.chain(dots).collect()))
+            }),
+            FXP_STAT | FXP_LSTAT => {
+                let path = path(request);
+                let metadata = match request.kind {
+                    FXP_STAT => std::fs::metadata(path),
+                    _ => std::fs::symlink_metadata(path),
+                };
+                return match metadata {
+                    Ok(metadata) => attrs(Packet::new(FXP_ATTRS).u32(id), metadata),
+                    Err(_) => status(FX_NO_SUCH_FILE),
+                };
+            }
+            FXP_READLINK => {
+                return match std::fs::read_link(path(request)) {
+                    Ok(target) => {
+                        let name = Packet::new(FXP_NAME).u32(id).u32(1);
+                        name.bytes(target.as_os_str().as_bytes()).bytes(b"").u32(0)
+                    }
+                    Err(_) => status(FX_NO_SUCH_FILE),
+                };
+            }
+            _ => {
+                let handle = request.bytes().unwrap().to_vec();
+                return match (request.kind, handles.get_mut(&handle)) {
+                    (FXP_READ, Some(Open::File(path, data))) => {
+                        let offset = request.u64().unwrap() as usize;
+                        let len = request.u32().unwrap().min(1000) as usize;
+                        match data.get(offset..data.len().min(offset + len)) {
+                            Some(chunk) if !chunk.is_empty() => {
+                                let mut served = served.lock().unwrap();
+                                *served.entry(path.clone()).or_default() += chunk.len();
+                                Packet::new(FXP_DATA).u32(id).bytes(chunk)
+                            }
+                            _ => status(FX_EOF),
+                        }
+                    }
+                    (FXP_READDIR, Some(Open::Dir(entries))) => match entries.take() {
+                        Some(entries) => {
+                            let mut name = Packet::new(FXP_NAME).u32(id).u32(entries.len() as u32);
+                            for entry in entries {
+                                name = name.bytes(entry.as_bytes()).bytes(b"").u32(0);
+                            }
+                            name
+                        }
+                        None => status(FX_EOF),
+                    },
+                    (FXP_CLOSE, Some(_)) => {
+                        handles.remove(&handle);
+                        status(FX_OK)
+                    }
+                    _ => status(FX_NO_SUCH_FILE),
+                };
+            }
+        };
+        match opened {
+            Ok(open) => {
+                handles.insert(handle.clone(), open);
+                Packet::new(FXP_HANDLE).u32(id).bytes(&handle)
+            }
+            Err(_) => status(FX_NO_SUCH_FILE),
+        }
+    }
+
+    /// An [`SftpFs`] connected to a test server for the files under `root`, and what the
+    /// server sent of each file.
+    fn connect(root: &Path) -> (SftpFs, Served) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let root = root.to_owned();
+        let served = Served::default();
+        let server_served = served.clone();
+        std::thread::spawn(move || serve(&root, &server_served, server));
+        let fs = SftpFs::over(client.try_clone().unwrap(), client).unwrap();
+        (fs, served)
+    }
+
+    #[test]
+    fn test_sftp_fs() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("usr/lib");
+        ElfBuilder::new().write(&lib, "libb.so");
+        ElfBuilder::new()
+            .runpath("$ORIGIN")
+            .needed("libb.so")
+            .write(&lib, "liba.so.1.2");
+        symlink("liba.so.1.2", lib.join("liba.so.1")).unwrap();
+        ElfBuilder::new()
+            .runpath("/usr/lib")
+            .needed("liba.so.1")
+            .write(dir.path().join("usr/bin"), "app");
+        let big: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.path().join("big"), &big).unwrap();
+
+        let (fs, _) = connect(dir.path());
+        assert_eq!(fs.read(Path::new("/big")).unwrap(), big);
+        // The server follows the symlink
+        let liba = fs.read(Path::new("/usr/lib/liba.so.1")).unwrap();
+        assert_eq!(liba, std::fs::read(lib.join("liba.so.1.2")).unwrap());
+        let stat = fs.stat(Path::new("/usr/lib/liba.so.1")).unwrap();
+        assert_eq!(stat.len, liba.len() as u64);
+        assert!(stat.uid.is_some() && stat.modified.is_some());
+        let kind = |path| fs.metadata(Path::new(path)).unwrap().kind;
+        assert_eq!(kind("/usr/lib/liba.so.1"), FileKind::Symlink);
+        assert_eq!(kind("/usr/lib"), FileKind::Dir);
+        assert_eq!(kind("/big"), FileKind::File);
+        let target = fs.read_link(Path::new("/usr/lib/liba.so.1")).unwrap();
+        assert_eq!(target, Path::new("liba.so.1.2"));
+        let mut entries = fs.read_dir(Path::new("/usr/lib")).unwrap();
+        entries.sort();
+        assert_eq!(entries, ["liba.so.1", "liba.so.1.2", "libb.so"]);
+        let missing = fs.read(Path::new("/nonexistent")).unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert!(fs.exists(Path::new("/usr/lib/liba.so.1")));
+        assert!(!fs.exists(Path::new("/usr/lib/libc.so.6")));
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .filesystem(Arc::new(fs));
+        let libs = ElfFile::with_config("/usr/bin/app", config)
+            .get_libs_full_paths()
+            .unwrap();
+        let expected = [
+            PathBuf::from("/usr/lib/liba.so.1"),
+            PathBuf::from("/usr/lib/libb.so"),
+        ];
+        assert_eq!(libs, expected);
+        assert!(!Path::new("/usr/lib/liba.so.1.2").exists());
+    }
+
+    #[test]
+    fn test_sftp_fs_fetches_once() {
+        let dir = tempfile::tempdir().unwrap();
+        // A library of another architecture comes first in the search
+        ElfBuilder::new()
+            .machine(183)
+            .write(dir.path().join("usr/lib32"), "libb.so");
+        let libb = ElfBuilder::new().write(dir.path().join("usr/lib"), "libb.so");
+        let app = ElfBuilder::new()
+            .runpath("/usr/lib32:/usr/lib")
+            .needed("libb.so")
+            .write(dir.path().join("usr/bin"), "app");
+        let big: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.path().join("big"), &big).unwrap();
+
+        let (fs, served) = connect(dir.path());
+        let sent = |path: &str| served.lock().unwrap().get(Path::new(path)).copied();
+        let big_path = Path::new("/big");
+        assert_eq!(fs.read_at(big_path, 0, 64).unwrap(), big[..64]);
+        assert_eq!(sent("/big"), Some(64));
+        assert_eq!(fs.read_at(big_path, 1000, 50).unwrap(), big[1000..1050]);
+        assert_eq!(fs.read(big_path).unwrap(), big);
+        assert_eq!(fs.read_at(big_path, 99_990, 50).unwrap(), big[99_990..]);
+        assert_eq!(sent("/big"), Some(big.len()));
+        assert!(fs.read_at(big_path, 200_000, 50).unwrap().is_empty());
+        let missing = fs.read_at(Path::new("/nonexistent"), 0, 0).unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+
+        let config = ResolverConfig::new()
+            .use_env(false)
+            .without_default_dirs()
+            .filesystem(Arc::new(fs));
+        let resolver = Arc::new(crate::Resolver::new(config));
+        for _ in 0..2 {
+            let elf_file = ElfFile::with_resolver("/usr/bin/app", resolver.clone());
+            let libs = elf_file.get_libs_full_paths().unwrap();
+            assert_eq!(libs, [PathBuf::from("/usr/lib/libb.so")]);
+            resolver.clear_cache();
+        }
+        // Only the header of the library passed over is fetched, and every file only once
+        assert_eq!(sent("/usr/lib32/libb.so"), Some(64));
+        let size = |path| Some(std::fs::metadata(path).unwrap().len() as usize);
+        assert_eq!(sent("/usr/lib/libb.so"), size(libb));
+        assert_eq!(sent("/usr/bin/app"), size(app));
+    }
+
+    #[test]
+    fn test_ssh_command() {
+        let command = ssh_command(Some("bastion"), "deploy@host");
+        let args = [
+            "-o",
+            "BatchMode=yes",
+            "-J",
+            "bastion",
+            "-s",
+            "--",
+            "deploy@host",
+            "sftp",
+        ];
+        assert!(command.get_args().eq(args));
+        assert_eq!(ssh_command(None, "host").get_args().count(), 6);
+    }
+}
@@ -0,0 +1,282 @@
+//! A reader for the rows of one table of an SQLite 3 database file, enough to read the rpm
+//! database, which rpm 4.16 and later keep in SQLite.
+//!
+//! Only the b-tree pages of the file are read: changes still in a `-wal` file next to it are
+//! not seen, and neither are indexes, which rows of a table don't need.
+
+const MAGIC: &[u8] = b"SQLite format 3\0";
+const HEADER_SIZE: usize = 100;
+const INTERIOR_TABLE_PAGE: u8 = 0x05;
+const LEAF_TABLE_PAGE: u8 = 0x0d;
+
+/// A value of a column, as stored in a record.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value<'a> {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Text(&'a [u8]),
+    Blob(&'a [u8]),
+}
+
+/// An SQLite database file read into memory.
+pub(crate) struct Database<'a> {
+    data: &'a [u8],
+    page_size: usize,
+    /// The page size minus the bytes each page reserves at its end.
+    usable_size: usize,
+}
+
+impl<'a> Database<'a> {
+    /// Checks the header of the database file `data`, returning `None` if it is not one.
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        if !data.starts_with(MAGIC) {
+            return None;
+        }
+        let page_size = match u16::from_be_bytes(data.get(16..18)?.try_into().ok()?) {
+            1 => 65536,
+            size if size >= 512 && size.is_power_of_two() => usize::from(size),
+            _ => return None,
+        };
+        let usable_size = page_size.checked_sub(usize::from(*data.get(20)?))?;
+        (usable_size >= 480).then_some(Self {
+            data,
+            page_size,
+            usable_size,
+        })
+    }
+
+    /// The rows of the table `name`, each as its rowid and the payload of its record, or `None`
+    /// if there is no such table or the file is corrupt.
+    pub fn table_rows(&self, name: &str) -> Option<Vec<(i64, Vec<u8>)>> {
+        // The schema table, on page 1, has columns type, name, tbl_name, rootpage and sql
+        let mut root = None;
+        for (_, payload) in self.rows(1)? {
+            let record = record(&payload)?;
+            if let [Value::Text(b"table"), Value::Text(table), _, Value::Integer(page), ..] =
+                record[..]
+            {
+                if table.eq_ignore_ascii_case(name.as_bytes()) {
+                    root = Some(usize::try_from(page).ok()?);
+                }
+            }
+        }
+        self.rows(root?)
+    }
+
+    /// The rows of the table b-tree whose root is page `root`.
+    fn rows(&self, root: usize) -> Option<Vec<(i64, Vec<u8>)>> {
+        let mut rows = Vec::new();
+        let mut pages = vec![root];
+        let mut visited = 0;
+        while let Some(number) = pages.pop() {
+            // A tree can't have more pages than the file, unless they form a cycle
+            visited += 1;
+            if visited > self.data.len() / self.page_size {
+                return None;
+            }
+            let page = self.page(number)?;
+            // Page 1 starts with the file header
+            let header = if number == 1 { HEADER_SIZE } else { 0 };
+            let kind = *page.get(header)?;
+            let cells = usize::from(read_u16(page, header + 3)?);
+            let pointers = header + if kind == INTERIOR_TABLE_PAGE { 12 } else { 8 };
+            let cell = |index: usize| read_u16(page, pointers + index * 2).map(usize::from);
+            match kind {
+                INTERIOR_TABLE_PAGE => {
+                    // Pushed in reverse, so the children are visited in rowid order
+                    pages.push(read_u32(page, header + 8)? as usize);
+                    for index in (0..cells).rev() {
+                        pages.push(read_u32(page, cell(index)?)? as usize);
+                    }
+                }
+                LEAF_TABLE_PAGE => {
+                    for index in 0..cells {
+                        rows.push(self.leaf_cell(page, cell(index)?)?);
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(rows)
+    }
+
+    /// The rowid and payload of the leaf table cell at `offset` in `page`, following the
+    /// overflow pages of payloads too large for one page.
+    fn leaf_cell(&self, page: &[u8], offset: usize) -> Option<(i64, Vec<u8>)> {
+        let (size, used) = varint(page.get(offset..)?)?;
+        let (rowid, rowid_used) = varint(page.get(offset + used..)?)?;
+        let start = offset + used + rowid_used;
+        let size = usize::try_from(size).ok()?;
+        let usable = self.usable_size;
+        let max_local = usable - 35;
+        let local = if size <= max_local {
+            size
+        } else {
+            let min_local = (usable - 12) * 32 / 255 - 23;
+            let local = min_local + (size - min_local) % (usable - 4);
+            if local <= max_local {
+                local
+            } else {
+                min_local
+            }
+        };
+        let mut payload = page.get(start..start + local)?.to_vec();
+        let mut next = if local < size {
+            read_u32(page, start + local)? as usize
+        } else {
+            0
+        };
+        let mut overflow_pages = 0;
+        while payload.len() < size {
+            overflow_pages += 1;
+            if next == 0 || overflow_pages > self.data.len() / self.page_size {
+                return None;
+            }
+            let overflow = self.page(next)?;
+            let chunk = (size - payload.len()).min(usable - 4);
+            payload.extend_from_slice(overflow.get(4..4 + chunk)?);
+            next = read_u32(overflow, 0)? as usize;
+        }
+        Some((rowid as i64, payload))
+    }
+
+    /// The page with the 1-based `number`.
+    fn page(&self, number: usize) -> Option<&'a [u8]> {
+        let start = number.checked_sub(1)?.checked_mul(self.page_size)?;
+        self.data.get(start..start.checked_add(self.page_size)?)
+    }
+}
+
+/// The values of the columns of the record `payload`.
+pub(crate) fn record(payload: &[u8]) -> Option<Vec<Value<'_>>> {
+    let (header_size, mut header_offset) = varint(payload)?;
+    let header_size = usize::try_from(header_size).ok()?;
+    let mut body = header_size;
+    let mut values = Vec::new();
+    while header_offset < header_size {
+        let (serial_type, used) = varint(payload.get(header_offset..header_size)?)?;
+        header_offset += used;
+        let integer = |size: usize| {
+            let bytes = payload.get(body..body + size)?;
+            // Big-endian two's complement, sign-extended from its first byte
+            let first = i64::from(*bytes.first()? as i8);
+            Some(
+                bytes[1..]
+                    .iter()
+                    .fold(first, |value, &byte| value << 8 | i64::from(byte)),
+            )
+        };
+        let (value, size) = match serial_type {
+            0 => (Value::Null, 0),
+            1..=4 => {
+                let size = serial_type as usize;
+                (Value::Integer(integer(size)?), size)
+            }
+            5 => (Value::Integer(integer(6)?), 6),
+            6 => (Value::Integer(integer(8)?), 8),
+            7 => (Value::Float(f64::from_bits(integer(8)? as u64)), 8),
+            8 => (Value::Integer(0), 0),
+            9 => (Value::Integer(1), 0),
+            10 | 11 => return None,
+            _ => {
+                let size = usize::try_from((serial_type - 12) / 2).ok()?;
+                let bytes = payload.get(body..body.checked_add(size)?)?;
+                if serial_type % 2 == 0 {
+                    (Value::Blob(bytes), size)
+                } else {
+                    (Value::Text(bytes), size)
+                }
+            }
+        };
+        values.push(value);
+        body += size;
+    }
+    Some(values)
+}
+
+/// Decodes the variable-length integer at the start of `data`, returning it and its length.
+fn varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (index, &byte) in data.iter().enumerate().take(9) {
+        if index == 8 {
+            // The ninth byte contributes all its bits
+            return Some((value << 8 | u64::from(byte), 9));
+        }
+        value = value << 7 | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Some((value, index + 1));
+        }
+    }
+    None
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{record, varint, Database, Value};
+    use crate::test_util::{encode_record, sqlite_database};
+
+    #[test]
+    fn test_table_rows() {
+        let large = vec![0xab; 2000];
+        let data = sqlite_database("Packages", &[b"first", b"second", &large]);
+        let database = Database::new(&data).unwrap();
+        let rows = database.table_rows("Packages").unwrap();
+        let blobs: Vec<_> = rows
+            .iter()
+            .map(|(rowid, payload)| match record(payload).unwrap()[..] {
+                [Value::Null, Value::Blob(blob)] => (*rowid, blob.to_vec()),
+                _ => panic!("unexpected record"),
+            })
+            .collect();
+        assert_eq!(
+            blobs,
+            [(1, b"first".to_vec()), (2, b"second".to_vec()), (3, large)]
+        );
+        assert!(database.table_rows("Missing").is_none());
+    }
+
+    #[test]
+    fn test_corrupt_database() {
+        let data = sqlite_database("Packages", &[b"first", &[0; 2000]]);
+        assert!(Database::new(b"not a database").is_none());
+        // Every truncation must fail or succeed, but not panic
+        for len in 0..data.len() {
+            if let Some(database) = Database::new(&data[..len]) {
+                let _ = database.table_rows("Packages");
+            }
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let values = [
+            Value::Null,
+            Value::Integer(-2),
+            Value::Float(1.5),
+            Value::Text(b"text"),
+            Value::Blob(b"\0blob"),
+        ];
+        assert_eq!(record(&encode_record(&values)).unwrap(), values);
+    }
+
+    #[test]
+    fn test_varint() {
+        assert_eq!(varint(&[0x05]), Some((5, 1)));
+        assert_eq!(varint(&[0x81, 0x00]), Some((128, 2)));
+        assert_eq!(varint(&[0xff; 9]), Some((u64::MAX, 9)));
+        assert_eq!(varint(&[0x80]), None);
+    }
+}
@@ -0,0 +1,538 @@
+//! A reader for squashfs 4.0 images (as `mksquashfs` writes them), as AppImages carry their
+//! files in, compressed with gzip, xz or zstd, or not compressed at all.
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::archive_fs::{read_at, too_large};
+use crate::filesystem::{FileKind, FileMetadata, FileSystem};
+use crate::os_str::OsStringExt;
+use crate::{gzip, xz, zstd};
+
+const MAGIC: &[u8] = b"hsqs";
+const SUPERBLOCK_SIZE: u64 = 96;
+const VERSION_MAJOR: u16 = 4;
+/// The id of gzip compression, which squashfs stores as zlib streams.
+const COMPRESSION_GZIP: u16 = 1;
+/// The id of xz compression, whose blocks are xz files with the LZMA2 filter alone.
+const COMPRESSION_XZ: u16 = 4;
+/// The id of zstd compression, which current `appimagetool` uses.
+const COMPRESSION_ZSTD: u16 = 6;
+/// The flag of the header of a metadata block whose contents are not compressed.
+const METADATA_UNCOMPRESSED: u16 = 0x8000;
+/// The most a metadata block holds once decompressed.
+const METADATA_SIZE: u64 = 8192;
+/// The largest data blocks `mksquashfs` makes.
+const MAX_BLOCK_SIZE: u32 = 1 << 20;
+/// The flag of the size of a data block or fragment that is not compressed.
+const BLOCK_UNCOMPRESSED: u32 = 1 << 24;
+/// The fragment index of files whose tail is not in a fragment.
+const NO_FRAGMENT: u32 = 0xffff_ffff;
+/// The number of entries in each metadata block of the fragment table.
+const FRAGMENTS_PER_BLOCK: u64 = 512;
+/// The most entries a header of a directory listing can have.
+const MAX_DIR_ENTRIES: u32 = 256;
+
+const INODE_DIR: u16 = 1;
+const INODE_FILE: u16 = 2;
+const INODE_SYMLINK: u16 = 3;
+const INODE_EXT_DIR: u16 = 8;
+const INODE_EXT_FILE: u16 = 9;
+const INODE_EXT_SYMLINK: u16 = 10;
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid squashfs image")
+}
+
+/// The little-endian fields of a part of an image, read in order.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid());
+        }
+        let (field, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(field)
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+/// A table of metadata blocks, decompressed whole.
+#[derive(Debug, Default)]
+struct Table {
+    data: Vec<u8>,
+    /// Where the contents of each block start in `data`, by the offset of the block from the
+    /// start of the table, which is what references into the table give.
+    blocks: HashMap<u64, usize>,
+}
+
+impl Table {
+    /// The contents of the table from `offset` in the block at `block`.
+    fn at(&self, block: u64, offset: usize) -> io::Result<&[u8]> {
+        let start = self.blocks.get(&block).ok_or_else(invalid)? + offset;
+        self.data.get(start..).ok_or_else(invalid)
+    }
+}
+
+/// What an inode is, with what is needed to read it.
+#[derive(Debug)]
+enum Inode {
+    Dir {
+        /// Where the listing starts in the directory table.
+        block: u64,
+        offset: usize,
+        /// The size of the listing, plus 3.
+        size: usize,
+    },
+    File {
+        /// Where the data blocks start, from the start of the image.
+        start: u64,
+        size: u64,
+        /// The sizes of the data blocks, with [`BLOCK_UNCOMPRESSED`] set for those that are not
+        /// compressed, or 0 for the sparse ones.
+        blocks: Vec<u32>,
+        /// The index of the fragment the tail of the file is in, and where it is there.
+        fragment: Option<(u32, usize)>,
+    },
+    Symlink(PathBuf),
+    /// A device, FIFO or socket.
+    Other,
+}
+
+/// The files of a squashfs image, at an offset in a file. Paths are read as they are, so they
+/// must not have symlinks in their directories.
+#[derive(Debug)]
+pub(crate) struct SquashFs {
+    path: PathBuf,
+    /// Where the image starts in the file.
+    offset: u64,
+    compression: u16,
+    block_size: u32,
+    /// The most bytes a table or file may take once decompressed.
+    limit: u64,
+    /// A reference to the inode of the root directory: the offset of its metadata block in the
+    /// inode table, shifted left by 16, and its offset in the block.
+    root: u64,
+    inodes: Table,
+    directories: Table,
+    /// Where each fragment starts, from the start of the image, and its size.
+    fragments: Vec<(u64, u32)>,
+}
+
+impl SquashFs {
+    /// Opens the squashfs image at `offset` in the file at `path`, reading its inode and
+    /// directory tables. No table, and no file read later, may take more than `limit` bytes
+    /// once decompressed.
+    ///
+    /// # Return Value [Err]
+    /// An [`io::ErrorKind::InvalidData`] error if there is no squashfs 4.0 image there, or it
+    /// is compressed other than with gzip, xz or zstd, or an [`io::ErrorKind::FileTooLarge`]
+    /// error if a table takes more than `limit` bytes.
+    pub(crate) fn open(path: &Path, offset: u64, limit: u64) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let superblock = read_at(&mut file, offset, SUPERBLOCK_SIZE)?;
+        if !superblock.starts_with(MAGIC) {
+            return Err(invalid());
+        }
+        let field = |at: usize| Fields(&superblock[at..]);
+        let (block_size, fragment_count) = (field(12).u32()?, field(16).u32()?);
+        let compression = field(20).u16()?;
+        if field(28).u16()? != VERSION_MAJOR
+            || !block_size.is_power_of_two()
+            || block_size > MAX_BLOCK_SIZE
+        {
+            return Err(invalid());
+        }
+        let tables = |at: usize| field(at).u64();
+        let (root, bytes_used) = (tables(32)?, tables(40)?);
+        let (inode_table, dir_table) = (tables(64)?, tables(72)?);
+        let fragment_table = tables(80)?;
+        let dir_end = [48, 56, 80, 88]
+            .into_iter()
+            .map(tables)
+            .collect::<io::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|&start| start > dir_table && start != u64::MAX)
+            .chain([bytes_used])
+            .min()
+            .unwrap_or(bytes_used);
+        let mut image = SquashFs {
+            path: path.to_owned(),
+            offset,
+            compression,
+            block_size,
+            limit,
+            root,
+            inodes: Table::default(),
+            directories: Table::default(),
+            fragments: Vec::new(),
+        };
+        image.inodes = image.table(&mut file, inode_table, dir_table)?;
+        image.directories = image.table(&mut file, dir_table, dir_end)?;
+        if fragment_count > 0 {
+            let count = u64::from(fragment_count);
+            if count * 16 > limit {
+                return Err(too_large("squashfs fragment table", limit));
+            }
+            let pointers = count.div_ceil(FRAGMENTS_PER_BLOCK);
+            let pointers = read_at(&mut file, offset + fragment_table, pointers * 8)?;
+            let mut entries = Vec::new();
+            for pointer in pointers.chunks(8) {
+                let (block, _) = image.metadata_block(&mut file, Fields(pointer).u64()?)?;
+                entries.extend(block);
+            }
+            let mut entries = Fields(&entries);
+            for _ in 0..count {
+                let (start, size) = (entries.u64()?, entries.u32()?);
+                entries.u32()?;
+                image.fragments.push((start, size));
+            }
+        }
+        Ok(image)
+    }
+
+    /// `data`, decompressed as the image compresses its blocks, which hold up to `size` bytes.
+    fn decompress(&self, data: &[u8], size: u64) -> io::Result<Vec<u8>> {
+        let decompressed = match self.compression {
+            COMPRESSION_GZIP => gzip::decompress_zlib(data, size),
+            COMPRESSION_XZ => xz::decompress(data, size),
+            COMPRESSION_ZSTD => zstd::decompress(data, size),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "squashfs compression not supported",
+            )),
+        };
+        // A block decompressing past its size is as malformed as the rest of the image
+        decompressed.map_err(|error| match error.kind() {
+            io::ErrorKind::FileTooLarge => invalid(),
+            _ => error,
+        })
+    }
+
+    /// The contents of the metadata block at `start` in the image, and its size there.
+    fn metadata_block(&self, file: &mut File, start: u64) -> io::Result<(Vec<u8>, u64)> {
+        let header = Fields(&read_at(file, self.offset + start, 2)?).u16()?;
+        let size = header & !METADATA_UNCOMPRESSED;
+        let data = read_at(file, self.offset + start + 2, u64::from(size))?;
+        let data = if header & METADATA_UNCOMPRESSED != 0 {
+            data
+        } else {
+            self.decompress(&data, METADATA_SIZE)?
+        };
+        Ok((data, 2 + u64::from(size)))
+    }
+
+    /// The metadata blocks from `start` up to `end` in the image.
+    fn table(&self, file: &mut File, start: u64, end: u64) -> io::Result<Table> {
+        let mut table = Table::default();
+        let mut at = start;
+        while at < end {
+            let (data, size) = self.metadata_block(file, at)?;
+            table.blocks.insert(at - start, table.data.len());
+            table.data.extend(data);
+            if table.data.len() as u64 > self.limit {
+                return Err(too_large("squashfs table", self.limit));
+            }
+            at += size;
+        }
+        Ok(table)
+    }
+
+    /// The inode `reference` refers to, and its permission bits.
+    fn inode(&self, reference: u64) -> io::Result<(Inode, u16)> {
+        let mut fields = Fields(
+            self.inodes
+                .at(reference >> 16, (reference & 0xffff) as usize)?,
+        );
+        let (kind, mode) = (fields.u16()?, fields.u16()?);
+        fields.take(12)?;
+        let block_sizes = |fields: &mut Fields, size: u64, fragment: u32| {
+            let block_size = u64::from(self.block_size);
+            let count = match fragment {
+                NO_FRAGMENT => size.div_ceil(block_size),
+                _ => size / block_size,
+            };
+            (0..count)
+                .map(|_| fields.u32())
+                .collect::<io::Result<Vec<_>>>()
+        };
+        let fragment =
+            |index: u32, offset: u32| (index != NO_FRAGMENT).then_some((index, offset as usize));
+        let inode = match kind {
+            INODE_DIR => {
+                let block = fields.u32()?;
+                fields.u32()?;
+                let (size, offset) = (fields.u16()?, fields.u16()?);
+                Inode::Dir {
+                    block: block.into(),
+                    offset: offset.into(),
+                    size: size.into(),
+                }
+            }
+            INODE_EXT_DIR => {
+                fields.u32()?;
+                let (size, block) = (fields.u32()?, fields.u32()?);
+                fields.take(6)?;
+                Inode::Dir {
+                    block: block.into(),
+                    offset: fields.u16()?.into(),
+                    size: size as usize,
+                }
+            }
+            INODE_FILE => {
+                let (start, index) = (fields.u32()?, fields.u32()?);
+                let (offset, size) = (fields.u32()?, fields.u32()?);
+                Inode::File {
+                    start: start.into(),
+                    size: size.into(),
+                    blocks: block_sizes(&mut fields, size.into(), index)?,
+                    fragment: fragment(index, offset),
+                }
+            }
+            INODE_EXT_FILE => {
+                let (start, size) = (fields.u64()?, fields.u64()?);
+                fields.take(12)?;
+                let (index, offset) = (fields.u32()?, fields.u32()?);
+                fields.u32()?;
+                Inode::File {
+                    start,
+                    size,
+                    blocks: block_sizes(&mut fields, size, index)?,
+                    fragment: fragment(index, offset),
+                }
+            }
+            INODE_SYMLINK | INODE_EXT_SYMLINK => {
+                fields.u32()?;
+                let size = fields.u32()?;
+                Inode::Symlink(PathBuf::from(OsString::from_vec(
+                    fields.take(size as usize)?.to_vec(),
+                )))
+            }
+            _ => Inode::Other,
+        };
+        Ok((inode, mode))
+    }
+
+    /// The names of the entries of the directory whose listing is at `offset` in the block at
+    /// `block` of the directory table, and references to their inodes.
+    fn entries(&self, block: u64, offset: usize, size: usize) -> io::Result<Vec<(OsString, u64)>> {
+        let listing = self.directories.at(block, offset)?;
+        let mut fields = Fields(listing.get(..size.saturating_sub(3)).ok_or_else(invalid)?);
+        let mut entries = Vec::new();
+        while !fields.0.is_empty() {
+            let (count, start) = (fields.u32()? + 1, fields.u32()?);
+            fields.u32()?;
+            if count > MAX_DIR_ENTRIES {
+                return Err(invalid());
+            }
+            for _ in 0..count {
+                let offset = fields.u16()?;
+                fields.take(4)?;
+                let len = usize::from(fields.u16()?) + 1;
+                let name = OsString::from_vec(fields.take(len)?.to_vec());
+                entries.push((name, (u64::from(start) << 16) | u64::from(offset)));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The inode at `path`, absolute in the image, and its permission bits.
+    fn find(&self, path: &Path) -> io::Result<(Inode, u16)> {
+        let mut inode = self.inode(self.root)?;
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => {
+                    let Inode::Dir {
+                        block,
+                        offset,
+                        size,
+                    } = inode.0
+                    else {
+                        return Err(io::ErrorKind::NotFound.into());
+                    };
+                    let entries = self.entries(block, offset, size)?;
+                    let (_, reference) = entries
+                        .into_iter()
+                        .find(|(entry, _)| entry == name)
+                        .ok_or(io::ErrorKind::NotFound)?;
+                    inode = self.inode(reference)?;
+                }
+                Component::ParentDir => return Err(io::ErrorKind::NotFound.into()),
+                _ => {}
+            }
+        }
+        Ok(inode)
+    }
+
+    /// A data block or fragment of `on_disk` bytes at `start` in the image, decompressed.
+    fn block(&self, file: &mut File, start: u64, on_disk: u32) -> io::Result<Vec<u8>> {
+        let size = u64::from(on_disk & !BLOCK_UNCOMPRESSED);
+        let data = read_at(file, self.offset + start, size)?;
+        if on_disk & BLOCK_UNCOMPRESSED != 0 {
+            Ok(data)
+        } else {
+            self.decompress(&data, self.block_size.into())
+        }
+    }
+}
+
+impl FileSystem for SquashFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let Inode::File {
+            start,
+            size,
+            blocks,
+            fragment,
+        } = self.find(path)?.0
+        else {
+            return Err(io::Error::other("not a regular file"));
+        };
+        if size > self.limit {
+            return Err(too_large("squashfs file", self.limit));
+        }
+        let mut file = File::open(&self.path)?;
+        let mut data = Vec::new();
+        let mut at = start;
+        for on_disk in blocks {
+            if on_disk == 0 {
+                data.resize(data.len() + self.block_size as usize, 0);
+                continue;
+            }
+            data.extend(self.block(&mut file, at, on_disk)?);
+            at += u64::from(on_disk & !BLOCK_UNCOMPRESSED);
+        }
+        if let Some((index, offset)) = fragment {
+            let &(start, on_disk) = self.fragments.get(index as usize).ok_or_else(invalid)?;
+            let fragment = self.block(&mut file, start, on_disk)?;
+            let tail = (size % u64::from(self.block_size)) as usize;
+            data.extend(fragment.get(offset..offset + tail).ok_or_else(invalid)?);
+        }
+        data.truncate(usize::try_from(size).map_err(io::Error::other)?);
+        Ok(data)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let (inode, mode) = self.find(path)?;
+        let (kind, len) = match inode {
+            Inode::Dir { .. } => (FileKind::Dir, 0),
+            Inode::File { size, .. } => (FileKind::File, size),
+            Inode::Symlink(target) => (FileKind::Symlink, target.as_os_str().len() as u64),
+            Inode::Other => (FileKind::File, 0),
+        };
+        Ok(FileMetadata {
+            kind,
+            len,
+            mode: u32::from(mode) & 0o7777,
+        })
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.find(path)?.0 {
+            Inode::Symlink(target) => Ok(target),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        match self.find(path)?.0 {
+            Inode::Dir {
+                block,
+                offset,
+                size,
+            } => Ok(self
+                .entries(block, offset, size)?
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()),
+            _ => Err(io::Error::other("not a directory")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SquashFs;
+    use crate::filesystem::{FileKind, FileSystem};
+    use crate::test_util::{squashfs, ArchiveEntry, SquashfsBlocks};
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    #[test]
+    #[cfg_attr(
+        not(all(feature = "gzip", feature = "xz", feature = "zstd")),
+        ignore = "needs the `gzip`, `xz` and `zstd` features"
+    )]
+    fn test_squashfs() {
+        let dir = tempfile::tempdir().unwrap();
+        let large: Vec<u8> = (0..10000u32).map(|i| (i % 251) as u8).collect();
+        let entries = [
+            ArchiveEntry::Dir("usr/lib/"),
+            ArchiveEntry::File("usr/lib/libfoo.so.1.2", b"foo"),
+            ArchiveEntry::Symlink("usr/lib/libfoo.so.1", "libfoo.so.1.2"),
+            ArchiveEntry::File("usr/share/large", &large),
+            ArchiveEntry::File("AppRun", b"#!/bin/sh\n"),
+        ];
+        let blocks = [
+            SquashfsBlocks::Uncompressed,
+            SquashfsBlocks::Gzip,
+            SquashfsBlocks::Xz,
+            SquashfsBlocks::Zstd,
+        ];
+        for blocks in blocks {
+            let mut data = b"prefix".to_vec();
+            data.extend(squashfs(&entries, blocks));
+            let path = dir.path().join("image");
+            fs::write(&path, data).unwrap();
+
+            let image = SquashFs::open(&path, 6, u64::MAX).unwrap();
+            assert_eq!(
+                image.read(Path::new("/usr/lib/libfoo.so.1.2")).unwrap(),
+                b"foo"
+            );
+            assert_eq!(image.read(Path::new("/usr/share/large")).unwrap(), large);
+            assert_eq!(image.read(Path::new("/AppRun")).unwrap(), b"#!/bin/sh\n");
+            assert_eq!(
+                image.read_link(Path::new("/usr/lib/libfoo.so.1")).unwrap(),
+                Path::new("libfoo.so.1.2")
+            );
+            let metadata = image.metadata(Path::new("/usr/lib/libfoo.so.1")).unwrap();
+            assert_eq!(metadata.kind, FileKind::Symlink);
+            assert_eq!(
+                image.metadata(Path::new("/usr")).unwrap().kind,
+                FileKind::Dir
+            );
+            let mut names = image.read_dir(Path::new("/")).unwrap();
+            names.sort();
+            assert_eq!(names, ["AppRun", "usr"]);
+            let missing = image.read(Path::new("/usr/lib/libbar.so.1"));
+            assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+            assert!(image.read(Path::new("/AppRun/usr")).is_err());
+
+            let open = SquashFs::open(&path, 0, u64::MAX);
+            assert_eq!(open.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+            let image = SquashFs::open(&path, 6, 5000).unwrap();
+            assert_eq!(image.read(Path::new("/AppRun")).unwrap(), b"#!/bin/sh\n");
+            let large = image.read(Path::new("/usr/share/large"));
+            assert_eq!(large.unwrap_err().kind(), io::ErrorKind::FileTooLarge);
+            let open = SquashFs::open(&path, 6, 16);
+            assert_eq!(open.unwrap_err().kind(), io::ErrorKind::FileTooLarge);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Size and shape figures of a dependency closure, for budgeting what shipping it costs.
+use std::path::{Path, PathBuf};
+
+use crate::{DependencyGraph, DependencyKind};
+
+/// Figures about the dependency closure of a file, as computed by
+/// [`ElfFile::closure_stats`](crate::ElfFile::closure_stats).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClosureStats {
+    /// The size of the analyzed file itself, in bytes.
+    pub file_size: u64,
+    /// The resolved libraries in load order, each with its size in bytes. Libraries whose size
+    /// can't be read are left out.
+    pub library_sizes: Vec<(PathBuf, u64)>,
+    /// The sum of [`ClosureStats::library_sizes`], leaving out the analyzed file.
+    pub total_size: u64,
+    /// The number of resolved libraries.
+    pub library_count: usize,
+    /// The number of libraries that could not be found or read.
+    pub unresolved_count: usize,
+    /// How many `DT_NEEDED` entries lead from the analyzed file to the library furthest from
+    /// it, following the shortest chain to each library: 1 if it only has direct dependencies,
+    /// and 0 if it has none.
+    pub max_depth: usize,
+}
+
+/// Computes the figures of `graph`, taking the size of each file from `size`.
+pub(crate) fn closure_stats(
+    graph: &DependencyGraph,
+    size: impl Fn(&Path) -> Option<u64>,
+) -> ClosureStats {
+    // Breadth-first, so each library is first reached through one of its shortest chains
+    let mut depths = vec![None; graph.nodes().len()];
+    depths[graph.root().index()] = Some(0);
+    let order = graph.load_order();
+    for &id in &order {
+        let depth = depths[id.index()].unwrap_or_default();
+        for &dependency in graph.dependencies(id) {
+            depths[dependency.index()].get_or_insert(depth + 1);
+        }
+    }
+
+    let root = graph.node(graph.root());
+    let mut stats = ClosureStats {
+        file_size: root.path().and_then(&size).unwrap_or_default(),
+        library_sizes: Vec::new(),
+        total_size: 0,
+        library_count: 0,
+        unresolved_count: 0,
+        max_depth: depths.iter().flatten().copied().max().unwrap_or_default(),
+    };
+    for &id in order.iter().skip(1) {
+        let node = graph.node(id);
+        let Some(path) = node.path() else {
+            // Virtual objects are loaded without being files
+            if node.kind != DependencyKind::Virtual {
+                stats.unresolved_count += 1;
+            }
+            continue;
+        };
+        stats.library_count += 1;
+        if let Some(size) = size(path) {
+            stats.library_sizes.push((path.to_owned(), size));
+            stats.total_size += size;
+        }
+    }
+    stats
+}
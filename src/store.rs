@@ -0,0 +1,93 @@
+//! The Nix and Guix stores, where every package lives in a directory of its own and programs
+//! find their libraries through `RUNPATH`s into the store rather than in system directories.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The directory of the Nix store.
+pub const NIX_STORE_DIR: &str = "/nix/store";
+/// The directory of the Guix store.
+pub const GUIX_STORE_DIR: &str = "/gnu/store";
+
+/// The length of the hashes store paths start with.
+const HASH_LEN: usize = 32;
+/// The characters of the base-32 encoding of store path hashes, which leaves out `e`, `o`, `t`
+/// and `u`.
+const HASH_ALPHABET: &[u8] = b"0123456789abcdfghijklmnpqrsvwxyz";
+
+/// A path in a Nix or Guix store, `/nix/store/<hash>-<name>`: the package a library comes
+/// from, as the store names it. See
+/// [`DependencyNode::store_path`](crate::DependencyNode::store_path).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StorePath {
+    /// The store path itself, in the directory of the store, like
+    /// `/nix/store/0c2wrxmg3wbfzflbvvqzq1ifzc0dz8pq-glibc-2.39-52`, even if the store was read
+    /// from [another root](crate::ResolverConfig::store_root).
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_path"))]
+    pub path: PathBuf,
+    /// The hash of the store path, like `0c2wrxmg3wbfzflbvvqzq1ifzc0dz8pq`.
+    pub hash: String,
+    /// The name of the package after the hash, usually with its version, like `glibc-2.39-52`.
+    pub name: String,
+}
+
+impl StorePath {
+    /// The store path `path` lies in, in the store at `dir`, or `None` if it isn't in the
+    /// store, or is there without being in a store path, like the `.links` directory.
+    pub fn parse<D: AsRef<Path>, P: AsRef<Path>>(dir: D, path: P) -> Option<StorePath> {
+        let dir = dir.as_ref();
+        let relative = path.as_ref().strip_prefix(dir).ok()?;
+        let name = relative.components().next()?.as_os_str().to_str()?;
+        let (hash, package) = name.split_once('-')?;
+        let valid = hash.len() == HASH_LEN
+            && hash.bytes().all(|byte| HASH_ALPHABET.contains(&byte))
+            && !package.is_empty();
+        valid.then(|| StorePath {
+            path: dir.join(name),
+            hash: hash.to_owned(),
+            name: package.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for StorePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.path.display().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StorePath, NIX_STORE_DIR};
+    use std::path::Path;
+
+    #[test]
+    fn test_store_path() {
+        let path = "/nix/store/0c2wrxmg3wbfzflbvvqzq1ifzc0dz8pq-glibc-2.39-52/lib/libc.so.6";
+        let store_path = StorePath::parse(NIX_STORE_DIR, path).unwrap();
+        assert_eq!(
+            store_path.path,
+            Path::new("/nix/store/0c2wrxmg3wbfzflbvvqzq1ifzc0dz8pq-glibc-2.39-52")
+        );
+        assert_eq!(store_path.hash, "0c2wrxmg3wbfzflbvvqzq1ifzc0dz8pq");
+        assert_eq!(store_path.name, "glibc-2.39-52");
+        assert_eq!(store_path.to_string(), store_path.path.to_str().unwrap());
+        let parse = |path: &str| StorePath::parse(NIX_STORE_DIR, path);
+        assert_eq!(
+            parse("/nix/store/.links/0c2wrxmg3wbfzflbvvqzq1ifzc0dz8pq"),
+            None
+        );
+        // Hashes have 32 characters of their own alphabet
+        assert_eq!(
+            parse("/nix/store/0c2wrxmg3wbfzflbvvqzq1ifzc0dz8p-glibc"),
+            None
+        );
+        assert_eq!(
+            parse("/nix/store/ec2wrxmg3wbfzflbvvqzq1ifzc0dz8pq-glibc"),
+            None
+        );
+        assert_eq!(parse("/nix/store/0c2wrxmg3wbfzflbvvqzq1ifzc0dz8pq-"), None);
+        assert_eq!(parse("/usr/lib/libc.so.6"), None);
+        assert!(StorePath::parse("/gnu/store", path.replace("/nix", "/gnu")).is_some());
+    }
+}
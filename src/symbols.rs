@@ -0,0 +1,917 @@
+//! Checking the symbols and symbol versions each object imports against those its closure
+//! exports, like `ldd -r` and `ldd -u`.
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::abi::{
+    SHT_DYNSYM, SHT_GNU_HASH, SHT_GNU_VERDEF, SHT_GNU_VERNEED, SHT_GNU_VERSYM, SHT_HASH,
+    STB_GNU_UNIQUE, STB_LOCAL, STB_WEAK, STV_HIDDEN, STV_INTERNAL, VERSYM_HIDDEN, VERSYM_VERSION,
+    VER_FLG_BASE, VER_FLG_WEAK,
+};
+use crate::object::{FileHeader, Object, Source, Stream};
+use crate::os_str::OsStrExt;
+use crate::parse::{c_str, parse_error};
+use crate::ParseError;
+use crate::{DependencyGraph, DependencyKind, Error, ResolveError};
+
+/// The soname of GCC's C++ standard library.
+const LIBSTDCXX_SONAME: &str = "libstdc++.so.6";
+
+/// A symbol an object imports that no object in its closure exports, as reported by
+/// [`ElfFile::undefined_symbols`](crate::ElfFile::undefined_symbols).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UndefinedSymbol {
+    /// The name of the symbol, with bytes that are not UTF-8 replaced with `U+FFFD`.
+    pub name: String,
+    /// The object that imports the symbol.
+    pub needed_by: PathBuf,
+}
+
+impl UndefinedSymbol {
+    /// The name of the symbol, demangled if it is a C++ or Rust one, see
+    /// [`demangle`](crate::demangle).
+    #[cfg(feature = "demangle")]
+    pub fn demangled_name(&self) -> String {
+        crate::demangle(&self.name).unwrap_or_else(|| self.name.clone())
+    }
+}
+
+/// A symbol version an object requires from a library that the library found for it does not
+/// define, as reported by [`ElfFile::missing_versions`](crate::ElfFile::missing_versions). This
+/// is what happens when a binary built against a newer library, such as one needing
+/// `GLIBC_2.34`, is run on a system with an older one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MissingVersion {
+    /// The name of the version, like `GLIBC_2.34`.
+    pub version: String,
+    /// The soname of the library the version is required from.
+    pub soname: String,
+    /// Where that library was found.
+    pub provider: PathBuf,
+    /// The object that requires the version.
+    pub needed_by: PathBuf,
+}
+
+/// A `DT_NEEDED` entry an object has no use for, as reported by
+/// [`ElfFile::unused_dependencies`](crate::ElfFile::unused_dependencies).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnusedDependency {
+    /// The soname of the library.
+    pub soname: String,
+    /// Where the library was found.
+    pub path: PathBuf,
+    /// The object whose `DT_NEEDED` entry names the library.
+    pub needed_by: PathBuf,
+}
+
+/// A `DT_NEEDED` entry an object only imports weak symbols from, as reported by
+/// [`ElfFile::weak_dependencies`](crate::ElfFile::weak_dependencies).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WeakDependency {
+    /// The soname of the library.
+    pub soname: String,
+    /// Where the library was found, or `None` if it could not be.
+    pub path: Option<PathBuf>,
+    /// The object whose `DT_NEEDED` entry names the library.
+    pub needed_by: PathBuf,
+}
+
+/// An object that exports a symbol, as reported by
+/// [`ElfFile::find_symbol`](crate::ElfFile::find_symbol).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SymbolProvider {
+    /// The object that exports the symbol.
+    pub path: PathBuf,
+    /// The version the symbol is defined with, like `GLIBC_2.34`, or `None` if it has none.
+    pub version: Option<String>,
+    /// Whether `version` is the default one, that links against the object bind to (`name@@V`),
+    /// rather than one kept for binaries built against older releases (`name@V`). Always `true`
+    /// for unversioned symbols.
+    pub default_version: bool,
+    /// How the symbol is bound.
+    pub binding: SymbolBinding,
+}
+
+/// The dynamic symbols an object exports, as reported by
+/// [`ElfFile::exported_symbols`](crate::ElfFile::exported_symbols).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExportedSymbols {
+    /// The object.
+    pub path: PathBuf,
+    /// The symbols, in the order of its `.dynsym` table.
+    pub symbols: Vec<ExportedSymbol>,
+}
+
+/// A symbol an object exports, like a line of `nm -D --defined-only`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExportedSymbol {
+    /// The name of the symbol, with bytes that are not UTF-8 replaced with `U+FFFD`.
+    pub name: String,
+    /// The version the symbol is defined with, like `GLIBC_2.34`, or `None` if it has none.
+    pub version: Option<String>,
+    /// Whether `version` is the default one, see [`SymbolProvider::default_version`].
+    pub default_version: bool,
+    /// How the symbol is bound.
+    pub binding: SymbolBinding,
+}
+
+impl ExportedSymbol {
+    /// The name of the symbol, demangled if it is a C++ or Rust one, see
+    /// [`demangle`](crate::demangle).
+    #[cfg(feature = "demangle")]
+    pub fn demangled_name(&self) -> String {
+        crate::demangle(&self.name).unwrap_or_else(|| self.name.clone())
+    }
+}
+
+/// A symbol exported by more than one object in a closure, as reported by
+/// [`ElfFile::symbol_conflicts`](crate::ElfFile::symbol_conflicts). References to it from
+/// anywhere in the process bind to the definition of the first provider, so the others are
+/// interposed, which is fine for a deliberate `malloc` replacement but crashes programs that
+/// load two copies of a library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SymbolConflict {
+    /// The name of the symbol, with bytes that are not UTF-8 replaced with `U+FFFD`.
+    pub name: String,
+    /// The objects that export the symbol, in load order, each with the definition references
+    /// would bind to.
+    pub providers: Vec<SymbolProvider>,
+}
+
+impl SymbolConflict {
+    /// The definition references bind to.
+    pub fn winner(&self) -> &SymbolProvider {
+        &self.providers[0]
+    }
+
+    /// The name of the symbol, demangled if it is a C++ or Rust one, see
+    /// [`demangle`](crate::demangle).
+    #[cfg(feature = "demangle")]
+    pub fn demangled_name(&self) -> String {
+        crate::demangle(&self.name).unwrap_or_else(|| self.name.clone())
+    }
+}
+
+/// The newest glibc a file and the libraries shipped with it need, from the highest
+/// `GLIBC_x.y` symbol version they require, as reported by
+/// [`ElfFile::minimum_glibc`](crate::ElfFile::minimum_glibc). Displays like `glibc >= 2.31`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GlibcRequirement {
+    /// The version of glibc, like `2.31` for `GLIBC_2.31`.
+    pub version: String,
+    /// The soname of the library the version is required from, like `libc.so.6` or
+    /// `libm.so.6`.
+    pub soname: String,
+    /// The object that requires the version, the first in load order if several do.
+    pub needed_by: PathBuf,
+}
+
+impl fmt::Display for GlibcRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "glibc >= {}", self.version)
+    }
+}
+
+/// The libstdc++ symbol versions the objects in a closure require and whether the libstdc++
+/// found for them provides them, as reported by
+/// [`ElfFile::libstdcxx_requirements`](crate::ElfFile::libstdcxx_requirements). The versions
+/// grow with each GCC release, so a program built with a newer GCC than the system's fails
+/// with "version `GLIBCXX_3.4.30' not found".
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LibstdcxxRequirements {
+    /// The highest `GLIBCXX_` version required, like `3.4.30`, or `None` if none is.
+    pub glibcxx: Option<String>,
+    /// The highest `CXXABI_` version required, like `1.3.13`, or `None` if none is.
+    pub cxxabi: Option<String>,
+    /// Where `libstdc++.so.6` was found, or `None` if nothing needs it or it was not found.
+    pub libstdcxx: Option<PathBuf>,
+    /// The versions that libstdc++ lacks, with the objects requiring them.
+    pub missing: Vec<MissingVersion>,
+}
+
+impl LibstdcxxRequirements {
+    /// Whether the libstdc++ that was found provides every version required, or none is.
+    pub fn is_satisfied(&self) -> bool {
+        let required = self.glibcxx.is_some() || self.cxxabi.is_some();
+        self.missing.is_empty() && (self.libstdcxx.is_some() || !required)
+    }
+}
+
+/// The binding of an exported symbol, which decides how definitions of the same name in
+/// different objects interact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SymbolBinding {
+    /// `STB_GLOBAL`: the first definition in load order wins.
+    Global,
+    /// `STB_WEAK`: treated like [`SymbolBinding::Global`] by ld.so, which binds to the first
+    /// definition whether it is weak or not.
+    Weak,
+    /// `STB_GNU_UNIQUE`: a single definition is used process-wide, even across `dlopen`
+    /// namespaces.
+    Unique,
+}
+
+/// A symbol an object exports.
+#[derive(Debug)]
+pub(crate) struct DefinedSymbol {
+    pub name: OsString,
+    pub version: Option<OsString>,
+    pub default_version: bool,
+    pub binding: SymbolBinding,
+}
+
+/// The dynamic symbols of an object that matter for linking it.
+#[derive(Debug, Default)]
+pub(crate) struct DynamicSymbols {
+    /// The symbols the object exports.
+    pub defined: Vec<DefinedSymbol>,
+    /// Whether the object has a `.dynsym` table at all. Without one, what it imports is unknown.
+    pub symbol_table: bool,
+    /// The symbols the object imports, except weak ones, which may stay undefined.
+    pub undefined: Vec<OsString>,
+    /// The symbols the object imports as weak references.
+    pub weak: Vec<OsString>,
+    /// The `.gnu.version_r` entries: the sonames of libraries, each with the versions the
+    /// object requires from them, except weak ones, which ld.so only warns about.
+    pub version_needs: Vec<(OsString, Vec<OsString>)>,
+    /// The versions the object defines in `.gnu.version_d`, except its base version.
+    pub version_defs: Vec<OsString>,
+    /// The hash table of `.dynsym`, `None` if it has none, or one that could not be read.
+    hash: Option<SymbolHash>,
+    /// The index in `defined` of each `.dynsym` entry that is there. Empty without `hash`.
+    defined_at: Vec<Option<usize>>,
+}
+
+impl DynamicSymbols {
+    /// The definitions of `name` the object exports, in the order of its symbol table. The
+    /// hash table finds them without going through every symbol, as ld.so does.
+    pub fn lookup<'a>(&'a self, name: &'a OsStr) -> impl Iterator<Item = &'a DefinedSymbol> {
+        let hashed = self.hash.as_ref().map(|hash| {
+            hash.candidates(name.as_bytes())
+                .into_iter()
+                .filter_map(|index| self.defined_at.get(index).copied().flatten())
+                .map(|index| &self.defined[index])
+        });
+        let scanned = self.hash.is_none().then(|| self.defined.iter());
+        hashed
+            .into_iter()
+            .flatten()
+            .chain(scanned.into_iter().flatten())
+            .filter(move |symbol| symbol.name == name)
+    }
+
+    /// Whether the object exports `name`, in any version.
+    pub fn defines(&self, name: &OsStr) -> bool {
+        self.lookup(name).next().is_some()
+    }
+}
+
+/// The `.gnu.hash` or `.hash` table of an object's `.dynsym`, which ld.so looks symbols up in.
+#[derive(Debug)]
+enum SymbolHash {
+    /// `.gnu.hash`: a Bloom filter of the hashes, with `bits` bits to a word and the second
+    /// bit of each hash at `shift`, then the first index in `.dynsym` of the symbols in each
+    /// bucket, and the hashes of the symbols from `start` on, the last of each bucket with
+    /// its low bit set.
+    Gnu {
+        bloom: Vec<u64>,
+        bits: u32,
+        shift: u32,
+        buckets: Vec<u32>,
+        start: u32,
+        chains: Vec<u32>,
+    },
+    /// `.hash`: the first index in `.dynsym` of the symbols in each bucket, and the index of
+    /// the next one after each symbol, 0 ending the bucket.
+    SysV { buckets: Vec<u32>, chains: Vec<u32> },
+}
+
+impl SymbolHash {
+    /// Reads the hash table in `data`, `None` if it is truncated.
+    fn parse(sh_type: u32, header: &FileHeader, data: &[u8]) -> Option<Self> {
+        let words = |offset: &mut usize, count: u32| -> Option<Vec<u32>> {
+            (0..count).map(|_| header.read_u32(data, offset)).collect()
+        };
+        let mut offset = 0;
+        if sh_type == SHT_HASH {
+            let [nbucket, nchain] = words(&mut offset, 2)?[..] else {
+                return None;
+            };
+            let buckets = words(&mut offset, nbucket)?;
+            return Some(SymbolHash::SysV {
+                buckets,
+                chains: words(&mut offset, nchain)?,
+            });
+        }
+        let [nbucket, start, nbloom, shift] = words(&mut offset, 4)?[..] else {
+            return None;
+        };
+        // The Bloom filter is made of address-sized words
+        let bloom: Option<Vec<u64>> = (0..nbloom)
+            .map(|_| header.read_word(data, &mut offset))
+            .collect();
+        let (bloom, bits) = (bloom?, if header.is_64_bit { 64 } else { 32 });
+        let buckets = words(&mut offset, nbucket)?;
+        // The chains take up the rest of the section
+        let count = data.len().saturating_sub(offset) / 4;
+        let chains = words(&mut offset, count.try_into().ok()?)?;
+        Some(SymbolHash::Gnu {
+            bloom,
+            bits,
+            shift,
+            buckets,
+            start,
+            chains,
+        })
+    }
+
+    /// The indices in `.dynsym` of the symbols that may be named `name`, in ascending order.
+    fn candidates(&self, name: &[u8]) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        match self {
+            SymbolHash::Gnu {
+                bloom,
+                bits,
+                shift,
+                buckets,
+                start,
+                chains,
+            } => {
+                if bloom.is_empty() || buckets.is_empty() {
+                    return candidates;
+                }
+                let hash = gnu_hash(name);
+                let word = bloom[(hash / bits) as usize % bloom.len()];
+                let second = hash.checked_shr(*shift).unwrap_or(0);
+                let mask = 1 << (hash % bits) | 1 << (second % bits);
+                if word & mask != mask {
+                    return candidates;
+                }
+                let first = buckets[hash as usize % buckets.len()];
+                // Empty buckets hold 0, which is before the first hashed symbol
+                let Some(first) = first.checked_sub(*start) else {
+                    return candidates;
+                };
+                for (i, &chain) in chains.iter().enumerate().skip(first as usize) {
+                    if chain | 1 == hash | 1 {
+                        candidates.push(i + *start as usize);
+                    }
+                    if chain & 1 != 0 {
+                        break;
+                    }
+                }
+            }
+            SymbolHash::SysV { buckets, chains } => {
+                if buckets.is_empty() {
+                    return candidates;
+                }
+                let mut index = buckets[sysv_hash(name) as usize % buckets.len()] as usize;
+                // A malformed table may loop, but holds no more symbols than chains
+                while index != 0 && candidates.len() < chains.len() {
+                    candidates.push(index);
+                    index = chains.get(index).copied().unwrap_or(0) as usize;
+                }
+                candidates.sort_unstable();
+            }
+        }
+        candidates
+    }
+}
+
+/// The hash of `name` in `.gnu.hash` tables.
+pub(crate) fn gnu_hash(name: &[u8]) -> u32 {
+    name.iter().fold(5381u32, |hash, &byte| {
+        hash.wrapping_mul(33).wrapping_add(byte.into())
+    })
+}
+
+/// The hash of `name` in `.hash` tables.
+pub(crate) fn sysv_hash(name: &[u8]) -> u32 {
+    let hash = name.iter().fold(0u32, |hash, &byte| {
+        let hash = hash.wrapping_mul(16).wrapping_add(byte.into());
+        hash ^ (hash >> 24) & 0xf0
+    });
+    hash & 0xfffffff
+}
+
+/// Reads the `.dynsym` table and the symbol version sections of the ELF file `reader` reads;
+/// `path` is only used for error reporting. Files without section headers have no symbols or
+/// versions as far as this is concerned.
+pub(crate) fn read_symbols_from<R: Read + Seek>(
+    path: &Path,
+    reader: R,
+) -> Result<DynamicSymbols, Error> {
+    let parse_error = |source| parse_error(path, source);
+    let mut elf = Object::open(Stream(reader)).map_err(parse_error)?;
+    let mut symbols = DynamicSymbols::default();
+    let definitions = read_versions(&mut elf, &mut symbols).map_err(parse_error)?;
+    symbols.hash = read_hash(&mut elf).map_err(parse_error)?;
+    let Some((table, strings)) = elf.symbols(SHT_DYNSYM).map_err(parse_error)? else {
+        return Ok(symbols);
+    };
+    symbols.symbol_table = true;
+    if symbols.hash.is_some() {
+        symbols.defined_at = vec![None; table.len()];
+    }
+    // The first entry is always the null symbol, and the index of each entry is that of its
+    // version in .gnu.version
+    let mut defined = Vec::new();
+    for (index, symbol) in table.iter().enumerate().skip(1) {
+        let name = usize::try_from(symbol.st_name)
+            .ok()
+            .and_then(|offset| strings.get(offset..))
+            .and_then(c_str);
+        let Some(name) = name else {
+            continue;
+        };
+        if name.is_empty() || symbol.bind() == STB_LOCAL {
+            continue;
+        }
+        let name = OsStr::from_bytes(name).to_owned();
+        if symbol.is_undefined() {
+            if symbol.bind() == STB_WEAK {
+                symbols.weak.push(name);
+            } else {
+                symbols.undefined.push(name);
+            }
+        } else if !matches!(symbol.visibility(), STV_HIDDEN | STV_INTERNAL) {
+            let binding = match symbol.bind() {
+                STB_WEAK => SymbolBinding::Weak,
+                STB_GNU_UNIQUE => SymbolBinding::Unique,
+                _ => SymbolBinding::Global,
+            };
+            defined.push((index, name, binding));
+        }
+    }
+    let version_indices = read_version_indices(&mut elf).map_err(parse_error)?;
+    for (index, name, binding) in defined {
+        // Symbols without a version of their own refer to the base version, the object itself
+        let version_index = version_indices.get(index).copied();
+        let definition = version_index.and_then(|version_index| {
+            let definition = definitions.get(&(version_index & VERSYM_VERSION))?;
+            Some((definition, version_index & VERSYM_HIDDEN != 0))
+        });
+        let definition = definition.filter(|((flags, _), _)| flags & VER_FLG_BASE == 0);
+        let (version, default_version) = match definition {
+            Some(((_, version), hidden)) => (version.clone(), !hidden),
+            None => (None, true),
+        };
+        if let Some(defined_at) = symbols.defined_at.get_mut(index) {
+            *defined_at = Some(symbols.defined.len());
+        }
+        symbols.defined.push(DefinedSymbol {
+            name,
+            version,
+            default_version,
+            binding,
+        });
+    }
+    Ok(symbols)
+}
+
+/// Reads the hash table of the `.dynsym` table of `elf`, preferring `.gnu.hash` to `.hash` as
+/// ld.so does. Tables that are truncated are left out, like missing ones.
+fn read_hash(elf: &mut Object<impl Source>) -> Result<Option<SymbolHash>, ParseError> {
+    let shdrs = &elf.sections;
+    let Some(dynsym) = shdrs.iter().position(|shdr| shdr.sh_type == SHT_DYNSYM) else {
+        return Ok(None);
+    };
+    let table = |sh_type| {
+        shdrs
+            .iter()
+            .find(|shdr| shdr.sh_type == sh_type && shdr.sh_link as usize == dynsym)
+    };
+    let Some(shdr) = table(SHT_GNU_HASH).or_else(|| table(SHT_HASH)).copied() else {
+        return Ok(None);
+    };
+    let header = elf.header;
+    let data = elf.section_data(&shdr)?;
+    Ok(SymbolHash::parse(shdr.sh_type, &header, &data))
+}
+
+/// The version index of each `.dynsym` entry, from the `.gnu.version` section, empty if there
+/// is none.
+fn read_version_indices(elf: &mut Object<impl Source>) -> Result<Vec<u16>, ParseError> {
+    let shdr = elf
+        .sections
+        .iter()
+        .find(|shdr| shdr.sh_type == SHT_GNU_VERSYM);
+    let Some(shdr) = shdr.copied() else {
+        return Ok(Vec::new());
+    };
+    let header = elf.header;
+    let data = elf.section_data(&shdr)?;
+    let mut offset = 0;
+    let indices = std::iter::from_fn(|| header.read_uint(&data, &mut offset, 2));
+    Ok(indices.map(|index| index as u16).collect())
+}
+
+/// Reads the symbol version sections of `elf` into `symbols`, and returns the flags and name of
+/// each version the object defines, by its index.
+fn read_versions(
+    elf: &mut Object<impl Source>,
+    symbols: &mut DynamicSymbols,
+) -> Result<HashMap<u16, (u16, Option<OsString>)>, ParseError> {
+    let section = |elf: &Object<_>, sh_type| {
+        let mut sections = elf.sections.iter();
+        sections.find(|shdr| shdr.sh_type == sh_type).copied()
+    };
+    // Each section's strings are in the section its sh_link names, usually .dynstr
+    let name = |strings: &[u8], offset: u32| {
+        let name = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| strings.get(offset..));
+        OsStr::from_bytes(name.and_then(c_str).unwrap_or_default()).to_owned()
+    };
+
+    if let Some(shdr) = section(elf, SHT_GNU_VERNEED) {
+        let strings = elf.linked_strings(&shdr)?;
+        for need in elf.version_needs(&shdr)? {
+            let versions = need
+                .versions
+                .iter()
+                .filter(|(_, flags)| flags & VER_FLG_WEAK == 0)
+                .map(|&(version, _)| name(&strings, version))
+                .collect();
+            symbols
+                .version_needs
+                .push((name(&strings, need.file), versions));
+        }
+    }
+    let mut definitions = HashMap::new();
+    if let Some(shdr) = section(elf, SHT_GNU_VERDEF) {
+        let strings = elf.linked_strings(&shdr)?;
+        for def in elf.version_defs(&shdr)? {
+            // The first name is the version's own, the others those of its parents
+            let version = def.names.first().map(|&version| name(&strings, version));
+            if let Some(version) = version.as_ref().filter(|_| def.flags & VER_FLG_BASE == 0) {
+                symbols.version_defs.push(version.clone());
+            }
+            // Like ld.so, the first definition of an index wins
+            definitions.entry(def.index).or_insert((def.flags, version));
+        }
+    }
+    Ok(definitions)
+}
+
+/// The symbols `objects` import that none of them export, in the order of `objects` and of
+/// their symbol tables.
+pub(crate) fn undefined_symbols(objects: &[(PathBuf, DynamicSymbols)]) -> Vec<UndefinedSymbol> {
+    let defined: HashSet<&OsStr> = objects
+        .iter()
+        .flat_map(|(_, symbols)| symbols.defined.iter().map(|symbol| symbol.name.as_os_str()))
+        .collect();
+    objects
+        .iter()
+        .flat_map(|(path, symbols)| {
+            symbols
+                .undefined
+                .iter()
+                .filter(|name| !defined.contains(name.as_os_str()))
+                .map(|name| UndefinedSymbol {
+                    name: name.to_string_lossy().into_owned(),
+                    needed_by: path.clone(),
+                })
+        })
+        .collect()
+}
+
+/// The `DT_NEEDED` entries of the objects in `graph` that provide none of the symbols, strong
+/// or weak, nor any of the symbol versions their parent imports, in the order of the nodes and
+/// of their entries. `symbols` holds the symbols of each node, by index, or `None` for those
+/// that could not be read. Parents without a symbol table are left out.
+pub(crate) fn unused_dependencies(
+    graph: &DependencyGraph,
+    symbols: &[Option<DynamicSymbols>],
+) -> Vec<UnusedDependency> {
+    let mut unused = Vec::new();
+    for id in graph.node_ids() {
+        let (Some(needed_by), Some(object)) = (graph.node(id).path(), &symbols[id.index()]) else {
+            continue;
+        };
+        if !object.symbol_table {
+            continue;
+        }
+        for &dependency in graph.dependencies(id) {
+            let node = graph.node(dependency);
+            let (Some(path), Some(library)) = (node.path(), &symbols[dependency.index()]) else {
+                continue;
+            };
+            if node.kind != DependencyKind::Needed {
+                continue;
+            }
+            let provides_symbol = object
+                .undefined
+                .iter()
+                .chain(&object.weak)
+                .any(|name| library.defines(name));
+            let provides_version = object
+                .version_needs
+                .iter()
+                .any(|(soname, _)| soname.to_string_lossy() == node.soname);
+            if !provides_symbol && !provides_version {
+                unused.push(UnusedDependency {
+                    soname: node.soname.clone(),
+                    path: path.to_owned(),
+                    needed_by: needed_by.to_owned(),
+                });
+            }
+        }
+    }
+    unused
+}
+
+/// The `DT_NEEDED` entries of the objects in `graph` that provide weak symbols their parent
+/// imports but no strong ones nor symbol versions, in the order of the nodes and of their
+/// entries, along with the missing libraries whose parent finds all of its strong imports in
+/// `interpreter` or the libraries that were read. `symbols` holds the symbols of each node, by
+/// index, or `None` for those that could not be read. Parents without a symbol table are left
+/// out.
+pub(crate) fn weak_dependencies(
+    graph: &DependencyGraph,
+    symbols: &[Option<DynamicSymbols>],
+    interpreter: Option<&DynamicSymbols>,
+) -> Vec<WeakDependency> {
+    let closure: HashSet<&OsStr> = symbols
+        .iter()
+        .flatten()
+        .chain(interpreter)
+        .flat_map(|symbols| symbols.defined.iter().map(|symbol| symbol.name.as_os_str()))
+        .collect();
+    let mut weak = Vec::new();
+    for id in graph.node_ids() {
+        let (Some(needed_by), Some(object)) = (graph.node(id).path(), &symbols[id.index()]) else {
+            continue;
+        };
+        if !object.symbol_table {
+            continue;
+        }
+        for &dependency in graph.dependencies(id) {
+            let node = graph.node(dependency);
+            if node.kind != DependencyKind::Needed {
+                continue;
+            }
+            let needs_version = object
+                .version_needs
+                .iter()
+                .any(|(soname, _)| soname.to_string_lossy() == node.soname);
+            let is_weak = match (&node.resolution, &symbols[dependency.index()]) {
+                (Ok(_), Some(library)) => {
+                    let provides =
+                        |imports: &[OsString]| imports.iter().any(|name| library.defines(name));
+                    !provides(&object.undefined) && provides(&object.weak)
+                }
+                // What a missing library would have provided is unknown, but it can't be needed
+                // for a strong import if every one is provided by something else
+                (Err(ResolveError::NotFound { .. }), _) => object
+                    .undefined
+                    .iter()
+                    .all(|name| closure.contains(name.as_os_str())),
+                _ => false,
+            };
+            if is_weak && !needs_version {
+                weak.push(WeakDependency {
+                    soname: node.soname.clone(),
+                    path: node.path().map(Path::to_path_buf),
+                    needed_by: needed_by.to_owned(),
+                });
+            }
+        }
+    }
+    weak
+}
+
+/// The definitions of `name` among `objects`, in their order.
+pub(crate) fn find_symbol(
+    objects: &[(PathBuf, DynamicSymbols)],
+    name: &str,
+) -> Vec<SymbolProvider> {
+    objects
+        .iter()
+        .flat_map(|(path, symbols)| {
+            symbols
+                .lookup(OsStr::new(name))
+                .map(|symbol| SymbolProvider {
+                    path: path.clone(),
+                    version: symbol
+                        .version
+                        .as_ref()
+                        .map(|v| v.to_string_lossy().into_owned()),
+                    default_version: symbol.default_version,
+                    binding: symbol.binding,
+                })
+        })
+        .collect()
+}
+
+/// The symbols each of `objects` exports, in their order.
+pub(crate) fn exported_symbols(objects: Vec<(PathBuf, DynamicSymbols)>) -> Vec<ExportedSymbols> {
+    let exported = |symbol: DefinedSymbol| ExportedSymbol {
+        name: symbol.name.to_string_lossy().into_owned(),
+        version: symbol.version.map(|v| v.to_string_lossy().into_owned()),
+        default_version: symbol.default_version,
+        binding: symbol.binding,
+    };
+    objects
+        .into_iter()
+        .map(|(path, symbols)| ExportedSymbols {
+            path,
+            symbols: symbols.defined.into_iter().map(exported).collect(),
+        })
+        .collect()
+}
+
+/// The symbols that more than one of `objects` exports with a default version or none, in the
+/// order they are first exported. Definitions of older versions (`name@V`) are left out, as
+/// only binaries built against them bind to them.
+pub(crate) fn symbol_conflicts(objects: &[(PathBuf, DynamicSymbols)]) -> Vec<SymbolConflict> {
+    let mut conflicts: Vec<SymbolConflict> = Vec::new();
+    let mut positions: HashMap<&OsStr, usize> = HashMap::new();
+    for (path, symbols) in objects {
+        for symbol in symbols
+            .defined
+            .iter()
+            .filter(|symbol| symbol.default_version)
+        {
+            let provider = SymbolProvider {
+                path: path.clone(),
+                version: symbol
+                    .version
+                    .as_ref()
+                    .map(|v| v.to_string_lossy().into_owned()),
+                default_version: true,
+                binding: symbol.binding,
+            };
+            let Some(&position) = positions.get(symbol.name.as_os_str()) else {
+                positions.insert(&symbol.name, conflicts.len());
+                conflicts.push(SymbolConflict {
+                    name: symbol.name.to_string_lossy().into_owned(),
+                    providers: vec![provider],
+                });
+                continue;
+            };
+            let providers = &mut conflicts[position].providers;
+            // An object exports each name once, but be safe with malformed tables
+            if providers.iter().all(|provider| provider.path != *path) {
+                providers.push(provider);
+            }
+        }
+    }
+    conflicts.retain(|conflict| conflict.providers.len() > 1);
+    conflicts
+}
+
+/// The highest glibc version the `objects` require, in load order.
+pub(crate) fn minimum_glibc(objects: &[(PathBuf, DynamicSymbols)]) -> Option<GlibcRequirement> {
+    let objects = objects
+        .iter()
+        .map(|(path, symbols)| (path.as_path(), symbols));
+    let (version, soname, needed_by) = highest_version(objects, "GLIBC_")?;
+    Some(GlibcRequirement {
+        version,
+        soname,
+        needed_by,
+    })
+}
+
+/// The libstdc++ versions the objects in `graph` require, and those the libstdc++ found for
+/// them lacks. `symbols` holds the symbols of each node, by index, as for [`missing_versions`].
+pub(crate) fn libstdcxx_requirements(
+    graph: &DependencyGraph,
+    symbols: &[Option<DynamicSymbols>],
+) -> LibstdcxxRequirements {
+    const PREFIXES: [&str; 2] = ["GLIBCXX_", "CXXABI_"];
+    let objects = graph
+        .load_order()
+        .into_iter()
+        .filter_map(|id| Some((graph.node(id).path()?, symbols[id.index()].as_ref()?)));
+    let objects: Vec<_> = objects.collect();
+    let highest = |prefix| Some(highest_version(objects.iter().copied(), prefix)?.0);
+    // Objects whose search paths differ may find different copies, the first loaded of which
+    // ld.so uses for all of them
+    let libstdcxx = graph
+        .load_order()
+        .into_iter()
+        .map(|id| graph.node(id))
+        .filter(|node| node.soname == LIBSTDCXX_SONAME)
+        .find_map(|node| node.path());
+    let mut missing = missing_versions(graph, symbols);
+    missing.retain(|missing| {
+        let version = OsStr::new(&missing.version);
+        PREFIXES
+            .iter()
+            .any(|prefix| version_number(version, prefix).is_some())
+    });
+    LibstdcxxRequirements {
+        glibcxx: highest(PREFIXES[0]),
+        cxxabi: highest(PREFIXES[1]),
+        libstdcxx: libstdcxx.map(Path::to_owned),
+        missing,
+    }
+}
+
+/// The highest version named `prefix` and then a dotted number, like `GLIBC_2.31`, that any of
+/// `objects` requires, as the number, with the soname of the library it is required from and
+/// the first object requiring it. Versions like `GLIBC_PRIVATE` are not numbered, so they are
+/// left out.
+pub(crate) fn highest_version<'a>(
+    objects: impl IntoIterator<Item = (&'a Path, &'a DynamicSymbols)>,
+    prefix: &str,
+) -> Option<(String, String, PathBuf)> {
+    let mut highest: Option<(Vec<u32>, &OsStr, &OsStr, &Path)> = None;
+    for (path, object) in objects {
+        for (soname, versions) in &object.version_needs {
+            for version in versions {
+                let Some(number) = version_number(version, prefix) else {
+                    continue;
+                };
+                let higher = highest.as_ref().is_none_or(|(highest, ..)| {
+                    compare_versions(&number, highest) == Ordering::Greater
+                });
+                if higher {
+                    highest = Some((number, version, soname, path));
+                }
+            }
+        }
+    }
+    let (_, version, soname, path) = highest?;
+    let version = version.to_string_lossy();
+    Some((
+        version[prefix.len()..].to_owned(),
+        soname.to_string_lossy().into_owned(),
+        path.to_owned(),
+    ))
+}
+
+/// The dotted number of the version `version` named `prefix` and then a number, like
+/// `[2, 31]` for `GLIBC_2.31`.
+pub(crate) fn version_number(version: &OsStr, prefix: &str) -> Option<Vec<u32>> {
+    let number = version.to_str()?.strip_prefix(prefix)?;
+    number.split('.').map(|part| part.parse().ok()).collect()
+}
+
+/// Compares dotted version numbers, in which missing parts count as 0, so `2.3` and `2.3.0`
+/// are the same.
+pub(crate) fn compare_versions(a: &[u32], b: &[u32]) -> Ordering {
+    let part = |version: &[u32], i: usize| version.get(i).copied().unwrap_or(0);
+    (0..a.len().max(b.len()))
+        .map(|i| part(a, i).cmp(&part(b, i)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// The versions the objects in `graph` require that the libraries found for them don't define,
+/// in the order of the nodes and of their `.gnu.version_r` sections. `symbols` holds the
+/// symbols of each node, by index, or `None` for those that could not be read.
+pub(crate) fn missing_versions(
+    graph: &DependencyGraph,
+    symbols: &[Option<DynamicSymbols>],
+) -> Vec<MissingVersion> {
+    let mut missing = Vec::new();
+    for id in graph.node_ids() {
+        let (Some(needed_by), Some(object)) = (graph.node(id).path(), &symbols[id.index()]) else {
+            continue;
+        };
+        for (soname, versions) in &object.version_needs {
+            // The requirement is on whichever library the object's own entry resolved to
+            let soname = soname.to_string_lossy();
+            let provider = graph
+                .dependencies(id)
+                .iter()
+                .find(|&&dependency| graph.node(dependency).soname == soname);
+            let Some(&provider) = provider else {
+                continue;
+            };
+            let (Some(provider_path), Some(provider)) =
+                (graph.node(provider).path(), &symbols[provider.index()])
+            else {
+                continue;
+            };
+            let versions = versions
+                .iter()
+                .filter(|version| !provider.version_defs.contains(version));
+            missing.extend(versions.map(|version| MissingVersion {
+                version: version.to_string_lossy().into_owned(),
+                soname: soname.clone().into_owned(),
+                provider: provider_path.to_owned(),
+                needed_by: needed_by.to_owned(),
+            }));
+        }
+    }
+    missing
+}
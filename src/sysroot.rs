@@ -0,0 +1,160 @@
+//! Path handling for analyzing a foreign root filesystem as if it were mounted at `/`.
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+use crate::filesystem::{FileKind, FileSystem, HostFs};
+
+/// The number of symlinks followed before giving up, matching Linux's `MAXSYMLINKS`.
+pub(crate) const MAX_SYMLINKS: usize = 40;
+
+/// Interprets the absolute path `path` relative to `root`. Relative paths are returned as-is.
+pub(crate) fn rebase(root: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix("/") {
+        Ok(relative) => root.join(relative),
+        Err(_) => path.to_owned(),
+    }
+}
+
+/// Finds the file `path` refers to, where `path` lies inside `root` and any symlinks along it
+/// are followed as if `root` were `/`, so absolute link targets stay inside `root`. Returns the
+/// host path of the file if it exists.
+pub(crate) fn resolve(root: &Path, path: &Path) -> Option<PathBuf> {
+    resolve_in(&HostFs, root, path)
+}
+
+/// Like [`resolve`], but looks up the files in `fs`.
+pub(crate) fn resolve_in(fs: &dyn FileSystem, root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut remaining: VecDeque<OsString> = components(relative).collect();
+    let mut resolved = root.to_owned();
+    let mut depth = 0;
+    let mut symlinks = 0;
+    while let Some(component) = remaining.pop_front() {
+        if component == ".." {
+            // Never climb above the root
+            if depth > 0 {
+                resolved.pop();
+                depth -= 1;
+            }
+            continue;
+        }
+        let next = resolved.join(&component);
+        if fs.metadata(&next).ok()?.kind != FileKind::Symlink {
+            resolved = next;
+            depth += 1;
+            continue;
+        }
+        symlinks += 1;
+        if symlinks > MAX_SYMLINKS {
+            return None;
+        }
+        let target = fs.read_link(&next).ok()?;
+        if target.is_absolute() {
+            resolved = root.to_owned();
+            depth = 0;
+        }
+        for component in components(&target).rev() {
+            remaining.push_front(component);
+        }
+    }
+    Some(resolved)
+}
+
+/// The symlinks followed from `path` to the file it refers to, as (host) paths: the target of
+/// `path`, the target of that, and so on, ending with the file itself. Empty if `path` is not
+/// a symlink. With a `root`, absolute targets are interpreted relative to it, and `..` never
+/// climbs above it. Symlinks in the directories along the way are not listed. The symlinks are
+/// looked up in `fs`.
+pub(crate) fn symlink_chain(fs: &dyn FileSystem, root: Option<&Path>, path: &Path) -> Vec<PathBuf> {
+    let floor = root.unwrap_or(Path::new("/"));
+    let mut chain = Vec::new();
+    let mut current = path.to_owned();
+    while chain.len() < MAX_SYMLINKS {
+        let Ok(target) = fs.read_link(&current) else {
+            break;
+        };
+        let mut next = if target.is_absolute() {
+            floor.to_owned()
+        } else {
+            current.parent().unwrap_or(floor).to_owned()
+        };
+        for component in components(&target) {
+            if component != ".." {
+                next.push(component);
+            } else if next != floor {
+                next.pop();
+            }
+        }
+        chain.push(next.clone());
+        current = next;
+    }
+    chain
+}
+
+/// The normal and `..` components of `path`, ignoring the root and `.` components.
+pub(crate) fn components(path: &Path) -> impl DoubleEndedIterator<Item = OsString> + '_ {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name.to_owned()),
+        Component::ParentDir => Some(OsString::from("..")),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rebase, resolve, symlink_chain};
+    use crate::filesystem::HostFs;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+
+    #[test]
+    fn test_resolve_stays_in_root() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        fs::create_dir_all(root.join("usr/lib")).unwrap();
+        fs::write(root.join("usr/lib/libfoo.so.1.2"), "").unwrap();
+        symlink("usr/lib", root.join("lib")).unwrap();
+        symlink("/usr/lib/libfoo.so.1.2", root.join("usr/lib/libfoo.so.1")).unwrap();
+        symlink(
+            "../../../../../usr/lib/libfoo.so.1",
+            root.join("usr/lib/escape"),
+        )
+        .unwrap();
+        symlink("loop", root.join("loop")).unwrap();
+
+        let expected = Some(root.join("usr/lib/libfoo.so.1.2"));
+        assert_eq!(rebase(root, Path::new("/lib")), root.join("lib"));
+        assert_eq!(resolve(root, &root.join("lib/libfoo.so.1")), expected);
+        assert_eq!(resolve(root, &root.join("usr/lib/escape")), expected);
+        assert_eq!(resolve(root, &root.join("usr/lib/libbar.so.1")), None);
+        assert_eq!(resolve(root, &root.join("loop")), None);
+    }
+
+    #[test]
+    fn test_symlink_chain() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        let lib = root.join("usr/lib");
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(lib.join("libfoo.so.1.2.3"), "").unwrap();
+        symlink("libfoo.so.1.2.3", lib.join("libfoo.so.1.2")).unwrap();
+        symlink("/usr/lib/libfoo.so.1.2", lib.join("libfoo.so.1")).unwrap();
+        symlink("../../../usr/lib/libfoo.so.1", lib.join("libfoo.so")).unwrap();
+
+        assert_eq!(
+            symlink_chain(&HostFs, Some(root), &lib.join("libfoo.so")),
+            [
+                lib.join("libfoo.so.1"),
+                lib.join("libfoo.so.1.2"),
+                lib.join("libfoo.so.1.2.3"),
+            ]
+        );
+        assert!(symlink_chain(&HostFs, Some(root), &lib.join("libfoo.so.1.2.3")).is_empty());
+        assert_eq!(
+            symlink_chain(&HostFs, None, &lib.join("libfoo.so.1.2")),
+            [lib.join("libfoo.so.1.2.3")]
+        );
+    }
+}
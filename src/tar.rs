@@ -0,0 +1,292 @@
+//! A reader of the entries of tar archives in the ustar, GNU and pax formats, locating the
+//! contents of each file without reading them.
+use std::ffi::OsStr;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::archive_fs::too_large;
+use crate::os_str::OsStrExt;
+
+const BLOCK_SIZE: u64 = 512;
+
+/// What a tar entry is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum EntryKind {
+    File,
+    /// A hard link to the earlier entry with this path.
+    HardLink(PathBuf),
+    Symlink(PathBuf),
+    Dir,
+    /// A device, FIFO or other special file, which holds no data.
+    Other,
+}
+
+/// An entry of a tar archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry {
+    /// The path as stored, without a leading `./` or `/` and trailing slashes.
+    pub(crate) path: PathBuf,
+    pub(crate) kind: EntryKind,
+    /// The permission bits.
+    pub(crate) mode: u32,
+    /// Where the contents of the entry start in the stream.
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+}
+
+/// The entries of the tar archive `reader` reads, from `start` up to `end`. Returns an
+/// [`io::ErrorKind::InvalidData`] error if the headers are malformed, or an
+/// [`io::ErrorKind::FileTooLarge`] error if a long path or pax header is over `limit` bytes.
+pub(crate) fn entries<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    limit: u64,
+) -> io::Result<Vec<Entry>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed tar header");
+    let mut entries = Vec::new();
+    // GNU and pax headers carry long paths and sizes for the entry after them
+    let (mut long_path, mut long_link, mut pax_size) = (None, None, None);
+    let mut pos = start;
+    while pos < end {
+        // Archives are made of whole blocks
+        if pos + BLOCK_SIZE > end {
+            return Err(invalid());
+        }
+        let mut header = [0; BLOCK_SIZE as usize];
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut header)?;
+        // The archive ends with zeroed blocks
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        // The checksum is the sum of the header bytes, with its own field taken as spaces
+        let sum: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| u64::from(if (148..156).contains(&i) { b' ' } else { byte }))
+            .sum();
+        if octal(&header[148..156]) != Some(sum) {
+            return Err(invalid());
+        }
+        let size = pax_size.take().or_else(|| number(&header[124..136]));
+        let size = size.ok_or_else(invalid)?;
+        let offset = pos + BLOCK_SIZE;
+        if offset
+            .checked_add(size)
+            .is_none_or(|data_end| data_end > end)
+        {
+            return Err(invalid());
+        }
+        pos = offset + size.next_multiple_of(BLOCK_SIZE);
+        let read_data = |reader: &mut R| -> io::Result<Vec<u8>> {
+            if size > limit {
+                return Err(too_large("tar header", limit));
+            }
+            let mut data = vec![0; usize::try_from(size).map_err(|_| invalid())?];
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut data)?;
+            Ok(data)
+        };
+        let kind = header[156];
+        match kind {
+            b'L' => {
+                long_path = Some(trim_nul(&read_data(reader)?).to_vec());
+                continue;
+            }
+            b'K' => {
+                long_link = Some(trim_nul(&read_data(reader)?).to_vec());
+                continue;
+            }
+            b'x' => {
+                let data = read_data(reader)?;
+                let records = pax_records(&data).ok_or_else(invalid)?;
+                for (key, value) in records {
+                    match key {
+                        b"path" => long_path = Some(value.to_vec()),
+                        b"linkpath" => long_link = Some(value.to_vec()),
+                        b"size" => {
+                            let size = std::str::from_utf8(value)
+                                .ok()
+                                .and_then(|size| size.parse().ok());
+                            pax_size = Some(size.ok_or_else(invalid)?);
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            // Global pax headers and other metadata apply to no entry of their own
+            b'g' | b'A'..=b'Z' => continue,
+            _ => {}
+        }
+        let path = long_path.take().unwrap_or_else(|| {
+            let name = trim_nul(&header[..100]);
+            let prefix = trim_nul(&header[345..500]);
+            // Only ustar archives split long paths into a prefix and a name
+            if header[257..262] == *b"ustar" && !prefix.is_empty() {
+                [prefix, b"/", name].concat()
+            } else {
+                name.to_vec()
+            }
+        });
+        let link = long_link
+            .take()
+            .unwrap_or_else(|| trim_nul(&header[157..257]).to_vec());
+        let link = PathBuf::from(OsStr::from_bytes(&link));
+        let kind = match kind {
+            b'0' | b'\0' | b'7' => EntryKind::File,
+            b'1' => EntryKind::HardLink(normalize(link.as_os_str().as_bytes())),
+            b'2' => EntryKind::Symlink(link),
+            b'5' => EntryKind::Dir,
+            _ => EntryKind::Other,
+        };
+        let mode = number(&header[100..108]).ok_or_else(invalid)? as u32 & 0o7777;
+        let size = if kind == EntryKind::File { size } else { 0 };
+        entries.push(Entry {
+            path: normalize(&path),
+            kind,
+            mode,
+            offset,
+            size,
+        });
+    }
+    Ok(entries)
+}
+
+/// `path` without a leading `./` or `/` and trailing slashes.
+pub(crate) fn normalize(mut path: &[u8]) -> PathBuf {
+    loop {
+        if let Some(rest) = path.strip_prefix(b"./") {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix(b"/") {
+            path = rest;
+        } else {
+            break;
+        }
+    }
+    while let Some(rest) = path.strip_suffix(b"/") {
+        path = rest;
+    }
+    PathBuf::from(OsStr::from_bytes(path))
+}
+
+/// `field` up to its first NUL byte.
+fn trim_nul(field: &[u8]) -> &[u8] {
+    let end = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+    &field[..end]
+}
+
+/// A numeric header field: octal digits padded with spaces or NULs, or, for numbers too big
+/// for them, a big-endian binary number after a byte with the high bit set.
+fn number(field: &[u8]) -> Option<u64> {
+    match field.first() {
+        Some(&first) if first & 0x80 != 0 => {
+            let mut value = u64::from(first & 0x7f);
+            for &byte in &field[1..] {
+                value = value.checked_mul(256)? | u64::from(byte);
+            }
+            Some(value)
+        }
+        _ => octal(field),
+    }
+}
+
+fn octal(field: &[u8]) -> Option<u64> {
+    let digits = trim_nul(field);
+    let digits = std::str::from_utf8(digits).ok()?.trim_matches(' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// The `key=value` records of a pax extended header, each written as `<length> key=value\n`.
+fn pax_records(mut data: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+    let mut records = Vec::new();
+    while !data.is_empty() {
+        let space = data.iter().position(|&byte| byte == b' ')?;
+        let length: usize = std::str::from_utf8(&data[..space]).ok()?.parse().ok()?;
+        let record = data.get(space + 1..length)?.strip_suffix(b"\n")?;
+        let equals = record.iter().position(|&byte| byte == b'=')?;
+        records.push((&record[..equals], &record[equals + 1..]));
+        data = &data[length..];
+    }
+    Some(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entries, Entry, EntryKind};
+    use crate::test_util::{tar, ArchiveEntry};
+    use std::io::{self, Cursor};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_entries() {
+        let long = format!("usr/lib/{}/libfoo.so.1", "x".repeat(120));
+        let archive = tar(&[
+            ArchiveEntry::Dir("./usr/"),
+            ArchiveEntry::File(&long, b"foo"),
+            ArchiveEntry::Symlink("usr/lib/libfoo.so", "libfoo.so.1"),
+            ArchiveEntry::HardLink("/usr/lib/libbar.so.1", "./usr/lib/libfoo.so"),
+        ]);
+        let listed = entries_of(&archive);
+        let summary: Vec<_> = listed
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.kind.clone(), entry.size))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (PathBuf::from("usr"), EntryKind::Dir, 0),
+                (PathBuf::from(&long), EntryKind::File, 3),
+                (
+                    PathBuf::from("usr/lib/libfoo.so"),
+                    EntryKind::Symlink(PathBuf::from("libfoo.so.1")),
+                    0
+                ),
+                (
+                    PathBuf::from("usr/lib/libbar.so.1"),
+                    EntryKind::HardLink(PathBuf::from("usr/lib/libfoo.so")),
+                    0
+                ),
+            ]
+        );
+        let file = &listed[1];
+        assert_eq!(&archive[file.offset as usize..][..3], b"foo");
+        assert_eq!(file.mode, 0o755);
+
+        // A pax header overrides the path of the next entry
+        let pax = tar(&[
+            ArchiveEntry::Pax(b"24 path=opt/libbaz.so.1\n10 size=3\n"),
+            ArchiveEntry::File("short", b"baz"),
+        ]);
+        let listed = entries_of(&pax);
+        assert_eq!(listed[0].path, PathBuf::from("opt/libbaz.so.1"));
+        assert_eq!(listed.len(), 1);
+
+        let mut corrupt = archive.clone();
+        corrupt[0] = b'x';
+        assert!(entries(
+            &mut Cursor::new(&corrupt),
+            0,
+            corrupt.len() as u64,
+            u64::MAX
+        )
+        .is_err());
+        // An entry can't run past the end of the archive
+        assert!(entries(&mut Cursor::new(&archive), 0, 1100, u64::MAX).is_err());
+        // Nor can a long path be kept in memory past the limit
+        let end = archive.len() as u64;
+        let error = entries(&mut Cursor::new(&archive), 0, end, 100).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::FileTooLarge);
+    }
+
+    fn entries_of(archive: &[u8]) -> Vec<Entry> {
+        entries(&mut Cursor::new(archive), 0, archive.len() as u64, u64::MAX).unwrap()
+    }
+}
@@ -0,0 +1,262 @@
+//! Targets named by triples like `aarch64-unknown-linux-gnu`, for resolving dependencies the
+//! way another system's dynamic linker would, whatever the host is.
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::abi::{EM_386, EM_AARCH64, EM_ARM, EM_PPC, EM_PPC64, EM_RISCV, EM_S390, EM_X86_64};
+use crate::parse::Arch;
+use crate::resolver::Libc;
+use crate::{musl, Error, Platform};
+
+/// The system to resolve dependencies for, set with
+/// [`ResolverConfig::target`](crate::ResolverConfig::target): an architecture and the
+/// platform whose dynamic linker loads programs for it, as named by a target triple.
+///
+/// ```
+/// use elf_dynamic_lib_getter::{Platform, Target};
+///
+/// let target = Target::from_triple("aarch64-unknown-linux-musl")?;
+/// assert_eq!(target.platform(), Platform::Musl);
+/// assert_eq!(target.interpreter().to_str(), Some("/lib/ld-musl-aarch64.so.1"));
+/// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Target {
+    triple: String,
+    arch: Arch,
+    platform: Platform,
+    interpreter: String,
+}
+
+impl Target {
+    /// The target named by `triple`, with or without a vendor, like `x86_64-unknown-linux-gnu`,
+    /// `aarch64-linux-gnu`, `armv7-unknown-linux-gnueabihf`, `x86_64-alpine-linux-musl`,
+    /// `aarch64-linux-android` or `x86_64-unknown-freebsd`. The architectures known are
+    /// those of [`Platform`]'s system directories: x86, ARM, RISC-V, PowerPC and s390x.
+    /// # Return Value [Err]
+    /// [`Error::UnknownTarget`] if the architecture or operating system is not known.
+    pub fn from_triple(triple: &str) -> Result<Self, Error> {
+        let unknown = || Error::UnknownTarget {
+            triple: triple.to_owned(),
+        };
+        let mut parts = triple.split('-');
+        let arch_name = parts.next().unwrap_or_default();
+        let rest: Vec<&str> = parts.collect();
+        // The environment follows the operating system, as in `linux-gnueabihf`
+        let env = match rest.iter().position(|&part| part == "linux") {
+            Some(index) => rest.get(index + 1).copied().unwrap_or("gnu"),
+            None if rest.iter().any(|part| part.starts_with("freebsd")) => "freebsd",
+            None => return Err(unknown()),
+        };
+        let platform = if env.starts_with("musl") {
+            Platform::Musl
+        } else if env.starts_with("android") {
+            Platform::Android
+        } else if env == "freebsd" {
+            Platform::FreeBsd
+        } else {
+            Platform::Glibc
+        };
+        let arch = arch_of(arch_name, env).ok_or_else(unknown)?;
+        let interpreter = interpreter_of(arch, platform, env.contains("hf"));
+        Ok(Self {
+            triple: triple.to_owned(),
+            arch,
+            platform,
+            interpreter,
+        })
+    }
+
+    /// The triple this target was named by.
+    pub fn triple(&self) -> &str {
+        &self.triple
+    }
+
+    /// The platform whose dynamic linker loads programs on this target.
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// The path of the dynamic linker programs built for this target name in `PT_INTERP`, like
+    /// `/lib/ld-linux-aarch64.so.1`.
+    pub fn interpreter(&self) -> &Path {
+        Path::new(&self.interpreter)
+    }
+
+    /// The `e_machine` of files built for this target, like `EM_AARCH64`.
+    pub fn machine(&self) -> u16 {
+        self.arch.machine
+    }
+
+    /// Whether files built for this target are 64-bit ELF files.
+    pub fn is_64_bit(&self) -> bool {
+        self.arch.is_64_bit
+    }
+
+    /// Whether files built for this target are big-endian.
+    pub fn is_big_endian(&self) -> bool {
+        self.arch.big_endian
+    }
+
+    pub(crate) fn arch(&self) -> Arch {
+        self.arch
+    }
+
+    /// The C library of programs on this target.
+    pub(crate) fn libc(&self) -> Libc {
+        match self.platform {
+            Platform::Glibc => Libc::Glibc,
+            Platform::Musl => Libc::Musl(self.musl_arch()),
+            Platform::FreeBsd => Libc::FreeBsd,
+            Platform::Android => Libc::Bionic(None),
+        }
+    }
+
+    /// The musl architecture of this target, which names musl's dynamic linker and path file.
+    pub(crate) fn musl_arch(&self) -> &'static str {
+        let file_name = self.interpreter.rsplit('/').next().unwrap_or_default();
+        match self.platform {
+            Platform::Musl => musl::arch_of_interpreter(file_name).unwrap_or_default(),
+            _ => "",
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = Error;
+
+    fn from_str(triple: &str) -> Result<Self, Error> {
+        Self::from_triple(triple)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.triple)
+    }
+}
+
+/// The architecture named by the first component `name` of a triple, whose environment `env`
+/// tells 32-bit x86-64 apart.
+fn arch_of(name: &str, env: &str) -> Option<Arch> {
+    let (machine, is_64_bit, big_endian) = match name {
+        "x86_64" | "amd64" => (EM_X86_64, !env.ends_with("x32"), false),
+        "i386" | "i486" | "i586" | "i686" | "x86" => (EM_386, false, false),
+        "aarch64" | "arm64" => (EM_AARCH64, true, false),
+        "aarch64_be" => (EM_AARCH64, true, true),
+        "powerpc" | "ppc" => (EM_PPC, false, true),
+        "powerpc64" | "ppc64" => (EM_PPC64, true, true),
+        "powerpc64le" | "ppc64le" => (EM_PPC64, true, false),
+        "s390x" => (EM_S390, true, true),
+        _ if name.starts_with("riscv64") => (EM_RISCV, true, false),
+        _ if name.starts_with("riscv32") => (EM_RISCV, false, false),
+        // Like armv7, armebv7r or thumbv7neon
+        _ if name.starts_with("arm") || name.starts_with("thumb") => {
+            (EM_ARM, false, name.contains("eb"))
+        }
+        _ => return None,
+    };
+    Some(Arch {
+        is_64_bit,
+        big_endian,
+        machine,
+    })
+}
+
+/// The dynamic linker of `platform` for `arch`, which for ARM depends on whether the
+/// hard-float ABI is used.
+fn interpreter_of(arch: Arch, platform: Platform, hard_float: bool) -> String {
+    let Arch {
+        is_64_bit,
+        big_endian,
+        machine,
+    } = arch;
+    match platform {
+        Platform::FreeBsd => return "/libexec/ld-elf.so.1".to_owned(),
+        Platform::Android if is_64_bit => return "/system/bin/linker64".to_owned(),
+        Platform::Android => return "/system/bin/linker".to_owned(),
+        Platform::Glibc | Platform::Musl => {}
+    }
+    if platform == Platform::Musl {
+        let musl_arch = match (machine, is_64_bit, big_endian) {
+            (EM_X86_64, true, _) => "x86_64",
+            (EM_X86_64, false, _) => "x32",
+            (EM_386, ..) => "i386",
+            (EM_AARCH64, _, false) => "aarch64",
+            (EM_AARCH64, _, true) => "aarch64_be",
+            (EM_ARM, _, false) if hard_float => "armhf",
+            (EM_ARM, _, false) => "arm",
+            (EM_ARM, _, true) if hard_float => "armebhf",
+            (EM_ARM, _, true) => "armeb",
+            (EM_RISCV, true, _) => "riscv64",
+            (EM_RISCV, false, _) => "riscv32",
+            (EM_PPC, ..) => "powerpc",
+            (EM_PPC64, _, false) => "powerpc64le",
+            (EM_PPC64, _, true) => "powerpc64",
+            _ => "s390x",
+        };
+        return format!("/lib/ld-musl-{musl_arch}.so.1");
+    }
+    let interpreter = match (machine, is_64_bit, big_endian) {
+        (EM_X86_64, true, _) => "/lib64/ld-linux-x86-64.so.2",
+        (EM_X86_64, false, _) => "/libx32/ld-linux-x32.so.2",
+        (EM_386, ..) => "/lib/ld-linux.so.2",
+        (EM_AARCH64, _, false) => "/lib/ld-linux-aarch64.so.1",
+        (EM_AARCH64, _, true) => "/lib/ld-linux-aarch64_be.so.1",
+        (EM_ARM, ..) if hard_float => "/lib/ld-linux-armhf.so.3",
+        (EM_ARM, ..) => "/lib/ld-linux.so.3",
+        (EM_RISCV, true, _) => "/lib/ld-linux-riscv64-lp64d.so.1",
+        (EM_RISCV, false, _) => "/lib/ld-linux-riscv32-ilp32d.so.1",
+        (EM_PPC, ..) => "/lib/ld.so.1",
+        (EM_PPC64, _, false) => "/lib64/ld64.so.2",
+        (EM_PPC64, _, true) => "/lib64/ld64.so.1",
+        _ => "/lib/ld64.so.1",
+    };
+    interpreter.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Target;
+    use crate::abi::{EM_AARCH64, EM_ARM, EM_X86_64};
+    use crate::resolver::Libc;
+    use crate::{Error, Platform};
+
+    #[test]
+    fn test_from_triple() {
+        let target = Target::from_triple("aarch64-unknown-linux-gnu").unwrap();
+        assert_eq!(target.platform(), Platform::Glibc);
+        assert_eq!((target.machine(), target.is_64_bit()), (EM_AARCH64, true));
+        assert_eq!(
+            target.interpreter().to_str(),
+            Some("/lib/ld-linux-aarch64.so.1")
+        );
+        assert_eq!(target.to_string(), "aarch64-unknown-linux-gnu");
+
+        // The vendor is optional, and the environment picks the ABI
+        let target: Target = "armv7-linux-gnueabihf".parse().unwrap();
+        assert_eq!((target.machine(), target.is_64_bit()), (EM_ARM, false));
+        assert_eq!(
+            target.interpreter().to_str(),
+            Some("/lib/ld-linux-armhf.so.3")
+        );
+        let target = Target::from_triple("x86_64-unknown-linux-gnux32").unwrap();
+        assert_eq!((target.machine(), target.is_64_bit()), (EM_X86_64, false));
+
+        let target = Target::from_triple("armv7-alpine-linux-musleabihf").unwrap();
+        assert_eq!(target.libc(), Libc::Musl("armhf"));
+        let target = Target::from_triple("aarch64-linux-android").unwrap();
+        assert_eq!(target.platform(), Platform::Android);
+        assert_eq!(target.interpreter().to_str(), Some("/system/bin/linker64"));
+        let target = Target::from_triple("x86_64-unknown-freebsd14.1").unwrap();
+        assert_eq!(target.libc(), Libc::FreeBsd);
+
+        for triple in ["mips-unknown-linux-gnu", "x86_64-apple-darwin", ""] {
+            assert!(matches!(
+                Target::from_triple(triple),
+                Err(Error::UnknownTarget { triple: unknown }) if unknown == triple
+            ));
+        }
+    }
+}
@@ -0,0 +1,1748 @@
+//! Helpers for writing small synthetic ELF, Mach-O and PE files, the databases package
+//! managers keep and the archives images and packages are made of, and an in-memory file
+//! system, in tests.
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::abi::{
+    DT_FLAGS_1, DT_NEEDED, DT_NULL, DT_RPATH, DT_RUNPATH, DT_SONAME, DT_STRSZ, DT_STRTAB,
+    EM_X86_64, ET_DYN, ET_EXEC, NT_GNU_BUILD_ID, PT_DYNAMIC, PT_INTERP, PT_LOAD, PT_NOTE, PT_NULL,
+    SHT_DYNAMIC, SHT_DYNSYM, SHT_GNU_HASH, SHT_GNU_VERDEF, SHT_GNU_VERNEED, SHT_GNU_VERSYM,
+    SHT_HASH, SHT_NOTE, SHT_PROGBITS, SHT_STRTAB, STB_GLOBAL, STB_WEAK, STT_FUNC, VER_FLG_BASE,
+};
+use crate::filesystem::{FileKind, FileMetadata, FileSystem};
+use crate::macho::{CPU_TYPE_X86_64, LC_LOAD_DYLIB, LC_LOAD_WEAK_DYLIB, LC_RPATH};
+use crate::parse::{DT_AUXILIARY, DT_FILTER};
+use crate::sqlite::Value;
+
+/// A symbol version, and whether it is the default one.
+type SymbolVersion = (String, bool);
+
+/// Builds a minimal shared object with a `.dynamic` section containing the configured entries.
+/// Virtual addresses equal file offsets and a single `PT_LOAD` maps the whole file.
+#[derive(Clone)]
+pub struct ElfBuilder {
+    is_64_bit: bool,
+    machine: u16,
+    dynamic: Vec<(i64, OsString)>,
+    /// Entries whose value is not a string.
+    values: Vec<(i64, u64)>,
+    interpreter: Option<String>,
+    /// `.dynsym` entries: the name, whether the symbol is defined, its binding, and its version
+    /// with whether that is the default one.
+    symbols: Vec<(String, bool, u8, Option<SymbolVersion>)>,
+    /// `.gnu.version_r` entries: a soname and the versions required from it.
+    version_needs: Vec<(String, Vec<String>)>,
+    /// `.gnu.version_d` entries, after the base version.
+    version_defs: Vec<String>,
+    /// The descriptor of the `NT_GNU_BUILD_ID` note.
+    build_id: Option<Vec<u8>>,
+    /// The contents of the `.rodata` section, if any.
+    rodata: Option<Vec<u8>>,
+    /// The `key=value` strings of the `.modinfo` section of a kernel module, if any.
+    modinfo: Vec<String>,
+    /// The JSON descriptors of the notes in the `.note.dlopen` section.
+    dlopen_notes: Vec<String>,
+    /// Empty segments after the others: their type and flags.
+    segments: Vec<(u32, u32)>,
+    e_type: u16,
+    /// The `EI_OSABI` and `EI_ABIVERSION` bytes.
+    os_abi: (u8, u8),
+    section_headers: bool,
+    dynamic_section: bool,
+    /// The type of the hash table of `.dynsym`, `SHT_GNU_HASH` or `SHT_HASH`, if it has one.
+    hash: Option<u32>,
+}
+
+impl ElfBuilder {
+    pub fn new() -> Self {
+        Self {
+            is_64_bit: true,
+            machine: EM_X86_64,
+            dynamic: Vec::new(),
+            values: Vec::new(),
+            interpreter: None,
+            symbols: Vec::new(),
+            version_needs: Vec::new(),
+            version_defs: Vec::new(),
+            build_id: None,
+            rodata: None,
+            modinfo: Vec::new(),
+            dlopen_notes: Vec::new(),
+            segments: Vec::new(),
+            e_type: ET_DYN,
+            os_abi: (0, 0),
+            section_headers: true,
+            dynamic_section: true,
+            hash: None,
+        }
+    }
+
+    pub fn elf32(mut self) -> Self {
+        self.is_64_bit = false;
+        self
+    }
+
+    pub fn machine(mut self, machine: u16) -> Self {
+        self.machine = machine;
+        self
+    }
+
+    pub fn os_abi(mut self, os_abi: u8, version: u8) -> Self {
+        self.os_abi = (os_abi, version);
+        self
+    }
+
+    pub fn needed<S: AsRef<OsStr>>(mut self, soname: S) -> Self {
+        self.dynamic.push((DT_NEEDED, soname.as_ref().to_owned()));
+        self
+    }
+
+    pub fn soname(mut self, soname: &str) -> Self {
+        self.dynamic.push((DT_SONAME, soname.into()));
+        self
+    }
+
+    pub fn rpath<S: AsRef<OsStr>>(mut self, rpath: S) -> Self {
+        self.dynamic.push((DT_RPATH, rpath.as_ref().to_owned()));
+        self
+    }
+
+    pub fn runpath(mut self, runpath: &str) -> Self {
+        self.dynamic.push((DT_RUNPATH, runpath.into()));
+        self
+    }
+
+    pub fn filter(mut self, filtees: &str) -> Self {
+        self.dynamic.push((DT_FILTER, filtees.into()));
+        self
+    }
+
+    pub fn auxiliary(mut self, filtees: &str) -> Self {
+        self.dynamic.push((DT_AUXILIARY, filtees.into()));
+        self
+    }
+
+    /// Adds a `PT_INTERP` segment naming `interpreter`.
+    pub fn interpreter(mut self, interpreter: &str) -> Self {
+        self.interpreter = Some(interpreter.to_owned());
+        self
+    }
+
+    pub fn flags_1(self, flags: i64) -> Self {
+        self.value(DT_FLAGS_1, flags as u64)
+    }
+
+    /// Adds an entry with a raw value, e.g. a string table offset that is out of bounds.
+    pub fn value(mut self, tag: i64, value: u64) -> Self {
+        self.values.push((tag, value));
+        self
+    }
+
+    /// Exports the function `name`.
+    pub fn defines(mut self, name: &str) -> Self {
+        self.symbols.push((name.to_owned(), true, STB_GLOBAL, None));
+        self
+    }
+
+    /// Exports the function `name` as a weak definition.
+    pub fn defines_weak(mut self, name: &str) -> Self {
+        self.symbols.push((name.to_owned(), true, STB_WEAK, None));
+        self
+    }
+
+    /// Exports the function `name` with the symbol version `version`, defining that too, as
+    /// `name@@version` if `default` is set and as `name@version` otherwise.
+    pub fn defines_versioned(mut self, name: &str, version: &str, default: bool) -> Self {
+        if !self.version_defs.iter().any(|defined| defined == version) {
+            self.version_defs.push(version.to_owned());
+        }
+        let version = Some((version.to_owned(), default));
+        self.symbols
+            .push((name.to_owned(), true, STB_GLOBAL, version));
+        self
+    }
+
+    /// Imports the function `name`.
+    pub fn imports(mut self, name: &str) -> Self {
+        self.symbols
+            .push((name.to_owned(), false, STB_GLOBAL, None));
+        self
+    }
+
+    /// Imports the function `name` as a weak reference, which may stay undefined.
+    pub fn imports_weak(mut self, name: &str) -> Self {
+        self.symbols.push((name.to_owned(), false, STB_WEAK, None));
+        self
+    }
+
+    /// Requires the symbol version `version` from the library `soname`.
+    pub fn needs_version(mut self, soname: &str, version: &str) -> Self {
+        match self
+            .version_needs
+            .iter_mut()
+            .find(|(name, _)| name == soname)
+        {
+            Some((_, versions)) => versions.push(version.to_owned()),
+            None => self
+                .version_needs
+                .push((soname.to_owned(), vec![version.to_owned()])),
+        }
+        self
+    }
+
+    /// Defines the symbol version `version`.
+    pub fn defines_version(mut self, version: &str) -> Self {
+        self.version_defs.push(version.to_owned());
+        self
+    }
+
+    /// Adds a `.note.gnu.build-id` section, and its `PT_NOTE` segment, with the build ID `id`.
+    pub fn build_id(mut self, id: &[u8]) -> Self {
+        self.build_id = Some(id.to_vec());
+        self
+    }
+
+    /// Adds a note to the `.note.dlopen` section, which is added if needed, declaring the
+    /// features in `json`.
+    pub fn dlopen_note(mut self, json: &str) -> Self {
+        self.dlopen_notes.push(json.to_owned());
+        self
+    }
+
+    /// Adds a `.rodata` section holding `data`.
+    pub fn rodata(mut self, data: &[u8]) -> Self {
+        self.rodata = Some(data.to_vec());
+        self
+    }
+
+    /// Adds `key=value` to the `.modinfo` section, which is added if needed, as kernel
+    /// modules declare their dependencies and version in.
+    pub fn modinfo(mut self, key: &str, value: &str) -> Self {
+        self.modinfo.push(format!("{key}={value}"));
+        self
+    }
+
+    /// Adds an empty segment of type `p_type`, like `PT_GNU_STACK`, with the flags `flags`.
+    pub fn segment(mut self, p_type: u32, flags: u32) -> Self {
+        self.segments.push((p_type, flags));
+        self
+    }
+
+    /// Makes the file an `ET_EXEC` executable, which is not position-independent.
+    pub fn position_dependent(mut self) -> Self {
+        self.e_type = ET_EXEC;
+        self
+    }
+
+    /// Sets the ELF type to `e_type`, like `ET_REL` for a relocatable object.
+    pub fn e_type(mut self, e_type: u16) -> Self {
+        self.e_type = e_type;
+        self
+    }
+
+    /// Adds a `.gnu.hash` table of the symbols, as linked with `--hash-style=gnu`.
+    pub fn gnu_hash(mut self) -> Self {
+        self.hash = Some(SHT_GNU_HASH);
+        self
+    }
+
+    /// Adds a `.hash` table of the symbols, as linked with `--hash-style=sysv`.
+    pub fn sysv_hash(mut self) -> Self {
+        self.hash = Some(SHT_HASH);
+        self
+    }
+
+    /// Omits the section header table, like `sstrip` does.
+    pub fn without_section_headers(mut self) -> Self {
+        self.section_headers = false;
+        self
+    }
+
+    /// Hides the dynamic array from both the program and section headers, like a statically
+    /// linked executable.
+    pub fn without_dynamic_section(mut self) -> Self {
+        self.dynamic_section = false;
+        self
+    }
+
+    /// Writes the file to `dir/name`, creating `dir` if needed, and returns its path.
+    pub fn write<P: AsRef<Path>, N: AsRef<Path>>(&self, dir: P, name: N) -> PathBuf {
+        write_file(dir.as_ref(), name.as_ref(), &self.build())
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let (ehdr_size, phdr_size, shdr_size, dyn_size, sym_size) = if self.is_64_bit {
+            (64, 56, 64, 16, 24)
+        } else {
+            (52, 32, 40, 8, 16)
+        };
+
+        // String tables
+        let mut dynstr = vec![0u8];
+        let mut string = |value: &[u8]| {
+            let offset = dynstr.len() as u32;
+            dynstr.extend_from_slice(value);
+            dynstr.push(0);
+            offset
+        };
+        let mut dyn_entries = Vec::new();
+        for (tag, value) in &self.dynamic {
+            dyn_entries.push((*tag, string(value.as_bytes()).into()));
+        }
+        dyn_entries.extend_from_slice(&self.values);
+        let mut symbols = Vec::new();
+        for (name, defined, bind, _) in &self.symbols {
+            symbols.push((string(name.as_bytes()), *defined, *bind));
+        }
+        let mut version_needs = Vec::new();
+        for (soname, versions) in &self.version_needs {
+            let soname = string(soname.as_bytes());
+            let versions: Vec<u32> = versions.iter().map(|v| string(v.as_bytes())).collect();
+            version_needs.push((soname, versions));
+        }
+        let mut version_defs = Vec::new();
+        if !self.version_defs.is_empty() {
+            // The base version, which stands for the object itself, comes first
+            version_defs.push(string(b"base"));
+            for version in &self.version_defs {
+                version_defs.push(string(version.as_bytes()));
+            }
+        }
+        let shstrtab = b"\0.dynstr\0.dynamic\0.shstrtab\0.dynsym\0.gnu.version_r\0.gnu.version_d\0\
+            .gnu.version\0.note.gnu.build-id\0.rodata\0.note.dlopen\0.modinfo\0.gnu.hash\0";
+
+        // Sections after .dynamic: name offset, type, contents, entry size and sh_info
+        let mut extra_sections: Vec<(u32, u32, Vec<u8>, u64, u32)> = Vec::new();
+        if !symbols.is_empty() {
+            let mut dynsym = Writer {
+                bytes: Vec::new(),
+                is_64_bit: self.is_64_bit,
+            };
+            dynsym.sym(0, 0, 0);
+            for (name, defined, bind) in symbols {
+                // Defined symbols point into .dynamic, as good a section as any
+                dynsym.sym(name, bind << 4 | STT_FUNC, if defined { 2 } else { 0 });
+            }
+            extra_sections.push((28, SHT_DYNSYM, dynsym.bytes, sym_size as u64, 1));
+        }
+        if let (Some(sh_type), false) = (self.hash, self.symbols.is_empty()) {
+            let mut hash = Writer {
+                bytes: Vec::new(),
+                is_64_bit: self.is_64_bit,
+            };
+            // Every symbol is in the one bucket, in the order of .dynsym
+            let count = self.symbols.len() as u32;
+            if sh_type == SHT_GNU_HASH {
+                let hashes: Vec<u32> = self
+                    .symbols
+                    .iter()
+                    .map(|(name, ..)| crate::symbols::gnu_hash(name.as_bytes()))
+                    .collect();
+                let (bits, shift) = (if self.is_64_bit { 64 } else { 32 }, 6);
+                let bloom = hashes.iter().fold(0u64, |bloom, hash| {
+                    bloom | 1 << (hash % bits) | 1 << ((hash >> shift) % bits)
+                });
+                for value in [1, 1, 1, shift] {
+                    hash.u32(value);
+                }
+                hash.word(bloom);
+                hash.u32(1);
+                for (i, value) in hashes.iter().enumerate() {
+                    let last = i == hashes.len() - 1;
+                    hash.u32(if last { value | 1 } else { value & !1 });
+                }
+                extra_sections.push((128, SHT_GNU_HASH, hash.bytes, 0, 0));
+            } else {
+                hash.u32(1);
+                hash.u32(count + 1);
+                hash.u32(count);
+                for index in 0..=count {
+                    hash.u32(index.saturating_sub(1));
+                }
+                extra_sections.push((132, SHT_HASH, hash.bytes, 4, 0));
+            }
+        }
+        if !version_needs.is_empty() {
+            let mut verneed = Writer {
+                bytes: Vec::new(),
+                is_64_bit: self.is_64_bit,
+            };
+            let mut index = 2;
+            for (i, (soname, versions)) in version_needs.iter().enumerate() {
+                let last = i == version_needs.len() - 1;
+                verneed.u16(1);
+                verneed.u16(versions.len() as u16);
+                verneed.u32(*soname);
+                verneed.u32(16);
+                verneed.u32(if last {
+                    0
+                } else {
+                    16 + 16 * versions.len() as u32
+                });
+                for (j, version) in versions.iter().enumerate() {
+                    verneed.u32(0); // vna_hash
+                    verneed.u16(0); // vna_flags
+                    verneed.u16(index);
+                    verneed.u32(*version);
+                    verneed.u32(if j == versions.len() - 1 { 0 } else { 16 });
+                    index += 1;
+                }
+            }
+            let count = version_needs.len() as u32;
+            extra_sections.push((36, SHT_GNU_VERNEED, verneed.bytes, 0, count));
+        }
+        if !version_defs.is_empty() {
+            let mut verdef = Writer {
+                bytes: Vec::new(),
+                is_64_bit: self.is_64_bit,
+            };
+            for (i, version) in version_defs.iter().enumerate() {
+                let last = i == version_defs.len() - 1;
+                verdef.u16(1);
+                verdef.u16(if i == 0 { VER_FLG_BASE } else { 0 });
+                verdef.u16(i as u16 + 1);
+                verdef.u16(1);
+                verdef.u32(0); // vd_hash
+                verdef.u32(20);
+                verdef.u32(if last { 0 } else { 28 });
+                verdef.u32(*version);
+                verdef.u32(0);
+            }
+            let count = version_defs.len() as u32;
+            extra_sections.push((51, SHT_GNU_VERDEF, verdef.bytes, 0, count));
+        }
+        if self
+            .symbols
+            .iter()
+            .any(|(_, _, _, version)| version.is_some())
+        {
+            let mut versym = Writer {
+                bytes: Vec::new(),
+                is_64_bit: self.is_64_bit,
+            };
+            // The null symbol is local, and unversioned symbols have the base version
+            versym.u16(0);
+            for (_, _, _, version) in &self.symbols {
+                versym.u16(match version {
+                    Some((version, default)) => {
+                        let index = self.version_defs.iter().position(|v| v == version);
+                        (index.unwrap() as u16 + 2) | if *default { 0 } else { 0x8000 }
+                    }
+                    None => 1,
+                });
+            }
+            extra_sections.push((66, SHT_GNU_VERSYM, versym.bytes, 2, 0));
+        }
+
+        if let Some(rodata) = &self.rodata {
+            extra_sections.push((98, SHT_PROGBITS, rodata.clone(), 0, 0));
+        }
+
+        if !self.modinfo.is_empty() {
+            let mut modinfo = Vec::new();
+            for entry in &self.modinfo {
+                modinfo.extend_from_slice(entry.as_bytes());
+                modinfo.push(0);
+            }
+            extra_sections.push((119, SHT_PROGBITS, modinfo, 0, 0));
+        }
+
+        if !self.dlopen_notes.is_empty() {
+            let mut notes = Writer {
+                bytes: Vec::new(),
+                is_64_bit: self.is_64_bit,
+            };
+            for json in &self.dlopen_notes {
+                notes.u32(4);
+                notes.u32(json.len() as u32 + 1);
+                notes.u32(0x407c_0c0a);
+                notes.bytes.extend_from_slice(b"FDO\0");
+                notes.bytes.extend_from_slice(json.as_bytes());
+                notes.bytes.push(0);
+                notes.bytes.resize(align(notes.bytes.len(), 4), 0);
+            }
+            extra_sections.push((106, SHT_NOTE, notes.bytes, 0, 0));
+        }
+
+        if let Some(id) = &self.build_id {
+            let mut note = Writer {
+                bytes: Vec::new(),
+                is_64_bit: self.is_64_bit,
+            };
+            note.u32(4);
+            note.u32(id.len() as u32);
+            note.u32(NT_GNU_BUILD_ID as u32);
+            note.bytes.extend_from_slice(b"GNU\0");
+            note.bytes.extend_from_slice(id);
+            note.bytes.resize(align(note.bytes.len(), 4), 0);
+            extra_sections.push((79, SHT_NOTE, note.bytes, 0, 0));
+        }
+
+        let mut interp = self.interpreter.clone().unwrap_or_default().into_bytes();
+        if self.interpreter.is_some() {
+            interp.push(0);
+        }
+
+        // Layout: ehdr, phdrs, interpreter, .dynstr, .dynamic, the other sections, .shstrtab,
+        // shdrs
+        let phoff = ehdr_size;
+        let phnum = 2
+            + usize::from(self.interpreter.is_some())
+            + usize::from(self.build_id.is_some())
+            + self.segments.len();
+        let interp_off = phoff + phnum * phdr_size;
+        let dynstr_off = interp_off + interp.len();
+        let dynamic_off = align(dynstr_off + dynstr.len(), 8);
+        dyn_entries.push((DT_STRTAB, dynstr_off as u64));
+        dyn_entries.push((DT_STRSZ, dynstr.len() as u64));
+        dyn_entries.push((DT_NULL, 0));
+        let dynamic_len = dyn_entries.len() * dyn_size;
+        let mut extra_offsets = Vec::new();
+        let mut shstrtab_off = dynamic_off + dynamic_len;
+        for (_, _, contents, _, _) in &extra_sections {
+            extra_offsets.push(shstrtab_off);
+            shstrtab_off = align(shstrtab_off + contents.len(), 8);
+        }
+        let shoff = align(shstrtab_off + shstrtab.len(), 8);
+        let shnum = if self.section_headers {
+            4 + extra_sections.len()
+        } else {
+            0
+        };
+        let file_len = shoff + shnum * shdr_size;
+        let mut out = Writer {
+            bytes: Vec::with_capacity(file_len),
+            is_64_bit: self.is_64_bit,
+        };
+        // e_ident
+        out.bytes.extend_from_slice(&[0x7f, b'E', b'L', b'F']);
+        out.bytes.push(if self.is_64_bit { 2 } else { 1 });
+        out.bytes
+            .extend_from_slice(&[1, 1, self.os_abi.0, self.os_abi.1]);
+        out.bytes.resize(16, 0);
+        out.u16(self.e_type);
+        out.u16(self.machine);
+        out.u32(1);
+        out.word(0); // e_entry
+        out.word(phoff as u64);
+        out.word(if self.section_headers {
+            shoff as u64
+        } else {
+            0
+        });
+        out.u32(0); // e_flags
+        out.u16(ehdr_size as u16);
+        out.u16(phdr_size as u16);
+        out.u16(phnum as u16);
+        out.u16(shdr_size as u16);
+        out.u16(shnum as u16);
+        out.u16(if self.section_headers { 3 } else { 0 }); // e_shstrndx
+
+        out.phdr(PT_LOAD, 0, file_len as u64, 4 | 2, 8);
+        let dynamic_type = if self.dynamic_section {
+            PT_DYNAMIC
+        } else {
+            PT_NULL
+        };
+        out.phdr(
+            dynamic_type,
+            dynamic_off as u64,
+            dynamic_len as u64,
+            4 | 2,
+            8,
+        );
+        if self.interpreter.is_some() {
+            out.phdr(PT_INTERP, interp_off as u64, interp.len() as u64, 4, 1);
+        }
+        // The note is the last of the other sections
+        if self.build_id.is_some() {
+            let (_, _, note, _, _) = extra_sections.last().unwrap();
+            let offset = *extra_offsets.last().unwrap() as u64;
+            out.phdr(PT_NOTE, offset, note.len() as u64, 4, 4);
+        }
+        for &(p_type, flags) in &self.segments {
+            out.phdr(p_type, 0, 0, flags, 8);
+        }
+
+        out.bytes.extend_from_slice(&interp);
+        out.bytes.extend_from_slice(&dynstr);
+        out.bytes.resize(dynamic_off, 0);
+        for (tag, value) in dyn_entries {
+            out.word(tag as u64);
+            out.word(value);
+        }
+        for ((_, _, contents, _, _), offset) in extra_sections.iter().zip(&extra_offsets) {
+            out.bytes.resize(*offset, 0);
+            out.bytes.extend_from_slice(contents);
+        }
+        out.bytes.resize(shstrtab_off, 0);
+        out.bytes.extend_from_slice(shstrtab);
+        out.bytes.resize(shoff, 0);
+        if !self.section_headers {
+            return out.bytes;
+        }
+
+        out.shdr(0, 0, 0, 0, 0, 0, 0, 0);
+        out.shdr(
+            1,
+            SHT_STRTAB,
+            dynstr_off as u64,
+            dynstr.len() as u64,
+            0,
+            0,
+            1,
+            0,
+        );
+        out.shdr(
+            9,
+            if self.dynamic_section {
+                SHT_DYNAMIC
+            } else {
+                SHT_PROGBITS
+            },
+            dynamic_off as u64,
+            dynamic_len as u64,
+            1,
+            0,
+            8,
+            dyn_size as u64,
+        );
+        out.shdr(
+            18,
+            SHT_STRTAB,
+            shstrtab_off as u64,
+            shstrtab.len() as u64,
+            0,
+            0,
+            1,
+            0,
+        );
+        for ((name, sh_type, contents, entsize, info), offset) in
+            extra_sections.iter().zip(extra_offsets)
+        {
+            let size = contents.len() as u64;
+            let align = if *sh_type == SHT_NOTE { 4 } else { 8 };
+            // Hash tables are of .dynsym, the first of these sections
+            let link = if matches!(*sh_type, SHT_GNU_HASH | SHT_HASH) {
+                4
+            } else {
+                1
+            };
+            out.shdr(
+                *name,
+                *sh_type,
+                offset as u64,
+                size,
+                link,
+                *info,
+                align,
+                *entsize,
+            );
+        }
+        out.bytes
+    }
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+    is_64_bit: bool,
+}
+
+impl Writer {
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Writes an address/offset/size field, whose width depends on the ELF class.
+    fn word(&mut self, value: u64) {
+        if self.is_64_bit {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        } else {
+            self.u32(value as u32);
+        }
+    }
+
+    fn phdr(&mut self, p_type: u32, offset: u64, size: u64, flags: u32, align: u64) {
+        if self.is_64_bit {
+            self.u32(p_type);
+            self.u32(flags);
+            self.word(offset);
+            self.word(offset);
+            self.word(offset);
+            self.word(size);
+            self.word(size);
+            self.word(align);
+        } else {
+            self.u32(p_type);
+            self.word(offset);
+            self.word(offset);
+            self.word(offset);
+            self.word(size);
+            self.word(size);
+            self.u32(flags);
+            self.word(align);
+        }
+    }
+
+    fn sym(&mut self, name: u32, info: u8, shndx: u16) {
+        self.u32(name);
+        if self.is_64_bit {
+            self.bytes.extend_from_slice(&[info, 0]);
+            self.u16(shndx);
+            self.word(0);
+            self.word(0);
+        } else {
+            self.word(0);
+            self.word(0);
+            self.bytes.extend_from_slice(&[info, 0]);
+            self.u16(shndx);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn shdr(
+        &mut self,
+        name: u32,
+        sh_type: u32,
+        offset: u64,
+        size: u64,
+        link: u32,
+        info: u32,
+        align: u64,
+        entsize: u64,
+    ) {
+        self.u32(name);
+        self.u32(sh_type);
+        self.word(if sh_type == SHT_DYNAMIC { 3 } else { 0 }); // sh_flags
+        self.word(offset); // sh_addr
+        self.word(offset);
+        self.word(size);
+        self.u32(link);
+        self.u32(info);
+        self.word(align);
+        self.word(entsize);
+    }
+}
+
+/// Builds a little-endian 64-bit Mach-O dylib with the configured load commands.
+pub struct MachOBuilder {
+    cputype: u32,
+    commands: Vec<(u32, String)>,
+}
+
+impl MachOBuilder {
+    pub fn new() -> Self {
+        Self {
+            cputype: CPU_TYPE_X86_64,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn cputype(mut self, cputype: u32) -> Self {
+        self.cputype = cputype;
+        self
+    }
+
+    pub fn dylib(mut self, install_name: &str) -> Self {
+        self.commands.push((LC_LOAD_DYLIB, install_name.to_owned()));
+        self
+    }
+
+    pub fn weak_dylib(mut self, install_name: &str) -> Self {
+        self.commands
+            .push((LC_LOAD_WEAK_DYLIB, install_name.to_owned()));
+        self
+    }
+
+    pub fn rpath(mut self, rpath: &str) -> Self {
+        self.commands.push((LC_RPATH, rpath.to_owned()));
+        self
+    }
+
+    /// Writes the file to `dir/name`, creating `dir` if needed, and returns its path.
+    pub fn write<P: AsRef<Path>>(&self, dir: P, name: &str) -> PathBuf {
+        write_file(dir.as_ref(), name, &self.build())
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut commands = Vec::new();
+        for (cmd, string) in &self.commands {
+            // `dylib_command` has a timestamp and two versions after the name offset
+            let fields: &[u32] = if *cmd == LC_RPATH {
+                &[12]
+            } else {
+                &[24, 2, 0x10000, 0x10000]
+            };
+            let cmdsize = align(8 + fields.len() * 4 + string.len() + 1, 8);
+            let start = commands.len();
+            commands.extend_from_slice(&cmd.to_le_bytes());
+            commands.extend_from_slice(&(cmdsize as u32).to_le_bytes());
+            for field in fields {
+                commands.extend_from_slice(&field.to_le_bytes());
+            }
+            commands.extend_from_slice(string.as_bytes());
+            commands.resize(start + cmdsize, 0);
+        }
+        // mach_header_64: magic, cputype, cpusubtype, MH_DYLIB, ncmds, sizeofcmds, flags
+        let header = [0xfeedfacf, self.cputype, 3, 6, self.commands.len() as u32];
+        let mut out: Vec<u8> = header
+            .iter()
+            .flat_map(|field| field.to_le_bytes())
+            .collect();
+        out.extend_from_slice(&(commands.len() as u32).to_le_bytes());
+        out.resize(32, 0);
+        out.extend_from_slice(&commands);
+        out
+    }
+}
+
+/// Writes a universal file holding `slices` to `dir/name`, and returns its path.
+pub fn fat_macho(dir: &Path, name: &str, slices: &[Vec<u8>]) -> PathBuf {
+    let mut out = Vec::new();
+    out.extend_from_slice(&0xcafebabe_u32.to_be_bytes());
+    out.extend_from_slice(&(slices.len() as u32).to_be_bytes());
+    let mut offset = align(8 + slices.len() * 20, 4096);
+    for slice in slices {
+        // fat_arch: cputype, cpusubtype, offset, size, align
+        let cputype = u32::from_le_bytes(slice[4..8].try_into().unwrap());
+        for field in [cputype, 3, offset as u32, slice.len() as u32, 12] {
+            out.extend_from_slice(&field.to_be_bytes());
+        }
+        offset = align(offset + slice.len(), 4096);
+    }
+    for slice in slices {
+        out.resize(align(out.len(), 4096), 0);
+        out.extend_from_slice(slice);
+    }
+    write_file(dir, name, &out)
+}
+
+pub fn write_file<N: AsRef<Path>>(dir: &Path, name: N, contents: &[u8]) -> PathBuf {
+    fs::create_dir_all(dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Builds a PE file with a single section holding its import tables.
+pub struct PeBuilder {
+    is_pe32_plus: bool,
+    imports: Vec<String>,
+    delay_imports: Vec<String>,
+}
+
+impl PeBuilder {
+    pub fn new() -> Self {
+        Self {
+            is_pe32_plus: true,
+            imports: Vec::new(),
+            delay_imports: Vec::new(),
+        }
+    }
+
+    /// Makes a 32-bit x86 file instead of an x86-64 one.
+    pub fn pe32(mut self) -> Self {
+        self.is_pe32_plus = false;
+        self
+    }
+
+    pub fn import(mut self, dll: &str) -> Self {
+        self.imports.push(dll.to_owned());
+        self
+    }
+
+    pub fn delay_import(mut self, dll: &str) -> Self {
+        self.delay_imports.push(dll.to_owned());
+        self
+    }
+
+    /// Writes the file to `dir/name`, creating `dir` if needed, and returns its path.
+    pub fn write<P: AsRef<Path>>(&self, dir: P, name: &str) -> PathBuf {
+        write_file(dir.as_ref(), name, &self.build())
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        const SECTION_RVA: u32 = 0x1000;
+        const SECTION_OFFSET: usize = 0x400;
+
+        // Section contents: import descriptors, delay-load descriptors, then the names
+        let delay_rva = SECTION_RVA + (self.imports.len() as u32 + 1) * 20;
+        let names_rva = delay_rva + (self.delay_imports.len() as u32 + 1) * 32;
+        let mut names = Vec::new();
+        let mut section = Vec::new();
+        let mut descriptor = |section: &mut Vec<u8>, fields: &[u32], dll: &str| {
+            for field in fields {
+                section.extend_from_slice(&field.to_le_bytes());
+            }
+            let name_rva = names_rva + names.len() as u32;
+            names.extend_from_slice(dll.as_bytes());
+            names.push(0);
+            name_rva
+        };
+        for dll in &self.imports {
+            let start = section.len();
+            let name = descriptor(&mut section, &[0, 0, 0], dll);
+            section.extend_from_slice(&name.to_le_bytes());
+            section.resize(start + 20, 0);
+        }
+        section.resize(section.len() + 20, 0);
+        for dll in &self.delay_imports {
+            // Attributes: the descriptor holds RVAs
+            let start = section.len();
+            let name = descriptor(&mut section, &[1], dll);
+            section.extend_from_slice(&name.to_le_bytes());
+            section.resize(start + 32, 0);
+        }
+        section.resize(section.len() + 32, 0);
+        section.extend_from_slice(&names);
+
+        let (machine, magic, directories_offset) = if self.is_pe32_plus {
+            (0x8664_u16, 0x20b_u16, 112)
+        } else {
+            (0x14c, 0x10b, 96)
+        };
+        let optional_size = directories_offset + 16 * 8;
+        let mut optional = vec![0; optional_size];
+        optional[..2].copy_from_slice(&magic.to_le_bytes());
+        optional[directories_offset - 4..directories_offset].copy_from_slice(&16_u32.to_le_bytes());
+        let mut directory = |index: usize, rva: u32| {
+            let entry = directories_offset + index * 8;
+            optional[entry..entry + 4].copy_from_slice(&rva.to_le_bytes());
+        };
+        directory(1, SECTION_RVA);
+        if !self.delay_imports.is_empty() {
+            directory(13, delay_rva);
+        }
+
+        let mut out = vec![0; 0x40];
+        out[..2].copy_from_slice(b"MZ");
+        out[0x3c..0x40].copy_from_slice(&0x40_u32.to_le_bytes());
+        out.extend_from_slice(b"PE\0\0");
+        // COFF header: machine, one section, no symbols, optional header size, characteristics
+        out.extend_from_slice(&machine.to_le_bytes());
+        out.extend_from_slice(&1_u16.to_le_bytes());
+        out.resize(out.len() + 12, 0);
+        out.extend_from_slice(&(optional_size as u16).to_le_bytes());
+        out.extend_from_slice(&0x2022_u16.to_le_bytes());
+        out.extend_from_slice(&optional);
+        // Section header
+        out.extend_from_slice(b".idata\0\0");
+        let size = section.len() as u32;
+        for field in [size, SECTION_RVA, size, SECTION_OFFSET as u32] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        out.resize(SECTION_OFFSET, 0);
+        out.extend_from_slice(&section);
+        out
+    }
+}
+
+/// The page size of [`sqlite_database`] files.
+const PAGE_SIZE: usize = 512;
+
+/// Encodes a record of `values`.
+pub fn encode_record(values: &[Value]) -> Vec<u8> {
+    let (mut header, mut body) = (Vec::new(), Vec::new());
+    for value in values {
+        let serial_type = match value {
+            Value::Null => 0,
+            Value::Integer(value) => {
+                body.extend_from_slice(&value.to_be_bytes());
+                6
+            }
+            Value::Float(value) => {
+                body.extend_from_slice(&value.to_be_bytes());
+                7
+            }
+            Value::Text(text) => {
+                body.extend_from_slice(text);
+                text.len() * 2 + 13
+            }
+            Value::Blob(blob) => {
+                body.extend_from_slice(blob);
+                blob.len() * 2 + 12
+            }
+        };
+        header.extend(encode_varint(serial_type as u64));
+    }
+    let mut record = encode_varint(header.len() as u64 + 1);
+    assert_eq!(
+        record.len(),
+        1,
+        "records with large headers are not supported"
+    );
+    record.extend(header);
+    record.extend(body);
+    record
+}
+
+fn encode_varint(mut value: u64) -> Vec<u8> {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.reverse();
+    bytes
+}
+
+/// A table page of `kind` holding `cells`, which start at `header` on the page.
+fn page(header: usize, kind: u8, cells: &[Vec<u8>], right: Option<u32>) -> Vec<u8> {
+    let mut page = vec![0; header];
+    page.push(kind);
+    page.extend_from_slice(&[0, 0]);
+    page.extend_from_slice(&(cells.len() as u16).to_be_bytes());
+    page.extend_from_slice(&[0, 0, 0]);
+    if let Some(right) = right {
+        page.extend_from_slice(&right.to_be_bytes());
+    }
+    let mut offset = page.len() + cells.len() * 2;
+    for cell in cells {
+        page.extend_from_slice(&(offset as u16).to_be_bytes());
+        offset += cell.len();
+    }
+    for cell in cells {
+        page.extend_from_slice(cell);
+    }
+    assert!(page.len() <= PAGE_SIZE);
+    page.resize(PAGE_SIZE, 0);
+    page
+}
+
+fn leaf_cell(rowid: i64, payload: &[u8]) -> Vec<u8> {
+    let mut cell = encode_varint(payload.len() as u64);
+    cell.extend(encode_varint(rowid as u64));
+    cell.extend_from_slice(payload);
+    cell
+}
+
+/// A database with the table `name` holding one row per payload, each with the blob as its
+/// second column. Rows in an interior page's left child come first, with the rest in its
+/// right child. A payload too large for a page goes on as many overflow pages as needed.
+pub fn sqlite_database(name: &str, blobs: &[&[u8]]) -> Vec<u8> {
+    let schema = encode_record(&[
+        Value::Text(b"table"),
+        Value::Text(name.as_bytes()),
+        Value::Text(name.as_bytes()),
+        Value::Integer(2),
+        Value::Text(b"CREATE TABLE ..."),
+    ]);
+    let mut data = b"SQLite format 3\0".to_vec();
+    data.extend_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    data.resize(100, 0);
+    let mut pages = vec![page(100, 0x0d, &[leaf_cell(1, &schema)], None)[100..].to_vec()];
+    let (left, right) = blobs.split_at(blobs.len() / 2);
+    // Page 2 is the root, pages 3 and 4 its children, and overflow pages come after them
+    let mut overflow = Vec::new();
+    let mut leaf = |rows: &[&[u8]], first_rowid: usize| {
+        let mut cells = Vec::new();
+        for (index, blob) in rows.iter().enumerate() {
+            let payload = encode_record(&[Value::Null, Value::Blob(blob)]);
+            let rowid = (first_rowid + index) as i64;
+            let max_local = PAGE_SIZE - 35;
+            if payload.len() <= max_local {
+                cells.push(leaf_cell(rowid, &payload));
+                continue;
+            }
+            let min_local = (PAGE_SIZE - 12) * 32 / 255 - 23;
+            let local = min_local + (payload.len() - min_local) % (PAGE_SIZE - 4);
+            let local = if local <= max_local { local } else { min_local };
+            let mut cell = encode_varint(payload.len() as u64);
+            cell.extend(encode_varint(rowid as u64));
+            cell.extend_from_slice(&payload[..local]);
+            let mut chunks = payload[local..].chunks(PAGE_SIZE - 4).peekable();
+            cell.extend_from_slice(&(5 + overflow.len() as u32).to_be_bytes());
+            while let Some(chunk) = chunks.next() {
+                let next = if chunks.peek().is_some() {
+                    6 + overflow.len() as u32
+                } else {
+                    0
+                };
+                let mut page = next.to_be_bytes().to_vec();
+                page.extend_from_slice(chunk);
+                page.resize(PAGE_SIZE, 0);
+                overflow.push(page);
+            }
+            cells.push(cell);
+        }
+        page(0, 0x0d, &cells, None)
+    };
+    let left_page = leaf(left, 1);
+    let right_page = leaf(right, 1 + left.len());
+    let mut interior = 3u32.to_be_bytes().to_vec();
+    interior.extend(encode_varint(left.len() as u64));
+    pages.push(page(0, 0x05, &[interior], Some(4)));
+    pages.extend([left_page, right_page]);
+    pages.extend(overflow);
+    for page in pages {
+        data.extend(page);
+    }
+    data
+}
+
+/// An rpm package header naming the package `name` for `arch`, with the files `files`.
+pub fn rpm_header(name: &str, arch: &str, files: &[&str]) -> Vec<u8> {
+    let mut dirnames: Vec<&str> = Vec::new();
+    let (mut basenames, mut dir_indexes) = (Vec::new(), Vec::new());
+    for file in files {
+        let (dir, base) = file.split_at(file.rfind('/').unwrap() + 1);
+        let index = dirnames
+            .iter()
+            .position(|&known| known == dir)
+            .unwrap_or_else(|| {
+                dirnames.push(dir);
+                dirnames.len() - 1
+            });
+        basenames.push(base);
+        dir_indexes.push(index as u32);
+    }
+    let mut store = Vec::new();
+    let mut entries = Vec::new();
+    let mut strings = |tag: u32, kind: u32, strings: &[&str], entries: &mut Vec<u8>| {
+        for value in [tag, kind, store.len() as u32, strings.len() as u32] {
+            entries.extend_from_slice(&value.to_be_bytes());
+        }
+        for string in strings {
+            store.extend_from_slice(string.as_bytes());
+            store.push(0);
+        }
+    };
+    strings(1000, 6, &[name], &mut entries);
+    strings(1022, 6, &[arch], &mut entries);
+    strings(1117, 8, &basenames, &mut entries);
+    strings(1118, 8, &dirnames, &mut entries);
+    store.resize(store.len().next_multiple_of(4), 0);
+    for value in [1116, 4, store.len() as u32, dir_indexes.len() as u32] {
+        entries.extend_from_slice(&value.to_be_bytes());
+    }
+    for index in dir_indexes {
+        store.extend_from_slice(&index.to_be_bytes());
+    }
+    let mut header = ((entries.len() / 16) as u32).to_be_bytes().to_vec();
+    header.extend_from_slice(&(store.len() as u32).to_be_bytes());
+    header.extend(entries);
+    header.extend(store);
+    header
+}
+
+/// An entry of a [`tar`] or [`cpio`] archive, by its path.
+pub enum ArchiveEntry<'a> {
+    File(&'a str, &'a [u8]),
+    Dir(&'a str),
+    Symlink(&'a str, &'a str),
+    HardLink(&'a str, &'a str),
+    /// A pax extended header of these records, for the entry after it.
+    Pax(&'a [u8]),
+}
+
+/// A ustar archive of `entries`, with paths too long for the header in GNU long name entries.
+pub fn tar(entries: &[ArchiveEntry]) -> Vec<u8> {
+    fn header(path: &str, kind: u8, link: &str, size: usize) -> Vec<u8> {
+        let mut header = vec![0; 512];
+        let mut field = |offset: usize, value: &[u8]| {
+            header[offset..offset + value.len()].copy_from_slice(value);
+        };
+        field(0, &path.as_bytes()[..path.len().min(100)]);
+        field(100, b"0000755\0");
+        field(108, b"0000000\0");
+        field(116, b"0000000\0");
+        field(124, format!("{size:011o}\0").as_bytes());
+        field(136, b"00000000000\0");
+        field(148, b"        ");
+        field(156, &[kind]);
+        field(157, link.as_bytes());
+        field(257, b"ustar\x0000");
+        let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+        header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+        header
+    }
+    let mut archive = Vec::new();
+    let mut add = |path: &str, kind: u8, link: &str, data: &[u8]| {
+        if path.len() > 100 {
+            let mut name = path.as_bytes().to_vec();
+            name.push(0);
+            archive.extend(header("././@LongLink", b'L', "", name.len()));
+            name.resize(name.len().next_multiple_of(512), 0);
+            archive.extend(name);
+        }
+        archive.extend(header(path, kind, link, data.len()));
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(512), 0);
+    };
+    for entry in entries {
+        match *entry {
+            ArchiveEntry::File(path, data) => add(path, b'0', "", data),
+            ArchiveEntry::Dir(path) => add(path, b'5', "", b""),
+            ArchiveEntry::Symlink(path, target) => add(path, b'2', target, b""),
+            ArchiveEntry::HardLink(path, target) => add(path, b'1', target, b""),
+            ArchiveEntry::Pax(records) => add("PaxHeader", b'x', "", records),
+        }
+    }
+    archive.resize(archive.len() + 1024, 0);
+    archive
+}
+
+/// `data` in a gzip file of uncompressed DEFLATE blocks.
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut gzip = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+    let blocks: Vec<&[u8]> = data.chunks(0xffff).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        gzip.push(u8::from(i + 1 == blocks.len()));
+        let length = block.len() as u16;
+        gzip.extend_from_slice(&length.to_le_bytes());
+        gzip.extend_from_slice(&(!length).to_le_bytes());
+        gzip.extend_from_slice(block);
+    }
+    if blocks.is_empty() {
+        gzip.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    gzip.extend_from_slice(&crc32(data).to_le_bytes());
+    gzip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    gzip
+}
+
+/// `len` zeros in a gzip file of one block of fixed Huffman codes, each copying the 258 bytes
+/// before it, so it is about 150 times smaller than what it decompresses to.
+pub fn gzip_zeros(len: usize) -> Vec<u8> {
+    // Huffman codes are stored from their most significant bit, other fields from their least
+    let mut bits = vec![true, true, false];
+    let mut code = |code: u32, len: u32| bits.extend((0..len).rev().map(|i| code >> i & 1 == 1));
+    let (copies, literals) = match len {
+        0 => (0, 0),
+        _ => ((len - 1) / 258, 1 + (len - 1) % 258),
+    };
+    for _ in 0..literals {
+        code(0x30, 8);
+    }
+    for _ in 0..copies {
+        // The length 258, then the distance 1
+        code(0xc5, 8);
+        code(0, 5);
+    }
+    code(0, 7);
+    let mut gzip = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+    gzip.extend(bits.chunks(8).map(|byte| {
+        let bits = byte.iter().enumerate();
+        bits.fold(0u8, |value, (i, &bit)| value | u8::from(bit) << i)
+    }));
+    gzip.extend_from_slice(&crc32(&vec![0; len]).to_le_bytes());
+    gzip.extend_from_slice(&(len as u32).to_le_bytes());
+    gzip
+}
+
+/// `data` in an xz file of one block of uncompressed LZMA2 chunks, without a check.
+pub fn xz(data: &[u8]) -> Vec<u8> {
+    let varint = |bytes: &mut Vec<u8>, mut value: usize| {
+        while value >= 0x80 {
+            bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+    };
+    let mut xz = vec![0xfd, b'7', b'z', b'X', b'Z', 0, 0, 0];
+    xz.extend_from_slice(&crc32(&[0, 0]).to_le_bytes());
+    // The block header: its size, one filter, LZMA2 with a 1 MiB dictionary, and padding
+    let header = [2, 0, 0x21, 1, 0x10, 0, 0, 0];
+    xz.extend_from_slice(&header);
+    xz.extend_from_slice(&crc32(&header).to_le_bytes());
+    let start = xz.len();
+    for (i, chunk) in data.chunks(0x10000).enumerate() {
+        // The first chunk resets the dictionary
+        xz.push(if i == 0 { 1 } else { 2 });
+        xz.extend_from_slice(&(chunk.len() as u16 - 1).to_be_bytes());
+        xz.extend_from_slice(chunk);
+    }
+    xz.push(0);
+    let unpadded_size = header.len() + 4 + xz.len() - start;
+    xz.resize(align(xz.len(), 4), 0);
+
+    let mut index = vec![0, 1];
+    varint(&mut index, unpadded_size);
+    varint(&mut index, data.len());
+    index.resize(align(index.len(), 4), 0);
+    index.extend_from_slice(&crc32(&index).to_le_bytes());
+    xz.extend_from_slice(&index);
+    let mut footer = (index.len() as u32 / 4 - 1).to_le_bytes().to_vec();
+    footer.extend_from_slice(&[0, 0]);
+    xz.extend_from_slice(&crc32(&footer).to_le_bytes());
+    xz.extend_from_slice(&footer);
+    xz.extend_from_slice(b"YZ");
+    xz
+}
+
+/// `data` in a zstd frame of raw blocks, without a checksum.
+pub fn zstd(data: &[u8]) -> Vec<u8> {
+    // The frame header, with a window of 128 KiB
+    let mut zstd = vec![0x28, 0xb5, 0x2f, 0xfd, 0, 0x38];
+    let blocks: Vec<&[u8]> = data.chunks(128 << 10).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        let header = (block.len() << 3) as u32 | u32::from(i + 1 == blocks.len());
+        zstd.extend_from_slice(&header.to_le_bytes()[..3]);
+        zstd.extend_from_slice(block);
+    }
+    if blocks.is_empty() {
+        zstd.extend_from_slice(&[1, 0, 0]);
+    }
+    zstd
+}
+
+/// The CRC-32 (ISO 3309) of `data`, as gzip and xz check it.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// The Adler-32 checksum of `data`, as zlib checks it.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// A `newc` cpio archive of `entries`, its paths prefixed with `./` as rpm writes them. The
+/// contents of a hard-linked file are stored with its first path.
+pub fn cpio(entries: &[ArchiveEntry]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut add = |path: &str, ino: usize, mode: u32, nlink: usize, data: &[u8]| {
+        let name = format!("{path}\0");
+        let fields = [
+            ino,
+            mode as usize,
+            0,
+            0,
+            nlink,
+            0,
+            data.len(),
+            0,
+            0,
+            0,
+            0,
+            name.len(),
+            0,
+        ];
+        archive.extend_from_slice(b"070701");
+        for field in fields {
+            archive.extend_from_slice(format!("{field:08x}").as_bytes());
+        }
+        archive.extend_from_slice(name.as_bytes());
+        archive.resize(archive.len().next_multiple_of(4), 0);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+    };
+    let path = |path: &str| format!("./{path}");
+    let ino_of = |target: &str| {
+        let files = entries.iter().position(|entry| {
+            matches!(*entry,
+            ArchiveEntry::File(path, _) if path == target)
+        });
+        files.expect("hard link to a file of the archive") + 1
+    };
+    let links = |ino: usize| {
+        1 + entries
+            .iter()
+            .filter(|entry| {
+                matches!(**entry, ArchiveEntry::HardLink(_, target)
+                if ino_of(target) == ino)
+            })
+            .count()
+    };
+    for (i, entry) in entries.iter().enumerate() {
+        match *entry {
+            ArchiveEntry::File(name, data) => add(&path(name), i + 1, 0o100755, links(i + 1), data),
+            ArchiveEntry::Dir(name) => add(&path(name), i + 1, 0o40755, 2, b""),
+            ArchiveEntry::Symlink(name, target) => {
+                add(&path(name), i + 1, 0o120777, 1, target.as_bytes())
+            }
+            ArchiveEntry::HardLink(name, target) => {
+                let ino = ino_of(target);
+                add(&path(name), ino, 0o100755, links(ino), b"")
+            }
+            ArchiveEntry::Pax(_) => unreachable!("cpio archives have no pax headers"),
+        }
+    }
+    add("TRAILER!!!", 0, 0, 1, b"");
+    archive
+}
+
+/// A `.deb` package whose files are the tar archive `data`, under the member name `data_name`.
+pub fn deb(data_name: &str, data: &[u8]) -> Vec<u8> {
+    let mut deb = b"!<arch>\n".to_vec();
+    let members: [(&str, &[u8]); 3] = [
+        ("debian-binary", b"2.0\n"),
+        (
+            "control.tar",
+            &tar(&[ArchiveEntry::File("control", b"Package: test\n")]),
+        ),
+        (data_name, data),
+    ];
+    for (name, contents) in members {
+        let header = format!(
+            "{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            0,
+            0,
+            0,
+            100644,
+            contents.len()
+        );
+        deb.extend_from_slice(header.as_bytes());
+        deb.extend_from_slice(contents);
+        if deb.len() % 2 == 1 {
+            deb.push(b'\n');
+        }
+    }
+    deb
+}
+
+/// An `.rpm` package with empty headers whose payload is `payload`.
+pub fn rpm(payload: &[u8]) -> Vec<u8> {
+    let mut rpm = vec![0xed, 0xab, 0xee, 0xdb, 3, 0];
+    rpm.resize(96, 0);
+    // A signature header of one 4-byte entry, padded to eight bytes, and a main header of none
+    for (entries, data_size) in [(1u32, 4u32), (0, 0)] {
+        rpm.extend_from_slice(&[0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0]);
+        rpm.extend_from_slice(&entries.to_be_bytes());
+        rpm.extend_from_slice(&data_size.to_be_bytes());
+        rpm.resize(rpm.len() + 16 * entries as usize + data_size as usize, 0);
+        rpm.resize(rpm.len().next_multiple_of(8), 0);
+    }
+    rpm.extend_from_slice(payload);
+    rpm
+}
+
+/// How [`squashfs`] stores the blocks of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SquashfsBlocks {
+    Uncompressed,
+    /// In zlib streams of stored DEFLATE blocks.
+    Gzip,
+    /// In [`xz`] files.
+    Xz,
+    /// In [`zstd`] frames.
+    Zstd,
+}
+
+/// A squashfs 4.0 image of `entries`, with the directories their paths imply, and its blocks
+/// stored as `blocks` says. Files are stored in data blocks of 4096 bytes without fragments.
+pub fn squashfs(entries: &[ArchiveEntry], blocks: SquashfsBlocks) -> Vec<u8> {
+    enum Node<'a> {
+        Dir(BTreeMap<&'a str, Node<'a>>),
+        File(&'a [u8]),
+        Symlink(&'a str),
+    }
+    const BLOCK_SIZE: usize = 4096;
+    const METADATA_SIZE: usize = 8192;
+    struct Image {
+        blocks: SquashfsBlocks,
+        data: Vec<u8>,
+        inodes: Vec<u8>,
+        directories: Vec<u8>,
+        count: u32,
+    }
+    impl Image {
+        /// `data` compressed as the image stores its blocks, or `data` itself, and its size
+        /// with `uncompressed_flag` set in that case.
+        fn block(&self, data: &[u8], uncompressed_flag: u32) -> (Vec<u8>, u32) {
+            let stream = match self.blocks {
+                SquashfsBlocks::Uncompressed => {
+                    return (data.to_vec(), data.len() as u32 | uncompressed_flag);
+                }
+                SquashfsBlocks::Gzip => {
+                    let mut stream = vec![0x78, 0x01, 1];
+                    let length = data.len() as u16;
+                    stream.extend_from_slice(&length.to_le_bytes());
+                    stream.extend_from_slice(&(!length).to_le_bytes());
+                    stream.extend_from_slice(data);
+                    stream.extend_from_slice(&adler32(data).to_be_bytes());
+                    stream
+                }
+                SquashfsBlocks::Xz => xz(data),
+                SquashfsBlocks::Zstd => zstd(data),
+            };
+            let size = stream.len() as u32;
+            (stream, size)
+        }
+
+        /// A reference to `at` in a metadata table: the offset of its block, shifted left by
+        /// 16, and its offset in the block. Blocks but the last are full, so they have the
+        /// same size when compressed.
+        fn reference(&self, at: usize) -> u64 {
+            let block_size = 2 + self.block(&[0; METADATA_SIZE], 0).0.len();
+            (((at / METADATA_SIZE * block_size) << 16) | (at % METADATA_SIZE)) as u64
+        }
+
+        /// The metadata blocks of `table`.
+        fn metadata(&self, table: &[u8]) -> Vec<u8> {
+            let mut blocks = Vec::new();
+            for chunk in table.chunks(METADATA_SIZE) {
+                let (data, size) = self.block(chunk, 0x8000);
+                blocks.extend_from_slice(&(size as u16).to_le_bytes());
+                blocks.extend(data);
+            }
+            blocks
+        }
+
+        /// Writes the inode of `node` after those of its entries, returning a reference to it,
+        /// its number and its type.
+        fn write(&mut self, node: &Node) -> (u64, u32, u16) {
+            let mut inode = Vec::new();
+            let kind = match node {
+                Node::Dir(entries) => {
+                    let mut listing = Vec::new();
+                    for (name, entry) in entries {
+                        let (child, number, kind) = self.write(entry);
+                        listing.extend_from_slice(&0u32.to_le_bytes());
+                        listing.extend_from_slice(&((child >> 16) as u32).to_le_bytes());
+                        listing.extend_from_slice(&number.to_le_bytes());
+                        listing.extend_from_slice(&(child as u16).to_le_bytes());
+                        listing.extend_from_slice(&0u16.to_le_bytes());
+                        listing.extend_from_slice(&kind.to_le_bytes());
+                        listing.extend_from_slice(&(name.len() as u16 - 1).to_le_bytes());
+                        listing.extend_from_slice(name.as_bytes());
+                    }
+                    let at = self.reference(self.directories.len());
+                    self.directories.extend(&listing);
+                    inode.extend_from_slice(&((at >> 16) as u32).to_le_bytes());
+                    inode.extend_from_slice(&2u32.to_le_bytes());
+                    inode.extend_from_slice(&(listing.len() as u16 + 3).to_le_bytes());
+                    inode.extend_from_slice(&(at as u16).to_le_bytes());
+                    inode.extend_from_slice(&1u32.to_le_bytes());
+                    1
+                }
+                Node::File(contents) => {
+                    inode.extend_from_slice(&(self.data.len() as u32 + 96).to_le_bytes());
+                    inode.extend_from_slice(&u32::MAX.to_le_bytes());
+                    inode.extend_from_slice(&0u32.to_le_bytes());
+                    inode.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+                    for chunk in contents.chunks(BLOCK_SIZE) {
+                        let (data, size) = self.block(chunk, 1 << 24);
+                        self.data.extend(data);
+                        inode.extend_from_slice(&size.to_le_bytes());
+                    }
+                    2
+                }
+                Node::Symlink(target) => {
+                    inode.extend_from_slice(&1u32.to_le_bytes());
+                    inode.extend_from_slice(&(target.len() as u32).to_le_bytes());
+                    inode.extend_from_slice(target.as_bytes());
+                    3
+                }
+            };
+            self.count += 1;
+            let at = self.reference(self.inodes.len());
+            let mode: u16 = if kind == 3 { 0o777 } else { 0o755 };
+            for field in [kind, mode, 0, 0] {
+                self.inodes.extend_from_slice(&field.to_le_bytes());
+            }
+            self.inodes.extend_from_slice(&0u32.to_le_bytes());
+            self.inodes.extend_from_slice(&self.count.to_le_bytes());
+            self.inodes.extend(inode);
+            (at, self.count, kind)
+        }
+    }
+
+    let mut root = Node::Dir(BTreeMap::new());
+    for entry in entries {
+        let (path, node) = match *entry {
+            ArchiveEntry::File(path, data) => (path, Some(Node::File(data))),
+            ArchiveEntry::Dir(path) => (path, None),
+            ArchiveEntry::Symlink(path, target) => (path, Some(Node::Symlink(target))),
+            _ => unreachable!("squashfs images have only files, directories and symlinks"),
+        };
+        let path = path.trim_start_matches("./").trim_end_matches('/');
+        let mut dir = &mut root;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let Node::Dir(entries) = dir else {
+                unreachable!("a path under a file")
+            };
+            dir = entries.entry(name).or_insert(Node::Dir(BTreeMap::new()));
+        }
+        if let Some(node) = node {
+            *dir = node;
+        }
+    }
+    let mut image = Image {
+        blocks,
+        data: Vec::new(),
+        inodes: Vec::new(),
+        directories: Vec::new(),
+        count: 0,
+    };
+    let (root, _, _) = image.write(&root);
+
+    let inode_table = 96 + image.data.len() as u64;
+    let inodes = image.metadata(&image.inodes);
+    let dir_table = inode_table + inodes.len() as u64;
+    let directories = image.metadata(&image.directories);
+    let bytes_used = dir_table + directories.len() as u64;
+    let mut superblock = b"hsqs".to_vec();
+    superblock.extend_from_slice(&image.count.to_le_bytes());
+    superblock.extend_from_slice(&0u32.to_le_bytes());
+    superblock.extend_from_slice(&(BLOCK_SIZE as u32).to_le_bytes());
+    superblock.extend_from_slice(&0u32.to_le_bytes());
+    // The compression, the log of the block size, no fragments, one id, and version 4.0
+    let (compression, flags) = match blocks {
+        SquashfsBlocks::Uncompressed => (1u16, 0x13),
+        SquashfsBlocks::Gzip => (1, 0x10),
+        SquashfsBlocks::Xz => (4, 0x10),
+        SquashfsBlocks::Zstd => (6, 0x10),
+    };
+    for field in [compression, 12, flags, 1, 4, 0] {
+        superblock.extend_from_slice(&field.to_le_bytes());
+    }
+    let tables = [
+        root,
+        bytes_used,
+        bytes_used,
+        u64::MAX,
+        inode_table,
+        dir_table,
+    ];
+    for field in tables.into_iter().chain([u64::MAX, u64::MAX]) {
+        superblock.extend_from_slice(&field.to_le_bytes());
+    }
+    [superblock, image.data, inodes, directories].concat()
+}
+
+/// A [`FileSystem`] of files and symlinks in memory, with the directories their paths imply.
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    /// The contents of each file, or the target of each symlink.
+    entries: BTreeMap<PathBuf, (FileKind, Vec<u8>)>,
+}
+
+impl MemoryFs {
+    pub fn file<P: Into<PathBuf>>(mut self, path: P, contents: &[u8]) -> Self {
+        self.entries
+            .insert(path.into(), (FileKind::File, contents.to_vec()));
+        self
+    }
+
+    pub fn symlink<P: Into<PathBuf>>(mut self, path: P, target: &str) -> Self {
+        self.entries
+            .insert(path.into(), (FileKind::Symlink, target.as_bytes().to_vec()));
+        self
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.entries
+            .keys()
+            .any(|entry| entry.starts_with(path) && entry != path)
+    }
+}
+
+impl FileSystem for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let real_path = crate::sysroot::resolve_in(self, Path::new("/"), path);
+        match real_path.and_then(|path| self.entries.get(&path)) {
+            Some((FileKind::File, contents)) => Ok(contents.clone()),
+            _ => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let (kind, len) = match self.entries.get(path) {
+            Some((kind, contents)) => (*kind, contents.len() as u64),
+            None if self.is_dir(path) => (FileKind::Dir, 0),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        };
+        Ok(FileMetadata {
+            kind,
+            len,
+            mode: 0o755,
+        })
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.entries.get(path) {
+            Some((FileKind::Symlink, target)) => Ok(PathBuf::from(OsStr::from_bytes(target))),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        let mut names: Vec<OsString> = self
+            .entries
+            .keys()
+            .filter_map(|entry| entry.strip_prefix(path).ok()?.iter().next())
+            .map(OsStr::to_owned)
+            .collect();
+        names.dedup();
+        Ok(names)
+    }
+}
+
+/// A 64-bit core dump of a process that mapped `files`, each at `start..end` from the page
+/// offset `page` of the file at its path, with the memory `memory` dumped at each address and
+/// `entry` as the entry point in its auxiliary vector.
+pub fn core(files: &[(u64, u64, u64, &str)], memory: &[(u64, &[u8])], entry: u64) -> Vec<u8> {
+    let words = |words: &[u64]| -> Vec<u8> { words.iter().flat_map(|w| w.to_le_bytes()).collect() };
+    let mut file_note = words(&[files.len() as u64, 4096]);
+    for &(start, end, page, _) in files {
+        file_note.extend(words(&[start, end, page]));
+    }
+    for (_, _, _, path) in files {
+        file_note.extend_from_slice(path.as_bytes());
+        file_note.push(0);
+    }
+    let mut notes = Vec::new();
+    for (n_type, desc) in [(6, words(&[9, entry, 0, 0])), (0x4649_4c45, file_note)] {
+        notes.extend_from_slice(&5u32.to_le_bytes());
+        notes.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+        notes.extend_from_slice(&(n_type as u32).to_le_bytes());
+        notes.extend_from_slice(b"CORE\0\0\0\0");
+        notes.extend_from_slice(&desc);
+        notes.resize(align(notes.len(), 4), 0);
+    }
+
+    let phnum = 1 + memory.len();
+    let mut core = b"\x7fELF\x02\x01\x01".to_vec();
+    core.resize(16, 0);
+    core.extend_from_slice(&crate::abi::ET_CORE.to_le_bytes());
+    core.extend_from_slice(&EM_X86_64.to_le_bytes());
+    core.extend_from_slice(&1u32.to_le_bytes());
+    core.extend(words(&[0, 64, 0]));
+    core.extend_from_slice(&0u32.to_le_bytes());
+    for half in [64, 56, phnum as u16, 64, 0, 0] {
+        core.extend_from_slice(&u16::to_le_bytes(half));
+    }
+    let mut offset = (64 + 56 * phnum) as u64;
+    let mut phdr = |p_type: u32, vaddr: u64, size: u64, offset: u64| {
+        core.extend_from_slice(&p_type.to_le_bytes());
+        core.extend_from_slice(&4u32.to_le_bytes());
+        core.extend(words(&[offset, vaddr, 0, size, size, 4]));
+    };
+    phdr(PT_NOTE, 0, notes.len() as u64, offset);
+    offset += notes.len() as u64;
+    for (address, contents) in memory {
+        phdr(PT_LOAD, *address, contents.len() as u64, offset);
+        offset += contents.len() as u64;
+    }
+    core.extend_from_slice(&notes);
+    for (_, contents) in memory {
+        core.extend_from_slice(contents);
+    }
+    core
+}
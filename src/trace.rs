@@ -0,0 +1,26 @@
+//! Events about what resolution does, emitted with `tracing` for the `tracing` feature and
+//! compiled out without it.
+
+/// Emits a `tracing` event at the debug level, with the arguments of [`tracing::debug!`].
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// Emits a `tracing` event at the warn level, with the arguments of [`tracing::warn!`].
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
+
+/// Emits a `tracing` event at the trace level, with the arguments of [`tracing::trace!`].
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
@@ -0,0 +1,561 @@
+//! Watching the dependency closures of binaries for changes to the files they are resolved
+//! from, as by package upgrades, with the `watch` feature.
+//!
+//! Changes are noticed through the `notify` crate, i.e. inotify or kqueue, on the directories
+//! holding the files of each closure. A file's `stat` remains what tells whether it changed:
+//! a notification only wakes [`Watcher::run`] to check at once, and where notifications are
+//! not available, or missed, as on network filesystems, the files are polled every interval.
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::disk_cache::Stamp;
+use crate::{
+    ChangedLibrary, DependencyGraph, ElfFile, Error, Resolver, ResolverConfig, LD_SO_CACHE_PATH,
+    LD_SO_CONF_PATH, LD_SO_PRELOAD_PATH,
+};
+
+/// The directory `ld.so.conf` usually includes its files from.
+const LD_SO_CONF_DIR: &str = "/etc/ld.so.conf.d";
+
+/// A change to the closure of a binary a [`Watcher`] watches, as returned by
+/// [`Watcher::poll`].
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// The watched binary was replaced, and its closure resolved again.
+    RootReplaced { root: PathBuf },
+    /// The file a library of the closure is loaded from was replaced, at the same path.
+    LibraryReplaced {
+        root: PathBuf,
+        soname: String,
+        path: PathBuf,
+    },
+    /// A library of the closure can no longer be found, or a library newly needed can't be.
+    /// `previous` is where it was found before, if it was.
+    DependencyMissing {
+        root: PathBuf,
+        soname: String,
+        previous: Option<PathBuf>,
+    },
+    /// A library of the closure is found that was not before, or was not needed before.
+    DependencyFound {
+        root: PathBuf,
+        soname: String,
+        path: PathBuf,
+    },
+    /// A library of the closure is now loaded from another file, as when one of the same name
+    /// was installed in a directory searched earlier.
+    DependencyMoved {
+        root: PathBuf,
+        soname: String,
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// A library is no longer part of the closure.
+    DependencyDropped { root: PathBuf, soname: String },
+    /// The watched binary can no longer be analyzed, as when it was removed. It is still
+    /// watched, and its changes are reported against the last closure resolved once it can be
+    /// analyzed again.
+    AnalysisFailed { root: PathBuf, error: Error },
+}
+
+/// Watches the dependency closures of registered binaries, and reports what changes when the
+/// files they are resolved from do, e.g. to alert when an update breaks the closure of a
+/// service.
+///
+/// The files watched are each binary, the libraries of its closure, every path a library was
+/// looked for at before it was found, or at all if it was not, and the files configuring the
+/// system directories, like `ld.so.cache`. They are checked for changes to their device,
+/// inode, size and modification and change times whenever a directory holding one of them
+/// notifies a change, or the interval of [`Watcher::run`] elapses, and a closure is resolved
+/// again once any of its files changes. Only the host's files can be watched, within a
+/// [sysroot](ResolverConfig::sysroot) or not: a [filesystem](ResolverConfig::filesystem) is
+/// never found to change.
+///
+/// ```no_run
+/// use std::ops::ControlFlow;
+/// use std::time::Duration;
+/// use elf_dynamic_lib_getter::{ResolverConfig, WatchEvent, Watcher};
+///
+/// let mut watcher = Watcher::new(ResolverConfig::new().use_env(false));
+/// watcher.watch("/usr/sbin/nginx")?;
+/// watcher.run(Duration::from_secs(10), |event| {
+///     if let WatchEvent::DependencyMissing { root, soname, .. } = &event {
+///         eprintln!("{} can no longer load {soname}", root.display());
+///     }
+///     ControlFlow::Continue(())
+/// });
+/// # Ok::<(), elf_dynamic_lib_getter::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Watcher {
+    config: ResolverConfig,
+    roots: Vec<Watched>,
+    /// The notifications of changes to the directories of the watched files, or `None` if they
+    /// can't be had, and the files are only polled.
+    notifier: Option<Notifier>,
+}
+
+/// The directories watched for notifications, and the notifications received.
+#[derive(Debug)]
+struct Notifier {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    dirs: HashSet<PathBuf>,
+    /// Whether directories were watched since the last check, whose files may have changed
+    /// before they were.
+    added: bool,
+}
+
+/// A binary being watched.
+#[derive(Debug)]
+struct Watched {
+    path: PathBuf,
+    /// The last closure resolved.
+    graph: DependencyGraph,
+    /// Whether the last analysis failed, so the failure is only reported once.
+    failed: bool,
+    stamps: Stamps,
+}
+
+/// The stamp of every file a closure depends on, or `None` for those that don't exist.
+type Stamps = HashMap<PathBuf, Option<Stamp>>;
+
+impl Watcher {
+    /// A watcher resolving closures according to `config`.
+    pub fn new(config: ResolverConfig) -> Self {
+        let (sender, events) = mpsc::channel();
+        let notifier = notify::recommended_watcher(sender)
+            .ok()
+            .map(|watcher| Notifier {
+                watcher,
+                events,
+                dirs: HashSet::new(),
+                added: false,
+            });
+        Self {
+            config,
+            roots: Vec::new(),
+            notifier,
+        }
+    }
+
+    /// Starts watching the binary at `path`, resolving its closure now to compare later ones
+    /// with. Watching a binary again resolves it again.
+    /// # Return Value [Err]
+    /// The error [`ElfFile::dependency_graph`] fails with, if the closure can't be resolved.
+    pub fn watch<P: Into<PathBuf>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.into();
+        let (graph, stamps) = self.analyze(&path)?;
+        let watched = Watched {
+            path,
+            graph,
+            failed: false,
+            stamps,
+        };
+        match self.roots.iter_mut().find(|root| root.path == watched.path) {
+            Some(root) => *root = watched,
+            None => self.roots.push(watched),
+        }
+        self.update_notifications();
+        Ok(())
+    }
+
+    /// Stops watching the binary at `path`. Returns whether it was watched.
+    pub fn unwatch<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        let len = self.roots.len();
+        self.roots.retain(|root| root.path != path.as_ref());
+        self.update_notifications();
+        self.roots.len() != len
+    }
+
+    /// The binaries watched, in the order they were first watched.
+    pub fn roots(&self) -> impl Iterator<Item = &Path> {
+        self.roots.iter().map(|root| root.path.as_path())
+    }
+
+    /// The last closure resolved for the binary at `path`, if it is watched.
+    pub fn graph<P: AsRef<Path>>(&self, path: P) -> Option<&DependencyGraph> {
+        let root = self.roots.iter().find(|root| root.path == path.as_ref())?;
+        Some(&root.graph)
+    }
+
+    /// Checks each watched binary once, resolving the closures whose files changed since the
+    /// last check again, and returns what changed about them, binary by binary.
+    pub fn poll(&mut self) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        for index in 0..self.roots.len() {
+            let root = &self.roots[index];
+            let changed: HashSet<&Path> = root
+                .stamps
+                .iter()
+                .filter(|(path, stamp)| Stamp::of(path) != **stamp)
+                .map(|(path, _)| path.as_path())
+                .collect();
+            if changed.is_empty() {
+                continue;
+            }
+            let replaced = |path: &Path| changed.contains(path);
+            let analysis = self.analyze(&root.path);
+            let path = root.path.clone();
+            match analysis {
+                Ok((graph, stamps)) => {
+                    if replaced(&path) {
+                        events.push(WatchEvent::RootReplaced { root: path.clone() });
+                    }
+                    events.extend(library_events(&path, &root.graph, &graph, replaced));
+                    self.roots[index] = Watched {
+                        path,
+                        graph,
+                        failed: false,
+                        stamps,
+                    };
+                }
+                Err(error) => {
+                    let root = &mut self.roots[index];
+                    for (path, stamp) in &mut root.stamps {
+                        *stamp = Stamp::of(path);
+                    }
+                    if !root.failed {
+                        root.failed = true;
+                        events.push(WatchEvent::AnalysisFailed { root: path, error });
+                    }
+                }
+            }
+        }
+        self.update_notifications();
+        events
+    }
+
+    /// [Polls](Watcher::poll) whenever a watched directory notifies a change, and at least
+    /// every `interval`, passing each event to `on_event`, until it breaks.
+    pub fn run<F>(&mut self, interval: Duration, mut on_event: F)
+    where
+        F: FnMut(WatchEvent) -> ControlFlow<()>,
+    {
+        loop {
+            for event in self.poll() {
+                if on_event(event).is_break() {
+                    return;
+                }
+            }
+            self.wait(interval);
+        }
+    }
+
+    /// Waits until a watched directory notifies a change that may concern its files, or
+    /// `interval` elapses.
+    fn wait(&mut self, interval: Duration) {
+        let Some(notifier) = &mut self.notifier else {
+            thread::sleep(interval);
+            return;
+        };
+        if std::mem::take(&mut notifier.added) {
+            return;
+        }
+        // Reading a file, as in resolving a closure, is notified too, but changes nothing
+        let is_change = |event: &notify::Result<notify::Event>| !matches!(event, Ok(event) if matches!(event.kind, EventKind::Access(_)));
+        loop {
+            match notifier.events.recv_timeout(interval) {
+                Ok(event) if is_change(&event) => break,
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => return,
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.notifier = None;
+                    return;
+                }
+            }
+        }
+        // An upgrade changes many files at once: check them once for all of them
+        while notifier.events.try_recv().is_ok() {}
+    }
+
+    /// Watches the directories holding the files of every closure for notifications, and no
+    /// others. A directory that can't be watched is only polled.
+    fn update_notifications(&mut self) {
+        let Some(notifier) = &mut self.notifier else {
+            return;
+        };
+        // A file that doesn't exist is noticed once created through the closest existing
+        // directory above it, which the directories it is created in are created in
+        let dirs: HashSet<PathBuf> = self
+            .roots
+            .iter()
+            .flat_map(|root| root.stamps.keys())
+            .filter_map(|path| path.ancestors().skip(1).find(|dir| dir.is_dir()))
+            .map(Path::to_owned)
+            .collect();
+        for dir in notifier.dirs.difference(&dirs) {
+            let _ = notifier.watcher.unwatch(dir);
+        }
+        let added: Vec<PathBuf> = dirs.difference(&notifier.dirs).cloned().collect();
+        notifier.dirs.retain(|dir| dirs.contains(dir));
+        for dir in added {
+            if notifier
+                .watcher
+                .watch(&dir, RecursiveMode::NonRecursive)
+                .is_ok()
+            {
+                notifier.dirs.insert(dir);
+                notifier.added = true;
+            }
+        }
+    }
+
+    /// Resolves the closure of the binary at `path`, along with the stamps of the files it
+    /// depends on.
+    fn analyze(&self, path: &Path) -> Result<(DependencyGraph, Stamps), Error> {
+        let resolver = Arc::new(Resolver::recording_probes(self.config.clone()));
+        let graph = ElfFile::with_resolver(path, resolver.clone()).dependency_graph();
+        let mut paths = resolver.take_probes();
+        paths.insert(path.to_owned());
+        for config_path in [
+            LD_SO_CACHE_PATH,
+            LD_SO_CONF_PATH,
+            LD_SO_CONF_DIR,
+            LD_SO_PRELOAD_PATH,
+        ] {
+            paths.insert(self.config.rebase(Path::new(config_path)));
+        }
+        // Adding or removing a plugin changes the modification time of its directory
+        let plugin_dirs = self.config.plugin_dirs.iter();
+        paths.extend(plugin_dirs.map(|dir| self.config.rebase(dir)));
+        let graph = graph?;
+        paths.extend(
+            graph
+                .nodes()
+                .iter()
+                .filter_map(|node| node.path())
+                .map(PathBuf::from),
+        );
+        let stamps = paths
+            .into_iter()
+            .map(|path| {
+                let stamp = Stamp::of(&path);
+                (path, stamp)
+            })
+            .collect();
+        Ok((graph, stamps))
+    }
+}
+
+/// The changes to the libraries of `root` from the closure `before` to `after`, where
+/// `replaced` tells the files replaced in between.
+fn library_events(
+    root: &Path,
+    before: &DependencyGraph,
+    after: &DependencyGraph,
+    replaced: impl Fn(&Path) -> bool,
+) -> Vec<WatchEvent> {
+    let root_path = || root.to_owned();
+    let diff = before.diff(after);
+    let changed: HashSet<&str> = diff
+        .changed
+        .iter()
+        .map(|library| library.soname.as_str())
+        .collect();
+    let mut events = Vec::new();
+    // Libraries still loaded from the same paths, from files replaced in between
+    let mut seen = HashSet::new();
+    for node in &after.nodes()[1..] {
+        let Some(path) = node.path() else {
+            continue;
+        };
+        let is_new = diff.added.iter().any(|(soname, _)| *soname == node.soname);
+        let moved = is_new || changed.contains(node.soname.as_str());
+        if replaced(path) && !moved && seen.insert(&node.soname) {
+            events.push(WatchEvent::LibraryReplaced {
+                root: root_path(),
+                soname: node.soname.clone(),
+                path: path.to_owned(),
+            });
+        }
+    }
+    for (soname, path) in diff.added {
+        events.push(match path {
+            Some(path) => WatchEvent::DependencyFound {
+                root: root_path(),
+                soname,
+                path,
+            },
+            None => WatchEvent::DependencyMissing {
+                root: root_path(),
+                soname,
+                previous: None,
+            },
+        });
+    }
+    for ChangedLibrary {
+        soname,
+        before,
+        after,
+    } in diff.changed
+    {
+        events.push(match (before, after) {
+            (Some(from), Some(to)) => WatchEvent::DependencyMoved {
+                root: root_path(),
+                soname,
+                from,
+                to,
+            },
+            (None, Some(path)) => WatchEvent::DependencyFound {
+                root: root_path(),
+                soname,
+                path,
+            },
+            (previous, None) => WatchEvent::DependencyMissing {
+                root: root_path(),
+                soname,
+                previous,
+            },
+        });
+    }
+    for (soname, _) in diff.removed {
+        events.push(WatchEvent::DependencyDropped {
+            root: root_path(),
+            soname,
+        });
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WatchEvent, Watcher};
+    use crate::test_util::ElfBuilder;
+    use crate::ResolverConfig;
+    use std::ops::ControlFlow;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+
+    /// The events of one poll, without the root, which is the same for all of them.
+    fn poll(watcher: &mut Watcher) -> Vec<String> {
+        let events = watcher.poll();
+        let describe = |event: WatchEvent| match event {
+            WatchEvent::RootReplaced { .. } => "root replaced".to_owned(),
+            WatchEvent::LibraryReplaced { soname, .. } => format!("{soname} replaced"),
+            WatchEvent::DependencyMissing {
+                soname, previous, ..
+            } => format!("{soname} missing, was {previous:?}"),
+            WatchEvent::DependencyFound { soname, path, .. } => {
+                format!("{soname} found at {}", path.display())
+            }
+            WatchEvent::DependencyMoved {
+                soname, from, to, ..
+            } => format!("{soname} moved from {} to {}", from.display(), to.display()),
+            WatchEvent::DependencyDropped { soname, .. } => format!("{soname} dropped"),
+            WatchEvent::AnalysisFailed { .. } => "analysis failed".to_owned(),
+        };
+        events.into_iter().map(describe).collect()
+    }
+
+    #[test]
+    fn test_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        let first = dir.path().join("first");
+        std::fs::create_dir(&first).unwrap();
+        let root = ElfBuilder::new()
+            .runpath("$ORIGIN/first:$ORIGIN/lib")
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+        let libfoo = ElfBuilder::new().write(&lib, "libfoo.so.1");
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let mut watcher = Watcher::new(config);
+        watcher.watch(&root).unwrap();
+        assert_eq!(watcher.roots().collect::<Vec<_>>(), [root.as_path()]);
+        assert!(poll(&mut watcher).is_empty());
+
+        // An upgrade needing a library that is not installed
+        ElfBuilder::new()
+            .runpath("$ORIGIN")
+            .needed("libbar.so.1")
+            .write(&lib, "libfoo.so.1");
+        assert_eq!(
+            poll(&mut watcher),
+            ["libfoo.so.1 replaced", "libbar.so.1 missing, was None"]
+        );
+        assert!(poll(&mut watcher).is_empty());
+        let libbar = ElfBuilder::new().write(&lib, "libbar.so.1");
+        let found = format!("libbar.so.1 found at {}", libbar.display());
+        assert_eq!(poll(&mut watcher), [found]);
+
+        // A library of the same name in a directory searched earlier is loaded instead
+        let shadow = ElfBuilder::new().write(&first, "libfoo.so.1");
+        let moved = format!(
+            "libfoo.so.1 moved from {} to {}",
+            libfoo.display(),
+            shadow.display()
+        );
+        assert_eq!(
+            poll(&mut watcher),
+            [moved, "libbar.so.1 dropped".to_owned()]
+        );
+
+        std::fs::remove_file(&shadow).unwrap();
+        std::fs::remove_file(&libfoo).unwrap();
+        let missing = format!("libfoo.so.1 missing, was {:?}", Some(shadow));
+        assert_eq!(poll(&mut watcher), [missing]);
+
+        // A binary that can't be analyzed is reported once, then compared again once it can
+        std::fs::remove_file(&root).unwrap();
+        assert_eq!(poll(&mut watcher), ["analysis failed"]);
+        assert!(poll(&mut watcher).is_empty());
+        ElfBuilder::new()
+            .needed("libbar.so.1")
+            .runpath("$ORIGIN/lib")
+            .write(dir.path(), "app");
+        let found = format!("libbar.so.1 found at {}", libbar.display());
+        assert_eq!(
+            poll(&mut watcher),
+            [
+                "root replaced".to_owned(),
+                found,
+                "libfoo.so.1 dropped".to_owned()
+            ]
+        );
+
+        assert!(watcher.unwatch(&root));
+        assert!(!watcher.unwatch(PathBuf::from("/nonexistent")));
+        assert!(watcher.graph(&root).is_none());
+    }
+
+    #[test]
+    fn test_run_notified() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = ElfBuilder::new()
+            .runpath("$ORIGIN/lib")
+            .needed("libfoo.so.1")
+            .write(dir.path(), "app");
+        let config = ResolverConfig::new().use_env(false).without_default_dirs();
+        let mut watcher = Watcher::new(config);
+        watcher.watch(&root).unwrap();
+        assert!(watcher.notifier.is_some());
+
+        // The library is installed in a directory that doesn't exist yet, long before the
+        // interval elapses
+        let lib = dir.path().join("lib");
+        let install = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            ElfBuilder::new().write(&lib, "libfoo.so.1")
+        });
+        let mut events = Vec::new();
+        watcher.run(Duration::from_secs(3600), |event| {
+            events.push(event);
+            ControlFlow::Break(())
+        });
+        let libfoo = install.join().unwrap();
+        assert!(matches!(
+            &events[..],
+            [WatchEvent::DependencyFound { soname, path, .. }]
+                if soname == "libfoo.so.1" && *path == libfoo
+        ));
+    }
+}
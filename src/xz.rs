@@ -0,0 +1,84 @@
+//! Decoding of xz files, as kernel modules and packages are compressed with, with `xz2` when
+//! the `xz` feature is enabled.
+use std::io;
+
+#[cfg(feature = "xz")]
+use xz2::bufread::XzDecoder;
+
+#[cfg(feature = "xz")]
+use crate::archive_fs::{decompress_into, malformed};
+
+const MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0];
+
+/// Whether `data` starts like an xz file.
+pub(crate) fn is_xz(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Decompresses the xz file `data`, including every stream of one made by concatenating
+/// several, to up to `limit` bytes. Returns an [`io::ErrorKind::InvalidData`] error if it is
+/// malformed, truncated or fails its checks, or an [`io::ErrorKind::FileTooLarge`] error if it
+/// decompresses to more.
+#[cfg(feature = "xz")]
+pub(crate) fn decompress(data: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+    if !is_xz(data) {
+        return Err(malformed("xz"));
+    }
+    let mut output = Vec::new();
+    decompress_into(XzDecoder::new_multi_decoder(data), "xz", &mut output, limit)?;
+    Ok(output)
+}
+
+/// Without the `xz` feature, an [`io::ErrorKind::Unsupported`] error.
+#[cfg(not(feature = "xz"))]
+pub(crate) fn decompress(_data: &[u8], _limit: u64) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "decompressing xz streams needs the `xz` feature",
+    ))
+}
+
+#[cfg(all(test, feature = "xz"))]
+mod tests {
+    use super::decompress;
+
+    // `printf 'hello hello hello, xz\n' | xz --check=crc32 --lzma2=dict=1MiB`, as kbuild
+    // compresses modules
+    const XZ_CRC32: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x01, 0x69, 0x22, 0xde, 0x36, 0x02, 0x00, 0x21,
+        0x01, 0x10, 0x00, 0x00, 0x00, 0xa8, 0x70, 0x8e, 0x86, 0xe0, 0x00, 0x15, 0x00, 0x11, 0x5d,
+        0x00, 0x34, 0x19, 0x49, 0xee, 0x8d, 0xe9, 0x4f, 0x7e, 0x47, 0x79, 0x9d, 0x78, 0xa9, 0xbe,
+        0xb0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1c, 0xba, 0xd1, 0x0d, 0x00, 0x01, 0x29, 0x16,
+        0x91, 0x9f, 0x14, 0x90, 0x90, 0x42, 0x99, 0x0d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x59,
+        0x5a,
+    ];
+    // `ab` 50 times, `0123456789` and `ab` 20 times, with xz's default CRC-64 check
+    const XZ_CRC64: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x04, 0xe6, 0xd6, 0xb4, 0x46, 0x02, 0x00, 0x21,
+        0x01, 0x16, 0x00, 0x00, 0x00, 0x74, 0x2f, 0xe5, 0xa3, 0xe0, 0x00, 0x95, 0x00, 0x15, 0x5d,
+        0x00, 0x30, 0x98, 0xa9, 0x40, 0x11, 0x80, 0xc4, 0x69, 0x26, 0xb5, 0xed, 0x2d, 0x7c, 0x71,
+        0xfb, 0xae, 0x1c, 0x07, 0xa4, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0xd4, 0x2f, 0x6e, 0x8a,
+        0x95, 0x01, 0xc2, 0x5c, 0x00, 0x01, 0x31, 0x96, 0x01, 0x00, 0x00, 0x00, 0x00, 0xb5, 0x89,
+        0xea, 0xb1, 0xc4, 0x67, 0xfb, 0x02, 0x00, 0x00, 0x00, 0x00, 0x04, 0x59, 0x5a,
+    ];
+
+    #[test]
+    fn test_decompress() {
+        let hello = b"hello hello hello, xz\n";
+        assert_eq!(decompress(XZ_CRC32, u64::MAX).unwrap(), hello);
+        let repeated = ["ab".repeat(50), "0123456789".into(), "ab".repeat(20)].concat();
+        assert_eq!(decompress(XZ_CRC64, u64::MAX).unwrap(), repeated.as_bytes());
+
+        // Concatenated streams, with stream padding between them
+        let concatenated = [XZ_CRC32, &[0; 4], XZ_CRC64].concat();
+        let expected = [&hello[..], repeated.as_bytes()].concat();
+        assert_eq!(decompress(&concatenated, u64::MAX).unwrap(), expected);
+
+        // A corrupted check
+        let mut corrupted = XZ_CRC32.to_vec();
+        corrupted[52] ^= 1;
+        assert!(decompress(&corrupted, u64::MAX).is_err());
+        assert!(decompress(&XZ_CRC64[..60], u64::MAX).is_err());
+        assert!(decompress(b"not xz", u64::MAX).is_err());
+    }
+}
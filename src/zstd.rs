@@ -0,0 +1,118 @@
+//! Decoding of zstd frames (RFC 8878), as kernel modules and packages are compressed with,
+//! with `ruzstd` when the `zstd` feature is enabled.
+use std::io;
+
+#[cfg(feature = "zstd")]
+use ruzstd::decoding::errors::{FrameDecoderError, ReadFrameHeaderError};
+#[cfg(feature = "zstd")]
+use ruzstd::decoding::StreamingDecoder;
+
+#[cfg(feature = "zstd")]
+use crate::archive_fs::{decompress_into, malformed};
+
+const MAGIC: u32 = 0xfd2f_b528;
+/// The magic of skippable frames, whose lowest four bits may be anything.
+#[cfg(feature = "zstd")]
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+
+/// Whether `data` starts like a zstd frame.
+pub(crate) fn is_zstd(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC.to_le_bytes())
+}
+
+/// Decompresses the zstd frames `data` consists of, skipping skippable frames, to up to
+/// `limit` bytes. Returns an [`io::ErrorKind::InvalidData`] error if it is malformed,
+/// truncated, needs a dictionary or fails its checksums, or an
+/// [`io::ErrorKind::FileTooLarge`] error if it decompresses to more.
+#[cfg(feature = "zstd")]
+pub(crate) fn decompress(data: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut rest = data;
+    while let Some(magic) = rest.get(..4) {
+        let magic = u32::from_le_bytes(magic.try_into().unwrap());
+        if magic != MAGIC && magic & !0xf != SKIPPABLE_MAGIC {
+            break;
+        }
+        let mut frame = match StreamingDecoder::new(&mut rest) {
+            Ok(frame) => frame,
+            Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::SkipFrame {
+                length,
+                ..
+            })) => {
+                rest = rest
+                    .get(length as usize..)
+                    .ok_or_else(|| malformed("zstd"))?;
+                continue;
+            }
+            Err(_) => return Err(malformed("zstd")),
+        };
+        decompress_into(&mut frame, "zstd", &mut output, limit)?;
+        // ruzstd reads the checksum of a frame, but leaves checking it to its caller
+        let expected = frame.decoder.get_checksum_from_data();
+        if expected.is_some() && expected != frame.decoder.get_calculated_checksum() {
+            return Err(malformed("zstd"));
+        }
+    }
+    if rest.len() == data.len() {
+        return Err(malformed("zstd"));
+    }
+    Ok(output)
+}
+
+/// Without the `zstd` feature, an [`io::ErrorKind::Unsupported`] error.
+#[cfg(not(feature = "zstd"))]
+pub(crate) fn decompress(_data: &[u8], _limit: u64) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "decompressing zstd streams needs the `zstd` feature",
+    ))
+}
+
+#[cfg(all(test, feature = "zstd"))]
+mod tests {
+    use super::{decompress, is_zstd};
+
+    // `printf 'hello hello hello, zstd\n' | zstd`, a frame without its content size and its
+    // literals stored raw
+    const ZSTD_RAW: &[u8] = &[
+        0x28, 0xb5, 0x2f, 0xfd, 0x04, 0x58, 0x9d, 0x00, 0x00, 0x68, 0x68, 0x65, 0x6c, 0x6c, 0x6f,
+        0x20, 0x2c, 0x20, 0x7a, 0x73, 0x74, 0x64, 0x0a, 0x01, 0x00, 0x31, 0x4a, 0x11, 0x65, 0xec,
+        0xf1, 0x91,
+    ];
+    // `zstd -19` of two lines about a fox and a dog, twice, with their literals Huffman coded
+    const ZSTD_HUFFMAN: &[u8] = &[
+        0x28, 0xb5, 0x2f, 0xfd, 0x24, 0xac, 0x3d, 0x02, 0x00, 0xd2, 0x83, 0x0d, 0x11, 0x90, 0x3d,
+        0x06, 0x50, 0xfa, 0x43, 0xe9, 0x0f, 0xa5, 0xcf, 0xff, 0x5f, 0x77, 0x36, 0xb6, 0x98, 0x19,
+        0x15, 0x18, 0x33, 0x6b, 0x79, 0xf6, 0x34, 0x1a, 0xd3, 0xe8, 0x03, 0x82, 0xe5, 0x7a, 0x8b,
+        0x76, 0x93, 0xea, 0xa7, 0xb9, 0xf0, 0x34, 0x3a, 0xbb, 0x9e, 0xe1, 0xf9, 0xc3, 0x70, 0x8e,
+        0x33, 0xa2, 0xac, 0xbc, 0x49, 0x35, 0x04, 0x00, 0x92, 0xc5, 0x2c, 0x22, 0x64, 0xb0, 0xd4,
+        0xe0, 0x19, 0xc7, 0x2c, 0x03, 0x90, 0x1c, 0x16, 0x3b,
+    ];
+
+    #[test]
+    fn test_decompress() {
+        let hello = b"hello hello hello, zstd\n";
+        assert!(is_zstd(ZSTD_RAW));
+        assert_eq!(decompress(ZSTD_RAW, u64::MAX).unwrap(), hello);
+        let lines = "the quick brown fox jumps over the lazy dog; \
+            the lazy dog sleeps under the brown tree\n"
+            .repeat(2);
+        assert_eq!(
+            decompress(ZSTD_HUFFMAN, u64::MAX).unwrap(),
+            lines.as_bytes()
+        );
+
+        // Concatenated frames, with a skippable frame between them
+        let skippable = [0x5e, 0x2a, 0x4d, 0x18, 0x02, 0x00, 0x00, 0x00, 0xff, 0xff];
+        let concatenated = [ZSTD_RAW, &skippable, ZSTD_HUFFMAN].concat();
+        let expected = [&hello[..], lines.as_bytes()].concat();
+        assert_eq!(decompress(&concatenated, u64::MAX).unwrap(), expected);
+
+        // A corrupted checksum
+        let mut corrupted = ZSTD_HUFFMAN.to_vec();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(decompress(&corrupted, u64::MAX).is_err());
+        assert!(decompress(&ZSTD_HUFFMAN[..60], u64::MAX).is_err());
+        assert!(decompress(b"not zstd", u64::MAX).is_err());
+    }
+}